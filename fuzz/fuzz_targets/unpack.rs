@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use solana_vote::test_utils::process_arbitrary_instruction;
+
+// Any input may be rejected, but decoding and processing it must never panic
+fuzz_target!(|data: &[u8]| {
+    let _ = process_arbitrary_instruction(data);
+});
@@ -0,0 +1,121 @@
+// Command line client for administering votes of a deployed registry
+use std::process::exit;
+
+use solana_cli_config::{Config, CONFIG_FILE};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{read_keypair_file, Keypair, Signer};
+use solana_sdk::transaction::Transaction;
+
+use solana_vote::cli::{parse_args, Command, USAGE};
+use solana_vote::{instruction, DepositStatus, VoteConfig, Voting};
+
+struct Client {
+    rpc: RpcClient,
+    payer: Keypair,
+    program_id: Pubkey,
+    registry: Pubkey,
+}
+
+impl Client {
+    fn send(&self, instruction: Instruction) -> Result<(), String> {
+        let blockhash = self.rpc.get_latest_blockhash().map_err(|error| error.to_string())?;
+        let transaction = Transaction::new_signed_with_payer(&[instruction], Some(&self.payer.pubkey()), &[&self.payer], blockhash);
+        let signature = self.rpc.send_and_confirm_transaction(&transaction).map_err(|error| error.to_string())?;
+        println!("Signature: {}", signature);
+        Ok(())
+    }
+
+    fn registry(&self) -> Result<Voting, String> {
+        let data = self.rpc.get_account_data(&self.registry).map_err(|error| error.to_string())?;
+        Voting::unpack(&data).map_err(|error| format!("cannot decode the registry: {}", error))
+    }
+}
+
+fn run() -> Result<(), String> {
+    let args = parse_args(std::env::args().skip(1)).map_err(|error| format!("{}\n\n{}", error, USAGE))?;
+
+    // Settings missing from the command line come from the Solana CLI config, like the `solana` tool does
+    let config_file = args.global.config_file.clone().or_else(|| CONFIG_FILE.clone());
+    let config = config_file.and_then(|path| Config::load(&path).ok()).unwrap_or_default();
+
+    let url = args.global.url.clone().unwrap_or(config.json_rpc_url);
+    let keypair_path = args.global.keypair.clone().unwrap_or(config.keypair_path);
+    let payer = read_keypair_file(&keypair_path).map_err(|error| format!("cannot read keypair {}: {}", keypair_path, error))?;
+
+    let client = Client {
+        rpc: RpcClient::new_with_commitment(url, CommitmentConfig::confirmed()),
+        payer,
+        program_id: args.global.program_id.ok_or("missing --program-id")?,
+        registry: args.global.registry.ok_or("missing --registry")?,
+    };
+    let (program_id, registry, signer) = (&client.program_id, &client.registry, client.payer.pubkey());
+
+    match args.command {
+        Command::Create { title, options } => {
+            let config = VoteConfig::from_labels(title, options).map_err(|error| error.to_string())?;
+            let voting = client.registry()?;
+            let policy = voting.get_creation_policy();
+            let treasury = policy.fee.map(|fee| fee.treasury);
+            if policy.deposit.is_some() {
+                client.send(instruction::create_vote_with_deposit(program_id, registry, &signer, config, treasury.as_ref(), voting.next_vote_id()))?;
+            } else {
+                client.send(instruction::create_vote(program_id, registry, &signer, config, treasury.as_ref()))?;
+            }
+
+            // The new vote is the latest one of the signer
+            let voting = client.registry()?;
+            let vote_id = voting
+                .vote_ids()
+                .into_iter()
+                .rev()
+                .find(|vote_id| voting.get_vote_summary(*vote_id).is_ok_and(|summary| summary.creator == signer));
+            if let Some(vote_id) = vote_id {
+                println!("Vote ID: {}", vote_id);
+            }
+        }
+        Command::Allow { vote_id, voter } => client.send(instruction::add_allowed_voter(program_id, registry, &signer, vote_id, &voter))?,
+        Command::Vote { vote_id, option_index, question_index } => {
+            client.send(instruction::cast_vote(program_id, registry, &signer, &signer, vote_id, question_index, option_index))?
+        }
+        Command::Delegate { vote_id, delegate } => client.send(instruction::delegate_vote(program_id, registry, &signer, vote_id, &delegate, None))?,
+        Command::Close { vote_id } => {
+            let mut close = instruction::close_vote(program_id, registry, &signer, vote_id);
+
+            // A held deposit goes back to the creator, or to the treasury when the admin closes the vote
+            let summary = client.registry()?.get_vote_summary(vote_id).map_err(|error| format!("cannot read the vote: {}", error))?;
+            if let Some(deposit) = summary.deposit.filter(|deposit| deposit.status == DepositStatus::Held) {
+                let recipient = if summary.creator == signer { signer } else { deposit.treasury };
+                close.accounts.extend(instruction::deposit_accounts(program_id, registry, vote_id, &recipient));
+            }
+            client.send(close)?
+        }
+        Command::Results { vote_id } => {
+            let voting = client.registry()?;
+
+            // Results are read locally, as the keypair owner, so hidden results stay hidden. The slot of
+            // the cluster stands in for the Clock sysvar when weighing conviction votes
+            let slot = client.rpc.get_slot().map_err(|error| error.to_string())?;
+            let results = voting.get_full_results_at(&signer, vote_id, slot).map_err(|error| format!("cannot read the results: {}", error))?;
+            print!("{}", results);
+        }
+        Command::List => {
+            let voting = client.registry()?;
+            for vote_id in voting.vote_ids() {
+                let summary = voting.get_vote_summary(vote_id).map_err(|error| error.to_string())?;
+                println!("{:>5}  {:<10}  {}", summary.id, format!("{:?}", summary.status), summary.title);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn main() {
+    if let Err(error) = run() {
+        eprintln!("{}", error);
+        exit(1);
+    }
+}
@@ -0,0 +1,184 @@
+// Strings of the vote state with a limit on their length in bytes. The limit holds for values built with
+// `new` or `try_from` and for decoded ones, so a crafted account or instruction cannot carry oversized text
+use std::borrow::Borrow;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::ops::Deref;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::msg;
+use solana_program::program_error::ProgramError;
+
+// A string that exceeded its limit, `field` names what it was meant for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StringTooLong {
+    pub field: &'static str,
+    pub len: usize,
+    pub max: usize,
+}
+
+impl fmt::Display for StringTooLong {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} is {} bytes long, the limit is {}", self.field, self.len, self.max)
+    }
+}
+
+impl std::error::Error for StringTooLong {}
+
+impl From<StringTooLong> for ProgramError {
+    fn from(error: StringTooLong) -> Self {
+        msg!("{}", error);
+        ProgramError::InvalidArgument
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BoundedString<const N: usize>(String);
+
+impl<const N: usize> BoundedString<N> {
+    pub const MAX_LEN: usize = N;
+
+    // `field` only shows up in the error
+    pub fn new(field: &'static str, value: impl Into<String>) -> Result<Self, StringTooLong> {
+        let value = value.into();
+        if value.len() > N {
+            return Err(StringTooLong { field, len: value.len(), max: N });
+        }
+        Ok(Self(value))
+    }
+
+    // Value of the greatest length, for measuring the space a field can grow into
+    pub(crate) fn longest() -> Self {
+        Self("x".repeat(N))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+impl<const N: usize> TryFrom<String> for BoundedString<N> {
+    type Error = StringTooLong;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::new("string", value)
+    }
+}
+
+impl<const N: usize> TryFrom<&str> for BoundedString<N> {
+    type Error = StringTooLong;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Self::new("string", value)
+    }
+}
+
+impl<const N: usize> From<BoundedString<N>> for String {
+    fn from(value: BoundedString<N>) -> Self {
+        value.0
+    }
+}
+
+impl<const N: usize> Deref for BoundedString<N> {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl<const N: usize> AsRef<str> for BoundedString<N> {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl<const N: usize> Borrow<str> for BoundedString<N> {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl<const N: usize> PartialEq<str> for BoundedString<N> {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl<const N: usize> PartialEq<String> for BoundedString<N> {
+    fn eq(&self, other: &String) -> bool {
+        self.0 == *other
+    }
+}
+
+impl<const N: usize> PartialEq<&str> for BoundedString<N> {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}
+
+impl<const N: usize> fmt::Display for BoundedString<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+// Same encoding as a `String`, a length prefix followed by the UTF-8 bytes
+impl<const N: usize> BorshSerialize for BoundedString<N> {
+    fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.0.serialize(writer)
+    }
+}
+
+impl<const N: usize> BorshDeserialize for BoundedString<N> {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> io::Result<Self> {
+        // Checked before reading the bytes, so an oversized prefix never allocates
+        let len = u32::deserialize_reader(reader)? as usize;
+        if len > N {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, StringTooLong { field: "encoded string", len, max: N }));
+        }
+
+        let mut bytes = vec![0; len];
+        reader.read_exact(&mut bytes)?;
+        String::from_utf8(bytes).map(Self).map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "string is not valid UTF-8"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bounded_string_limit() {
+        assert_eq!(BoundedString::<4>::try_from("four").unwrap(), "four");
+        assert_eq!(BoundedString::<4>::try_from("fives".to_string()), Err(StringTooLong { field: "string", len: 5, max: 4 }));
+
+        // The limit counts bytes, not characters
+        let error = BoundedString::<4>::new("label", "Süßé").unwrap_err();
+        assert_eq!(error, StringTooLong { field: "label", len: 7, max: 4 });
+        assert_eq!(error.to_string(), "label is 7 bytes long, the limit is 4");
+    }
+
+    #[test]
+    fn test_decode_over_long_string() {
+        let value = BoundedString::<4>::try_from("four").unwrap();
+        let encoded = borsh::to_vec(&value).unwrap();
+        assert_eq!(encoded, borsh::to_vec(&"four".to_string()).unwrap());
+        assert_eq!(BoundedString::<4>::try_from_slice(&encoded).unwrap(), value);
+
+        // Plain strings over the limit fail to decode
+        let error = BoundedString::<4>::try_from_slice(&borsh::to_vec(&"fives".to_string()).unwrap()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "encoded string is 5 bytes long, the limit is 4");
+
+        // A huge length prefix is rejected before the bytes are read
+        let error = BoundedString::<4>::try_from_slice(&u32::MAX.to_le_bytes()).unwrap_err();
+        assert_eq!(error.to_string(), format!("encoded string is {} bytes long, the limit is 4", u32::MAX));
+
+        assert!(BoundedString::<4>::try_from_slice(&[2, 0, 0, 0, 0xff, 0xfe]).is_err());
+    }
+}
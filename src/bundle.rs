@@ -0,0 +1,73 @@
+// Portable copy of a closed vote, for recreating it in another registry, e.g. when moving from devnet to mainnet.
+// A bundle carries the full Borsh encoded state of the vote, the hash of its results and a hash over both, so an
+// edited or truncated bundle is refused before anything of it is imported
+use base64::prelude::*;
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::hash::{hashv, Hash};
+use solana_program::program_error::ProgramError;
+
+// Layout of the bundles written by this version, older ones are refused once it changes
+pub const BUNDLE_VERSION: u8 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct VoteBundle {
+    pub version: u8,
+    pub vote_state: Vec<u8>, // Borsh encoded vote, as stored in the source registry
+    pub results_hash: Hash,  // `VoteResults::hash` of the vote in the source registry
+    pub hash: Hash,          // Over the version, the state and the results hash
+}
+
+impl VoteBundle {
+    pub(crate) fn new(vote_state: Vec<u8>, results_hash: Hash) -> Self {
+        let hash = content_hash(BUNDLE_VERSION, &vote_state, &results_hash);
+        Self { version: BUNDLE_VERSION, vote_state, results_hash, hash }
+    }
+
+    // Return error if the bundle has another layout or was changed after the export
+    pub fn verify(&self) -> Result<(), ProgramError> {
+        if self.version != BUNDLE_VERSION {
+            return Err(ProgramError::InvalidArgument); // Return error for bundles of another version
+        }
+        if content_hash(self.version, &self.vote_state, &self.results_hash) != self.hash {
+            return Err(ProgramError::InvalidAccountData); // Return error if the contents do not match the hash
+        }
+        Ok(())
+    }
+
+    // The blob handed between clusters, Borsh encoded and then base64
+    pub fn to_base64(&self) -> String {
+        BASE64_STANDARD.encode(borsh::to_vec(self).expect("bundles serialize into memory"))
+    }
+
+    pub fn from_base64(blob: &str) -> Result<Self, ProgramError> {
+        let bytes = BASE64_STANDARD.decode(blob.trim()).map_err(|_| ProgramError::InvalidArgument)?;
+        Self::try_from_slice(&bytes).map_err(|_| ProgramError::InvalidAccountData)
+    }
+}
+
+fn content_hash(version: u8, vote_state: &[u8], results_hash: &Hash) -> Hash {
+    hashv(&[&[version], vote_state, results_hash.as_ref()])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundle_roundtrip() {
+        let bundle = VoteBundle::new(vec![1, 2, 3], Hash::new_unique());
+        let decoded = VoteBundle::from_base64(&bundle.to_base64()).unwrap();
+        assert_eq!(decoded, bundle);
+        assert_eq!(decoded.verify(), Ok(()));
+
+        // Changing any part of the contents breaks the hash
+        let mut tampered = bundle.clone();
+        tampered.vote_state[0] = 9;
+        assert_eq!(tampered.verify(), Err(ProgramError::InvalidAccountData));
+        let mut tampered = bundle.clone();
+        tampered.results_hash = Hash::new_unique();
+        assert_eq!(tampered.verify(), Err(ProgramError::InvalidAccountData));
+        assert_eq!(VoteBundle { version: BUNDLE_VERSION + 1, ..bundle }.verify(), Err(ProgramError::InvalidArgument));
+        assert_eq!(VoteBundle::from_base64("not base64!"), Err(ProgramError::InvalidArgument));
+    }
+}
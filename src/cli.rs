@@ -0,0 +1,158 @@
+use std::str::FromStr;
+
+use solana_program::pubkey::Pubkey;
+
+use crate::{OptionIndex, VoteId};
+
+// Connection settings shared by every subcommand. Unset values come from the Solana CLI config file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GlobalArgs {
+    pub config_file: Option<String>, // --config, defaults to the standard Solana CLI config
+    pub url: Option<String>,         // --url, overrides the RPC URL of the config
+    pub keypair: Option<String>,     // --keypair, overrides the keypair path of the config
+    pub program_id: Option<Pubkey>,  // --program-id
+    pub registry: Option<Pubkey>,    // --registry
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Create { title: String, options: Vec<String> },
+    Allow { vote_id: VoteId, voter: Pubkey },
+    Vote { vote_id: VoteId, option_index: OptionIndex, question_index: u32 },
+    Delegate { vote_id: VoteId, delegate: Pubkey },
+    Close { vote_id: VoteId },
+    Results { vote_id: VoteId },
+    List,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Args {
+    pub global: GlobalArgs,
+    pub command: Command,
+}
+
+pub const USAGE: &str = "usage: solana-vote-cli [--config PATH] [--url URL] [--keypair PATH] --program-id ID --registry ADDRESS <command>
+
+commands:
+    create <title> <option>...
+    allow <vote id> <voter>
+    vote <vote id> <option index> [--question <index>]
+    delegate <vote id> <delegate>
+    close <vote id>
+    results <vote id>
+    list";
+
+fn parse_value<T: FromStr>(value: Option<String>, name: &str) -> Result<T, String> {
+    let value = value.ok_or(format!("missing {}", name))?;
+    value.parse().map_err(|_| format!("invalid {}: {}", name, value))
+}
+
+// Parse the arguments following the program name
+pub fn parse_args<I: IntoIterator<Item = String>>(args: I) -> Result<Args, String> {
+    let mut global = GlobalArgs::default();
+    let mut question_index = None;
+    let mut positional = Vec::new();
+
+    // Flags may appear anywhere, everything else is positional
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--config" | "-C" => global.config_file = Some(parse_value(args.next(), "config path")?),
+            "--url" | "-u" => global.url = Some(parse_value(args.next(), "RPC URL")?),
+            "--keypair" | "-k" => global.keypair = Some(parse_value(args.next(), "keypair path")?),
+            "--program-id" => global.program_id = Some(parse_value(args.next(), "program ID")?),
+            "--registry" => global.registry = Some(parse_value(args.next(), "registry address")?),
+            "--question" => question_index = Some(parse_value(args.next(), "question index")?),
+            flag if flag.starts_with("--") => return Err(format!("unknown flag: {}", flag)),
+            _ => positional.push(arg),
+        }
+    }
+
+    let mut positional = positional.into_iter();
+    let name = positional.next().ok_or("missing command")?;
+
+    if question_index.is_some() && name != "vote" {
+        return Err("--question only applies to the vote command".to_string());
+    }
+
+    let command = match name.as_str() {
+        "create" => {
+            let title = positional.next().ok_or("missing title")?;
+            let options: Vec<String> = positional.by_ref().collect();
+            if options.is_empty() {
+                return Err("a vote needs at least one option".to_string());
+            }
+            Command::Create { title, options }
+        }
+        "allow" => Command::Allow {
+            vote_id: parse_value(positional.next(), "vote ID")?,
+            voter: parse_value(positional.next(), "voter")?,
+        },
+        "vote" => Command::Vote {
+            vote_id: parse_value(positional.next(), "vote ID")?,
+            option_index: parse_value(positional.next(), "option index")?,
+            question_index: question_index.unwrap_or(0),
+        },
+        "delegate" => Command::Delegate {
+            vote_id: parse_value(positional.next(), "vote ID")?,
+            delegate: parse_value(positional.next(), "delegate")?,
+        },
+        "close" => Command::Close { vote_id: parse_value(positional.next(), "vote ID")? },
+        "results" => Command::Results { vote_id: parse_value(positional.next(), "vote ID")? },
+        "list" => Command::List,
+        _ => return Err(format!("unknown command: {}", name)),
+    };
+
+    if let Some(extra) = positional.next() {
+        return Err(format!("unexpected argument: {}", extra));
+    }
+
+    Ok(Args { global, command })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Result<Args, String> {
+        parse_args(line.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let program_id = Pubkey::new_unique();
+        let voter = Pubkey::new_unique();
+
+        let parsed = args(&format!("--url http://localhost:8899 --program-id {} allow 3 {}", program_id, voter)).unwrap();
+        assert_eq!(parsed.global.url.as_deref(), Some("http://localhost:8899"));
+        assert_eq!(parsed.global.program_id, Some(program_id));
+        assert_eq!(parsed.global.keypair, None);
+        assert_eq!(parsed.command, Command::Allow { vote_id: VoteId(3), voter });
+
+        assert_eq!(
+            args("create Budget Yes No --keypair id.json").unwrap(),
+            Args {
+                global: GlobalArgs { keypair: Some("id.json".to_string()), ..GlobalArgs::default() },
+                command: Command::Create { title: "Budget".to_string(), options: vec!["Yes".to_string(), "No".to_string()] },
+            }
+        );
+        assert_eq!(args("vote 1 2").unwrap().command, Command::Vote { vote_id: VoteId(1), option_index: OptionIndex(2), question_index: 0 });
+        assert_eq!(args("vote 1 2 --question 1").unwrap().command, Command::Vote { vote_id: VoteId(1), option_index: OptionIndex(2), question_index: 1 });
+        assert_eq!(args("results 7").unwrap().command, Command::Results { vote_id: VoteId(7) });
+        assert_eq!(args("list").unwrap().command, Command::List);
+    }
+
+    #[test]
+    fn test_parse_args_invalid() {
+        assert_eq!(args(""), Err("missing command".to_string()));
+        assert_eq!(args("tally 1"), Err("unknown command: tally".to_string()));
+        assert_eq!(args("create Budget"), Err("a vote needs at least one option".to_string()));
+        assert_eq!(args("close"), Err("missing vote ID".to_string()));
+        assert_eq!(args("close one"), Err("invalid vote ID: one".to_string()));
+        assert_eq!(args("allow 1 not-a-key"), Err("invalid voter: not-a-key".to_string()));
+        assert_eq!(args("close 1 2"), Err("unexpected argument: 2".to_string()));
+        assert_eq!(args("close 1 --question 1"), Err("--question only applies to the vote command".to_string()));
+        assert_eq!(args("list --verbose"), Err("unknown flag: --verbose".to_string()));
+        assert_eq!(args("list --url"), Err("missing RPC URL".to_string()));
+    }
+}
@@ -0,0 +1,167 @@
+// Decoders for the raw data of accounts fetched from a validator, so off-chain services need no copy of the state
+// definitions. Only the bytes are checked, whoever fetched the account checks that the program owns it
+use std::fmt;
+
+use borsh::BorshDeserialize;
+use solana_program::program_error::ProgramError;
+use solana_program::pubkey::Pubkey;
+
+use crate::{CreationPolicy, Vote, VoteId, VoteSummary, VoteView, Voting, VIEW_DISCRIMINATOR, VIEW_VERSION};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    NotAVoteAccount,         // The data does not start with the discriminator of the account kind
+    UnsupportedVersion(u16), // Layout version these decoders cannot read
+    Corrupt,                 // The account is of the kind asked for but does not decode, or contradicts itself
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::NotAVoteAccount => write!(f, "not an account of the voting program"),
+            DecodeError::UnsupportedVersion(version) => write!(f, "layout version {} is not supported, this version reads {}", version, VIEW_VERSION),
+            DecodeError::Corrupt => write!(f, "the account data is corrupt"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+// What a registry account holds, the votes summarized in ID order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegistryView {
+    pub admin: Pubkey,
+    pub paused: bool,
+    pub creation_policy: CreationPolicy,
+    pub next_vote_id: VoteId,
+    pub shard_capacity: Option<u32>, // Set once the registry is sharded
+    pub outbox: Option<Pubkey>,
+    pub votes: Vec<VoteSummary>,
+}
+
+// Decode a registry account. The registry carries no version, a new layout comes with a new `REGISTRY_DISCRIMINATOR`
+// and accounts still in the legacy layout decode as well. Every vote has to sit under its own ID, below the next ID,
+// and pass the checks the program runs before using it
+pub fn decode_registry(data: &[u8]) -> Result<RegistryView, DecodeError> {
+    let voting = Voting::unpack(data).map_err(|error| match error {
+        ProgramError::UninitializedAccount => DecodeError::NotAVoteAccount,
+        _ => DecodeError::Corrupt,
+    })?; // The free space of the account follows the state
+
+    let mut vote_ids: Vec<VoteId> = voting.votes.keys().copied().collect();
+    vote_ids.sort();
+    for vote_id in &vote_ids {
+        voting.check_vote(*vote_id).map_err(|_| DecodeError::Corrupt)?;
+    }
+
+    Ok(RegistryView {
+        admin: voting.admin,
+        paused: voting.paused,
+        creation_policy: voting.creation_policy,
+        next_vote_id: voting.current_id,
+        shard_capacity: voting.shard_capacity,
+        outbox: voting.outbox,
+        votes: vote_ids.iter().map(|vote_id| voting.votes[vote_id].summary()).collect(),
+    })
+}
+
+// Decode a vote state account into its view. Besides the view, the Borsh encoded vote behind it has to decode,
+// pass the checks of `Vote::load` and be the vote the view describes
+pub fn decode_vote(data: &[u8]) -> Result<VoteView<'_>, DecodeError> {
+    if !data.starts_with(&VIEW_DISCRIMINATOR) {
+        return Err(DecodeError::NotAVoteAccount);
+    }
+    let version = data.get(8..10).map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]])).ok_or(DecodeError::Corrupt)?;
+    if version != VIEW_VERSION {
+        return Err(DecodeError::UnsupportedVersion(version));
+    }
+
+    let view = VoteView::try_from_bytes(data).map_err(|_| DecodeError::Corrupt)?;
+    let mut state = &data[view.state_offset() as usize..];
+    let vote = Vote::deserialize(&mut state).map_err(|_| DecodeError::Corrupt)?;
+    vote.check_invariants().map_err(|_| DecodeError::Corrupt)?;
+
+    let options = vote.questions.iter().map(|question| question.options.len()).sum::<usize>();
+    if view.id() != vote.id || view.creator() != vote.creator || view.question_count() as usize != vote.questions.len() || view.option_count() as usize != options {
+        return Err(DecodeError::Corrupt); // Return error if the view was written for another state
+    }
+
+    Ok(view)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout_snapshots::{fixture, CANONICAL_VOTE_TYPES};
+    use crate::{ViewStatus, ViewVoteType, VoteStatus, VIEW_HEADER_LEN};
+
+    #[test]
+    fn test_decode_registry_fixture() {
+        let data = fixture("registry");
+        let registry = decode_registry(&data).unwrap();
+        assert_eq!(registry.admin, Pubkey::new_from_array([1; 32]));
+        assert!(!registry.paused);
+        assert_eq!(registry.next_vote_id, VoteId(1));
+        assert_eq!((registry.shard_capacity, registry.outbox), (None, None));
+        assert_eq!(registry.votes.len(), 1);
+        assert_eq!((registry.votes[0].id, registry.votes[0].title.as_str(), registry.votes[0].status.clone()), (VoteId(0), "Budget", VoteStatus::Open));
+
+        // Accounts are larger than their state
+        let mut padded = data.clone();
+        padded.resize(data.len() + 1_024, 0);
+        assert_eq!(decode_registry(&padded), Ok(registry));
+
+        assert_eq!(decode_registry(&fixture("vote_single_choice_account")), Err(DecodeError::NotAVoteAccount));
+        assert_eq!(decode_registry(&[]), Err(DecodeError::NotAVoteAccount));
+        assert_eq!(decode_registry(&data[..data.len() - 1]), Err(DecodeError::Corrupt));
+
+        // Accounts not written since the votes moved into slots
+        assert_eq!(decode_registry(&fixture("registry_v1")), decode_registry(&data));
+    }
+
+    #[test]
+    fn test_decode_vote_fixtures() {
+        let view_types = [
+            ViewVoteType::SingleChoice,
+            ViewVoteType::TimeWeighted,
+            ViewVoteType::TokenWeighted,
+            ViewVoteType::PointAllocation,
+            ViewVoteType::Conviction,
+            ViewVoteType::TokenBurn,
+        ];
+        for ((name, _), view_type) in CANONICAL_VOTE_TYPES.iter().zip(view_types) {
+            let data = fixture(&format!("{}_account", name));
+            let view = decode_vote(&data).unwrap_or_else(|error| panic!("{}: {}", name, error));
+            assert_eq!((view.id(), view.creator(), view.status(), view.vote_type()), (VoteId(0), Pubkey::new_from_array([2; 32]), ViewStatus::Open, view_type), "{}", name);
+            assert_eq!(view.options().map(|option| option.label).collect::<Vec<_>>(), vec!["Yes", "No"], "{}", name);
+
+            let mut padded = data.clone();
+            padded.resize(data.len() + 1_024, 0);
+            assert!(decode_vote(&padded).is_ok(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_decode_vote_rejects() {
+        let data = fixture("vote_single_choice_account");
+        let patched = |offset: usize, bytes: &[u8]| {
+            let mut data = data.clone();
+            data[offset..offset + bytes.len()].copy_from_slice(bytes);
+            data
+        };
+
+        // Other accounts of the program and foreign accounts are told apart from damaged votes
+        assert_eq!(decode_vote(&fixture("registry")).err(), Some(DecodeError::NotAVoteAccount));
+        assert_eq!(decode_vote(&patched(0, b"svvotevx")).err(), Some(DecodeError::NotAVoteAccount));
+        assert_eq!(decode_vote(&[0; VIEW_HEADER_LEN]).err(), Some(DecodeError::NotAVoteAccount));
+        assert_eq!(decode_vote(&patched(8, &2u16.to_le_bytes())).err(), Some(DecodeError::UnsupportedVersion(2)));
+        assert_eq!(DecodeError::UnsupportedVersion(2).to_string(), "layout version 2 is not supported, this version reads 1");
+
+        // A truncated view or state, and a view contradicting the state behind it
+        assert_eq!(decode_vote(&data[..9]).err(), Some(DecodeError::Corrupt));
+        assert_eq!(decode_vote(&data[..VIEW_HEADER_LEN]).err(), Some(DecodeError::Corrupt));
+        assert_eq!(decode_vote(&data[..data.len() - 1]).err(), Some(DecodeError::Corrupt));
+        assert_eq!(decode_vote(&patched(12, &7u32.to_le_bytes())).err(), Some(DecodeError::Corrupt));
+        assert_eq!(decode_vote(&patched(16, &[9; 32])).err(), Some(DecodeError::Corrupt));
+    }
+}
@@ -0,0 +1,114 @@
+// Checks a voter has to pass to join an open vote by themselves. Every check reads the account the
+// voter presents next to their signature, which has to be the voter's own account
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::account_info::AccountInfo;
+use solana_program::msg;
+use solana_program::program_error::ProgramError;
+use solana_program::pubkey::Pubkey;
+use solana_program::system_program;
+
+use crate::VoteError;
+
+// A single rule, `Err` carries the reason the account failed it
+pub trait EligibilityCheck {
+    fn check(&self, account: &AccountInfo) -> Result<(), &'static str>;
+}
+
+// The system account of the voter holds at least `min_lamports`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct MinStake {
+    pub min_lamports: u64,
+}
+
+impl EligibilityCheck for MinStake {
+    fn check(&self, account: &AccountInfo) -> Result<(), &'static str> {
+        if *account.owner != system_program::id() {
+            return Err("the account is not a system account");
+        }
+        if account.lamports() < self.min_lamports {
+            return Err("the account holds too few lamports");
+        }
+        Ok(())
+    }
+}
+
+// The account of the voter holds at least `min_len` bytes of data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct MinAccountDataLen {
+    pub min_len: u64,
+}
+
+impl EligibilityCheck for MinAccountDataLen {
+    fn check(&self, account: &AccountInfo) -> Result<(), &'static str> {
+        if (account.data_len() as u64) < self.min_len {
+            return Err("the account holds too little data");
+        }
+        Ok(())
+    }
+}
+
+// Rule of an open vote, voters passing it may join with `register_voter`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub enum Eligibility {
+    MinStake(MinStake),
+    MinAccountDataLen(MinAccountDataLen),
+}
+
+impl EligibilityCheck for Eligibility {
+    fn check(&self, account: &AccountInfo) -> Result<(), &'static str> {
+        match self {
+            Eligibility::MinStake(check) => check.check(account),
+            Eligibility::MinAccountDataLen(check) => check.check(account),
+        }
+    }
+}
+
+// Run the check against the account the voter presented, logging the reason of a failure
+pub fn ensure_eligible(check: &impl EligibilityCheck, voter: &Pubkey, account: &AccountInfo) -> Result<(), ProgramError> {
+    let result = if account.key != voter { Err("the account does not belong to the voter") } else { check.check(account) };
+
+    result.map_err(|reason| {
+        msg!("Eligibility not met for {}: {}", voter, reason);
+        VoteError::EligibilityNotMet.into()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{take_logs, TestHarness};
+
+    #[test]
+    fn test_min_stake_boundary() {
+        let mut harness = TestHarness::new();
+        let voter = Pubkey::new_unique();
+        harness.set_owner(&voter, &system_program::id());
+        let check = Eligibility::MinStake(MinStake { min_lamports: 1_000 });
+
+        harness.set_lamports(&voter, 999);
+        take_logs();
+        assert_eq!(ensure_eligible(&check, &voter, &harness.account_for(&voter)), Err(VoteError::EligibilityNotMet.into()));
+        assert_eq!(take_logs(), vec![format!("Eligibility not met for {}: the account holds too few lamports", voter)]);
+
+        harness.set_lamports(&voter, 1_000);
+        assert!(ensure_eligible(&check, &voter, &harness.account_for(&voter)).is_ok());
+
+        // Lamports of someone else or of a program account do not count
+        let other = Pubkey::new_unique();
+        assert_eq!(ensure_eligible(&check, &other, &harness.account_for(&voter)), Err(VoteError::EligibilityNotMet.into()));
+        harness.set_owner(&voter, &Pubkey::new_unique());
+        assert_eq!(ensure_eligible(&check, &voter, &harness.account_for(&voter)), Err(VoteError::EligibilityNotMet.into()));
+    }
+
+    #[test]
+    fn test_min_account_data_len() {
+        let mut harness = TestHarness::new();
+        let voter = Pubkey::new_unique();
+        let check = MinAccountDataLen { min_len: 8 };
+
+        harness.set_data(&voter, vec![0; 7]);
+        assert_eq!(ensure_eligible(&check, &voter, &harness.account_for(&voter)), Err(VoteError::EligibilityNotMet.into()));
+        harness.set_data(&voter, vec![0; 8]);
+        assert!(ensure_eligible(&check, &voter, &harness.account_for(&voter)).is_ok());
+    }
+}
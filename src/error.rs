@@ -0,0 +1,214 @@
+use std::fmt;
+
+use solana_program::msg;
+use solana_program::program_error::ProgramError;
+
+// Errors of the voting program, reported to clients as custom program errors. The codes are part of the
+// interface: new variants take the next free code and existing ones are never renumbered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum VoteError {
+    VoteCancelled = 0, // The vote was declared void by its creator
+    VoteStillOpen = 1, // The operation needs a closed vote
+    AllowlistFull = 2, // The state account has no room for another allowed voter
+    CreationCooldown = 3, // The creator has to wait before creating another vote
+    ProgramPaused = 4, // The registry admin has frozen all mutations
+    MinimumDurationNotElapsed = 5, // The vote cannot be closed before its minimum duration, cancelling is exempt
+    OptionCapReached = 6, // The option already received the maximum number of ballots
+    VoterNotAllowed = 7, // The caller is not on the allowlist of the vote
+    VoterBanned = 8, // The creator struck the ballots of the voter
+    DelegationExpired = 9, // The remaining votes of the caller came from delegations that expired
+    VoterWindowClosed = 10, // The personal voting window of the caller has passed
+    DeadlinePassed = 11, // The vote no longer accepts ballots, it can only be finalized
+    TooSoon = 12, // The voter cast a ballot too few slots ago
+    ApprovalRequired = 13, // The vote has several creators, the action needs their approvals
+    AlreadyApproved = 14, // The caller already approved the pending admin action
+    PendingActionExpired = 15, // The pending admin action can no longer be approved, it has to be proposed again
+    CorruptState = 16, // The stored state of the vote is inconsistent, only the registry admin can repair it
+    PointBudgetMismatch = 17, // The allocated points do not add up to the budget of the voter
+    OptionFull = 18, // Every seat of the option is taken by other voters
+    EligibilityNotMet = 19, // The account of the caller failed the eligibility check of the open vote
+    DelegateNotAllowed = 20, // The delegate is not on the allowlist and the vote only delegates to allowed voters
+    MissingVoterAccount = 21, // The account list ends before the position of the voter, delegator or delegate
+    MissingCreatorAccount = 22, // The account list ends before the position of the creator or registry admin
+    BallotsAlreadyCast = 23, // The change is only possible before the first ballot of the vote
+    BatchTooLarge = 24, // The call would add more voters than `MAX_BATCH_VOTERS`, cast more ballots than `MAX_BATCH_BALLOTS` or read more votes than `MAX_RESULTS_BATCH` at once
+    TooManyOptionsForFullResults = 25, // The vote has more options than `MAX_OPTIONS_FULL_RESULTS`, its results are read by page
+    VoterOptedOut = 26, // The voter left the vote and asked not to be added again
+    PrerequisiteNotMet = 27, // The vote waits for its prerequisite vote to close with the required winner
+    ResponseTooLarge = 28, // The answer of a read instruction does not fit in the return data, read it by page or from the account
+    InsufficientVotes = 29, // The voter does not hold the votes the ballot or delegation would spend
+    VoterExpired = 30, // The allowlist entry of the caller was only valid until a time that has passed
+    DelegateCapReached = 31, // The delegation would take the delegate past `max_received_delegations`
+    InsufficientTokens = 32, // The token account of the voter holds less than a ballot burns
+    WrongTokenMint = 33,     // The token account or the mint is not the mint of the token-burn vote
+    BurnNotApproved = 34,    // The vote address may not burn what a ballot costs from the token account
+    VoteNotFound = 35,       // No vote of the registry has the ID
+    ResultsHidden = 36,      // The visibility settings of the vote hide its results from the caller
+    CommentRequired = 37,    // The vote takes only ballots carrying a comment of the voter
+    OptionWithdrawn = 38,    // The creator withdrew the option, it takes no more ballots
+    DuplicateAccount = 39,   // The same account was passed in two places that need distinct accounts
+    OutboxFull = 40,         // Every entry of the outbox is unconsumed and its policy refuses new ones
+    VoterParticipated = 41,  // The voter cast a ballot or delegated in the vote, they cannot attest the opposite
+}
+
+impl VoteError {
+    // Every variant in code order
+    pub const ALL: [VoteError; 42] = [
+        VoteError::VoteCancelled,
+        VoteError::VoteStillOpen,
+        VoteError::AllowlistFull,
+        VoteError::CreationCooldown,
+        VoteError::ProgramPaused,
+        VoteError::MinimumDurationNotElapsed,
+        VoteError::OptionCapReached,
+        VoteError::VoterNotAllowed,
+        VoteError::VoterBanned,
+        VoteError::DelegationExpired,
+        VoteError::VoterWindowClosed,
+        VoteError::DeadlinePassed,
+        VoteError::TooSoon,
+        VoteError::ApprovalRequired,
+        VoteError::AlreadyApproved,
+        VoteError::PendingActionExpired,
+        VoteError::CorruptState,
+        VoteError::PointBudgetMismatch,
+        VoteError::OptionFull,
+        VoteError::EligibilityNotMet,
+        VoteError::DelegateNotAllowed,
+        VoteError::MissingVoterAccount,
+        VoteError::MissingCreatorAccount,
+        VoteError::BallotsAlreadyCast,
+        VoteError::BatchTooLarge,
+        VoteError::TooManyOptionsForFullResults,
+        VoteError::VoterOptedOut,
+        VoteError::PrerequisiteNotMet,
+        VoteError::ResponseTooLarge,
+        VoteError::InsufficientVotes,
+        VoteError::VoterExpired,
+        VoteError::DelegateCapReached,
+        VoteError::InsufficientTokens,
+        VoteError::WrongTokenMint,
+        VoteError::BurnNotApproved,
+        VoteError::VoteNotFound,
+        VoteError::ResultsHidden,
+        VoteError::CommentRequired,
+        VoteError::OptionWithdrawn,
+        VoteError::DuplicateAccount,
+        VoteError::OutboxFull,
+        VoteError::VoterParticipated,
+    ];
+
+    // Code of the custom program error
+    pub const fn code(&self) -> u32 {
+        *self as u32
+    }
+
+    pub fn from_code(code: u32) -> Option<VoteError> {
+        VoteError::ALL.into_iter().find(|error| error.code() == code)
+    }
+
+    // Explanation for support teams, shown in the transaction logs
+    pub fn description(&self) -> &'static str {
+        match self {
+            VoteError::VoteCancelled => "the vote was cancelled by its creator",
+            VoteError::VoteStillOpen => "the vote is still open",
+            VoteError::AllowlistFull => "the allowlist of the vote is full",
+            VoteError::CreationCooldown => "the creator has to wait before creating another vote",
+            VoteError::ProgramPaused => "the registry is paused by its admin",
+            VoteError::MinimumDurationNotElapsed => "the minimum duration of the vote has not elapsed yet",
+            VoteError::OptionCapReached => "the option already received the maximum number of ballots",
+            VoteError::VoterNotAllowed => "the caller is not allowed to vote",
+            VoteError::VoterBanned => "the voter was banned from the vote by its creator",
+            VoteError::DelegationExpired => "the delegated votes expired and belong to the delegator again",
+            VoteError::VoterWindowClosed => "the voting window of the voter has closed",
+            VoteError::DeadlinePassed => "the deadline of the vote has passed",
+            VoteError::TooSoon => "the voter has to wait a few slots before casting another ballot",
+            VoteError::ApprovalRequired => "the action needs the approval of several creators of the vote",
+            VoteError::AlreadyApproved => "the caller already approved the pending action",
+            VoteError::PendingActionExpired => "the pending action expired and has to be proposed again",
+            VoteError::CorruptState => "the stored state of the vote is inconsistent and has to be repaired",
+            VoteError::PointBudgetMismatch => "the allocated points do not match the point budget of the voter",
+            VoteError::OptionFull => "every seat of the option is taken",
+            VoteError::EligibilityNotMet => "the caller does not meet the eligibility requirements of the vote",
+            VoteError::DelegateNotAllowed => "votes can only be delegated to allowed voters",
+            VoteError::MissingVoterAccount => "the instruction lacks the account of the voter",
+            VoteError::MissingCreatorAccount => "the instruction lacks the account of the creator",
+            VoteError::BallotsAlreadyCast => "ballots were already cast in the vote",
+            VoteError::BatchTooLarge => "the batch holds too many voters or ballots for a single instruction",
+            VoteError::TooManyOptionsForFullResults => "the vote has too many options to return its full results at once",
+            VoteError::VoterOptedOut => "the voter left the vote and asked not to be added again",
+            VoteError::PrerequisiteNotMet => "the prerequisite vote has not passed with the required option",
+            VoteError::ResponseTooLarge => "the answer does not fit in the return data",
+            VoteError::InsufficientVotes => "the voter has no votes left to spend",
+            VoteError::VoterExpired => "the allowlist entry of the voter has expired",
+            VoteError::DelegateCapReached => "the delegate cannot receive more delegations",
+            VoteError::InsufficientTokens => "the token account holds fewer tokens than the ballot burns",
+            VoteError::WrongTokenMint => "the token account does not hold the token the vote burns",
+            VoteError::BurnNotApproved => "the vote is not approved to burn the tokens of the ballot",
+            VoteError::VoteNotFound => "the vote does not exist",
+            VoteError::ResultsHidden => "the results of the vote are hidden from the caller",
+            VoteError::CommentRequired => "the vote requires a comment with every ballot",
+            VoteError::OptionWithdrawn => "the option was withdrawn from the vote",
+            VoteError::DuplicateAccount => "the same account was passed twice where distinct accounts are needed",
+            VoteError::OutboxFull => "the outbox is full of unconsumed entries",
+            VoteError::VoterParticipated => "the voter took part in the vote",
+        }
+    }
+}
+
+impl fmt::Display for VoteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.description())
+    }
+}
+
+impl From<VoteError> for ProgramError {
+    fn from(error: VoteError) -> Self {
+        ProgramError::Custom(error.code())
+    }
+}
+
+impl TryFrom<u32> for VoteError {
+    type Error = ProgramError;
+
+    fn try_from(code: u32) -> Result<Self, Self::Error> {
+        VoteError::from_code(code).ok_or(ProgramError::Custom(code))
+    }
+}
+
+// Code, name and message of every error as a JSON array, for generating the error table of TypeScript
+// clients. `error_codes.json` holds the current output
+#[cfg(any(test, feature = "client"))]
+pub fn error_codes_json() -> String {
+    let entries: Vec<String> = VoteError::ALL
+        .iter()
+        .map(|error| format!("  {{ \"code\": {}, \"name\": \"{:?}\", \"message\": \"{}\" }}", error.code(), error, error.description()))
+        .collect();
+    format!("[\n{}\n]\n", entries.join(",\n"))
+}
+
+// Log a readable reason for a rejected call, `context` names the vote and the caller
+pub fn log_error(err: &VoteError, context: &str) {
+    msg!("Error {:?}: {} ({})", err, err, context);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_code_round_trip() {
+        for (position, error) in VoteError::ALL.into_iter().enumerate() {
+            assert_eq!(error.code(), position as u32); // `ALL` lists the variants in code order
+            assert_eq!(VoteError::from_code(error.code()), Some(error));
+        }
+        assert_eq!(VoteError::from_code(VoteError::ALL.len() as u32), None);
+    }
+
+    // Fails when a variant is renumbered, renamed or reworded. Additions update the snapshot
+    #[test]
+    fn test_error_codes_snapshot() {
+        assert_eq!(error_codes_json(), include_str!("error_codes.json"));
+    }
+}
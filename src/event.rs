@@ -0,0 +1,145 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::clock::Slot;
+use solana_program::hash::Hash;
+use solana_program::log::sol_log_data;
+use solana_program::pubkey::Pubkey;
+#[cfg(any(test, feature = "client", feature = "wasm"))]
+use {base64::prelude::*, solana_program::program_error::ProgramError};
+
+use crate::{ClosureRecord, Comment, Milestones, OptionIndex, VoteId};
+
+// Prefix of the transaction log lines carrying program data
+#[cfg(any(test, feature = "client", feature = "wasm"))]
+pub const LOG_PREFIX: &str = "Program data: ";
+
+// Layout of the envelopes and events logged by this version, raised whenever either changes
+pub const EVENT_VERSION: u8 = 2;
+
+// Logged form of an event. `seq` counts the events of a vote from 0, so an indexer orders them and notices a
+// missing one without looking at the transactions
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct EventEnvelope {
+    pub version: u8,
+    pub seq: u64,
+    pub event: VoteEvent,
+}
+
+// Events of the voting program, logged as Borsh encoded program data for indexers. The ones changing the state
+// carry the clock they ran at, so `replay_events` can apply them again
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub enum VoteEvent {
+    VoterBallotsInvalidated {
+        vote_id: VoteId,
+        voter: Pubkey,
+        ballots_removed: u32, // Ballots struck from the tallies
+    },
+    MilestoneReached {
+        vote_id: VoteId,
+        milestone: Milestones, // A single flag
+    },
+    VoterScrubbed {
+        vote_id: VoteId,
+        ballots_anonymized: u32, // The voter is not named, so the logs do not keep them either
+    },
+    ResultsCertified {
+        vote_id: VoteId,
+        certifier: Pubkey,
+        results_hash: Hash,
+        certified_at: i64,
+    },
+    VoteCreated {
+        vote_id: VoteId,
+        state: Vec<u8>, // Borsh encoded vote as created, its allowlist included
+    },
+    VoterAdded {
+        vote_id: VoteId,
+        voter: Pubkey,
+        weight: u64,
+        valid_until: Option<i64>,
+        slot: Slot,
+        timestamp: i64,
+    },
+    BallotCast {
+        vote_id: VoteId,
+        voter: Pubkey,
+        question_index: u32,
+        option_index: OptionIndex,
+        comment: Option<Comment>,
+        slot: Slot,
+        timestamp: i64,
+    },
+    VoteClosed {
+        vote_id: VoteId,
+        closure: ClosureRecord,
+        slot: Slot,
+    },
+    OptionWithdrawn {
+        vote_id: VoteId,
+        option_index: OptionIndex, // Of the first question
+        ballots_refunded: u32, // Ballots taken from the tallies and given back to their voters
+        slot: Slot,
+        timestamp: i64,
+    },
+}
+
+impl VoteEvent {
+    pub fn vote_id(&self) -> VoteId {
+        match self {
+            Self::VoterBallotsInvalidated { vote_id, .. }
+            | Self::MilestoneReached { vote_id, .. }
+            | Self::VoterScrubbed { vote_id, .. }
+            | Self::ResultsCertified { vote_id, .. }
+            | Self::VoteCreated { vote_id, .. }
+            | Self::VoterAdded { vote_id, .. }
+            | Self::BallotCast { vote_id, .. }
+            | Self::VoteClosed { vote_id, .. }
+            | Self::OptionWithdrawn { vote_id, .. } => *vote_id,
+        }
+    }
+}
+
+impl EventEnvelope {
+    pub fn emit(&self) {
+        if let Ok(data) = borsh::to_vec(self) {
+            sol_log_data(&[&data]);
+        }
+    }
+
+    // Decode an envelope from a transaction log line, `None` for lines without program data
+    #[cfg(any(test, feature = "client", feature = "wasm"))]
+    pub fn from_log(log: &str) -> Result<Option<Self>, ProgramError> {
+        let Some(data) = log.strip_prefix(LOG_PREFIX) else {
+            return Ok(None);
+        };
+
+        let bytes = BASE64_STANDARD.decode(data.trim()).map_err(|_| ProgramError::InvalidArgument)?; // Events are logged as a single base64 field
+        if bytes.first() != Some(&EVENT_VERSION) {
+            return Err(ProgramError::InvalidArgument); // Return error for envelopes of another version
+        }
+        Self::try_from_slice(&bytes).map(Some).map_err(|_| ProgramError::InvalidAccountData)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_log() {
+        let event = VoteEvent::VoterBallotsInvalidated { vote_id: VoteId(3), voter: Pubkey::new_unique(), ballots_removed: 2 };
+        let envelope = EventEnvelope { version: EVENT_VERSION, seq: 7, event };
+        let log = format!("{}{}", LOG_PREFIX, BASE64_STANDARD.encode(borsh::to_vec(&envelope).unwrap()));
+
+        assert_eq!(EventEnvelope::from_log(&log), Ok(Some(envelope.clone())));
+        assert_eq!(envelope.event.vote_id(), VoteId(3));
+        assert_eq!(EventEnvelope::from_log("Program log: Created vote 3"), Ok(None));
+        assert_eq!(EventEnvelope::from_log("Program data: not base64!"), Err(ProgramError::InvalidArgument));
+        let truncated = format!("{}{}", LOG_PREFIX, BASE64_STANDARD.encode([EVENT_VERSION, 0, 0]));
+        assert_eq!(EventEnvelope::from_log(&truncated), Err(ProgramError::InvalidAccountData));
+
+        // Envelopes of another version are refused before their event is decoded
+        let envelope = EventEnvelope { version: EVENT_VERSION + 1, ..envelope };
+        let log = format!("{}{}", LOG_PREFIX, BASE64_STANDARD.encode(borsh::to_vec(&envelope).unwrap()));
+        assert_eq!(EventEnvelope::from_log(&log), Err(ProgramError::InvalidArgument));
+    }
+}
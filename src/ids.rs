@@ -0,0 +1,203 @@
+// Identifiers passed around the vote API, kept as distinct types so a vote ID can never be handed where an
+// option index is expected or the other way round
+use std::fmt;
+use std::io::{self, Read};
+use std::num::ParseIntError;
+use std::str::FromStr;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::program_error::ProgramError;
+
+// ID of a vote in its registry, assigned in creation order
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, BorshSerialize, BorshDeserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
+pub struct VoteId(pub u64);
+
+impl VoteId {
+    // ID following this one, `None` once the IDs run out
+    pub fn next(self) -> Option<Self> {
+        self.0.checked_add(1).map(Self)
+    }
+
+    // Encoding used in the seeds of the vote address and in signed messages
+    pub fn to_le_bytes(self) -> [u8; 8] {
+        self.0.to_le_bytes()
+    }
+}
+
+impl From<u32> for VoteId {
+    fn from(id: u32) -> Self {
+        Self(u64::from(id))
+    }
+}
+
+impl From<u64> for VoteId {
+    fn from(id: u64) -> Self {
+        Self(id)
+    }
+}
+
+impl From<VoteId> for u64 {
+    fn from(id: VoteId) -> Self {
+        id.0
+    }
+}
+
+impl FromStr for VoteId {
+    type Err = ParseIntError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        value.parse().map(Self)
+    }
+}
+
+impl fmt::Display for VoteId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+// Position of an option within a question
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, BorshSerialize, BorshDeserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
+pub struct OptionIndex(pub u16);
+
+impl OptionIndex {
+    // Index checked against the number of options of the question it is meant for
+    pub fn new(index: usize, options_count: usize) -> Result<Self, ProgramError> {
+        if index >= options_count {
+            return Err(ProgramError::InvalidArgument); // Return error if the question has no such option
+        }
+        Self::try_from(index)
+    }
+
+    pub fn index(self) -> usize {
+        usize::from(self.0)
+    }
+
+    pub fn to_le_bytes(self) -> [u8; 2] {
+        self.0.to_le_bytes()
+    }
+}
+
+impl From<u16> for OptionIndex {
+    fn from(index: u16) -> Self {
+        Self(index)
+    }
+}
+
+impl TryFrom<u32> for OptionIndex {
+    type Error = ProgramError;
+
+    fn try_from(index: u32) -> Result<Self, Self::Error> {
+        u16::try_from(index).map(Self).map_err(|_| ProgramError::InvalidArgument)
+    }
+}
+
+impl TryFrom<usize> for OptionIndex {
+    type Error = ProgramError;
+
+    fn try_from(index: usize) -> Result<Self, Self::Error> {
+        u16::try_from(index).map(Self).map_err(|_| ProgramError::InvalidArgument)
+    }
+}
+
+impl From<OptionIndex> for u32 {
+    fn from(index: OptionIndex) -> Self {
+        u32::from(index.0)
+    }
+}
+
+impl From<OptionIndex> for usize {
+    fn from(index: OptionIndex) -> Self {
+        index.index()
+    }
+}
+
+impl FromStr for OptionIndex {
+    type Err = ParseIntError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        value.parse().map(Self)
+    }
+}
+
+impl fmt::Display for OptionIndex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+// Two-letter ISO 639-1 code of a language the option labels are translated into, e.g. `ru`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, BorshSerialize)]
+pub struct LangCode([u8; 2]);
+
+impl LangCode {
+    // Lowercase ASCII letters only, so every code has a single spelling
+    pub fn new(code: &str) -> Result<Self, ProgramError> {
+        let bytes: [u8; 2] = code.as_bytes().try_into().map_err(|_| ProgramError::InvalidArgument)?; // Return error if the code is not two bytes long
+        Self::from_bytes(bytes).ok_or(ProgramError::InvalidArgument)
+    }
+
+    fn from_bytes(bytes: [u8; 2]) -> Option<Self> {
+        bytes.iter().all(u8::is_ascii_lowercase).then_some(Self(bytes))
+    }
+
+    pub fn as_str(&self) -> &str {
+        std::str::from_utf8(&self.0).unwrap_or_default() // Always ASCII, checked on every way in
+    }
+}
+
+// Decoded codes are checked like new ones, so a crafted account cannot hold text that is not a code
+impl BorshDeserialize for LangCode {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let bytes = <[u8; 2]>::deserialize_reader(reader)?;
+        Self::from_bytes(bytes).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid language code"))
+    }
+}
+
+impl FromStr for LangCode {
+    type Err = ProgramError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Self::new(value)
+    }
+}
+
+impl fmt::Display for LangCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lang_code() {
+        assert_eq!(LangCode::new("ru").map(|code| code.to_string()), Ok("ru".to_string()));
+        for code in ["RU", "r", "rus", "r1", "ру"] {
+            assert_eq!(LangCode::new(code), Err(ProgramError::InvalidArgument));
+        }
+        assert!(LangCode::try_from_slice(b"R1").is_err());
+        assert_eq!(LangCode::try_from_slice(b"en").ok(), LangCode::new("en").ok());
+    }
+
+    #[test]
+    fn test_option_index_bounds() {
+        assert_eq!(OptionIndex::new(2, 3), Ok(OptionIndex(2)));
+        assert_eq!(OptionIndex::new(3, 3), Err(ProgramError::InvalidArgument));
+        assert_eq!(OptionIndex::try_from(70_000u32), Err(ProgramError::InvalidArgument));
+        assert_eq!(OptionIndex::try_from(7usize).map(usize::from), Ok(7));
+    }
+
+    #[test]
+    fn test_vote_id_encoding() {
+        let id = VoteId::from(3u32);
+        assert_eq!(borsh::to_vec(&id).unwrap(), 3u64.to_le_bytes());
+        assert_eq!(id.to_string(), "3");
+        assert_eq!(id.next(), Some(VoteId(4)));
+        assert_eq!(VoteId(u64::MAX).next(), None);
+    }
+}
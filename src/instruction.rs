@@ -0,0 +1,464 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::instruction::{AccountMeta, Instruction};
+use solana_program::sysvar::instructions as sysvar_instructions;
+#[allow(deprecated)]
+use solana_program::sysvar::recent_blockhashes;
+use solana_program::{program_error::ProgramError, pubkey::Pubkey, system_program};
+
+use crate::{get_outbox_address, get_receipt_address, get_shard_address, get_vote_address, CreationPolicy, DelegationScope, OptionIndex, OutboxPolicy, SignedBallot, VoteConfig, VoteId};
+
+// Instructions understood by the program, encoded with Borsh. Every instruction reads its accounts at the
+// positions listed below, `caller_index` and `registry_index` give the positions of the acting account and
+// of the registry. Accounts past the listed ones are ignored
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub enum VoteInstruction {
+    // Accounts: [registry (writable, uninitialized)]
+    InitializeRegistry { admin: Pubkey },
+
+    // Accounts: [admin (signer), registry (writable)]
+    UpdatePolicy { policy: CreationPolicy },
+
+    // Accounts: [admin (signer), registry (writable)]
+    Pause,
+
+    // Accounts: [admin (signer), registry (writable)]
+    Unpause,
+
+    // Accounts: [payer (signer, writable), voter (signer), registry (writable), receipt (writable), system program].
+    // The first ballot of the voter creates the receipt at `get_receipt_address`, funded by the payer, which may
+    // be the fee payer of the transaction. The first ballot of a voter on an open vote joins it, passing the voter
+    // account to the eligibility check. Ballots of token-burn votes add [token account of the voter (writable),
+    // mint (writable), vote address, token program], the vote address has to be approved as delegate of the account
+    CastVote { vote_id: VoteId, question_index: u32, option_index: OptionIndex },
+
+    // Accounts: [payer (signer, writable), voter (signer), registry (writable), system program], followed by the
+    // receipt (writable) of every ballot in order. Ballots on the first question of several votes, applied together.
+    // Token-burn votes only take `CastVote`
+    CastVotes { ballots: Vec<(VoteId, OptionIndex)> },
+
+    // Accounts: [creator (signer, writable), registry (writable)], followed by [treasury (writable)] when the registry
+    // charges a creation fee, [vote address (writable)] when it takes a creation deposit and the system program with either.
+    // On a sharded registry the `shard_accounts` of the next shard come last, the creator funds a new shard
+    CreateVote { config: Box<VoteConfig> }, // Boxed, the config is far larger than the other instructions
+
+    // Accounts: [creator (signer), registry (writable)]
+    AddAllowedVoter { vote_id: VoteId, voter: Pubkey },
+
+    // Accounts: [delegator (signer), registry (writable)]
+    DelegateVote { vote_id: VoteId, delegate: Pubkey, expires_at: Option<i64> },
+
+    // Accounts: [creator or registry admin (signer), registry (writable), recent blockhashes sysvar].
+    // The sysvar seeds the tie-break of votes breaking ties by blockhash. Votes holding a creation deposit are
+    // followed by the `deposit_accounts`
+    CloseVote { vote_id: VoteId },
+
+    // Accounts: [registry admin (signer), registry (writable)]
+    RepairVote { vote_id: VoteId },
+
+    // Accounts: [delegate (signer), registry (writable)]
+    AcceptDelegation { vote_id: VoteId, delegator: Pubkey },
+
+    // Accounts: [delegator (signer), registry (writable)]
+    WithdrawDelegation { vote_id: VoteId, delegate: Pubkey },
+
+    // Accounts: [voter (signer), registry (writable), eligibility account of the voter]
+    RegisterVoter { vote_id: VoteId },
+
+    // Accounts: [relayer (signer, writable), registry (writable), receipt of the voter (writable), system program,
+    // instructions sysvar]. Right after an ed25519 program instruction verifying `signature` of the voter over
+    // `ballot.to_message(program_id, registry)`. The relayer pays the fees and the receipt, the ballot counts for the voter
+    CastVoteSigned { ballot: SignedBallot, signature: [u8; 64] },
+
+    // Read-only queries for other programs. Accounts: [registry]. The answer is set as return data,
+    // a single byte that is 1 for yes and 0 for no
+    VoteExists { vote_id: VoteId },
+    HasVoted { vote_id: VoteId, voter: Pubkey },
+    OptionExists { vote_id: VoteId, index: OptionIndex }, // Options of the first question
+
+    // Read instructions answering with a Borsh encoded value of `responses`. Accounts: [registry, viewer],
+    // the visibility settings of the vote are checked against the viewer. Data that is not public needs the
+    // viewer to sign, otherwise the read fails with `MissingRequiredSignature`
+    GetResults { vote_id: VoteId, question_index: Option<u32> }, // `ResultsResponse`, every question for `None`
+    GetOptions { vote_id: VoteId },                              // `OptionsResponse`, accounts: [registry]
+    IsVoterAllowed { vote_id: VoteId, voter: Pubkey },           // A bool like the queries above, accounts: [registry]
+    GetVoterStatus { vote_id: VoteId, voter: Pubkey },           // `VoterStatusResponse`
+
+    // Dry runs answering with a `SimulationResponse`, nothing is written. Accounts: those of the simulated call
+    // after the registry, the voter or delegator does not sign. Accounts: [registry, voter, eligibility account
+    // (for a voter joining an open vote)] and [registry, delegator]
+    SimulateVote { vote_id: VoteId, question_index: u32, option_index: OptionIndex },
+    SimulateDelegate { vote_id: VoteId, delegate: Pubkey, expires_at: Option<i64> },
+
+    // Accounts: [creator or registry admin (signer, writable), registry (writable), receipt of the voter (writable)].
+    // Removes the identity of the voter from a closed or cancelled vote and closes their receipt, if any, whose
+    // rent goes to the caller
+    ScrubVoter { vote_id: VoteId, voter: Pubkey },
+
+    // Accounts: [creator or observer (signer), registry (writable)]
+    CertifyResults { vote_id: VoteId },
+
+    // Accounts: [admin (signer), registry (writable)]
+    EnableSharding { shard_capacity: u32 },
+
+    // `ResultsBatchResponse` for at most `MAX_RESULTS_BATCH` votes. Accounts: [registry, viewer], the viewer signs when
+    // one of the votes keeps its results from the public
+    GetResultsBatch { vote_ids: Vec<VoteId> },
+
+    // Accounts as for `CastVote`. The ballot of a vote requiring a comment, `CastVote` is refused there
+    CastVoteWithComment { vote_id: VoteId, question_index: u32, option_index: OptionIndex, comment: String },
+
+    // Accounts: [admin (signer, writable), registry (writable), outbox (writable), system program]. The admin funds
+    // the outbox at `get_outbox_address`, closures then take `outbox_account` as their last account
+    EnableOutbox { capacity: u32, policy: OutboxPolicy },
+
+    // Accounts: [admin (signer), registry, outbox (writable)]
+    AckOutbox { vote_ids: Vec<VoteId> },
+
+    // Accounts: [allowlisted voter (signer), registry (writable)]
+    AttestNonParticipation { vote_id: VoteId },
+
+    // Accounts as for `DelegateVote`. Moves the votes of the questions in scope only
+    DelegateVoteScoped { vote_id: VoteId, delegate: Pubkey, scope: DelegationScope },
+}
+
+// Borsh tags of the instructions decoded by hand, their positions in the enum
+const CAST_VOTE_TAG: u8 = 4;
+const DELEGATE_VOTE_TAG: u8 = 8;
+
+// Reads the fixed-size fields of an instruction off the front of its data, without allocating
+struct FixedReader<'a>(&'a [u8]);
+
+impl FixedReader<'_> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], ProgramError> {
+        let field = self.0.get(..N).ok_or(ProgramError::InvalidInstructionData)?; // Return error if the data is truncated
+        self.0 = &self.0[N..];
+        field.try_into().map_err(|_| ProgramError::InvalidInstructionData)
+    }
+
+    fn finish<T>(self, instruction: T) -> Result<T, ProgramError> {
+        if !self.0.is_empty() {
+            return Err(ProgramError::InvalidInstructionData); // Return error for trailing bytes, like Borsh
+        }
+        Ok(instruction)
+    }
+}
+
+impl VoteInstruction {
+    // Ballots and delegations make up most of the traffic, their fixed layouts are read straight from the data.
+    // Both decode exactly as Borsh would, the other instructions go through Borsh
+    pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        match data.split_first() {
+            Some((&CAST_VOTE_TAG, fields)) => Self::unpack_cast_vote(FixedReader(fields)),
+            Some((&DELEGATE_VOTE_TAG, fields)) => Self::unpack_delegate_vote(FixedReader(fields)),
+            _ => Self::try_from_slice(data).map_err(|_| ProgramError::InvalidInstructionData),
+        }
+    }
+
+    fn unpack_cast_vote(mut reader: FixedReader) -> Result<Self, ProgramError> {
+        let vote_id = VoteId(u64::from_le_bytes(reader.take()?));
+        let question_index = u32::from_le_bytes(reader.take()?);
+        let option_index = OptionIndex(u16::from_le_bytes(reader.take()?));
+        reader.finish(VoteInstruction::CastVote { vote_id, question_index, option_index })
+    }
+
+    fn unpack_delegate_vote(mut reader: FixedReader) -> Result<Self, ProgramError> {
+        let vote_id = VoteId(u64::from_le_bytes(reader.take()?));
+        let delegate = Pubkey::new_from_array(reader.take()?);
+        let expires_at = match reader.take::<1>()? {
+            [0] => None,
+            [1] => Some(i64::from_le_bytes(reader.take()?)),
+            _ => return Err(ProgramError::InvalidInstructionData), // Return error for an invalid option tag
+        };
+        reader.finish(VoteInstruction::DelegateVote { vote_id, delegate, expires_at })
+    }
+
+    pub fn pack(&self) -> Vec<u8> {
+        borsh::to_vec(self).unwrap_or_default()
+    }
+
+    // Position of the account acting in the instruction: the voter, delegator or delegate, or the creator
+    // or registry admin. `None` for the instructions nobody signs
+    pub fn caller_index(&self) -> Option<usize> {
+        match self {
+            VoteInstruction::InitializeRegistry { .. } | VoteInstruction::VoteExists { .. } | VoteInstruction::HasVoted { .. } | VoteInstruction::OptionExists { .. } => None,
+            VoteInstruction::GetResults { .. } | VoteInstruction::GetOptions { .. } | VoteInstruction::IsVoterAllowed { .. } | VoteInstruction::GetVoterStatus { .. } => None, // The viewer follows the registry
+            VoteInstruction::GetResultsBatch { .. } => None,
+            VoteInstruction::SimulateVote { .. } | VoteInstruction::SimulateDelegate { .. } => None,
+            VoteInstruction::CastVote { .. } | VoteInstruction::CastVotes { .. } | VoteInstruction::CastVoteWithComment { .. } => Some(1), // After the payer
+            _ => Some(0),
+        }
+    }
+
+    // Position of the registry account
+    pub fn registry_index(&self) -> usize {
+        match self.caller_index() {
+            Some(index) => index + 1,
+            None => 0,
+        }
+    }
+
+    // Whether the caller acts as a voter, as opposed to the creator or admin of the registry
+    pub fn is_voter_instruction(&self) -> bool {
+        matches!(
+            self,
+            VoteInstruction::CastVote { .. }
+                | VoteInstruction::CastVotes { .. }
+                | VoteInstruction::CastVoteWithComment { .. }
+                | VoteInstruction::DelegateVote { .. }
+                | VoteInstruction::DelegateVoteScoped { .. }
+                | VoteInstruction::AcceptDelegation { .. }
+                | VoteInstruction::WithdrawDelegation { .. }
+                | VoteInstruction::RegisterVoter { .. }
+                | VoteInstruction::CastVoteSigned { .. } // The relayer stands in for the voter
+        )
+    }
+}
+
+// Instruction run by `caller` against the registry, for the instructions that need no other accounts
+fn registry_instruction(program_id: &Pubkey, registry: &Pubkey, caller: &Pubkey, instruction: VoteInstruction) -> Instruction {
+    let accounts = vec![AccountMeta::new_readonly(*caller, true), AccountMeta::new(*registry, false)];
+    Instruction::new_with_bytes(*program_id, &instruction.pack(), accounts)
+}
+
+pub fn initialize_registry(program_id: &Pubkey, registry: &Pubkey, admin: &Pubkey) -> Instruction {
+    let instruction = VoteInstruction::InitializeRegistry { admin: *admin };
+    Instruction::new_with_bytes(*program_id, &instruction.pack(), vec![AccountMeta::new(*registry, false)])
+}
+
+pub fn update_policy(program_id: &Pubkey, registry: &Pubkey, admin: &Pubkey, policy: CreationPolicy) -> Instruction {
+    registry_instruction(program_id, registry, admin, VoteInstruction::UpdatePolicy { policy })
+}
+
+// `treasury` is required when the registry charges a creation fee
+pub fn create_vote(program_id: &Pubkey, registry: &Pubkey, creator: &Pubkey, config: VoteConfig, treasury: Option<&Pubkey>) -> Instruction {
+    let mut accounts = vec![AccountMeta::new(*creator, true), AccountMeta::new(*registry, false)];
+    if let Some(treasury) = treasury {
+        accounts.push(AccountMeta::new(*treasury, false));
+        accounts.push(AccountMeta::new_readonly(system_program::id(), false));
+    }
+
+    Instruction::new_with_bytes(*program_id, &VoteInstruction::CreateVote { config: Box::new(config) }.pack(), accounts)
+}
+
+// Creation on a registry taking a creation deposit, which goes to the vote address of `vote_id`. The ID is the
+// one the vote gets, see `Voting::next_vote_id`
+pub fn create_vote_with_deposit(program_id: &Pubkey, registry: &Pubkey, creator: &Pubkey, config: VoteConfig, treasury: Option<&Pubkey>, vote_id: VoteId) -> Instruction {
+    let mut accounts = vec![AccountMeta::new(*creator, true), AccountMeta::new(*registry, false)];
+    if let Some(treasury) = treasury {
+        accounts.push(AccountMeta::new(*treasury, false));
+    }
+    accounts.push(AccountMeta::new(get_vote_address(program_id, registry, vote_id).0, false));
+    accounts.push(AccountMeta::new_readonly(system_program::id(), false));
+
+    Instruction::new_with_bytes(*program_id, &VoteInstruction::CreateVote { config: Box::new(config) }.pack(), accounts)
+}
+
+// Accounts that move the creation deposit of a vote to `recipient`, the creator on a refund and the treasury
+// otherwise. They follow the other accounts of an instruction ending the vote
+pub fn deposit_accounts(program_id: &Pubkey, registry: &Pubkey, vote_id: VoteId, recipient: &Pubkey) -> Vec<AccountMeta> {
+    vec![
+        AccountMeta::new(get_vote_address(program_id, registry, vote_id).0, false),
+        AccountMeta::new(*recipient, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ]
+}
+
+pub fn add_allowed_voter(program_id: &Pubkey, registry: &Pubkey, creator: &Pubkey, vote_id: VoteId, voter: &Pubkey) -> Instruction {
+    registry_instruction(program_id, registry, creator, VoteInstruction::AddAllowedVoter { vote_id, voter: *voter })
+}
+
+// The voter's receipt is funded by `payer`, which may be the voter
+pub fn cast_vote(program_id: &Pubkey, registry: &Pubkey, voter: &Pubkey, payer: &Pubkey, vote_id: VoteId, question_index: u32, option_index: OptionIndex) -> Instruction {
+    cast_instruction(program_id, registry, voter, payer, VoteInstruction::CastVote { vote_id, question_index, option_index }, vote_id)
+}
+
+#[allow(clippy::too_many_arguments)] // The arguments of `cast_vote` and the comment
+pub fn cast_vote_with_comment(program_id: &Pubkey, registry: &Pubkey, voter: &Pubkey, payer: &Pubkey, vote_id: VoteId, question_index: u32, option_index: OptionIndex, comment: String) -> Instruction {
+    cast_instruction(program_id, registry, voter, payer, VoteInstruction::CastVoteWithComment { vote_id, question_index, option_index, comment }, vote_id)
+}
+
+fn cast_instruction(program_id: &Pubkey, registry: &Pubkey, voter: &Pubkey, payer: &Pubkey, instruction: VoteInstruction, vote_id: VoteId) -> Instruction {
+    let (vote_address, _) = get_vote_address(program_id, registry, vote_id);
+    let (receipt_address, _) = get_receipt_address(program_id, &vote_address, voter);
+
+    let accounts = vec![
+        AccountMeta::new(*payer, true),
+        AccountMeta::new_readonly(*voter, true),
+        AccountMeta::new(*registry, false),
+        AccountMeta::new(receipt_address, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+
+    Instruction::new_with_bytes(*program_id, &instruction.pack(), accounts)
+}
+
+// Accounts a ballot of a token-burn vote adds to `cast_vote`, burning from `token_account` of the voter
+pub fn token_burn_accounts(program_id: &Pubkey, registry: &Pubkey, vote_id: VoteId, token_account: &Pubkey, mint: &Pubkey) -> Vec<AccountMeta> {
+    let (vote_address, _) = get_vote_address(program_id, registry, vote_id);
+    vec![
+        AccountMeta::new(*token_account, false),
+        AccountMeta::new(*mint, false),
+        AccountMeta::new_readonly(vote_address, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ]
+}
+
+// Accounts a creation on a sharded registry adds to `create_vote`, for the shard of `Voting::next_shard_index`
+pub fn shard_accounts(program_id: &Pubkey, registry: &Pubkey, shard_index: u32) -> Vec<AccountMeta> {
+    vec![AccountMeta::new(get_shard_address(program_id, registry, shard_index).0, false), AccountMeta::new_readonly(system_program::id(), false)]
+}
+
+// Several ballots of `voter` in one transaction, at most `MAX_BATCH_BALLOTS`
+pub fn cast_votes(program_id: &Pubkey, registry: &Pubkey, voter: &Pubkey, payer: &Pubkey, ballots: &[(VoteId, OptionIndex)]) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(*payer, true),
+        AccountMeta::new_readonly(*voter, true),
+        AccountMeta::new(*registry, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    for (vote_id, _) in ballots {
+        let (vote_address, _) = get_vote_address(program_id, registry, *vote_id);
+        accounts.push(AccountMeta::new(get_receipt_address(program_id, &vote_address, voter).0, false));
+    }
+
+    Instruction::new_with_bytes(*program_id, &VoteInstruction::CastVotes { ballots: ballots.to_vec() }.pack(), accounts)
+}
+
+pub fn delegate_vote(program_id: &Pubkey, registry: &Pubkey, delegator: &Pubkey, vote_id: VoteId, delegate: &Pubkey, expires_at: Option<i64>) -> Instruction {
+    registry_instruction(program_id, registry, delegator, VoteInstruction::DelegateVote { vote_id, delegate: *delegate, expires_at })
+}
+
+#[allow(deprecated)]
+pub fn close_vote(program_id: &Pubkey, registry: &Pubkey, caller: &Pubkey, vote_id: VoteId) -> Instruction {
+    let mut instruction = registry_instruction(program_id, registry, caller, VoteInstruction::CloseVote { vote_id });
+    instruction.accounts.push(AccountMeta::new_readonly(recent_blockhashes::id(), false));
+    instruction
+}
+
+pub fn repair_vote(program_id: &Pubkey, registry: &Pubkey, admin: &Pubkey, vote_id: VoteId) -> Instruction {
+    registry_instruction(program_id, registry, admin, VoteInstruction::RepairVote { vote_id })
+}
+
+pub fn accept_delegation(program_id: &Pubkey, registry: &Pubkey, delegate: &Pubkey, vote_id: VoteId, delegator: &Pubkey) -> Instruction {
+    registry_instruction(program_id, registry, delegate, VoteInstruction::AcceptDelegation { vote_id, delegator: *delegator })
+}
+
+// The voter presents their own account to the eligibility check, e.g. their wallet for a minimum stake
+pub fn register_voter(program_id: &Pubkey, registry: &Pubkey, voter: &Pubkey, vote_id: VoteId) -> Instruction {
+    let mut instruction = registry_instruction(program_id, registry, voter, VoteInstruction::RegisterVoter { vote_id });
+    instruction.accounts.push(AccountMeta::new_readonly(*voter, false));
+    instruction
+}
+
+// Has to follow the ed25519 program instruction verifying `signature`
+pub fn cast_vote_signed(program_id: &Pubkey, registry: &Pubkey, relayer: &Pubkey, ballot: SignedBallot, signature: [u8; 64]) -> Instruction {
+    let (vote_address, _) = get_vote_address(program_id, registry, ballot.vote_id);
+    let (receipt_address, _) = get_receipt_address(program_id, &vote_address, &ballot.voter);
+
+    let accounts = vec![
+        AccountMeta::new(*relayer, true),
+        AccountMeta::new(*registry, false),
+        AccountMeta::new(receipt_address, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(sysvar_instructions::id(), false),
+    ];
+
+    Instruction::new_with_bytes(*program_id, &VoteInstruction::CastVoteSigned { ballot, signature }.pack(), accounts)
+}
+
+// Query instruction of the read-only instructions, which only take the registry
+fn query_instruction(program_id: &Pubkey, registry: &Pubkey, instruction: VoteInstruction) -> Instruction {
+    Instruction::new_with_bytes(*program_id, &instruction.pack(), vec![AccountMeta::new_readonly(*registry, false)])
+}
+
+pub fn vote_exists(program_id: &Pubkey, registry: &Pubkey, vote_id: VoteId) -> Instruction {
+    query_instruction(program_id, registry, VoteInstruction::VoteExists { vote_id })
+}
+
+pub fn has_voted(program_id: &Pubkey, registry: &Pubkey, vote_id: VoteId, voter: &Pubkey) -> Instruction {
+    query_instruction(program_id, registry, VoteInstruction::HasVoted { vote_id, voter: *voter })
+}
+
+pub fn option_exists(program_id: &Pubkey, registry: &Pubkey, vote_id: VoteId, index: OptionIndex) -> Instruction {
+    query_instruction(program_id, registry, VoteInstruction::OptionExists { vote_id, index })
+}
+
+// Read instruction of the queries checked against the visibility settings, answered for `viewer`. The viewer
+// signs, data that is not public is only read for them with their signature
+fn viewer_query_instruction(program_id: &Pubkey, registry: &Pubkey, viewer: &Pubkey, instruction: VoteInstruction) -> Instruction {
+    let accounts = vec![AccountMeta::new_readonly(*registry, false), AccountMeta::new_readonly(*viewer, true)];
+    Instruction::new_with_bytes(*program_id, &instruction.pack(), accounts)
+}
+
+pub fn get_results(program_id: &Pubkey, registry: &Pubkey, viewer: &Pubkey, vote_id: VoteId, question_index: Option<u32>) -> Instruction {
+    viewer_query_instruction(program_id, registry, viewer, VoteInstruction::GetResults { vote_id, question_index })
+}
+
+pub fn get_results_batch(program_id: &Pubkey, registry: &Pubkey, viewer: &Pubkey, vote_ids: Vec<VoteId>) -> Instruction {
+    viewer_query_instruction(program_id, registry, viewer, VoteInstruction::GetResultsBatch { vote_ids })
+}
+
+pub fn get_options(program_id: &Pubkey, registry: &Pubkey, vote_id: VoteId) -> Instruction {
+    query_instruction(program_id, registry, VoteInstruction::GetOptions { vote_id })
+}
+
+pub fn is_voter_allowed(program_id: &Pubkey, registry: &Pubkey, vote_id: VoteId, voter: &Pubkey) -> Instruction {
+    query_instruction(program_id, registry, VoteInstruction::IsVoterAllowed { vote_id, voter: *voter })
+}
+
+pub fn get_voter_status(program_id: &Pubkey, registry: &Pubkey, viewer: &Pubkey, vote_id: VoteId, voter: &Pubkey) -> Instruction {
+    viewer_query_instruction(program_id, registry, viewer, VoteInstruction::GetVoterStatus { vote_id, voter: *voter })
+}
+
+pub fn scrub_voter(program_id: &Pubkey, registry: &Pubkey, caller: &Pubkey, vote_id: VoteId, voter: &Pubkey) -> Instruction {
+    let (vote_address, _) = get_vote_address(program_id, registry, vote_id);
+    let (receipt, _) = get_receipt_address(program_id, &vote_address, voter);
+    let accounts = vec![AccountMeta::new(*caller, true), AccountMeta::new(*registry, false), AccountMeta::new(receipt, false)];
+    Instruction::new_with_bytes(*program_id, &VoteInstruction::ScrubVoter { vote_id, voter: *voter }.pack(), accounts)
+}
+
+pub fn certify_results(program_id: &Pubkey, registry: &Pubkey, certifier: &Pubkey, vote_id: VoteId) -> Instruction {
+    registry_instruction(program_id, registry, certifier, VoteInstruction::CertifyResults { vote_id })
+}
+
+pub fn enable_sharding(program_id: &Pubkey, registry: &Pubkey, admin: &Pubkey, shard_capacity: u32) -> Instruction {
+    registry_instruction(program_id, registry, admin, VoteInstruction::EnableSharding { shard_capacity })
+}
+
+pub fn enable_outbox(program_id: &Pubkey, registry: &Pubkey, admin: &Pubkey, capacity: u32, policy: OutboxPolicy) -> Instruction {
+    let mut instruction = registry_instruction(program_id, registry, admin, VoteInstruction::EnableOutbox { capacity, policy });
+    instruction.accounts[0].is_writable = true; // Pays for the outbox
+    instruction.accounts.extend([outbox_account(program_id, registry), AccountMeta::new_readonly(system_program::id(), false)]);
+    instruction
+}
+
+pub fn ack_outbox(program_id: &Pubkey, registry: &Pubkey, admin: &Pubkey, vote_ids: Vec<VoteId>) -> Instruction {
+    let mut instruction = registry_instruction(program_id, registry, admin, VoteInstruction::AckOutbox { vote_ids });
+    instruction.accounts.push(outbox_account(program_id, registry));
+    instruction
+}
+
+pub fn delegate_vote_scoped(program_id: &Pubkey, registry: &Pubkey, delegator: &Pubkey, vote_id: VoteId, delegate: &Pubkey, scope: DelegationScope) -> Instruction {
+    registry_instruction(program_id, registry, delegator, VoteInstruction::DelegateVoteScoped { vote_id, delegate: *delegate, scope })
+}
+
+pub fn attest_non_participation(program_id: &Pubkey, registry: &Pubkey, voter: &Pubkey, vote_id: VoteId) -> Instruction {
+    registry_instruction(program_id, registry, voter, VoteInstruction::AttestNonParticipation { vote_id })
+}
+
+// Account to append to the closures of a registry with an outbox, after any deposit accounts
+pub fn outbox_account(program_id: &Pubkey, registry: &Pubkey) -> AccountMeta {
+    AccountMeta::new(get_outbox_address(program_id, registry).0, false)
+}
+
+pub fn simulate_vote(program_id: &Pubkey, registry: &Pubkey, voter: &Pubkey, vote_id: VoteId, question_index: u32, option_index: OptionIndex) -> Instruction {
+    viewer_query_instruction(program_id, registry, voter, VoteInstruction::SimulateVote { vote_id, question_index, option_index })
+}
+
+pub fn simulate_delegate(program_id: &Pubkey, registry: &Pubkey, delegator: &Pubkey, vote_id: VoteId, delegate: &Pubkey, expires_at: Option<i64>) -> Instruction {
+    viewer_query_instruction(program_id, registry, delegator, VoteInstruction::SimulateDelegate { vote_id, delegate: *delegate, expires_at })
+}
+
+pub fn withdraw_delegation(program_id: &Pubkey, registry: &Pubkey, delegator: &Pubkey, vote_id: VoteId, delegate: &Pubkey) -> Instruction {
+    registry_instruction(program_id, registry, delegator, VoteInstruction::WithdrawDelegation { vote_id, delegate: *delegate })
+}
@@ -0,0 +1,271 @@
+// Byte snapshots of the serialized accounts and instructions. Deployed accounts and clients built against an
+// older version decode these bytes, so a layout change has to be deliberate. Fixtures are hex in
+// `src/layout_fixtures`, 32 bytes per line so a changed field shows up as a changed line in the diff
+use borsh::BorshSerialize;
+use solana_program::pubkey::Pubkey;
+use std::fmt::Write;
+use std::path::PathBuf;
+
+use crate::instruction::VoteInstruction;
+use crate::test_utils::{set_clock, set_slot, TestHarness};
+use crate::{
+    CreationDeposit, CreationFee, CreationPolicy, DelegationScope, OptionIndex, OutboxPolicy, SignedBallot, VoteConfig, VoteId, VoteType, Voting, REGISTRY_DISCRIMINATOR,
+};
+
+const BYTES_PER_LINE: usize = 32;
+
+// Set to regenerate the fixtures instead of comparing against them
+const UPDATE_ENV: &str = "UPDATE_LAYOUTS";
+
+fn fixture_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/layout_fixtures").join(format!("{}.hex", name))
+}
+
+fn to_hex_lines(bytes: &[u8]) -> String {
+    let mut hex = String::new();
+    for line in bytes.chunks(BYTES_PER_LINE) {
+        for byte in line {
+            write!(hex, "{:02x}", byte).unwrap();
+        }
+        hex.push('\n');
+    }
+    hex
+}
+
+// Bytes of a checked-in fixture, e.g. the accounts the decoders are tested on
+pub(crate) fn fixture(name: &str) -> Vec<u8> {
+    let path = fixture_path(name);
+    from_hex_lines(&std::fs::read_to_string(&path).unwrap_or_else(|_| panic!("no layout fixture at {}", path.display())))
+}
+
+fn from_hex_lines(hex: &str) -> Vec<u8> {
+    let digits: Vec<u8> = hex.bytes().filter(|byte| !byte.is_ascii_whitespace()).collect();
+    digits.chunks(2).map(|pair| u8::from_str_radix(std::str::from_utf8(pair).unwrap(), 16).expect("fixtures hold hex digits")).collect()
+}
+
+// Compare `bytes` with the fixture `name`. A difference fails with the first changed line and the steps for
+// changing a layout on purpose:
+//   1. Bump the version covering the bytes: `VIEW_VERSION` for vote state accounts, `EVENT_VERSION` for the
+//      logged events, `BUNDLE_VERSION` for bundles. The registry has no version field, it needs a new
+//      `REGISTRY_DISCRIMINATOR` so old accounts are told apart. Instructions append new variants at the end
+//   2. Add a migration decoding the previous layout, and a test decoding the old fixture with it
+//   3. Regenerate the fixtures with `UPDATE_LAYOUTS=1 cargo test layout_snapshots` and commit them
+fn assert_layout_or_instruct(name: &str, bytes: &[u8]) {
+    let path = fixture_path(name);
+    if std::env::var_os(UPDATE_ENV).is_some() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, to_hex_lines(bytes)).unwrap();
+        return;
+    }
+
+    let instructions = format!(
+        "If the change is deliberate: bump the layout version covering it, add a migration for the old bytes, \
+         then regenerate with `{}=1 cargo test layout_snapshots` (see `assert_layout_or_instruct`)",
+        UPDATE_ENV
+    );
+    let Ok(fixture) = std::fs::read_to_string(&path) else {
+        panic!("no layout fixture at {}. Generate it with `{}=1 cargo test layout_snapshots`", path.display(), UPDATE_ENV);
+    };
+    let expected = from_hex_lines(&fixture);
+    if expected == bytes {
+        return;
+    }
+
+    let offset = expected.iter().zip(bytes).position(|(old, new)| old != new).unwrap_or(expected.len().min(bytes.len()));
+    let line = offset / BYTES_PER_LINE;
+    let line_of = |bytes: &[u8]| to_hex_lines(bytes.chunks(BYTES_PER_LINE).nth(line).unwrap_or_default());
+    panic!(
+        "layout of `{}` changed: {} bytes before, {} now, first difference at byte {} (line {} of {})\n- {}+ {}{}",
+        name,
+        expected.len(),
+        bytes.len(),
+        offset,
+        line + 1,
+        path.display(),
+        line_of(&expected),
+        line_of(bytes),
+        instructions
+    );
+}
+
+// Fixed keys, `Pubkey::new_unique` depends on the order the tests run in
+const fn key(seed: u8) -> Pubkey {
+    Pubkey::new_from_array([seed; 32])
+}
+
+pub(crate) const CANONICAL_VOTE_TYPES: [(&str, VoteType); 6] = [
+    ("vote_single_choice", VoteType::SingleChoice),
+    ("vote_time_weighted", VoteType::TimeWeighted { start: 1_700_000_000, end: 1_700_086_400, start_weight_bps: 10_000, end_weight_bps: 5_000 }),
+    ("vote_token_weighted", VoteType::TokenWeighted),
+    ("vote_point_allocation", VoteType::PointAllocation { points_per_voter: 10, allow_partial: false }),
+    ("vote_conviction", VoteType::Conviction { period_slots: 1_000, max_multiplier: 8 }),
+    ("vote_token_burn", VoteType::TokenBurn { mint: key(9), burn_amount: 50 }),
+];
+
+// Registry holding one vote of the type, with a voter who cast a ballot where the type takes plain ballots
+pub(crate) fn canonical_registry(vote_type: VoteType) -> (Voting, VoteId) {
+    set_clock(1_700_000_000);
+    set_slot(250_000_000);
+
+    let (creator, voter) = (key(2), key(3));
+    let mut harness = TestHarness::with_voting(Voting::new(key(1)));
+    let config = VoteConfig { vote_type, ..VoteConfig::from_labels("Budget".to_string(), vec!["Yes".to_string(), "No".to_string()]).unwrap() };
+    let vote_id = harness.create_vote(config, &creator).unwrap();
+    harness.allow(vote_id, &creator, &voter).unwrap();
+    match vote_type {
+        VoteType::SingleChoice | VoteType::TimeWeighted { .. } | VoteType::Conviction { .. } => harness.cast(vote_id, &voter, 1).unwrap(),
+        VoteType::PointAllocation { .. } => harness.voting.vote_allocate_as(&voter, vote_id, &[(OptionIndex(0), 3), (OptionIndex(1), 7)]).unwrap(),
+        VoteType::TokenWeighted | VoteType::TokenBurn { .. } => {}
+    }
+    (harness.voting, vote_id)
+}
+
+#[test]
+fn test_registry_layout() {
+    let (voting, _) = canonical_registry(VoteType::SingleChoice);
+    let mut bytes = REGISTRY_DISCRIMINATOR.to_vec();
+    voting.serialize(&mut bytes).unwrap();
+    assert_layout_or_instruct("registry", &bytes);
+}
+
+// Registries written before the votes moved into slots decode to the same registry
+#[test]
+fn test_registry_v1_migration() {
+    let (voting, _) = canonical_registry(VoteType::SingleChoice);
+    let legacy = fixture("registry_v1");
+    assert!(legacy.starts_with(&crate::LEGACY_REGISTRY_DISCRIMINATOR));
+    assert_eq!(Voting::unpack(&legacy).unwrap(), voting);
+}
+
+#[test]
+fn test_vote_layouts() {
+    for (name, vote_type) in CANONICAL_VOTE_TYPES {
+        let (voting, vote_id) = canonical_registry(vote_type);
+        assert_layout_or_instruct(name, &borsh::to_vec(&voting.votes[&vote_id]).unwrap());
+    }
+}
+
+// Vote state accounts as `Vote::save` writes them, the view in front of the vote. The free space after the
+// state is left out
+#[test]
+fn test_vote_account_layouts() {
+    for (name, vote_type) in CANONICAL_VOTE_TYPES {
+        let (voting, vote_id) = canonical_registry(vote_type);
+        let vote = &voting.votes[&vote_id];
+        let mut data = vec![0; vote.view_len()];
+        vote.write_view(&mut data).unwrap();
+        vote.serialize(&mut data).unwrap();
+        assert_layout_or_instruct(&format!("{}_account", name), &data);
+    }
+}
+
+#[test]
+fn test_voter_info_layout() {
+    let (voting, vote_id) = canonical_registry(VoteType::SingleChoice);
+    assert_layout_or_instruct("voter_info", &borsh::to_vec(&voting.votes[&vote_id].allowed_voters[&key(3)]).unwrap());
+}
+
+// Fixture name of every instruction. The match is exhaustive, so a new variant does not compile until it has a
+// fixture in `test_instruction_layouts`
+fn instruction_fixture(instruction: &VoteInstruction) -> &'static str {
+    match instruction {
+        VoteInstruction::InitializeRegistry { .. } => "instruction_initialize_registry",
+        VoteInstruction::UpdatePolicy { .. } => "instruction_update_policy",
+        VoteInstruction::Pause => "instruction_pause",
+        VoteInstruction::Unpause => "instruction_unpause",
+        VoteInstruction::CastVote { .. } => "instruction_cast_vote",
+        VoteInstruction::CastVotes { .. } => "instruction_cast_votes",
+        VoteInstruction::CreateVote { .. } => "instruction_create_vote",
+        VoteInstruction::AddAllowedVoter { .. } => "instruction_add_allowed_voter",
+        VoteInstruction::DelegateVote { .. } => "instruction_delegate_vote",
+        VoteInstruction::CloseVote { .. } => "instruction_close_vote",
+        VoteInstruction::RepairVote { .. } => "instruction_repair_vote",
+        VoteInstruction::AcceptDelegation { .. } => "instruction_accept_delegation",
+        VoteInstruction::WithdrawDelegation { .. } => "instruction_withdraw_delegation",
+        VoteInstruction::RegisterVoter { .. } => "instruction_register_voter",
+        VoteInstruction::CastVoteSigned { .. } => "instruction_cast_vote_signed",
+        VoteInstruction::VoteExists { .. } => "instruction_vote_exists",
+        VoteInstruction::HasVoted { .. } => "instruction_has_voted",
+        VoteInstruction::OptionExists { .. } => "instruction_option_exists",
+        VoteInstruction::GetResults { .. } => "instruction_get_results",
+        VoteInstruction::GetOptions { .. } => "instruction_get_options",
+        VoteInstruction::IsVoterAllowed { .. } => "instruction_is_voter_allowed",
+        VoteInstruction::GetVoterStatus { .. } => "instruction_get_voter_status",
+        VoteInstruction::SimulateVote { .. } => "instruction_simulate_vote",
+        VoteInstruction::SimulateDelegate { .. } => "instruction_simulate_delegate",
+        VoteInstruction::ScrubVoter { .. } => "instruction_scrub_voter",
+        VoteInstruction::CertifyResults { .. } => "instruction_certify_results",
+        VoteInstruction::EnableSharding { .. } => "instruction_enable_sharding",
+        VoteInstruction::GetResultsBatch { .. } => "instruction_get_results_batch",
+        VoteInstruction::CastVoteWithComment { .. } => "instruction_cast_vote_with_comment",
+        VoteInstruction::EnableOutbox { .. } => "instruction_enable_outbox",
+        VoteInstruction::AckOutbox { .. } => "instruction_ack_outbox",
+        VoteInstruction::AttestNonParticipation { .. } => "instruction_attest_non_participation",
+        VoteInstruction::DelegateVoteScoped { .. } => "instruction_delegate_vote_scoped",
+    }
+}
+
+#[test]
+fn test_instruction_layouts() {
+    let (vote_id, voter, option_index) = (VoteId(7), key(3), OptionIndex(1));
+    let policy = CreationPolicy {
+        fee: Some(CreationFee { lamports: 5_000, treasury: key(4) }),
+        cooldown_slots: Some(10),
+        deposit: Some(CreationDeposit { lamports: 1_000_000, treasury: key(5) }),
+    };
+    let config = VoteConfig::from_labels("Budget".to_string(), vec!["Yes".to_string(), "No".to_string()]).unwrap();
+    let ballot = SignedBallot { vote_id, option_index, voter, nonce: 3, expiry: 1_700_003_600 };
+
+    let instructions = [
+        VoteInstruction::InitializeRegistry { admin: key(1) },
+        VoteInstruction::UpdatePolicy { policy },
+        VoteInstruction::Pause,
+        VoteInstruction::Unpause,
+        VoteInstruction::CastVote { vote_id, question_index: 1, option_index },
+        VoteInstruction::CastVotes { ballots: vec![(vote_id, option_index), (VoteId(8), OptionIndex(0))] },
+        VoteInstruction::CreateVote { config: Box::new(config) },
+        VoteInstruction::AddAllowedVoter { vote_id, voter },
+        VoteInstruction::DelegateVote { vote_id, delegate: key(6), expires_at: Some(1_700_086_400) },
+        VoteInstruction::CloseVote { vote_id },
+        VoteInstruction::RepairVote { vote_id },
+        VoteInstruction::AcceptDelegation { vote_id, delegator: key(6) },
+        VoteInstruction::WithdrawDelegation { vote_id, delegate: key(6) },
+        VoteInstruction::RegisterVoter { vote_id },
+        VoteInstruction::CastVoteSigned { ballot, signature: [0xab; 64] },
+        VoteInstruction::VoteExists { vote_id },
+        VoteInstruction::HasVoted { vote_id, voter },
+        VoteInstruction::OptionExists { vote_id, index: option_index },
+        VoteInstruction::GetResults { vote_id, question_index: Some(1) },
+        VoteInstruction::GetOptions { vote_id },
+        VoteInstruction::IsVoterAllowed { vote_id, voter },
+        VoteInstruction::GetVoterStatus { vote_id, voter },
+        VoteInstruction::SimulateVote { vote_id, question_index: 0, option_index },
+        VoteInstruction::SimulateDelegate { vote_id, delegate: key(6), expires_at: None },
+        VoteInstruction::ScrubVoter { vote_id, voter },
+        VoteInstruction::CertifyResults { vote_id },
+        VoteInstruction::EnableSharding { shard_capacity: 16 },
+        VoteInstruction::GetResultsBatch { vote_ids: vec![vote_id, VoteId(8)] },
+        VoteInstruction::CastVoteWithComment { vote_id, question_index: 0, option_index, comment: "Over budget".to_string() },
+        VoteInstruction::EnableOutbox { capacity: 64, policy: OutboxPolicy::EvictOldest },
+        VoteInstruction::AckOutbox { vote_ids: vec![vote_id] },
+        VoteInstruction::AttestNonParticipation { vote_id },
+        VoteInstruction::DelegateVoteScoped { vote_id, delegate: key(6), scope: DelegationScope::Question(1) },
+    ];
+    for instruction in &instructions {
+        assert_layout_or_instruct(instruction_fixture(instruction), &instruction.pack());
+    }
+}
+
+#[test]
+fn test_layout_diff_message() {
+    let path = fixture_path("instruction_pause");
+    let fixture = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(from_hex_lines(&fixture), VoteInstruction::Pause.pack());
+    assert_eq!(from_hex_lines(&to_hex_lines(&[0, 255, 16])), vec![0, 255, 16]);
+
+    if std::env::var_os(UPDATE_ENV).is_none() {
+        let message = *std::panic::catch_unwind(|| assert_layout_or_instruct("instruction_pause", &[3])).unwrap_err().downcast::<String>().unwrap();
+        assert!(message.starts_with("layout of `instruction_pause` changed: 1 bytes before, 1 now, first difference at byte 0"), "{}", message);
+        assert!(message.contains("- 02\n+ 03\n") && message.contains("cargo test layout_snapshots"), "{}", message);
+    }
+}
@@ -0,0 +1,252 @@
+// Double-entry bookkeeping of the votes of a vote. Every vote that changes hands moves between two endpoints,
+// a voter, the tally of an option or the void, through `CreditLedger::transfer`, the only code changing the
+// votes a voter holds. Totals per question let `Vote::check_invariants` reconcile the votes on record
+use std::collections::HashMap;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::program_error::ProgramError;
+use solana_program::pubkey::Pubkey;
+
+use crate::{OptionIndex, VoteError, VoterInfo};
+
+// Number of movements each vote keeps for `get_ledger`, older ones are evicted. The totals cover all of them
+pub const MAX_LEDGER_ENTRIES: usize = 64;
+
+// Where votes come from or go to. Granted votes come out of the void and forfeited ones go back into it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub enum CreditEndpoint {
+    Voter(Pubkey),
+    Tally(OptionIndex),
+    Void,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub enum CreditCause {
+    Grant,              // The creator, a voucher, a group or a registration gave the votes
+    Ballot,             // Spent on a ballot or an allocation
+    Retraction,         // A retracted ballot gave the vote back
+    BallotChanged,      // A ballot moved to another option
+    Struck,             // The creator struck the ballots of the voter
+    Delegation,         // Passed on to a delegate
+    DelegationReturned, // The delegation expired or the delegate left, the unspent votes went back
+    DelegationHeld,     // Promised in a pending delegation
+    DelegationReleased, // The pending delegation was answered or lapsed, the held votes went back
+    Forfeit,            // The voter left, was struck, got fewer votes or their delegator took the votes back
+    Withdrawn,          // The creator withdrew the option of the ballot and refunded it
+}
+
+// Movement of votes of a question, numbered from 1 in the order they happened
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct CreditEntry {
+    pub seq: u64,
+    pub question_index: u32,
+    pub from: CreditEndpoint,
+    pub to: CreditEndpoint,
+    pub amount: u32,
+    pub cause: CreditCause,
+}
+
+// Balances of a question over every movement, evicted ones included
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct CreditTotals {
+    pub granted: u64,
+    pub tallied: u64,   // Spent on ballots that are still counted
+    pub held: u64,      // Promised in pending delegations
+    pub forfeited: u64,
+}
+
+impl CreditTotals {
+    // Votes the voters should hold between them
+    pub fn outstanding(&self) -> Option<u64> {
+        self.granted.checked_sub(self.tallied)?.checked_sub(self.held)?.checked_sub(self.forfeited)
+    }
+}
+
+// Movements of a vote after a given sequence number, with the totals of every question
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct LedgerPage {
+    pub entries: Vec<CreditEntry>, // Oldest first
+    pub latest_seq: u64,           // 0 while no votes moved
+    pub overflow: bool,            // Some of the requested movements were evicted
+    pub totals: Vec<CreditTotals>, // For each question
+}
+
+#[derive(Debug, Clone, PartialEq, BorshSerialize, BorshDeserialize)]
+pub(crate) struct CreditLedger {
+    entries: Vec<CreditEntry>, // Latest `MAX_LEDGER_ENTRIES` movements, oldest first
+    latest_seq: u64,
+    totals: Vec<CreditTotals>, // For each question
+}
+
+impl CreditLedger {
+    pub(crate) fn new(questions_count: usize) -> Self {
+        Self { entries: Vec::new(), latest_seq: 0, totals: vec![CreditTotals::default(); questions_count] }
+    }
+
+    // Full ledger with the longest entries, for sizing the state account
+    pub(crate) fn longest(questions_count: usize) -> Self {
+        let entry = CreditEntry { seq: 0, question_index: 0, from: CreditEndpoint::Voter(Pubkey::default()), to: CreditEndpoint::Voter(Pubkey::default()), amount: 0, cause: CreditCause::Grant };
+        Self { entries: vec![entry; MAX_LEDGER_ENTRIES], ..Self::new(questions_count) }
+    }
+
+    pub(crate) fn totals(&self) -> &[CreditTotals] {
+        &self.totals
+    }
+
+    // Movements after `seq`, flagged as overflowing when the first of them was evicted
+    pub(crate) fn since(&self, seq: u64) -> LedgerPage {
+        let entries = self.entries.iter().filter(|entry| entry.seq > seq).copied().collect();
+        let overflow = self.entries.first().is_some_and(|entry| entry.seq > seq.saturating_add(1));
+
+        LedgerPage { entries, latest_seq: self.latest_seq, overflow, totals: self.totals.clone() }
+    }
+
+    // Point the kept movements of `voter` at `replacement`, for scrubbed voters
+    pub(crate) fn rename(&mut self, voter: &Pubkey, replacement: Pubkey) {
+        for entry in &mut self.entries {
+            for endpoint in [&mut entry.from, &mut entry.to] {
+                if *endpoint == CreditEndpoint::Voter(*voter) {
+                    *endpoint = CreditEndpoint::Voter(replacement);
+                }
+            }
+        }
+    }
+
+    // Move `amount` votes of a question between two endpoints. Every balance is computed with checked math before
+    // anything changes, so a voter spending votes they do not hold fails here and leaves the state as it was.
+    // Votes only come out of the void as grants or released holds, and tallies only trade with voters
+    pub(crate) fn transfer(
+        &mut self,
+        voters: &mut HashMap<Pubkey, VoterInfo>,
+        question_index: usize,
+        from: CreditEndpoint,
+        to: CreditEndpoint,
+        amount: u32,
+        cause: CreditCause,
+    ) -> Result<(), ProgramError> {
+        if amount == 0 {
+            return Ok(());
+        }
+        if from == to || matches!((from, to), (CreditEndpoint::Void, CreditEndpoint::Tally(_)) | (CreditEndpoint::Tally(_), CreditEndpoint::Void)) {
+            return Err(ProgramError::InvalidArgument); // Return error if the movement makes no sense
+        }
+
+        let mut totals = self.totals.get(question_index).copied().ok_or(ProgramError::InvalidArgument)?; // Return error if the question does not exist
+        let added = u64::from(amount);
+        let mut balances: Vec<(Pubkey, u32, u32)> = Vec::with_capacity(2); // New votes left and spent votes of the voters involved
+
+        match from {
+            CreditEndpoint::Voter(voter) => {
+                let (votes_left, spent) = balance(voters, &voter, question_index)?;
+                let votes_left = votes_left.checked_sub(amount).ok_or(VoteError::InsufficientVotes)?;
+                let spent = match to {
+                    CreditEndpoint::Tally(_) => spent.checked_add(amount).ok_or(ProgramError::ArithmeticOverflow)?,
+                    _ => spent,
+                };
+                balances.push((voter, votes_left, spent));
+            }
+            CreditEndpoint::Tally(_) => totals.tallied = totals.tallied.checked_sub(added).ok_or(VoteError::InsufficientVotes)?,
+            CreditEndpoint::Void if cause == CreditCause::Grant => totals.granted = totals.granted.checked_add(added).ok_or(ProgramError::ArithmeticOverflow)?,
+            CreditEndpoint::Void => totals.held = totals.held.checked_sub(added).ok_or(VoteError::InsufficientVotes)?,
+        }
+
+        match to {
+            CreditEndpoint::Voter(voter) => {
+                let (votes_left, spent) = balance(voters, &voter, question_index)?;
+                let votes_left = votes_left.checked_add(amount).ok_or(ProgramError::ArithmeticOverflow)?;
+                let spent = match from {
+                    CreditEndpoint::Tally(_) => spent.checked_sub(amount).ok_or(VoteError::InsufficientVotes)?,
+                    _ => spent,
+                };
+                balances.push((voter, votes_left, spent));
+            }
+            CreditEndpoint::Tally(_) => totals.tallied = totals.tallied.checked_add(added).ok_or(ProgramError::ArithmeticOverflow)?,
+            CreditEndpoint::Void if cause == CreditCause::DelegationHeld => totals.held = totals.held.checked_add(added).ok_or(ProgramError::ArithmeticOverflow)?,
+            CreditEndpoint::Void => totals.forfeited = totals.forfeited.checked_add(added).ok_or(ProgramError::ArithmeticOverflow)?,
+        }
+
+        let seq = self.latest_seq.checked_add(1).ok_or(ProgramError::ArithmeticOverflow)?;
+
+        // Nothing can fail from here on
+        for (voter, votes_left, spent) in balances {
+            if let Some(voter_info) = voters.get_mut(&voter) {
+                voter_info.votes_left[question_index] = votes_left;
+                voter_info.spent[question_index] = spent;
+            }
+        }
+        // Ballots and delegations mark the voter for good, see `Voting::attest_non_participation`
+        if let (CreditEndpoint::Voter(voter), CreditCause::Ballot | CreditCause::Delegation | CreditCause::DelegationHeld) = (from, cause) {
+            if let Some(voter_info) = voters.get_mut(&voter) {
+                voter_info.participated = true;
+            }
+        }
+        self.totals[question_index] = totals;
+        self.latest_seq = seq;
+
+        if self.entries.len() >= MAX_LEDGER_ENTRIES {
+            self.entries.remove(0);
+        }
+        self.entries.push(CreditEntry { seq, question_index: question_index as u32, from, to, amount, cause });
+        Ok(())
+    }
+}
+
+// Votes left and spent votes of a voter on a question
+fn balance(voters: &HashMap<Pubkey, VoterInfo>, voter: &Pubkey, question_index: usize) -> Result<(u32, u32), ProgramError> {
+    let voter_info = voters.get(voter).ok_or(ProgramError::InvalidArgument)?; // Return error if the voter is not found
+    match (voter_info.votes_left.get(question_index), voter_info.spent.get(question_index)) {
+        (Some(votes_left), Some(spent)) => Ok((*votes_left, *spent)),
+        _ => Err(VoteError::CorruptState.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transfer_overspend() {
+        let voter = Pubkey::new_unique();
+        let mut voters = HashMap::from([(voter, VoterInfo::new(vec![0]))]);
+        let mut ledger = CreditLedger::new(1);
+
+        ledger.transfer(&mut voters, 0, CreditEndpoint::Void, CreditEndpoint::Voter(voter), 1, CreditCause::Grant).unwrap();
+        ledger.transfer(&mut voters, 0, CreditEndpoint::Voter(voter), CreditEndpoint::Tally(OptionIndex(0)), 1, CreditCause::Ballot).unwrap();
+        assert_eq!((voters[&voter].votes_left[0], voters[&voter].spent[0]), (0, 1));
+
+        // The second ballot finds no vote, nothing changes
+        let before = (voters.clone(), ledger.clone());
+        assert_eq!(
+            ledger.transfer(&mut voters, 0, CreditEndpoint::Voter(voter), CreditEndpoint::Tally(OptionIndex(1)), 1, CreditCause::Ballot),
+            Err(VoteError::InsufficientVotes.into())
+        );
+        assert_eq!((voters.clone(), ledger.clone()), before);
+
+        // Neither can a tally give back more than it counts, nor votes come from the void unless granted or held
+        let retracted = ledger.transfer(&mut voters, 0, CreditEndpoint::Tally(OptionIndex(0)), CreditEndpoint::Voter(voter), 2, CreditCause::Retraction);
+        assert_eq!(retracted, Err(VoteError::InsufficientVotes.into()));
+        let released = ledger.transfer(&mut voters, 0, CreditEndpoint::Void, CreditEndpoint::Voter(voter), 1, CreditCause::DelegationReleased);
+        assert_eq!(released, Err(VoteError::InsufficientVotes.into()));
+        assert_eq!(ledger.transfer(&mut voters, 0, CreditEndpoint::Tally(OptionIndex(0)), CreditEndpoint::Void, 1, CreditCause::Forfeit), Err(ProgramError::InvalidArgument));
+
+        assert_eq!(ledger.totals()[0], CreditTotals { granted: 1, tallied: 1, held: 0, forfeited: 0 });
+        assert_eq!(ledger.totals()[0].outstanding(), Some(0));
+        assert_eq!(ledger.since(0).entries.len(), 2);
+    }
+
+    #[test]
+    fn test_ledger_eviction() {
+        let voter = Pubkey::new_unique();
+        let mut voters = HashMap::from([(voter, VoterInfo::new(vec![0]))]);
+        let mut ledger = CreditLedger::new(1);
+
+        for _ in 0..MAX_LEDGER_ENTRIES + 2 {
+            ledger.transfer(&mut voters, 0, CreditEndpoint::Void, CreditEndpoint::Voter(voter), 1, CreditCause::Grant).unwrap();
+        }
+
+        let page = ledger.since(0);
+        assert_eq!((page.entries.len(), page.latest_seq, page.overflow), (MAX_LEDGER_ENTRIES, MAX_LEDGER_ENTRIES as u64 + 2, true));
+        assert!(!ledger.since(2).overflow);
+        assert_eq!(page.totals[0].granted, MAX_LEDGER_ENTRIES as u64 + 2);
+    }
+}
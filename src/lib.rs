@@ -1,797 +1,10839 @@
+use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{program_error::ProgramError, pubkey::Pubkey};
 use solana_program::account_info::AccountInfo;
-use std::collections::HashMap;
-
-#[derive(Debug, Clone)]
+use solana_program::clock::{Clock, Slot, DEFAULT_MS_PER_SLOT};
+use solana_program::ed25519_program;
+use solana_program::hash::{hash, hashv, Hash};
+use solana_program::program::{invoke, invoke_signed};
+use solana_program::msg;
+use solana_program::rent::Rent;
+use solana_program::{system_instruction, system_program};
+use solana_program::sysvar::{instructions as sysvar_instructions, Sysvar};
+#[allow(deprecated)]
+use solana_program::sysvar::recent_blockhashes;
+use solana_program::sysvar::slot_hashes;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use unicode_normalization::UnicodeNormalization;
+
+use ledger::CreditLedger;
+
+#[cfg(any(test, feature = "client"))]
+pub mod cli;
+mod bounded;
+#[cfg(any(test, feature = "client"))]
+mod bundle;
+#[cfg(any(test, feature = "client"))]
+pub mod decode;
+mod eligibility;
+mod entrypoint;
+mod error;
+mod event;
+mod ids;
+#[cfg(test)]
+mod layout_snapshots;
+mod ledger;
+mod outbox;
+#[cfg(any(test, feature = "client"))]
+mod replay;
+mod shard;
+mod slots;
+#[cfg(any(test, feature = "client"))]
+mod shared;
+pub mod tally;
+pub mod instruction;
+pub mod processor;
+pub mod responses;
+pub mod sampling;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
+mod view;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use bounded::{BoundedString, StringTooLong};
+pub use eligibility::{Eligibility, EligibilityCheck, MinAccountDataLen, MinStake};
+pub use error::{log_error, VoteError};
+pub use event::{EventEnvelope, VoteEvent, EVENT_VERSION};
+pub use ids::{LangCode, OptionIndex, VoteId};
+pub use ledger::{CreditCause, CreditEndpoint, CreditEntry, CreditTotals, LedgerPage, MAX_LEDGER_ENTRIES};
+pub use outbox::{Outbox, OutboxEntry, OutboxOutcome, OutboxPolicy, MAX_OUTBOX_CAPACITY, OUTBOX_DISCRIMINATOR};
+pub use shard::{RegistryShard, ShardEntry, MAX_SHARD_CAPACITY, SHARD_DISCRIMINATOR};
+pub use slots::VoteSlot;
+pub use responses::{OptionsResponse, QuestionTally, ResultsBatchResponse, ResultsResponse, SimulationResponse, VoterStatusResponse};
+pub use view::{OptionView, TallyView, ViewStatus, ViewVoteType, VoteView, VIEW_DISCRIMINATOR, VIEW_HEADER_LEN, VIEW_OPTION_LEN, VIEW_TALLY_LEN, VIEW_VERSION};
+#[cfg(any(test, feature = "client", feature = "wasm"))]
+pub use event::LOG_PREFIX;
+#[cfg(any(test, feature = "client"))]
+pub use error::error_codes_json;
+#[cfg(any(test, feature = "client"))]
+pub use bundle::{VoteBundle, BUNDLE_VERSION};
+#[cfg(any(test, feature = "client"))]
+pub use replay::{compare_with_account, replay_events, FieldDiff};
+#[cfg(any(test, feature = "client"))]
+pub use shared::SharedVoting;
+
+// Prefix of the registry account data, marks the account as initialized. The layout behind it is in `slots`
+pub const REGISTRY_DISCRIMINATOR: [u8; 8] = *b"svregis2";
+
+// Prefix of registry accounts not written since the votes moved into slots of their own
+pub const LEGACY_REGISTRY_DISCRIMINATOR: [u8; 8] = *b"svregist";
+
+#[derive(Debug, Clone, PartialEq, BorshSerialize, BorshDeserialize)]
 struct VoterInfo {
-    pub votes_left: u32,         // Number of remaining votes
+    pub votes_left: Vec<u32>,    // Number of remaining votes for each question, only changed by the ledger
+    pub spent: Vec<u32>,         // Votes of each question spent on ballots that still count
     pub delegate: Option<Pubkey>, // Delegate if any
+    pub ballots: Vec<Ballot>,     // Ballots cast by the voter, including the ones cast with delegated votes
+    pub delegations: Vec<Delegation>, // Votes the voter handed to delegates
+    pub banned: bool,             // Struck by the creator, the voter cannot vote or be added again
+    pub added_at: i64,            // Unix timestamp of the allowlist entry, only recorded for votes with a per-voter window
+    pub weight: u64,              // Own weight of the voter in token-weighted votes
+    pub received_weight: u64,     // Weight delegated to the voter, counted on top of their own
+    pub last_voted_slot: Option<Slot>, // Slot of the latest ballot, only recorded while the voter has more votes on its question
+    pub held_weight: u64,         // Own weight promised in pending delegations
+    pub via_delegation_only: bool, // Entry created by a delegation to a voter the creator never added
+    pub last_ballot_nonce: Option<u64>, // Nonce of the latest relayed ballot, the next one needs a higher nonce
+    pub valid_until: Option<i64>, // Last Unix timestamp the entry votes and delegates at, for voters with a limited term
+    pub participated: bool,       // Cast a ballot or delegated at some point, stays set when the ballots or delegations are undone
 }
 
-#[derive(Debug)]
-pub struct Vote {
-    id: u32,
-    title: String,
-    options: Vec<String>,
-    votes: HashMap<String, u32>,
-    creator: Pubkey,
-    allowed_voters: HashMap<Pubkey, VoterInfo>, // Stores information about allowed voters
-    is_close_vote_results: bool,
-    is_vote_open: bool
-}
+impl VoterInfo {
+    fn new(votes_left: Vec<u32>) -> Self {
+        Self {
+            spent: vec![0; votes_left.len()],
+            votes_left,
+            delegate: None,
+            ballots: Vec::new(),
+            delegations: Vec::new(),
+            banned: false,
+            added_at: 0,
+            weight: 1,
+            received_weight: 0,
+            last_voted_slot: None,
+            held_weight: 0,
+            via_delegation_only: false,
+            last_ballot_nonce: None,
+            valid_until: None,
+            participated: false,
+        }
+    }
 
-impl Vote {
-    // Method to get voting options
-    fn get_options(&self) -> &Vec<String> {
-        &self.options
+    // Own weight not handed or promised to delegates
+    fn available_weight(&self) -> u64 {
+        let delegated: u64 = self.delegations.iter().map(|delegation| delegation.weight).sum();
+        self.weight.saturating_sub(delegated).saturating_sub(self.held_weight)
     }
+}
 
-    fn add_allowed_voter(&mut self, voter: Pubkey, caller: &Pubkey) -> Result<(), ProgramError>{
-        if *caller != self.creator {
-            return Err(ProgramError::InvalidArgument); // Return error if not the creator
+// What a voter can still do in a vote
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct VoterStatus {
+    pub votes_left: Vec<u32>, // For each question
+    pub delegate: Option<Pubkey>,
+    pub banned: bool,
+    pub deadline: Option<i64>, // Last Unix timestamp the voter may cast a ballot at, for votes with a per-voter window
+    pub weight: u64,
+    pub received_weight: u64,
+    pub received_delegations: u64, // Delegated power counted against `VoteConfig::max_received_delegations`
+    pub via_delegation_only: bool, // The voter only holds delegated votes, the creator never added them
+    pub valid_until: Option<i64>, // Expiry of the allowlist entry, if any
+}
+
+// A recorded ballot, kept so it can be struck from the tallies later and replayed by auditors
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct Ballot {
+    pub question_index: u32,
+    pub option_index: OptionIndex,
+    pub weight: u64, // Weight in basis points added to the weighted tally
+    pub cast_slot: Option<Slot>, // Slot the ballot was cast or last changed at, only recorded in conviction votes
+    pub in_grace: bool, // Cast or last changed after the deadline, inside the grace period
+    pub comment: Option<Comment>, // Justification of the voter, only in votes requiring one
+}
+
+#[derive(Debug, Clone, PartialEq, BorshSerialize, BorshDeserialize)]
+struct Delegation {
+    delegate: Pubkey,
+    votes: Vec<u32>, // Moved votes of each question the delegate has not spent yet
+    expires_at: Option<i64>, // Unix timestamp from which the unspent votes can be reclaimed by the delegator
+    weight: u64, // Weight handed over in token-weighted votes, where no votes are moved
+}
+
+// Questions whose votes a delegation moves. Votes are credited per question, so the delegate can only spend
+// the moved votes on their own question
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub enum DelegationScope {
+    AllQuestions,
+    Question(u32),
+}
+
+impl DelegationScope {
+    fn covers(&self, question_index: usize) -> bool {
+        match *self {
+            DelegationScope::AllQuestions => true,
+            DelegationScope::Question(index) => index as usize == question_index,
         }
+    }
+}
 
-        // Check if the voting is closed
-        if !self.is_vote_open {
-            return Err(ProgramError::InvalidArgument); // Return error if voting is closed
+// Delegation waiting for the delegate to accept it. The votes or weight stay held from the delegator until then
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct PendingDelegation {
+    pub delegator: Pubkey,
+    pub delegate: Pubkey,
+    pub votes: Vec<u32>, // Held votes of each question
+    pub weight: u64,     // Held weight in token-weighted votes, where no votes are held
+    pub expires_at: Option<i64>,
+    pub scope: DelegationScope, // Applied again when the delegate accepts
+}
+
+impl Delegation {
+    fn is_expired(&self, now: i64) -> bool {
+        self.expires_at.is_some_and(|expires_at| now >= expires_at)
+    }
+}
+
+// Who is allowed to read a restricted part of a vote
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub enum Visibility {
+    Public,        // Anyone can read
+    AllowlistOnly, // Only the creator and allowed voters can read
+    CreatorOnly,   // Only the creator of the vote can read
+}
+
+// Weight of a full ballot in basis points
+pub const FULL_WEIGHT_BPS: u64 = 10_000;
+
+// Longest cancellation reason in bytes, so a cancelled vote still fits in its account
+pub const MAX_REASON_LEN: usize = 200;
+
+// Longest ballot comment in bytes
+pub const MAX_COMMENT_LEN: usize = 280;
+
+// Longest vote and question title and option label in bytes
+pub const MAX_TITLE_LEN: usize = 128;
+pub const MAX_LABEL_LEN: usize = 64;
+
+// Most option results a single `get_results_page` call returns
+pub const MAX_RESULTS_PAGE: u32 = 32;
+
+// Most options, across every question, `get_full_results` returns in one call. Larger votes are read by page
+pub const MAX_OPTIONS_FULL_RESULTS: usize = 32;
+
+// Most voters a single `import_allowlist` call adds, so the instruction stays within the compute budget
+pub const MAX_BATCH_VOTERS: usize = 64;
+
+// Most ballots a single `vote_many` call casts
+pub const MAX_BATCH_BALLOTS: usize = 8;
+
+// Most votes a single `get_results_batch` call reads, an answer for that many fits in the return data
+pub const MAX_RESULTS_BATCH: usize = 32;
+
+// How ballots are counted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub enum VoteType {
+    SingleChoice, // Every ballot carries a full weight
+    TimeWeighted {
+        start: i64,            // Unix timestamp at which ballots carry `start_weight_bps`
+        end: i64,              // Unix timestamp at which ballots carry `end_weight_bps`
+        start_weight_bps: u16,
+        end_weight_bps: u16,
+    }, // Ballot weight is linearly interpolated between start and end by the cast time
+    TokenWeighted, // Ballots carry the recorded weight of the voter plus the weight delegated to them
+    PointAllocation {
+        points_per_voter: u32, // Budget of every vote of a voter
+        allow_partial: bool,   // Allocations may leave points unspent, otherwise they must use the whole budget
+    }, // Voters spread points over options with `vote_allocate`, weighted tallies hold the points
+    Conviction {
+        period_slots: u64,   // Slots a ballot has to stay unchanged for its weight to double
+        max_multiplier: u32, // Cap on the growth, e.g. 8 for three doublings
+    }, // Ballots weigh more the longer they stay, counted when the results are read. Changing a ballot starts it over
+    TokenBurn {
+        mint: Pubkey,     // Token the ballots burn
+        burn_amount: u64, // Base units of the token every ballot burns
+    }, // Every ballot cast with `CastVote` burns tokens of the voter, who approves the vote address to burn them
+}
+
+impl VoteType {
+    // Weight in basis points of a ballot cast at `now`, rounded down
+    fn weight_at(&self, now: i64) -> u64 {
+        match *self {
+            VoteType::SingleChoice | VoteType::TokenWeighted => FULL_WEIGHT_BPS, // Token weights come from the voters
+            VoteType::TokenBurn { .. } => FULL_WEIGHT_BPS, // The burn is the cost of the ballot, not its weight
+            VoteType::PointAllocation { .. } => FULL_WEIGHT_BPS, // Points come from the allocations
+            VoteType::Conviction { .. } => FULL_WEIGHT_BPS, // Conviction is applied when the results are read
+            VoteType::TimeWeighted { start, end, start_weight_bps, end_weight_bps } => {
+                if now <= start {
+                    return start_weight_bps as u64;
+                }
+                if now >= end {
+                    return end_weight_bps as u64;
+                }
+
+                // Both parts are non-negative, so the division rounds down
+                let elapsed = (now - start) as u128;
+                let remaining = (end - now) as u128;
+                let weighted = start_weight_bps as u128 * remaining + end_weight_bps as u128 * elapsed;
+                (weighted / (end - start) as u128) as u64
+            }
         }
+    }
 
-        let new_voter = VoterInfo {
-            votes_left: 1,            // Initialize with 1 vote
-            delegate: None,           // Empty delegate
+    // Factor the weight of a ballot cast at `cast_slot` has grown by at `slot`, 1 outside of conviction votes
+    fn conviction_multiplier(&self, cast_slot: Option<Slot>, slot: Slot) -> u64 {
+        let (VoteType::Conviction { period_slots, max_multiplier }, Some(cast_slot)) = (*self, cast_slot) else {
+            return 1;
         };
 
-        self.allowed_voters.insert(voter, new_voter); // Initialize new voter
+        let periods = slot.saturating_sub(cast_slot) / period_slots.max(1);
+        let multiplier = 1u64 << periods.min(63);
+        multiplier.min(u64::from(max_multiplier.max(1)))
+    }
+}
+
+pub const MAX_DESCRIPTION_LEN: usize = 256; // In bytes
+
+pub type Title = BoundedString<MAX_TITLE_LEN>;
+pub type Label = BoundedString<MAX_LABEL_LEN>;
+pub type Description = BoundedString<MAX_DESCRIPTION_LEN>;
+pub type Reason = BoundedString<MAX_REASON_LEN>;
+pub type Comment = BoundedString<MAX_COMMENT_LEN>;
+
+// Longest write-in option label in bytes, and the most options a question can grow to through write-ins
+pub const MAX_WRITE_IN_LEN: usize = MAX_LABEL_LEN;
+pub const MAX_WRITE_IN_OPTIONS: usize = 64;
+
+// An option of a question, ballots refer to it by its position in the question
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct VoteOption {
+    pub label: Label, // Unique within the question, tallies are keyed by it
+    pub description: Option<Description>,
+    pub order: u16, // Display position, unique within the question but not necessarily contiguous
+    pub abstain: bool, // Ballots for the option count toward the quorum but not toward the shares, it cannot win
+    pub proposed_by: Option<Pubkey>, // Voter who wrote the option in, `None` for options of the creator
+    pub content_hash: Option<[u8; 32]>, // Hash of the image or icon the front end shows for the option
+    pub withdrawn: bool, // Taken out of the vote by the creator, it takes no more ballots and cannot win
+}
 
-        Ok(())
+// Form labels are compared in: trimmed and in Unicode normalization form C, so text typed with composed
+// or decomposed characters matches. Labels are stored as the creator or the voter wrote them
+fn normalized_label(label: &str) -> String {
+    label.trim().nfc().collect()
+}
+
+impl VoteOption {
+    // Leading and trailing whitespace is dropped from the label
+    pub fn new(label: String, order: u16) -> Result<Self, StringTooLong> {
+        Ok(Self {
+            label: Label::new("label", label.trim())?,
+            description: None,
+            order,
+            abstain: false,
+            proposed_by: None,
+            content_hash: None,
+            withdrawn: false,
+        })
     }
 
-    fn remove_allowed_voter(&mut self, voter: &Pubkey, caller: &Pubkey) -> Result<(), ProgramError> {
-        // Check if the calling address is the creator of the vote
-        if *caller != self.creator {
-            return Err(ProgramError::InvalidArgument); // Return error if not the creator
+    pub fn abstention(label: String, order: u16) -> Result<Self, StringTooLong> {
+        Ok(Self { abstain: true, ..Self::new(label, order)? })
+    }
+
+    // Options without descriptions, displayed in the given order
+    pub fn from_labels(labels: Vec<String>) -> Result<Vec<Self>, StringTooLong> {
+        labels.into_iter().enumerate().map(|(index, label)| Self::new(label, index.min(u16::MAX as usize) as u16)).collect()
+    }
+}
+
+// Parameters of a single question of a vote
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct QuestionConfig {
+    pub title: Title,
+    pub options: Vec<VoteOption>,
+}
+
+impl QuestionConfig {
+    // Check that labels and display orders are unique
+    fn validate(&self) -> Result<(), ProgramError> {
+        let mut labels = HashSet::new();
+        let mut orders = HashSet::new();
+
+        for option in &self.options {
+            if !labels.insert(normalized_label(&option.label)) || !orders.insert(option.order) {
+                return Err(ProgramError::InvalidArgument); // Return error if a label or an order is repeated
+            }
         }
 
-        // Check if the voting is closed
-        if !self.is_vote_open {
-            return Err(ProgramError::InvalidArgument); // Return error if voting is closed
+        Ok(())
+    }
+}
+
+// How `get_winner` settles a tie for the lead
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub enum TieBreak {
+    None,           // The outcome stays a tie
+    FirstListed,    // The tied option listed first wins
+    CreatorDecides, // The creator picks one of the tied options with `resolve_tie` after closing
+    Blockhash,      // Picked from the latest blockhash when the vote closes
+}
+
+// Syntax every option label of a vote follows, write-ins included, so clients can parse the labels
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub enum OptionFormat {
+    #[default]
+    FreeText,
+    IsoDate,      // Calendar date as YYYY-MM-DD, e.g. the day of a meeting
+    Integer,      // Decimal i64
+    PubkeyBase58, // Account address
+}
+
+impl OptionFormat {
+    pub fn validate(&self, label: &str) -> Result<(), ProgramError> {
+        let valid = match self {
+            OptionFormat::FreeText => true,
+            OptionFormat::IsoDate => is_iso_date(label),
+            OptionFormat::Integer => label.parse::<i64>().is_ok(),
+            OptionFormat::PubkeyBase58 => label.parse::<Pubkey>().is_ok(),
+        };
+        if !valid {
+            msg!("Option {:?} is not in the {:?} format", label, self);
+            return Err(ProgramError::InvalidArgument); // Return error if the label does not follow the format
         }
+        Ok(())
+    }
+}
 
-        // Remove the voter from the list if they exist
-        if self.allowed_voters.remove(voter).is_some() {
-            Ok(())
-        } else {
-            Err(ProgramError::InvalidArgument) // Return error if the voter is not found
+// YYYY-MM-DD naming a day of the Gregorian calendar
+fn is_iso_date(label: &str) -> bool {
+    let bytes = label.as_bytes();
+    if bytes.len() != 10 || bytes[4] != b'-' || bytes[7] != b'-' {
+        return false;
+    }
+
+    let number = |range: std::ops::Range<usize>| {
+        let digits = &bytes[range];
+        digits.iter().all(u8::is_ascii_digit).then(|| digits.iter().fold(0, |number, digit| number * 10 + u32::from(digit - b'0')))
+    };
+    let (Some(year), Some(month), Some(day)) = (number(0..4), number(5..7), number(8..10)) else {
+        return false;
+    };
+
+    let leap_year = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap_year => 29,
+        2 => 28,
+        _ => return false,
+    };
+    (1..=days).contains(&day)
+}
+
+// Who votes can be delegated to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub enum DelegationPolicy {
+    #[default]
+    AllowlistOnly, // Only to voters on the allowlist
+    AnyRecipient,  // To anyone, recipients missing from the allowlist get an entry marked `via_delegation_only`
+}
+
+// Voters of the source vote `import_allowlist` copies, voters with a ballot on record count as participants
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub enum ImportFilter {
+    AllEligible,      // Every voter on the allowlist
+    ParticipantsOnly, // Voters who cast a ballot
+    NonParticipants,  // Voters who never did
+}
+
+impl ImportFilter {
+    fn matches(self, participated: bool) -> bool {
+        match self {
+            ImportFilter::AllEligible => true,
+            ImportFilter::ParticipantsOnly => participated,
+            ImportFilter::NonParticipants => !participated,
         }
     }
+}
 
-    fn is_voter_allowed(&self, voter: &Pubkey) -> bool {
-        self.allowed_voters.contains_key(voter)
+// Changes bringing an allowlist in line with a desired set of voters and their votes per question, see
+// `diff_allowlist`. Every list is sorted by voter, so the same allowlist and desired set give the same diff
+#[derive(Debug, Clone, Default, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct AllowlistDiff {
+    pub to_add: Vec<(Pubkey, u32)>,
+    pub to_remove: Vec<Pubkey>,
+    pub to_update: Vec<(Pubkey, u32)>, // New votes per question of voters who have not used any of theirs
+}
+
+impl AllowlistDiff {
+    pub fn len(&self) -> usize {
+        self.to_add.len() + self.to_remove.len() + self.to_update.len()
     }
 
-    fn vote(&mut self, voter: &Pubkey, option_index: usize) -> Result<(), ProgramError> {
-        // Check if the voter is in the allowed list
-        if !self.is_voter_allowed(voter) {
-            return Err(ProgramError::InvalidArgument); // Return error if voter is not allowed
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // The diff cut into diffs of at most `size` changes, for diffs over `MAX_BATCH_VOTERS`. Removals come
+    // first so they free room on the allowlist for the additions
+    pub fn pages(&self, size: usize) -> Vec<AllowlistDiff> {
+        let size = size.max(1);
+        let mut pages = vec![AllowlistDiff::default()];
+
+        for voter in &self.to_remove {
+            Self::page_with_room(&mut pages, size).to_remove.push(*voter);
+        }
+        for entry in &self.to_update {
+            Self::page_with_room(&mut pages, size).to_update.push(*entry);
+        }
+        for entry in &self.to_add {
+            Self::page_with_room(&mut pages, size).to_add.push(*entry);
         }
 
-        // Check if the voting is closed
-        if !self.is_vote_open {
-            return Err(ProgramError::InvalidArgument); // Return error if voting is closed
+        pages.retain(|page| !page.is_empty());
+        pages
+    }
+
+    fn page_with_room(pages: &mut Vec<AllowlistDiff>, size: usize) -> &mut AllowlistDiff {
+        if pages.last().is_some_and(|page| page.len() >= size) {
+            pages.push(AllowlistDiff::default());
         }
+        pages.last_mut().unwrap() // Safely unwrap as the list starts with a page
+    }
+}
 
-        if let Some(voter_info) = self.allowed_voters.get_mut(voter) {
-            // Check if the voter still has votes left
-            if voter_info.votes_left <= 0 {
-                return Err(ProgramError::InvalidArgument); // Return error if the voter has exhausted their votes
-            }
+// Parameters used to create a new vote
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct VoteConfig {
+    pub title: Title,
+    pub description: Option<Description>,
+    pub questions: Vec<QuestionConfig>, // Questions sharing the allowlist and the visibility settings
+    pub translations: Vec<(LangCode, Vec<Label>)>, // Option labels of the first question in other languages, in option order
+    pub option_format: OptionFormat, // Syntax of the option labels of every question
+    pub vote_type: VoteType,
+    pub results_visibility: Visibility,   // Who may read the tallies
+    pub allowlist_visibility: Visibility, // Who may read the list of allowed voters
+    pub projection_visibility: Visibility, // Who may read `get_projection`, whatever the results visibility
+    pub min_duration: Option<i64>, // Seconds after creation before the creator may close the vote
+    pub per_option_cap: Option<u64>, // Maximum number of ballots a single option can receive
+    pub per_option_voter_cap: Option<u32>, // Maximum number of distinct voters holding a ballot for a single option
+    pub allowlist_group: Option<u32>, // Voter group copied into the allowlist at creation, later group edits do not apply
+    pub winning_threshold_bps: Option<u16>, // Share of the ballots the leading option needs to win, e.g. 6667 for two thirds
+    pub quorum: Option<u32>, // Ballots a question needs, abstentions included, to have an outcome
+    pub seats: u8, // Options `get_elected` elects from the first question, for votes filling several seats
+    pub per_option_quorum: Option<u32>, // Ballots an option needs to take a seat
+    pub required_certifications: u8, // Certifications of the results the vote needs to count as certified, none for votes not certified
+    pub per_voter_window: Option<i64>, // Seconds every voter may vote for after being added to the allowlist
+    pub deadline: Option<i64>, // Last Unix timestamp ballots are accepted at, anyone can finalize the vote afterwards
+    pub grace_period_slots: u16, // Slots after the deadline ballots still land in, for transactions sent just before it
+    pub min_slots_between_ballots: Option<u64>, // Slots a voter holding several votes waits between ballots, by default the next slot
+    pub creators: Vec<Pubkey>, // Co-creators approving admin actions, including the creator. Empty for a single creator
+    pub approval_threshold: u8, // Approvals of `creators` an admin action needs
+    pub tie_break: TieBreak,
+    pub require_delegate_acceptance: bool, // Delegations only take effect once the delegate accepts them
+    pub require_comment: bool, // Every ballot carries a comment of the voter, readable by the creator and the observers
+    pub refund_on_withdraw: bool, // Ballots of a withdrawn option go back to their voters instead of staying counted
+    pub allow_write_ins: bool, // Voters may add options with `vote_write_in`
+    pub delegation_policy: DelegationPolicy,
+    pub max_received_delegations: Option<u32>, // Delegated power a single delegate may hold, see `Vote::received_delegations`
+    pub eligibility: Option<Eligibility>, // Makes the vote open, anyone passing the check may join with `register_voter`
+    pub track_history: bool, // Keep the tallies of every window of `history_bucket_slots` slots a ballot landed in
+    pub history_bucket_slots: u64,
+    pub prerequisite: Option<Prerequisite>, // Earlier vote that has to pass first, the vote starts out pending
+}
+
+impl VoteConfig {
+    // Single-question config with public results and a public allowlist
+    pub fn new(title: String, options: Vec<VoteOption>) -> Result<Self, StringTooLong> {
+        let question = QuestionConfig {
+            title: Title::new("title", title.clone())?,
+            options,
+        };
+
+        Self::with_questions(title, vec![question])
+    }
+
+    // Single-question config from bare option labels
+    pub fn from_labels(title: String, labels: Vec<String>) -> Result<Self, StringTooLong> {
+        Self::new(title, VoteOption::from_labels(labels)?)
+    }
+
+    // Config for a vote made of several questions
+    pub fn with_questions(title: String, questions: Vec<QuestionConfig>) -> Result<Self, StringTooLong> {
+        Ok(Self {
+            title: Title::new("title", title)?,
+            description: None,
+            questions,
+            translations: Vec::new(),
+            option_format: OptionFormat::FreeText,
+            vote_type: VoteType::SingleChoice,
+            results_visibility: Visibility::Public,
+            allowlist_visibility: Visibility::Public,
+            projection_visibility: Visibility::CreatorOnly,
+            min_duration: None,
+            per_option_cap: None,
+            per_option_voter_cap: None,
+            allowlist_group: None,
+            winning_threshold_bps: None,
+            quorum: None,
+            seats: 1,
+            per_option_quorum: None,
+            required_certifications: 0,
+            per_voter_window: None,
+            deadline: None,
+            grace_period_slots: 0,
+            min_slots_between_ballots: None,
+            creators: Vec::new(),
+            approval_threshold: 0,
+            tie_break: TieBreak::None,
+            require_delegate_acceptance: false,
+            require_comment: false,
+            refund_on_withdraw: false,
+            allow_write_ins: false,
+            delegation_policy: DelegationPolicy::AllowlistOnly,
+            max_received_delegations: None,
+            eligibility: None,
+            track_history: false,
+            history_bucket_slots: 0,
+            prerequisite: None,
+        })
+    }
+}
+
+// Tally of a single option
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize)]
+pub struct OptionResult {
+    pub label: String,
+    pub description: Option<String>,
+    pub content_hash: Option<[u8; 32]>,
+    pub order: u16,
+    pub votes: u32,          // Number of ballots cast for the option
+    pub weighted_votes: u64, // Sum of the ballot weights in basis points, or of the voter weights in token-weighted votes
+    pub is_full: bool,       // Set when the option reached the per-option cap and takes no more ballots
+    pub abstain: bool,
+    pub withdrawn: bool,
+    pub share_bps: u16, // Share of the weighted ballots, abstentions excluded, zero for abstentions
+}
 
-            // Check if the selected option index is correct
-            if option_index >= self.options.len() {
-                return Err(ProgramError::InvalidArgument); // Return error if index is out of range
+// Results of a single question, in option order
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize)]
+pub struct QuestionResults {
+    pub title: String,
+    pub options: Vec<OptionResult>,
+    pub total_votes: u32,
+    pub total_weighted_votes: u64,
+}
+
+// Full results of a vote, in question order
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize)]
+pub struct VoteResults {
+    pub vote_id: VoteId,
+    pub questions: Vec<QuestionResults>,
+    pub is_void: bool, // Set when the vote was cancelled, the tallies then carry no outcome
+    pub per_option_cap: Option<u64>,
+    pub closure: Option<ClosureRecord>,
+    pub grace_ballots: u32, // Counted ballots cast inside the grace period after the deadline
+}
+
+impl VoteResults {
+    // Fingerprint of everything the results show, option content hashes included, for audits
+    // comparing what a front end displayed with the state on chain
+    pub fn hash(&self) -> Hash {
+        hash(&borsh::to_vec(self).expect("results serialize into memory"))
+    }
+}
+
+// Field of a CSV row, quoted when it holds a separator, a quote or a line break
+#[cfg(any(test, feature = "client"))]
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+// Share in basis points as a percentage with two decimals
+fn format_share(share_bps: u16) -> String {
+    format!("{}.{:02}%", share_bps / 100, share_bps % 100)
+}
+
+// Results as a plain text table, one block per question
+impl fmt::Display for VoteResults {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Vote {}", self.vote_id)?;
+        if self.is_void {
+            write!(f, " (cancelled)")?;
+        }
+        writeln!(f)?;
+
+        for (index, question) in self.questions.iter().enumerate() {
+            let width = question.options.iter().map(|option| option.label.chars().count()).max().unwrap_or(0).max("Option".len());
+
+            writeln!(f, "\nQuestion {}: {}", index, question.title)?;
+            writeln!(f, "{:>5}  {:<width$}  {:>8}  {:>8}", "Index", "Option", "Votes", "Share")?;
+            for (option_index, option) in question.options.iter().enumerate() {
+                writeln!(f, "{:>5}  {:<width$}  {:>8}  {:>8}", option_index, option.label, option.votes, format_share(option.share_bps))?;
             }
+            writeln!(f, "{:>5}  {:<width$}  {:>8}", "", "Total", question.total_votes)?;
+        }
+
+        Ok(())
+    }
+}
+
+// Slice of the option results of a question, in option order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResultsPage {
+    pub vote_id: VoteId,
+    pub question_index: u32,
+    pub offset: u32,
+    pub options: Vec<OptionResult>, // At most `MAX_RESULTS_PAGE` options from `offset` on
+    pub total_options: u32,
+}
+
+// Number of changes each vote keeps for `get_changes_since`, older ones are evicted
+pub const MAX_CHANGELOG_ENTRIES: usize = 128;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub enum ChangeKind {
+    BallotCast,
+    VoterAdded,
+    VoterRemoved,
+    Delegated,
+    Closed,
+    BallotRetracted,
+    OptionWrittenIn,
+    Edited,
+    VoterUpdated, // The votes of an allowed voter changed
+    OptionWithdrawn,
+}
+
+// State change of a vote, numbered from 1 in the order they happened
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct ChangeEntry {
+    pub seq: u64,
+    pub kind: ChangeKind,
+    pub at: i64, // Unix timestamp
+}
+
+// Changes of a vote after a given sequence number
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Changes {
+    pub entries: Vec<ChangeEntry>, // Oldest first
+    pub latest_seq: u64,           // 0 while the vote has no changes
+    pub overflow: bool,            // Some of the requested changes were evicted, the state should be read again in full
+}
+
+// Seconds before the deadline from which a change of the vote raises `Milestones::DEADLINE_SOON`
+pub const DEADLINE_SOON_WINDOW: i64 = 3_600;
+
+// Points in the life of a vote its creator may want to hear about, as bit flags. Each one is raised at
+// most once, by the first change of the vote that finds it met, and logs a `VoteEvent::MilestoneReached`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct Milestones(u8);
+
+impl Milestones {
+    pub const QUORUM_REACHED: Self = Self(1 << 0); // Every question holds at least the quorum of ballots
+    pub const MAJORITY_TURNOUT: Self = Self(1 << 1); // More than half of the eligible voters cast a ballot
+    pub const DEADLINE_SOON: Self = Self(1 << 2); // The vote changed within `DEADLINE_SOON_WINDOW` of its deadline
+    pub const ALL: [Self; 3] = [Self::QUORUM_REACHED, Self::MAJORITY_TURNOUT, Self::DEADLINE_SOON];
+
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn insert(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+}
+
+// Number of history buckets each vote keeps for `get_history`, older ones are evicted
+pub const MAX_HISTORY_BUCKETS: usize = 64;
+
+// Most observers a vote can name, they are part of the state account
+pub const MAX_OBSERVERS: usize = 16;
+
+// Most languages the option labels of a vote can be translated into
+pub const MAX_TRANSLATIONS: usize = 4;
+
+// Tallies of a vote at the end of a window of slots
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct HistoryBucket {
+    pub upto_slot: u64,                   // Last slot of the window
+    pub per_option_counts: Vec<Vec<u32>>, // Ballots of every option by question, cumulative since the vote opened
+}
+
+// Aggregates of a question, computed without building the option results
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuestionSummary {
+    pub total_options: u32,
+    pub total_votes: u32,
+    pub total_weighted_votes: u64,
+    pub leading_option: Option<u32>, // Option with the most weighted ballots, abstentions excluded, none without ballots or on a tie
+    pub leading_share_bps: u16,
+}
+
+// Turnout and leading options of a vote
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResultsSummary {
+    pub vote_id: VoteId,
+    pub eligible_voters: u32, // Allowed voters, banned ones excluded
+    pub participating_voters: u32, // Voters who cast at least one ballot
+    pub questions: Vec<QuestionSummary>,
+}
+
+// Compact state of a vote, as listed by `get_results_batch`
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct VoteResultsLite {
+    pub leading_option: Option<u32>, // Of the first question, see `QuestionSummary::leading_option`
+    pub total_ballots: u32, // Across every question
+    pub status: VoteStatusKind,
+    pub quorum_met: bool, // Every question holds the quorum of ballots, always set for votes without a quorum
+}
+
+// Entry of a `get_results_batch` answer, a vote that cannot be read carries the reason
+pub type BatchResult = (VoteId, Result<VoteResultsLite, VoteError>);
+
+// `VoteStatus` without the details of a cancellation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub enum VoteStatusKind {
+    Open,
+    Closed,
+    Cancelled,
+    Pending,
+}
+
+impl From<&VoteStatus> for VoteStatusKind {
+    fn from(status: &VoteStatus) -> Self {
+        match status {
+            VoteStatus::Open => VoteStatusKind::Open,
+            VoteStatus::Closed => VoteStatusKind::Closed,
+            VoteStatus::Cancelled { .. } => VoteStatusKind::Cancelled,
+            VoteStatus::Pending => VoteStatusKind::Pending,
+        }
+    }
+}
+
+// Count of an option with the most it could still reach if every remaining vote of its question went to it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OptionProjection {
+    pub label: String,
+    pub votes: u32,
+    pub max_votes: u32, // Limited by the per-option cap, if any
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuestionProjection {
+    pub options: Vec<OptionProjection>,
+    pub uncast_votes: u32, // Votes the allowed voters, banned ones excluded, still hold
+    pub pending_delegated_votes: u32, // Votes held in delegations the delegates have not accepted yet
+}
+
+// Best case of every option before the vote closes, see `get_projection`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Projection {
+    pub vote_id: VoteId,
+    pub questions: Vec<QuestionProjection>,
+}
+
+// Why a vote stopped accepting ballots
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub enum ClosureReason {
+    Creator,   // Closed manually by the creator
+    Expired,   // Finalized after the deadline
+    Admin,     // Closed by the registry admin
+    Cancelled, // Declared void by the creator
+}
+
+// Seconds a proposed admin action of a multi-creator vote can collect approvals for
+pub const PENDING_ACTION_LIFETIME: i64 = 7 * 24 * 3_600;
+
+// Action on a multi-creator vote that runs once enough creators approved it
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub enum AdminAction {
+    Close,
+    Cancel { reason: Reason },
+}
+
+// Admin action proposed by one of the creators of a vote
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct PendingAdminAction {
+    pub action: AdminAction,
+    pub proposed_by: Pubkey,
+    pub approvals: Vec<Pubkey>, // Creators who approved, the proposer first
+    pub expires_at: i64, // Unix timestamp after which the action can no longer be approved
+}
+
+// Field of a vote the creator can edit after creation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub enum EditedField {
+    Title,
+    Description,
+}
+
+// Audit entry of an edit. Only the hash of the replaced value is kept, a vote without a description
+// hashes as the empty string
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct EditRecord {
+    pub field: EditedField,
+    pub old_hash: Hash,
+    pub at: i64, // Unix timestamp
+    pub by: Pubkey,
+}
+
+// Most edits a vote takes, the audit trail is never trimmed
+pub const MAX_EDITS: usize = 32;
+
+// Most voters `sample_voters` draws at once
+pub const MAX_SAMPLE_SIZE: usize = 32;
+
+// Committee drawn from the allowlist of a closed vote, kept so anyone can repeat the draw with `sampling::draw_sample`
+// over the voters who were not banned
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct VoterSample {
+    pub seed: Hash, // `sampling::sample_seed` of the results hash and the slot hash
+    pub slot: Slot, // Slot of the slot hash
+    pub voters: Vec<Pubkey>, // In the order they were drawn
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub enum DepositStatus {
+    Held,      // The vault still holds the lamports
+    Refunded,  // Returned to the creator, who ended the vote before its deadline
+    Forfeited, // Sent to the treasury, the vote ended any other way
+}
+
+// Creation deposit of a vote, held in the system account at its vote address
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct CreatorDeposit {
+    pub lamports: u64,
+    pub vault: Pubkey, // `get_vote_address` of the vote, which signs the transfer out
+    pub registry: Pubkey, // Seed of the vault
+    pub bump: u8,
+    pub treasury: Pubkey, // Receives the deposit when it is forfeited
+    pub status: DepositStatus,
+}
+
+// Who ended a vote, when and why
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct ClosureRecord {
+    pub closed_by: Pubkey,
+    pub closed_at: i64, // Unix timestamp
+    pub reason: ClosureReason,
+    pub tie_break_hash: Option<Hash>, // Latest blockhash at close time, for votes breaking ties by blockhash
+}
+
+impl fmt::Display for ClosureReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let reason = match self {
+            ClosureReason::Creator => "by the creator",
+            ClosureReason::Expired => "after the deadline",
+            ClosureReason::Admin => "by the registry admin",
+            ClosureReason::Cancelled => "by cancellation",
+        };
+        f.write_str(reason)
+    }
+}
+
+// Vote that has to close with `required_option_index` winning its first question before a dependent vote opens
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct Prerequisite {
+    pub vote_id: VoteId,
+    pub required_option_index: OptionIndex,
+}
+
+// Where an imported vote came from: its ID in the source registry and the hash of the results it had there
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct ImportOrigin {
+    pub vote_id: VoteId,
+    pub results_hash: Hash,
+}
+
+// Lifecycle state of a vote
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub enum VoteStatus {
+    Open,   // Ballots are accepted
+    Closed, // Results are final
+    Cancelled {
+        reason: Reason,
+        at: i64, // Unix timestamp of the cancellation
+    }, // The vote was declared void and has no winner
+    Pending, // Waiting for its prerequisite, setup changes are accepted but ballots and delegations are not
+}
+
+impl fmt::Display for VoteStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VoteStatus::Open => f.write_str("open"),
+            VoteStatus::Closed => f.write_str("closed"),
+            VoteStatus::Cancelled { reason, .. } => write!(f, "cancelled: {}", reason),
+            VoteStatus::Pending => f.write_str("pending"),
+        }
+    }
+}
+
+// Final outcome of a question
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Winner { option_index: usize },
+    Tie { option_indices: Vec<usize> }, // Options sharing the highest tally, in option order
+    NoVotes,
+    NoThresholdMet { leading_option: usize, share_bps: u16 }, // The leading option missed the winning threshold
+    QuorumNotMet { total_votes: u32 },
+}
+
+// Signature of the creator or an observer on the results of a closed vote
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct Certification {
+    pub certifier: Pubkey,
+    pub results_hash: Hash, // `VoteResults::hash` of the results the certifier saw
+    pub certified_at: i64,  // Unix timestamp
+}
+
+// Statement of an allowlisted voter that they took no part in a closed vote, see `Voting::attest_non_participation`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct NonParticipationAttestation {
+    pub voter: Pubkey,
+    pub at: i64, // Unix timestamp
+}
+
+// Option taking a seat of a vote filling several seats, see `Voting::get_elected`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ElectedOption {
+    pub option_index: usize,
+    pub label: String,
+    pub votes: u32,
+    pub weighted_votes: u64,
+}
+
+// Short description of a vote, so clients know which calls they may attempt
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VoteSummary {
+    pub id: VoteId,
+    pub title: String,
+    pub description: Option<String>,
+    pub creator: Pubkey,
+    pub questions_count: usize,
+    pub status: VoteStatus,
+    pub results_visibility: Visibility,
+    pub allowlist_visibility: Visibility,
+    pub per_option_cap: Option<u64>,
+    pub winning_threshold_bps: Option<u16>,
+    pub quorum: Option<u32>,
+    pub deadline: Option<i64>,
+    pub closure: Option<ClosureRecord>,
+    pub runoff_of: Option<VoteId>, // Vote this one is the runoff of
+    pub parent: Option<(VoteId, OptionIndex)>, // Vote and option of the first question this one is a sub-vote of
+    pub edit_count: u32, // Edits of the title or description since creation
+    pub deposit: Option<CreatorDeposit>,
+    pub pinned: bool,
+    pub display_order: Option<u16>,
+}
+
+// Invitation signed off-chain by the creator that lets a voter add themselves
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Voucher {
+    pub vote_id: VoteId,
+    pub voter: Pubkey,
+    pub votes: u32,  // Number of votes granted to the voter
+    pub expiry: i64, // Unix timestamp after which the voucher can no longer be redeemed
+}
+
+impl Voucher {
+    // Bytes the creator signs with the ed25519 program. Vote IDs repeat across registries and deployments, so
+    // the message names the program and the registry the voucher is issued for
+    pub fn to_message(&self, program_id: &Pubkey, registry: &Pubkey) -> Vec<u8> {
+        let mut message = Vec::with_capacity(123);
+        message.extend_from_slice(b"voucher");
+        message.extend_from_slice(program_id.as_ref());
+        message.extend_from_slice(registry.as_ref());
+        message.extend_from_slice(&self.vote_id.to_le_bytes());
+        message.extend_from_slice(self.voter.as_ref());
+        message.extend_from_slice(&self.votes.to_le_bytes());
+        message.extend_from_slice(&self.expiry.to_le_bytes());
+        message
+    }
+}
+
+// Ballot signed off-chain by a voter, which a relayer submits with `cast_vote_signed` and pays the fees of
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct SignedBallot {
+    pub vote_id: VoteId,
+    pub option_index: OptionIndex, // Option of the first question
+    pub voter: Pubkey,
+    pub nonce: u64,  // Has to exceed the nonce of the previous relayed ballot of the voter
+    pub expiry: i64, // Unix timestamp after which the ballot can no longer be relayed
+}
+
+impl SignedBallot {
+    // Bytes the voter signs with the ed25519 program, tagged so they never read as a voucher. Like a voucher's
+    // they name the program and the registry, a ballot relayed to another registry does not verify
+    pub fn to_message(&self, program_id: &Pubkey, registry: &Pubkey) -> Vec<u8> {
+        let mut message = Vec::with_capacity(134);
+        message.extend_from_slice(b"ballot");
+        message.extend_from_slice(program_id.as_ref());
+        message.extend_from_slice(registry.as_ref());
+        message.extend_from_slice(&self.vote_id.to_le_bytes());
+        message.extend_from_slice(&self.option_index.to_le_bytes());
+        message.extend_from_slice(self.voter.as_ref());
+        message.extend_from_slice(&self.nonce.to_le_bytes());
+        message.extend_from_slice(&self.expiry.to_le_bytes());
+        message
+    }
+}
+
+// Proof that a voter took part in a vote, stored in its own account at `get_receipt_address`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct VoteReceipt {
+    pub vote_id: VoteId,
+    pub voter: Pubkey,
+    pub question_index: u32,
+    pub option_index: Option<OptionIndex>, // Left out unless the results are public, the receipt then only proves participation
+    pub timestamp: i64, // Unix timestamp of the first ballot
+}
+
+impl VoteReceipt {
+    pub const LEN: usize = 8 + 32 + 4 + (1 + 2) + 8;
+
+    pub fn load(account: &AccountInfo, program_id: &Pubkey) -> Result<Self, ProgramError> {
+        if account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId); // Return error if the receipt was not created by the program
+        }
+
+        Ok(Self::deserialize(&mut &account.try_borrow_data()?[..])?) // A receipt without an option leaves trailing zeros
+    }
+}
+
+// Address of the account holding the state of a vote of the registry
+pub fn get_vote_address(program_id: &Pubkey, registry: &Pubkey, vote_id: VoteId) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"vote", registry.as_ref(), &vote_id.to_le_bytes()], program_id)
+}
+
+// Address of the directory account of the `shard_index`th shard of a sharded registry
+pub fn get_shard_address(program_id: &Pubkey, registry: &Pubkey, shard_index: u32) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"registry", registry.as_ref(), &shard_index.to_le_bytes()], program_id)
+}
+
+// Address of the outbox account of a registry
+pub fn get_outbox_address(program_id: &Pubkey, registry: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"outbox", registry.as_ref()], program_id)
+}
+
+// Address of the receipt left by the first ballot of `voter` on the vote at `vote_address`
+pub fn get_receipt_address(program_id: &Pubkey, vote_address: &Pubkey, voter: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"receipt", vote_address.as_ref(), voter.as_ref()], program_id)
+}
+
+// Key standing in for the `index`th scrubbed voter of a vote, counted from 1. Zeroed but for the index,
+// so it belongs to no wallet and cannot be traced back to the voter
+fn scrubbed_key(index: u32) -> Pubkey {
+    let mut bytes = [0; 32];
+    bytes[28..].copy_from_slice(&index.to_be_bytes());
+    Pubkey::new_from_array(bytes)
+}
+
+// Read the current unix timestamp from the Clock sysvar, or the one of the event being replayed
+fn current_timestamp() -> Result<i64, ProgramError> {
+    #[cfg(any(test, feature = "client"))]
+    if let Some((_, timestamp)) = replay::replay_clock() {
+        return Ok(timestamp);
+    }
+    Ok(Clock::get()?.unix_timestamp)
+}
+
+// Read the current slot from the Clock sysvar, or the one of the event being replayed
+fn current_slot() -> Result<Slot, ProgramError> {
+    #[cfg(any(test, feature = "client"))]
+    if let Some((slot, _)) = replay::replay_clock() {
+        return Ok(slot);
+    }
+    Ok(Clock::get()?.slot)
+}
+
+// Key of the first account, the caller of a read-only method
+fn caller_key<'a>(accounts: &'a [AccountInfo]) -> Result<&'a Pubkey, ProgramError> {
+    Ok(accounts.first().ok_or(ProgramError::InvalidArgument)?.key) // Return error if no accounts are provided
+}
+
+// Stands in for a viewer who did not sign, it is nobody's key the data of a vote could be shown to
+const UNSIGNED_VIEWER: Pubkey = Pubkey::new_from_array([0; 32]);
+
+// Key of the first account for a read of `restricted` data, data whose visibility is not `Public`. Anyone can
+// pass the key of the creator or of an allowed voter, so restricted data is only read for a viewer who signs.
+// A viewer who does not sign reads public data as `UNSIGNED_VIEWER`
+fn viewer_key<'a>(accounts: &'a [AccountInfo], restricted: bool) -> Result<&'a Pubkey, ProgramError> {
+    let viewer = accounts.first().ok_or(ProgramError::InvalidArgument)?; // Return error if no accounts are provided
+
+    match (viewer.is_signer, restricted) {
+        (true, _) => Ok(viewer.key),
+        (false, false) => Ok(&UNSIGNED_VIEWER),
+        (false, true) => Err(ProgramError::MissingRequiredSignature),
+    }
+}
+
+// Key of the first account, which has to sign the calls changing the registry
+fn signer_key<'a>(accounts: &'a [AccountInfo]) -> Result<&'a Pubkey, ProgramError> {
+    let caller = accounts.first().ok_or(ProgramError::InvalidArgument)?; // Return error if no accounts are provided
+
+    if !caller.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    Ok(caller.key)
+}
+
+// Refuse an account passed twice where the call needs distinct ones, e.g. the vault of a deposit named again as
+// its recipient. Moving lamports between aliased accounts would borrow the same balance twice or credit it twice
+pub fn check_unique_accounts(keys: &[&Pubkey]) -> Result<(), ProgramError> {
+    for (index, key) in keys.iter().enumerate() {
+        if keys[..index].contains(key) {
+            return Err(VoteError::DuplicateAccount.into());
+        }
+    }
+
+    Ok(())
+}
+
+// Latest blockhash held by the recent blockhashes sysvar account
+#[allow(deprecated)]
+fn latest_blockhash(sysvar_account: &AccountInfo) -> Result<Hash, ProgramError> {
+    if *sysvar_account.key != recent_blockhashes::id() {
+        return Err(ProgramError::InvalidArgument); // Return error if another account is passed as the sysvar
+    }
+
+    // Entries follow their u64 count, newest first, each starting with its blockhash
+    let data = sysvar_account.try_borrow_data()?;
+    let count = data.get(..8).map_or(0, |count| u64::from_le_bytes(count.try_into().unwrap_or_default()));
+    let blockhash: [u8; 32] = data.get(8..40).filter(|_| count > 0).and_then(|bytes| bytes.try_into().ok()).ok_or(ProgramError::InvalidAccountData)?;
+
+    Ok(Hash::new_from_array(blockhash))
+}
+
+// Latest entry of the slot hashes sysvar account, with its slot
+fn latest_slot_hash(sysvar_account: &AccountInfo) -> Result<(Slot, Hash), ProgramError> {
+    if *sysvar_account.key != slot_hashes::id() {
+        return Err(ProgramError::InvalidArgument); // Return error if another account is passed as the sysvar
+    }
+
+    // Entries follow their u64 count, newest first, each a slot and its hash
+    let data = sysvar_account.try_borrow_data()?;
+    let count = data.get(..8).map_or(0, |count| u64::from_le_bytes(count.try_into().unwrap_or_default()));
+    let entry = data.get(8..48).filter(|_| count > 0).ok_or(ProgramError::InvalidAccountData)?;
+    let slot = u64::from_le_bytes(entry[..8].try_into().map_err(|_| ProgramError::InvalidAccountData)?);
+    let hash: [u8; 32] = entry[8..].try_into().map_err(|_| ProgramError::InvalidAccountData)?;
+
+    Ok((slot, Hash::new_from_array(hash)))
+}
+
+// Check that the instruction right before the current one is an ed25519 program
+// instruction verifying a signature of `signer` over exactly `message`, and return that signature
+fn verify_ed25519_signature(instructions_sysvar: &AccountInfo, signer: &Pubkey, message: &[u8]) -> Result<[u8; 64], ProgramError> {
+    let instruction = sysvar_instructions::get_instruction_relative(-1, instructions_sysvar)?;
+
+    if instruction.program_id != ed25519_program::id() {
+        return Err(ProgramError::InvalidArgument); // Return error if there is no preceding signature check
+    }
+
+    let data = &instruction.data;
+
+    // Expect a single signature with the offsets header right after the count and padding bytes
+    if data.len() < 16 || data[0] != 1 {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+
+    let read_u16 = |offset: usize| u16::from_le_bytes([data[offset], data[offset + 1]]);
+    let signature_offset = read_u16(2) as usize;
+    let public_key_offset = read_u16(6) as usize;
+    let message_data_offset = read_u16(10) as usize;
+    let message_data_size = read_u16(12) as usize;
+
+    // The signature, public key and message must all live in the ed25519 instruction itself
+    if read_u16(4) != u16::MAX || read_u16(8) != u16::MAX || read_u16(14) != u16::MAX {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+
+    let public_key = data.get(public_key_offset..public_key_offset + 32).ok_or(ProgramError::InvalidInstructionData)?;
+    let signed_message = data.get(message_data_offset..message_data_offset + message_data_size).ok_or(ProgramError::InvalidInstructionData)?;
+
+    let signature = data.get(signature_offset..signature_offset + 64).ok_or(ProgramError::InvalidInstructionData)?;
+
+    if public_key != signer.as_ref() || signed_message != message {
+        return Err(ProgramError::InvalidArgument); // Return error if the signature is not over the expected data
+    }
+
+    signature.try_into().map_err(|_| ProgramError::InvalidInstructionData)
+}
+
+// A single question of a vote with its own options and tallies
+#[derive(Debug, Clone, PartialEq, BorshSerialize, BorshDeserialize)]
+struct Question {
+    title: Title,
+    options: Vec<VoteOption>,
+    votes: HashMap<String, u32>,
+    weighted_votes: HashMap<String, u64>, // Sum of the ballot weights in basis points
+    participants: HashMap<String, HashSet<Pubkey>>, // Voters holding a ballot for each option, only tracked with a per-option voter cap
+}
+
+impl Question {
+    fn new(config: QuestionConfig) -> Self {
+        Self {
+            title: config.title,
+            options: config.options,
+            votes: HashMap::new(), // Initialize an empty map for votes
+            weighted_votes: HashMap::new(),
+            participants: HashMap::new(),
+        }
+    }
+
+    // Position of the option labelled `label`, compared in normalized form
+    fn option_named(&self, label: &str) -> Option<usize> {
+        let label = normalized_label(label);
+        self.options.iter().position(|option| normalized_label(&option.label) == label)
+    }
+
+    // Seats of the option nobody holds yet, `None` without a per-option voter cap
+    fn remaining_seats(&self, label: &str, voter_cap: Option<u32>) -> Option<u32> {
+        let taken = self.participants.get(label).map_or(0, |participants| participants.len() as u32);
+        voter_cap.map(|cap| cap.saturating_sub(taken))
+    }
+
+    // Check that the voter already holds a seat on the option or can take one
+    fn ensure_seat(&self, label: &str, voter: &Pubkey, voter_cap: Option<u32>) -> Result<(), ProgramError> {
+        let seated = self.participants.get(label).is_some_and(|participants| participants.contains(voter));
+        if !seated && self.remaining_seats(label, voter_cap) == Some(0) {
+            return Err(VoteError::OptionFull.into());
+        }
+        Ok(())
+    }
+
+    fn take_seat(&mut self, label: &str, voter: &Pubkey, voter_cap: Option<u32>) {
+        if voter_cap.is_some() {
+            self.participants.entry(label.to_string()).or_default().insert(*voter);
+        }
+    }
+
+    fn free_seat(&mut self, label: &str, voter: &Pubkey) {
+        if let Some(participants) = self.participants.get_mut(label) {
+            participants.remove(voter);
+        }
+    }
+
+    // Readers pass the weighted tallies in, conviction votes count more than the stored ones
+    fn results_page(&self, weighted_votes: &HashMap<String, u64>, per_option_cap: Option<u64>, offset: u32, limit: u32) -> Vec<OptionResult> {
+        let counted_total = tally::counted_total(&self.options, weighted_votes);
+
+        self.options
+            .iter()
+            .skip(offset as usize)
+            .take(limit.min(MAX_RESULTS_PAGE) as usize)
+            .map(|option| tally::option_result(option, &self.votes, weighted_votes, per_option_cap, counted_total))
+            .collect()
+    }
+
+    fn summary(&self, weighted_votes: &HashMap<String, u64>) -> QuestionSummary {
+        let weighted_of = |option: &VoteOption| weighted_votes.get(option.label.as_str()).copied().unwrap_or(0);
+
+        // Single pass over the options, a tie for the lead clears the leading option
+        let mut best = 0;
+        let mut leading_option = None;
+        for (index, option) in self.options.iter().enumerate().filter(|(_, option)| !option.abstain) {
+            let weighted_votes = weighted_of(option);
+            if weighted_votes > best {
+                best = weighted_votes;
+                leading_option = Some(index as u32);
+            } else if weighted_votes == best {
+                leading_option = None;
+            }
+        }
+
+        QuestionSummary {
+            total_options: self.options.len().min(u32::MAX as usize) as u32,
+            total_votes: self.votes.values().sum(),
+            total_weighted_votes: weighted_votes.values().sum(),
+            leading_option,
+            leading_share_bps: tally::share_bps(best, tally::counted_total(&self.options, weighted_votes)),
+        }
+    }
+
+    fn results(&self, weighted_votes: &HashMap<String, u64>, per_option_cap: Option<u64>) -> QuestionResults {
+        let options = tally::option_results(&self.options, &self.votes, weighted_votes, per_option_cap);
+
+        QuestionResults {
+            title: self.title.to_string(),
+            total_votes: options.iter().map(|option| option.votes).sum(),
+            total_weighted_votes: options.iter().map(|option| option.weighted_votes).sum(),
+            options,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, BorshSerialize, BorshDeserialize)]
+pub struct Vote {
+    id: VoteId,
+    title: Title,
+    description: Option<Description>,
+    questions: Vec<Question>, // Always holds at least one question
+    vote_type: VoteType,
+    creator: Pubkey,
+    allowed_voters: HashMap<Pubkey, VoterInfo>, // Stores information about allowed voters
+    results_visibility: Visibility,
+    allowlist_visibility: Visibility,
+    redeemed_vouchers: HashSet<Hash>, // Hashes of the vouchers that were already used
+    max_voters: Option<u32>,  // Capacity of the allowlist in the state account, if any
+    max_options: Option<u16>, // Capacity of options per question in the state account, if any
+    min_close_at: Option<i64>, // Creation time plus the minimum duration, the vote cannot be closed earlier
+    per_option_cap: Option<u64>,
+    per_option_voter_cap: Option<u32>,
+    winning_threshold_bps: Option<u16>,
+    quorum: Option<u32>,
+    per_voter_window: Option<i64>,
+    deadline: Option<i64>,
+    min_slots_between_ballots: Option<u64>,
+    creators: Vec<Pubkey>, // Empty for a single creator
+    approval_threshold: u8,
+    pending_action: Option<PendingAdminAction>,
+    tie_break: TieBreak,
+    tie_resolutions: HashMap<u32, u32>, // Option the creator picked for each tied question
+    require_delegate_acceptance: bool,
+    pending_delegations: Vec<PendingDelegation>,
+    allow_write_ins: bool,
+    delegation_policy: DelegationPolicy,
+    eligibility: Option<Eligibility>,
+    history_bucket_slots: Option<u64>, // Set when the vote tracks its history
+    history: Vec<HistoryBucket>, // Latest `MAX_HISTORY_BUCKETS` buckets, oldest first
+    changelog: Vec<ChangeEntry>, // Latest `MAX_CHANGELOG_ENTRIES` changes, oldest first
+    latest_change_seq: u64,
+    closure: Option<ClosureRecord>, // Set while the vote is closed or cancelled
+    runoff_of: Option<VoteId>, // Source vote of a runoff created with `create_runoff`
+    parent: Option<(VoteId, OptionIndex)>, // Parent of a sub-vote created with `create_subvote`
+    conviction_frozen_at: Option<Slot>, // Slot a conviction vote closed at, its ballots stop gaining weight there
+    observers: Vec<Pubkey>, // May read results and the allowlist whatever their visibility, without a vote
+    edits: Vec<EditRecord>, // Edits of the title and the description, oldest first
+    deposit: Option<CreatorDeposit>, // Set on registries taking a creation deposit
+    projection_visibility: Visibility,
+    milestones: Milestones, // Raised lazily by `record_change`
+    opted_out: Vec<Pubkey>, // Voters who left with `do_not_readd`, only they can bring themselves back
+    sample: Option<VoterSample>, // Set once `sample_voters` drew a committee
+    prerequisite: Option<Prerequisite>,
+    pinned: bool, // Listed before the unpinned votes by `list_votes`
+    display_order: Option<u16>, // Display priority among votes pinned alike, lowest first
+    ledger: CreditLedger, // Every movement of votes, see `ledger`
+    max_received_delegations: Option<u32>,
+    imported: Option<ImportOrigin>, // Set on votes recreated from a bundle by `import_bundle`
+    seats: u8,
+    per_option_quorum: Option<u32>,
+    scrubbed_voters: u32, // Voters whose identity `scrub_voter` removed, their entries sit under `scrubbed_key`
+    required_certifications: u8,
+    certifications: Vec<Certification>, // In signing order, at most one per certifier
+    translations: Vec<(LangCode, Vec<Label>)>, // Display only, tallies stay keyed by the labels of the options
+    option_format: OptionFormat,
+    grace_period_slots: u16,
+    require_comment: bool,
+    refund_on_withdraw: bool,
+    attestations: Vec<NonParticipationAttestation>, // In attestation order, at most one per voter
+    event_seq: u64, // Sequence number of the next event of the vote
+    #[borsh(skip)]
+    events_muted: bool, // Not stored, set on the copies simulations and replays work on
+    status: VoteStatus
+}
+
+// Overview for logs and test failures: status, tallies in option order and turnout
+impl fmt::Display for Vote {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Vote {}: {} ({}", self.id, self.title, self.status)?;
+        if let (VoteStatus::Closed, Some(closure)) = (&self.status, self.closure) {
+            write!(f, " {}", closure.reason)?;
+        }
+        writeln!(f, ")")?;
+
+        for (index, question) in self.questions.iter().enumerate() {
+            writeln!(f, "Question {}: {}", index, question.title)?;
+            for (option_index, option) in question.options.iter().enumerate() {
+                let votes = question.votes.get(option.label.as_str()).copied().unwrap_or(0);
+                writeln!(f, "  {}. {}: {}", option_index, option.label, votes)?;
+            }
+        }
+
+        let (eligible, participating) = self.turnout();
+        write!(f, "Turnout: {} of {} voters", participating, eligible)
+    }
+}
+
+impl Vote {
+    fn from_config(id: VoteId, creator: Pubkey, config: VoteConfig) -> Self {
+        let questions_count = config.questions.len();
+        Self {
+            id,
+            title: config.title,
+            description: config.description,
+            questions: config.questions.into_iter().map(Question::new).collect(),
+            vote_type: config.vote_type,
+            creator,
+            allowed_voters: HashMap::new(), // Initialize an empty map for allowed voters
+            results_visibility: config.results_visibility,
+            allowlist_visibility: config.allowlist_visibility,
+            redeemed_vouchers: HashSet::new(),
+            max_voters: None,
+            max_options: None,
+            min_close_at: None,
+            per_option_cap: config.per_option_cap,
+            per_option_voter_cap: config.per_option_voter_cap,
+            winning_threshold_bps: config.winning_threshold_bps,
+            quorum: config.quorum,
+            per_voter_window: config.per_voter_window,
+            deadline: config.deadline,
+            min_slots_between_ballots: config.min_slots_between_ballots,
+            creators: config.creators,
+            approval_threshold: config.approval_threshold,
+            pending_action: None,
+            tie_break: config.tie_break,
+            tie_resolutions: HashMap::new(),
+            require_delegate_acceptance: config.require_delegate_acceptance,
+            pending_delegations: Vec::new(),
+            allow_write_ins: config.allow_write_ins,
+            delegation_policy: config.delegation_policy,
+            eligibility: config.eligibility,
+            history_bucket_slots: config.track_history.then_some(config.history_bucket_slots),
+            history: Vec::new(),
+            changelog: Vec::new(),
+            latest_change_seq: 0,
+            closure: None,
+            runoff_of: None,
+            parent: None,
+            conviction_frozen_at: None,
+            observers: Vec::new(),
+            edits: Vec::new(),
+            deposit: None,
+            projection_visibility: config.projection_visibility,
+            milestones: Milestones::default(),
+            opted_out: Vec::new(),
+            sample: None,
+            prerequisite: config.prerequisite,
+            pinned: false,
+            display_order: None,
+            ledger: CreditLedger::new(questions_count),
+            max_received_delegations: config.max_received_delegations,
+            imported: None,
+            seats: config.seats,
+            per_option_quorum: config.per_option_quorum,
+            scrubbed_voters: 0,
+            required_certifications: config.required_certifications,
+            certifications: Vec::new(),
+            translations: config.translations,
+            option_format: config.option_format,
+            grace_period_slots: config.grace_period_slots,
+            require_comment: config.require_comment,
+            refund_on_withdraw: config.refund_on_withdraw,
+            attestations: Vec::new(),
+            event_seq: 0,
+            events_muted: false,
+            status: if config.prerequisite.is_some() { VoteStatus::Pending } else { VoteStatus::Open }
+        }
+    }
+
+    // Options of a single-question vote that make it into a runoff between the `top_n` best tallies. Options
+    // tied with the last one taken come along, options without votes never do
+    fn runoff_options(&self, top_n: usize) -> Result<Vec<VoteOption>, ProgramError> {
+        let [question] = self.questions.as_slice() else {
+            return Err(ProgramError::InvalidArgument); // Return error if the runoff would not know which question to repeat
+        };
+
+        let mut ranked: Vec<(u32, &VoteOption)> = question
+            .options
+            .iter()
+            .map(|option| (question.votes.get(option.label.as_str()).copied().unwrap_or(0), option))
+            .filter(|(votes, _)| *votes > 0)
+            .collect();
+        if ranked.len() < 2 || top_n < 2 {
+            return Err(ProgramError::InvalidArgument); // Return error if there is nothing to run off
+        }
+        ranked.sort_by_key(|(votes, _)| std::cmp::Reverse(*votes)); // Stable, so tied options keep their order
+
+        let cutoff = ranked[top_n.min(ranked.len()) - 1].0;
+        Ok(ranked.into_iter().take_while(|(votes, _)| *votes >= cutoff).map(|(_, option)| option.clone()).collect())
+    }
+
+    // Current counts with the votes still uncast or held in pending delegations. Integer counts only, the weights
+    // of token-weighted ballots are not projected
+    fn projection(&self) -> Projection {
+        let questions = self
+            .questions
+            .iter()
+            .enumerate()
+            .map(|(index, question)| {
+                let uncast_votes = self
+                    .allowed_voters
+                    .values()
+                    .filter(|voter_info| !voter_info.banned)
+                    .fold(0u32, |total, voter_info| total.saturating_add(voter_info.votes_left.get(index).copied().unwrap_or(0)));
+                let pending_delegated_votes = self
+                    .pending_delegations
+                    .iter()
+                    .fold(0u32, |total, pending| total.saturating_add(pending.votes.get(index).copied().unwrap_or(0)));
+                let remaining = u64::from(uncast_votes) + u64::from(pending_delegated_votes);
+
+                let options = question
+                    .options
+                    .iter()
+                    .map(|option| {
+                        let votes = question.votes.get(option.label.as_str()).copied().unwrap_or(0);
+                        let mut max_votes = u64::from(votes) + remaining;
+                        if let Some(cap) = self.per_option_cap {
+                            max_votes = max_votes.min(cap.max(u64::from(votes)));
+                        }
+                        OptionProjection { label: option.label.to_string(), votes, max_votes: max_votes.min(u64::from(u32::MAX)) as u32 }
+                    })
+                    .collect();
+
+                QuestionProjection { options, uncast_votes, pending_delegated_votes }
+            })
+            .collect();
+
+        Projection { vote_id: self.id, questions }
+    }
+
+    // Allowed voters who are not banned, and the ones among them who cast at least one ballot
+    fn turnout(&self) -> (u32, u32) {
+        let voters = self.allowed_voters.values();
+        let eligible = voters.clone().filter(|voter_info| !voter_info.banned).count() as u32;
+        let participating = voters.filter(|voter_info| !voter_info.ballots.is_empty()).count() as u32;
+        (eligible, participating)
+    }
+
+    // Config of a fresh vote with the same questions and settings. Deadlines and the minimum
+    // duration belong to a single run of the vote and are not carried over
+    fn to_config(&self, title: String) -> Result<VoteConfig, StringTooLong> {
+        let questions = self
+            .questions
+            .iter()
+            .map(|question| QuestionConfig { title: question.title.clone(), options: question.options.clone() })
+            .collect();
+
+        Ok(VoteConfig {
+            description: self.description.clone(),
+            translations: self.translations.clone(),
+            option_format: self.option_format,
+            grace_period_slots: self.grace_period_slots,
+            require_comment: self.require_comment,
+            refund_on_withdraw: self.refund_on_withdraw,
+            vote_type: self.vote_type,
+            results_visibility: self.results_visibility,
+            allowlist_visibility: self.allowlist_visibility,
+            projection_visibility: self.projection_visibility,
+            per_option_cap: self.per_option_cap,
+            per_option_voter_cap: self.per_option_voter_cap,
+            winning_threshold_bps: self.winning_threshold_bps,
+            quorum: self.quorum,
+            seats: self.seats,
+            per_option_quorum: self.per_option_quorum,
+            required_certifications: self.required_certifications,
+            per_voter_window: self.per_voter_window,
+            min_slots_between_ballots: self.min_slots_between_ballots,
+            tie_break: self.tie_break,
+            require_delegate_acceptance: self.require_delegate_acceptance,
+            allow_write_ins: self.allow_write_ins,
+            delegation_policy: self.delegation_policy,
+            max_received_delegations: self.max_received_delegations,
+            eligibility: self.eligibility,
+            track_history: self.history_bucket_slots.is_some(),
+            history_bucket_slots: self.history_bucket_slots.unwrap_or(0),
+            ..VoteConfig::with_questions(title, questions)?
+        })
+    }
+
+    // Portable copy of the closed vote, for `Voting::import_bundle` in another registry
+    #[cfg(any(test, feature = "client"))]
+    pub fn export_bundle(&self) -> Result<VoteBundle, ProgramError> {
+        if self.status != VoteStatus::Closed {
+            return Err(ProgramError::InvalidArgument); // Return error if the vote can still change
+        }
+
+        let results_hash = self.full_results(None)?.hash();
+        Ok(VoteBundle::new(borsh::to_vec(self)?, results_hash))
+    }
+
+    // Number of bytes a vote created from `config` needs to hold `max_voters` allowed voters
+    pub fn required_space(config: &VoteConfig, max_voters: usize) -> usize {
+        let (base_space, voter_space) = Self::space_layout(config);
+        base_space + voter_space * max_voters
+    }
+
+    // Space of a vote without voters and space of every allowed voter, both measured
+    // on the largest serialized state they can grow into
+    fn space_layout(config: &VoteConfig) -> (usize, usize) {
+        let mut prototype = Self::from_config(VoteId::default(), Pubkey::default(), config.clone());
+        prototype.max_voters = Some(0);
+        prototype.max_options = Some(0);
+        prototype.min_close_at = Some(0);
+        prototype.per_option_cap = Some(0);
+        prototype.per_option_voter_cap = Some(0);
+        prototype.winning_threshold_bps = Some(0);
+        prototype.quorum = Some(0);
+        prototype.per_option_quorum = Some(0);
+        prototype.per_voter_window = Some(0);
+        prototype.deadline = Some(0);
+        prototype.min_slots_between_ballots = Some(0);
+        prototype.max_received_delegations = Some(0);
+        prototype.pending_action = Some(PendingAdminAction {
+            action: AdminAction::Cancel { reason: Reason::longest() },
+            proposed_by: Pubkey::default(),
+            approvals: prototype.creators.clone(),
+            expires_at: 0,
+        });
+        prototype.tie_resolutions = (0..prototype.questions.len() as u32).map(|question_index| (question_index, 0)).collect();
+        prototype.changelog = vec![ChangeEntry { seq: 0, kind: ChangeKind::BallotCast, at: 0 }; MAX_CHANGELOG_ENTRIES];
+        prototype.ledger = CreditLedger::longest(prototype.questions.len());
+        prototype.closure = Some(ClosureRecord { closed_by: Pubkey::default(), closed_at: 0, reason: ClosureReason::Cancelled, tie_break_hash: Some(Hash::default()) });
+        prototype.conviction_frozen_at = Some(0);
+        prototype.observers = vec![Pubkey::default(); MAX_OBSERVERS];
+        prototype.description = Some(Description::longest());
+        prototype.edits = vec![EditRecord { field: EditedField::Title, old_hash: Hash::default(), at: 0, by: Pubkey::default() }; MAX_EDITS];
+        prototype.deposit = Some(CreatorDeposit {
+            lamports: 0,
+            vault: Pubkey::default(),
+            registry: Pubkey::default(),
+            bump: 0,
+            treasury: Pubkey::default(),
+            status: DepositStatus::Held,
+        });
+        prototype.prerequisite = Some(Prerequisite { vote_id: VoteId::default(), required_option_index: OptionIndex::default() });
+        prototype.display_order = Some(0);
+        prototype.parent = Some((VoteId::default(), OptionIndex::default()));
+        prototype.imported = Some(ImportOrigin { vote_id: VoteId::default(), results_hash: Hash::default() });
+        prototype.certifications = vec![Certification { certifier: Pubkey::default(), results_hash: Hash::default(), certified_at: 0 }; MAX_OBSERVERS + 1];
+        let translated_options = prototype.questions.first().map_or(0, |question| question.options.len());
+        let lang = LangCode::new("en").expect("a valid language code"); // Codes all take two bytes
+        prototype.translations = vec![(lang, vec![Label::longest(); translated_options]); MAX_TRANSLATIONS];
+        prototype.sample = Some(VoterSample { seed: Hash::default(), slot: 0, voters: vec![Pubkey::default(); MAX_SAMPLE_SIZE] });
+        prototype.status = VoteStatus::Cancelled { reason: Reason::longest(), at: 0 };
+
+        // Options of the first question can be given a content hash until the first ballot
+        for option in prototype.questions.iter_mut().take(1).flat_map(|question| &mut question.options) {
+            option.content_hash = Some([0; 32]);
+        }
+
+        // Questions accepting write-ins can grow to the most options with the longest labels
+        if config.allow_write_ins {
+            for question in &mut prototype.questions {
+                for index in question.options.len()..MAX_WRITE_IN_OPTIONS {
+                    let label = format!("{:0>width$}", index, width = MAX_WRITE_IN_LEN);
+                    let option = VoteOption::new(label, 0).expect("write-in labels fit the label limit");
+                    question.options.push(VoteOption { proposed_by: Some(Pubkey::default()), ..option });
+                }
+            }
+        }
+
+        // A tracked history fills up with counts of every option
+        if prototype.history_bucket_slots.is_some() {
+            let per_option_counts = prototype.questions.iter().map(|question| vec![0; question.options.len()]).collect();
+            prototype.history = vec![HistoryBucket { upto_slot: 0, per_option_counts }; MAX_HISTORY_BUCKETS];
+        }
+
+        // Every option gets a tally entry once it receives a ballot, and a seat list with a voter cap
+        let seated = config.per_option_voter_cap.is_some();
+        for question in &mut prototype.questions {
+            for option in &question.options {
+                question.votes.insert(option.label.to_string(), 0);
+                question.weighted_votes.insert(option.label.to_string(), 0);
+                if seated {
+                    question.participants.insert(option.label.to_string(), HashSet::new());
+                }
+            }
+        }
+
+        let base_space = borsh::object_length(&prototype).unwrap_or(usize::MAX);
+
+        // A voter may delegate and may have joined through a voucher. Ballots and delegations are
+        // sized for a single vote per question, voters holding more votes need extra room
+        let questions_count = prototype.questions.len();
+        let comment = prototype.require_comment.then(Comment::longest);
+        let ballot = Ballot { question_index: 0, option_index: OptionIndex::default(), weight: 0, cast_slot: Some(0), in_grace: false, comment };
+        let voter = VoterInfo {
+            delegate: Some(Pubkey::default()),
+            last_voted_slot: Some(0),
+            ballots: vec![ballot; questions_count],
+            delegations: vec![Delegation { delegate: Pubkey::default(), votes: vec![0; questions_count], expires_at: Some(0), weight: 0 }],
+            last_ballot_nonce: Some(0),
+            valid_until: Some(0),
+            ..VoterInfo::new(vec![0; questions_count])
+        };
+        prototype.allowed_voters.insert(Pubkey::default(), voter);
+        prototype.redeemed_vouchers.insert(Hash::default());
+        prototype.attestations.push(NonParticipationAttestation { voter: Pubkey::default(), at: 0 });
+        for question in prototype.questions.iter_mut().filter(|_| seated) {
+            let label = question.options[0].label.clone();
+            question.take_seat(&label, &Pubkey::default(), Some(0));
+        }
+        prototype.pending_delegations.push(PendingDelegation {
+            delegator: Pubkey::default(),
+            delegate: Pubkey::default(),
+            votes: vec![0; questions_count],
+            weight: 0,
+            expires_at: Some(0),
+            scope: DelegationScope::Question(0),
+        });
+
+        let with_voter_space = borsh::object_length(&prototype).unwrap_or(usize::MAX);
+
+        // The view in front of the state only depends on the options
+        (base_space.saturating_add(prototype.view_len()), with_voter_space.saturating_sub(base_space))
+    }
+
+    // Write the view of the vote into the data of its state account, followed by the serialized vote
+    pub fn save(&self, account: &AccountInfo) -> Result<(), ProgramError> {
+        let serialized = borsh::to_vec(self)?;
+        let mut data = account.try_borrow_mut_data()?;
+
+        if self.view_len().saturating_add(serialized.len()) > data.len() {
+            return Err(ProgramError::AccountDataTooSmall); // Return error if the state does not fit
+        }
+
+        let state_offset = self.write_view(&mut data)?;
+        data[state_offset..state_offset + serialized.len()].copy_from_slice(&serialized);
+
+        Ok(())
+    }
+
+    // Deserialize a vote from the data of its state account
+    pub fn load(account: &AccountInfo) -> Result<Self, ProgramError> {
+        let vote = Self::unpack(&account.try_borrow_data()?)?;
+
+        // Partially written or corrupted data can still decode
+        vote.check_invariants()?;
+        Ok(vote)
+    }
+
+    // Decode the data of a state account as it is, without checking the decoded state
+    pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        let state_offset = VoteView::try_from_bytes(data)?.state_offset() as usize;
+        Ok(Self::deserialize(&mut data.get(state_offset..).ok_or(ProgramError::InvalidAccountData)?)?)
+    }
+
+    // Cheap structural checks of decoded state: tallies only name options of their question, every voter has
+    // an entry per question, ballots point at existing options, the tallies cover the ballots on record and the
+    // votes reconcile with the ledger.
+    // Tallies can exceed the ballots on record, removed voters take their ballots but not their votes along
+    fn check_invariants(&self) -> Result<(), ProgramError> {
+        let corrupt = || Err(VoteError::CorruptState.into());
+
+        if self.questions.is_empty() {
+            return corrupt();
+        }
+
+        for question in &self.questions {
+            let is_option = |label: &String| question.options.iter().any(|option| option.label == *label);
+            if !question.votes.keys().all(is_option) || !question.weighted_votes.keys().all(is_option) {
+                return corrupt();
+            }
+        }
+
+        if !self.has_valid_voters() || !self.ledger_reconciles() {
+            return corrupt();
+        }
+
+        for (question, (votes, weighted_votes)) in self.questions.iter().zip(self.ballot_tallies()) {
+            for (label, count) in &votes {
+                if question.votes.get(label).copied().unwrap_or(0) < *count {
+                    return corrupt();
+                }
+            }
+            for (label, weight) in &weighted_votes {
+                if question.weighted_votes.get(label).copied().unwrap_or(0) < *weight {
+                    return corrupt();
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // The votes the voters hold and the ones held in pending delegations add up to the totals of the ledger,
+    // and the voters did not spend more votes than the tallies count. Removed voters take their spent votes along
+    fn ledger_reconciles(&self) -> bool {
+        let totals = self.ledger.totals();
+        totals.len() == self.questions.len()
+            && totals.iter().enumerate().all(|(index, totals)| {
+                let votes_left: u64 = self.allowed_voters.values().map(|voter_info| u64::from(voter_info.votes_left.get(index).copied().unwrap_or(0))).sum();
+                let spent: u64 = self.allowed_voters.values().map(|voter_info| u64::from(voter_info.spent.get(index).copied().unwrap_or(0))).sum();
+                let held: u64 = self.pending_delegations.iter().map(|pending| u64::from(pending.votes.get(index).copied().unwrap_or(0))).sum();
+                totals.outstanding() == Some(votes_left) && totals.held == held && spent <= totals.tallied
+            })
+    }
+
+    // Every voter holds votes and delegations for each question, and their ballots point at existing options
+    fn has_valid_voters(&self) -> bool {
+        let questions_count = self.questions.len();
+
+        self.allowed_voters.values().all(|voter_info| {
+            voter_info.votes_left.len() == questions_count
+                && voter_info.spent.len() == questions_count
+                && voter_info.delegations.iter().all(|delegation| delegation.votes.len() == questions_count)
+                && voter_info.ballots.iter().all(|ballot| {
+                    self.questions.get(ballot.question_index as usize).is_some_and(|question| (ballot.option_index.index()) < question.options.len())
+                })
+        })
+    }
+
+    // Tallies of every question counted from the ballots of the voters, which `has_valid_voters` has to accept
+    fn ballot_tallies(&self) -> Vec<(HashMap<String, u32>, HashMap<String, u64>)> {
+        let mut tallies = vec![(HashMap::new(), HashMap::new()); self.questions.len()];
+
+        for ballot in self.allowed_voters.values().flat_map(|voter_info| voter_info.ballots.iter()) {
+            let (votes, weighted_votes) = &mut tallies[ballot.question_index as usize];
+            let label = &self.questions[ballot.question_index as usize].options[ballot.option_index.index()].label;
+            *votes.entry(label.to_string()).or_insert(0) += 1;
+            *weighted_votes.entry(label.to_string()).or_insert(0) += ballot.weight;
+        }
+
+        tallies
+    }
+
+    // Rebuild the tallies from the ballots on record, possible as long as the voters themselves are intact
+    fn repair_tallies(&mut self) -> Result<(), ProgramError> {
+        if self.questions.is_empty() || !self.has_valid_voters() {
+            return Err(VoteError::CorruptState.into()); // Return error if the ballots cannot be trusted either
+        }
+
+        let tallies = self.ballot_tallies();
+        for (question, (votes, weighted_votes)) in self.questions.iter_mut().zip(tallies) {
+            question.votes = votes;
+            question.weighted_votes = weighted_votes;
+        }
+
+        self.check_invariants()
+    }
+
+    // Entries counted against `max_voters`, voters who opted out keep their seat so the state account fits them
+    fn allowlist_len(&self) -> usize {
+        self.allowed_voters.len() + self.opted_out.len()
+    }
+
+    // Check that one more voter fits in the allowlist
+    fn ensure_allowlist_capacity(&self, voter: &Pubkey) -> Result<(), ProgramError> {
+        if self.allowed_voters.contains_key(voter) {
+            return Ok(()); // Updating an existing entry takes no extra space
+        }
+
+        match self.max_voters {
+            Some(max_voters) if self.allowlist_len() >= max_voters as usize => Err(VoteError::AllowlistFull.into()),
+            _ => Ok(()),
+        }
+    }
+
+    // Method to get voting options of the first question
+    fn get_options(&self) -> &Vec<VoteOption> {
+        &self.questions[0].options
+    }
+
+    fn summary(&self) -> VoteSummary {
+        VoteSummary {
+            id: self.id,
+            title: self.title.to_string(),
+            creator: self.creator,
+            description: self.description.as_ref().map(|description| description.to_string()),
+            questions_count: self.questions.len(),
+            status: self.status.clone(),
+            results_visibility: self.results_visibility,
+            allowlist_visibility: self.allowlist_visibility,
+            per_option_cap: self.per_option_cap,
+            winning_threshold_bps: self.winning_threshold_bps,
+            quorum: self.quorum,
+            deadline: self.deadline,
+            closure: self.closure,
+            runoff_of: self.runoff_of,
+            parent: self.parent,
+            edit_count: self.edits.len() as u32,
+            deposit: self.deposit,
+            pinned: self.pinned,
+            display_order: self.display_order,
+        }
+    }
+
+    // Results with the conviction of the ballots measured at `now`, or at the current slot without one
+    fn full_results(&self, now: Option<Slot>) -> Result<VoteResults, ProgramError> {
+        let slot = self.conviction_slot(now)?;
+        Ok(VoteResults {
+            vote_id: self.id,
+            questions: self.questions.iter().enumerate().map(|(index, question)| question.results(&self.weighted_votes_at(index, slot), self.per_option_cap)).collect(),
+            is_void: matches!(self.status, VoteStatus::Cancelled { .. }),
+            per_option_cap: self.per_option_cap,
+            closure: self.closure,
+            grace_ballots: self.allowed_voters.values().flat_map(|voter_info| voter_info.ballots.iter()).filter(|ballot| ballot.in_grace).count() as u32,
+        })
+    }
+
+    // Weight in basis points of a ballot cast right now
+    fn ballot_weight(&self, voter: &Pubkey) -> Result<u64, ProgramError> {
+        match self.vote_type {
+            VoteType::SingleChoice | VoteType::PointAllocation { .. } | VoteType::Conviction { .. } | VoteType::TokenBurn { .. } => Ok(FULL_WEIGHT_BPS),
+            VoteType::TimeWeighted { .. } => Ok(self.vote_type.weight_at(current_timestamp()?)),
+            VoteType::TokenWeighted => {
+                let voter_info = self.allowed_voters.get(voter).ok_or(ProgramError::InvalidArgument)?;
+                let received = voter_info.received_weight.saturating_sub(self.expired_delegated_weight(voter)?);
+                Ok(voter_info.available_weight().saturating_add(received))
+            }
+        }
+    }
+
+    // Slot to record on a new or changed ballot, only conviction votes read the clock for it
+    fn conviction_cast_slot(&self) -> Result<Option<Slot>, ProgramError> {
+        match self.vote_type {
+            VoteType::Conviction { .. } => Ok(Some(current_slot()?)),
+            _ => Ok(None),
+        }
+    }
+
+    // Slot the conviction of the ballots is measured at, the current one while the vote is open. `None`
+    // outside of conviction votes, whose stored weighted tallies are final
+    fn conviction_slot(&self, now: Option<Slot>) -> Result<Option<Slot>, ProgramError> {
+        if !matches!(self.vote_type, VoteType::Conviction { .. }) {
+            return Ok(None);
+        }
+
+        match (self.conviction_frozen_at, now) {
+            (Some(slot), _) | (None, Some(slot)) => Ok(Some(slot)),
+            (None, None) => Ok(Some(current_slot()?)),
+        }
+    }
+
+    // Stop the conviction of the ballots from growing further once a conviction vote ends
+    fn freeze_conviction(&mut self) -> Result<(), ProgramError> {
+        self.conviction_frozen_at = self.conviction_cast_slot()?;
+        Ok(())
+    }
+
+    // Weighted tallies of a question with the conviction the ballots built up by `slot`. Without a slot,
+    // the stored tallies of the weights the ballots were cast with
+    fn weighted_votes_at(&self, question_index: usize, slot: Option<Slot>) -> Cow<'_, HashMap<String, u64>> {
+        let question = &self.questions[question_index];
+        let Some(slot) = slot else {
+            return Cow::Borrowed(&question.weighted_votes);
+        };
+
+        let mut weighted_votes = HashMap::new();
+        let ballots = self.allowed_voters.values().flat_map(|voter_info| &voter_info.ballots);
+        for ballot in ballots.filter(|ballot| ballot.question_index as usize == question_index) {
+            let label = question.options[ballot.option_index.index()].label.to_string();
+            let weight = ballot.weight.saturating_mul(self.vote_type.conviction_multiplier(ballot.cast_slot, slot));
+            let total: &mut u64 = weighted_votes.entry(label).or_insert(0);
+            *total = total.saturating_add(weight);
+        }
+        Cow::Owned(weighted_votes)
+    }
+
+    // Check that the vote still accepts changes
+    fn ensure_open(&self) -> Result<(), ProgramError> {
+        match self.status {
+            VoteStatus::Open | VoteStatus::Pending => Ok(()),
+            VoteStatus::Closed => Err(ProgramError::InvalidArgument), // Return error if voting is closed
+            VoteStatus::Cancelled { .. } => Err(VoteError::VoteCancelled.into()),
+        }
+    }
+
+    fn winner(&self, question_index: usize) -> Result<Outcome, ProgramError> {
+        match self.status {
+            VoteStatus::Open | VoteStatus::Pending => return Err(VoteError::VoteStillOpen.into()),
+            VoteStatus::Closed => {}
+            VoteStatus::Cancelled { .. } => return Err(VoteError::VoteCancelled.into()), // A void vote has no winner
+        }
+
+        let question = self.questions.get(question_index).ok_or(ProgramError::InvalidArgument)?;
+        let results = question.results(&self.weighted_votes_at(question_index, self.conviction_slot(None)?), self.per_option_cap);
+
+        // Abstentions count toward the quorum
+        if let Some(quorum) = self.quorum {
+            if results.total_votes < quorum {
+                return Ok(Outcome::QuorumNotMet { total_votes: results.total_votes });
+            }
+        }
+
+        // Abstentions and withdrawn options cannot win
+        let candidate = |option: &&OptionResult| !option.abstain && !option.withdrawn;
+        let best = results.options.iter().filter(candidate).map(|option| option.weighted_votes).max().unwrap_or(0);
+        if best == 0 {
+            return Ok(Outcome::NoVotes);
+        }
+
+        let leaders: Vec<usize> = results
+            .options
+            .iter()
+            .enumerate()
+            .filter(|(_, option)| candidate(option) && option.weighted_votes == best)
+            .map(|(index, _)| index)
+            .collect();
+
+        let leading_option = if leaders.len() > 1 {
+            match self.break_tie(question_index, &leaders) {
+                Some(option_index) => option_index,
+                None => return Ok(Outcome::Tie { option_indices: leaders }),
+            }
+        } else {
+            leaders[0]
+        };
+        let share_bps = results.options[leading_option].share_bps;
+        if self.winning_threshold_bps.is_some_and(|threshold| share_bps < threshold) {
+            return Ok(Outcome::NoThresholdMet { leading_option, share_bps });
+        }
+
+        Ok(Outcome::Winner { option_index: leading_option })
+    }
+
+    // Up to `seats` options of the first question with the highest weighted tallies, each with at least
+    // `per_option_quorum` ballots, in rank order. When the last seats go to a group of tied options the tie-break
+    // picks among them one seat at a time, and seats it cannot settle stay empty
+    fn elected(&self) -> Result<Vec<ElectedOption>, ProgramError> {
+        match self.status {
+            VoteStatus::Open | VoteStatus::Pending => return Err(VoteError::VoteStillOpen.into()),
+            VoteStatus::Closed => {}
+            VoteStatus::Cancelled { .. } => return Err(VoteError::VoteCancelled.into()), // A void vote elects nobody
+        }
+
+        let results = self.questions[0].results(&self.weighted_votes_at(0, self.conviction_slot(None)?), self.per_option_cap);
+        if self.quorum.is_some_and(|quorum| results.total_votes < quorum) {
+            return Ok(Vec::new());
+        }
+
+        // Highest tally first, the sort is stable so equal tallies stay in option order
+        let mut candidates: Vec<usize> = results
+            .options
+            .iter()
+            .enumerate()
+            .filter(|(_, option)| !option.abstain && !option.withdrawn && option.weighted_votes > 0 && self.per_option_quorum.map_or(true, |quorum| option.votes >= quorum))
+            .map(|(index, _)| index)
+            .collect();
+        candidates.sort_by_key(|index| std::cmp::Reverse(results.options[*index].weighted_votes));
+
+        let seats = usize::from(self.seats);
+        let mut elected: Vec<usize> = Vec::new();
+        let mut start = 0;
+        while start < candidates.len() {
+            // Candidates with the tally of the first one not placed yet
+            let tally = results.options[candidates[start]].weighted_votes;
+            let end = start + candidates[start..].iter().take_while(|index| results.options[**index].weighted_votes == tally).count();
+            let group = &candidates[start..end];
+            start = end;
+
+            if elected.len() + group.len() <= seats {
+                elected.extend_from_slice(group);
+                continue;
+            }
+
+            let mut tied = group.to_vec();
+            while elected.len() < seats {
+                let Some(pick) = self.break_tie(0, &tied) else {
+                    break;
+                };
+                elected.push(pick);
+                tied.retain(|index| *index != pick);
+            }
+            break;
+        }
+
+        Ok(elected
+            .into_iter()
+            .map(|index| {
+                let option = &results.options[index];
+                ElectedOption { option_index: index, label: option.label.clone(), votes: option.votes, weighted_votes: option.weighted_votes }
+            })
+            .collect())
+    }
+
+    // Option winning a tie between `leaders` of a closed question, if the tie-break settled it
+    fn break_tie(&self, question_index: usize, leaders: &[usize]) -> Option<usize> {
+        match self.tie_break {
+            TieBreak::None => None,
+            TieBreak::FirstListed => leaders.first().copied(),
+            TieBreak::CreatorDecides => {
+                let option_index = *self.tie_resolutions.get(&(question_index as u32))? as usize;
+                leaders.contains(&option_index).then_some(option_index)
+            }
+            TieBreak::Blockhash => {
+                // Every question draws from its own hash of the seed
+                let seed = self.closure?.tie_break_hash?;
+                let draw = hashv(&[seed.as_ref(), &(question_index as u32).to_le_bytes()]).to_bytes();
+                let draw = u64::from_le_bytes(draw[..8].try_into().ok()?);
+                leaders.get((draw % leaders.len() as u64) as usize).copied()
+            }
+        }
+    }
+
+    // Draw a committee of `count` voters who were not banned, seeded by the final results and a slot hash
+    fn sample_voters(&mut self, caller: &Pubkey, count: usize, slot: Slot, slot_hash: &Hash) -> Result<Vec<Pubkey>, ProgramError> {
+        if *caller != self.creator {
+            return Err(ProgramError::InvalidArgument); // Only the creator can draw a sample
+        }
+        match self.status {
+            VoteStatus::Open | VoteStatus::Pending => return Err(VoteError::VoteStillOpen.into()),
+            VoteStatus::Closed => {}
+            VoteStatus::Cancelled { .. } => return Err(VoteError::VoteCancelled.into()),
+        }
+        if self.sample.is_some() {
+            return Err(ProgramError::InvalidArgument); // A second draw would let the creator pick the committee they like
+        }
+
+        let eligible: Vec<Pubkey> = self.allowed_voters.iter().filter(|(_, voter_info)| !voter_info.banned).map(|(voter, _)| *voter).collect();
+        if count == 0 || count > eligible.len() || count > MAX_SAMPLE_SIZE {
+            return Err(ProgramError::InvalidArgument); // Return error if the committee cannot be filled
+        }
+
+        let seed = sampling::sample_seed(&self.full_results(None)?.hash(), slot_hash);
+        let voters = sampling::draw_sample(&seed, &eligible, count);
+        self.sample = Some(VoterSample { seed, slot, voters: voters.clone() });
+        Ok(voters)
+    }
+
+    fn cancel(&mut self, reason: String, caller: &Pubkey, now: i64) -> Result<(), ProgramError> {
+        if *caller != self.creator {
+            return Err(ProgramError::InvalidArgument); // Only the creator can cancel the vote
+        }
+
+        // Co-created votes are cancelled through an approved admin action
+        self.ensure_single_creator()?;
+
+        self.void(Reason::new("reason", reason)?, caller, now)
+    }
+
+    fn void(&mut self, reason: Reason, caller: &Pubkey, now: i64) -> Result<(), ProgramError> {
+        // Only open votes can be cancelled
+        self.ensure_open()?;
+
+        self.freeze_conviction()?;
+        self.status = VoteStatus::Cancelled { reason, at: now };
+        self.closure = Some(ClosureRecord { closed_by: *caller, closed_at: now, reason: ClosureReason::Cancelled, tie_break_hash: None });
+        self.refund_pending_delegations()?;
+
+        Ok(())
+    }
+
+    // Seed of the tie-break of a closing vote, read from the recent blockhashes sysvar following the caller in `accounts`
+    fn closing_tie_break_hash(&self, accounts: &[AccountInfo]) -> Result<Option<Hash>, ProgramError> {
+        if self.tie_break != TieBreak::Blockhash {
+            return Ok(None);
+        }
+
+        let sysvar_account = accounts.get(1).ok_or(ProgramError::NotEnoughAccountKeys)?;
+        latest_blockhash(sysvar_account).map(Some)
+    }
+
+    fn holds_deposit(&self) -> bool {
+        self.deposit.is_some_and(|deposit| deposit.status == DepositStatus::Held)
+    }
+
+    // The deposit goes back to the creator when they end the vote before its deadline, to the treasury otherwise
+    fn refunds_deposit(&self, by_creator: bool, now: i64) -> bool {
+        by_creator && self.deadline.map_or(true, |deadline| now <= deadline)
+    }
+
+    // Check the accounts moving the held deposit, before the vote ends so a wrong list leaves it untouched.
+    // `accounts` end with [vault, recipient, system program]
+    fn check_deposit_accounts(&self, by_creator: bool, now: i64, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+        let Some(deposit) = self.deposit.filter(|deposit| deposit.status == DepositStatus::Held) else {
+            return Ok(());
+        };
+
+        let [_, .., vault, recipient, system_program_account] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys); // Return error if the deposit accounts are missing
+        };
+        check_unique_accounts(&[vault.key, recipient.key, system_program_account.key])?;
+        let expected_recipient = if self.refunds_deposit(by_creator, now) { self.creator } else { deposit.treasury };
+        if *vault.key != deposit.vault || *recipient.key != expected_recipient || *system_program_account.key != system_program::id() {
+            return Err(ProgramError::InvalidArgument); // Return error if the deposit accounts are wrong
+        }
+        Ok(())
+    }
+
+    // Empty the vault of an ended vote into the account `check_deposit_accounts` expects
+    fn settle_deposit(&mut self, by_creator: bool, now: i64, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+        self.check_deposit_accounts(by_creator, now, accounts)?;
+        let refund = self.refunds_deposit(by_creator, now);
+        let vote_id = self.id;
+        let Some(deposit) = self.deposit.as_mut().filter(|deposit| deposit.status == DepositStatus::Held) else {
+            return Ok(());
+        };
+        let [.., vault, recipient, system_program_account] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        invoke_signed(
+            &system_instruction::transfer(vault.key, recipient.key, vault.lamports()),
+            &[vault.clone(), recipient.clone(), system_program_account.clone()],
+            &[&[b"vote", deposit.registry.as_ref(), &vote_id.to_le_bytes(), &[deposit.bump]]],
+        )?;
+
+        deposit.status = if refund { DepositStatus::Refunded } else { DepositStatus::Forfeited };
+        Ok(())
+    }
+
+    // Close the vote, returns false when it was already closed. `accounts` start with the caller
+    fn close(&mut self, closed_by: &Pubkey, reason: ClosureReason, now: i64, accounts: &[AccountInfo]) -> Result<bool, ProgramError> {
+        if let VoteStatus::Cancelled { .. } = self.status {
+            return Err(VoteError::VoteCancelled.into()); // A cancelled vote cannot be closed
+        }
+        if self.status == VoteStatus::Closed {
+            return Ok(false); // Closing again changes nothing
+        }
+
+        if self.min_close_at.is_some_and(|min_close_at| now < min_close_at) {
+            return Err(VoteError::MinimumDurationNotElapsed.into());
+        }
+
+        let tie_break_hash = self.closing_tie_break_hash(accounts)?;
+
+        self.close_with(ClosureRecord { closed_by: *closed_by, closed_at: now, reason, tie_break_hash })?;
+        Ok(true)
+    }
+
+    // End the vote with `closure`, shared with the replay of its `VoteClosed` event
+    fn close_with(&mut self, closure: ClosureRecord) -> Result<(), ProgramError> {
+        self.freeze_conviction()?;
+        self.status = VoteStatus::Closed; // Close the vote
+        self.closure = Some(closure);
+        self.refund_pending_delegations()?;
+        self.emit(VoteEvent::VoteClosed { vote_id: self.id, closure, slot: current_slot()? });
+        self.record_change(ChangeKind::Closed)
+    }
+
+    // Log an event of the vote under its next sequence number
+    fn emit(&mut self, event: VoteEvent) {
+        let seq = self.event_seq;
+        self.event_seq = seq.saturating_add(1);
+
+        if !self.events_muted {
+            EventEnvelope { version: EVENT_VERSION, seq, event }.emit();
+        }
+    }
+
+    // Append a change to the changelog, evicting the oldest entry when it is full
+    fn record_change(&mut self, kind: ChangeKind) -> Result<(), ProgramError> {
+        let at = current_timestamp()?;
+        increase(&mut self.latest_change_seq, 1)?;
+
+        if self.changelog.len() >= MAX_CHANGELOG_ENTRIES {
+            self.changelog.remove(0);
+        }
+        self.changelog.push(ChangeEntry { seq: self.latest_change_seq, kind, at });
+
+        if matches!(kind, ChangeKind::BallotCast | ChangeKind::BallotRetracted) {
+            self.record_history()?;
+        }
+        self.update_milestones(at);
+        Ok(())
+    }
+
+    // Raise the milestones the vote meets at `now` and has not raised yet. Raised flags stay even when
+    // the vote later falls back below them, a retracted ballot does not lower the quorum flag
+    fn update_milestones(&mut self, now: i64) {
+        let quorum_reached = self.quorum.is_some_and(|quorum| {
+            self.questions.iter().all(|question| {
+                let total: u32 = question.options.iter().map(|option| question.votes.get(option.label.as_str()).copied().unwrap_or(0)).sum();
+                total >= quorum
+            })
+        });
+        let (eligible, participating) = self.turnout();
+        let majority_turnout = eligible > 0 && u64::from(participating) * 2 > u64::from(eligible);
+        let deadline_soon = self.deadline.is_some_and(|deadline| deadline.saturating_sub(now) <= DEADLINE_SOON_WINDOW);
+
+        let met = [quorum_reached, majority_turnout, deadline_soon];
+        for (milestone, met) in Milestones::ALL.into_iter().zip(met) {
+            if met && !self.milestones.contains(milestone) {
+                self.milestones.insert(milestone);
+                self.emit(VoteEvent::MilestoneReached { vote_id: self.id, milestone });
+            }
+        }
+    }
+
+    // Store the current tallies in the bucket of the current slot, starting a new bucket when the
+    // slot lies past the latest one
+    fn record_history(&mut self) -> Result<(), ProgramError> {
+        let Some(bucket_slots) = self.history_bucket_slots else {
+            return Ok(()); // The vote does not track its history
+        };
+
+        let slot = current_slot()?;
+        let upto_slot = (slot / bucket_slots).saturating_mul(bucket_slots).saturating_add(bucket_slots - 1);
+        let per_option_counts = self
+            .questions
+            .iter()
+            .map(|question| question.options.iter().map(|option| question.votes.get(option.label.as_str()).copied().unwrap_or(0)).collect())
+            .collect();
+
+        match self.history.last_mut() {
+            Some(bucket) if bucket.upto_slot == upto_slot => bucket.per_option_counts = per_option_counts,
+            _ => {
+                if self.history.len() >= MAX_HISTORY_BUCKETS {
+                    self.history.remove(0);
+                }
+                self.history.push(HistoryBucket { upto_slot, per_option_counts });
+            }
+        }
+        Ok(())
+    }
+
+    // Changes after `seq`, flagged as overflowing when the first of them was evicted
+    fn changes_since(&self, seq: u64) -> Changes {
+        let entries = self.changelog.iter().filter(|entry| entry.seq > seq).copied().collect();
+        let overflow = self.changelog.first().is_some_and(|entry| entry.seq > seq.saturating_add(1));
+
+        Changes { entries, latest_seq: self.latest_change_seq, overflow }
+    }
+
+    fn ensure_single_creator(&self) -> Result<(), ProgramError> {
+        if !self.creators.is_empty() {
+            return Err(VoteError::ApprovalRequired.into());
+        }
+
+        Ok(())
+    }
+
+    // Record the approval of a creator on the pending action, returns the action once it has enough approvals
+    fn approve_action(&mut self, action: &AdminAction, approver: &Pubkey, now: i64) -> Result<Option<AdminAction>, ProgramError> {
+        if !self.creators.contains(approver) {
+            return Err(ProgramError::InvalidArgument); // Only the creators can approve admin actions
+        }
+
+        let pending = self.pending_action.as_mut().ok_or(ProgramError::InvalidArgument)?; // Return error if nothing was proposed
+        if pending.action != *action {
+            return Err(ProgramError::InvalidArgument); // Return error if the approver signed a different action
+        }
+        if now > pending.expires_at {
+            return Err(VoteError::PendingActionExpired.into());
+        }
+        if pending.approvals.contains(approver) {
+            return Err(VoteError::AlreadyApproved.into());
+        }
+
+        pending.approvals.push(*approver);
+
+        if pending.approvals.len() < self.approval_threshold as usize {
+            return Ok(None);
+        }
+
+        Ok(self.pending_action.take().map(|pending| pending.action))
+    }
+
+    // Check if the caller may read data protected by the given visibility. The key is taken as it is, reads through
+    // accounts only pass one that signed for restricted data, see `viewer_key`
+    fn can_view(&self, visibility: Visibility, caller: &Pubkey) -> bool {
+        if self.observers.contains(caller) {
+            return true;
+        }
+
+        match visibility {
+            Visibility::Public => true,
+            Visibility::AllowlistOnly => *caller == self.creator || self.is_voter_allowed(caller),
+            Visibility::CreatorOnly => *caller == self.creator,
+        }
+    }
+
+    fn add_allowed_voter(&mut self, voter: Pubkey, weight: u64, valid_until: Option<i64>, caller: &Pubkey) -> Result<(), ProgramError>{
+        if *caller != self.creator {
+            return Err(ProgramError::InvalidArgument); // Return error if not the creator
+        }
+
+        // Check if the voting is closed
+        self.ensure_open()?;
+
+        // A struck voter cannot be added back, neither can one who left for good
+        self.ensure_not_banned(&voter)?;
+        self.ensure_not_opted_out(&voter)?;
+
+        // Check if the allowlist still has room
+        self.ensure_allowlist_capacity(&voter)?;
+
+        if let Some(valid_until) = valid_until {
+            if valid_until < current_timestamp()? {
+                return Err(ProgramError::InvalidArgument); // Return error if the entry would be expired already
+            }
+        }
+
+        let mut new_voter = self.new_voter()?;
+        new_voter.weight = weight;
+        new_voter.valid_until = valid_until;
+
+        self.enter_voter(voter, new_voter, 1)?; // Initialize with 1 vote per question
+
+        self.emit(VoteEvent::VoterAdded { vote_id: self.id, voter, weight, valid_until, slot: current_slot()?, timestamp: current_timestamp()? });
+        self.record_change(ChangeKind::VoterAdded)
+    }
+
+    fn remove_allowed_voter(&mut self, voter: &Pubkey, caller: &Pubkey) -> Result<(), ProgramError> {
+        // Check if the calling address is the creator of the vote
+        if *caller != self.creator {
+            return Err(ProgramError::InvalidArgument); // Return error if not the creator
+        }
+
+        // Check if the voting is closed
+        self.ensure_open()?;
+
+        // Removing a struck voter would lift the ban
+        self.ensure_not_banned(voter)?;
+
+        if !self.allowed_voters.contains_key(voter) {
+            return Err(ProgramError::InvalidArgument); // Return error if the voter is not found
+        }
+
+        self.drop_voter(voter)
+    }
+
+    // Replace the voter by an anonymous key everywhere in the ended vote: their allowlist entry with its ballots
+    // moves under `scrubbed_key`, so the tallies, the turnout and the ledger still add up, and delegations,
+    // seats, write-ins, the committee sample and the ledger name the anonymous key instead. Creators cannot
+    // be scrubbed, they sign the record of the vote. Returns the number of anonymized ballots
+    fn scrub_voter(&mut self, voter: &Pubkey) -> Result<u32, ProgramError> {
+        if matches!(self.status, VoteStatus::Open | VoteStatus::Pending) {
+            return Err(VoteError::VoteStillOpen.into());
+        }
+        if *voter == self.creator || self.creators.contains(voter) {
+            return Err(ProgramError::InvalidArgument); // Return error if the voter created the vote
+        }
+
+        let voter_info = self.allowed_voters.remove(voter).ok_or(ProgramError::InvalidArgument)?; // Return error if the voter is not found
+        self.scrubbed_voters = self.scrubbed_voters.checked_add(1).ok_or(ProgramError::ArithmeticOverflow)?;
+        let anonymous = scrubbed_key(self.scrubbed_voters);
+        let ballots = voter_info.ballots.len() as u32;
+        self.allowed_voters.insert(anonymous, voter_info);
+
+        let rename = |key: &mut Pubkey| {
+            if key == voter {
+                *key = anonymous;
+            }
+        };
+        for voter_info in self.allowed_voters.values_mut() {
+            if let Some(delegate) = &mut voter_info.delegate {
+                rename(delegate);
+            }
+            voter_info.delegations.iter_mut().for_each(|delegation| rename(&mut delegation.delegate));
+        }
+        for pending in &mut self.pending_delegations {
+            rename(&mut pending.delegator);
+            rename(&mut pending.delegate);
+        }
+        for question in &mut self.questions {
+            for participants in question.participants.values_mut() {
+                if participants.remove(voter) {
+                    participants.insert(anonymous);
+                }
+            }
+            question.options.iter_mut().filter_map(|option| option.proposed_by.as_mut()).for_each(rename);
+        }
+        if let Some(sample) = &mut self.sample {
+            sample.voters.iter_mut().for_each(rename);
+        }
+        self.ledger.rename(voter, anonymous);
+        self.attestations.iter_mut().for_each(|attestation| rename(&mut attestation.voter));
+        self.observers.retain(|observer| observer != voter);
+        self.opted_out.retain(|opted_out| opted_out != voter);
+
+        self.record_change(ChangeKind::VoterRemoved)?;
+        Ok(ballots)
+    }
+
+    // Take the allowlist entry of the voter out. Their ballots stand, delegations to them would be orphaned
+    // and return to their delegators, the creator included when they are a voter themselves
+    fn drop_voter(&mut self, voter: &Pubkey) -> Result<(), ProgramError> {
+        self.return_received_delegations(voter)?;
+        self.lapse_pending_delegations(voter)?;
+
+        self.forfeit_votes(voter)?;
+        self.allowed_voters.remove(voter);
+        self.record_change(ChangeKind::VoterRemoved)
+    }
+
+    // A voter taking themselves off the allowlist, under the same rules as a removal by the creator. Votes
+    // they handed to delegates are taken back as far as the delegates have not spent them
+    fn leave(&mut self, voter: &Pubkey, do_not_readd: bool) -> Result<(), ProgramError> {
+        self.ensure_open()?;
+
+        // Leaving would lift the ban
+        self.ensure_not_banned(voter)?;
+
+        let voter_info = self.allowed_voters.get(voter).ok_or(ProgramError::InvalidArgument)?; // Return error if the voter is not found
+        let delegations = voter_info.delegations.clone();
+        self.take_back_delegations(&delegations)?;
+
+        self.drop_voter(voter)?;
+        if do_not_readd {
+            self.opted_out.push(*voter);
+        }
+        Ok(())
+    }
+
+    fn prune_expired_voters(&mut self, max: usize, now: i64) -> Result<u32, ProgramError> {
+        self.ensure_open()?;
+
+        let mut expired: Vec<(i64, Pubkey)> = self
+            .allowed_voters
+            .iter()
+            .filter(|(_, voter_info)| !voter_info.banned)
+            .filter_map(|(voter, voter_info)| voter_info.valid_until.filter(|valid_until| *valid_until < now).map(|valid_until| (valid_until, *voter)))
+            .collect();
+        expired.sort();
+        expired.truncate(max);
+
+        for (_, voter) in &expired {
+            self.drop_voter(voter)?;
+        }
+        Ok(expired.len() as u32)
+    }
+
+    // Whether the entry of the voter still holds exactly the votes it was given: no ballots and no delegations,
+    // neither handed out, promised nor received
+    fn holds_granted_votes(&self, voter: &Pubkey, voter_info: &VoterInfo) -> bool {
+        voter_info.ballots.is_empty()
+            && voter_info.delegations.is_empty()
+            && voter_info.delegate.is_none()
+            && voter_info.held_weight == 0
+            && !voter_info.via_delegation_only
+            && !self.allowed_voters.values().any(|other| other.delegations.iter().any(|delegation| delegation.delegate == *voter))
+    }
+
+    // Changes from the allowlist to `desired`. Struck voters and voters who opted out stay as they are, so
+    // do entries created by delegations. Voters who used votes keep their allotment, the others are updated
+    fn diff_allowlist(&self, desired: &[(Pubkey, u32)]) -> Result<AllowlistDiff, ProgramError> {
+        let mut desired = desired.to_vec();
+        desired.sort();
+        if desired.windows(2).any(|pair| pair[0].0 == pair[1].0) || desired.iter().any(|(_, votes)| *votes == 0) {
+            return Err(ProgramError::InvalidArgument); // Return error if a voter is listed twice or without votes
+        }
+
+        let mut diff = AllowlistDiff::default();
+        for (voter, votes) in &desired {
+            match self.allowed_voters.get(voter) {
+                None if !self.opted_out.contains(voter) => diff.to_add.push((*voter, *votes)),
+                Some(voter_info) if self.holds_granted_votes(voter, voter_info) && voter_info.votes_left.iter().any(|left| left != votes) => {
+                    diff.to_update.push((*voter, *votes))
+                }
+                _ => {}
+            }
+        }
+
+        let mut removed: Vec<Pubkey> = self
+            .allowed_voters
+            .iter()
+            .filter(|(voter, voter_info)| !voter_info.banned && !voter_info.via_delegation_only && desired.binary_search_by_key(voter, |(desired, _)| desired).is_err())
+            .map(|(voter, _)| *voter)
+            .collect();
+        removed.sort();
+        diff.to_remove = removed;
+
+        Ok(diff)
+    }
+
+    // Apply a diff of `diff_allowlist`. Removals follow the rules of `remove_allowed_voter`, the ballots of removed
+    // voters stand. A diff gone stale fails, e.g. when a voter to update cast a ballot since
+    fn apply_allowlist_diff(&mut self, diff: &AllowlistDiff, caller: &Pubkey) -> Result<(), ProgramError> {
+        if *caller != self.creator {
+            return Err(ProgramError::InvalidArgument); // Return error if not the creator
+        }
+        self.ensure_open()?;
+
+        if diff.len() > MAX_BATCH_VOTERS {
+            msg!("Diff of {} changes, the limit is {}, apply the pages of `AllowlistDiff::pages` one by one", diff.len(), MAX_BATCH_VOTERS);
+            return Err(VoteError::BatchTooLarge.into());
+        }
+
+        let mut voters = HashSet::new();
+        let all_voters = diff.to_remove.iter().chain(diff.to_update.iter().map(|(voter, _)| voter)).chain(diff.to_add.iter().map(|(voter, _)| voter));
+        if !all_voters.into_iter().all(|voter| voters.insert(*voter)) {
+            return Err(ProgramError::InvalidArgument); // Return error if a voter has several changes
+        }
+
+        for voter in &diff.to_remove {
+            self.remove_allowed_voter(voter, caller)?;
+        }
+
+        for (voter, votes) in &diff.to_update {
+            let voter_info = self.allowed_voters.get(voter).ok_or(ProgramError::InvalidArgument)?; // Return error if the voter is not found
+            if *votes == 0 || !self.holds_granted_votes(voter, voter_info) {
+                return Err(ProgramError::InvalidArgument); // Return error if the voter used votes since the diff
+            }
+
+            self.forfeit_votes(voter)?;
+            self.grant_votes(voter, *votes)?;
+            self.record_change(ChangeKind::VoterUpdated)?;
+        }
+
+        for (voter, votes) in &diff.to_add {
+            if *votes == 0 || self.allowed_voters.contains_key(voter) {
+                return Err(ProgramError::InvalidArgument); // Return error if the voter is already allowed
+            }
+
+            self.add_allowed_voter(*voter, 1, None, caller)?;
+            self.grant_votes(voter, *votes - 1)?; // On top of the vote the entry came with
+        }
+
+        Ok(())
+    }
+
+    // Replace the title or the description, recording the hash of the old value. Edits stay possible
+    // after the first ballot, clients flag edited votes through the edit count of the summary
+    fn edit(&mut self, caller: &Pubkey, field: EditedField, value: Option<&str>) -> Result<(), ProgramError> {
+        if *caller != self.creator {
+            return Err(ProgramError::InvalidArgument); // Return error if not the creator
+        }
+        self.ensure_open()?;
+
+        if self.edits.len() >= MAX_EDITS {
+            return Err(ProgramError::InvalidArgument); // Return error if the audit trail is full
+        }
+
+        let old_hash = match field {
+            EditedField::Title => {
+                let title = Title::new("title", value.ok_or(ProgramError::InvalidArgument)?)?; // A vote always has a title
+                hash(std::mem::replace(&mut self.title, title).as_bytes())
+            }
+            EditedField::Description => {
+                let description = value.map(|value| Description::new("description", value)).transpose()?;
+                hash(std::mem::replace(&mut self.description, description).as_deref().unwrap_or("").as_bytes())
+            }
+        };
+
+        self.edits.push(EditRecord { field, old_hash, at: current_timestamp()?, by: *caller });
+        self.record_change(ChangeKind::Edited)
+    }
+
+    // Pin the image of an option of the first question. Only possible before the first ballot,
+    // so nobody voted for an option whose picture changed afterwards
+    fn set_option_content_hash(&mut self, caller: &Pubkey, index: usize, content_hash: Option<[u8; 32]>) -> Result<(), ProgramError> {
+        if *caller != self.creator {
+            return Err(ProgramError::InvalidArgument); // Return error if not the creator
+        }
+        self.ensure_open()?;
+
+        if self.allowed_voters.values().any(|voter_info| !voter_info.ballots.is_empty()) {
+            return Err(VoteError::BallotsAlreadyCast.into());
+        }
+
+        let option = self.questions[0].options.get_mut(index).ok_or(ProgramError::InvalidArgument)?; // Return error if the option does not exist
+        option.content_hash = content_hash;
+        Ok(())
+    }
+
+    // Add or replace the translation of the option labels of the first question. Like the content hashes only
+    // possible before the first ballot, so nobody voted on a label that read differently afterwards
+    fn set_translation(&mut self, caller: &Pubkey, lang: LangCode, labels: Vec<Label>) -> Result<(), ProgramError> {
+        if *caller != self.creator {
+            return Err(ProgramError::InvalidArgument); // Return error if not the creator
+        }
+        self.ensure_open()?;
+
+        if self.allowed_voters.values().any(|voter_info| !voter_info.ballots.is_empty()) {
+            return Err(VoteError::BallotsAlreadyCast.into());
+        }
+        if labels.len() != self.get_options().len() {
+            return Err(ProgramError::InvalidArgument); // Return error if the labels do not match the options one to one
+        }
+
+        match self.translations.iter().position(|(known, _)| *known == lang) {
+            Some(position) => self.translations[position].1 = labels,
+            None if self.translations.len() < MAX_TRANSLATIONS => self.translations.push((lang, labels)),
+            None => return Err(ProgramError::InvalidArgument), // Return error if there is no room for another language
+        }
+        Ok(())
+    }
+
+    // Options of the first question with the labels of `lang`, or the labels of the options without a translation
+    fn localized_options(&self, lang: LangCode) -> Vec<VoteOption> {
+        let labels = self.translations.iter().find(|(known, _)| *known == lang).map(|(_, labels)| labels.as_slice()).unwrap_or_default();
+        self.get_options()
+            .iter()
+            .enumerate()
+            .map(|(index, option)| VoteOption { label: labels.get(index).cloned().unwrap_or_else(|| option.label.clone()), ..option.clone() })
+            .collect()
+    }
+
+    // Observers only read, so they can be named and dropped after the vote closed too
+    fn add_observer(&mut self, observer: Pubkey, caller: &Pubkey) -> Result<(), ProgramError> {
+        if *caller != self.creator {
+            return Err(ProgramError::InvalidArgument); // Return error if not the creator
+        }
+        if self.observers.contains(&observer) || self.observers.len() >= MAX_OBSERVERS {
+            return Err(ProgramError::InvalidArgument); // Return error if the observer is known or there is no room
+        }
+
+        self.observers.push(observer);
+        Ok(())
+    }
+
+    fn remove_observer(&mut self, observer: &Pubkey, caller: &Pubkey) -> Result<(), ProgramError> {
+        if *caller != self.creator {
+            return Err(ProgramError::InvalidArgument); // Return error if not the creator
+        }
+
+        let position = self.observers.iter().position(|known| known == observer).ok_or(ProgramError::InvalidArgument)?; // Return error if the observer is not found
+        self.observers.remove(position);
+        Ok(())
+    }
+
+    // The creator and the observers certify the results once each. The certification keeps the hash of
+    // the results at signing, so later changes of the results show up as a mismatch
+    fn certify_results(&mut self, caller: &Pubkey, now: i64) -> Result<Hash, ProgramError> {
+        match self.status {
+            VoteStatus::Open | VoteStatus::Pending => return Err(VoteError::VoteStillOpen.into()), // Return error if the results can still change
+            VoteStatus::Cancelled { .. } => return Err(VoteError::VoteCancelled.into()), // Return error if there are no results to certify
+            VoteStatus::Closed => {}
+        }
+        if *caller != self.creator && !self.observers.contains(caller) {
+            return Err(ProgramError::InvalidArgument); // Return error if the caller is neither the creator nor an observer
+        }
+        // Observers dropped after certifying keep their certification, so new ones are still bounded
+        if self.certifications.iter().any(|certification| certification.certifier == *caller) || self.certifications.len() > MAX_OBSERVERS {
+            return Err(ProgramError::InvalidArgument); // Return error if the caller already certified or there is no room
+        }
+
+        let results_hash = self.full_results(None)?.hash();
+        self.certifications.push(Certification { certifier: *caller, results_hash, certified_at: now });
+        Ok(results_hash)
+    }
+
+    // Enough certifications of the current results, votes requiring none are never certified
+    fn is_certified(&self) -> Result<bool, ProgramError> {
+        if self.required_certifications == 0 || self.status != VoteStatus::Closed {
+            return Ok(false);
+        }
+
+        let results_hash = self.full_results(None)?.hash();
+        let matching = self.certifications.iter().filter(|certification| certification.results_hash == results_hash).count();
+        Ok(matching >= usize::from(self.required_certifications))
+    }
+
+    // An allowlisted voter states once that they took no part in the closed vote. Ballots and delegations,
+    // including votes a delegate spent for them, leave a mark on the entry that retractions and returned
+    // delegations do not clear
+    fn attest_non_participation(&mut self, voter: &Pubkey, now: i64) -> Result<(), ProgramError> {
+        match self.status {
+            VoteStatus::Open | VoteStatus::Pending => return Err(VoteError::VoteStillOpen.into()), // Return error if the voter can still take part
+            VoteStatus::Cancelled { .. } => return Err(VoteError::VoteCancelled.into()),
+            VoteStatus::Closed => {}
+        }
+        let voter_info = self.allowed_voters.get(voter).ok_or(ProgramError::InvalidArgument)?; // Return error if the voter is not allowed
+        if voter_info.participated {
+            return Err(VoteError::VoterParticipated.into());
+        }
+        if self.attestations.iter().any(|attestation| attestation.voter == *voter) {
+            return Err(ProgramError::InvalidArgument); // Return error if the voter already attested
+        }
+
+        self.attestations.push(NonParticipationAttestation { voter: *voter, at: now });
+        Ok(())
+    }
+
+    // Give the unspent votes delegated to `delegate` back to their delegators, like expired delegations.
+    // Weight the delegate already voted with stays spent
+    fn return_received_delegations(&mut self, delegate: &Pubkey) -> Result<(), ProgramError> {
+        let Some(delegate_info) = self.allowed_voters.get(delegate) else {
+            return Ok(());
+        };
+        let weight_spent = !delegate_info.ballots.is_empty();
+        let mut unspent = delegate_info.votes_left.clone();
+
+        let mut delegators: Vec<Pubkey> = self
+            .allowed_voters
+            .iter()
+            .filter(|(_, voter_info)| voter_info.delegations.iter().any(|delegation| delegation.delegate == *delegate))
+            .map(|(delegator, _)| *delegator)
+            .collect();
+        delegators.sort();
+
+        for delegator in delegators {
+            let voter_info = self.allowed_voters.get_mut(&delegator).unwrap(); // Safely extract the delegator as it was just listed
+            let mut returned = vec![0; unspent.len()];
+            for delegation in voter_info.delegations.iter_mut().filter(|delegation| delegation.delegate == *delegate) {
+                for ((moved_votes, unspent_votes), returned_votes) in delegation.votes.iter_mut().zip(unspent.iter_mut()).zip(returned.iter_mut()) {
+                    let back = (*moved_votes).min(*unspent_votes);
+                    *returned_votes += back;
+                    *unspent_votes -= back;
+                    *moved_votes = 0;
+                }
+            }
+
+            voter_info.delegations.retain(|delegation| delegation.delegate != *delegate || (delegation.weight > 0 && weight_spent));
+            voter_info.delegate = voter_info.delegations.last().map(|delegation| delegation.delegate);
+
+            for (question_index, returned_votes) in returned.into_iter().enumerate() {
+                self.move_votes(question_index, CreditEndpoint::Voter(*delegate), CreditEndpoint::Voter(delegator), returned_votes, CreditCause::DelegationReturned)?;
+            }
+        }
+        Ok(())
+    }
+
+    // `message` is the signed `Voucher::to_message`, its hash marks the voucher as redeemed
+    fn redeem_invitation(&mut self, voucher: &Voucher, message: &[u8], now: i64) -> Result<(), ProgramError> {
+        // Check if the voting is closed
+        self.ensure_open()?;
+
+        // Check if the voucher has expired
+        if now > voucher.expiry {
+            return Err(ProgramError::InvalidArgument); // Return error if the voucher is expired
+        }
+
+        self.ensure_not_banned(&voucher.voter)?;
+        self.ensure_not_opted_out(&voucher.voter)?;
+
+        // Check if the allowlist still has room
+        self.ensure_allowlist_capacity(&voucher.voter)?;
+
+        // Each voucher can only be redeemed once
+        let voucher_hash = hash(message);
+        if !self.redeemed_vouchers.insert(voucher_hash) {
+            return Err(ProgramError::InvalidArgument); // Return error if the voucher was already used
+        }
+
+        let new_voter = self.new_voter()?;
+
+        self.enter_voter(voucher.voter, new_voter, voucher.votes)?;
+
+        self.record_change(ChangeKind::VoterAdded)
+    }
+
+    // Add a voter who passed the eligibility check of an open vote, with one vote per question
+    fn register_voter(&mut self, voter: &Pubkey) -> Result<(), ProgramError> {
+        self.ensure_open()?;
+        self.ensure_not_banned(voter)?;
+
+        if self.allowed_voters.contains_key(voter) {
+            return Err(ProgramError::InvalidArgument); // Return error if the voter already joined
+        }
+
+        // Joining by themselves lifts an opt-out, the seat it kept is taken over
+        if !self.opted_out.contains(voter) {
+            self.ensure_allowlist_capacity(voter)?;
+        }
+
+        let new_voter = self.new_voter()?;
+        self.opted_out.retain(|opted_out| opted_out != voter);
+        self.enter_voter(*voter, new_voter, 1)?;
+
+        self.record_change(ChangeKind::VoterAdded)
+    }
+
+    fn is_voter_allowed(&self, voter: &Pubkey) -> bool {
+        self.allowed_voters.get(voter).is_some_and(|voter_info| !voter_info.banned)
+    }
+
+    // Fresh allowlist entry without votes, stamped with the clock only when the vote has a per-voter window
+    fn new_voter(&self) -> Result<VoterInfo, ProgramError> {
+        let added_at = if self.per_voter_window.is_some() { current_timestamp()? } else { 0 };
+        Ok(VoterInfo { added_at, ..VoterInfo::new(vec![0; self.questions.len()]) })
+    }
+
+    // Put the entry on the allowlist with `votes` votes per question. An entry it replaces forfeits its votes
+    fn enter_voter(&mut self, voter: Pubkey, voter_info: VoterInfo, votes: u32) -> Result<(), ProgramError> {
+        self.forfeit_votes(&voter)?;
+        self.allowed_voters.insert(voter, voter_info);
+        self.grant_votes(&voter, votes)
+    }
+
+    fn grant_votes(&mut self, voter: &Pubkey, votes: u32) -> Result<(), ProgramError> {
+        for question_index in 0..self.questions.len() {
+            self.move_votes(question_index, CreditEndpoint::Void, CreditEndpoint::Voter(*voter), votes, CreditCause::Grant)?;
+        }
+        Ok(())
+    }
+
+    // Give up every vote the voter holds, if they are allowed at all
+    fn forfeit_votes(&mut self, voter: &Pubkey) -> Result<(), ProgramError> {
+        let Some(votes_left) = self.allowed_voters.get(voter).map(|voter_info| voter_info.votes_left.clone()) else {
+            return Ok(());
+        };
+        for (question_index, votes) in votes_left.into_iter().enumerate() {
+            self.move_votes(question_index, CreditEndpoint::Voter(*voter), CreditEndpoint::Void, votes, CreditCause::Forfeit)?;
+        }
+        Ok(())
+    }
+
+    fn move_votes(&mut self, question_index: usize, from: CreditEndpoint, to: CreditEndpoint, amount: u32, cause: CreditCause) -> Result<(), ProgramError> {
+        self.ledger.transfer(&mut self.allowed_voters, question_index, from, to, amount, cause)
+    }
+
+    // Last Unix timestamp the voter may cast a ballot at, if the vote has a per-voter window
+    fn voter_deadline(&self, voter_info: &VoterInfo) -> Option<i64> {
+        self.per_voter_window.map(|window| voter_info.added_at.saturating_add(window))
+    }
+
+    // Check that the delegation policy lets votes go to the delegate
+    fn ensure_delegate_allowed(&self, delegate: &Pubkey) -> Result<(), ProgramError> {
+        if self.delegation_policy == DelegationPolicy::AllowlistOnly && !self.allowed_voters.contains_key(delegate) {
+            return Err(VoteError::DelegateNotAllowed.into());
+        }
+        Ok(())
+    }
+
+    // Delegated power the voter holds from others: the weight of weight delegations and one vote for every
+    // delegation of votes, however many questions it covers. Delegations count until they are taken back
+    // or reclaimed, whether or not the delegate spent the votes
+    fn received_delegations(&self, delegate: &Pubkey) -> u64 {
+        self.allowed_voters
+            .values()
+            .flat_map(|voter_info| &voter_info.delegations)
+            .filter(|delegation| delegation.delegate == *delegate)
+            .map(|delegation| if delegation.weight > 0 { delegation.weight } else { u64::from(delegation.votes.iter().copied().max().unwrap_or(0)) })
+            .sum()
+    }
+
+    // Return error if `amount` more delegated power would exceed the cap of the delegate
+    fn ensure_delegate_capacity(&self, delegate: &Pubkey, amount: u64) -> Result<(), ProgramError> {
+        match self.max_received_delegations {
+            Some(cap) if self.received_delegations(delegate).saturating_add(amount) > u64::from(cap) => Err(VoteError::DelegateCapReached.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn ensure_not_banned(&self, voter: &Pubkey) -> Result<(), ProgramError> {
+        match self.allowed_voters.get(voter) {
+            Some(voter_info) if voter_info.banned => Err(VoteError::VoterBanned.into()),
+            _ => Ok(()),
+        }
+    }
+
+    // Ballots and delegations wait until the prerequisite passed and the vote opened
+    fn ensure_not_pending(&self) -> Result<(), ProgramError> {
+        if self.status == VoteStatus::Pending {
+            return Err(VoteError::PrerequisiteNotMet.into());
+        }
+        Ok(())
+    }
+
+    // Entries with a limited term neither vote nor delegate past it, the clock is only read for them
+    fn ensure_not_expired(&self, voter: &Pubkey) -> Result<(), ProgramError> {
+        if let Some(valid_until) = self.allowed_voters.get(voter).and_then(|voter_info| voter_info.valid_until) {
+            if current_timestamp()? > valid_until {
+                return Err(VoteError::VoterExpired.into());
+            }
+        }
+        Ok(())
+    }
+
+    fn ensure_not_opted_out(&self, voter: &Pubkey) -> Result<(), ProgramError> {
+        if self.opted_out.contains(voter) {
+            return Err(VoteError::VoterOptedOut.into());
+        }
+        Ok(())
+    }
+
+    // Grace period in seconds, the slots at their nominal duration rounded up
+    fn grace_seconds(&self) -> i64 {
+        (i64::from(self.grace_period_slots) * DEFAULT_MS_PER_SLOT as i64 + 999) / 1000
+    }
+
+    // Whether a ballot cast right now lands after the deadline, ballots are only accepted there inside the grace period
+    fn in_grace_period(&self) -> Result<bool, ProgramError> {
+        match self.deadline {
+            Some(deadline) => Ok(current_timestamp()? > deadline),
+            None => Ok(false),
+        }
+    }
+
+    // Check that the voter may cast a ballot right now
+    fn ensure_can_vote(&self, voter: &Pubkey) -> Result<(), ProgramError> {
+        // Check if the voter is in the allowed list
+        if !self.is_voter_allowed(voter) {
+            return Err(VoteError::VoterNotAllowed.into()); // Return error if voter is not allowed
+        }
+
+        // Check if the voting is closed
+        self.ensure_open()?;
+        self.ensure_not_pending()?;
+
+        // Check if the deadline and its grace period have passed
+        if let Some(deadline) = self.deadline {
+            if current_timestamp()? > deadline.saturating_add(self.grace_seconds()) {
+                return Err(VoteError::DeadlinePassed.into());
+            }
+        }
+
+        // Check if the voter's personal window is still open
+        if let Some(deadline) = self.allowed_voters.get(voter).and_then(|voter_info| self.voter_deadline(voter_info)) {
+            if current_timestamp()? > deadline {
+                return Err(VoteError::VoterWindowClosed.into());
+            }
+        }
+        self.ensure_not_expired(voter)?;
+
+        Ok(())
+    }
+
+    fn vote(&mut self, voter: &Pubkey, question_index: usize, option_index: OptionIndex) -> Result<(), ProgramError> {
+        self.vote_with_comment(voter, question_index, option_index, None)
+    }
+
+    fn vote_with_comment(&mut self, voter: &Pubkey, question_index: usize, option_index: OptionIndex, comment: Option<Comment>) -> Result<(), ProgramError> {
+        self.ensure_can_vote(voter)?;
+        self.ensure_comment(comment.as_ref())?;
+
+        // Point allocation votes take their ballots from `allocate`
+        if let VoteType::PointAllocation { .. } = self.vote_type {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let weight = self.ballot_weight(voter)?;
+        if weight == 0 {
+            return Err(ProgramError::InvalidArgument); // Return error if the voter handed all their weight to delegates
+        }
+        let expired_votes = self.expired_delegated_votes(voter, question_index)?;
+        let cast_slot = self.conviction_cast_slot()?;
+        let in_grace = self.in_grace_period()?;
+
+        // Check if the selected question index is correct
+        let question = self.questions.get_mut(question_index).ok_or(ProgramError::InvalidArgument)?;
+        let voter_info = self.allowed_voters.get(voter).ok_or(ProgramError::InvalidArgument)?; // Return error if the voter is not found
+
+        // Votes of expired delegations belong to the delegator again, delegated votes count as spent last.
+        // A voter without any vote left is turned away by the ledger
+        let votes_left = voter_info.votes_left[question_index];
+        if votes_left > 0 && votes_left <= expired_votes {
+            return Err(VoteError::DelegationExpired.into());
+        }
+
+        // Check if the selected option index is correct
+        if option_index.index() >= question.options.len() {
+            return Err(ProgramError::InvalidArgument); // Return error if index is out of range
+        }
+        if question.options[option_index.index()].withdrawn {
+            return Err(VoteError::OptionWithdrawn.into());
+        }
+
+        // Repeated ballots on a question are rate limited, so the clock is only read for voters holding several votes
+        let mut slot = None;
+        if voter_info.ballots.iter().any(|ballot| ballot.question_index as usize == question_index) {
+            let current = current_slot()?;
+            let min_slots = self.min_slots_between_ballots.unwrap_or(1);
+            if voter_info.last_voted_slot.is_some_and(|last| current < last.saturating_add(min_slots)) {
+                return Err(VoteError::TooSoon.into());
+            }
+            slot = Some(current);
+        }
+
+        // Check the caps up front, so a rejected ballot leaves the vote with the voter
+        let option_key = question.options[option_index.index()].label.clone();
+        let count = question.votes.get(option_key.as_str()).copied().unwrap_or(0);
+        if self.per_option_cap.is_some_and(|cap| u64::from(count) >= cap) {
+            return Err(VoteError::OptionCapReached.into());
+        }
+        question.ensure_seat(&option_key, voter, self.per_option_voter_cap)?;
+
+        // Spend the vote on the selected option and count it
+        self.ledger.transfer(&mut self.allowed_voters, question_index, CreditEndpoint::Voter(*voter), CreditEndpoint::Tally(option_index), 1, CreditCause::Ballot)?;
+        tally::add_ballot(&mut question.votes, &mut question.weighted_votes, &option_key, weight, self.per_option_cap)?;
+        question.take_seat(&option_key, voter, self.per_option_voter_cap);
+
+        let voter_info = self.allowed_voters.get_mut(voter).unwrap(); // Safely extract the voter as we already checked for existence
+        if voter_info.votes_left[question_index] > 0 {
+            voter_info.last_voted_slot = Some(match slot {
+                Some(slot) => slot,
+                None => current_slot()?,
+            });
+        }
+
+        voter_info.ballots.push(Ballot {
+            question_index: question_index as u32,
+            option_index,
+            weight,
+            cast_slot,
+            in_grace,
+            comment: comment.clone(),
+        });
+
+        let votes_left = voter_info.votes_left[question_index];
+        self.consume_delegated_vote(voter, question_index, votes_left)?;
+        self.emit(VoteEvent::BallotCast {
+            vote_id: self.id,
+            voter: *voter,
+            question_index: question_index as u32,
+            option_index,
+            comment,
+            slot: current_slot()?,
+            timestamp: current_timestamp()?,
+        });
+        self.record_change(ChangeKind::BallotCast)
+    }
+
+    // Copy of a ballot for a reader, comments are for the creator and the observers only
+    fn shown_ballot(&self, ballot: &Ballot, caller: &Pubkey) -> Ballot {
+        let comment = ballot.comment.clone().filter(|_| self.can_view(Visibility::CreatorOnly, caller));
+        Ballot { comment, ..ballot.clone() }
+    }
+
+    // Votes requiring a comment take every ballot with one, the others take none, their ballots have no room for it
+    fn ensure_comment(&self, comment: Option<&Comment>) -> Result<(), ProgramError> {
+        match (self.require_comment, comment) {
+            (true, None) => Err(VoteError::CommentRequired.into()),
+            (false, Some(_)) => Err(ProgramError::InvalidArgument), // Return error for a comment the vote does not take
+            _ => Ok(()),
+        }
+    }
+
+    // Spend one vote of a point allocation vote on a question. `allocations` pairs option indices with
+    // points, every allocated option gets a ballot weighted by its points
+    fn allocate(&mut self, voter: &Pubkey, question_index: usize, allocations: &[(OptionIndex, u32)]) -> Result<(), ProgramError> {
+        self.ensure_can_vote(voter)?;
+        self.ensure_comment(None)?;
+
+        let VoteType::PointAllocation { points_per_voter, allow_partial } = self.vote_type else {
+            return Err(ProgramError::InvalidArgument); // Return error if the vote does not count points
+        };
+
+        let expired_votes = self.expired_delegated_votes(voter, question_index)?;
+        let in_grace = self.in_grace_period()?;
+        let question = self.questions.get_mut(question_index).ok_or(ProgramError::InvalidArgument)?;
+        let voter_info = self.allowed_voters.get(voter).ok_or(ProgramError::InvalidArgument)?;
+
+        // A voter without any vote left is turned away by the ledger
+        let votes_left = voter_info.votes_left[question_index];
+        if votes_left > 0 && votes_left <= expired_votes {
+            return Err(VoteError::DelegationExpired.into());
+        }
+
+        // Every option may appear once and must exist
+        let mut seen = HashSet::new();
+        for (option_index, _) in allocations {
+            if option_index.index() >= question.options.len() || !seen.insert(*option_index) {
+                return Err(ProgramError::InvalidArgument); // Return error for unknown or repeated options
+            }
+        }
+
+        let total: u64 = allocations.iter().map(|(_, points)| u64::from(*points)).sum();
+        let budget = u64::from(points_per_voter);
+        if total == 0 || total > budget || (!allow_partial && total < budget) {
+            return Err(VoteError::PointBudgetMismatch.into());
+        }
+
+        // Check the caps up front, so a rejected allocation leaves the tallies untouched
+        let allocations: Vec<(OptionIndex, u32)> = allocations.iter().copied().filter(|(_, points)| *points > 0).collect();
+        for (option_index, _) in &allocations {
+            if question.options[option_index.index()].withdrawn {
+                return Err(VoteError::OptionWithdrawn.into());
+            }
+            let label = &question.options[option_index.index()].label;
+            let count = question.votes.get(label.as_str()).copied().unwrap_or(0);
+            if self.per_option_cap.is_some_and(|cap| u64::from(count) >= cap) {
+                return Err(VoteError::OptionCapReached.into());
+            }
+            question.ensure_seat(label, voter, self.per_option_voter_cap)?;
+        }
+
+        // Repeated allocations on a question are rate limited like ballots
+        let mut slot = None;
+        if voter_info.ballots.iter().any(|ballot| ballot.question_index as usize == question_index) {
+            let current = current_slot()?;
+            let min_slots = self.min_slots_between_ballots.unwrap_or(1);
+            if voter_info.last_voted_slot.is_some_and(|last| current < last.saturating_add(min_slots)) {
+                return Err(VoteError::TooSoon.into());
+            }
+            slot = Some(current);
+        }
+
+        // The whole allocation spends one vote, booked on the first allocated option
+        let first_option = allocations.first().map(|(option_index, _)| *option_index).ok_or(VoteError::PointBudgetMismatch)?;
+        self.ledger.transfer(&mut self.allowed_voters, question_index, CreditEndpoint::Voter(*voter), CreditEndpoint::Tally(first_option), 1, CreditCause::Ballot)?;
+
+        let voter_info = self.allowed_voters.get_mut(voter).unwrap(); // Safely extract the voter as we already checked for existence
+        for (option_index, points) in allocations {
+            let option_key = question.options[option_index.index()].label.clone();
+            tally::add_ballot(&mut question.votes, &mut question.weighted_votes, &option_key, u64::from(points), self.per_option_cap)?;
+            question.take_seat(&option_key, voter, self.per_option_voter_cap);
+            voter_info.ballots.push(Ballot {
+                question_index: question_index as u32,
+                option_index,
+                weight: u64::from(points),
+                cast_slot: None,
+                in_grace,
+                comment: None,
+            });
+        }
+
+        if voter_info.votes_left[question_index] > 0 {
+            voter_info.last_voted_slot = Some(match slot {
+                Some(slot) => slot,
+                None => current_slot()?,
+            });
+        }
+
+        let votes_left = voter_info.votes_left[question_index];
+        self.consume_delegated_vote(voter, question_index, votes_left)?;
+        self.record_change(ChangeKind::BallotCast)
+    }
+
+    // Cast a ballot for the option labelled `text`, adding it to the question first when no option has
+    // that label. Returns the index of the option
+    fn write_in(&mut self, voter: &Pubkey, question_index: usize, text: &str) -> Result<OptionIndex, ProgramError> {
+        if !self.allow_write_ins {
+            return Err(ProgramError::InvalidArgument); // Return error if the creator did not allow write-ins
+        }
+        self.ensure_can_vote(voter)?;
+
+        let label = text.trim();
+        if label.is_empty() || label.len() > MAX_WRITE_IN_LEN {
+            return Err(ProgramError::InvalidArgument); // Return error if the label is empty or too long
+        }
+
+        let question = self.questions.get_mut(question_index).ok_or(ProgramError::InvalidArgument)?;
+        if let Some(position) = question.option_named(label) {
+            let option_index = OptionIndex::try_from(position)?;
+            self.vote(voter, question_index, option_index)?;
+            return Ok(option_index);
+        }
+
+        // The option goes after the last displayed one and has to fit in the state account
+        let capacity = self.max_options.map_or(MAX_WRITE_IN_OPTIONS, |max_options| MAX_WRITE_IN_OPTIONS.min(max_options as usize));
+        if question.options.len() >= capacity {
+            return Err(ProgramError::InvalidArgument); // Return error if the question holds the most options
+        }
+        self.option_format.validate(label)?;
+        let option_index = OptionIndex::try_from(question.options.len())?; // Position of the new option
+        let order = match question.options.iter().map(|option| option.order).max() {
+            Some(order) => order.checked_add(1).ok_or(ProgramError::InvalidArgument)?,
+            None => 0,
+        };
+        question.options.push(VoteOption { proposed_by: Some(*voter), ..VoteOption::new(label.to_string(), order)? });
+
+        // A rejected ballot takes the new option back out
+        if let Err(error) = self.vote(voter, question_index, option_index) {
+            self.questions[question_index].options.pop();
+            return Err(error);
+        }
+
+        self.record_change(ChangeKind::OptionWrittenIn)?;
+        Ok(option_index)
+    }
+
+    // Take an option of the first question out of the vote, e.g. when a candidate drops out. It stays listed, marked
+    // withdrawn. With `refund_on_withdraw` its ballots leave the tallies and their votes go back to the voters, otherwise
+    // they stay counted. Returns the number of refunded ballots
+    fn withdraw_option(&mut self, option_index: OptionIndex, caller: &Pubkey) -> Result<u32, ProgramError> {
+        if *caller != self.creator {
+            return Err(ProgramError::InvalidArgument); // Only the creator can withdraw options
+        }
+
+        // Check if the voting is closed
+        self.ensure_open()?;
+
+        let option = self.questions[0].options.get_mut(option_index.index()).ok_or(ProgramError::InvalidArgument)?; // Return error if index is out of range
+        if option.withdrawn {
+            return Err(ProgramError::InvalidArgument); // Return error if the option was already withdrawn
+        }
+        option.withdrawn = true;
+        let label = option.label.clone();
+
+        let mut refunded: u32 = 0;
+        if self.refund_on_withdraw {
+            let mut voters: Vec<Pubkey> = self
+                .allowed_voters
+                .iter()
+                .filter(|(_, voter_info)| voter_info.ballots.iter().any(|ballot| ballot.question_index == 0 && ballot.option_index == option_index))
+                .map(|(voter, _)| *voter)
+                .collect();
+            voters.sort();
+
+            for voter in voters {
+                let voter_info = self.allowed_voters.get_mut(&voter).unwrap(); // Safely extract the voter as we just listed it
+                let (withdrawn, kept): (Vec<Ballot>, Vec<Ballot>) =
+                    std::mem::take(&mut voter_info.ballots).into_iter().partition(|ballot| ballot.question_index == 0 && ballot.option_index == option_index);
+                voter_info.ballots = kept;
+
+                let question = &mut self.questions[0];
+                for ballot in &withdrawn {
+                    tally::remove_ballot(&mut question.votes, &mut question.weighted_votes, &label, ballot.weight);
+                }
+                question.free_seat(&label, &voter);
+
+                let count = u32::try_from(withdrawn.len()).map_err(|_| ProgramError::ArithmeticOverflow)?;
+                self.move_votes(0, CreditEndpoint::Tally(option_index), CreditEndpoint::Voter(voter), count, CreditCause::Withdrawn)?;
+                refunded = refunded.checked_add(count).ok_or(ProgramError::ArithmeticOverflow)?;
+            }
+        }
+
+        self.record_change(ChangeKind::OptionWithdrawn)?;
+        Ok(refunded)
+    }
+
+    // Take back the latest ballot of the voter on a question, the vote returns to the voter and a
+    // seat the ballot held on its option is freed
+    fn retract_ballot(&mut self, voter: &Pubkey, question_index: usize) -> Result<(), ProgramError> {
+        self.ensure_can_vote(voter)?;
+
+        // An allocation spreads one vote over several ballots
+        if let VoteType::PointAllocation { .. } = self.vote_type {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let question = self.questions.get_mut(question_index).ok_or(ProgramError::InvalidArgument)?;
+        let voter_info = self.allowed_voters.get(voter).ok_or(ProgramError::InvalidArgument)?;
+
+        let position = voter_info
+            .ballots
+            .iter()
+            .rposition(|ballot| ballot.question_index as usize == question_index)
+            .ok_or(ProgramError::InvalidArgument)?; // Return error if the voter cast no ballot on the question
+        let option_index = voter_info.ballots[position].option_index;
+        self.ledger.transfer(&mut self.allowed_voters, question_index, CreditEndpoint::Tally(option_index), CreditEndpoint::Voter(*voter), 1, CreditCause::Retraction)?;
+
+        let voter_info = self.allowed_voters.get_mut(voter).unwrap(); // Safely extract the voter as we already checked for existence
+        let ballot = voter_info.ballots.remove(position);
+
+        let label = question.options[ballot.option_index.index()].label.clone();
+        tally::remove_ballot(&mut question.votes, &mut question.weighted_votes, &label, ballot.weight);
+
+        // The seat stays taken while another ballot of the voter is on the option
+        if !voter_info.ballots.iter().any(|other| other.question_index == ballot.question_index && other.option_index == ballot.option_index) {
+            question.free_seat(&label, voter);
+        }
+
+        self.record_change(ChangeKind::BallotRetracted)
+    }
+
+    // Move the latest ballot of the voter on a question to another option. The ballot keeps its weight,
+    // on conviction votes it starts building conviction again from the current slot
+    fn change_ballot(&mut self, voter: &Pubkey, question_index: usize, option_index: OptionIndex) -> Result<(), ProgramError> {
+        self.ensure_can_vote(voter)?;
+
+        // An allocation spreads one vote over several ballots
+        if let VoteType::PointAllocation { .. } = self.vote_type {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let cast_slot = self.conviction_cast_slot()?;
+        let in_grace = self.in_grace_period()?;
+        let question = self.questions.get_mut(question_index).ok_or(ProgramError::InvalidArgument)?;
+        let voter_info = self.allowed_voters.get_mut(voter).ok_or(ProgramError::InvalidArgument)?;
+
+        let position = voter_info
+            .ballots
+            .iter()
+            .rposition(|ballot| ballot.question_index as usize == question_index)
+            .ok_or(ProgramError::InvalidArgument)?; // Return error if the voter cast no ballot on the question
+        let ballot = voter_info.ballots[position].clone();
+        if option_index.index() >= question.options.len() || option_index == ballot.option_index {
+            return Err(ProgramError::InvalidArgument); // Return error for unknown options or a change to the same one
+        }
+        if question.options[option_index.index()].withdrawn {
+            return Err(VoteError::OptionWithdrawn.into());
+        }
+
+        // The new option is counted first, so a full option leaves the ballot where it was
+        let old_label = question.options[ballot.option_index.index()].label.clone();
+        let new_label = question.options[option_index.index()].label.clone();
+        question.ensure_seat(&new_label, voter, self.per_option_voter_cap)?;
+        tally::add_ballot(&mut question.votes, &mut question.weighted_votes, &new_label, ballot.weight, self.per_option_cap)?;
+        question.take_seat(&new_label, voter, self.per_option_voter_cap);
+        tally::remove_ballot(&mut question.votes, &mut question.weighted_votes, &old_label, ballot.weight);
+
+        voter_info.ballots[position].option_index = option_index;
+        voter_info.ballots[position].cast_slot = cast_slot;
+        voter_info.ballots[position].in_grace = in_grace;
+
+        // The seat stays taken while another ballot of the voter is on the old option
+        if !voter_info.ballots.iter().any(|other| other.question_index == ballot.question_index && other.option_index == ballot.option_index) {
+            question.free_seat(&old_label, voter);
+        }
+
+        self.move_votes(question_index, CreditEndpoint::Tally(ballot.option_index), CreditEndpoint::Tally(option_index), 1, CreditCause::BallotChanged)?;
+        self.record_change(ChangeKind::BallotCast)
+    }
+
+    fn delegate_vote(&mut self, delegate: &Pubkey, delegator: &Pubkey, scope: DelegationScope, expires_at: Option<i64>) -> Result<(), ProgramError> {
+        // Check if the delegator is allowed
+        if let Some(voter_info) = self.allowed_voters.get(delegator).cloned() {
+            // Check if the voting is closed
+            self.ensure_open()?;
+            self.ensure_not_pending()?;
+            self.ensure_not_expired(delegator)?;
+            self.ensure_scope(scope)?;
+
+            // Received votes of expired delegations cannot be passed on
+            let expired_votes = (0..self.questions.len())
+                .map(|question_index| self.expired_delegated_votes(delegator, question_index))
+                .collect::<Result<Vec<u32>, ProgramError>>()?;
+
+            // One vote of every question in scope the delegator can still vote on
+            let moved: Vec<u32> = voter_info
+                .votes_left
+                .iter()
+                .zip(&expired_votes)
+                .enumerate()
+                .map(|(question_index, (votes, expired))| u32::from(votes > expired && scope.covers(question_index)))
+                .collect();
+
+            if moved.iter().any(|moved_votes| *moved_votes > 0) {
+                // Votes cannot be handed to a struck voter or past the allowlist
+                self.ensure_not_banned(delegate)?;
+                self.ensure_not_opted_out(delegate)?;
+                self.ensure_delegate_allowed(delegate)?;
+                self.ensure_delegate_capacity(delegate, 1)?;
+
+                // The delegate may need a new allowlist entry
+                self.ensure_allowlist_capacity(delegate)?;
+
+                // Get or create an entry for the delegate
+                let new_entry = VoterInfo { via_delegation_only: true, ..self.new_voter()? };
+                self.allowed_voters.entry(*delegate).or_insert(new_entry);
+
+                for (question_index, moved_votes) in moved.iter().enumerate() {
+                    self.move_votes(question_index, CreditEndpoint::Voter(*delegator), CreditEndpoint::Voter(*delegate), *moved_votes, CreditCause::Delegation)?;
+                }
+
+                // Set the delegate
+                let updated_voter_info = self.allowed_voters.get_mut(delegator).unwrap(); // Safely extract the voter as we already checked for existence
+                updated_voter_info.delegate = Some(*delegate);
+                updated_voter_info.delegations.push(Delegation { delegate: *delegate, votes: moved.clone(), expires_at, weight: 0 });
+                let votes_left = updated_voter_info.votes_left.clone();
+
+                // Passing on a vote spends it like a ballot
+                for (question_index, moved_votes) in moved.iter().enumerate() {
+                    if *moved_votes > 0 {
+                        self.consume_delegated_vote(delegator, question_index, votes_left[question_index])?;
+                    }
+                }
+
+                Ok(())
+            } else {
+                Err(ProgramError::InvalidArgument) // No available votes
+            }
+        } else {
+            Err(ProgramError::InvalidArgument) // Delegator is not allowed
+        }
+    }
+
+    // Hold the votes, or in token-weighted votes the weight, of a delegation until the delegate accepts it,
+    // so the delegator cannot promise them twice
+    fn propose_delegation(&mut self, delegate: &Pubkey, delegator: &Pubkey, weight: Option<u64>, scope: DelegationScope, expires_at: Option<i64>) -> Result<(), ProgramError> {
+        if !self.is_voter_allowed(delegator) || delegate == delegator {
+            return Err(ProgramError::InvalidArgument); // Delegator is not allowed
+        }
+
+        // Check if the voting is closed
+        self.ensure_open()?;
+        self.ensure_not_pending()?;
+        self.ensure_not_expired(delegator)?;
+
+        // Votes cannot be promised to a struck voter or past the allowlist
+        self.ensure_not_banned(delegate)?;
+        self.ensure_not_opted_out(delegate)?;
+        self.ensure_delegate_allowed(delegate)?;
+
+        if self.pending_delegations.iter().any(|pending| pending.delegator == *delegator && pending.delegate == *delegate) {
+            return Err(ProgramError::InvalidArgument); // Return error if the delegate has not answered the previous one yet
+        }
+
+        self.ensure_scope(scope)?;
+
+        let mut pending = PendingDelegation { delegator: *delegator, delegate: *delegate, votes: vec![0; self.questions.len()], weight: 0, expires_at, scope };
+
+        if self.vote_type == VoteType::TokenWeighted || weight.is_some() {
+            if self.vote_type != VoteType::TokenWeighted {
+                return Err(ProgramError::InvalidArgument); // Only token-weighted votes delegate weight
+            }
+
+            let voter_info = &self.allowed_voters[delegator];
+            if !voter_info.ballots.is_empty() {
+                return Err(ProgramError::InvalidArgument); // The delegator already voted with their weight
+            }
+
+            let available = voter_info.available_weight();
+            pending.weight = weight.unwrap_or(available);
+            if pending.weight == 0 || pending.weight > available {
+                return Err(ProgramError::InvalidArgument); // No available weight
+            }
+
+            let voter_info = self.allowed_voters.get_mut(delegator).unwrap();
+            voter_info.held_weight += pending.weight;
+            voter_info.participated = true; // Weight moves outside the ledger
+        } else {
+            // Received votes of expired delegations cannot be passed on
+            let expired_votes = (0..self.questions.len())
+                .map(|question_index| self.expired_delegated_votes(delegator, question_index))
+                .collect::<Result<Vec<u32>, ProgramError>>()?;
+
+            // Hold one vote of every question in scope the delegator can still vote on
+            let voter_info = &self.allowed_voters[delegator];
+            for (question_index, ((held, votes), expired)) in pending.votes.iter_mut().zip(&voter_info.votes_left).zip(&expired_votes).enumerate() {
+                *held = u32::from(votes > expired && scope.covers(question_index));
+            }
+            if pending.votes.iter().all(|held| *held == 0) {
+                return Err(ProgramError::InvalidArgument); // No available votes
+            }
+
+            for (question_index, held) in pending.votes.iter().enumerate() {
+                self.move_votes(question_index, CreditEndpoint::Voter(*delegator), CreditEndpoint::Void, *held, CreditCause::DelegationHeld)?;
+            }
+        }
+
+        self.pending_delegations.push(pending);
+        Ok(())
+    }
+
+    // Return error if a scoped delegation names a question the vote does not have
+    fn ensure_scope(&self, scope: DelegationScope) -> Result<(), ProgramError> {
+        match scope {
+            DelegationScope::Question(index) if index as usize >= self.questions.len() => Err(ProgramError::InvalidArgument),
+            _ => Ok(()),
+        }
+    }
+
+    // Take the pending delegation out of the vote and give its held votes back to the delegator
+    fn take_pending_delegation(&mut self, delegator: &Pubkey, delegate: &Pubkey) -> Result<PendingDelegation, ProgramError> {
+        let index = self
+            .pending_delegations
+            .iter()
+            .position(|pending| pending.delegator == *delegator && pending.delegate == *delegate)
+            .ok_or(ProgramError::InvalidArgument)?; // Return error if there is no such pending delegation
+
+        let pending = self.pending_delegations.remove(index);
+        self.refund_pending_delegation(&pending)?;
+        Ok(pending)
+    }
+
+    // Delegators leave before their pending delegations lapse, so the held votes always find them
+    fn refund_pending_delegation(&mut self, pending: &PendingDelegation) -> Result<(), ProgramError> {
+        if let Some(voter_info) = self.allowed_voters.get_mut(&pending.delegator) {
+            voter_info.held_weight = voter_info.held_weight.saturating_sub(pending.weight);
+        }
+        for (question_index, held) in pending.votes.iter().enumerate() {
+            self.move_votes(question_index, CreditEndpoint::Void, CreditEndpoint::Voter(pending.delegator), *held, CreditCause::DelegationReleased)?;
+        }
+        Ok(())
+    }
+
+    // Pending delegations cannot be accepted once the vote has ended
+    fn refund_pending_delegations(&mut self) -> Result<(), ProgramError> {
+        for pending in std::mem::take(&mut self.pending_delegations) {
+            self.refund_pending_delegation(&pending)?;
+        }
+        Ok(())
+    }
+
+    // Drop the pending delegations from and to a voter leaving the vote, refunding the delegators
+    fn lapse_pending_delegations(&mut self, voter: &Pubkey) -> Result<(), ProgramError> {
+        let (lapsed, kept): (Vec<PendingDelegation>, Vec<PendingDelegation>) =
+            std::mem::take(&mut self.pending_delegations).into_iter().partition(|pending| pending.delegator == *voter || pending.delegate == *voter);
+
+        self.pending_delegations = kept;
+        for pending in &lapsed {
+            self.refund_pending_delegation(pending)?;
+        }
+        Ok(())
+    }
+
+    // Votes of the question the voter received through delegations that expired. The clock is only read
+    // when such a delegation has an expiry
+    fn expired_delegated_votes(&self, voter: &Pubkey, question_index: usize) -> Result<u32, ProgramError> {
+        let mut received = self
+            .allowed_voters
+            .values()
+            .flat_map(|voter_info| voter_info.delegations.iter())
+            .filter(|delegation| delegation.delegate == *voter && delegation.expires_at.is_some())
+            .peekable();
+
+        if received.peek().is_none() {
+            return Ok(0);
+        }
+
+        let now = current_timestamp()?;
+        Ok(received
+            .filter(|delegation| delegation.is_expired(now))
+            .map(|delegation| delegation.votes.get(question_index).copied().unwrap_or(0))
+            .sum())
+    }
+
+    // Book a spent vote of the question against the delegations the voter received once their own votes
+    // are used up. Delegated votes count as spent last, the delegation expiring first is spent first
+    fn consume_delegated_vote(&mut self, voter: &Pubkey, question_index: usize, votes_left: u32) -> Result<(), ProgramError> {
+        let mut received: Vec<&mut Delegation> = self
+            .allowed_voters
+            .values_mut()
+            .flat_map(|voter_info| voter_info.delegations.iter_mut())
+            .filter(|delegation| delegation.delegate == *voter && delegation.votes[question_index] > 0)
+            .collect();
+
+        let outstanding: u32 = received.iter().map(|delegation| delegation.votes[question_index]).sum();
+        if votes_left >= outstanding {
+            return Ok(()); // The voter spent one of their own votes
+        }
+
+        // Expired delegations cannot be spent, the caller already checked enough valid ones remain
+        let now = if received.iter().any(|delegation| delegation.expires_at.is_some()) { Some(current_timestamp()?) } else { None };
+        received.retain(|delegation| now.map_or(true, |now| !delegation.is_expired(now)));
+
+        if let Some(delegation) = received.into_iter().min_by_key(|delegation| delegation.expires_at.unwrap_or(i64::MAX)) {
+            delegation.votes[question_index] -= 1;
+        }
+
+        Ok(())
+    }
+
+    // Weight the voter received through delegations that expired, reads the clock only when such a delegation has an expiry
+    fn expired_delegated_weight(&self, voter: &Pubkey) -> Result<u64, ProgramError> {
+        let mut received = self
+            .allowed_voters
+            .values()
+            .flat_map(|voter_info| voter_info.delegations.iter())
+            .filter(|delegation| delegation.delegate == *voter && delegation.weight > 0 && delegation.expires_at.is_some())
+            .peekable();
+
+        if received.peek().is_none() {
+            return Ok(0);
+        }
+
+        let now = current_timestamp()?;
+        Ok(received.filter(|delegation| delegation.is_expired(now)).map(|delegation| delegation.weight).sum())
+    }
+
+    // Hand `weight` of the delegator's own weight, or all of it, to the delegate in a token-weighted vote. The delegate
+    // counts it on top of their own weight in every ballot. Weight can only be delegated before the delegator's
+    // first ballot and cannot be passed on, so it is never counted twice
+    fn delegate_weight(&mut self, delegate: &Pubkey, delegator: &Pubkey, weight: Option<u64>, expires_at: Option<i64>) -> Result<(), ProgramError> {
+        if self.vote_type != VoteType::TokenWeighted {
+            return Err(ProgramError::InvalidArgument); // Only token-weighted votes delegate weight
+        }
+
+        if !self.is_voter_allowed(delegator) || delegate == delegator {
+            return Err(ProgramError::InvalidArgument); // Delegator is not allowed
+        }
+
+        // Check if the voting is closed
+        self.ensure_open()?;
+        self.ensure_not_pending()?;
+        self.ensure_not_expired(delegator)?;
+
+        let voter_info = &self.allowed_voters[delegator];
+        if !voter_info.ballots.is_empty() {
+            return Err(ProgramError::InvalidArgument); // The delegator already voted with their weight
+        }
+
+        let available = voter_info.available_weight();
+        let weight = weight.unwrap_or(available);
+        if weight == 0 || weight > available {
+            return Err(ProgramError::InvalidArgument); // No available weight
+        }
+
+        // Weight cannot be handed to a struck voter or past the allowlist
+        self.ensure_not_banned(delegate)?;
+        self.ensure_not_opted_out(delegate)?;
+        self.ensure_delegate_allowed(delegate)?;
+        self.ensure_delegate_capacity(delegate, weight)?;
+
+        // The delegate may need a new allowlist entry, with a ballot for every question but no weight of their own
+        self.ensure_allowlist_capacity(delegate)?;
+        let new_entry = VoterInfo { weight: 0, via_delegation_only: true, ..self.new_voter()? };
+        if !self.allowed_voters.contains_key(delegate) {
+            self.enter_voter(*delegate, new_entry, 1)?;
+        }
+
+        let entry = self.allowed_voters.get_mut(delegate).unwrap(); // Safely extract the delegate as it was just entered
+        entry.received_weight = entry.received_weight.checked_add(weight).ok_or(ProgramError::ArithmeticOverflow)?;
+
+        let voter_info = self.allowed_voters.get_mut(delegator).unwrap(); // Safely extract the voter as we already checked for existence
+        voter_info.delegate = Some(*delegate);
+        voter_info.participated = true; // Weight moves outside the ledger
+        voter_info.delegations.push(Delegation {
+            delegate: *delegate,
+            votes: vec![0; self.questions.len()],
+            expires_at,
+            weight,
+        });
+
+        Ok(())
+    }
+
+    // Move the unspent votes of the delegator's expired delegations back to the delegator
+    fn reclaim_expired_delegations(&mut self, delegator: &Pubkey) -> Result<(), ProgramError> {
+        let voter_info = self.allowed_voters.get(delegator).ok_or(ProgramError::InvalidArgument)?; // Return error if the delegator is not found
+        if voter_info.delegations.iter().all(|delegation| delegation.expires_at.is_none()) {
+            return Ok(()); // Nothing can expire, no need to read the clock
+        }
+
+        let now = current_timestamp()?;
+
+        // Weight the delegate already voted with stays spent
+        let weight_spent = |delegation: &Delegation| delegation.weight > 0 && self.allowed_voters.get(&delegation.delegate).is_some_and(|delegate_info| !delegate_info.ballots.is_empty());
+        let delegations = self.allowed_voters[delegator].delegations.clone();
+        let (expired, active): (Vec<Delegation>, Vec<Delegation>) = delegations.into_iter().partition(|delegation| delegation.is_expired(now) && !weight_spent(delegation));
+
+        let voter_info = self.allowed_voters.get_mut(delegator).unwrap(); // Safely extract the voter as we already checked for existence
+        voter_info.delegate = active.last().map(|delegation| delegation.delegate);
+        voter_info.delegations = active;
+
+        for delegation in &expired {
+            if let Some(delegate_info) = self.allowed_voters.get_mut(&delegation.delegate) {
+                delegate_info.received_weight = delegate_info.received_weight.saturating_sub(delegation.weight);
+                let unspent: Vec<u32> = delegate_info.votes_left.iter().zip(&delegation.votes).map(|(delegate_votes, moved_votes)| (*delegate_votes).min(*moved_votes)).collect();
+                for (question_index, unspent_votes) in unspent.into_iter().enumerate() {
+                    self.move_votes(question_index, CreditEndpoint::Voter(delegation.delegate), CreditEndpoint::Voter(*delegator), unspent_votes, CreditCause::DelegationReturned)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Strike every ballot of the voter from the tallies and ban them from the vote. Votes delegated to
+    // the voter and not spent yet are forfeited with their own. Votes the voter delegated are taken back
+    // from the delegate as far as the delegate still holds them, delegated votes count as spent last,
+    // and ballots the delegate already cast stand. Returns the number of struck ballots
+    fn invalidate_voter_ballots(&mut self, voter: &Pubkey, caller: &Pubkey) -> Result<u32, ProgramError> {
+        if *caller != self.creator {
+            return Err(ProgramError::InvalidArgument); // Only the creator can strike ballots
+        }
+
+        // Only ballots of an open vote can be struck
+        self.ensure_open()?;
+
+        let voter_info = self.allowed_voters.get(voter).ok_or(ProgramError::InvalidArgument)?; // Return error if the voter is not found
+        if voter_info.banned {
+            return Err(VoteError::VoterBanned.into()); // The ballots were already struck
+        }
+
+        self.lapse_pending_delegations(voter)?;
+
+        let voter_info = self.allowed_voters.get_mut(voter).unwrap(); // Safely extract the voter as we already checked for existence
+        voter_info.banned = true;
+        voter_info.received_weight = 0;
+        let ballots = std::mem::take(&mut voter_info.ballots);
+        let delegations = std::mem::take(&mut voter_info.delegations);
+        let mut spent = voter_info.spent.clone();
+
+        // The spent votes leave the tallies with the ballots, an allocation spreads one over several ballots
+        for ballot in &ballots {
+            let question_index = ballot.question_index as usize;
+            let question = &mut self.questions[question_index];
+            let label = question.options[ballot.option_index.index()].label.clone();
+            tally::remove_ballot(&mut question.votes, &mut question.weighted_votes, &label, ballot.weight);
+            question.free_seat(&label, voter);
+
+            if spent[question_index] > 0 {
+                spent[question_index] -= 1;
+                self.move_votes(question_index, CreditEndpoint::Tally(ballot.option_index), CreditEndpoint::Voter(*voter), 1, CreditCause::Struck)?;
+            }
+        }
+        self.forfeit_votes(voter)?;
+
+        self.take_back_delegations(&delegations)?;
+
+        Ok(ballots.len() as u32)
+    }
+
+    // Remove the votes of the delegations from their delegates as far as they are unspent, and the
+    // weight from delegates who have not voted yet
+    fn take_back_delegations(&mut self, delegations: &[Delegation]) -> Result<(), ProgramError> {
+        for delegation in delegations {
+            if let Some(delegate_info) = self.allowed_voters.get_mut(&delegation.delegate) {
+                if delegate_info.ballots.is_empty() {
+                    delegate_info.received_weight = delegate_info.received_weight.saturating_sub(delegation.weight);
+                }
+                let unspent: Vec<u32> = delegate_info.votes_left.iter().zip(&delegation.votes).map(|(delegate_votes, moved_votes)| (*delegate_votes).min(*moved_votes)).collect();
+                for (question_index, unspent_votes) in unspent.into_iter().enumerate() {
+                    self.move_votes(question_index, CreditEndpoint::Voter(delegation.delegate), CreditEndpoint::Void, unspent_votes, CreditCause::Forfeit)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Lamports paid by the creator of every new vote
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct CreationFee {
+    pub lamports: u64,
+    pub treasury: Pubkey, // Account receiving the fee
+}
+
+// Lamports the creator of every new vote locks at its vote address. They are refunded when the creator closes
+// or cancels the vote before its deadline, and forfeited to `treasury` otherwise
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct CreationDeposit {
+    pub lamports: u64, // At least the rent-exempt balance of an empty account
+    pub treasury: Pubkey,
+}
+
+// Registry rules that make flooding it with votes expensive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, BorshSerialize, BorshDeserialize)]
+pub struct CreationPolicy {
+    pub fee: Option<CreationFee>,
+    pub cooldown_slots: Option<Slot>, // Minimum number of slots between two votes of the same creator
+    pub deposit: Option<CreationDeposit>,
+}
+
+// Reusable list of voters with their number of votes, copied into the allowlist of new votes
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct VoterGroup {
+    pub id: u32,
+    pub owner: Pubkey, // Only the owner can edit the members
+    pub members: Vec<(Pubkey, u32)>,
+}
+
+// Vote settings published by the registry admin, anyone can create votes from them. Every vote made from the
+// template gets its own title in place of the one of `config`
+#[derive(Debug, Clone, PartialEq, BorshSerialize, BorshDeserialize)]
+pub struct VoteTemplateDef {
+    pub id: u32,
+    pub name: Title,
+    pub config: VoteConfig,
+    pub retired: bool, // No new votes, the ones already made from it are unaffected
+}
+
+// Running counters over the whole registry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, BorshSerialize, BorshDeserialize)]
+pub struct RegistryStats {
+    pub total_votes_created: u64,
+    pub currently_open: u64,
+    pub total_ballots_cast: u64,
+    pub total_voters_registered: u64, // Allowlist entries ever created, the entries of delegates included
+}
+
+// Add `amount` to a registry counter
+fn increase(counter: &mut u64, amount: u64) -> Result<(), ProgramError> {
+    *counter = counter.checked_add(amount).ok_or(ProgramError::ArithmeticOverflow)?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Voting {
+    pub votes: HashMap<VoteId, Vote>, // List of votes
+    current_id: VoteId,
+    admin: Pubkey, // Manages registry-level settings
+    creation_policy: CreationPolicy,
+    last_created: HashMap<Pubkey, Slot>, // Slot of the latest vote of each creator
+    paused: bool, // Blocks every mutation while reads keep working
+    stats: RegistryStats,
+    groups: HashMap<u32, VoterGroup>,
+    next_group_id: u32,
+    templates: HashMap<u32, VoteTemplateDef>,
+    next_template_id: u32,
+    shard_capacity: Option<u32>, // Votes per shard, unsharded registries keep no shards
+    shard_count: u32,
+    newest_shard_len: u32,
+    vote_shards: HashMap<VoteId, u32>, // Shard of every vote created since sharding was enabled
+    outbox: Option<Pubkey>, // Account queueing the outcomes of closed votes
+}
+
+impl Voting {
+    pub fn new(admin: Pubkey) -> Self {
+        Self {
+            votes: HashMap::new(),
+            current_id: VoteId(0),
+            admin,
+            creation_policy: CreationPolicy::default(),
+            last_created: HashMap::new(),
+            paused: false,
+            stats: RegistryStats::default(),
+            groups: HashMap::new(),
+            next_group_id: 0,
+            templates: HashMap::new(),
+            next_template_id: 0,
+            shard_capacity: None,
+            shard_count: 0,
+            newest_shard_len: 0,
+            vote_shards: HashMap::new(),
+            outbox: None,
+        }
+    }
+
+    // Write a fresh registry with the given admin into an uninitialized account
+    pub fn initialize_registry(account: &AccountInfo, admin: Pubkey) -> Result<(), ProgramError> {
+        let data = account.try_borrow_data()?;
+        if data.starts_with(&REGISTRY_DISCRIMINATOR) || data.starts_with(&LEGACY_REGISTRY_DISCRIMINATOR) {
+            return Err(ProgramError::AccountAlreadyInitialized); // Return error if the registry already exists
+        }
+        drop(data);
+
+        Self::new(admin).save(account)
+    }
+
+    // Serialize the registry behind its discriminator into the account data
+    pub fn save(&self, account: &AccountInfo) -> Result<(), ProgramError> {
+        let mut serialized = REGISTRY_DISCRIMINATOR.to_vec();
+        self.serialize(&mut serialized)?;
+
+        let mut data = account.try_borrow_mut_data()?;
+        if serialized.len() > data.len() {
+            return Err(ProgramError::AccountDataTooSmall); // Return error if the state does not fit
+        }
+
+        data[..serialized.len()].copy_from_slice(&serialized);
+
+        Ok(())
+    }
+
+    // Deserialize the registry from an initialized account
+    pub fn load(account: &AccountInfo) -> Result<Self, ProgramError> {
+        Self::unpack(&account.try_borrow_data()?)
+    }
+
+    // Decode the data of a registry account, e.g. fetched by a client. Accounts in the legacy layout decode too
+    pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        if let Some(mut state) = data.strip_prefix(&REGISTRY_DISCRIMINATOR) {
+            return Ok(Self::deserialize(&mut state)?);
+        }
+        if let Some(state) = data.strip_prefix(&LEGACY_REGISTRY_DISCRIMINATOR) {
+            return Ok(slots::deserialize_legacy(state)?);
+        }
+
+        Err(ProgramError::UninitializedAccount) // Return error if the registry was never initialized
+    }
+
+    // Refuse to operate on a vote whose decoded state is inconsistent. Unknown IDs are left to the operation
+    pub fn check_vote(&self, vote_id: VoteId) -> Result<(), ProgramError> {
+        let Some(vote) = self.votes.get(&vote_id) else {
+            return Ok(());
+        };
+
+        if vote.id != vote_id || vote_id >= self.current_id {
+            return Err(VoteError::CorruptState.into());
+        }
+        vote.check_invariants()
+    }
+
+    // Recompute the tallies of a corrupted vote from its ballots, only the registry admin can call this.
+    // Ballots of voters removed from the allowlist are no longer on record and drop out of the tallies
+    pub fn admin_repair(&mut self, vote_id: VoteId, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+        self.ensure_admin(accounts)?;
+
+        if vote_id >= self.current_id {
+            return Err(ProgramError::InvalidArgument); // Return error if the ID was never handed out
+        }
+
+        let vote = self.votes.get_mut(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+        vote.id = vote_id;
+        vote.repair_tallies()
+    }
+
+    // Check that the first account is the registry admin and signed the transaction
+    fn ensure_admin(&self, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+        let caller = accounts.first().ok_or(ProgramError::NotEnoughAccountKeys)?;
+
+        if *caller.key != self.admin {
+            return Err(ProgramError::InvalidArgument); // Only the registry admin can call this method
+        }
+
+        if !caller.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        Ok(())
+    }
+
+    // Check that the registry accepts mutations
+    fn ensure_not_paused(&self) -> Result<(), ProgramError> {
+        if self.paused {
+            return Err(VoteError::ProgramPaused.into());
+        }
+
+        Ok(())
+    }
+
+    pub fn admin(&self) -> &Pubkey {
+        &self.admin
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    // Split the directory of the registry into shards of `shard_capacity` votes, see `RegistryShard`. Only the
+    // registry admin enables sharding, and only before the first vote, so every vote has a shard
+    pub fn enable_sharding(&mut self, shard_capacity: u32, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+        self.ensure_not_paused()?;
+        self.ensure_admin(accounts)?;
+
+        if self.shard_capacity.is_some() || !self.votes.is_empty() {
+            return Err(ProgramError::InvalidArgument); // Return error if sharding is already on or votes exist
+        }
+        if shard_capacity == 0 || shard_capacity > MAX_SHARD_CAPACITY {
+            return Err(ProgramError::InvalidArgument); // Return error if a shard could not hold the votes
+        }
+
+        self.shard_capacity = Some(shard_capacity);
+        Ok(())
+    }
+
+    pub fn shard_capacity(&self) -> Option<u32> {
+        self.shard_capacity
+    }
+
+    pub fn shard_count(&self) -> u32 {
+        self.shard_count
+    }
+
+    // Shard listing the vote, `None` on unsharded registries and for unknown IDs
+    pub fn shard_for_vote_id(&self, vote_id: VoteId) -> Option<u32> {
+        self.vote_shards.get(&vote_id).copied()
+    }
+
+    // Shard the next vote goes into, a new one when the newest is full. Clients derive the shard account of
+    // `create_vote` from it
+    pub fn next_shard_index(&self) -> Option<u32> {
+        let capacity = self.shard_capacity?;
+        if self.shard_count == 0 || self.newest_shard_len >= capacity {
+            Some(self.shard_count)
+        } else {
+            Some(self.shard_count - 1)
+        }
+    }
+
+    // Queue the outcomes of closed votes in the outbox account following the admin in `accounts`, which the
+    // caller has created with room for `capacity` entries. Closures then take the outbox as their last account
+    pub fn enable_outbox(&mut self, capacity: u32, policy: OutboxPolicy, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+        self.ensure_not_paused()?;
+        self.ensure_admin(accounts)?;
+
+        let outbox_account = accounts.get(1).ok_or(ProgramError::NotEnoughAccountKeys)?;
+        if self.outbox.is_some() {
+            return Err(ProgramError::InvalidArgument); // Return error if the registry already has an outbox
+        }
+        if capacity == 0 || capacity > MAX_OUTBOX_CAPACITY {
+            return Err(ProgramError::InvalidArgument); // Return error if the outbox could not hold the entries
+        }
+        if outbox_account.data_len() < Outbox::space(capacity) {
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+
+        Outbox::new(capacity, policy).save(outbox_account)?;
+        self.outbox = Some(*outbox_account.key);
+        Ok(())
+    }
+
+    pub fn outbox_address(&self) -> Option<Pubkey> {
+        self.outbox
+    }
+
+    // Mark the outbox entries of the votes consumed, only the registry admin can call this. `accounts` are
+    // [admin, outbox], returns how many entries were acknowledged
+    pub fn ack_outbox(&self, vote_ids: &[VoteId], accounts: &[AccountInfo]) -> Result<u32, ProgramError> {
+        self.ensure_not_paused()?;
+        self.ensure_admin(accounts)?;
+
+        let outbox_account = accounts.get(1).ok_or(ProgramError::NotEnoughAccountKeys)?;
+        if self.outbox != Some(*outbox_account.key) {
+            return Err(ProgramError::InvalidArgument); // Return error if the account is not the outbox of the registry
+        }
+
+        let mut outbox = Outbox::unpack(&outbox_account.try_borrow_data()?)?;
+        let acked = outbox.ack(vote_ids);
+        outbox.save(outbox_account)?;
+        Ok(acked)
+    }
+
+    // Split the outbox account off the end of the accounts of a closure. Returns error on registries with an
+    // outbox when it is missing or full under `RejectWhenFull`, before anything changes
+    fn split_outbox_account<'a, 'info>(&self, accounts: &'a [AccountInfo<'info>]) -> Result<(&'a [AccountInfo<'info>], Option<&'a AccountInfo<'info>>), ProgramError> {
+        let Some(address) = self.outbox else {
+            return Ok((accounts, None));
+        };
+
+        match accounts.split_last() {
+            Some((outbox_account, rest)) if !rest.is_empty() && *outbox_account.key == address => {
+                Outbox::unpack(&outbox_account.try_borrow_data()?)?.ensure_room()?;
+                Ok((rest, Some(outbox_account)))
+            }
+            _ => Err(ProgramError::NotEnoughAccountKeys), // Return error if the outbox does not come last
+        }
+    }
+
+    // Queue the outcome of a vote that just closed
+    fn post_to_outbox(&self, vote_id: VoteId, outbox_account: Option<&AccountInfo>) -> Result<(), ProgramError> {
+        let Some(outbox_account) = outbox_account else {
+            return Ok(());
+        };
+
+        let vote = self.votes.get(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+        let closed_at = vote.closure.map(|closure| closure.closed_at).ok_or(ProgramError::InvalidArgument)?;
+        let entry = OutboxEntry { vote_id, outcome: OutboxOutcome::from(&vote.winner(0)?), closed_at, consumed: false };
+
+        let mut outbox = Outbox::unpack(&outbox_account.try_borrow_data()?)?;
+        outbox.push(entry)?;
+        outbox.save(outbox_account)
+    }
+
+    // Record the vote under its ID, in the newest shard on sharded registries, and log its state as created
+    fn insert_vote(&mut self, vote_id: VoteId, mut vote: Vote) -> Result<(), ProgramError> {
+        if let Some(shard_index) = self.next_shard_index() {
+            if shard_index == self.shard_count {
+                self.shard_count += 1;
+                self.newest_shard_len = 0;
+            }
+            self.newest_shard_len += 1;
+            self.vote_shards.insert(vote_id, shard_index);
+        }
+
+        let state = borsh::to_vec(&vote)?;
+        vote.emit(VoteEvent::VoteCreated { vote_id, state });
+        self.votes.insert(vote_id, vote);
+        Ok(())
+    }
+
+    // Freeze all mutations of the registry, e.g. while a tally bug is investigated
+    pub fn pause(&mut self, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+        self.ensure_admin(accounts)?;
+
+        self.paused = true;
+
+        Ok(())
+    }
+
+    pub fn unpause(&mut self, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+        self.ensure_admin(accounts)?;
+
+        self.paused = false;
+
+        Ok(())
+    }
+
+    pub fn update_policy(&mut self, policy: CreationPolicy, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+        self.ensure_not_paused()?;
+        self.ensure_admin(accounts)?;
+
+        if let Some(deposit) = policy.deposit {
+            if deposit.lamports < Rent::get()?.minimum_balance(0) {
+                return Err(ProgramError::InvalidArgument); // Return error if the vault of a vote could not hold the deposit
+            }
+        }
+
+        self.creation_policy = policy;
+
+        Ok(())
+    }
+
+    pub fn get_creation_policy(&self) -> CreationPolicy {
+        self.creation_policy
+    }
+
+    pub fn get_registry_stats(&self) -> RegistryStats {
+        self.stats
+    }
+
+    // Count the allowlist entries a call created, removed entries stay counted
+    fn count_new_voters(&mut self, vote_id: VoteId, voters_before: usize) -> Result<(), ProgramError> {
+        let voters_after = self.votes.get(&vote_id).map_or(0, |vote| vote.allowed_voters.len());
+        increase(&mut self.stats.total_voters_registered, voters_after.saturating_sub(voters_before) as u64)
+    }
+
+    // A vote left the open state through a close or a cancellation
+    fn count_finished_vote(&mut self) -> Result<(), ProgramError> {
+        self.stats.currently_open = self.stats.currently_open.checked_sub(1).ok_or(ProgramError::ArithmeticOverflow)?;
+        Ok(())
+    }
+
+    // Create a voter group owned by the caller, members are listed at most once
+    pub fn create_group(&mut self, members: Vec<(Pubkey, u32)>, accounts: &[AccountInfo]) -> Result<u32, ProgramError> {
+        self.create_group_as(signer_key(accounts)?, members)
+    }
+
+    pub fn create_group_as(&mut self, caller: &Pubkey, members: Vec<(Pubkey, u32)>) -> Result<u32, ProgramError> {
+        self.ensure_not_paused()?;
+
+        let mut seen = HashSet::new();
+        if !members.iter().all(|(member, _)| seen.insert(*member)) {
+            return Err(ProgramError::InvalidArgument); // Return error if a member is listed twice
+        }
+
+        let group_id = self.next_group_id;
+        self.next_group_id = group_id.checked_add(1).ok_or(ProgramError::ArithmeticOverflow)?;
+
+        let group = VoterGroup {
+            id: group_id,
+            owner: *caller,
+            members,
+        };
+        self.groups.insert(group_id, group);
+
+        Ok(group_id)
+    }
+
+    // Get a group for editing by its owner
+    fn owned_group(&mut self, group_id: u32, caller: &Pubkey) -> Result<&mut VoterGroup, ProgramError> {
+        self.ensure_not_paused()?;
+
+        let group = self.groups.get_mut(&group_id).ok_or(ProgramError::InvalidArgument)?;
+
+        if group.owner != *caller {
+            return Err(ProgramError::InvalidArgument); // Only the owner can edit the group
+        }
+
+        Ok(group)
+    }
+
+    // Add a member to the group, or change the votes of an existing member
+    pub fn add_group_member(&mut self, group_id: u32, member: Pubkey, votes: u32, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+        self.add_group_member_as(signer_key(accounts)?, group_id, member, votes)
+    }
+
+    pub fn add_group_member_as(&mut self, caller: &Pubkey, group_id: u32, member: Pubkey, votes: u32) -> Result<(), ProgramError> {
+        let group = self.owned_group(group_id, caller)?;
+
+        match group.members.iter_mut().find(|(existing, _)| *existing == member) {
+            Some(entry) => entry.1 = votes,
+            None => group.members.push((member, votes)),
+        }
+
+        Ok(())
+    }
+
+    pub fn remove_group_member(&mut self, group_id: u32, member: &Pubkey, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+        self.remove_group_member_as(signer_key(accounts)?, group_id, member)
+    }
+
+    pub fn remove_group_member_as(&mut self, caller: &Pubkey, group_id: u32, member: &Pubkey) -> Result<(), ProgramError> {
+        let group = self.owned_group(group_id, caller)?;
+
+        let members_before = group.members.len();
+        group.members.retain(|(existing, _)| existing != member);
+
+        if group.members.len() == members_before {
+            return Err(ProgramError::InvalidArgument); // Return error if the member is not in the group
+        }
+
+        Ok(())
+    }
+
+    pub fn get_group(&self, group_id: u32) -> Result<&VoterGroup, ProgramError> {
+        self.groups.get(&group_id).ok_or(ProgramError::InvalidArgument)
+    }
+
+    // Publish the settings of `config` as a template. Co-creators, a voter group, a prerequisite and a deadline
+    // only make sense for a single vote and are refused
+    pub fn publish_template(&mut self, name: String, config: VoteConfig, accounts: &[AccountInfo]) -> Result<u32, ProgramError> {
+        self.ensure_not_paused()?;
+        self.ensure_admin(accounts)?;
+
+        let name = Title::new("name", name)?;
+        if config.questions.is_empty() {
+            return Err(ProgramError::InvalidArgument); // Return error if the template has no question
+        }
+        for question in &config.questions {
+            question.validate()?;
+        }
+        for option in config.questions.iter().flat_map(|question| &question.options) {
+            config.option_format.validate(&option.label)?;
+        }
+        if !config.creators.is_empty() || config.allowlist_group.is_some() || config.prerequisite.is_some() || config.deadline.is_some() {
+            return Err(ProgramError::InvalidArgument); // Return error if the template holds settings of a single vote
+        }
+
+        let template_id = self.next_template_id;
+        self.next_template_id = template_id.checked_add(1).ok_or(ProgramError::ArithmeticOverflow)?;
+        self.templates.insert(template_id, VoteTemplateDef { id: template_id, name, config, retired: false });
+
+        Ok(template_id)
+    }
+
+    pub fn retire_template(&mut self, template_id: u32, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+        self.ensure_not_paused()?;
+        self.ensure_admin(accounts)?;
+
+        let template = self.templates.get_mut(&template_id).ok_or(ProgramError::InvalidArgument)?; // Return error if the template does not exist
+        template.retired = true;
+
+        Ok(())
+    }
+
+    pub fn get_template(&self, template_id: u32) -> Result<&VoteTemplateDef, ProgramError> {
+        self.templates.get(&template_id).ok_or(ProgramError::InvalidArgument)
+    }
+
+    // Create a vote with the settings of a template: its questions and options, vote type, visibility, quorum and
+    // durations. Takes the accounts of `create_vote_with_config`
+    pub fn create_vote_from_template(&mut self, template_id: u32, title: String, accounts: &[AccountInfo]) -> Result<VoteId, ProgramError> {
+        let template = self.templates.get(&template_id).ok_or(ProgramError::InvalidArgument)?; // Return error if the template does not exist
+        if template.retired {
+            return Err(ProgramError::InvalidArgument); // Return error if the template was retired
+        }
+
+        let config = VoteConfig { title: Title::new("title", title)?, ..template.config.clone() };
+        self.create_vote_with_config(config, accounts)
+    }
+
+    pub fn create_vote(&mut self, title: String, options: Vec<String>, is_close_vote_results: bool, accounts: &[AccountInfo]) -> Result<VoteId, ProgramError> {
+        let mut config = VoteConfig::from_labels(title, options)?;
+
+        // Closed results used to hide both the tallies and the allowlist from outsiders
+        if is_close_vote_results {
+            config.results_visibility = Visibility::AllowlistOnly;
+            config.allowlist_visibility = Visibility::AllowlistOnly;
+        }
+
+        self.create_vote_with_config(config, accounts)
+    }
+
+    // Accounts: [creator, vote state account (optional), treasury (only with a creation fee), registry and the vote
+    // address of the new vote (only with a creation deposit), system program (with a fee or a deposit)].
+    // When the state account is given, the allowlist and option capacity are derived from its allocated size
+    pub fn create_vote_with_config(&mut self, config: VoteConfig, accounts: &[AccountInfo]) -> Result<VoteId, ProgramError> {
+        self.ensure_not_paused()?;
+
+        if accounts.is_empty() {
+            return Err(ProgramError::InvalidArgument); // Return error if no accounts are provided
+        }
+
+        let fee_accounts = if self.creation_policy.fee.is_some() { 1 } else { 0 };
+        let deposit_accounts = if self.creation_policy.deposit.is_some() { 2 } else { 0 };
+        let policy_accounts = match fee_accounts + deposit_accounts {
+            0 => 0,
+            accounts => accounts + 1, // The system program follows
+        };
+        if accounts.len() < 1 + policy_accounts {
+            return Err(ProgramError::NotEnoughAccountKeys); // Return error if the fee or deposit accounts are missing
+        }
+        let state_account = if accounts.len() > 1 + policy_accounts { Some(&accounts[1]) } else { None };
+        let policy_accounts = &accounts[accounts.len() - policy_accounts..];
+
+        // A vote needs at least one question
+        if config.questions.is_empty() {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        for question in &config.questions {
+            question.validate()?;
+        }
+        for option in config.questions.iter().flat_map(|question| &question.options) {
+            config.option_format.validate(&option.label)?;
+        }
+
+        // The weight interpolation needs a non-empty time range
+        if let VoteType::TimeWeighted { start, end, .. } = config.vote_type {
+            if start >= end {
+                return Err(ProgramError::InvalidArgument);
+            }
+        }
+
+        // Every translation covers the options of the first question exactly, once per language
+        let translated_options = config.questions.first().map_or(0, |question| question.options.len());
+        let mut languages: Vec<LangCode> = config.translations.iter().map(|(lang, _)| *lang).collect();
+        languages.sort();
+        languages.dedup();
+        if languages.len() != config.translations.len() || languages.len() > MAX_TRANSLATIONS || config.translations.iter().any(|(_, labels)| labels.len() != translated_options) {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        if config.seats == 0 {
+            return Err(ProgramError::InvalidArgument); // Return error if the vote has no seat to fill
+        }
+
+        if usize::from(config.required_certifications) > MAX_OBSERVERS + 1 {
+            return Err(ProgramError::InvalidArgument); // Return error if the creator and the observers could never certify
+        }
+
+        if config.track_history && config.history_bucket_slots == 0 {
+            return Err(ProgramError::InvalidArgument); // Return error if the history has no bucket width
+        }
+
+        // Prerequisites are existing votes, which have lower IDs, so they cannot form a cycle
+        if let Some(prerequisite) = config.prerequisite {
+            let source = self.votes.get(&prerequisite.vote_id).ok_or(ProgramError::InvalidArgument)?; // Return error if the prerequisite does not exist
+            if matches!(source.status, VoteStatus::Cancelled { .. }) || prerequisite.required_option_index.index() >= source.questions[0].options.len() {
+                return Err(ProgramError::InvalidArgument); // Return error if the prerequisite can never pass with the option
+            }
+        }
+
+        if matches!(config.vote_type, VoteType::TokenBurn { burn_amount: 0, .. }) {
+            return Err(ProgramError::InvalidArgument); // Return error if the ballots would burn nothing
+        }
+
+        if matches!(config.vote_type, VoteType::PointAllocation { points_per_voter: 0, .. }) {
+            return Err(ProgramError::InvalidArgument); // Return error if voters would have no points to allocate
+        }
+
+        if config.refund_on_withdraw && matches!(config.vote_type, VoteType::PointAllocation { .. }) {
+            return Err(ProgramError::InvalidArgument); // Return error if refunds would split allocations, which spend one vote over several ballots
+        }
+
+        if matches!(config.vote_type, VoteType::Conviction { period_slots: 0, .. } | VoteType::Conviction { max_multiplier: 0, .. }) {
+            return Err(ProgramError::InvalidArgument); // Return error if conviction could never build up
+        }
+
+        if config.min_duration.is_some_and(|min_duration| min_duration < 0) {
+            return Err(ProgramError::InvalidArgument); // Return error if the minimum duration is negative
+        }
+
+        if let Some(deadline) = config.deadline {
+            if deadline <= current_timestamp()? {
+                return Err(ProgramError::InvalidArgument); // Return error if the deadline is not in the future
+            }
+        }
+
+        if config.per_voter_window.is_some_and(|window| window < 0) {
+            return Err(ProgramError::InvalidArgument); // Return error if the window is negative
+        }
+
+        if config.winning_threshold_bps.is_some_and(|threshold| u64::from(threshold) > FULL_WEIGHT_BPS) {
+            return Err(ProgramError::InvalidArgument); // Return error if the threshold is above 100%
+        }
+
+        // Co-creators are listed once, include the creator and can reach the approval threshold
+        if config.creators.is_empty() {
+            if config.approval_threshold != 0 {
+                return Err(ProgramError::InvalidArgument); // Return error if a threshold is set without co-creators
+            }
+        } else {
+            let unique: HashSet<&Pubkey> = config.creators.iter().collect();
+            if unique.len() != config.creators.len() || !unique.contains(accounts[0].key) {
+                return Err(ProgramError::InvalidArgument);
+            }
+            if config.approval_threshold == 0 || config.approval_threshold as usize > config.creators.len() {
+                return Err(ProgramError::InvalidArgument); // Return error if the threshold cannot be reached
+            }
+        }
+
+        // Snapshot of the voter group, if the allowlist starts from one
+        let group_members = match config.allowlist_group {
+            Some(group_id) => self.groups.get(&group_id).ok_or(ProgramError::InvalidArgument)?.members.clone(), // Return error if the group does not exist
+            None => Vec::new(),
+        };
+
+        let creator = accounts[0].key;
+
+        // Check if the creator is still cooling down from their previous vote
+        let mut created_slot = None;
+        if let Some(cooldown_slots) = self.creation_policy.cooldown_slots {
+            let slot = current_slot()?;
+            if let Some(last_slot) = self.last_created.get(creator) {
+                if slot < last_slot.saturating_add(cooldown_slots) {
+                    return Err(VoteError::CreationCooldown.into());
+                }
+            }
+            created_slot = Some(slot);
+        }
+
+        let mut capacity = None;
+        if let Some(state_account) = state_account {
+            let (base_space, voter_space) = Vote::space_layout(&config);
+            let space = state_account.data_len();
+
+            if space < base_space {
+                return Err(ProgramError::AccountDataTooSmall); // Return error if not even an empty vote fits
+            }
+
+            let max_voters = ((space - base_space) / voter_space).min(u32::MAX as usize) as u32;
+            if group_members.len() > max_voters as usize {
+                return Err(VoteError::AllowlistFull.into()); // Return error if the group does not fit in the state account
+            }
+            let mut max_options = config.questions.iter().map(|question| question.options.len()).max().unwrap_or(0);
+            if config.allow_write_ins {
+                max_options = max_options.max(MAX_WRITE_IN_OPTIONS); // The space layout made room for the write-ins
+            }
+            capacity = Some((max_voters, max_options.min(u16::MAX as usize) as u16));
+        }
+
+        // Charge the creation fee
+        if let Some(fee) = self.creation_policy.fee {
+            let treasury = &policy_accounts[0];
+            let system_program_account = &accounts[accounts.len() - 1];
+
+            if *treasury.key != fee.treasury || *system_program_account.key != system_program::id() {
+                return Err(ProgramError::InvalidArgument); // Return error if the fee accounts are wrong
+            }
+
+            invoke(
+                &system_instruction::transfer(creator, treasury.key, fee.lamports),
+                &[accounts[0].clone(), treasury.clone(), system_program_account.clone()],
+            )?;
+        }
+
+        // Lock the creation deposit at the vote address, a PDA of the program owning the registry
+        let mut deposit = None;
+        if let Some(CreationDeposit { lamports, treasury }) = self.creation_policy.deposit {
+            let registry = &policy_accounts[fee_accounts];
+            let vault = &policy_accounts[fee_accounts + 1];
+            let system_program_account = &accounts[accounts.len() - 1];
+
+            check_unique_accounts(&[accounts[0].key, registry.key, vault.key])?;
+            let (vault_address, bump) = get_vote_address(registry.owner, registry.key, self.current_id);
+            if *vault.key != vault_address {
+                return Err(ProgramError::InvalidSeeds); // Return error if the vault is not the address of the new vote
+            }
+            if *system_program_account.key != system_program::id() {
+                return Err(ProgramError::InvalidArgument);
+            }
+
+            invoke(
+                &system_instruction::transfer(creator, vault.key, lamports),
+                &[accounts[0].clone(), vault.clone(), system_program_account.clone()],
+            )?;
+            deposit = Some(CreatorDeposit { lamports, vault: vault_address, registry: *registry.key, bump, treasury, status: DepositStatus::Held });
+        }
+
+        if let Some(slot) = created_slot {
+            self.last_created.insert(*creator, slot);
+        }
+
+        let min_duration = config.min_duration;
+        let mut vote = Vote::from_config(self.current_id, *creator, config);
+        vote.deposit = deposit;
+        if let Some(min_duration) = min_duration {
+            vote.min_close_at = Some(current_timestamp()?.saturating_add(min_duration));
+        }
+        if let Some((max_voters, max_options)) = capacity {
+            vote.max_voters = Some(max_voters);
+            vote.max_options = Some(max_options);
+        }
+
+        for (member, votes) in &group_members {
+            let voter_info = vote.new_voter()?;
+            vote.enter_voter(*member, voter_info, *votes)?;
+        }
+
+        increase(&mut self.stats.total_voters_registered, group_members.len() as u64)?;
+        increase(&mut self.stats.total_votes_created, 1)?;
+        increase(&mut self.stats.currently_open, 1)?;
+
+        let vote_id = self.current_id;
+        self.current_id = vote_id.next().ok_or(ProgramError::ArithmeticOverflow)?; // Increment the identifier for the next vote
+        self.insert_vote(vote_id, vote)?; // Add the vote to the list
+
+        Ok(vote_id)
+    }
+
+    // Recreate a closed vote of another registry from its bundle, under the next vote ID of this one. The vote
+    // stays closed with its allowlist, ballots and history, and `get_import_origin` keeps the ID and the results
+    // hash it had in the source. The results are recomputed from the imported state and have to match that hash.
+    // Only the registry admin imports votes
+    #[cfg(any(test, feature = "client"))]
+    pub fn import_bundle(&mut self, bundle: VoteBundle, accounts: &[AccountInfo]) -> Result<VoteId, ProgramError> {
+        self.ensure_not_paused()?;
+        self.ensure_admin(accounts)?;
+
+        bundle.verify()?;
+        let mut vote = Vote::try_from_slice(&bundle.vote_state).map_err(|_| ProgramError::InvalidAccountData)?;
+        if vote.status != VoteStatus::Closed {
+            return Err(ProgramError::InvalidArgument); // Return error if the bundled vote is not closed
+        }
+        vote.check_invariants()?;
+        if vote.full_results(None)?.hash() != bundle.results_hash {
+            return Err(ProgramError::InvalidAccountData); // Return error if the state does not give the exported results
+        }
+
+        // A vote imported twice keeps its first origin
+        let vote_id = self.current_id;
+        vote.imported = Some(vote.imported.unwrap_or(ImportOrigin { vote_id: vote.id, results_hash: bundle.results_hash }));
+        vote.id = vote_id;
+        vote.deposit = None; // The deposit stayed with the source registry
+        vote.event_seq = 0; // The events of the source stay with the source
+
+        increase(&mut self.stats.total_votes_created, 1)?;
+        self.current_id = vote_id.next().ok_or(ProgramError::ArithmeticOverflow)?;
+        self.insert_vote(vote_id, vote)?;
+
+        Ok(vote_id)
+    }
+
+    pub fn get_import_origin(&self, vote_id: VoteId) -> Result<Option<ImportOrigin>, ProgramError> {
+        Ok(self.votes.get(&vote_id).ok_or(ProgramError::InvalidArgument)?.imported)
+    }
+
+    // Create an open vote from the configuration of an existing one, open or closed. Accounts are the ones of
+    // `create_vote_with_config` and the caller becomes the creator of the clone. With `copy_allowlist`, the voters
+    // of the source who were not banned are allowed again with one vote per question and their weight
+    pub fn clone_vote(&mut self, source_vote_id: VoteId, new_title: String, copy_allowlist: bool, accounts: &[AccountInfo]) -> Result<VoteId, ProgramError> {
+        let source = self.votes.get(&source_vote_id).ok_or(ProgramError::InvalidArgument)?;
+        let config = source.to_config(new_title)?;
+        self.clone_from(source_vote_id, config, copy_allowlist, accounts)
+    }
+
+    // Create a vote from `config`, allowing the voters of the source again when `copy_allowlist` is set
+    fn clone_from(&mut self, source_vote_id: VoteId, config: VoteConfig, copy_allowlist: bool, accounts: &[AccountInfo]) -> Result<VoteId, ProgramError> {
+        let source = self.votes.get(&source_vote_id).ok_or(ProgramError::InvalidArgument)?;
+        let voters: Vec<(Pubkey, u64)> = if copy_allowlist {
+            source.allowed_voters.iter().filter(|(_, voter_info)| !voter_info.banned).map(|(voter, voter_info)| (*voter, voter_info.weight)).collect()
+        } else {
+            Vec::new()
+        };
+
+        self.create_with_voters(config, voters, accounts)
+    }
+
+    // Create a vote and allow `voters` with their weight and one vote per question
+    fn create_with_voters(&mut self, config: VoteConfig, mut voters: Vec<(Pubkey, u64)>, accounts: &[AccountInfo]) -> Result<VoteId, ProgramError> {
+        voters.sort();
+
+        let vote_id = self.create_vote_with_config(config, accounts)?;
+
+        let vote = self.votes.get_mut(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+        let voters_before = vote.allowed_voters.len();
+        let creator = vote.creator;
+        for (voter, weight) in voters {
+            vote.add_allowed_voter(voter, weight, None, &creator)?;
+        }
+        self.count_new_voters(vote_id, voters_before)?;
+
+        Ok(vote_id)
+    }
+
+    // Open a runoff of a closed single-question vote between its `top_n` leading options, with the settings and
+    // the allowlist of the source like `clone_vote`. Accounts are the ones of `create_vote_with_config`, the
+    // caller has to be the creator of the source
+    pub fn create_runoff(&mut self, source_vote_id: VoteId, top_n: usize, accounts: &[AccountInfo]) -> Result<VoteId, ProgramError> {
+        let caller = signer_key(accounts)?;
+
+        let source = self.votes.get(&source_vote_id).ok_or(ProgramError::InvalidArgument)?;
+        if source.creator != *caller {
+            return Err(ProgramError::InvalidArgument); // Only the creator of the source can start its runoff
+        }
+        if source.status != VoteStatus::Closed {
+            return Err(VoteError::VoteStillOpen.into()); // Cancelled votes have no result to run off
+        }
+
+        let mut config = source.to_config(format!("{} (runoff)", source.title))?;
+        config.questions[0].options = source.runoff_options(top_n)?;
+
+        // Translations keep the labels of the options in the runoff, and are dropped if they miss a write-in among them
+        let kept: Vec<usize> = config.questions[0].options.iter().filter_map(|option| source.get_options().iter().position(|known| known.label == option.label)).collect();
+        for (_, labels) in &mut config.translations {
+            *labels = kept.iter().filter_map(|index| labels.get(*index).cloned()).collect();
+        }
+        config.translations.retain(|(_, labels)| labels.len() == kept.len());
+
+        let vote_id = self.clone_from(source_vote_id, config, true, accounts)?;
+        if let Some(vote) = self.votes.get_mut(&vote_id) {
+            vote.runoff_of = Some(source_vote_id);
+        }
+        Ok(vote_id)
+    }
+
+    // Open a follow-up vote among the voters whose ballots on the first question of a closed vote went to
+    // `parent_option_index`, e.g. on the budget of the project that won. They are allowed with their weight
+    // and one vote per question. Accounts are the ones of `create_vote_with_config`, the caller has to be the
+    // creator of the parent
+    pub fn create_subvote(&mut self, parent_vote_id: VoteId, parent_option_index: OptionIndex, config: VoteConfig, accounts: &[AccountInfo]) -> Result<VoteId, ProgramError> {
+        let caller = signer_key(accounts)?;
+
+        let parent = self.votes.get(&parent_vote_id).ok_or(ProgramError::InvalidArgument)?;
+        if parent.creator != *caller {
+            return Err(ProgramError::InvalidArgument); // Only the creator of the parent can open its sub-votes
+        }
+        if parent.status != VoteStatus::Closed {
+            return Err(VoteError::VoteStillOpen.into()); // Cancelled votes selected no option
+        }
+        if parent_option_index.index() >= parent.get_options().len() {
+            return Err(ProgramError::InvalidArgument); // Return error if the option does not exist
+        }
+        // Groups and eligibility checks would let in voters who did not select the option
+        if config.allowlist_group.is_some() || config.eligibility.is_some() {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let voters: Vec<(Pubkey, u64)> = parent
+            .allowed_voters
+            .iter()
+            .filter(|(_, voter_info)| !voter_info.banned && voter_info.ballots.iter().any(|ballot| ballot.question_index == 0 && ballot.option_index == parent_option_index))
+            .map(|(voter, voter_info)| (*voter, voter_info.weight))
+            .collect();
+        if voters.is_empty() {
+            return Err(ProgramError::InvalidArgument); // Return error if no ballot selected the option
+        }
+
+        let vote_id = self.create_with_voters(config, voters, accounts)?;
+        if let Some(vote) = self.votes.get_mut(&vote_id) {
+            vote.parent = Some((parent_vote_id, parent_option_index));
+        }
+        Ok(vote_id)
+    }
+
+    // Allow the voters of another vote, e.g. the participants of a first round, with `votes` votes per question.
+    // Banned voters are skipped, and voters already on the destination allowlist keep their entry. Returns the
+    // number of voters added
+    pub fn import_allowlist(&mut self, dest_vote_id: VoteId, source_vote_id: VoteId, filter: ImportFilter, votes: u32, accounts: &[AccountInfo]) -> Result<u32, ProgramError> {
+        self.import_allowlist_as(signer_key(accounts)?, dest_vote_id, source_vote_id, filter, votes)
+    }
+
+    pub fn import_allowlist_as(&mut self, caller: &Pubkey, dest_vote_id: VoteId, source_vote_id: VoteId, filter: ImportFilter, votes: u32) -> Result<u32, ProgramError> {
+        self.ensure_not_paused()?;
+
+        if dest_vote_id == source_vote_id || votes == 0 {
+            return Err(ProgramError::InvalidArgument); // Return error if the vote imports itself or would allow voters without votes
+        }
+
+        let source = self.votes.get(&source_vote_id).ok_or(ProgramError::InvalidArgument)?;
+        let mut voters: Vec<(Pubkey, u64)> = source
+            .allowed_voters
+            .iter()
+            .filter(|(_, voter_info)| !voter_info.banned && filter.matches(!voter_info.ballots.is_empty()))
+            .map(|(voter, voter_info)| (*voter, voter_info.weight))
+            .collect();
+        voters.sort();
+
+        let vote = self.votes.get_mut(&dest_vote_id).ok_or(ProgramError::InvalidArgument)?;
+        if vote.creator != *caller {
+            return Err(ProgramError::InvalidArgument); // Only the creator of the destination can import voters
+        }
+        vote.ensure_open()?;
+
+        voters.retain(|(voter, _)| !vote.allowed_voters.contains_key(voter) && !vote.opted_out.contains(voter)); // Already allowed, banned or opted out of the destination
+
+        // Checked up front, so a batch that does not fit adds nobody
+        if voters.len() > MAX_BATCH_VOTERS {
+            msg!("Batch of {} voters, the limit is {}", voters.len(), MAX_BATCH_VOTERS);
+            return Err(VoteError::BatchTooLarge.into());
+        }
+        if vote.max_voters.is_some_and(|max_voters| vote.allowlist_len() + voters.len() > max_voters as usize) {
+            return Err(VoteError::AllowlistFull.into());
+        }
+
+        let voters_before = vote.allowed_voters.len();
+        for (voter, weight) in voters {
+            let mut voter_info = vote.new_voter()?;
+            voter_info.weight = weight;
+            vote.enter_voter(voter, voter_info, votes)?;
+            vote.record_change(ChangeKind::VoterAdded)?;
+        }
+
+        let imported = vote.allowed_voters.len() - voters_before;
+        self.count_new_voters(dest_vote_id, voters_before)?;
+        Ok(imported as u32)
+    }
+
+    // Changes syncing the allowlist with `desired`, a set of voters with their votes per question, e.g. from
+    // an off-chain membership database. Only read, by the creator and the observers
+    pub fn diff_allowlist(&self, vote_id: VoteId, desired: &[(Pubkey, u32)], accounts: &[AccountInfo]) -> Result<AllowlistDiff, ProgramError> {
+        self.diff_allowlist_as(viewer_key(accounts, true)?, vote_id, desired)
+    }
+
+    pub fn diff_allowlist_as(&self, caller: &Pubkey, vote_id: VoteId, desired: &[(Pubkey, u32)]) -> Result<AllowlistDiff, ProgramError> {
+        let vote = self.votes.get(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+
+        if !vote.can_view(Visibility::CreatorOnly, caller) {
+            return Err(ProgramError::InvalidArgument); // Return error if the caller is neither the creator nor an observer
+        }
+
+        vote.diff_allowlist(desired)
+    }
+
+    // Apply a diff of `diff_allowlist` at once: when one change fails, none is kept. Diffs over `MAX_BATCH_VOTERS`
+    // changes are refused, `AllowlistDiff::pages` cuts them into diffs that fit
+    pub fn apply_allowlist_diff(&mut self, vote_id: VoteId, diff: &AllowlistDiff, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+        self.apply_allowlist_diff_as(signer_key(accounts)?, vote_id, diff)
+    }
+
+    pub fn apply_allowlist_diff_as(&mut self, caller: &Pubkey, vote_id: VoteId, diff: &AllowlistDiff) -> Result<(), ProgramError> {
+        self.ensure_not_paused()?;
+
+        // Applied to a copy, so a failing change leaves the vote as it was
+        let mut vote = self.votes.get(&vote_id).ok_or(ProgramError::InvalidArgument)?.clone();
+        let voters_before = vote.allowed_voters.len();
+        vote.apply_allowlist_diff(diff, caller)?;
+
+        self.votes.insert(vote_id, vote);
+        self.count_new_voters(vote_id, voters_before - diff.to_remove.len()) // Every voter to remove was on the allowlist
+    }
+
+    pub fn vote(&mut self, vote_id: VoteId, accounts: &[AccountInfo], option_index: OptionIndex) -> Result<(), ProgramError> {
+        self.vote_on_question(vote_id, accounts, 0, option_index)
+    }
+
+    pub fn vote_as(&mut self, caller: &Pubkey, vote_id: VoteId, option_index: OptionIndex) -> Result<(), ProgramError> {
+        self.vote_on_question_as(caller, vote_id, 0, option_index)
+    }
+
+    // Voters of an open vote join with their first ballot, the eligibility account follows the voter
+    pub fn vote_on_question(&mut self, vote_id: VoteId, accounts: &[AccountInfo], question_index: usize, option_index: OptionIndex) -> Result<(), ProgramError> {
+        signer_key(accounts)?;
+        self.join_and_vote(vote_id, accounts, question_index, option_index, None)
+    }
+
+    // Ballot with the comment votes created with `VoteConfig::require_comment` need, at most `MAX_COMMENT_LEN` bytes
+    pub fn vote_with_comment(&mut self, vote_id: VoteId, accounts: &[AccountInfo], question_index: usize, option_index: OptionIndex, comment: String) -> Result<(), ProgramError> {
+        signer_key(accounts)?;
+        self.join_and_vote(vote_id, accounts, question_index, option_index, Some(comment))
+    }
+
+    pub fn vote_with_comment_as(&mut self, voter: &Pubkey, vote_id: VoteId, question_index: usize, option_index: OptionIndex, comment: String) -> Result<(), ProgramError> {
+        self.cast_as(voter, vote_id, question_index, option_index, Some(comment))
+    }
+
+    // Ballot of the first account, shared by `vote_on_question`, `vote_with_comment` and the simulation
+    fn join_and_vote(&mut self, vote_id: VoteId, accounts: &[AccountInfo], question_index: usize, option_index: OptionIndex, comment: Option<String>) -> Result<(), ProgramError> {
+        let voter = caller_key(accounts)?;
+
+        let joins = self.votes.get(&vote_id).is_some_and(|vote| vote.eligibility.is_some() && !vote.allowed_voters.contains_key(voter));
+        if joins {
+            self.register_voter(vote_id, accounts)?;
+        }
+
+        self.cast_as(voter, vote_id, question_index, option_index, comment)
+    }
+
+    // What a ballot of the first account would do, e.g. to warn before the voter signs that they already voted.
+    // The ballot runs through the code of `vote_on_question` on a copy of the registry, so the prediction cannot
+    // drift from the real call. Accounts as for `vote_on_question`, the voter does not sign, but restricted
+    // tallies only come with the answer when they do
+    pub fn simulate_vote(&self, vote_id: VoteId, question_index: usize, option_index: OptionIndex, accounts: &[AccountInfo]) -> Result<SimulationResponse, ProgramError> {
+        let mut scratch = self.clone();
+        scratch.mute_events(vote_id);
+        let outcome = scratch.join_and_vote(vote_id, accounts, question_index, option_index, None);
+        Ok(scratch.simulation_response(vote_id, viewer_key(accounts, false)?, outcome))
+    }
+
+    pub fn simulate_vote_as(&self, voter: &Pubkey, vote_id: VoteId, question_index: usize, option_index: OptionIndex) -> Result<SimulationResponse, ProgramError> {
+        let mut scratch = self.clone();
+        scratch.mute_events(vote_id);
+        let outcome = scratch.vote_on_question_as(voter, vote_id, question_index, option_index);
+        Ok(scratch.simulation_response(vote_id, voter, outcome))
+    }
+
+    // What `delegate_vote` would do for the first account, which does not sign
+    pub fn simulate_delegate(&self, vote_id: VoteId, delegate: &Pubkey, expires_at: Option<i64>, accounts: &[AccountInfo]) -> Result<SimulationResponse, ProgramError> {
+        let delegator = caller_key(accounts)?;
+        self.simulate_delegation(delegator, viewer_key(accounts, false)?, vote_id, delegate, expires_at)
+    }
+
+    pub fn simulate_delegate_as(&self, delegator: &Pubkey, vote_id: VoteId, delegate: &Pubkey, expires_at: Option<i64>) -> Result<SimulationResponse, ProgramError> {
+        self.simulate_delegation(delegator, delegator, vote_id, delegate, expires_at)
+    }
+
+    // Tallies of the answer are the ones `viewer` may read
+    fn simulate_delegation(&self, delegator: &Pubkey, viewer: &Pubkey, vote_id: VoteId, delegate: &Pubkey, expires_at: Option<i64>) -> Result<SimulationResponse, ProgramError> {
+        let mut scratch = self.clone();
+        scratch.mute_events(vote_id);
+        let outcome = scratch.delegate(delegator, vote_id, delegate, None, DelegationScope::AllQuestions, expires_at);
+        Ok(scratch.simulation_response(vote_id, viewer, outcome))
+    }
+
+    // A simulated call logs nothing, its events would read as real ones to indexers
+    fn mute_events(&mut self, vote_id: VoteId) {
+        if let Some(vote) = self.votes.get_mut(&vote_id) {
+            vote.events_muted = true;
+        }
+    }
+
+    // Outcome of a call run on a copy of the registry. The tallies it led to are only projected for callers
+    // who may read the results
+    fn simulation_response(&self, vote_id: VoteId, caller: &Pubkey, outcome: Result<(), ProgramError>) -> SimulationResponse {
+        let questions = match outcome {
+            Ok(()) => self.get_results_response_as(caller, vote_id, None).map(|response| response.questions).unwrap_or_default(),
+            Err(_) => Vec::new(),
+        };
+        SimulationResponse { vote_id, error: outcome.err().map(u64::from), questions }
+    }
+
+    // Ballot of `voter`, who has to be on the allowlist already, open votes included
+    pub fn vote_on_question_as(&mut self, voter: &Pubkey, vote_id: VoteId, question_index: usize, option_index: OptionIndex) -> Result<(), ProgramError> {
+        self.cast_as(voter, vote_id, question_index, option_index, None)
+    }
+
+    fn cast_as(&mut self, voter: &Pubkey, vote_id: VoteId, question_index: usize, option_index: OptionIndex, comment: Option<String>) -> Result<(), ProgramError> {
+        self.ensure_not_paused()?;
+        let comment = comment.map(|comment| Comment::new("comment", comment)).transpose()?;
+        self.open_if_prerequisite_met(vote_id)?;
+
+        let vote = self.votes.get_mut(&vote_id).ok_or(ProgramError::InvalidArgument)?; // Return error if the ID does not exist
+
+        // Expired delegations of the voter return before the ballot
+        if vote.is_voter_allowed(voter) {
+            vote.reclaim_expired_delegations(voter)?;
+        }
+
+        // Call the voting method
+        vote.vote_with_comment(voter, question_index, option_index, comment)?;
+
+        increase(&mut self.stats.total_ballots_cast, 1)
+    }
+
+    // Ballots of the caller on the first question of several votes, applied together: when one fails, none is
+    // kept and the position of the failing ballot is logged. The caller has to be on every allowlist already
+    pub fn vote_many(&mut self, ballots: &[(VoteId, OptionIndex)], accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+        self.vote_many_as(signer_key(accounts)?, ballots)
+    }
+
+    pub fn vote_many_as(&mut self, voter: &Pubkey, ballots: &[(VoteId, OptionIndex)]) -> Result<(), ProgramError> {
+        if ballots.len() > MAX_BATCH_BALLOTS {
+            msg!("Batch of {} ballots, the limit is {}", ballots.len(), MAX_BATCH_BALLOTS);
+            return Err(VoteError::BatchTooLarge.into());
+        }
+
+        // The votes are restored from a copy, the batch may hold several ballots on one vote
+        let mut saved = HashMap::new();
+        for (vote_id, _) in ballots {
+            if let Some(vote) = self.votes.get(vote_id) {
+                saved.entry(*vote_id).or_insert_with(|| vote.clone());
+            }
+        }
+        let stats = self.stats;
+
+        for (position, (vote_id, option_index)) in ballots.iter().enumerate() {
+            if let Err(error) = self.vote_on_question_as(voter, *vote_id, 0, *option_index) {
+                msg!("Ballot {} of the batch failed on vote {}", position, vote_id);
+                self.votes.extend(saved);
+                self.stats = stats;
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+
+    // Ballot for the option of the question labelled `label`, matched after normalization. Returns the index of the option
+    pub fn vote_by_name(&mut self, vote_id: VoteId, accounts: &[AccountInfo], question_index: usize, label: &str) -> Result<OptionIndex, ProgramError> {
+        let option_index = self.option_named(vote_id, question_index, label)?;
+        self.vote_on_question(vote_id, accounts, question_index, option_index)?;
+        Ok(option_index)
+    }
+
+    pub fn vote_by_name_as(&mut self, voter: &Pubkey, vote_id: VoteId, question_index: usize, label: &str) -> Result<OptionIndex, ProgramError> {
+        let option_index = self.option_named(vote_id, question_index, label)?;
+        self.vote_on_question_as(voter, vote_id, question_index, option_index)?;
+        Ok(option_index)
+    }
+
+    fn option_named(&self, vote_id: VoteId, question_index: usize, label: &str) -> Result<OptionIndex, ProgramError> {
+        let vote = self.votes.get(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+        let question = vote.questions.get(question_index).ok_or(ProgramError::InvalidArgument)?;
+        let position = question.option_named(label).ok_or(ProgramError::InvalidArgument)?; // Return error if no option has the label
+        OptionIndex::try_from(position)
+    }
+
+    // Vote for the option labelled `text` on the first question, adding it when the vote allows write-ins
+    // and no option has that label yet. Returns the index of the option
+    pub fn vote_write_in(&mut self, vote_id: VoteId, accounts: &[AccountInfo], text: &str) -> Result<OptionIndex, ProgramError> {
+        self.vote_write_in_as(signer_key(accounts)?, vote_id, text)
+    }
+
+    pub fn vote_write_in_as(&mut self, voter: &Pubkey, vote_id: VoteId, text: &str) -> Result<OptionIndex, ProgramError> {
+        self.ensure_not_paused()?;
+        self.open_if_prerequisite_met(vote_id)?;
+
+        let vote = self.votes.get_mut(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+
+        // Expired delegations of the voter return before the ballot
+        if vote.is_voter_allowed(voter) {
+            vote.reclaim_expired_delegations(voter)?;
+        }
+
+        let option_index = vote.write_in(voter, 0, text)?;
+
+        increase(&mut self.stats.total_ballots_cast, 1)?;
+        Ok(option_index)
+    }
+
+    // Take back the latest ballot of the caller on a question
+    pub fn retract_ballot(&mut self, vote_id: VoteId, accounts: &[AccountInfo], question_index: usize) -> Result<(), ProgramError> {
+        self.retract_ballot_as(signer_key(accounts)?, vote_id, question_index)
+    }
+
+    pub fn retract_ballot_as(&mut self, voter: &Pubkey, vote_id: VoteId, question_index: usize) -> Result<(), ProgramError> {
+        self.ensure_not_paused()?;
+
+        let vote = self.votes.get_mut(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+        vote.retract_ballot(voter, question_index)
+    }
+
+    // Move the latest ballot of the caller on a question to another option
+    pub fn change_vote(&mut self, vote_id: VoteId, accounts: &[AccountInfo], question_index: usize, option_index: OptionIndex) -> Result<(), ProgramError> {
+        self.change_vote_as(signer_key(accounts)?, vote_id, question_index, option_index)
+    }
+
+    pub fn change_vote_as(&mut self, voter: &Pubkey, vote_id: VoteId, question_index: usize, option_index: OptionIndex) -> Result<(), ProgramError> {
+        self.ensure_not_paused()?;
+
+        let vote = self.votes.get_mut(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+        vote.change_ballot(voter, question_index, option_index)
+    }
+
+    // Spread the point budget of the caller over options of the first question. `allocations` pairs
+    // option indices with points
+    pub fn vote_allocate(&mut self, vote_id: VoteId, accounts: &[AccountInfo], allocations: &[(OptionIndex, u32)]) -> Result<(), ProgramError> {
+        self.vote_allocate_on_question(vote_id, accounts, 0, allocations)
+    }
+
+    pub fn vote_allocate_as(&mut self, voter: &Pubkey, vote_id: VoteId, allocations: &[(OptionIndex, u32)]) -> Result<(), ProgramError> {
+        self.vote_allocate_on_question_as(voter, vote_id, 0, allocations)
+    }
+
+    pub fn vote_allocate_on_question(&mut self, vote_id: VoteId, accounts: &[AccountInfo], question_index: usize, allocations: &[(OptionIndex, u32)]) -> Result<(), ProgramError> {
+        self.vote_allocate_on_question_as(signer_key(accounts)?, vote_id, question_index, allocations)
+    }
+
+    pub fn vote_allocate_on_question_as(&mut self, voter: &Pubkey, vote_id: VoteId, question_index: usize, allocations: &[(OptionIndex, u32)]) -> Result<(), ProgramError> {
+        self.ensure_not_paused()?;
+        self.open_if_prerequisite_met(vote_id)?;
+
+        let vote = self.votes.get_mut(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+
+        // Expired delegations of the voter return before the allocation
+        if vote.is_voter_allowed(voter) {
+            vote.reclaim_expired_delegations(voter)?;
+        }
+
+        vote.allocate(voter, question_index, allocations)?;
+
+        increase(&mut self.stats.total_ballots_cast, 1)
+    }
+
+    pub fn close_vote(&mut self, vote_id: VoteId, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+        self.ensure_not_paused()?;
+
+        if accounts.is_empty() {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let caller = accounts[0].key;
+        let (accounts, outbox_account) = self.split_outbox_account(accounts)?;
+
+        if let Some(vote) = self.votes.get_mut(&vote_id) {
+            // The registry admin can close any vote
+            let reason = if vote.creator == *caller {
+                ClosureReason::Creator
+            } else if self.admin == *caller && accounts[0].is_signer {
+                ClosureReason::Admin
+            } else {
+                return Err(ProgramError::InvalidArgument); // Only the creator or the admin can close the vote
+            };
+
+            // Co-created votes are closed through an approved admin action, no single key can close them
+            vote.ensure_single_creator()?;
+
+            let now = current_timestamp()?;
+            vote.check_deposit_accounts(reason == ClosureReason::Creator, now, accounts)?;
+            if vote.close(caller, reason, now, accounts)? {
+                vote.settle_deposit(reason == ClosureReason::Creator, now, accounts)?;
+                self.count_finished_vote()?;
+                self.post_to_outbox(vote_id, outbox_account)?;
+            }
+            Ok(())
+        } else {
+            Err(ProgramError::InvalidArgument) // Vote not found
+        }
+    }
+
+    // Propose an admin action on a co-created vote. The proposal counts as the first approval, and
+    // replaces an earlier proposal once that one expired
+    pub fn propose_admin_action(&mut self, vote_id: VoteId, action: AdminAction, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+        self.ensure_not_paused()?;
+
+        if accounts.is_empty() {
+            return Err(ProgramError::InvalidArgument); // Return error if no accounts are provided
+        }
+        if !accounts[0].is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let caller = accounts[0].key;
+
+        let vote = self.votes.get_mut(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+        if !vote.creators.contains(caller) {
+            return Err(ProgramError::InvalidArgument); // Only the creators can propose admin actions
+        }
+
+        // Admin actions only apply to open votes
+        vote.ensure_open()?;
+
+        let now = current_timestamp()?;
+        if vote.pending_action.as_ref().is_some_and(|pending| now <= pending.expires_at) {
+            return Err(ProgramError::InvalidArgument); // Return error if another action still collects approvals
+        }
+
+        vote.pending_action = Some(PendingAdminAction {
+            action: action.clone(),
+            proposed_by: *caller,
+            approvals: Vec::new(),
+            expires_at: now.saturating_add(PENDING_ACTION_LIFETIME),
+        });
+
+        self.approve_admin_action(vote_id, &action, accounts)
+    }
+
+    // Approve the pending admin action of a co-created vote, `action` must match the proposal.
+    // The action runs with the approval that reaches the threshold, whose accounts end with the deposit accounts of
+    // `check_deposit_accounts` on votes holding one
+    pub fn approve_admin_action(&mut self, vote_id: VoteId, action: &AdminAction, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+        self.ensure_not_paused()?;
+
+        if accounts.is_empty() {
+            return Err(ProgramError::InvalidArgument); // Return error if no accounts are provided
+        }
+        if !accounts[0].is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let caller = accounts[0].key;
+
+        let vote = self.votes.get_mut(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+
+        let now = current_timestamp()?;
+        match vote.approve_action(action, caller, now)? {
+            None => Ok(()),
+            Some(AdminAction::Close) => {
+                if vote.close(caller, ClosureReason::Creator, now, accounts)? {
+                    vote.settle_deposit(true, now, accounts)?;
+                    self.count_finished_vote()?;
+                }
+                Ok(())
+            }
+            Some(AdminAction::Cancel { reason }) => {
+                vote.void(reason, caller, now)?;
+                vote.settle_deposit(true, now, accounts)?;
+                self.count_finished_vote()
+            }
+        }
+    }
+
+    pub fn get_pending_admin_action(&self, vote_id: VoteId) -> Result<Option<PendingAdminAction>, ProgramError> {
+        let vote = self.votes.get(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+        Ok(vote.pending_action.clone())
+    }
+
+    // Close a vote whose deadline has passed, anyone may call this. The deposit of the vote, if it holds one,
+    // goes to the treasury
+    pub fn finalize_expired_vote(&mut self, vote_id: VoteId, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+        self.ensure_not_paused()?;
+
+        if accounts.is_empty() {
+            return Err(ProgramError::InvalidArgument); // Return error if no accounts are provided
+        }
+
+        let caller = accounts[0].key;
+        let (accounts, outbox_account) = self.split_outbox_account(accounts)?;
+
+        let vote = self.votes.get_mut(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+
+        // Check if the voting is closed
+        vote.ensure_open()?;
+
+        let now = current_timestamp()?;
+        if vote.deadline.map_or(true, |deadline| now <= deadline) {
+            return Err(VoteError::VoteStillOpen.into()); // Return error if the vote has no deadline or it has not passed yet
+        }
+
+        let tie_break_hash = vote.closing_tie_break_hash(accounts)?;
+        vote.check_deposit_accounts(false, now, accounts)?;
+
+        vote.freeze_conviction()?;
+        vote.status = VoteStatus::Closed;
+        vote.closure = Some(ClosureRecord { closed_by: *caller, closed_at: now, reason: ClosureReason::Expired, tie_break_hash });
+        vote.refund_pending_delegations()?;
+        vote.record_change(ChangeKind::Closed)?;
+        vote.settle_deposit(false, now, accounts)?;
+        self.count_finished_vote()?;
+        self.post_to_outbox(vote_id, outbox_account)
+    }
+
+    // Finalize up to `max` votes whose deadline has passed, in ID order so repeated calls make progress.
+    // Returns how many were closed, callers repeat until it returns zero. Votes holding a creation deposit
+    // need their own vault account and are finalized one at a time with `finalize_expired_vote`
+    pub fn finalize_all_expired(&mut self, max: usize, accounts: &[AccountInfo]) -> Result<usize, ProgramError> {
+        self.ensure_not_paused()?;
+
+        let now = current_timestamp()?;
+        let expired: Vec<VoteId> = self
+            .vote_ids()
+            .into_iter()
+            .filter(|vote_id| {
+                let vote = &self.votes[vote_id];
+                matches!(vote.status, VoteStatus::Open | VoteStatus::Pending) && vote.deadline.is_some_and(|deadline| now > deadline) && !vote.holds_deposit()
+            })
+            .take(max)
+            .collect();
+
+        for vote_id in &expired {
+            self.finalize_expired_vote(*vote_id, accounts)?;
+        }
+        Ok(expired.len())
+    }
+
+    // Accept ballots again on a closed vote whose deadline has not passed
+    pub fn reopen_vote(&mut self, vote_id: VoteId, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+        self.reopen_vote_as(signer_key(accounts)?, vote_id)
+    }
+
+    pub fn reopen_vote_as(&mut self, caller: &Pubkey, vote_id: VoteId) -> Result<(), ProgramError> {
+        self.ensure_not_paused()?;
+
+        let vote = self.votes.get_mut(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+
+        if vote.creator != *caller {
+            return Err(ProgramError::InvalidArgument); // Only the creator can reopen the vote
+        }
+        if vote.status != VoteStatus::Closed {
+            return Err(ProgramError::InvalidArgument); // Only closed votes can be reopened, cancelled ones stay void
+        }
+
+        if let Some(deadline) = vote.deadline {
+            if current_timestamp()? > deadline {
+                return Err(VoteError::DeadlinePassed.into());
+            }
+        }
+
+        vote.status = VoteStatus::Open;
+        vote.closure = None;
+        vote.conviction_frozen_at = None; // Ballots keep the conviction they built up, including the closed time
+        vote.tie_resolutions.clear(); // Ties are settled again on the next close
+        increase(&mut self.stats.currently_open, 1)
+    }
+
+    // Declare an open vote void, e.g. when the proposal was withdrawn
+    // `accounts` start with the creator, and end with the deposit accounts of `check_deposit_accounts` on votes holding one
+    pub fn cancel_vote(&mut self, vote_id: VoteId, reason: String, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+        let caller = signer_key(accounts)?;
+        self.cancel(caller, vote_id, reason, accounts)
+    }
+
+    // Votes holding a creation deposit need the accounts of `cancel_vote`
+    pub fn cancel_vote_as(&mut self, caller: &Pubkey, vote_id: VoteId, reason: String) -> Result<(), ProgramError> {
+        self.cancel(caller, vote_id, reason, &[])
+    }
+
+    fn cancel(&mut self, caller: &Pubkey, vote_id: VoteId, reason: String, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+        self.ensure_not_paused()?;
+
+        let vote = self.votes.get_mut(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+        let now = current_timestamp()?;
+        vote.check_deposit_accounts(true, now, accounts)?;
+        vote.cancel(reason, caller, now)?;
+        vote.settle_deposit(true, now, accounts)?;
+
+        self.count_finished_vote()
+    }
+
+    // Whether the data of the vote guarded by `visibility` is restricted, see `viewer_key`. Unknown IDs are left
+    // to the read
+    fn restricted(&self, vote_id: VoteId, visibility: impl FnOnce(&Vote) -> Visibility) -> bool {
+        self.votes.get(&vote_id).is_some_and(|vote| visibility(vote) != Visibility::Public)
+    }
+
+    // Returns the tallies of the given question, or of every question when no index is given
+    pub fn get_results(&self, vote_id: VoteId, accounts: &[AccountInfo], question_index: Option<usize>) -> Result<Vec<HashMap<String, u32>>, ProgramError> {
+        self.get_results_as(viewer_key(accounts, self.restricted(vote_id, |vote| vote.results_visibility))?, vote_id, question_index)
+    }
+
+    pub fn get_results_as(&self, caller: &Pubkey, vote_id: VoteId, question_index: Option<usize>) -> Result<Vec<HashMap<String, u32>>, ProgramError> {
+        // Extract the vote by ID
+        let vote = self.votes.get(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+
+        // Check if the caller may see the results
+        if !vote.can_view(vote.results_visibility, caller) {
+            return Err(ProgramError::InvalidArgument); // Return error if the results are hidden from the caller
+        }
+
+        // Return the voting results
+        match question_index {
+            Some(index) => {
+                let question = vote.questions.get(index).ok_or(ProgramError::InvalidArgument)?;
+                Ok(vec![question.votes.clone()])
+            }
+            None => Ok(vote.questions.iter().map(|question| question.votes.clone()).collect()),
+        }
+    }
+
+    // Results as the `GetResults` instruction returns them, with the votes in option order
+    pub fn get_results_response(&self, vote_id: VoteId, question_index: Option<usize>, accounts: &[AccountInfo]) -> Result<ResultsResponse, ProgramError> {
+        self.get_results_response_as(viewer_key(accounts, self.restricted(vote_id, |vote| vote.results_visibility))?, vote_id, question_index)
+    }
+
+    pub fn get_results_response_as(&self, caller: &Pubkey, vote_id: VoteId, question_index: Option<usize>) -> Result<ResultsResponse, ProgramError> {
+        let results = self.get_results_as(caller, vote_id, question_index)?;
+        let vote = self.votes.get(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+        let first = question_index.unwrap_or(0);
+
+        let questions = results
+            .iter()
+            .zip(&vote.questions[first..])
+            .zip(first..)
+            .map(|((votes, question), index)| QuestionTally {
+                question_index: index as u32,
+                votes: question.options.iter().map(|option| votes.get(option.label.as_str()).copied().unwrap_or(0)).collect(),
+            })
+            .collect();
+
+        Ok(ResultsResponse { vote_id, questions })
+    }
+
+    // Recorded ballots of a question for replaying the tally, visible like the results. Sorted by voter,
+    // the order of the ballots of each voter is kept
+    pub fn get_ballots(&self, vote_id: VoteId, question_index: usize, accounts: &[AccountInfo]) -> Result<Vec<Ballot>, ProgramError> {
+        self.get_ballots_as(viewer_key(accounts, self.restricted(vote_id, |vote| vote.results_visibility))?, vote_id, question_index)
+    }
+
+    pub fn get_ballots_as(&self, caller: &Pubkey, vote_id: VoteId, question_index: usize) -> Result<Vec<Ballot>, ProgramError> {
+        let vote = self.votes.get(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+
+        // Check if the caller may see the results
+        if !vote.can_view(vote.results_visibility, caller) {
+            return Err(ProgramError::InvalidArgument); // Return error if the results are hidden from the caller
+        }
+
+        if question_index >= vote.questions.len() {
+            return Err(ProgramError::InvalidArgument); // Return error if index is out of range
+        }
+
+        let mut voters: Vec<(&Pubkey, &VoterInfo)> = vote.allowed_voters.iter().collect();
+        voters.sort_by_key(|(voter, _)| **voter);
+
+        Ok(voters
+            .into_iter()
+            .flat_map(|(_, voter_info)| voter_info.ballots.iter())
+            .filter(|ballot| ballot.question_index as usize == question_index)
+            .map(|ballot| vote.shown_ballot(ballot, caller))
+            .collect())
+    }
+
+    // Latest ballot of a voter on a question, visible like the results
+    pub fn get_ballot(&self, vote_id: VoteId, voter: &Pubkey, question_index: usize, accounts: &[AccountInfo]) -> Result<Ballot, ProgramError> {
+        self.get_ballot_as(viewer_key(accounts, self.restricted(vote_id, |vote| vote.results_visibility))?, vote_id, voter, question_index)
+    }
+
+    pub fn get_ballot_as(&self, caller: &Pubkey, vote_id: VoteId, voter: &Pubkey, question_index: usize) -> Result<Ballot, ProgramError> {
+        let vote = self.votes.get(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+
+        // Check if the caller may see the results
+        if !vote.can_view(vote.results_visibility, caller) {
+            return Err(ProgramError::InvalidArgument); // Return error if the results are hidden from the caller
+        }
+
+        let voter_info = vote.allowed_voters.get(voter).ok_or(ProgramError::InvalidArgument)?; // Return error if the voter is not found
+        let ballot = voter_info
+            .ballots
+            .iter()
+            .rev()
+            .find(|ballot| ballot.question_index as usize == question_index)
+            .ok_or(ProgramError::InvalidArgument)?; // Return error if the voter cast no ballot on the question
+        Ok(vote.shown_ballot(ballot, caller))
+    }
+
+    // Every ballot of a vote as CSV, one row per ballot sorted by voter, visible like the results. The comment
+    // column stays empty for callers other than the creator and the observers
+    #[cfg(any(test, feature = "client"))]
+    pub fn export_ballots_csv_as(&self, caller: &Pubkey, vote_id: VoteId) -> Result<String, ProgramError> {
+        let vote = self.votes.get(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+
+        // Check if the caller may see the results
+        if !vote.can_view(vote.results_visibility, caller) {
+            return Err(ProgramError::InvalidArgument); // Return error if the results are hidden from the caller
+        }
+
+        let mut voters: Vec<(&Pubkey, &VoterInfo)> = vote.allowed_voters.iter().collect();
+        voters.sort_by_key(|(voter, _)| **voter);
+
+        let mut csv = String::from("voter,question_index,option_index,option,weight,in_grace,comment\n");
+        for (voter, voter_info) in voters {
+            for ballot in &voter_info.ballots {
+                let label = &vote.questions[ballot.question_index as usize].options[ballot.option_index.index()].label;
+                let comment = vote.shown_ballot(ballot, caller).comment.map(String::from).unwrap_or_default();
+                csv.push_str(&format!(
+                    "{},{},{},{},{},{},{}\n",
+                    voter,
+                    ballot.question_index,
+                    ballot.option_index,
+                    csv_field(label),
+                    ballot.weight,
+                    ballot.in_grace,
+                    csv_field(&comment)
+                ));
+            }
+        }
+        Ok(csv)
+    }
+
+    // On-chain callers are limited to `MAX_OPTIONS_FULL_RESULTS`, the `_as` and `_at` readers off-chain are not
+    pub fn get_full_results(&self, vote_id: VoteId, accounts: &[AccountInfo]) -> Result<VoteResults, ProgramError> {
+        self.ensure_full_results_fit(vote_id)?;
+        self.get_full_results_as(viewer_key(accounts, self.restricted(vote_id, |vote| vote.results_visibility))?, vote_id)
+    }
+
+    fn ensure_full_results_fit(&self, vote_id: VoteId) -> Result<(), ProgramError> {
+        let vote = self.votes.get(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+        let options: usize = vote.questions.iter().map(|question| question.options.len()).sum();
+        if options > MAX_OPTIONS_FULL_RESULTS {
+            msg!("Vote {} has {} options, full results hold at most {}", vote_id, options, MAX_OPTIONS_FULL_RESULTS);
+            return Err(VoteError::TooManyOptionsForFullResults.into());
+        }
+        Ok(())
+    }
+
+    pub fn get_full_results_as(&self, caller: &Pubkey, vote_id: VoteId) -> Result<VoteResults, ProgramError> {
+        let vote = self.votes.get(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+
+        // Check if the caller may see the results
+        if !vote.can_view(vote.results_visibility, caller) {
+            return Err(ProgramError::InvalidArgument); // Return error if the results are hidden from the caller
+        }
+
+        vote.full_results(None)
+    }
+
+    // Results with the conviction of the ballots measured at `slot`, for readers off-chain that have no
+    // Clock sysvar. Closed votes keep the conviction they had when they closed
+    pub fn get_full_results_at(&self, caller: &Pubkey, vote_id: VoteId, slot: Slot) -> Result<VoteResults, ProgramError> {
+        let vote = self.votes.get(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+
+        // Check if the caller may see the results
+        if !vote.can_view(vote.results_visibility, caller) {
+            return Err(ProgramError::InvalidArgument); // Return error if the results are hidden from the caller
+        }
+
+        vote.full_results(Some(slot))
+    }
+
+    // Fingerprint of the full results, see `VoteResults::hash`
+    pub fn get_results_hash(&self, vote_id: VoteId, accounts: &[AccountInfo]) -> Result<Hash, ProgramError> {
+        self.ensure_full_results_fit(vote_id)?;
+        self.get_results_hash_as(viewer_key(accounts, self.restricted(vote_id, |vote| vote.results_visibility))?, vote_id)
+    }
+
+    pub fn get_results_hash_as(&self, caller: &Pubkey, vote_id: VoteId) -> Result<Hash, ProgramError> {
+        Ok(self.get_full_results_as(caller, vote_id)?.hash())
+    }
+
+    // Open a pending vote once its prerequisite closed with the required option winning, the first ballot
+    // or delegation on the vote does the check
+    fn open_if_prerequisite_met(&mut self, vote_id: VoteId) -> Result<(), ProgramError> {
+        let Some(vote) = self.votes.get(&vote_id) else {
+            return Ok(()); // The caller reports the missing vote
+        };
+        let (VoteStatus::Pending, Some(prerequisite)) = (&vote.status, vote.prerequisite) else {
+            return Ok(());
+        };
+
+        let required = Outcome::Winner { option_index: prerequisite.required_option_index.index() };
+        let passed = self.votes.get(&prerequisite.vote_id).is_some_and(|source| source.winner(0) == Ok(required));
+        if !passed {
+            msg!("Vote {} opens once vote {} passes with option {}", vote_id, prerequisite.vote_id, prerequisite.required_option_index);
+            return Err(VoteError::PrerequisiteNotMet.into());
+        }
+
+        self.votes.get_mut(&vote_id).unwrap().status = VoteStatus::Open; // Safely extract the vote as it was just read
+        Ok(())
+    }
+
+    // Draw `count` voters of a closed vote, e.g. for a jury. The draw is seeded by the results hash and the
+    // latest slot hash, read from the slot hashes sysvar following the creator in `accounts`, and recorded
+    // on the vote; a vote is sampled once
+    pub fn sample_voters(&mut self, vote_id: VoteId, count: usize, accounts: &[AccountInfo]) -> Result<Vec<Pubkey>, ProgramError> {
+        let caller = signer_key(accounts)?;
+        let sysvar_account = accounts.get(1).ok_or(ProgramError::NotEnoughAccountKeys)?;
+        let (slot, slot_hash) = latest_slot_hash(sysvar_account)?;
+        self.sample_voters_as(caller, vote_id, count, slot, &slot_hash)
+    }
+
+    pub fn sample_voters_as(&mut self, caller: &Pubkey, vote_id: VoteId, count: usize, slot: Slot, slot_hash: &Hash) -> Result<Vec<Pubkey>, ProgramError> {
+        self.ensure_not_paused()?;
+        self.ensure_full_results_fit(vote_id)?;
+
+        let vote = self.votes.get_mut(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+        vote.sample_voters(caller, count, slot, slot_hash)
+    }
+
+    // Sample drawn with `sample_voters`, visible like the allowlist it was drawn from
+    pub fn get_voter_sample(&self, vote_id: VoteId, accounts: &[AccountInfo]) -> Result<Option<VoterSample>, ProgramError> {
+        self.get_voter_sample_as(viewer_key(accounts, self.restricted(vote_id, |vote| vote.allowlist_visibility))?, vote_id)
+    }
+
+    pub fn get_voter_sample_as(&self, caller: &Pubkey, vote_id: VoteId) -> Result<Option<VoterSample>, ProgramError> {
+        let vote = self.votes.get(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+
+        if !vote.can_view(vote.allowlist_visibility, caller) {
+            return Err(ProgramError::InvalidArgument); // Return error if the allowlist is hidden from the caller
+        }
+
+        Ok(vote.sample.clone())
+    }
+
+    // Option results of a question from `offset` on, at most `MAX_RESULTS_PAGE` of them per call
+    pub fn get_results_page(&self, vote_id: VoteId, question_index: usize, offset: u32, limit: u32, accounts: &[AccountInfo]) -> Result<ResultsPage, ProgramError> {
+        self.get_results_page_as(viewer_key(accounts, self.restricted(vote_id, |vote| vote.results_visibility))?, vote_id, question_index, offset, limit)
+    }
+
+    pub fn get_results_page_as(&self, caller: &Pubkey, vote_id: VoteId, question_index: usize, offset: u32, limit: u32) -> Result<ResultsPage, ProgramError> {
+        let vote = self.votes.get(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+
+        // Check if the caller may see the results
+        if !vote.can_view(vote.results_visibility, caller) {
+            return Err(ProgramError::InvalidArgument); // Return error if the results are hidden from the caller
+        }
+
+        let question = vote.questions.get(question_index).ok_or(ProgramError::InvalidArgument)?;
+
+        Ok(ResultsPage {
+            vote_id,
+            question_index: question_index as u32,
+            offset,
+            options: question.results_page(&vote.weighted_votes_at(question_index, vote.conviction_slot(None)?), vote.per_option_cap, offset, limit),
+            total_options: question.options.len().min(u32::MAX as usize) as u32,
+        })
+    }
+
+    // Turnout and leading option of every question, cheap enough for large option lists
+    pub fn get_summary(&self, vote_id: VoteId, accounts: &[AccountInfo]) -> Result<ResultsSummary, ProgramError> {
+        self.get_summary_as(viewer_key(accounts, self.restricted(vote_id, |vote| vote.results_visibility))?, vote_id)
+    }
+
+    pub fn get_summary_as(&self, caller: &Pubkey, vote_id: VoteId) -> Result<ResultsSummary, ProgramError> {
+        let vote = self.votes.get(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+
+        // Check if the caller may see the results
+        if !vote.can_view(vote.results_visibility, caller) {
+            return Err(ProgramError::InvalidArgument); // Return error if the results are hidden from the caller
+        }
+
+        let (eligible_voters, participating_voters) = vote.turnout();
+        let slot = vote.conviction_slot(None)?;
+        Ok(ResultsSummary {
+            vote_id,
+            eligible_voters,
+            participating_voters,
+            questions: vote.questions.iter().enumerate().map(|(index, question)| question.summary(&vote.weighted_votes_at(index, slot))).collect(),
+        })
+    }
+
+    // Compact results of up to `MAX_RESULTS_BATCH` votes in one call, e.g. for a dashboard. Each vote applies its own
+    // visibility settings, a vote that is missing or hidden from the caller carries its error without failing the others
+    pub fn get_results_batch(&self, vote_ids: &[VoteId], accounts: &[AccountInfo]) -> Result<Vec<BatchResult>, ProgramError> {
+        self.get_results_batch_as(viewer_key(accounts, vote_ids.iter().any(|vote_id| self.restricted(*vote_id, |vote| vote.results_visibility)))?, vote_ids)
+    }
+
+    pub fn get_results_batch_as(&self, caller: &Pubkey, vote_ids: &[VoteId]) -> Result<Vec<BatchResult>, ProgramError> {
+        if vote_ids.len() > MAX_RESULTS_BATCH {
+            msg!("The batch reads {} votes, at most {} are allowed", vote_ids.len(), MAX_RESULTS_BATCH);
+            return Err(VoteError::BatchTooLarge.into());
+        }
+
+        vote_ids.iter().map(|vote_id| Ok((*vote_id, self.results_lite(caller, *vote_id)?))).collect()
+    }
+
+    fn results_lite(&self, caller: &Pubkey, vote_id: VoteId) -> Result<Result<VoteResultsLite, VoteError>, ProgramError> {
+        let Some(vote) = self.votes.get(&vote_id) else {
+            return Ok(Err(VoteError::VoteNotFound));
+        };
+        if !vote.can_view(vote.results_visibility, caller) {
+            return Ok(Err(VoteError::ResultsHidden));
+        }
+
+        let slot = vote.conviction_slot(None)?;
+        let totals: Vec<u32> = vote.questions.iter().map(|question| question.votes.values().sum()).collect();
+        Ok(Ok(VoteResultsLite {
+            leading_option: vote.questions.first().and_then(|question| question.summary(&vote.weighted_votes_at(0, slot)).leading_option),
+            total_ballots: totals.iter().fold(0u32, |total, votes| total.saturating_add(*votes)),
+            status: VoteStatusKind::from(&vote.status),
+            quorum_met: vote.quorum.map_or(true, |quorum| totals.iter().all(|total| *total >= quorum)),
+        }))
+    }
+
+    // Best case of every option: its votes so far plus every uncast vote and every vote held in a pending delegation
+    // of its question. Readable by the creator and the observers by default, see `VoteConfig::projection_visibility`
+    pub fn get_projection(&self, vote_id: VoteId, accounts: &[AccountInfo]) -> Result<Projection, ProgramError> {
+        self.get_projection_as(viewer_key(accounts, self.restricted(vote_id, |vote| vote.projection_visibility))?, vote_id)
+    }
+
+    pub fn get_projection_as(&self, caller: &Pubkey, vote_id: VoteId) -> Result<Projection, ProgramError> {
+        let vote = self.votes.get(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+
+        if !vote.can_view(vote.projection_visibility, caller) {
+            return Err(ProgramError::InvalidArgument); // Return error if the projection is hidden from the caller
+        }
+
+        Ok(vote.projection())
+    }
+
+    pub fn get_winner(&self, vote_id: VoteId, question_index: usize, accounts: &[AccountInfo]) -> Result<Outcome, ProgramError> {
+        self.get_winner_as(viewer_key(accounts, self.restricted(vote_id, |vote| vote.results_visibility))?, vote_id, question_index)
+    }
+
+    pub fn get_winner_as(&self, caller: &Pubkey, vote_id: VoteId, question_index: usize) -> Result<Outcome, ProgramError> {
+        let vote = self.votes.get(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+
+        // Check if the caller may see the results
+        if !vote.can_view(vote.results_visibility, caller) {
+            return Err(ProgramError::InvalidArgument); // Return error if the results are hidden from the caller
+        }
+
+        vote.winner(question_index)
+    }
+
+    // Options of the first question taking the `seats` of a closed vote, see `VoteConfig::per_option_quorum`
+    pub fn get_elected(&self, vote_id: VoteId, accounts: &[AccountInfo]) -> Result<Vec<ElectedOption>, ProgramError> {
+        self.get_elected_as(viewer_key(accounts, self.restricted(vote_id, |vote| vote.results_visibility))?, vote_id)
+    }
+
+    pub fn get_elected_as(&self, caller: &Pubkey, vote_id: VoteId) -> Result<Vec<ElectedOption>, ProgramError> {
+        let vote = self.votes.get(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+
+        // Check if the caller may see the results
+        if !vote.can_view(vote.results_visibility, caller) {
+            return Err(ProgramError::InvalidArgument); // Return error if the results are hidden from the caller
+        }
+
+        vote.elected()
+    }
+
+    // Pick the winner of a tie in a closed vote breaking ties by creator decision. The pick is final
+    pub fn resolve_tie(&mut self, vote_id: VoteId, question_index: usize, option_index: OptionIndex, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+        self.resolve_tie_as(signer_key(accounts)?, vote_id, question_index, option_index)
+    }
+
+    pub fn resolve_tie_as(&mut self, caller: &Pubkey, vote_id: VoteId, question_index: usize, option_index: OptionIndex) -> Result<(), ProgramError> {
+        self.ensure_not_paused()?;
+
+        let vote = self.votes.get_mut(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+
+        if vote.creator != *caller {
+            return Err(ProgramError::InvalidArgument); // Only the creator can resolve ties
+        }
+        if vote.tie_break != TieBreak::CreatorDecides || vote.tie_resolutions.contains_key(&(question_index as u32)) {
+            return Err(ProgramError::InvalidArgument); // Return error if the vote breaks ties otherwise or the tie is resolved
+        }
+
+        // The pick has to be one of the tied options
+        match vote.winner(question_index)? {
+            Outcome::Tie { option_indices } if option_indices.contains(&option_index.index()) => {}
+            _ => return Err(ProgramError::InvalidArgument),
+        }
+
+        vote.tie_resolutions.insert(question_index as u32, option_index.into());
+        Ok(())
+    }
+
+    // Tallies of the vote over time, oldest bucket first. Empty for votes that do not track their history
+    pub fn get_history(&self, vote_id: VoteId, accounts: &[AccountInfo]) -> Result<Vec<HistoryBucket>, ProgramError> {
+        self.get_history_as(viewer_key(accounts, self.restricted(vote_id, |vote| vote.results_visibility))?, vote_id)
+    }
+
+    pub fn get_history_as(&self, caller: &Pubkey, vote_id: VoteId) -> Result<Vec<HistoryBucket>, ProgramError> {
+        let vote = self.votes.get(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+
+        // The history reveals the tallies
+        if !vote.can_view(vote.results_visibility, caller) {
+            return Err(ProgramError::InvalidArgument); // Return error if the results are hidden from the caller
+        }
+
+        Ok(vote.history.clone())
+    }
+
+    // Changes of the vote after `seq`, for indexers polling the vote. Pass 0 for every kept change
+    pub fn get_changes_since(&self, vote_id: VoteId, seq: u64) -> Result<Changes, ProgramError> {
+        let vote = self.votes.get(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+        Ok(vote.changes_since(seq))
+    }
+
+    // Movements of votes after `since_seq`, at most the latest `MAX_LEDGER_ENTRIES`, with the totals of every
+    // question. Only for the creator and the observers, the entries name the voters
+    pub fn get_ledger(&self, vote_id: VoteId, since_seq: u64, accounts: &[AccountInfo]) -> Result<LedgerPage, ProgramError> {
+        self.get_ledger_as(viewer_key(accounts, true)?, vote_id, since_seq)
+    }
+
+    pub fn get_ledger_as(&self, caller: &Pubkey, vote_id: VoteId, since_seq: u64) -> Result<LedgerPage, ProgramError> {
+        let vote = self.votes.get(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+
+        if !vote.can_view(Visibility::CreatorOnly, caller) {
+            return Err(ProgramError::InvalidArgument); // Return error if the caller is neither the creator nor an observer
+        }
+
+        Ok(vote.ledger.since(since_seq))
+    }
+
+    pub fn get_allowed_voters(&self, vote_id: VoteId, accounts: &[AccountInfo]) -> Result<Vec<Pubkey>, ProgramError> {
+        self.get_allowed_voters_as(viewer_key(accounts, self.restricted(vote_id, |vote| vote.allowlist_visibility))?, vote_id)
+    }
+
+    pub fn get_allowed_voters_as(&self, caller: &Pubkey, vote_id: VoteId) -> Result<Vec<Pubkey>, ProgramError> {
+        let vote = self.votes.get(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+
+        // Check if the caller may see the allowlist
+        if !vote.can_view(vote.allowlist_visibility, caller) {
+            return Err(ProgramError::InvalidArgument); // Return error if the allowlist is hidden from the caller
+        }
+
+        // Sort the voters so the output does not depend on the map order
+        let mut voters: Vec<Pubkey> = vote.allowed_voters.iter().filter(|(_, voter_info)| !voter_info.banned).map(|(voter, _)| *voter).collect();
+        voters.sort();
+
+        Ok(voters)
+    }
+
+    // Allowed voters who only joined through delegations, the creator never added them. Sorted like `get_allowed_voters`
+    pub fn get_delegation_only_voters(&self, vote_id: VoteId, accounts: &[AccountInfo]) -> Result<Vec<Pubkey>, ProgramError> {
+        self.get_delegation_only_voters_as(viewer_key(accounts, self.restricted(vote_id, |vote| vote.allowlist_visibility))?, vote_id)
+    }
+
+    pub fn get_delegation_only_voters_as(&self, caller: &Pubkey, vote_id: VoteId) -> Result<Vec<Pubkey>, ProgramError> {
+        let vote = self.votes.get(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+        let voters = self.get_allowed_voters_as(caller, vote_id)?;
+        Ok(voters.into_iter().filter(|voter| vote.allowed_voters[voter].via_delegation_only).collect())
+    }
+
+    // Open votes the voter can still cast a ballot in, soonest deadline first and votes without a deadline last,
+    // then by ID. Votes past their deadline stay listed until they are finalized
+    pub fn pending_votes_for(&self, voter: &Pubkey) -> Vec<VoteSummary> {
+        let mut pending: Vec<VoteSummary> = self
+            .votes
+            .values()
+            .filter(|vote| vote.status == VoteStatus::Open && vote.is_voter_allowed(voter))
+            .filter(|vote| vote.allowed_voters[voter].votes_left.iter().any(|votes| *votes > 0))
+            .map(|vote| vote.summary())
+            .collect();
+
+        pending.sort_by_key(|summary| (summary.deadline.is_none(), summary.deadline, summary.id));
+        pending
+    }
+
+    // ID the next vote created in the registry gets, e.g. to derive its vote address ahead of the creation
+    pub fn next_vote_id(&self) -> VoteId {
+        self.current_id
+    }
+
+    // IDs of every vote in the registry, in creation order
+    pub fn vote_ids(&self) -> Vec<VoteId> {
+        let mut vote_ids: Vec<VoteId> = self.votes.keys().copied().collect();
+        vote_ids.sort();
+        vote_ids
+    }
+
+    // Summaries of every vote in display order: pinned votes first, then by display order with unordered votes
+    // last, then by ID. Spans every shard of a sharded registry
+    pub fn list_votes(&self) -> Vec<VoteSummary> {
+        self.sorted_for_display(|_| true)
+    }
+
+    // Summaries of the votes of one shard, in creation order like the shard account lists them
+    pub fn list_shard(&self, shard_index: u32) -> Result<Vec<VoteSummary>, ProgramError> {
+        if shard_index >= self.shard_count {
+            return Err(ProgramError::InvalidArgument); // Return error if the shard does not exist
+        }
+
+        let mut summaries: Vec<VoteSummary> = self
+            .vote_shards
+            .iter()
+            .filter(|(_, index)| **index == shard_index)
+            .map(|(vote_id, _)| self.votes[vote_id].summary())
+            .collect();
+        summaries.sort_by_key(|summary| summary.id);
+        Ok(summaries)
+    }
+
+    // Summaries of the open votes, in the order of `list_votes`
+    pub fn list_open_votes(&self) -> Vec<VoteSummary> {
+        self.sorted_for_display(|vote| vote.status == VoteStatus::Open)
+    }
+
+    fn sorted_for_display(&self, filter: impl Fn(&Vote) -> bool) -> Vec<VoteSummary> {
+        let mut summaries: Vec<VoteSummary> = self.votes.values().filter(|vote| filter(vote)).map(|vote| vote.summary()).collect();
+        summaries.sort_by_key(|summary| (!summary.pinned, summary.display_order.is_none(), summary.display_order, summary.id));
+        summaries
+    }
+
+    // Pin a vote and set its display order for front ends, see `list_votes`. Up to the creator at any time, and
+    // to the registry admin, e.g. for announcements to the whole registry
+    pub fn set_display_order(&mut self, vote_id: VoteId, pinned: bool, order: Option<u16>, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+        self.set_display_order_as(signer_key(accounts)?, vote_id, pinned, order)
+    }
+
+    pub fn set_display_order_as(&mut self, caller: &Pubkey, vote_id: VoteId, pinned: bool, order: Option<u16>) -> Result<(), ProgramError> {
+        self.ensure_not_paused()?;
+
+        let admin = self.admin;
+        let vote = self.votes.get_mut(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+        if *caller != vote.creator && *caller != admin {
+            return Err(ProgramError::InvalidArgument); // Return error if neither the creator nor the registry admin
+        }
+
+        vote.pinned = pinned;
+        vote.display_order = order;
+        Ok(())
+    }
+
+    pub fn get_vote_summary(&self, vote_id: VoteId) -> Result<VoteSummary, ProgramError> {
+        if let Some(vote) = self.votes.get(&vote_id) {
+            Ok(vote.summary())
+        } else {
+            Err(ProgramError::InvalidArgument) // Return error if the vote does not exist
+        }
+    }
+
+    pub fn add_allowed_voter(&mut self, vote_id: VoteId, voter: Pubkey, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+        self.add_allowed_voter_as(signer_key(accounts)?, vote_id, voter)
+    }
+
+    pub fn add_allowed_voter_as(&mut self, caller: &Pubkey, vote_id: VoteId, voter: Pubkey) -> Result<(), ProgramError> {
+        self.add_expiring_voter_as(caller, vote_id, voter, None)
+    }
+
+    // Add a voter whose entry only votes and delegates until `valid_until`, e.g. a contractor for their quarter.
+    // Expired entries can be pruned by anyone with `prune_expired_voters`
+    pub fn add_expiring_voter(&mut self, vote_id: VoteId, voter: Pubkey, valid_until: Option<i64>, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+        self.add_expiring_voter_as(signer_key(accounts)?, vote_id, voter, valid_until)
+    }
+
+    pub fn add_expiring_voter_as(&mut self, caller: &Pubkey, vote_id: VoteId, voter: Pubkey, valid_until: Option<i64>) -> Result<(), ProgramError> {
+        self.ensure_not_paused()?;
+
+        if let Some(vote) = self.votes.get_mut(&vote_id) {
+            let voters_before = vote.allowed_voters.len();
+            vote.add_allowed_voter(voter, 1, valid_until, caller)?;
+            self.count_new_voters(vote_id, voters_before)
+        } else {
+            Err(ProgramError::InvalidArgument) // Return error if the vote does not exist
+        }
+    }
+
+    // Remove up to `max` expired allowlist entries of an open vote to free their room, the earliest expiry first.
+    // Anyone may call it. Ballots of pruned voters stand like the ones of removed voters, struck voters stay
+    // listed so their ban holds. Returns the number of pruned entries
+    pub fn prune_expired_voters(&mut self, vote_id: VoteId, max: u32) -> Result<u32, ProgramError> {
+        self.ensure_not_paused()?;
+
+        let vote = self.votes.get_mut(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+        vote.prune_expired_voters(max as usize, current_timestamp()?)
+    }
+
+    // Add a voter with their token weight to a token-weighted vote
+    pub fn add_weighted_voter(&mut self, vote_id: VoteId, voter: Pubkey, weight: u64, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+        self.add_weighted_voter_as(signer_key(accounts)?, vote_id, voter, weight)
+    }
+
+    pub fn add_weighted_voter_as(&mut self, caller: &Pubkey, vote_id: VoteId, voter: Pubkey, weight: u64) -> Result<(), ProgramError> {
+        self.ensure_not_paused()?;
+
+        let vote = self.votes.get_mut(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+        if vote.vote_type != VoteType::TokenWeighted {
+            return Err(ProgramError::InvalidArgument); // Only token-weighted votes record weights
+        }
+
+        let voters_before = vote.allowed_voters.len();
+        vote.add_allowed_voter(voter, weight, None, caller)?;
+        self.count_new_voters(vote_id, voters_before)
+    }
+
+    // Accounts: [voter, instructions sysvar, registry]. The instruction right before this one must be an ed25519
+    // program instruction with the creator's signature over `voucher.to_message` for the registry and its owner
+    pub fn redeem_invitation(&mut self, vote_id: VoteId, accounts: &[AccountInfo], voucher: Voucher) -> Result<(), ProgramError> {
+        self.ensure_not_paused()?;
+
+        let [voter, sysvar_account, registry, ..] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys); // Return error if the voter, the sysvar or the registry is missing
+        };
+
+        // The voucher must be issued for this vote and for the calling voter
+        if voucher.vote_id != vote_id || voucher.voter != *voter.key {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let vote = self.votes.get_mut(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+
+        let message = voucher.to_message(registry.owner, registry.key);
+        verify_ed25519_signature(sysvar_account, &vote.creator, &message)?;
+
+        let voters_before = vote.allowed_voters.len();
+        vote.redeem_invitation(&voucher, &message, current_timestamp()?)?;
+        self.count_new_voters(vote_id, voters_before)
+    }
+
+    // Accounts: [relayer (signer), instructions sysvar, registry]. Casts the ballot of `ballot.voter` on the first
+    // question. The instruction right before this one must be an ed25519 program instruction with the voter's
+    // `signature` over `ballot.to_message` for the registry and its owner, the relayer only pays for the transaction
+    pub fn cast_vote_signed(&mut self, accounts: &[AccountInfo], ballot: SignedBallot, signature: [u8; 64]) -> Result<(), ProgramError> {
+        signer_key(accounts)?;
+        let [_, instructions_sysvar, registry, ..] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys); // Return error if the sysvar or the registry is missing
+        };
+
+        if verify_ed25519_signature(instructions_sysvar, &ballot.voter, &ballot.to_message(registry.owner, registry.key))? != signature {
+            return Err(ProgramError::InvalidArgument); // Return error if the instruction carries another signature than the verified one
+        }
+
+        if current_timestamp()? > ballot.expiry {
+            return Err(ProgramError::InvalidArgument); // Return error if the ballot is expired
+        }
+
+        // Nonces only grow, so a relayed ballot cannot be submitted again
+        let vote = self.votes.get(&ballot.vote_id).ok_or(ProgramError::InvalidArgument)?;
+        let last_nonce = vote.allowed_voters.get(&ballot.voter).and_then(|voter_info| voter_info.last_ballot_nonce);
+        if last_nonce.is_some_and(|last_nonce| ballot.nonce <= last_nonce) {
+            return Err(ProgramError::InvalidArgument); // Return error if the nonce was already used
+        }
+
+        self.vote_on_question_as(&ballot.voter, ballot.vote_id, 0, ballot.option_index)?;
+
+        if let Some(voter_info) = self.votes.get_mut(&ballot.vote_id).and_then(|vote| vote.allowed_voters.get_mut(&ballot.voter)) {
+            voter_info.last_ballot_nonce = Some(ballot.nonce);
+        }
+        Ok(())
+    }
+
+    // Accounts: [voter, eligibility account]. Joins an open vote once the account of the voter passes
+    // the eligibility check of the vote
+    pub fn register_voter(&mut self, vote_id: VoteId, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+        self.ensure_not_paused()?;
+
+        let vote = self.votes.get_mut(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+        let eligibility = vote.eligibility.ok_or(ProgramError::InvalidArgument)?; // Return error if the vote is not open
+
+        if accounts.len() < 2 {
+            return Err(ProgramError::NotEnoughAccountKeys); // Return error if the voter or the eligibility account is missing
+        }
+        let voter = accounts[0].key;
+        eligibility::ensure_eligible(&eligibility, voter, &accounts[1])?;
+
+        let voters_before = vote.allowed_voters.len();
+        vote.register_voter(voter)?;
+        self.count_new_voters(vote_id, voters_before)
+    }
+
+    pub fn remove_allowed_voter(&mut self, vote_id: VoteId, voter: &Pubkey, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+        self.remove_allowed_voter_as(signer_key(accounts)?, vote_id, voter)
+    }
+
+    pub fn remove_allowed_voter_as(&mut self, caller: &Pubkey, vote_id: VoteId, voter: &Pubkey) -> Result<(), ProgramError> {
+        self.ensure_not_paused()?;
+
+        if let Some(vote) = self.votes.get_mut(&vote_id) {
+            vote.remove_allowed_voter(voter, caller)
+        } else {
+            Err(ProgramError::InvalidArgument) // Return error if the vote does not exist
+        }
+    }
+
+    // Take the caller off the allowlist of an open vote. With `do_not_readd` the creator cannot add them
+    // again, only joining an open vote by themselves brings them back
+    pub fn leave_vote(&mut self, vote_id: VoteId, do_not_readd: bool, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+        self.leave_vote_as(signer_key(accounts)?, vote_id, do_not_readd)
+    }
+
+    pub fn leave_vote_as(&mut self, voter: &Pubkey, vote_id: VoteId, do_not_readd: bool) -> Result<(), ProgramError> {
+        self.ensure_not_paused()?;
+
+        let vote = self.votes.get_mut(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+        vote.leave(voter, do_not_readd)
+    }
+
+    // Set or clear the content hash of an option of the first question, before any ballot is cast
+    pub fn set_option_content_hash(&mut self, vote_id: VoteId, index: OptionIndex, content_hash: Option<[u8; 32]>, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+        self.set_option_content_hash_as(signer_key(accounts)?, vote_id, index, content_hash)
+    }
+
+    pub fn set_option_content_hash_as(&mut self, caller: &Pubkey, vote_id: VoteId, index: OptionIndex, content_hash: Option<[u8; 32]>) -> Result<(), ProgramError> {
+        self.ensure_not_paused()?;
+
+        let vote = self.votes.get_mut(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+        vote.set_option_content_hash(caller, index.index(), content_hash)
+    }
+
+    // Translate the option labels of the first question into `lang`, one label per option in option order.
+    // Only for the creator and before any ballot is cast
+    pub fn set_translation(&mut self, vote_id: VoteId, lang: LangCode, labels: Vec<String>, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+        self.set_translation_as(signer_key(accounts)?, vote_id, lang, labels)
+    }
+
+    pub fn set_translation_as(&mut self, caller: &Pubkey, vote_id: VoteId, lang: LangCode, labels: Vec<String>) -> Result<(), ProgramError> {
+        self.ensure_not_paused()?;
+
+        let labels = labels.into_iter().map(|label| Label::new("label", label)).collect::<Result<Vec<_>, _>>()?;
+        let vote = self.votes.get_mut(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+        vote.set_translation(caller, lang, labels)
+    }
+
+    // Options of the first question labelled in `lang`, falling back to the labels of the options when the vote
+    // has no translation into it. The labels are for display, ballots still name options by index
+    pub fn get_options_localized(&self, vote_id: VoteId, lang: LangCode) -> Result<Vec<VoteOption>, ProgramError> {
+        let vote = self.votes.get(&vote_id).ok_or(ProgramError::InvalidArgument)?; // Return error if the vote does not exist
+        Ok(vote.localized_options(lang))
+    }
+
+    // Fix the title of a vote, the edit is recorded in the audit trail of the vote
+    pub fn update_title(&mut self, vote_id: VoteId, new_title: String, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+        self.update_title_as(signer_key(accounts)?, vote_id, new_title)
+    }
+
+    pub fn update_title_as(&mut self, caller: &Pubkey, vote_id: VoteId, new_title: String) -> Result<(), ProgramError> {
+        self.ensure_not_paused()?;
+
+        let vote = self.votes.get_mut(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+        vote.edit(caller, EditedField::Title, Some(&new_title))
+    }
+
+    // Set or clear the description of a vote, recorded like title edits
+    pub fn update_description(&mut self, vote_id: VoteId, new_description: Option<String>, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+        self.update_description_as(signer_key(accounts)?, vote_id, new_description)
+    }
+
+    pub fn update_description_as(&mut self, caller: &Pubkey, vote_id: VoteId, new_description: Option<String>) -> Result<(), ProgramError> {
+        self.ensure_not_paused()?;
+
+        let vote = self.votes.get_mut(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+        vote.edit(caller, EditedField::Description, new_description.as_deref())
+    }
+
+    // Milestones the vote has raised so far, see `Milestones`
+    pub fn get_milestones(&self, vote_id: VoteId) -> Result<Milestones, ProgramError> {
+        let vote = self.votes.get(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+        Ok(vote.milestones)
+    }
+
+    // Audit trail of the title and description edits, oldest first
+    pub fn get_edits(&self, vote_id: VoteId) -> Result<Vec<EditRecord>, ProgramError> {
+        let vote = self.votes.get(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+        Ok(vote.edits.clone())
+    }
+
+    // Let `observer` read the results and the allowlist of the vote without making them a voter
+    pub fn add_observer(&mut self, vote_id: VoteId, observer: Pubkey, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+        self.add_observer_as(signer_key(accounts)?, vote_id, observer)
+    }
+
+    pub fn add_observer_as(&mut self, caller: &Pubkey, vote_id: VoteId, observer: Pubkey) -> Result<(), ProgramError> {
+        self.ensure_not_paused()?;
+
+        let vote = self.votes.get_mut(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+        vote.add_observer(observer, caller)
+    }
+
+    pub fn remove_observer(&mut self, vote_id: VoteId, observer: &Pubkey, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+        self.remove_observer_as(signer_key(accounts)?, vote_id, observer)
+    }
+
+    pub fn remove_observer_as(&mut self, caller: &Pubkey, vote_id: VoteId, observer: &Pubkey) -> Result<(), ProgramError> {
+        self.ensure_not_paused()?;
+
+        let vote = self.votes.get_mut(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+        vote.remove_observer(observer, caller)
+    }
+
+    // Sign the results of a closed vote as its creator or one of its observers, see `VoteConfig::required_certifications`
+    pub fn certify_results(&mut self, vote_id: VoteId, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+        self.certify_results_as(signer_key(accounts)?, vote_id)
+    }
+
+    pub fn certify_results_as(&mut self, caller: &Pubkey, vote_id: VoteId) -> Result<(), ProgramError> {
+        self.ensure_not_paused()?;
+
+        let now = current_timestamp()?;
+        let vote = self.votes.get_mut(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+        let results_hash = vote.certify_results(caller, now)?;
+
+        vote.emit(VoteEvent::ResultsCertified { vote_id, certifier: *caller, results_hash, certified_at: now });
+        Ok(())
+    }
+
+    // Certifications in signing order. A certification whose hash differs from the current `VoteResults::hash`
+    // was given to other results
+    pub fn get_certifications(&self, vote_id: VoteId) -> Result<Vec<Certification>, ProgramError> {
+        let vote = self.votes.get(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+        Ok(vote.certifications.clone())
+    }
+
+    // Record that the caller, an allowlisted voter, cast no ballot and delegated nothing in the closed vote
+    pub fn attest_non_participation(&mut self, vote_id: VoteId, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+        self.attest_non_participation_as(signer_key(accounts)?, vote_id)
+    }
+
+    pub fn attest_non_participation_as(&mut self, voter: &Pubkey, vote_id: VoteId) -> Result<(), ProgramError> {
+        self.ensure_not_paused()?;
+
+        let now = current_timestamp()?;
+        let vote = self.votes.get_mut(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+        vote.attest_non_participation(voter, now)
+    }
+
+    // Attestations of non-participation in attestation order
+    pub fn get_attestations(&self, vote_id: VoteId) -> Result<Vec<NonParticipationAttestation>, ProgramError> {
+        let vote = self.votes.get(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+        Ok(vote.attestations.clone())
+    }
+
+    // Whether the current results carry the certifications the vote requires
+    pub fn is_certified(&self, vote_id: VoteId) -> Result<bool, ProgramError> {
+        self.votes.get(&vote_id).ok_or(ProgramError::InvalidArgument)?.is_certified()
+    }
+
+    // Remaining votes and personal deadline of a voter, visible to the voter and to whoever may read the allowlist
+    pub fn get_voter_status(&self, vote_id: VoteId, voter: &Pubkey, accounts: &[AccountInfo]) -> Result<VoterStatus, ProgramError> {
+        self.get_voter_status_as(viewer_key(accounts, self.restricted(vote_id, |vote| vote.allowlist_visibility))?, vote_id, voter)
+    }
+
+    pub fn get_voter_status_as(&self, caller: &Pubkey, vote_id: VoteId, voter: &Pubkey) -> Result<VoterStatus, ProgramError> {
+        let vote = self.votes.get(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+
+        if caller != voter && !vote.can_view(vote.allowlist_visibility, caller) {
+            return Err(ProgramError::InvalidArgument); // Return error if the allowlist is hidden from the caller
+        }
+
+        let voter_info = vote.allowed_voters.get(voter).ok_or(ProgramError::InvalidArgument)?; // Return error if the voter is not found
+
+        Ok(VoterStatus {
+            votes_left: voter_info.votes_left.clone(),
+            delegate: voter_info.delegate,
+            banned: voter_info.banned,
+            deadline: vote.voter_deadline(voter_info),
+            weight: voter_info.weight,
+            received_weight: voter_info.received_weight,
+            received_delegations: vote.received_delegations(voter),
+            via_delegation_only: voter_info.via_delegation_only,
+            valid_until: voter_info.valid_until,
+        })
+    }
+
+    pub fn get_voter_status_response(&self, vote_id: VoteId, voter: &Pubkey, accounts: &[AccountInfo]) -> Result<VoterStatusResponse, ProgramError> {
+        let status = self.get_voter_status(vote_id, voter, accounts)?;
+        Ok(VoterStatusResponse { vote_id, voter: *voter, status })
+    }
+
+    pub fn is_voter_allowed(&self, vote_id: VoteId, voter: &Pubkey) -> Result<bool, ProgramError> {
+        if let Some(vote) = self.votes.get(&vote_id) {
+            Ok(vote.is_voter_allowed(voter))
+        } else {
+            Err(ProgramError::InvalidArgument) // Return error if the vote does not exist
+        }
+    }
+
+    // Delegate one vote of every question, until `expires_at` if given. Token-weighted votes hand over all the available weight instead
+    pub fn delegate_vote(&mut self, vote_id: VoteId, delegate: &Pubkey, expires_at: Option<i64>, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+        self.delegate(signer_key(accounts)?, vote_id, delegate, None, DelegationScope::AllQuestions, expires_at)
+    }
+
+    pub fn delegate_vote_as(&mut self, delegator: &Pubkey, vote_id: VoteId, delegate: &Pubkey, expires_at: Option<i64>) -> Result<(), ProgramError> {
+        self.delegate(delegator, vote_id, delegate, None, DelegationScope::AllQuestions, expires_at)
+    }
+
+    // Delegate one vote of the questions in `scope`, e.g. only the budget question of a vote. Scoped delegations
+    // do not expire and are refused in token-weighted votes, where the weight counts on every question
+    pub fn delegate_vote_scoped(&mut self, vote_id: VoteId, delegate: &Pubkey, scope: DelegationScope, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+        self.delegate(signer_key(accounts)?, vote_id, delegate, None, scope, None)
+    }
+
+    pub fn delegate_vote_scoped_as(&mut self, delegator: &Pubkey, vote_id: VoteId, delegate: &Pubkey, scope: DelegationScope) -> Result<(), ProgramError> {
+        self.delegate(delegator, vote_id, delegate, None, scope, None)
+    }
+
+    // Hand part of the caller's weight to a delegate in a token-weighted vote
+    pub fn delegate_weight(&mut self, vote_id: VoteId, delegate: &Pubkey, weight: u64, expires_at: Option<i64>, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+        self.delegate(signer_key(accounts)?, vote_id, delegate, Some(weight), DelegationScope::AllQuestions, expires_at)
+    }
+
+    pub fn delegate_weight_as(&mut self, delegator: &Pubkey, vote_id: VoteId, delegate: &Pubkey, weight: u64, expires_at: Option<i64>) -> Result<(), ProgramError> {
+        self.delegate(delegator, vote_id, delegate, Some(weight), DelegationScope::AllQuestions, expires_at)
+    }
+
+    fn delegate(&mut self, delegator: &Pubkey, vote_id: VoteId, delegate: &Pubkey, weight: Option<u64>, scope: DelegationScope, expires_at: Option<i64>) -> Result<(), ProgramError> {
+        self.ensure_not_paused()?;
+        self.open_if_prerequisite_met(vote_id)?;
+
+        // Check if the vote with the given ID exists
+        let vote = self.votes.get_mut(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+
+        if scope != DelegationScope::AllQuestions && (vote.vote_type == VoteType::TokenWeighted || weight.is_some()) {
+            return Err(ProgramError::InvalidArgument); // Return error for a scoped delegation of weight
+        }
+
+        if let Some(expires_at) = expires_at {
+            if expires_at <= current_timestamp()? {
+                return Err(ProgramError::InvalidArgument); // Return error if the delegation would expire right away
+            }
+        }
+
+        // Expired delegations of the delegator return before the new one
+        if vote.is_voter_allowed(delegator) {
+            vote.reclaim_expired_delegations(delegator)?;
+        }
+
+        // The delegate has to accept the delegation first
+        if vote.require_delegate_acceptance {
+            return vote.propose_delegation(delegate, delegator, weight, scope, expires_at);
+        }
+
+        self.apply_delegation(vote_id, delegate, delegator, weight, scope, expires_at)
+    }
+
+    fn apply_delegation(&mut self, vote_id: VoteId, delegate: &Pubkey, delegator: &Pubkey, weight: Option<u64>, scope: DelegationScope, expires_at: Option<i64>) -> Result<(), ProgramError> {
+        let vote = self.votes.get_mut(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+
+        // Call the delegate_vote method of the vote
+        let voters_before = vote.allowed_voters.len();
+        match (vote.vote_type, weight) {
+            (VoteType::TokenWeighted, _) | (_, Some(_)) => vote.delegate_weight(delegate, delegator, weight, expires_at)?,
+            _ => vote.delegate_vote(delegate, delegator, scope, expires_at)?,
+        }
+        vote.record_change(ChangeKind::Delegated)?;
+        self.count_new_voters(vote_id, voters_before)
+    }
+
+    // Accept a pending delegation of `delegator` to the caller, in votes requiring delegate acceptance
+    pub fn accept_delegation(&mut self, vote_id: VoteId, delegator: &Pubkey, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+        self.accept_delegation_as(signer_key(accounts)?, vote_id, delegator)
+    }
+
+    pub fn accept_delegation_as(&mut self, delegate: &Pubkey, vote_id: VoteId, delegator: &Pubkey) -> Result<(), ProgramError> {
+        self.ensure_not_paused()?;
+
+        let vote = self.votes.get_mut(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+
+        // Check if the voting is closed
+        vote.ensure_open()?;
+
+        // The held votes go back to the delegator and move on from there like a direct delegation
+        let pending = vote.take_pending_delegation(delegator, delegate)?;
+        if let Some(expires_at) = pending.expires_at {
+            if expires_at <= current_timestamp()? {
+                return Err(ProgramError::InvalidArgument); // Return error if the delegation expired before it was accepted
+            }
+        }
+
+        if vote.is_voter_allowed(delegator) {
+            vote.reclaim_expired_delegations(delegator)?;
+        }
+
+        let weight = (pending.weight > 0).then_some(pending.weight);
+        self.apply_delegation(vote_id, delegate, delegator, weight, pending.scope, pending.expires_at)
+    }
+
+    // Withdraw a pending delegation of the caller before the delegate accepts it, the held votes return
+    pub fn withdraw_delegation(&mut self, vote_id: VoteId, delegate: &Pubkey, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+        self.withdraw_delegation_as(signer_key(accounts)?, vote_id, delegate)
+    }
+
+    pub fn withdraw_delegation_as(&mut self, delegator: &Pubkey, vote_id: VoteId, delegate: &Pubkey) -> Result<(), ProgramError> {
+        self.ensure_not_paused()?;
+
+        let vote = self.votes.get_mut(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+
+        vote.take_pending_delegation(delegator, delegate)?;
+        Ok(())
+    }
+
+    pub fn get_pending_delegations(&self, vote_id: VoteId) -> Result<Vec<PendingDelegation>, ProgramError> {
+        let vote = self.votes.get(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+        Ok(vote.pending_delegations.clone())
+    }
+
+    // Return the unspent votes of expired delegations to the delegator, anyone may call this
+    pub fn reclaim_expired_delegations(&mut self, vote_id: VoteId, delegator: &Pubkey) -> Result<(), ProgramError> {
+        self.ensure_not_paused()?;
+
+        let vote = self.votes.get_mut(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+
+        // Check if the voting is closed
+        vote.ensure_open()?;
+
+        vote.reclaim_expired_delegations(delegator)
+    }
+
+    // Strike the ballots of a voter from an open vote, e.g. a sybil found after the fact
+    pub fn invalidate_voter_ballots(&mut self, vote_id: VoteId, voter: &Pubkey, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+        self.invalidate_voter_ballots_as(signer_key(accounts)?, vote_id, voter)
+    }
+
+    pub fn invalidate_voter_ballots_as(&mut self, caller: &Pubkey, vote_id: VoteId, voter: &Pubkey) -> Result<(), ProgramError> {
+        self.ensure_not_paused()?;
+
+        let vote = self.votes.get_mut(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+        let ballots_removed = vote.invalidate_voter_ballots(voter, caller)?;
+
+        vote.emit(VoteEvent::VoterBallotsInvalidated { vote_id, voter: *voter, ballots_removed });
+
+        Ok(())
+    }
+
+    // Withdraw an option of the first question of an open vote, see `VoteConfig::refund_on_withdraw`
+    pub fn withdraw_option(&mut self, vote_id: VoteId, index: OptionIndex, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+        self.withdraw_option_as(signer_key(accounts)?, vote_id, index)
+    }
+
+    pub fn withdraw_option_as(&mut self, caller: &Pubkey, vote_id: VoteId, index: OptionIndex) -> Result<(), ProgramError> {
+        self.ensure_not_paused()?;
+
+        let vote = self.votes.get_mut(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+        let ballots_refunded = vote.withdraw_option(index, caller)?;
+
+        vote.emit(VoteEvent::OptionWithdrawn { vote_id, option_index: index, ballots_refunded, slot: current_slot()?, timestamp: current_timestamp()? });
+
+        Ok(())
+    }
+
+    pub fn get_options(&self, vote_id: VoteId) -> Result<&Vec<VoteOption>, ProgramError> {
+        if let Some(vote) = self.votes.get(&vote_id) {
+            Ok(vote.get_options())
+        } else {
+            Err(ProgramError::InvalidArgument) // Return error if the vote does not exist
+        }
+    }
+
+    // Options of the first question as the `GetOptions` instruction returns them
+    pub fn get_options_response(&self, vote_id: VoteId) -> Result<OptionsResponse, ProgramError> {
+        let vote = self.votes.get(&vote_id).ok_or(ProgramError::InvalidArgument)?; // Return error if the vote does not exist
+        Ok(OptionsResponse { vote_id, options: vote.get_options().clone(), format: vote.option_format })
+    }
+
+    // Syntax the option labels of the vote follow
+    pub fn get_option_format(&self, vote_id: VoteId) -> Result<OptionFormat, ProgramError> {
+        Ok(self.votes.get(&vote_id).ok_or(ProgramError::InvalidArgument)?.option_format) // Return error if the vote does not exist
+    }
+
+    // How ballots of the vote are weighted, and for token-burn votes what they cost
+    pub fn get_vote_type(&self, vote_id: VoteId) -> Result<VoteType, ProgramError> {
+        Ok(self.votes.get(&vote_id).ok_or(ProgramError::InvalidArgument)?.vote_type) // Return error if the vote does not exist
+    }
+
+    pub fn vote_exists(&self, vote_id: VoteId) -> bool {
+        self.votes.contains_key(&vote_id)
+    }
+
+    // Whether the voter holds a ballot in the vote, struck and retracted ballots do not count
+    pub fn has_voted(&self, vote_id: VoteId, voter: &Pubkey) -> bool {
+        let voter_info = self.votes.get(&vote_id).and_then(|vote| vote.allowed_voters.get(voter));
+        voter_info.is_some_and(|voter_info| !voter_info.ballots.is_empty())
+    }
+
+    // Remove the identity of a voter from a closed or cancelled vote, e.g. to honour a request to erase personal
+    // data. The results and the summary do not change, but ballot-level data does: `get_ballots` lists the
+    // ballots under the anonymous key, so a hash over them changes while `VoteResults::hash` stays the same.
+    // Voter groups of the registry are not touched. The creator or the registry admin scrubs
+    pub fn scrub_voter(&mut self, vote_id: VoteId, voter: &Pubkey, accounts: &[AccountInfo]) -> Result<u32, ProgramError> {
+        self.scrub_voter_as(signer_key(accounts)?, vote_id, voter)
+    }
+
+    pub fn scrub_voter_as(&mut self, caller: &Pubkey, vote_id: VoteId, voter: &Pubkey) -> Result<u32, ProgramError> {
+        self.ensure_not_paused()?;
+
+        let is_admin = *caller == self.admin;
+        let vote = self.votes.get_mut(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+        if *caller != vote.creator && !is_admin {
+            return Err(ProgramError::InvalidArgument); // Only the creator or the registry admin can scrub voters
+        }
+
+        let ballots_anonymized = vote.scrub_voter(voter)?;
+        vote.emit(VoteEvent::VoterScrubbed { vote_id, ballots_anonymized });
+        Ok(ballots_anonymized)
+    }
+
+    // Whether the first question of the vote has an option at `index`
+    pub fn option_exists(&self, vote_id: VoteId, index: OptionIndex) -> bool {
+        self.votes.get(&vote_id).is_some_and(|vote| index.index() < vote.get_options().len())
+    }
+
+    // Seats left on every option of a question in option order, `None` for votes without a per-option voter cap
+    pub fn get_option_seats(&self, vote_id: VoteId, question_index: usize) -> Result<Vec<Option<u32>>, ProgramError> {
+        let vote = self.votes.get(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+        let question = vote.questions.get(question_index).ok_or(ProgramError::InvalidArgument)?;
+        Ok(question.options.iter().map(|option| question.remaining_seats(&option.label, vote.per_option_voter_cap)).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{install_stubs, set_clock, set_slot, take_logs, TestHarness};
+    use solana_program::instruction::Instruction;
+    use solana_program::pubkey::Pubkey;
+    use solana_program::sysvar::instructions::{construct_instructions_data, store_current_index, BorrowedAccountMeta, BorrowedInstruction};
+    use solana_sdk::ed25519_instruction::new_ed25519_instruction;
+    use solana_sdk::signature::{Keypair, Signer};
+
+    // Build the instructions sysvar data for a transaction where the last instruction is the current one
+    fn instructions_sysvar_data(instructions: &[Instruction]) -> Vec<u8> {
+        let borrowed: Vec<BorrowedInstruction> = instructions
+            .iter()
+            .map(|instruction| BorrowedInstruction {
+                program_id: &instruction.program_id,
+                accounts: instruction
+                    .accounts
+                    .iter()
+                    .map(|meta| BorrowedAccountMeta { pubkey: &meta.pubkey, is_signer: meta.is_signer, is_writable: meta.is_writable })
+                    .collect(),
+                data: &instruction.data,
+            })
+            .collect();
+
+        let mut data = construct_instructions_data(&borrowed);
+        store_current_index(&mut data, (instructions.len() - 1) as u16);
+        data
+    }
+
+    #[test]
+    fn test_vote_error_codes() {
+        for error in VoteError::ALL {
+            let ProgramError::Custom(code) = ProgramError::from(error) else {
+                panic!("{:?} is not a custom error", error);
+            };
+            assert_eq!(VoteError::try_from(code), Ok(error));
+            assert!(!error.description().is_empty());
+        }
+
+        assert!(VoteError::try_from(VoteError::ALL.len() as u32).is_err());
+    }
+
+    #[test]
+    fn test_create_vote() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+
+        harness.create_vote(VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string(), "Option 2".to_string()]).unwrap(), &creator).unwrap();
+
+        assert_eq!(harness.voting.votes.len(), 1);
+        let vote = harness.voting.votes.get(&VoteId(0)).unwrap();
+        assert_eq!(vote.title, "Test Vote");
+        assert_eq!(vote.questions[0].options.len(), 2);
+    }
+
+    #[test]
+    fn test_add_allowed_voter() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voter1 = Pubkey::new_unique();
+
+        harness.create_vote(VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string()]).unwrap(), &creator).unwrap();
+
+        assert!(harness.allow(VoteId(0), &creator, &voter1).is_ok());
+
+        let vote = harness.voting.votes.get(&VoteId(0)).unwrap();
+        assert!(vote.is_voter_allowed(&voter1));
+    }
+
+    #[test]
+    fn test_vote() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voter1 = Pubkey::new_unique();
+
+        harness.create_vote(VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string(), "Option 2".to_string()]).unwrap(), &creator).unwrap();
+
+        assert!(harness.allow(VoteId(0), &creator, &voter1).is_ok());
+
+        assert!(harness.cast(VoteId(0), &voter1, 0).is_ok());
+
+        let vote = harness.voting.votes.get_mut(&VoteId(0)).unwrap();
+        assert_eq!(*vote.questions[0].votes.get("Option 1").unwrap(), 1);
+    }
+
+    #[test]
+    fn test_vote_not_allowed() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voter1 = Pubkey::new_unique();
+        let voter2 = Pubkey::new_unique();
+
+        harness.create_vote(VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string()]).unwrap(), &creator).unwrap();
+
+        assert!(harness.allow(VoteId(0), &creator, &voter1).is_ok());
+
+        assert!(harness.cast(VoteId(0), &voter2, 0).is_err()); // Voter is not allowed
+    }
+
+    #[test]
+    fn test_vote_no_votes_left() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voter1 = Pubkey::new_unique();
+
+        harness.create_vote(VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string()]).unwrap(), &creator).unwrap();
+
+        harness.allow(VoteId(0), &creator, &voter1).unwrap();
+
+        // Set that voter1 has no votes left
+        let new_voter = VoterInfo::new(vec![0]);
+        harness.voting.votes.get_mut(&VoteId(0)).unwrap().allowed_voters.insert(voter1, new_voter);
+
+        assert!(harness.cast(VoteId(0), &voter1, 0).is_err()); // No votes left for voting
+    }
+
+    #[test]
+    fn test_remove_allowed_voter() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voter1 = Pubkey::new_unique();
+
+        harness.create_vote(VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string()]).unwrap(), &creator).unwrap();
+
+        assert!(harness.allow(VoteId(0), &creator, &voter1).is_ok());
+
+        // Remove the allowed voter
+        assert!(harness.voting.remove_allowed_voter_as(&creator, VoteId(0), &voter1).is_ok());
+        let vote = harness.voting.votes.get(&VoteId(0)).unwrap();
+        assert!(!vote.is_voter_allowed(&voter1)); // Check that the voter has been removed
+    }
+
+    #[test]
+    fn test_remove_allowed_voter_not_creator() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voter1 = Pubkey::new_unique();
+        let non_creator = Pubkey::new_unique();
+
+        harness.create_vote(VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string()]).unwrap(), &creator).unwrap();
+
+        assert!(harness.allow(VoteId(0), &creator, &voter1).is_ok());
+
+
+        // Attempt to remove an allowed voter not as the creator
+        assert!(harness.voting.remove_allowed_voter_as(&non_creator, VoteId(0), &voter1).is_err());
+    }
+
+    #[test]
+    fn test_remove_creator_voter_with_delegations() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voter = Pubkey::new_unique();
+
+        let vote_id = harness.create_vote(VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string()]).unwrap(), &creator).unwrap();
+        harness.allow(vote_id, &creator, &creator).unwrap();
+        harness.allow(vote_id, &creator, &voter).unwrap();
+        harness.delegate(vote_id, &voter, &creator).unwrap();
+        assert_eq!(harness.voting.votes[&vote_id].allowed_voters[&creator].votes_left, vec![2]);
+
+        // The creator removing themselves hands the delegated vote back instead of orphaning it
+        harness.voting.remove_allowed_voter_as(&creator, vote_id, &creator).unwrap();
+        let voter_info = &harness.voting.votes[&vote_id].allowed_voters[&voter];
+        assert_eq!(voter_info.votes_left, vec![1]);
+        assert!(voter_info.delegations.is_empty());
+        assert_eq!(voter_info.delegate, None);
+        assert!(harness.cast(vote_id, &voter, 0).is_ok());
+    }
+
+    #[test]
+    fn test_remove_voter_with_partly_spent_delegations() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let delegate = Pubkey::new_unique();
+        let (delegator1, delegator2) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+        let vote_id = harness.create_vote(VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string()]).unwrap(), &creator).unwrap();
+        for voter in [delegate, delegator1, delegator2] {
+            harness.allow(vote_id, &creator, &voter).unwrap();
+        }
+        harness.delegate(vote_id, &delegator1, &delegate).unwrap();
+        harness.delegate(vote_id, &delegator2, &delegate).unwrap();
+
+        // The delegate spends their own vote and one delegated vote, the ballots stand
+        set_slot(1);
+        harness.cast(vote_id, &delegate, 0).unwrap();
+        set_slot(2);
+        harness.cast(vote_id, &delegate, 0).unwrap();
+
+        harness.voting.remove_allowed_voter_as(&creator, vote_id, &delegate).unwrap();
+        let vote = &harness.voting.votes[&vote_id];
+        assert!(!vote.allowed_voters.contains_key(&delegate));
+        assert_eq!(vote.allowed_voters[&delegator1].votes_left[0] + vote.allowed_voters[&delegator2].votes_left[0], 1);
+        assert!(vote.allowed_voters.values().all(|voter_info| voter_info.delegations.is_empty()));
+        assert_eq!(vote.questions[0].votes["Option 1"], 2);
+    }
+
+    #[test]
+    fn test_leave_vote_before_voting() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voter = Pubkey::new_unique();
+        let delegate = Pubkey::new_unique();
+
+        let vote_id = harness.create_vote(VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string()]).unwrap(), &creator).unwrap();
+        harness.allow(vote_id, &creator, &voter).unwrap();
+        harness.allow(vote_id, &creator, &delegate).unwrap();
+        harness.delegate(vote_id, &voter, &delegate).unwrap();
+
+        // The delegated vote leaves the delegate along with the voter
+        let voter_info = harness.account_for(&voter);
+        assert!(harness.voting.leave_vote(vote_id, false, &[voter_info]).is_ok());
+        let vote = &harness.voting.votes[&vote_id];
+        assert!(!vote.allowed_voters.contains_key(&voter));
+        assert_eq!(vote.allowed_voters[&delegate].votes_left, vec![1]);
+        assert_eq!(harness.voting.get_changes_since(vote_id, 0).unwrap().entries.last().map(|entry| entry.kind), Some(ChangeKind::VoterRemoved));
+
+        // Without the flag the creator may add the voter back, only voters on the allowlist can leave
+        assert_eq!(harness.voting.leave_vote_as(&voter, vote_id, false), Err(ProgramError::InvalidArgument));
+        assert!(harness.allow(vote_id, &creator, &voter).is_ok());
+        assert!(harness.cast(vote_id, &voter, 0).is_ok());
+    }
+
+    #[test]
+    fn test_leave_vote_after_voting() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voter = Pubkey::new_unique();
+        let delegator = Pubkey::new_unique();
+
+        let vote_id = harness.create_vote(VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string(), "Option 2".to_string()]).unwrap(), &creator).unwrap();
+        harness.allow(vote_id, &creator, &voter).unwrap();
+        harness.allow(vote_id, &creator, &delegator).unwrap();
+        harness.delegate(vote_id, &delegator, &voter).unwrap();
+        harness.cast(vote_id, &voter, 1).unwrap();
+
+        // The ballot stands as with a removal by the creator, the unspent delegated vote goes back
+        assert!(harness.voting.leave_vote_as(&voter, vote_id, false).is_ok());
+        let vote = &harness.voting.votes[&vote_id];
+        assert_eq!(vote.questions[0].votes["Option 2"], 1);
+        assert_eq!(vote.allowed_voters[&delegator].votes_left, vec![1]);
+        assert!(vote.allowed_voters[&delegator].delegations.is_empty());
+        assert!(!harness.voting.has_voted(vote_id, &voter));
+        assert!(harness.cast(vote_id, &delegator, 0).is_ok());
+
+        // Closed votes keep their allowlist
+        let other = Pubkey::new_unique();
+        harness.allow(vote_id, &creator, &other).unwrap();
+        harness.close(vote_id, &creator).unwrap();
+        assert!(harness.voting.leave_vote_as(&other, vote_id, false).is_err());
+    }
+
+    #[test]
+    fn test_leave_vote_do_not_readd() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voter = Pubkey::new_unique();
+        let friend = Pubkey::new_unique();
+
+        let config = VoteConfig {
+            delegation_policy: DelegationPolicy::AnyRecipient,
+            ..VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string()]).unwrap()
+        };
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+        harness.allow(vote_id, &creator, &voter).unwrap();
+        harness.allow(vote_id, &creator, &friend).unwrap();
+        assert!(harness.voting.leave_vote_as(&voter, vote_id, true).is_ok());
+
+        // Neither the creator nor a delegation brings the voter back
+        assert_eq!(harness.allow(vote_id, &creator, &voter), Err(VoteError::VoterOptedOut.into()));
+        assert_eq!(harness.delegate(vote_id, &friend, &voter), Err(VoteError::VoterOptedOut.into()));
+        assert!(!harness.voting.votes[&vote_id].allowed_voters.contains_key(&voter));
+
+        // Struck voters cannot leave to lift their ban
+        harness.voting.invalidate_voter_ballots_as(&creator, vote_id, &friend).unwrap();
+        assert_eq!(harness.voting.leave_vote_as(&friend, vote_id, true), Err(VoteError::VoterBanned.into()));
+    }
+
+    #[test]
+    fn test_delegate_vote() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voter1 = Pubkey::new_unique();
+        let delegate = Pubkey::new_unique();
+
+        let config = VoteConfig { delegation_policy: DelegationPolicy::AnyRecipient, ..VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string()]).unwrap() };
+        harness.create_vote(config, &creator).unwrap();
+
+        assert!(harness.allow(VoteId(0), &creator, &voter1).is_ok());
+
+        // Set that voter1 has 1 vote
+        let new_voter = VoterInfo::new(vec![1]);
+        harness.voting.votes.get_mut(&VoteId(0)).unwrap().allowed_voters.insert(voter1, new_voter);
+
+        // Perform the vote delegation
+        let result = harness.delegate(VoteId(0), &voter1, &delegate);
+
+        assert!(result.is_ok());
+
+        if let Some(voter_info) = harness.voting.votes.get_mut(&VoteId(0)).unwrap().allowed_voters.get(&voter1) {
+            assert_eq!(voter_info.votes_left, vec![0]);
+            assert_eq!(voter_info.delegate, Some(delegate));
+        } else {
+            panic!("Voter1 information not found.");
+        }
+
+        if let Some(delegate_info) = harness.voting.votes.get_mut(&VoteId(0)).unwrap().allowed_voters.get(&delegate) {
+            assert_eq!(delegate_info.votes_left, vec![1]);
+            assert!(delegate_info.via_delegation_only);
+        } else {
+            panic!("Delegate information not found.");
+        }
+    }
+
+    #[test]
+    fn test_delegation_policy() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voter1 = Pubkey::new_unique();
+        let voter2 = Pubkey::new_unique();
+        let outsider = Pubkey::new_unique();
+
+        // By default only allowed voters can receive votes, and a refused delegation creates no entry
+        let strict_id = harness.create_vote(VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string()]).unwrap(), &creator).unwrap();
+        harness.allow(strict_id, &creator, &voter1).unwrap();
+        harness.allow(strict_id, &creator, &voter2).unwrap();
+        assert_eq!(harness.delegate(strict_id, &voter1, &outsider), Err(VoteError::DelegateNotAllowed.into()));
+        assert!(!harness.voting.votes[&strict_id].allowed_voters.contains_key(&outsider));
+        assert_eq!(harness.voting.votes[&strict_id].allowed_voters[&voter1].votes_left, vec![1]);
+
+        harness.delegate(strict_id, &voter1, &voter2).unwrap();
+        assert_eq!(harness.voting.votes[&strict_id].allowed_voters[&voter2].votes_left, vec![2]);
+        assert!(harness.voting.get_delegation_only_voters_as(&creator, strict_id).unwrap().is_empty());
+
+        // The vote can opt out, the recipient then joins marked as a delegation-only voter
+        let config = VoteConfig { delegation_policy: DelegationPolicy::AnyRecipient, ..VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string()]).unwrap() };
+        let open_id = harness.create_vote(config, &creator).unwrap();
+        harness.allow(open_id, &creator, &voter1).unwrap();
+        harness.delegate(open_id, &voter1, &outsider).unwrap();
+
+        let creator_info = harness.account_for(&creator);
+        let accounts = std::slice::from_ref(&creator_info);
+        assert!(harness.voting.get_voter_status(open_id, &outsider, accounts).unwrap().via_delegation_only);
+        assert!(!harness.voting.get_voter_status(open_id, &voter1, accounts).unwrap().via_delegation_only);
+        assert_eq!(harness.voting.get_delegation_only_voters(open_id, accounts).unwrap(), vec![outsider]);
+
+        // Adding the recipient to the allowlist later makes them a regular voter
+        harness.allow(open_id, &creator, &outsider).unwrap();
+        assert!(harness.voting.get_delegation_only_voters_as(&creator, open_id).unwrap().is_empty());
+    }
+
+    // Vote requiring delegate acceptance with an allowed delegator holding one vote
+    fn acceptance_vote(harness: &mut TestHarness, creator: &Pubkey, delegator: &Pubkey) -> VoteId {
+        let config = VoteConfig { require_delegate_acceptance: true, delegation_policy: DelegationPolicy::AnyRecipient, ..VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string(), "Option 2".to_string()]).unwrap() };
+        let vote_id = harness.create_vote(config, creator).unwrap();
+        harness.allow(vote_id, creator, delegator).unwrap();
+        vote_id
+    }
+
+    #[test]
+    fn test_delegate_acceptance() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let delegator = Pubkey::new_unique();
+        let delegate = Pubkey::new_unique();
+        let vote_id = acceptance_vote(&mut harness, &creator, &delegator);
+
+        // The vote is held from the delegator, the delegate gets nothing yet
+        assert!(harness.delegate(vote_id, &delegator, &delegate).is_ok());
+        let vote = &harness.voting.votes[&vote_id];
+        assert_eq!(vote.allowed_voters[&delegator].votes_left, vec![0]);
+        assert!(!vote.allowed_voters.contains_key(&delegate));
+        assert_eq!(
+            harness.voting.get_pending_delegations(vote_id).unwrap(),
+            vec![PendingDelegation { delegator, delegate, votes: vec![1], weight: 0, expires_at: None, scope: DelegationScope::AllQuestions }]
+        );
+
+        // The held vote can neither be cast nor promised again
+        assert!(harness.cast(vote_id, &delegator, 0).is_err());
+        assert!(harness.delegate(vote_id, &delegator, &Pubkey::new_unique()).is_err());
+
+        // Only the delegate can accept it
+        assert_eq!(harness.voting.accept_delegation_as(&Pubkey::new_unique(), vote_id, &delegator), Err(ProgramError::InvalidArgument));
+
+        assert!(harness.voting.accept_delegation_as(&delegate, vote_id, &delegator).is_ok());
+        assert!(harness.voting.get_pending_delegations(vote_id).unwrap().is_empty());
+
+        let vote = &harness.voting.votes[&vote_id];
+        assert_eq!(vote.allowed_voters[&delegator].votes_left, vec![0]);
+        assert_eq!(vote.allowed_voters[&delegator].delegate, Some(delegate));
+        assert_eq!(vote.allowed_voters[&delegate].votes_left, vec![1]);
+        assert!(harness.cast(vote_id, &delegate, 1).is_ok());
+
+        // Accepting twice finds nothing
+        assert_eq!(harness.voting.accept_delegation_as(&delegate, vote_id, &delegator), Err(ProgramError::InvalidArgument));
+    }
+
+    #[test]
+    fn test_withdraw_pending_delegation() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let delegator = Pubkey::new_unique();
+        let delegate = Pubkey::new_unique();
+
+        // Weight is held the same way in token-weighted votes
+        let config = VoteConfig {
+            vote_type: VoteType::TokenWeighted,
+            require_delegate_acceptance: true,
+            delegation_policy: DelegationPolicy::AnyRecipient,
+            ..VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string(), "Option 2".to_string()]).unwrap()
+        };
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+        harness.voting.add_weighted_voter_as(&creator, vote_id, delegator, 500).unwrap();
+
+        assert!(harness.voting.delegate_weight_as(&delegator, vote_id, &delegate, 200, None).is_ok());
+        assert_eq!(harness.voting.votes[&vote_id].allowed_voters[&delegator].available_weight(), 300);
+
+        // Only the delegator can withdraw it, then the weight is theirs again
+        assert_eq!(harness.voting.withdraw_delegation_as(&delegate, vote_id, &delegator), Err(ProgramError::InvalidArgument));
+        assert!(harness.voting.withdraw_delegation_as(&delegator, vote_id, &delegate).is_ok());
+        assert_eq!(harness.voting.votes[&vote_id].allowed_voters[&delegator].available_weight(), 500);
+        assert!(harness.voting.get_pending_delegations(vote_id).unwrap().is_empty());
+
+        // A withdrawn delegation cannot be accepted
+        assert_eq!(harness.voting.accept_delegation_as(&delegate, vote_id, &delegator), Err(ProgramError::InvalidArgument));
+
+        assert!(harness.cast(vote_id, &delegator, 0).is_ok());
+        let results = harness.voting.get_full_results_as(&creator, vote_id).unwrap();
+        assert_eq!(results.questions[0].options[0].weighted_votes, 500);
+    }
+
+    #[test]
+    fn test_pending_delegation_refund_at_close() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let delegator = Pubkey::new_unique();
+        let delegate = Pubkey::new_unique();
+        let vote_id = acceptance_vote(&mut harness, &creator, &delegator);
+
+        assert!(harness.delegate(vote_id, &delegator, &delegate).is_ok());
+        assert!(harness.close(vote_id, &creator).is_ok());
+
+        // Closing refunds the held vote and drops the pending delegation
+        assert!(harness.voting.get_pending_delegations(vote_id).unwrap().is_empty());
+        assert_eq!(harness.voting.votes[&vote_id].allowed_voters[&delegator].votes_left, vec![1]);
+
+        assert!(harness.voting.accept_delegation_as(&delegate, vote_id, &delegator).is_err());
+    }
+
+    #[test]
+    fn test_delegate_vote_not_allowed() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voter1 = Pubkey::new_unique();
+        let delegate = Pubkey::new_unique();
+        let non_allowed_voter = Pubkey::new_unique();
+
+        harness.create_vote(VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string()]).unwrap(), &creator).unwrap();
+
+        assert!(harness.allow(VoteId(0), &creator, &voter1).is_ok());
+
+        // Attempt to delegate vote to a non-allowed voter
+        assert!(harness.delegate(VoteId(0), &non_allowed_voter, &delegate).is_err());
+    }
+
+    #[test]
+    fn test_account_wrappers() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voter = Pubkey::new_unique();
+
+        let vote_id = harness.create_vote(VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string()]).unwrap(), &creator).unwrap();
+        harness.allow(vote_id, &creator, &voter).unwrap();
+
+        // Changes need the first account to sign, reads only take its key
+        let mut voter_info = harness.account_for(&voter);
+        voter_info.is_signer = false;
+        assert_eq!(harness.voting.vote(vote_id, std::slice::from_ref(&voter_info), OptionIndex(0)), Err(ProgramError::MissingRequiredSignature));
+        assert_eq!(harness.voting.vote(vote_id, &[], OptionIndex(0)), Err(ProgramError::InvalidArgument));
+        assert!(!harness.voting.has_voted(vote_id, &voter));
+        assert!(harness.voting.get_voter_status(vote_id, &voter, std::slice::from_ref(&voter_info)).is_ok());
+
+        voter_info.is_signer = true;
+        harness.voting.vote(vote_id, &[voter_info], OptionIndex(0)).unwrap();
+        assert_eq!(harness.voting.get_voter_status_as(&voter, vote_id, &voter).unwrap().votes_left, vec![0]);
+        assert_eq!(harness.voting.get_results(vote_id, &[], None), Err(ProgramError::InvalidArgument));
+    }
+
+    #[test]
+    fn test_delegate_vote_no_votes_left() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voter1 = Pubkey::new_unique();
+        let delegate = Pubkey::new_unique();
+
+        harness.create_vote(VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string()]).unwrap(), &creator).unwrap();
+
+        assert!(harness.allow(VoteId(0), &creator, &voter1).is_ok());
+
+        // Set that voter1 has no votes
+        let new_voter = VoterInfo::new(vec![0]);
+        harness.voting.votes.get_mut(&VoteId(0)).unwrap().allowed_voters.insert(voter1, new_voter);
+
+        // Check that delegation fails as voter1 has no votes
+        assert!(harness.delegate(VoteId(0), &voter1, &delegate).is_err());
+    }
+
+    #[test]
+    fn test_vote_nonexistent() {
+        let mut harness = TestHarness::new();
+        let voter1 = Pubkey::new_unique();
+
+        // Attempt to vote on a non-existent voting
+        assert!(harness.cast(VoteId(999), &voter1, 0).is_err());
+    }
+
+    #[test]
+    fn test_vote_after_closing() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voter1 = Pubkey::new_unique();
+
+        // Create a vote
+        harness.create_vote(VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string(), "Option 2".to_string()]).unwrap(), &creator).unwrap();
+
+        // Add allowed voter
+        assert!(harness.allow(VoteId(0), &creator, &voter1).is_ok());
+
+        // Close the vote
+        assert!(harness.close(VoteId(0), &creator).is_ok());
+
+        // Check that voting does not pass as it is closed
+        assert!(harness.cast(VoteId(0), &voter1, 0).is_err());
+    }
+
+    #[test]
+    fn test_visibility_combinations() {
+        let visibilities = [Visibility::Public, Visibility::AllowlistOnly, Visibility::CreatorOnly];
+
+        // Expected access for (creator, allowed voter, stranger) under each visibility
+        let expected_access = |visibility: Visibility| match visibility {
+            Visibility::Public => [true, true, true],
+            Visibility::AllowlistOnly => [true, true, false],
+            Visibility::CreatorOnly => [true, false, false],
+        };
+
+        for results_visibility in visibilities {
+            for allowlist_visibility in visibilities {
+                let mut harness = TestHarness::new();
+                let creator = Pubkey::new_unique();
+                let voter1 = Pubkey::new_unique();
+                let stranger = Pubkey::new_unique();
+
+                let config = VoteConfig {
+                    results_visibility,
+                    allowlist_visibility,
+                    ..VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string()]).unwrap()
+                };
+                let vote_id = harness.create_vote(config, &creator).unwrap();
+
+                assert!(harness.voting.add_allowed_voter_as(&creator, vote_id, voter1).is_ok());
+
+                let callers = [creator, voter1, stranger];
+                for (i, caller) in callers.iter().enumerate() {
+
+                    let results = harness.voting.get_results_as(caller, vote_id, None);
+                    assert_eq!(results.is_ok(), expected_access(results_visibility)[i], "results {:?}, caller {}", results_visibility, i);
+
+                    let allowlist = harness.voting.get_allowed_voters_as(caller, vote_id);
+                    assert_eq!(allowlist.is_ok(), expected_access(allowlist_visibility)[i], "allowlist {:?}, caller {}", allowlist_visibility, i);
+                    if let Ok(voters) = allowlist {
+                        assert_eq!(voters, vec![voter1]);
+                    }
+                }
+
+                let summary = harness.voting.get_vote_summary(vote_id).unwrap();
+                assert_eq!(summary.results_visibility, results_visibility);
+                assert_eq!(summary.allowlist_visibility, allowlist_visibility);
+            }
+        }
+    }
+
+    #[test]
+    fn test_restricted_reads_need_signature() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voter = Pubkey::new_unique();
+
+        let config = VoteConfig {
+            results_visibility: Visibility::AllowlistOnly,
+            allowlist_visibility: Visibility::Public,
+            ..VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string()]).unwrap()
+        };
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+        harness.allow(vote_id, &creator, &voter).unwrap();
+
+        // Anyone can name the creator or a voter without their signature
+        let mut creator_info = harness.account_for(&creator);
+        creator_info.is_signer = false;
+        let mut voter_info = harness.account_for(&voter);
+        voter_info.is_signer = false;
+        let accounts = [creator_info.clone(), voter_info.clone()];
+        for viewer in accounts.chunks(1) {
+            assert_eq!(harness.voting.get_results(vote_id, viewer, None), Err(ProgramError::MissingRequiredSignature));
+            assert_eq!(harness.voting.get_full_results(vote_id, viewer).err(), Some(ProgramError::MissingRequiredSignature));
+            assert_eq!(harness.voting.get_results_batch(&[vote_id], viewer).err(), Some(ProgramError::MissingRequiredSignature));
+            assert_eq!(harness.voting.get_ledger(vote_id, 0, viewer).err(), Some(ProgramError::MissingRequiredSignature));
+        }
+
+        // A dry run of the voter's ballot predicts it without showing the tallies
+        let simulation = harness.voting.simulate_vote(vote_id, 0, OptionIndex(0), std::slice::from_ref(&voter_info)).unwrap();
+        assert_eq!((simulation.error, simulation.questions.len()), (None, 0));
+
+        // Public data needs no signature, restricted data is read once the viewer signs
+        assert_eq!(harness.voting.get_allowed_voters(vote_id, std::slice::from_ref(&creator_info)), Ok(vec![voter]));
+        voter_info.is_signer = true;
+        assert!(harness.voting.get_results(vote_id, std::slice::from_ref(&voter_info), None).is_ok());
+        let simulation = harness.voting.simulate_vote(vote_id, 0, OptionIndex(0), std::slice::from_ref(&voter_info)).unwrap();
+        assert_eq!(simulation.questions.len(), 1);
+    }
+
+    #[test]
+    fn test_projection() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let auditor = Pubkey::new_unique();
+        let voters: Vec<Pubkey> = (0..7).map(|_| Pubkey::new_unique()).collect();
+
+        let config = VoteConfig {
+            results_visibility: Visibility::Public,
+            require_delegate_acceptance: true,
+            ..VoteConfig::from_labels("Test Vote".to_string(), vec!["Yes".to_string(), "No".to_string(), "Maybe".to_string()]).unwrap()
+        };
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+        for voter in &voters {
+            harness.allow(vote_id, &creator, voter).unwrap();
+        }
+        harness.voting.add_observer_as(&creator, vote_id, auditor).unwrap();
+
+        // Two ballots for Yes and one for No, three voters left to vote and one delegation waiting for its delegate
+        harness.cast(vote_id, &voters[0], 0).unwrap();
+        harness.cast(vote_id, &voters[1], 0).unwrap();
+        harness.cast(vote_id, &voters[2], 1).unwrap();
+        harness.delegate(vote_id, &voters[5], &voters[3]).unwrap();
+
+        let projection = harness.voting.get_projection_as(&creator, vote_id).unwrap();
+        assert_eq!(projection.questions[0].uncast_votes, 3);
+        assert_eq!(projection.questions[0].pending_delegated_votes, 1);
+        let reachable: Vec<(u32, u32)> = projection.questions[0].options.iter().map(|option| (option.votes, option.max_votes)).collect();
+        assert_eq!(reachable, vec![(2, 6), (1, 5), (0, 4)]);
+
+        // An accepted delegation leaves the totals alone, the votes move to the delegate
+        harness.voting.accept_delegation_as(&voters[3], vote_id, &voters[5]).unwrap();
+        let projection = harness.voting.get_projection_as(&auditor, vote_id).unwrap();
+        assert_eq!((projection.questions[0].uncast_votes, projection.questions[0].pending_delegated_votes), (4, 0));
+        assert_eq!(projection.questions[0].options[2].max_votes, 4);
+
+        // Public results do not make the projection public
+        assert_eq!(harness.voting.get_projection_as(&voters[0], vote_id), Err(ProgramError::InvalidArgument));
+        let config = VoteConfig { projection_visibility: Visibility::AllowlistOnly, ..VoteConfig::from_labels("Open Projection".to_string(), vec!["Yes".to_string()]).unwrap() };
+        let open_id = harness.create_vote(config, &creator).unwrap();
+        harness.allow(open_id, &creator, &voters[0]).unwrap();
+        assert_eq!(harness.voting.get_projection_as(&voters[0], open_id).unwrap().questions[0].options[0].max_votes, 1);
+        assert!(harness.voting.get_projection_as(&Pubkey::new_unique(), open_id).is_err());
+    }
+
+    #[test]
+    fn test_vote_many() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voter = Pubkey::new_unique();
+        let config = || VoteConfig::from_labels("Poll".to_string(), vec!["Yes".to_string(), "No".to_string()]).unwrap();
+        let vote_ids: Vec<VoteId> = (0..5).map(|_| harness.create_vote(config(), &creator).unwrap()).collect();
+        for vote_id in &vote_ids {
+            harness.allow(*vote_id, &creator, &voter).unwrap();
+        }
+        let voter_info = harness.account_for(&voter);
+
+        // One ballot on each of three votes
+        let ballots = [(vote_ids[0], OptionIndex(0)), (vote_ids[1], OptionIndex(1)), (vote_ids[2], OptionIndex(0))];
+        harness.voting.vote_many(&ballots, std::slice::from_ref(&voter_info)).unwrap();
+        for (vote_id, option_index) in ballots {
+            let results = harness.voting.get_full_results_as(&creator, vote_id).unwrap();
+            assert_eq!(results.questions[0].options[option_index.index()].votes, 1);
+        }
+
+        // An unknown option in the second entry leaves the first vote untouched
+        let before = harness.voting.clone();
+        take_logs();
+        let ballots = [(vote_ids[3], OptionIndex(0)), (vote_ids[4], OptionIndex(2))];
+        assert_eq!(harness.voting.vote_many(&ballots, std::slice::from_ref(&voter_info)), Err(ProgramError::InvalidArgument));
+        assert_eq!(take_logs(), vec![format!("Ballot 1 of the batch failed on vote {}", vote_ids[4])]);
+        assert_eq!(harness.voting, before);
+        assert!(!harness.voting.has_voted(vote_ids[3], &voter));
+
+        // So does a second ballot on a vote the voter has no votes left on
+        let ballots = [(vote_ids[3], OptionIndex(0)), (vote_ids[3], OptionIndex(1))];
+        assert_eq!(harness.voting.vote_many(&ballots, std::slice::from_ref(&voter_info)), Err(VoteError::InsufficientVotes.into()));
+        assert_eq!(harness.voting, before);
+
+        let ballots = vec![(vote_ids[3], OptionIndex(0)); MAX_BATCH_BALLOTS + 1];
+        assert_eq!(harness.voting.vote_many_as(&voter, &ballots), Err(VoteError::BatchTooLarge.into()));
+    }
+
+    #[test]
+    fn test_observers() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voter = Pubkey::new_unique();
+        let auditor = Pubkey::new_unique();
+
+        let config = VoteConfig {
+            results_visibility: Visibility::CreatorOnly,
+            allowlist_visibility: Visibility::AllowlistOnly,
+            ..VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string(), "Option 2".to_string()]).unwrap()
+        };
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+        harness.allow(vote_id, &creator, &voter).unwrap();
+        harness.cast(vote_id, &voter, 1).unwrap();
+
+        // Only the creator names observers, once each
+        assert_eq!(harness.voting.add_observer_as(&voter, vote_id, auditor), Err(ProgramError::InvalidArgument));
+        assert!(harness.voting.get_results_as(&auditor, vote_id, None).is_err());
+        assert!(harness.voting.add_observer_as(&creator, vote_id, auditor).is_ok());
+        assert_eq!(harness.voting.add_observer_as(&creator, vote_id, auditor), Err(ProgramError::InvalidArgument));
+
+        // The observer reads restricted results, ballots and the allowlist
+        assert_eq!(harness.voting.get_results_as(&auditor, vote_id, None).unwrap()[0]["Option 2"], 1);
+        assert_eq!(harness.voting.get_full_results_as(&auditor, vote_id).unwrap().questions[0].total_votes, 1);
+        assert_eq!(harness.voting.get_ballots_as(&auditor, vote_id, 0).unwrap().len(), 1);
+        assert_eq!(harness.voting.get_allowed_voters_as(&auditor, vote_id).unwrap(), vec![voter]);
+
+        // but cannot vote or delegate
+        assert!(harness.cast(vote_id, &auditor, 0).is_err());
+        assert!(harness.delegate(vote_id, &auditor, &voter).is_err());
+        assert_eq!(harness.voting.get_full_results_as(&creator, vote_id).unwrap().questions[0].total_votes, 1);
+
+        // A removed observer loses access
+        assert_eq!(harness.voting.remove_observer_as(&voter, vote_id, &auditor), Err(ProgramError::InvalidArgument));
+        assert!(harness.voting.remove_observer_as(&creator, vote_id, &auditor).is_ok());
+        assert_eq!(harness.voting.remove_observer_as(&creator, vote_id, &auditor), Err(ProgramError::InvalidArgument));
+        assert!(harness.voting.get_results_as(&auditor, vote_id, None).is_err());
+        assert!(harness.voting.get_allowed_voters_as(&auditor, vote_id).is_err());
+
+        // The list of observers is bounded
+        for _ in 0..MAX_OBSERVERS {
+            assert!(harness.voting.add_observer_as(&creator, vote_id, Pubkey::new_unique()).is_ok());
+        }
+        assert_eq!(harness.voting.add_observer_as(&creator, vote_id, auditor), Err(ProgramError::InvalidArgument));
+    }
+
+    #[test]
+    fn test_close_vote_results_flag_defaults() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+
+        let open_id = harness.create_vote(VoteConfig::from_labels("Open Vote".to_string(), vec!["Option 1".to_string()]).unwrap(), &creator).unwrap();
+        let creator_info = harness.account_for(&creator);
+        let closed_id = harness.voting.create_vote("Closed Vote".to_string(), vec!["Option 1".to_string()], true, &[creator_info]).unwrap();
+
+        let open_summary = harness.voting.get_vote_summary(open_id).unwrap();
+        assert_eq!(open_summary.results_visibility, Visibility::Public);
+        assert_eq!(open_summary.allowlist_visibility, Visibility::Public);
+
+        let closed_summary = harness.voting.get_vote_summary(closed_id).unwrap();
+        assert_eq!(closed_summary.results_visibility, Visibility::AllowlistOnly);
+        assert_eq!(closed_summary.allowlist_visibility, Visibility::AllowlistOnly);
+    }
+
+    #[test]
+    fn test_creator_reads_restricted_results() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let stranger = Pubkey::new_unique();
+
+        let creator_info = harness.account_for(&creator);
+        let vote_id = harness.voting.create_vote("Test Vote".to_string(), vec!["Option 1".to_string()], true, std::slice::from_ref(&creator_info)).unwrap();
+        assert!(!harness.voting.is_voter_allowed(vote_id, &creator).unwrap());
+
+        assert!(harness.voting.get_results_as(&creator, vote_id, None).is_ok());
+        assert!(harness.voting.get_allowed_voters_as(&creator, vote_id).is_ok());
+
+        assert!(harness.voting.get_results_as(&stranger, vote_id, None).is_err());
+        assert!(harness.voting.get_allowed_voters_as(&stranger, vote_id).is_err());
+    }
+
+    // Registry account the signed-message helpers pass, owned by the program of the harness
+    const SIGNED_REGISTRY: Pubkey = Pubkey::new_from_array([7; 32]);
+
+    // Redeem a voucher signed by `signer`, as if an ed25519 instruction preceded the call
+    fn redeem_voucher(harness: &mut TestHarness, voucher: Voucher, signer: &Keypair) -> Result<(), ProgramError> {
+        redeem_voucher_signed_for(harness, voucher, signer, &SIGNED_REGISTRY)
+    }
+
+    // Redeem a voucher in `SIGNED_REGISTRY` whose signature is over the voucher of `signed_registry`
+    fn redeem_voucher_signed_for(harness: &mut TestHarness, voucher: Voucher, signer: &Keypair, signed_registry: &Pubkey) -> Result<(), ProgramError> {
+        let dalek_keypair = ed25519_dalek::Keypair::from_bytes(&signer.to_bytes()).unwrap();
+        let ed25519_instruction = new_ed25519_instruction(&dalek_keypair, &voucher.to_message(harness.owner(), signed_registry));
+        let redeem_instruction = Instruction::new_with_bytes(*harness.owner(), &[], vec![]);
+
+        let sysvar_key = sysvar_instructions::id();
+        harness.set_data(&sysvar_key, instructions_sysvar_data(&[ed25519_instruction, redeem_instruction]));
+
+        let voter_info = harness.account_for(&voucher.voter);
+        let sysvar_info = harness.account_for(&sysvar_key);
+        let registry_info = harness.account_for(&SIGNED_REGISTRY);
+
+        harness.voting.redeem_invitation(voucher.vote_id, &[voter_info, sysvar_info, registry_info], voucher)
+    }
+
+    #[test]
+    fn test_redeem_invitation() {
+        let mut harness = TestHarness::new();
+        let creator = Keypair::new();
+        let voter1 = Pubkey::new_unique();
+        set_clock(1_000);
+
+        let vote_id = harness.create_vote(VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string()]).unwrap(), &creator.pubkey()).unwrap();
+
+        let voucher = Voucher { vote_id, voter: voter1, votes: 2, expiry: 2_000 };
+        assert!(redeem_voucher(&mut harness, voucher, &creator).is_ok());
+
+        let vote = harness.voting.votes.get(&vote_id).unwrap();
+        assert!(vote.is_voter_allowed(&voter1));
+        assert_eq!(vote.allowed_voters.get(&voter1).unwrap().votes_left, vec![2]);
+
+        // The same voucher cannot be used twice
+        assert!(redeem_voucher(&mut harness, voucher, &creator).is_err());
+    }
+
+    #[test]
+    fn test_redeem_invitation_expired() {
+        let mut harness = TestHarness::new();
+        let creator = Keypair::new();
+        let voter1 = Pubkey::new_unique();
+        set_clock(2_001);
+
+        let vote_id = harness.create_vote(VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string()]).unwrap(), &creator.pubkey()).unwrap();
+
+        let voucher = Voucher { vote_id, voter: voter1, votes: 1, expiry: 2_000 };
+        assert!(redeem_voucher(&mut harness, voucher, &creator).is_err());
+        assert!(!harness.voting.votes.get(&vote_id).unwrap().is_voter_allowed(&voter1));
+    }
+
+    #[test]
+    fn test_redeem_invitation_not_signed_by_creator() {
+        let mut harness = TestHarness::new();
+        let creator = Keypair::new();
+        let impostor = Keypair::new();
+        let voter1 = Pubkey::new_unique();
+        set_clock(1_000);
+
+        let vote_id = harness.create_vote(VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string()]).unwrap(), &creator.pubkey()).unwrap();
+
+        let voucher = Voucher { vote_id, voter: voter1, votes: 1, expiry: 2_000 };
+        assert!(redeem_voucher(&mut harness, voucher, &impostor).is_err());
+        assert!(!harness.voting.votes.get(&vote_id).unwrap().is_voter_allowed(&voter1));
+    }
+
+    #[test]
+    fn test_redeem_invitation_of_another_registry() {
+        let mut harness = TestHarness::new();
+        let creator = Keypair::new();
+        let voter1 = Pubkey::new_unique();
+        set_clock(1_000);
+
+        let vote_id = harness.create_vote(VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string()]).unwrap(), &creator.pubkey()).unwrap();
+        let voucher = Voucher { vote_id, voter: voter1, votes: 1, expiry: 2_000 };
+
+        // A vote with the same ID in another registry does not make the voucher redeemable here
+        assert!(redeem_voucher_signed_for(&mut harness, voucher, &creator, &Pubkey::new_unique()).is_err());
+
+        // Nor does the same registry key under another program
+        let owner = *harness.owner();
+        harness.set_owner(&SIGNED_REGISTRY, &Pubkey::new_unique());
+        assert!(redeem_voucher(&mut harness, voucher, &creator).is_err());
+        assert!(!harness.voting.votes.get(&vote_id).unwrap().is_voter_allowed(&voter1));
+
+        harness.set_owner(&SIGNED_REGISTRY, &owner);
+        assert!(redeem_voucher(&mut harness, voucher, &creator).is_ok());
+    }
+
+    // Relay `ballot` with an ed25519 instruction of `signer` over `signed`, which is usually the same ballot
+    fn relay_ballot(harness: &mut TestHarness, ballot: SignedBallot, signed: &SignedBallot, signer: &Keypair) -> Result<(), ProgramError> {
+        relay_ballot_signed_for(harness, ballot, signed, signer, &SIGNED_REGISTRY)
+    }
+
+    // Relay `ballot` to `SIGNED_REGISTRY` with a signature over `signed` in `signed_registry`
+    fn relay_ballot_signed_for(harness: &mut TestHarness, ballot: SignedBallot, signed: &SignedBallot, signer: &Keypair, signed_registry: &Pubkey) -> Result<(), ProgramError> {
+        let dalek_keypair = ed25519_dalek::Keypair::from_bytes(&signer.to_bytes()).unwrap();
+        let ed25519_instruction = new_ed25519_instruction(&dalek_keypair, &signed.to_message(harness.owner(), signed_registry));
+        let signature_offset = u16::from_le_bytes([ed25519_instruction.data[2], ed25519_instruction.data[3]]) as usize;
+        let signature = ed25519_instruction.data[signature_offset..signature_offset + 64].try_into().unwrap();
+        let relay_instruction = Instruction::new_with_bytes(*harness.owner(), &[], vec![]);
+
+        let sysvar_key = sysvar_instructions::id();
+        harness.set_data(&sysvar_key, instructions_sysvar_data(&[ed25519_instruction, relay_instruction]));
+
+        let relayer = Pubkey::new_unique();
+        let relayer_info = harness.account_for(&relayer);
+        let sysvar_info = harness.account_for(&sysvar_key);
+        let registry_info = harness.account_for(&SIGNED_REGISTRY);
+
+        harness.voting.cast_vote_signed(&[relayer_info, sysvar_info, registry_info], ballot, signature)
+    }
+
+    #[test]
+    fn test_cast_vote_signed() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voter = Keypair::new();
+        set_clock(1_000);
+
+        let config = VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string(), "Option 2".to_string()]).unwrap();
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+        harness.allow(vote_id, &creator, &voter.pubkey()).unwrap();
+
+        // The relayer signs nothing but the transaction, the ballot counts for the voter
+        let ballot = SignedBallot { vote_id, option_index: OptionIndex(1), voter: voter.pubkey(), nonce: 1, expiry: 2_000 };
+        assert!(relay_ballot(&mut harness, ballot, &ballot, &voter).is_ok());
+        assert_eq!(harness.voting.get_results_as(&creator, vote_id, None).unwrap()[0]["Option 2"], 1);
+        assert_eq!(harness.voting.votes[&vote_id].allowed_voters[&voter.pubkey()].last_ballot_nonce, Some(1));
+
+        // Once the voter takes the ballot back, replaying it or an older one is still rejected
+        harness.voting.retract_ballot_as(&voter.pubkey(), vote_id, 0).unwrap();
+        assert_eq!(relay_ballot(&mut harness, ballot, &ballot, &voter), Err(ProgramError::InvalidArgument));
+        let stale = SignedBallot { nonce: 0, ..ballot };
+        assert_eq!(relay_ballot(&mut harness, stale, &stale, &voter), Err(ProgramError::InvalidArgument));
+
+        // Nonces may skip values
+        let next = SignedBallot { nonce: 5, option_index: OptionIndex(0), ..ballot };
+        assert!(relay_ballot(&mut harness, next, &next, &voter).is_ok());
+        assert_eq!(harness.voting.get_results_as(&creator, vote_id, None).unwrap()[0]["Option 1"], 1);
+    }
+
+    #[test]
+    fn test_cast_vote_signed_rejections() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voter = Keypair::new();
+        let impostor = Keypair::new();
+        set_clock(1_000);
+
+        let config = VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string(), "Option 2".to_string()]).unwrap();
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+        harness.allow(vote_id, &creator, &voter.pubkey()).unwrap();
+        let ballot = SignedBallot { vote_id, option_index: OptionIndex(1), voter: voter.pubkey(), nonce: 1, expiry: 2_000 };
+
+        // The signature has to be over the relayed ballot, by the voter
+        let tampered = SignedBallot { option_index: OptionIndex(0), ..ballot };
+        assert_eq!(relay_ballot(&mut harness, tampered, &ballot, &voter), Err(ProgramError::InvalidArgument));
+        assert_eq!(relay_ballot(&mut harness, ballot, &ballot, &impostor), Err(ProgramError::InvalidArgument));
+
+        set_clock(2_001);
+        assert_eq!(relay_ballot(&mut harness, ballot, &ballot, &voter), Err(ProgramError::InvalidArgument));
+
+        // Nothing was counted, and the nonce is still unused
+        assert!(!harness.voting.has_voted(vote_id, &voter.pubkey()));
+        set_clock(2_000);
+        assert!(relay_ballot(&mut harness, ballot, &ballot, &voter).is_ok());
+    }
+
+    #[test]
+    fn test_cast_vote_signed_in_another_registry() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voter = Keypair::new();
+        set_clock(1_000);
+
+        let config = VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string(), "Option 2".to_string()]).unwrap();
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+        harness.allow(vote_id, &creator, &voter.pubkey()).unwrap();
+        let ballot = SignedBallot { vote_id, option_index: OptionIndex(1), voter: voter.pubkey(), nonce: 1, expiry: 2_000 };
+
+        // A ballot the voter signed for a vote with the same ID elsewhere is not theirs to relay here
+        assert_eq!(relay_ballot_signed_for(&mut harness, ballot, &ballot, &voter, &Pubkey::new_unique()), Err(ProgramError::InvalidArgument));
+        let owner = *harness.owner();
+        harness.set_owner(&SIGNED_REGISTRY, &Pubkey::new_unique());
+        assert_eq!(relay_ballot(&mut harness, ballot, &ballot, &voter), Err(ProgramError::InvalidArgument));
+        assert!(!harness.voting.has_voted(vote_id, &voter.pubkey()));
+
+        harness.set_owner(&SIGNED_REGISTRY, &owner);
+        assert!(relay_ballot(&mut harness, ballot, &ballot, &voter).is_ok());
+    }
+
+    #[test]
+    fn test_time_weighted_vote() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voters = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+
+        let config = VoteConfig {
+            vote_type: VoteType::TimeWeighted { start: 1_000, end: 2_000, start_weight_bps: 15_000, end_weight_bps: 5_000 },
+            ..VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string(), "Option 2".to_string()]).unwrap()
+        };
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+
+        for voter in voters {
+            assert!(harness.voting.add_allowed_voter_as(&creator, vote_id, voter).is_ok());
+        }
+
+        // Vote at the start, the midpoint and the end of the range
+        for (voter, (now, option_index)) in voters.iter().zip([(1_000, 0), (1_500, 0), (2_000, 1)]) {
+            set_clock(now);
+            assert!(harness.voting.vote_as(voter, vote_id, OptionIndex(option_index)).is_ok());
+        }
+
+        let results = harness.voting.get_full_results_as(&creator, vote_id).unwrap();
+
+        let question = &results.questions[0];
+        assert_eq!(question.options[0].votes, 2);
+        assert_eq!(question.options[0].weighted_votes, 15_000 + 10_000);
+        assert_eq!(question.options[1].votes, 1);
+        assert_eq!(question.options[1].weighted_votes, 5_000);
+        assert_eq!(question.total_votes, 3);
+        assert_eq!(question.total_weighted_votes, 30_000);
+    }
+
+    fn conviction_vote(harness: &mut TestHarness, creator: &Pubkey, voters: &[Pubkey]) -> VoteId {
+        let config = VoteConfig {
+            vote_type: VoteType::Conviction { period_slots: 100, max_multiplier: 8 },
+            ..VoteConfig::from_labels("Grants".to_string(), vec!["Fund".to_string(), "Defer".to_string(), "Reject".to_string()]).unwrap()
+        };
+        let vote_id = harness.create_vote(config, creator).unwrap();
+        for voter in voters {
+            harness.allow(vote_id, creator, voter).unwrap();
+        }
+        vote_id
+    }
+
+    fn weighted_votes_at(harness: &TestHarness, creator: &Pubkey, vote_id: VoteId, slot: Slot) -> Vec<u64> {
+        let results = harness.voting.get_full_results_at(creator, vote_id, slot).unwrap();
+        results.questions[0].options.iter().map(|option| option.weighted_votes).collect()
+    }
+
+    #[test]
+    fn test_conviction_multiplier_steps() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voters = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let vote_id = conviction_vote(&mut harness, &creator, &voters);
+
+        set_slot(1_000);
+        harness.cast(vote_id, &voters[0], 0).unwrap();
+        set_slot(1_150);
+        harness.cast(vote_id, &voters[1], 1).unwrap();
+
+        // Doubling every full period of 100 slots, capped at eight times
+        let steps: Vec<Vec<u64>> = [1_000, 1_099, 1_100, 1_200, 1_299, 1_300, 1_450, 5_000].iter().map(|slot| weighted_votes_at(&harness, &creator, vote_id, *slot)).collect();
+        assert_eq!(
+            steps,
+            vec![
+                vec![10_000, 10_000, 0], // The later ballot counts once even before it was cast
+                vec![10_000, 10_000, 0],
+                vec![20_000, 10_000, 0],
+                vec![40_000, 10_000, 0],
+                vec![40_000, 20_000, 0],
+                vec![80_000, 20_000, 0],
+                vec![80_000, 80_000, 0],
+                vec![80_000, 80_000, 0],
+            ]
+        );
+
+        // Reads on-chain use the current slot, the stored tallies keep the cast weights
+        set_slot(1_250);
+        let results = harness.voting.get_full_results_as(&creator, vote_id).unwrap();
+        assert_eq!(results.questions[0].options[0].weighted_votes, 40_000);
+        assert_eq!(results.questions[0].total_weighted_votes, 60_000);
+        assert_eq!(results.questions[0].options[0].votes, 1);
+        assert_eq!(harness.voting.get_summary_as(&creator, vote_id).unwrap().questions[0].total_weighted_votes, 60_000);
+        assert_eq!(harness.voting.votes[&vote_id].questions[0].weighted_votes["Fund"], 10_000);
+    }
+
+    #[test]
+    fn test_conviction_resets_on_change() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voters = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let vote_id = conviction_vote(&mut harness, &creator, &voters);
+
+        set_slot(0);
+        harness.cast(vote_id, &voters[0], 0).unwrap();
+        harness.cast(vote_id, &voters[1], 0).unwrap();
+
+        // The changed ballot starts over at the slot of the change
+        set_slot(350);
+        assert!(harness.voting.change_vote_as(&voters[0], vote_id, 0, OptionIndex(2)).is_ok());
+        assert_eq!(weighted_votes_at(&harness, &creator, vote_id, 350), vec![80_000, 0, 10_000]);
+        assert_eq!(weighted_votes_at(&harness, &creator, vote_id, 450), vec![80_000, 0, 20_000]);
+        let results = harness.voting.get_full_results_at(&creator, vote_id, 450).unwrap();
+        let votes: Vec<u32> = results.questions[0].options.iter().map(|option| option.votes).collect();
+        assert_eq!(votes, vec![1, 0, 1]);
+
+        // Changes to the same or an unknown option, and by voters without a ballot, are rejected
+        let other = Pubkey::new_unique();
+        harness.allow(vote_id, &creator, &other).unwrap();
+        assert_eq!(harness.voting.change_vote_as(&voters[0], vote_id, 0, OptionIndex(2)), Err(ProgramError::InvalidArgument));
+        assert_eq!(harness.voting.change_vote_as(&voters[0], vote_id, 0, OptionIndex(3)), Err(ProgramError::InvalidArgument));
+        assert_eq!(harness.voting.change_vote_as(&other, vote_id, 0, OptionIndex(1)), Err(ProgramError::InvalidArgument));
+
+        // Closing freezes the conviction at the closing slot, reopening lets it grow again
+        set_slot(500);
+        harness.close(vote_id, &creator).unwrap();
+        set_slot(10_000);
+        assert_eq!(harness.voting.get_full_results_as(&creator, vote_id).unwrap().questions[0].options[2].weighted_votes, 20_000);
+        assert_eq!(weighted_votes_at(&harness, &creator, vote_id, 10_000), vec![80_000, 0, 20_000]);
+        harness.voting.reopen_vote_as(&creator, vote_id).unwrap();
+        assert_eq!(weighted_votes_at(&harness, &creator, vote_id, 10_000), vec![80_000, 0, 80_000]);
+    }
+
+    #[test]
+    fn test_conviction_config_validation() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        for vote_type in [VoteType::Conviction { period_slots: 0, max_multiplier: 8 }, VoteType::Conviction { period_slots: 100, max_multiplier: 0 }] {
+            let config = VoteConfig { vote_type, ..VoteConfig::from_labels("Grants".to_string(), vec!["Fund".to_string()]).unwrap() };
+            assert_eq!(harness.create_vote(config, &creator), Err(ProgramError::InvalidArgument));
+        }
+
+        // Other vote types have no conviction to apply
+        assert_eq!(VoteType::SingleChoice.conviction_multiplier(Some(0), 1_000_000), 1);
+        assert_eq!(VoteType::Conviction { period_slots: 1, max_multiplier: u32::MAX }.conviction_multiplier(Some(0), u64::MAX), u64::from(u32::MAX));
+        assert_eq!(VoteType::Conviction { period_slots: 1, max_multiplier: 8 }.conviction_multiplier(None, 1_000), 1);
+    }
+
+    fn point_vote(harness: &mut TestHarness, creator: &Pubkey, voters: &[Pubkey], allow_partial: bool) -> VoteId {
+        let config = VoteConfig {
+            vote_type: VoteType::PointAllocation { points_per_voter: 100, allow_partial },
+            ..VoteConfig::from_labels("Budget".to_string(), vec!["Parks".to_string(), "Roads".to_string(), "Schools".to_string()]).unwrap()
+        };
+        let vote_id = harness.create_vote(config, creator).unwrap();
+        for voter in voters {
+            harness.allow(vote_id, creator, voter).unwrap();
+        }
+        vote_id
+    }
+
+    #[test]
+    fn test_point_allocation() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voters = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let vote_id = point_vote(&mut harness, &creator, &voters, false);
+
+        assert!(harness.voting.vote_allocate_as(&voters[0], vote_id, &[(OptionIndex(0), 60), (OptionIndex(2), 40)]).is_ok());
+
+        // Over and under the budget, repeated and unknown options
+        assert_eq!(harness.voting.vote_allocate_as(&voters[1], vote_id, &[(OptionIndex(0), 70), (OptionIndex(1), 40)]), Err(VoteError::PointBudgetMismatch.into()));
+        assert_eq!(harness.voting.vote_allocate_as(&voters[1], vote_id, &[(OptionIndex(0), 50)]), Err(VoteError::PointBudgetMismatch.into()));
+        assert_eq!(harness.voting.vote_allocate_as(&voters[1], vote_id, &[(OptionIndex(0), 50), (OptionIndex(0), 50)]), Err(ProgramError::InvalidArgument));
+        assert_eq!(harness.voting.vote_allocate_as(&voters[1], vote_id, &[(OptionIndex(0), 50), (OptionIndex(3), 50)]), Err(ProgramError::InvalidArgument));
+        assert_eq!(harness.voting.vote_as(&voters[1], vote_id, OptionIndex(0)), Err(ProgramError::InvalidArgument));
+
+        assert!(harness.voting.vote_allocate_as(&voters[1], vote_id, &[(OptionIndex(1), 30), (OptionIndex(0), 20), (OptionIndex(2), 50)]).is_ok());
+        assert_eq!(harness.voting.vote_allocate_as(&voters[1], vote_id, &[(OptionIndex(0), 100)]), Err(VoteError::InsufficientVotes.into())); // The budget is spent
+
+        // Point totals of 80, 30 and 90 out of 200
+        let results = harness.voting.get_full_results_as(&creator, vote_id).unwrap();
+        let question = &results.questions[0];
+        let points: Vec<(u64, u16)> = question.options.iter().map(|option| (option.weighted_votes, option.share_bps)).collect();
+        assert_eq!(points, vec![(80, 4_000), (30, 1_500), (90, 4_500)]);
+        assert_eq!(question.total_weighted_votes, 200);
+    }
+
+    #[test]
+    fn test_point_allocation_partial_budget() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voters = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let vote_id = point_vote(&mut harness, &creator, &voters, true);
+
+        assert_eq!(harness.voting.vote_allocate_as(&voters[0], vote_id, &[(OptionIndex(0), 0)]), Err(VoteError::PointBudgetMismatch.into()));
+        assert_eq!(harness.voting.vote_allocate_as(&voters[0], vote_id, &[(OptionIndex(0), 101)]), Err(VoteError::PointBudgetMismatch.into()));
+        assert!(harness.voting.vote_allocate_as(&voters[0], vote_id, &[(OptionIndex(0), 10), (OptionIndex(1), 0)]).is_ok());
+        assert!(harness.voting.vote_allocate_as(&voters[1], vote_id, &[(OptionIndex(1), 20)]).is_ok());
+
+        // One third and two thirds of the spent points, the zero allocation casts no ballot
+        let results = harness.voting.get_full_results_as(&creator, vote_id).unwrap();
+        let question = &results.questions[0];
+        let points: Vec<(u32, u64, u16)> = question.options.iter().map(|option| (option.votes, option.weighted_votes, option.share_bps)).collect();
+        assert_eq!(points, vec![(1, 10, 3_333), (1, 20, 6_667), (0, 0, 0)]);
+    }
+
+    #[test]
+    fn test_time_weighted_rounding() {
+        let vote_type = VoteType::TimeWeighted { start: 0, end: 3, start_weight_bps: 10_000, end_weight_bps: 0 };
+
+        // 6666.67 and 3333.33 are rounded down
+        assert_eq!(vote_type.weight_at(-5), 10_000);
+        assert_eq!(vote_type.weight_at(1), 6_666);
+        assert_eq!(vote_type.weight_at(2), 3_333);
+        assert_eq!(vote_type.weight_at(10), 0);
+
+        // Rising weights are rounded down as well
+        let vote_type = VoteType::TimeWeighted { start: 0, end: 3, start_weight_bps: 0, end_weight_bps: 10_000 };
+        assert_eq!(vote_type.weight_at(1), 3_333);
+        assert_eq!(vote_type.weight_at(2), 6_666);
+    }
+
+    #[test]
+    fn test_time_weighted_invalid_range() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+
+        let config = VoteConfig {
+            vote_type: VoteType::TimeWeighted { start: 2_000, end: 2_000, start_weight_bps: 10_000, end_weight_bps: 5_000 },
+            ..VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string()]).unwrap()
+        };
+
+        let account_info = harness.account_for(&creator);
+        assert!(harness.voting.create_vote_with_config(config, &[account_info]).is_err());
+    }
+
+    fn board_meeting_config() -> VoteConfig {
+        let motion = |title: &str| QuestionConfig {
+            title: title.try_into().unwrap(),
+            options: VoteOption::from_labels(vec!["Yes".to_string(), "No".to_string()]).unwrap(),
+        };
+
+        VoteConfig::with_questions("Board Meeting".to_string(), vec![motion("Motion 1"), motion("Motion 2")]).unwrap()
+    }
+
+    #[test]
+    fn test_multi_question_independent_tallies() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voter1 = Pubkey::new_unique();
+
+        let vote_id = harness.create_vote(board_meeting_config(), &creator).unwrap();
+
+        assert!(harness.voting.add_allowed_voter_as(&creator, vote_id, voter1).is_ok());
+
+        assert!(harness.voting.vote_on_question_as(&voter1, vote_id, 0, OptionIndex(0)).is_ok());
+        assert!(harness.voting.vote_on_question_as(&voter1, vote_id, 1, OptionIndex(1)).is_ok());
+
+        // Out of range question
+        assert!(harness.voting.vote_on_question_as(&voter1, vote_id, 2, OptionIndex(0)).is_err());
+
+        let all_results = harness.voting.get_results_as(&voter1, vote_id, None).unwrap();
+        assert_eq!(all_results.len(), 2);
+        assert_eq!(all_results[0].get("Yes"), Some(&1));
+        assert_eq!(all_results[0].get("No"), None);
+        assert_eq!(all_results[1].get("Yes"), None);
+        assert_eq!(all_results[1].get("No"), Some(&1));
+
+        let second_results = harness.voting.get_results_as(&voter1, vote_id, Some(1)).unwrap();
+        assert_eq!(second_results, vec![all_results[1].clone()]);
+
+        assert!(harness.voting.get_results_as(&voter1, vote_id, Some(2)).is_err());
+    }
+
+    #[test]
+    fn test_delegate_vote_scoped() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let [delegator, delegate] = [Pubkey::new_unique(), Pubkey::new_unique()];
+
+        let config = VoteConfig { delegation_policy: DelegationPolicy::AnyRecipient, ..board_meeting_config() };
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+        harness.allow(vote_id, &creator, &delegator).unwrap();
+        assert_eq!(harness.voting.delegate_vote_scoped_as(&delegator, vote_id, &delegate, DelegationScope::Question(2)), Err(ProgramError::InvalidArgument));
+
+        // Only the vote of the first motion moves
+        let delegator_info = harness.account_for(&delegator);
+        harness.voting.delegate_vote_scoped(vote_id, &delegate, DelegationScope::Question(0), &[delegator_info]).unwrap();
+        let votes_left = |harness: &TestHarness, vote_id, voter: &Pubkey| harness.voting.get_voter_status_as(voter, vote_id, voter).unwrap().votes_left;
+        assert_eq!(votes_left(&harness, vote_id, &delegator), vec![0, 1]);
+        assert_eq!(votes_left(&harness, vote_id, &delegate), vec![1, 0]);
+
+        // The delegate spends it on the first motion and holds nothing for the second
+        harness.voting.vote_on_question_as(&delegate, vote_id, 0, OptionIndex(1)).unwrap();
+        assert_eq!(harness.voting.vote_on_question_as(&delegate, vote_id, 1, OptionIndex(0)), Err(VoteError::InsufficientVotes.into()));
+
+        // The delegator keeps their own vote on the second motion
+        harness.voting.vote_on_question_as(&delegator, vote_id, 1, OptionIndex(0)).unwrap();
+        let results = harness.voting.get_results_as(&creator, vote_id, None).unwrap();
+        assert_eq!((results[0].get("No"), results[1].get("Yes")), (Some(&1), Some(&1)));
+
+        // A pending delegation holds and later moves the votes in scope only
+        let config = VoteConfig { require_delegate_acceptance: true, delegation_policy: DelegationPolicy::AnyRecipient, ..board_meeting_config() };
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+        harness.allow(vote_id, &creator, &delegator).unwrap();
+        harness.voting.delegate_vote_scoped_as(&delegator, vote_id, &delegate, DelegationScope::Question(1)).unwrap();
+        assert_eq!(harness.voting.get_pending_delegations(vote_id).unwrap()[0].votes, vec![0, 1]);
+        harness.voting.accept_delegation_as(&delegate, vote_id, &delegator).unwrap();
+        assert_eq!(votes_left(&harness, vote_id, &delegator), vec![1, 0]);
+        assert_eq!(votes_left(&harness, vote_id, &delegate), vec![0, 1]);
+
+        // Weight counts on every question, token-weighted votes take no scope
+        let config = VoteConfig { vote_type: VoteType::TokenWeighted, delegation_policy: DelegationPolicy::AnyRecipient, ..board_meeting_config() };
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+        harness.allow(vote_id, &creator, &delegator).unwrap();
+        assert_eq!(harness.voting.delegate_vote_scoped_as(&delegator, vote_id, &delegate, DelegationScope::Question(0)), Err(ProgramError::InvalidArgument));
+        harness.voting.delegate_vote_scoped_as(&delegator, vote_id, &delegate, DelegationScope::AllQuestions).unwrap();
+    }
+
+    #[test]
+    fn test_multi_question_vote_exhaustion() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voter1 = Pubkey::new_unique();
+
+        let vote_id = harness.create_vote(board_meeting_config(), &creator).unwrap();
+
+        assert!(harness.voting.add_allowed_voter_as(&creator, vote_id, voter1).is_ok());
+
+
+        // The default vote method votes on the first question
+        assert!(harness.voting.vote_as(&voter1, vote_id, OptionIndex(0)).is_ok());
+        assert!(harness.voting.vote_on_question_as(&voter1, vote_id, 0, OptionIndex(1)).is_err()); // No votes left on the first question
+
+        // The second question still has its own vote
+        assert!(harness.voting.vote_on_question_as(&voter1, vote_id, 1, OptionIndex(0)).is_ok());
+        assert!(harness.voting.vote_on_question_as(&voter1, vote_id, 1, OptionIndex(0)).is_err());
+
+        let voter_info = harness.voting.votes.get(&vote_id).unwrap().allowed_voters.get(&voter1).unwrap();
+        assert_eq!(voter_info.votes_left, vec![0, 0]);
+    }
+
+    #[test]
+    fn test_cancel_vote() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voter1 = Pubkey::new_unique();
+        set_clock(1_000);
+
+        let vote_id = harness.create_vote(VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string(), "Option 2".to_string()]).unwrap(), &creator).unwrap();
+
+        let account_info = harness.account_for(&creator);
+        assert!(harness.voting.add_allowed_voter_as(&creator, vote_id, voter1).is_ok());
+
+        // Only the creator can cancel
+        assert!(harness.voting.cancel_vote_as(&voter1, vote_id, "Withdrawn".to_string()).is_err());
+
+        assert!(harness.voting.cancel_vote_as(&creator, vote_id, "Withdrawn".to_string()).is_ok());
+
+        let summary = harness.voting.get_vote_summary(vote_id).unwrap();
+        assert_eq!(summary.status, VoteStatus::Cancelled { reason: "Withdrawn".try_into().unwrap(), at: 1_000 });
+
+        let cancelled = Err(ProgramError::from(VoteError::VoteCancelled));
+        assert_eq!(harness.voting.vote_as(&voter1, vote_id, OptionIndex(0)), cancelled);
+        assert_eq!(harness.voting.close_vote(vote_id, std::slice::from_ref(&account_info)), cancelled);
+        assert_eq!(harness.voting.cancel_vote_as(&creator, vote_id, "Again".to_string()), cancelled);
+        assert_eq!(harness.voting.get_winner_as(&creator, vote_id, 0), Err(ProgramError::from(VoteError::VoteCancelled)));
+
+        let results = harness.voting.get_full_results_as(&creator, vote_id).unwrap();
+        assert!(results.is_void);
+    }
+
+    #[test]
+    fn test_min_duration_before_close() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        set_clock(1_000);
+
+        let config = VoteConfig {
+            min_duration: Some(3_600),
+            ..VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string()]).unwrap()
+        };
+        let vote_id = harness.create_vote(config.clone(), &creator).unwrap();
+        let cancelled_id = harness.create_vote(config, &creator).unwrap();
+
+        let account_info = harness.account_for(&creator);
+
+        set_clock(4_599);
+        assert_eq!(harness.voting.close_vote(vote_id, std::slice::from_ref(&account_info)), Err(VoteError::MinimumDurationNotElapsed.into()));
+        assert_eq!(harness.voting.get_vote_summary(vote_id).unwrap().status, VoteStatus::Open);
+
+        // Cancelling is not held back by the minimum duration
+        assert!(harness.voting.cancel_vote_as(&creator, cancelled_id, "Withdrawn".to_string()).is_ok());
+
+        set_clock(4_600);
+        assert!(harness.voting.close_vote(vote_id, &[account_info]).is_ok());
+        assert_eq!(harness.voting.get_vote_summary(vote_id).unwrap().status, VoteStatus::Closed);
+    }
+
+    #[test]
+    fn test_per_option_cap() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voters = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+
+        let config = VoteConfig {
+            per_option_cap: Some(2),
+            ..VoteConfig::from_labels("Budget".to_string(), vec!["Option 1".to_string(), "Option 2".to_string()]).unwrap()
+        };
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+
+        for voter in voters {
+            assert!(harness.voting.add_allowed_voter_as(&creator, vote_id, voter).is_ok());
+        }
+
+        for voter in &voters[..2] {
+            assert!(harness.voting.vote_as(voter, vote_id, OptionIndex(0)).is_ok());
+        }
+
+        let results = harness.voting.get_full_results_as(&creator, vote_id).unwrap();
+        assert_eq!(results.per_option_cap, Some(2));
+        assert!(results.questions[0].options[0].is_full);
+        assert!(!results.questions[0].options[1].is_full);
+
+        // The full option rejects the ballot and the voter keeps their vote for another option
+        assert_eq!(harness.voting.vote_as(&voters[2], vote_id, OptionIndex(0)), Err(VoteError::OptionCapReached.into()));
+        assert_eq!(harness.voting.votes[&vote_id].allowed_voters[&voters[2]].votes_left, vec![1]);
+        assert!(harness.voting.vote_as(&voters[2], vote_id, OptionIndex(1)).is_ok());
+
+        let results = harness.voting.get_full_results_as(&creator, vote_id).unwrap();
+        assert_eq!(results.questions[0].options[0].votes, 2);
+        assert_eq!(results.questions[0].options[1].votes, 1);
+    }
+
+    #[test]
+    fn test_withdraw_option() {
+        let creator = Pubkey::new_unique();
+        let voters = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+        let labels = vec!["Alice".to_string(), "Bob".to_string(), "Carol".to_string()];
+
+        for refund_on_withdraw in [false, true] {
+            let mut harness = TestHarness::new();
+            let config = VoteConfig { refund_on_withdraw, ..VoteConfig::from_labels("Chair".to_string(), labels.clone()).unwrap() };
+            let vote_id = harness.create_vote(config, &creator).unwrap();
+            for voter in &voters {
+                harness.allow(vote_id, &creator, voter).unwrap();
+            }
+            harness.cast(vote_id, &voters[0], 0).unwrap();
+            harness.cast(vote_id, &voters[1], 0).unwrap();
+
+            // Bob drops out, only the creator can withdraw him and only once
+            assert_eq!(harness.voting.withdraw_option_as(&voters[0], vote_id, OptionIndex(0)), Err(ProgramError::InvalidArgument));
+            harness.voting.withdraw_option_as(&creator, vote_id, OptionIndex(0)).unwrap();
+            assert_eq!(harness.voting.withdraw_option_as(&creator, vote_id, OptionIndex(0)), Err(ProgramError::InvalidArgument));
+            assert!(harness.voting.get_options(vote_id).unwrap()[0].withdrawn);
+            assert_eq!(harness.cast(vote_id, &voters[2], 0), Err(VoteError::OptionWithdrawn.into()));
+
+            let results = harness.voting.get_full_results_as(&creator, vote_id).unwrap();
+            assert!(results.questions[0].options[0].withdrawn);
+            let votes_left = harness.voting.votes[&vote_id].allowed_voters[&voters[0]].votes_left.clone();
+            if refund_on_withdraw {
+                // The ballots left the tally and the voters can vote again
+                assert_eq!((results.questions[0].options[0].votes, votes_left), (0, vec![1]));
+                assert!(harness.voting.get_ballots_as(&creator, vote_id, 0).unwrap().is_empty());
+                harness.cast(vote_id, &voters[0], 2).unwrap();
+            } else {
+                assert_eq!((results.questions[0].options[0].votes, votes_left), (2, vec![0]));
+            }
+            harness.cast(vote_id, &voters[2], 1).unwrap();
+
+            // The withdrawn option cannot win, even while it holds the most ballots
+            harness.close(vote_id, &creator).unwrap();
+            let expected = if refund_on_withdraw { Outcome::Tie { option_indices: vec![1, 2] } } else { Outcome::Winner { option_index: 1 } };
+            assert_eq!(harness.voting.get_winner_as(&creator, vote_id, 0), Ok(expected));
+            assert_eq!(harness.voting.withdraw_option_as(&creator, vote_id, OptionIndex(1)), Err(ProgramError::InvalidArgument));
+        }
+
+        // Refunds do not split point allocations
+        let config = VoteConfig {
+            refund_on_withdraw: true,
+            vote_type: VoteType::PointAllocation { points_per_voter: 10, allow_partial: false },
+            ..VoteConfig::from_labels("Chair".to_string(), labels).unwrap()
+        };
+        assert_eq!(TestHarness::new().create_vote(config, &creator), Err(ProgramError::InvalidArgument));
+    }
+
+    #[test]
+    fn test_per_option_voter_cap() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voters = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+
+        let config = VoteConfig {
+            per_option_voter_cap: Some(2),
+            ..VoteConfig::from_labels("Workshops".to_string(), vec!["Morning".to_string(), "Afternoon".to_string()]).unwrap()
+        };
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+        for voter in &voters {
+            harness.allow(vote_id, &creator, voter).unwrap();
+        }
+        assert_eq!(harness.voting.get_option_seats(vote_id, 0), Ok(vec![Some(2), Some(2)]));
+
+        let voter_infos: Vec<AccountInfo> = voters.iter().map(|voter| harness.account_for(voter)).collect();
+        for voter_info in &voter_infos[..2] {
+            assert!(harness.voting.vote(vote_id, std::slice::from_ref(voter_info), OptionIndex(0)).is_ok());
+        }
+        assert_eq!(harness.voting.get_option_seats(vote_id, 0), Ok(vec![Some(0), Some(2)]));
+
+        // The third voter keeps their vote
+        assert_eq!(harness.voting.vote(vote_id, std::slice::from_ref(&voter_infos[2]), OptionIndex(0)), Err(VoteError::OptionFull.into()));
+        assert_eq!(harness.voting.votes[&vote_id].allowed_voters[&voters[2]].votes_left, vec![1]);
+
+        // Retracting a ballot frees its seat and returns the vote
+        assert!(harness.voting.retract_ballot(vote_id, std::slice::from_ref(&voter_infos[0]), 0).is_ok());
+        assert_eq!(harness.voting.retract_ballot(vote_id, std::slice::from_ref(&voter_infos[0]), 0), Err(ProgramError::InvalidArgument));
+        assert_eq!(harness.voting.votes[&vote_id].allowed_voters[&voters[0]].votes_left, vec![1]);
+        assert_eq!(harness.voting.get_option_seats(vote_id, 0), Ok(vec![Some(1), Some(2)]));
+
+        assert!(harness.voting.vote(vote_id, std::slice::from_ref(&voter_infos[2]), OptionIndex(0)).is_ok());
+        assert!(harness.voting.vote(vote_id, std::slice::from_ref(&voter_infos[0]), OptionIndex(1)).is_ok());
+
+        let results = harness.voting.get_full_results_as(&creator, vote_id).unwrap();
+        assert_eq!(results.questions[0].options[0].votes, 2);
+        assert_eq!(results.questions[0].options[1].votes, 1);
+        assert_eq!(harness.voting.get_option_seats(vote_id, 0), Ok(vec![Some(0), Some(1)]));
+    }
+
+    #[test]
+    fn test_get_winner() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voter1 = Pubkey::new_unique();
+
+        let vote_id = harness.create_vote(VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string(), "Option 2".to_string()]).unwrap(), &creator).unwrap();
+
+        let account_info = harness.account_for(&creator);
+        assert!(harness.voting.add_allowed_voter_as(&creator, vote_id, voter1).is_ok());
+
+        assert!(harness.voting.vote_as(&voter1, vote_id, OptionIndex(1)).is_ok());
+
+        // No winner is declared while the vote is open
+        assert_eq!(harness.voting.get_winner_as(&creator, vote_id, 0), Err(ProgramError::from(VoteError::VoteStillOpen)));
+
+        assert!(harness.voting.close_vote(vote_id, std::slice::from_ref(&account_info)).is_ok());
+        assert_eq!(harness.voting.get_winner_as(&creator, vote_id, 0), Ok(Outcome::Winner { option_index: 1 }));
+
+        // A closed vote can no longer be cancelled
+        set_clock(1_000);
+        assert!(harness.voting.cancel_vote_as(&creator, vote_id, "Too late".to_string()).is_err());
+    }
+
+    // Outcome of a closed single-question vote with one ballot per entry of `ballots`
+    fn outcome_after(config: VoteConfig, ballots: &[u16]) -> Outcome {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+
+        for option_index in ballots {
+            let voter = Pubkey::new_unique();
+            harness.allow(vote_id, &creator, &voter).unwrap();
+            harness.cast(vote_id, &voter, *option_index).unwrap();
+        }
+
+        harness.close(vote_id, &creator).unwrap();
+        harness.voting.get_winner_as(&creator, vote_id, 0).unwrap()
+    }
+
+    fn threshold_config(winning_threshold_bps: Option<u16>, quorum: Option<u32>) -> VoteConfig {
+        let options = vec![
+            VoteOption::new("Yes".to_string(), 0).unwrap(),
+            VoteOption::new("No".to_string(), 1).unwrap(),
+            VoteOption::abstention("Abstain".to_string(), 2).unwrap(),
+        ];
+        VoteConfig { winning_threshold_bps, quorum, ..VoteConfig::new("Bylaw Change".to_string(), options).unwrap() }
+    }
+
+    #[test]
+    fn test_winning_threshold() {
+        // Two of three ballots round to exactly 6667 basis points
+        assert_eq!(outcome_after(threshold_config(Some(6667), None), &[0, 0, 1]), Outcome::Winner { option_index: 0 });
+        assert_eq!(outcome_after(threshold_config(Some(6668), None), &[0, 0, 1]), Outcome::NoThresholdMet { leading_option: 0, share_bps: 6667 });
+
+        // Exactly at the threshold, then one ballot under it
+        assert_eq!(outcome_after(threshold_config(Some(7500), None), &[0, 0, 0, 1]), Outcome::Winner { option_index: 0 });
+        assert_eq!(outcome_after(threshold_config(Some(7500), None), &[0, 0, 1, 1]), Outcome::Tie { option_indices: vec![0, 1] });
+        assert_eq!(outcome_after(threshold_config(Some(7500), None), &[0, 0, 0, 1, 1]), Outcome::NoThresholdMet { leading_option: 0, share_bps: 6000 });
+
+        // No threshold keeps plain plurality
+        assert_eq!(outcome_after(threshold_config(None, None), &[0, 0, 0, 1, 1]), Outcome::Winner { option_index: 0 });
+
+        assert_eq!(tally::share_bps(1, 3), 3333);
+        assert_eq!(tally::share_bps(1, 8), 1250);
+        assert_eq!(tally::share_bps(1, 20_000), 1); // Half a basis point rounds up
+        assert_eq!(tally::share_bps(0, 0), 0);
+
+        let mut harness = TestHarness::new();
+        assert_eq!(harness.create_vote(threshold_config(Some(10_001), None), &Pubkey::new_unique()), Err(ProgramError::InvalidArgument));
+    }
+
+    #[test]
+    fn test_winning_threshold_abstentions() {
+        // Abstentions are left out of the shares and cannot win
+        assert_eq!(outcome_after(threshold_config(Some(6667), None), &[0, 0, 1, 2, 2, 2, 2, 2]), Outcome::Winner { option_index: 0 });
+        assert_eq!(outcome_after(threshold_config(None, None), &[2, 2]), Outcome::NoVotes);
+
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let vote_id = harness.create_vote(threshold_config(Some(6667), None), &creator).unwrap();
+        for option_index in [0, 1, 2] {
+            let voter = Pubkey::new_unique();
+            harness.allow(vote_id, &creator, &voter).unwrap();
+            harness.cast(vote_id, &voter, option_index).unwrap();
+        }
+        let results = harness.voting.get_full_results_as(&creator, vote_id).unwrap();
+        let shares: Vec<u16> = results.questions[0].options.iter().map(|option| option.share_bps).collect();
+        assert_eq!(shares, vec![5000, 5000, 0]);
+
+        // Abstentions count toward the quorum, then the threshold applies to the rest
+        assert_eq!(outcome_after(threshold_config(Some(6667), Some(4)), &[0, 0, 1]), Outcome::QuorumNotMet { total_votes: 3 });
+        assert_eq!(outcome_after(threshold_config(Some(6667), Some(4)), &[0, 0, 1, 2]), Outcome::Winner { option_index: 0 });
+        assert_eq!(outcome_after(threshold_config(Some(6667), Some(4)), &[0, 1, 2, 2]), Outcome::Tie { option_indices: vec![0, 1] });
+        assert_eq!(outcome_after(threshold_config(Some(6667), Some(4)), &[0, 0, 1, 1, 0, 2]), Outcome::NoThresholdMet { leading_option: 0, share_bps: 6000 });
+    }
+
+    // Closed two-option vote tied at one ballot each, closed with the recent blockhashes sysvar holding `blockhash`
+    fn tied_vote(tie_break: TieBreak, blockhash: Hash) -> (TestHarness, VoteId, Pubkey) {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let config = VoteConfig { tie_break, ..VoteConfig::from_labels("Mascot".to_string(), vec!["Owl".to_string(), "Fox".to_string()]).unwrap() };
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+
+        for option_index in [0, 1] {
+            let voter = Pubkey::new_unique();
+            harness.allow(vote_id, &creator, &voter).unwrap();
+            harness.cast(vote_id, &voter, option_index).unwrap();
+        }
+
+        #[allow(deprecated)]
+        let sysvar_key = recent_blockhashes::id();
+        let mut data = 1u64.to_le_bytes().to_vec();
+        data.extend_from_slice(blockhash.as_ref());
+        data.extend_from_slice(&5_000u64.to_le_bytes()); // Lamports per signature
+        harness.set_data(&sysvar_key, data);
+        let sysvar_info = harness.account_for(&sysvar_key);
+
+        let creator_info = harness.account_for(&creator);
+        harness.voting.close_vote(vote_id, &[creator_info, sysvar_info]).unwrap();
+        (harness, vote_id, creator)
+    }
+
+    #[test]
+    fn test_tie_break_none_and_first_listed() {
+        let (harness, vote_id, creator) = tied_vote(TieBreak::None, Hash::default());
+        assert_eq!(harness.voting.get_winner_as(&creator, vote_id, 0), Ok(Outcome::Tie { option_indices: vec![0, 1] }));
+
+        let (harness, vote_id, creator) = tied_vote(TieBreak::FirstListed, Hash::default());
+        assert_eq!(harness.voting.get_winner_as(&creator, vote_id, 0), Ok(Outcome::Winner { option_index: 0 }));
+    }
+
+    #[test]
+    fn test_tie_break_creator_decides() {
+        let (mut harness, vote_id, creator) = tied_vote(TieBreak::CreatorDecides, Hash::default());
+
+        // The tie stands until the creator resolves it
+        assert_eq!(harness.voting.get_winner_as(&creator, vote_id, 0), Ok(Outcome::Tie { option_indices: vec![0, 1] }));
+        assert_eq!(harness.voting.resolve_tie_as(&Pubkey::new_unique(), vote_id, 0, OptionIndex(1)), Err(ProgramError::InvalidArgument));
+        assert_eq!(harness.voting.resolve_tie_as(&creator, vote_id, 0, OptionIndex(2)), Err(ProgramError::InvalidArgument));
+
+        assert!(harness.voting.resolve_tie_as(&creator, vote_id, 0, OptionIndex(1)).is_ok());
+        assert_eq!(harness.voting.get_winner_as(&creator, vote_id, 0), Ok(Outcome::Winner { option_index: 1 }));
+
+        // The pick is final
+        assert_eq!(harness.voting.resolve_tie_as(&creator, vote_id, 0, OptionIndex(0)), Err(ProgramError::InvalidArgument));
+
+        // Other strategies cannot be resolved by hand
+        let (mut harness, vote_id, creator) = tied_vote(TieBreak::None, Hash::default());
+        assert_eq!(harness.voting.resolve_tie_as(&creator, vote_id, 0, OptionIndex(0)), Err(ProgramError::InvalidArgument));
+    }
+
+    #[test]
+    fn test_tie_break_blockhash() {
+        let blockhash = hash(b"slot 42");
+        let (harness, vote_id, creator) = tied_vote(TieBreak::Blockhash, blockhash);
+
+        let draw = hashv(&[blockhash.as_ref(), &0u32.to_le_bytes()]).to_bytes();
+        let expected = (u64::from_le_bytes(draw[..8].try_into().unwrap()) % 2) as usize;
+        assert_eq!(harness.voting.get_winner_as(&creator, vote_id, 0), Ok(Outcome::Winner { option_index: expected }));
+        assert_eq!(harness.voting.get_vote_summary(vote_id).unwrap().closure.unwrap().tie_break_hash, Some(blockhash));
+
+        // The same blockhash always picks the same option
+        let (harness, vote_id, creator) = tied_vote(TieBreak::Blockhash, blockhash);
+        assert_eq!(harness.voting.get_winner_as(&creator, vote_id, 0), Ok(Outcome::Winner { option_index: expected }));
+
+        // Closing needs the sysvar
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let config = VoteConfig { tie_break: TieBreak::Blockhash, ..VoteConfig::from_labels("Mascot".to_string(), vec!["Owl".to_string(), "Fox".to_string()]).unwrap() };
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+        assert_eq!(harness.close(vote_id, &creator), Err(ProgramError::NotEnoughAccountKeys));
+
+        let creator_info = harness.account_for(&creator);
+        let impostor_info = harness.account_for(&Pubkey::new_unique());
+        assert_eq!(harness.voting.close_vote(vote_id, &[creator_info, impostor_info]), Err(ProgramError::InvalidArgument));
+    }
+
+    // Closed vote whose allowlist holds `voters`, the first of them banned
+    fn sampled_vote(creator: &Pubkey, voters: &[Pubkey]) -> (TestHarness, VoteId) {
+        let mut harness = TestHarness::new();
+        let vote_id = harness.create_vote(VoteConfig::from_labels("Jury".to_string(), vec!["Yes".to_string(), "No".to_string()]).unwrap(), creator).unwrap();
+        for voter in voters {
+            harness.allow(vote_id, creator, voter).unwrap();
+        }
+        harness.cast(vote_id, &voters[1], 0).unwrap();
+        harness.voting.invalidate_voter_ballots_as(creator, vote_id, &voters[0]).unwrap();
+        (harness, vote_id)
+    }
+
+    #[test]
+    fn test_sample_voters() {
+        let creator = Pubkey::new_unique();
+        let voters: Vec<Pubkey> = (0..10).map(|_| Pubkey::new_unique()).collect();
+        let (mut harness, vote_id) = sampled_vote(&creator, &voters);
+        let slot_hash = hash(b"slot 42");
+
+        // Only closed votes can be sampled
+        assert_eq!(harness.voting.sample_voters_as(&creator, vote_id, 3, 42, &slot_hash), Err(VoteError::VoteStillOpen.into()));
+        harness.close(vote_id, &creator).unwrap();
+
+        // The sysvar is read from the account after the creator
+        let sysvar_key = slot_hashes::id();
+        let mut data = 1u64.to_le_bytes().to_vec();
+        data.extend_from_slice(&42u64.to_le_bytes());
+        data.extend_from_slice(slot_hash.as_ref());
+        harness.set_data(&sysvar_key, data);
+        let accounts = [harness.account_for(&creator), harness.account_for(&sysvar_key)];
+        assert_eq!(harness.voting.sample_voters(vote_id, 7, &accounts[..1]), Err(ProgramError::NotEnoughAccountKeys));
+        assert!(harness.voting.sample_voters_as(&voters[1], vote_id, 7, 42, &slot_hash).is_err());
+
+        // The banned voter leaves nine voters to draw from
+        assert_eq!(harness.voting.sample_voters(vote_id, 10, &accounts), Err(ProgramError::InvalidArgument));
+        let sample = harness.voting.sample_voters(vote_id, 7, &accounts).unwrap();
+        assert_eq!(sample.iter().collect::<HashSet<_>>().len(), 7);
+        assert!(sample.iter().all(|voter| voters[1..].contains(voter)));
+
+        // The record lets anyone repeat the draw, which happens once
+        let record = harness.voting.get_voter_sample_as(&creator, vote_id).unwrap().unwrap();
+        let results_hash = harness.voting.get_results_hash_as(&creator, vote_id).unwrap();
+        assert_eq!(record, VoterSample { seed: sampling::sample_seed(&results_hash, &slot_hash), slot: 42, voters: sample.clone() });
+        assert_eq!(sampling::draw_sample(&record.seed, &voters[1..], 7), sample);
+        assert_eq!(harness.voting.sample_voters(vote_id, 7, &accounts), Err(ProgramError::InvalidArgument));
+
+        // The same state and slot hash draw the same sample, another slot hash does not
+        let (mut same, vote_id) = sampled_vote(&creator, &voters);
+        same.close(vote_id, &creator).unwrap();
+        assert_eq!(same.voting.sample_voters_as(&creator, vote_id, 7, 42, &slot_hash), Ok(sample.clone()));
+        let (mut other, vote_id) = sampled_vote(&creator, &voters);
+        other.close(vote_id, &creator).unwrap();
+        assert_ne!(other.voting.sample_voters_as(&creator, vote_id, 7, 43, &hash(b"slot 43")), Ok(sample));
+    }
+
+    // Motion A and motion B, which needs option 0 of A to win
+    fn linked_votes(harness: &mut TestHarness, creator: &Pubkey, voter: &Pubkey) -> (VoteId, VoteId) {
+        let motion_a = harness.create_vote(VoteConfig::from_labels("Motion A".to_string(), vec!["Yes".to_string(), "No".to_string()]).unwrap(), creator).unwrap();
+        let config = VoteConfig {
+            prerequisite: Some(Prerequisite { vote_id: motion_a, required_option_index: OptionIndex(0) }),
+            ..VoteConfig::from_labels("Motion B".to_string(), vec!["Yes".to_string(), "No".to_string()]).unwrap()
+        };
+        let motion_b = harness.create_vote(config, creator).unwrap();
+        for vote_id in [motion_a, motion_b] {
+            harness.allow(vote_id, creator, voter).unwrap();
+        }
+        (motion_a, motion_b)
+    }
+
+    #[test]
+    fn test_prerequisite_passed() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voter = Pubkey::new_unique();
+        let (motion_a, motion_b) = linked_votes(&mut harness, &creator, &voter);
+        assert_eq!(harness.voting.get_vote_summary(motion_b).unwrap().status, VoteStatus::Pending);
+
+        // Neither ballots nor delegations are taken while motion A is open
+        assert_eq!(harness.cast(motion_b, &voter, 0), Err(VoteError::PrerequisiteNotMet.into()));
+        assert_eq!(harness.delegate(motion_b, &voter, &creator), Err(VoteError::PrerequisiteNotMet.into()));
+        assert_eq!(harness.voting.get_vote_summary(motion_b).unwrap().status, VoteStatus::Pending);
+
+        harness.cast(motion_a, &voter, 0).unwrap();
+        harness.close(motion_a, &creator).unwrap();
+
+        // The first ballot after motion A passed opens motion B
+        assert!(harness.cast(motion_b, &voter, 1).is_ok());
+        assert_eq!(harness.voting.get_vote_summary(motion_b).unwrap().status, VoteStatus::Open);
+        assert_eq!(harness.voting.get_full_results_as(&creator, motion_b).unwrap().questions[0].options[1].votes, 1);
+    }
+
+    #[test]
+    fn test_prerequisite_failed() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voter = Pubkey::new_unique();
+        let (motion_a, motion_b) = linked_votes(&mut harness, &creator, &voter);
+
+        // Motion A rejected keeps motion B pending for good
+        harness.cast(motion_a, &voter, 1).unwrap();
+        harness.close(motion_a, &creator).unwrap();
+        assert_eq!(harness.cast(motion_b, &voter, 0), Err(VoteError::PrerequisiteNotMet.into()));
+        assert_eq!(harness.voting.get_vote_summary(motion_b).unwrap().status, VoteStatus::Pending);
+
+        // Unknown or cancelled prerequisites and options they do not have are refused at creation
+        let dependent = |vote_id, option_index| VoteConfig {
+            prerequisite: Some(Prerequisite { vote_id, required_option_index: OptionIndex(option_index) }),
+            ..VoteConfig::from_labels("Motion C".to_string(), vec!["Yes".to_string()]).unwrap()
+        };
+        assert!(harness.create_vote(dependent(VoteId(9), 0), &creator).is_err());
+        assert!(harness.create_vote(dependent(motion_a, 2), &creator).is_err());
+        let cancelled = harness.create_vote(VoteConfig::from_labels("Motion D".to_string(), vec!["Yes".to_string()]).unwrap(), &creator).unwrap();
+        harness.voting.cancel_vote_as(&creator, cancelled, "Withdrawn".to_string()).unwrap();
+        assert!(harness.create_vote(dependent(cancelled, 0), &creator).is_err());
+    }
+
+    #[test]
+    fn test_credit_ledger() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let observer = Pubkey::new_unique();
+        let voters: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+        let vote_id = harness.create_vote(VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string(), "Option 2".to_string()]).unwrap(), &creator).unwrap();
+        harness.voting.add_observer_as(&creator, vote_id, observer).unwrap();
+
+        // Grants, a ballot, a delegation spent by the delegate, a retraction, a removal and a strike
+        for voter in &voters {
+            harness.allow(vote_id, &creator, voter).unwrap();
+        }
+        harness.cast(vote_id, &voters[0], 0).unwrap();
+        harness.voting.delegate_vote_as(&voters[1], vote_id, &voters[2], None).unwrap();
+        harness.cast(vote_id, &voters[2], 1).unwrap();
+        harness.voting.retract_ballot_as(&voters[0], vote_id, 0).unwrap();
+        harness.cast(vote_id, &voters[0], 1).unwrap();
+        harness.voting.remove_allowed_voter_as(&creator, vote_id, &voters[3]).unwrap();
+        harness.voting.invalidate_voter_ballots_as(&creator, vote_id, &voters[0]).unwrap();
+
+        // Every movement reconciles with the votes the voters hold
+        assert_eq!(harness.voting.check_vote(vote_id), Ok(()));
+        let ledger = harness.voting.get_ledger_as(&observer, vote_id, 0).unwrap();
+        assert_eq!(ledger.totals, vec![CreditTotals { granted: 4, tallied: 1, held: 0, forfeited: 2 }]);
+        assert_eq!(ledger.totals[0].outstanding(), Some(1)); // The vote the delegate still holds
+        assert_eq!((ledger.entries.len(), ledger.latest_seq, ledger.overflow), (12, 12, false));
+        assert_eq!(
+            ledger.entries[4],
+            CreditEntry { seq: 5, question_index: 0, from: CreditEndpoint::Voter(voters[0]), to: CreditEndpoint::Tally(OptionIndex(0)), amount: 1, cause: CreditCause::Ballot }
+        );
+        assert_eq!(ledger.entries[5].cause, CreditCause::Delegation);
+        assert_eq!(harness.voting.get_ledger_as(&creator, vote_id, 10).unwrap().entries.iter().map(|entry| entry.cause).collect::<Vec<_>>(), vec![CreditCause::Struck, CreditCause::Forfeit]);
+        assert_eq!(harness.voting.get_ledger_as(&voters[1], vote_id, 0), Err(ProgramError::InvalidArgument));
+
+        // The delegator has no vote left: the ledger refuses the over-spend and nothing is counted
+        let before = harness.voting.clone();
+        assert_eq!(harness.cast(vote_id, &voters[1], 0), Err(VoteError::InsufficientVotes.into()));
+        assert_eq!(harness.voting, before);
+
+        // Votes slipped to a voter past the ledger no longer reconcile
+        harness.voting.votes.get_mut(&vote_id).unwrap().allowed_voters.get_mut(&voters[1]).unwrap().votes_left = vec![1];
+        assert_eq!(harness.voting.check_vote(vote_id), Err(VoteError::CorruptState.into()));
+    }
+
+    #[test]
+    fn test_changelog() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let vote_id = harness.create_vote(VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string(), "Option 2".to_string()]).unwrap(), &creator).unwrap();
+        assert_eq!(harness.voting.get_changes_since(vote_id, 0), Ok(Changes { entries: vec![], latest_seq: 0, overflow: false }));
+
+        // 100 voters join and cast their ballot, 200 changes in all
+        set_clock(1_000);
+        for _ in 0..100 {
+            let voter = Pubkey::new_unique();
+            harness.allow(vote_id, &creator, &voter).unwrap();
+            harness.cast(vote_id, &voter, 0).unwrap();
+        }
+
+        // The first 72 changes were evicted
+        let changes = harness.voting.get_changes_since(vote_id, 10).unwrap();
+        assert!(changes.overflow);
+        assert_eq!(changes.latest_seq, 200);
+        assert_eq!(changes.entries.len(), MAX_CHANGELOG_ENTRIES);
+        assert_eq!(changes.entries[0], ChangeEntry { seq: 73, kind: ChangeKind::VoterAdded, at: 1_000 });
+        assert!(!harness.voting.get_changes_since(vote_id, 72).unwrap().overflow);
+
+        let changes = harness.voting.get_changes_since(vote_id, 197).unwrap();
+        assert!(!changes.overflow);
+        assert_eq!(
+            changes.entries,
+            vec![
+                ChangeEntry { seq: 198, kind: ChangeKind::BallotCast, at: 1_000 },
+                ChangeEntry { seq: 199, kind: ChangeKind::VoterAdded, at: 1_000 },
+                ChangeEntry { seq: 200, kind: ChangeKind::BallotCast, at: 1_000 },
+            ]
+        );
+
+        // Removals, delegations and the closure follow
+        let (delegator, delegate) = (Pubkey::new_unique(), Pubkey::new_unique());
+        harness.allow(vote_id, &creator, &delegator).unwrap();
+        harness.allow(vote_id, &creator, &delegate).unwrap();
+        harness.delegate(vote_id, &delegator, &delegate).unwrap();
+        harness.voting.remove_allowed_voter_as(&creator, vote_id, &delegate).unwrap();
+        set_clock(2_000);
+        harness.close(vote_id, &creator).unwrap();
+
+        let kinds: Vec<ChangeKind> = harness.voting.get_changes_since(vote_id, 202).unwrap().entries.iter().map(|entry| entry.kind).collect();
+        assert_eq!(kinds, vec![ChangeKind::Delegated, ChangeKind::VoterRemoved, ChangeKind::Closed]);
+        assert_eq!(harness.voting.get_changes_since(vote_id, 205), Ok(Changes { entries: vec![], latest_seq: 205, overflow: false }));
+        assert_eq!(harness.voting.get_changes_since(VoteId(vote_id.0 + 1), 0), Err(ProgramError::InvalidArgument));
+    }
+
+    #[test]
+    fn test_closure_records() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let finalizer = Pubkey::new_unique();
+        let admin = *harness.voting.admin();
+        let config = VoteConfig::from_labels("Weekly Vote".to_string(), vec!["Option 1".to_string()]).unwrap();
+
+        set_clock(1_000);
+        let closed_id = harness.create_vote(config.clone(), &creator).unwrap();
+        let admin_id = harness.create_vote(config.clone(), &creator).unwrap();
+        let expiring_id = harness.create_vote(VoteConfig { deadline: Some(2_000), ..config.clone() }, &creator).unwrap();
+        let cancelled_id = harness.create_vote(config, &creator).unwrap();
+        for vote_id in [closed_id, admin_id, expiring_id, cancelled_id] {
+            assert_eq!(harness.voting.get_vote_summary(vote_id).unwrap().closure, None);
+        }
+
+        set_clock(1_500);
+        assert!(harness.close(closed_id, &creator).is_ok());
+        assert!(harness.close(admin_id, &finalizer).is_err()); // Neither the creator nor the admin
+        assert!(harness.close(admin_id, &admin).is_ok());
+        assert!(harness.voting.cancel_vote_as(&creator, cancelled_id, "Withdrawn".to_string()).is_ok());
+
+        // The deadline has to pass before anyone can finalize
+        let finalizer_info = harness.account_for(&finalizer);
+        assert_eq!(harness.voting.finalize_expired_vote(expiring_id, std::slice::from_ref(&finalizer_info)), Err(VoteError::VoteStillOpen.into()));
+        set_clock(2_001);
+        let voter = Pubkey::new_unique();
+        harness.allow(expiring_id, &creator, &voter).unwrap();
+        assert_eq!(harness.cast(expiring_id, &voter, 0), Err(VoteError::DeadlinePassed.into()));
+        assert!(harness.voting.finalize_expired_vote(expiring_id, &[finalizer_info]).is_ok());
+
+        let expected = [
+            (closed_id, creator, 1_500, ClosureReason::Creator),
+            (admin_id, admin, 1_500, ClosureReason::Admin),
+            (cancelled_id, creator, 1_500, ClosureReason::Cancelled),
+            (expiring_id, finalizer, 2_001, ClosureReason::Expired),
+        ];
+        for (vote_id, closed_by, closed_at, reason) in expected {
+            let record = Some(ClosureRecord { closed_by, closed_at, reason, tie_break_hash: None });
+            assert_eq!(harness.voting.get_vote_summary(vote_id).unwrap().closure, record);
+            assert_eq!(harness.voting.get_full_results_as(&creator, vote_id).unwrap().closure, record);
+        }
+
+        // Reopening clears the record, a vote past its deadline stays closed
+        assert!(harness.voting.reopen_vote_as(&creator, closed_id).is_ok());
+        assert_eq!(harness.voting.get_vote_summary(closed_id).unwrap().closure, None);
+        assert_eq!(harness.voting.get_vote_summary(closed_id).unwrap().status, VoteStatus::Open);
+        assert_eq!(harness.voting.reopen_vote_as(&creator, expiring_id), Err(VoteError::DeadlinePassed.into()));
+        assert!(harness.voting.reopen_vote_as(&creator, cancelled_id).is_err());
+        assert_eq!(harness.voting.get_registry_stats().currently_open, 1);
+    }
+
+    #[test]
+    fn test_grace_period() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voters: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+        let config = VoteConfig::from_labels("Weekly Vote".to_string(), vec!["Option 1".to_string(), "Option 2".to_string()]).unwrap();
+
+        // 10 slots after the deadline make a grace period of 4 seconds
+        set_clock(1_000);
+        let vote_id = harness.create_vote(VoteConfig { deadline: Some(2_000), grace_period_slots: 10, ..config }, &creator).unwrap();
+        for voter in &voters {
+            harness.allow(vote_id, &creator, voter).unwrap();
+        }
+
+        set_clock(2_000);
+        assert!(harness.cast(vote_id, &voters[0], 0).is_ok());
+        set_clock(2_004);
+        assert!(harness.cast(vote_id, &voters[1], 1).is_ok());
+        set_clock(2_005);
+        assert_eq!(harness.cast(vote_id, &voters[2], 1), Err(VoteError::DeadlinePassed.into()));
+
+        // Grace ballots count like any other and carry the flag
+        let ballots = harness.voting.get_ballots_as(&creator, vote_id, 0).unwrap();
+        assert_eq!(ballots.iter().map(|ballot| ballot.in_grace).collect::<Vec<_>>(), vec![false, true]);
+        let results = harness.voting.get_full_results_as(&creator, vote_id).unwrap();
+        assert_eq!(results.questions[0].total_votes, 2);
+        assert_eq!(results.grace_ballots, 1);
+
+        // Finalizing only waits for the deadline itself
+        set_clock(2_001);
+        let finalizer_info = harness.account_for(&voters[3]);
+        assert!(harness.voting.finalize_expired_vote(vote_id, &[finalizer_info]).is_ok());
+    }
+
+    #[test]
+    fn test_ballot_comments() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let observer = Pubkey::new_unique();
+        let outsider = Pubkey::new_unique();
+        let voters = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let config = VoteConfig::from_labels("Board Vote".to_string(), vec!["Approve".to_string(), "Reject".to_string()]).unwrap();
+        let plain_id = harness.create_vote(config.clone(), &creator).unwrap();
+        let vote_id = harness.create_vote(VoteConfig { require_comment: true, ..config }, &creator).unwrap();
+        harness.voting.add_observer_as(&creator, vote_id, observer).unwrap();
+        for voter in &voters {
+            harness.allow(vote_id, &creator, voter).unwrap();
+        }
+        harness.allow(plain_id, &creator, &voters[0]).unwrap();
+
+        // Ballots without a comment and comments over the limit are refused
+        assert_eq!(harness.cast(vote_id, &voters[0], 0), Err(VoteError::CommentRequired.into()));
+        assert_eq!(harness.voting.vote_with_comment_as(&voters[0], vote_id, 0, OptionIndex(0), "x".repeat(MAX_COMMENT_LEN + 1)), Err(ProgramError::InvalidArgument));
+        assert_eq!(harness.voting.vote_with_comment_as(&voters[0], plain_id, 0, OptionIndex(0), "Fine".to_string()), Err(ProgramError::InvalidArgument));
+        let comment = "Costs are too high, \"defer\" to Q3";
+        harness.voting.vote_with_comment_as(&voters[0], vote_id, 0, OptionIndex(1), comment.to_string()).unwrap();
+        harness.voting.vote_with_comment_as(&voters[1], vote_id, 0, OptionIndex(0), "x".repeat(MAX_COMMENT_LEN)).unwrap();
+
+        // Only the creator and the observers read the comments
+        for reader in [creator, observer] {
+            assert_eq!(harness.voting.get_ballot_as(&reader, vote_id, &voters[0], 0).unwrap().comment.as_deref(), Some(comment));
+        }
+        let ballot = harness.voting.get_ballot_as(&outsider, vote_id, &voters[0], 0).unwrap();
+        assert_eq!((ballot.option_index, ballot.comment), (OptionIndex(1), None));
+        assert!(harness.voting.get_ballots_as(&outsider, vote_id, 0).unwrap().iter().all(|ballot| ballot.comment.is_none()));
+
+        // The export quotes the comment and leaves it out for other readers
+        let csv = harness.voting.export_ballots_csv_as(&creator, vote_id).unwrap();
+        let row = format!("{},0,1,Reject,10000,false,\"Costs are too high, \"\"defer\"\" to Q3\"", voters[0]);
+        assert!(csv.starts_with("voter,question_index,option_index,option,weight,in_grace,comment\n"));
+        assert!(csv.lines().any(|line| line == row));
+        let csv = harness.voting.export_ballots_csv_as(&outsider, vote_id).unwrap();
+        assert!(csv.lines().any(|line| line == format!("{},0,1,Reject,10000,false,", voters[0])));
+    }
+
+    #[test]
+    fn test_finalize_all_expired() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let finalizer = Pubkey::new_unique();
+        let config = |deadline| VoteConfig { deadline, ..VoteConfig::from_labels("Weekly Vote".to_string(), vec!["Option 1".to_string()]).unwrap() };
+
+        set_clock(1_000);
+        let live_id = harness.create_vote(config(Some(5_000)), &creator).unwrap();
+        let expired: Vec<VoteId> = (0..5).map(|_| harness.create_vote(config(Some(2_000)), &creator).unwrap()).collect();
+        let open_id = harness.create_vote(config(None), &creator).unwrap();
+        assert_eq!(harness.voting.get_registry_stats().currently_open, 7);
+
+        let finalizer_info = harness.account_for(&finalizer);
+        assert_eq!(harness.voting.finalize_all_expired(3, std::slice::from_ref(&finalizer_info)), Ok(0)); // Nothing has expired yet
+
+        set_clock(2_001);
+        assert_eq!(harness.voting.finalize_all_expired(3, std::slice::from_ref(&finalizer_info)), Ok(3));
+        let closed = |harness: &TestHarness, vote_id| harness.voting.get_vote_summary(vote_id).unwrap().status == VoteStatus::Closed;
+        assert_eq!(expired.iter().map(|vote_id| closed(&harness, *vote_id)).collect::<Vec<_>>(), [true, true, true, false, false]);
+        assert_eq!(harness.voting.get_registry_stats().currently_open, 4);
+
+        assert_eq!(harness.voting.finalize_all_expired(3, std::slice::from_ref(&finalizer_info)), Ok(2));
+        assert_eq!(harness.voting.finalize_all_expired(3, std::slice::from_ref(&finalizer_info)), Ok(0));
+        assert_eq!(harness.voting.get_registry_stats().currently_open, 2);
+        for vote_id in &expired {
+            let record = Some(ClosureRecord { closed_by: finalizer, closed_at: 2_001, reason: ClosureReason::Expired, tie_break_hash: None });
+            assert_eq!(harness.voting.get_vote_summary(*vote_id).unwrap().closure, record);
+        }
+        assert!(!closed(&harness, live_id));
+        assert!(!closed(&harness, open_id));
+    }
+
+    #[test]
+    fn test_pending_votes_for() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let member = Pubkey::new_unique();
+        let config = |deadline| VoteConfig { deadline, ..VoteConfig::from_labels("Weekly Vote".to_string(), vec!["Option 1".to_string()]).unwrap() };
+
+        set_clock(1_000);
+        let late_id = harness.create_vote(config(Some(5_000)), &creator).unwrap();
+        let voted_id = harness.create_vote(config(Some(2_000)), &creator).unwrap();
+        let closed_id = harness.create_vote(config(None), &creator).unwrap();
+        let other_id = harness.create_vote(config(Some(1_500)), &creator).unwrap();
+        let open_ended_id = harness.create_vote(config(None), &creator).unwrap();
+        let soon_id = harness.create_vote(config(Some(3_000)), &creator).unwrap();
+        let struck_id = harness.create_vote(config(None), &creator).unwrap();
+
+        for vote_id in [late_id, voted_id, closed_id, open_ended_id, soon_id, struck_id] {
+            harness.allow(vote_id, &creator, &member).unwrap();
+        }
+        harness.allow(other_id, &creator, &Pubkey::new_unique()).unwrap();
+
+        harness.cast(voted_id, &member, 0).unwrap();
+        harness.close(closed_id, &creator).unwrap();
+        harness.voting.invalidate_voter_ballots_as(&creator, struck_id, &member).unwrap();
+
+        let pending: Vec<(VoteId, Option<i64>)> = harness.voting.pending_votes_for(&member).iter().map(|summary| (summary.id, summary.deadline)).collect();
+        assert_eq!(pending, vec![(soon_id, Some(3_000)), (late_id, Some(5_000)), (open_ended_id, None)]);
+
+        assert!(harness.voting.pending_votes_for(&Pubkey::new_unique()).is_empty());
+    }
+
+    #[test]
+    fn test_display_order() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let admin = *harness.voting.admin();
+        let config = VoteConfig::from_labels("Weekly Vote".to_string(), vec!["Option 1".to_string()]).unwrap();
+        let ids: Vec<VoteId> = (0..6).map(|_| harness.create_vote(config.clone(), &creator).unwrap()).collect();
+
+        // Pinned first, then ordered by priority, then the rest by ID
+        harness.voting.set_display_order_as(&creator, ids[4], false, Some(2)).unwrap();
+        harness.voting.set_display_order_as(&creator, ids[5], false, Some(1)).unwrap();
+        harness.voting.set_display_order_as(&creator, ids[3], true, None).unwrap();
+        harness.voting.set_display_order_as(&admin, ids[2], true, Some(7)).unwrap(); // A registry-wide announcement
+        let listed: Vec<VoteId> = harness.voting.list_votes().iter().map(|summary| summary.id).collect();
+        assert_eq!(listed, vec![ids[2], ids[3], ids[5], ids[4], ids[0], ids[1]]);
+
+        let summary = harness.voting.get_vote_summary(ids[2]).unwrap();
+        assert_eq!((summary.pinned, summary.display_order), (true, Some(7)));
+
+        // Closed votes keep their place in the full list only, and can still be reordered
+        harness.close(ids[3], &creator).unwrap();
+        harness.voting.set_display_order_as(&creator, ids[3], false, Some(0)).unwrap();
+        let open: Vec<VoteId> = harness.voting.list_open_votes().iter().map(|summary| summary.id).collect();
+        assert_eq!(open, vec![ids[2], ids[5], ids[4], ids[0], ids[1]]);
+        assert_eq!(harness.voting.list_votes()[1].id, ids[3]);
+
+        // Nobody else orders the votes
+        let voter = Pubkey::new_unique();
+        harness.allow(ids[0], &creator, &voter).unwrap();
+        assert_eq!(harness.voting.set_display_order_as(&voter, ids[0], true, None), Err(ProgramError::InvalidArgument));
+        assert_eq!(harness.voting.set_display_order_as(&creator, VoteId(99), true, None), Err(ProgramError::InvalidArgument));
+        assert!(!harness.voting.get_vote_summary(ids[0]).unwrap().pinned);
+    }
+
+    #[test]
+    fn test_weighted_delegation() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let whale = Pubkey::new_unique();
+        let delegate = Pubkey::new_unique();
+        let config = VoteConfig {
+            vote_type: VoteType::TokenWeighted,
+            ..VoteConfig::from_labels("Treasury Vote".to_string(), vec!["Yes".to_string(), "No".to_string()]).unwrap()
+        };
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+
+        harness.voting.add_weighted_voter_as(&creator, vote_id, whale, 1_000).unwrap();
+        harness.voting.add_weighted_voter_as(&creator, vote_id, delegate, 5).unwrap();
+
+        assert!(harness.delegate(vote_id, &whale, &delegate).is_ok());
+        let status = harness.voting.get_voter_status_as(&delegate, vote_id, &delegate).unwrap();
+        assert_eq!((status.weight, status.received_weight), (5, 1_000));
+
+        // The whale has no weight left, the delegate votes with both
+        assert!(harness.cast(vote_id, &whale, 1).is_err());
+        assert!(harness.delegate(vote_id, &whale, &creator).is_err());
+        assert!(harness.cast(vote_id, &delegate, 0).is_ok());
+
+        let results = harness.voting.get_full_results_as(&creator, vote_id).unwrap();
+        assert_eq!(results.questions[0].options[0].votes, 1);
+        assert_eq!(results.questions[0].options[0].weighted_votes, 1_005);
+        assert_eq!(results.questions[0].options[1].weighted_votes, 0);
+
+        // Weighted votes only take weights recorded by the creator
+        let plain_id = harness.create_vote(VoteConfig::from_labels("Plain Vote".to_string(), vec!["Yes".to_string()]).unwrap(), &creator).unwrap();
+        assert!(harness.voting.add_weighted_voter_as(&creator, plain_id, whale, 1_000).is_err());
+    }
+
+    #[test]
+    fn test_weighted_delegation_portion_and_expiry() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let holder = Pubkey::new_unique();
+        let first = Pubkey::new_unique();
+        let second = Pubkey::new_unique();
+
+        set_clock(1_000);
+        let config = VoteConfig {
+            vote_type: VoteType::TokenWeighted,
+            delegation_policy: DelegationPolicy::AnyRecipient,
+            ..VoteConfig::from_labels("Treasury Vote".to_string(), vec!["Yes".to_string(), "No".to_string()]).unwrap()
+        };
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+        harness.voting.add_weighted_voter_as(&creator, vote_id, holder, 100).unwrap();
+
+        // Portions go to delegates without an allowlist entry of their own
+        assert!(harness.voting.delegate_weight_as(&holder, vote_id, &first, 30, None).is_ok());
+        assert!(harness.voting.delegate_weight_as(&holder, vote_id, &second, 20, Some(2_000)).is_ok());
+        assert!(harness.voting.delegate_weight_as(&holder, vote_id, &second, 51, None).is_err());
+
+        assert!(harness.cast(vote_id, &first, 0).is_ok());
+
+        // The expired portion returns to the holder, who votes with the rest
+        set_clock(2_000);
+        assert!(harness.cast(vote_id, &second, 0).is_err());
+        assert!(harness.voting.reclaim_expired_delegations(vote_id, &holder).is_ok());
+        assert!(harness.cast(vote_id, &holder, 1).is_ok());
+
+        let results = harness.voting.get_full_results_as(&creator, vote_id).unwrap();
+        assert_eq!(results.questions[0].options[0].weighted_votes, 30);
+        assert_eq!(results.questions[0].options[1].weighted_votes, 70);
+
+        // Voted weight cannot be delegated afterwards
+        assert!(harness.voting.delegate_weight_as(&holder, vote_id, &second, 10, None).is_err());
+    }
+
+    #[test]
+    fn test_allowlist_capacity_from_account_size() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voters = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+        set_clock(1_000);
+
+        let config = VoteConfig { delegation_policy: DelegationPolicy::AnyRecipient, ..VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string(), "Option 2".to_string()]).unwrap() };
+
+        // Allocate a state account with room for exactly 3 voters
+        let state_key = Pubkey::new_unique();
+        harness.set_data(&state_key, vec![0; Vote::required_space(&config, 3)]);
+        let state_info = harness.account_for(&state_key);
+
+        let account_info = harness.account_for(&creator);
+        let vote_id = harness.voting.create_vote_with_config(config, &[account_info.clone(), state_info.clone()]).unwrap();
+
+        for voter in &voters[..3] {
+            assert!(harness.voting.add_allowed_voter_as(&creator, vote_id, *voter).is_ok());
+        }
+        assert_eq!(
+            harness.voting.add_allowed_voter_as(&creator, vote_id, voters[3]),
+            Err(ProgramError::from(VoteError::AllowlistFull))
+        );
+
+        // Delegating to an outsider would also need a new entry
+        assert_eq!(harness.voting.delegate_vote_as(&voters[0], vote_id, &voters[3], None), Err(ProgramError::from(VoteError::AllowlistFull)));
+        assert!(harness.voting.delegate_vote_as(&voters[0], vote_id, &voters[1], None).is_ok());
+
+        // The largest state the vote can reach still saves cleanly
+        assert!(harness.voting.cancel_vote_as(&creator, vote_id, "x".repeat(MAX_REASON_LEN)).is_ok());
+
+        let vote = harness.voting.votes.get(&vote_id).unwrap();
+        assert!(vote.save(&state_info).is_ok());
+        assert_eq!(&Vote::load(&state_info).unwrap(), vote);
+    }
+
+    #[test]
+    fn test_create_vote_account_too_small() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+
+        let config = VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string()]).unwrap();
+
+        let state_key = Pubkey::new_unique();
+        harness.set_data(&state_key, vec![0; Vote::required_space(&config, 0) - 1]);
+        let state_info = harness.account_for(&state_key);
+
+        let account_info = harness.account_for(&creator);
+        assert_eq!(harness.voting.create_vote_with_config(config, &[account_info, state_info]), Err(ProgramError::AccountDataTooSmall));
+    }
+
+    #[test]
+    fn test_update_policy_admin_only() {
+        let mut harness = TestHarness::new();
+        let admin = harness.voting.admin;
+        let stranger = Pubkey::new_unique();
+
+        let policy = CreationPolicy { fee: None, cooldown_slots: Some(10), deposit: None };
+
+        let stranger_info = harness.account_for(&stranger);
+        assert!(harness.voting.update_policy(policy, &[stranger_info]).is_err());
+        assert_eq!(harness.voting.get_creation_policy(), CreationPolicy::default());
+
+        let admin_info = harness.account_for(&admin);
+        assert!(harness.voting.update_policy(policy, &[admin_info]).is_ok());
+        assert_eq!(harness.voting.get_creation_policy(), policy);
+    }
+
+    #[test]
+    fn test_option_metadata() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+
+        let options = vec![
+            VoteOption { description: Some("The current logo".try_into().unwrap()), ..VoteOption::new("Blue".to_string(), 20).unwrap() },
+            VoteOption::new("Green".to_string(), 10).unwrap(),
+        ];
+        let vote_id = harness.create_vote(VoteConfig::new("Logo".to_string(), options.clone()).unwrap(), &creator).unwrap();
+
+        assert_eq!(harness.voting.get_options(vote_id).unwrap(), &options);
+
+        let results = harness.voting.get_full_results_as(&creator, vote_id).unwrap();
+        let blue = &results.questions[0].options[0];
+        assert_eq!(blue.label, "Blue");
+        assert_eq!(blue.description.as_deref(), Some("The current logo"));
+        assert_eq!(blue.order, 20);
+        assert_eq!(results.questions[0].options[1].order, 10);
+    }
+
+    #[test]
+    fn test_option_content_hash() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voter = Pubkey::new_unique();
+
+        // Hashes given with the config end up in the options and the results
+        let options = vec![
+            VoteOption { content_hash: Some([1; 32]), ..VoteOption::new("Blue".to_string(), 0).unwrap() },
+            VoteOption::new("Green".to_string(), 1).unwrap(),
+        ];
+        let vote_id = harness.create_vote(VoteConfig::new("Logo".to_string(), options).unwrap(), &creator).unwrap();
+        harness.allow(vote_id, &creator, &voter).unwrap();
+        assert_eq!(harness.voting.get_options(vote_id).unwrap()[0].content_hash, Some([1; 32]));
+        assert_eq!(harness.voting.get_full_results_as(&creator, vote_id).unwrap().questions[0].options[0].content_hash, Some([1; 32]));
+
+        // The creator pins or changes the others before the first ballot, and the fingerprint follows
+        let unpinned = harness.voting.get_results_hash_as(&creator, vote_id).unwrap();
+        assert_eq!(harness.voting.set_option_content_hash_as(&voter, vote_id, OptionIndex(1), Some([2; 32])), Err(ProgramError::InvalidArgument));
+        assert_eq!(harness.voting.set_option_content_hash_as(&creator, vote_id, OptionIndex(2), Some([2; 32])), Err(ProgramError::InvalidArgument));
+        assert!(harness.voting.set_option_content_hash_as(&creator, vote_id, OptionIndex(1), Some([2; 32])).is_ok());
+        let pinned = harness.voting.get_results_hash_as(&creator, vote_id).unwrap();
+        assert_ne!(pinned, unpinned);
+        assert_eq!(pinned, harness.voting.get_full_results_as(&creator, vote_id).unwrap().hash());
+
+        assert!(harness.voting.set_option_content_hash_as(&creator, vote_id, OptionIndex(1), None).is_ok());
+        assert_eq!(harness.voting.get_results_hash_as(&creator, vote_id).unwrap(), unpinned);
+        assert!(harness.voting.set_option_content_hash_as(&creator, vote_id, OptionIndex(1), Some([2; 32])).is_ok());
+
+        // Once a ballot is cast the images are locked
+        harness.cast(vote_id, &voter, 1).unwrap();
+        assert_eq!(harness.voting.set_option_content_hash_as(&creator, vote_id, OptionIndex(1), Some([3; 32])), Err(VoteError::BallotsAlreadyCast.into()));
+        assert_eq!(harness.voting.get_options(vote_id).unwrap()[1].content_hash, Some([2; 32]));
+
+        // The hash survives serialization of the vote
+        let vote = &harness.voting.votes[&vote_id];
+        assert_eq!(&Vote::try_from_slice(&borsh::to_vec(vote).unwrap()).unwrap(), vote);
+    }
+
+    #[test]
+    fn test_option_translations() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voter = Pubkey::new_unique();
+        let [en, ru, de] = ["en", "ru", "de"].map(|code| LangCode::new(code).unwrap());
+        let labels = |labels: &[&str]| labels.iter().map(|label| label.to_string()).collect::<Vec<_>>();
+        let localized = |harness: &TestHarness, vote_id, lang| harness.voting.get_options_localized(vote_id, lang).unwrap().into_iter().map(|option| option.label.into_string()).collect::<Vec<_>>();
+
+        // Translations given with the config cover every option once per language
+        let translation = (de, vec![Label::new("label", "Ja").unwrap(), Label::new("label", "Nein").unwrap()]);
+        let config = VoteConfig { translations: vec![translation.clone()], ..VoteConfig::from_labels("Budget".to_string(), labels(&["Yes", "No"])).unwrap() };
+        assert!(harness.create_vote(VoteConfig { translations: vec![translation.clone(), translation.clone()], ..config.clone() }, &creator).is_err());
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+        assert_eq!(localized(&harness, vote_id, de), labels(&["Ja", "Nein"]));
+
+        // The creator adds languages, with exactly one label per option
+        assert_eq!(harness.voting.set_translation_as(&voter, vote_id, ru, labels(&["Да", "Нет"])), Err(ProgramError::InvalidArgument));
+        assert_eq!(harness.voting.set_translation_as(&creator, vote_id, ru, labels(&["Да"])), Err(ProgramError::InvalidArgument));
+        assert_eq!(harness.voting.set_translation_as(&creator, vote_id, ru, labels(&["Да", "Нет", "Воздержаться"])), Err(ProgramError::InvalidArgument));
+        harness.voting.set_translation_as(&creator, vote_id, ru, labels(&["Да", "Нет"])).unwrap();
+        assert_eq!(localized(&harness, vote_id, ru), labels(&["Да", "Нет"]));
+
+        // Missing languages fall back to the labels of the options
+        assert_eq!(localized(&harness, vote_id, en), labels(&["Yes", "No"]));
+
+        // Ballots and tallies still go by the labels of the options
+        harness.allow(vote_id, &creator, &voter).unwrap();
+        harness.cast(vote_id, &voter, 1).unwrap();
+        assert_eq!(harness.voting.votes[&vote_id].questions[0].votes["No"], 1);
+        assert_eq!(harness.voting.set_translation_as(&creator, vote_id, ru, labels(&["Да", "Нет"])), Err(VoteError::BallotsAlreadyCast.into()));
+        let vote = &harness.voting.votes[&vote_id];
+        assert_eq!(&Vote::try_from_slice(&borsh::to_vec(vote).unwrap()).unwrap(), vote);
+    }
+
+    #[test]
+    fn test_option_format() {
+        let samples = [
+            (OptionFormat::IsoDate, vec!["2024-02-29", "2023-12-31", "2000-02-29", "0001-01-01"], vec!["2023-02-29", "1900-02-29", "2023-13-01", "2023-04-31", "2023-00-10", "2023-01-00", "2023-1-01", "2023/01/01", "+023-01-01", "20230101"]),
+            (OptionFormat::Integer, vec!["0", "-42", "9223372036854775807"], vec!["", "1.5", "9223372036854775808", "12a"]),
+            (OptionFormat::PubkeyBase58, vec!["11111111111111111111111111111111"], vec!["0OIl", "not a key"]),
+            (OptionFormat::FreeText, vec!["Anything", "2023-02-29"], vec![]),
+        ];
+        for (format, valid, invalid) in &samples {
+            for label in valid {
+                assert_eq!(format.validate(label), Ok(()), "{:?} {}", format, label);
+            }
+            for label in invalid {
+                assert_eq!(format.validate(label), Err(ProgramError::InvalidArgument), "{:?} {}", format, label);
+            }
+        }
+
+        // Creation checks every option of every question
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let dates = |labels: &[&str]| VoteConfig {
+            option_format: OptionFormat::IsoDate,
+            allow_write_ins: true,
+            ..VoteConfig::from_labels("Meeting".to_string(), labels.iter().map(|label| label.to_string()).collect()).unwrap()
+        };
+        assert_eq!(harness.create_vote(dates(&["2023-03-01", "2023-02-29"]), &creator), Err(ProgramError::InvalidArgument));
+        let vote_id = harness.create_vote(dates(&["2023-03-01", "2023-03-02"]), &creator).unwrap();
+        assert_eq!(harness.voting.get_option_format(vote_id), Ok(OptionFormat::IsoDate));
+        assert_eq!(harness.voting.get_options_response(vote_id).unwrap().format, OptionFormat::IsoDate);
+
+        // Write-ins follow the format too
+        let voter = Pubkey::new_unique();
+        harness.allow(vote_id, &creator, &voter).unwrap();
+        assert_eq!(harness.voting.vote_write_in_as(&voter, vote_id, "next friday"), Err(ProgramError::InvalidArgument));
+        assert_eq!(harness.voting.vote_write_in_as(&voter, vote_id, "2023-03-03"), Ok(OptionIndex(2)));
+    }
+
+    #[test]
+    fn test_open_vote_eligibility() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voters = [Pubkey::new_unique(), Pubkey::new_unique()];
+        for voter in &voters {
+            harness.set_owner(voter, &system_program::id());
+            harness.set_lamports(voter, 5_000);
+        }
+
+        let config = VoteConfig {
+            eligibility: Some(Eligibility::MinStake(MinStake { min_lamports: 5_000 })),
+            ..VoteConfig::from_labels("Open Vote".to_string(), vec!["Option 1".to_string(), "Option 2".to_string()]).unwrap()
+        };
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+
+        // The eligibility account is required
+        let voter_info = harness.account_for(&voters[0]);
+        assert_eq!(harness.voting.register_voter(vote_id, std::slice::from_ref(&voter_info)), Err(ProgramError::NotEnoughAccountKeys));
+        assert_eq!(harness.voting.vote(vote_id, std::slice::from_ref(&voter_info), OptionIndex(0)), Err(ProgramError::NotEnoughAccountKeys));
+
+        harness.set_lamports(&voters[0], 4_999);
+        let accounts = [voter_info.clone(), voter_info.clone()];
+        assert_eq!(harness.voting.register_voter(vote_id, &accounts), Err(VoteError::EligibilityNotMet.into()));
+
+        harness.set_lamports(&voters[0], 5_000);
+        assert!(harness.voting.register_voter(vote_id, &accounts).is_ok());
+        assert_eq!(harness.voting.register_voter(vote_id, &accounts), Err(ProgramError::InvalidArgument));
+        assert!(harness.voting.vote_as(&voters[0], vote_id, OptionIndex(1)).is_ok());
+
+        // The second voter joins with their first ballot
+        let voter_info = harness.account_for(&voters[1]);
+        assert!(harness.voting.vote(vote_id, &[voter_info.clone(), voter_info], OptionIndex(0)).is_ok());
+        assert_eq!(harness.voting.votes[&vote_id].allowed_voters.len(), 2);
+
+        // Votes without an eligibility rule are not open
+        let vote_id = harness.create_vote(VoteConfig::from_labels("Closed Vote".to_string(), vec!["Option 1".to_string()]).unwrap(), &creator).unwrap();
+        let voter_info = harness.account_for(&voters[0]);
+        assert_eq!(harness.voting.register_voter(vote_id, &[voter_info.clone(), voter_info]), Err(ProgramError::InvalidArgument));
+    }
+
+    #[test]
+    fn test_tally_history() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voters: Vec<Pubkey> = (0..5).map(|_| Pubkey::new_unique()).collect();
+
+        let config = VoteConfig {
+            track_history: true,
+            history_bucket_slots: 10,
+            results_visibility: Visibility::CreatorOnly,
+            ..VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string(), "Option 2".to_string()]).unwrap()
+        };
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+        for voter in &voters {
+            harness.allow(vote_id, &creator, voter).unwrap();
+        }
+
+        // Two ballots share the first window, then every ballot crosses into a later one
+        for (voter, (slot, option_index)) in voters.iter().zip([(3, 0), (7, 1), (15, 0), (27, 0), (41, 1)]) {
+            set_slot(slot);
+            harness.cast(vote_id, voter, option_index).unwrap();
+        }
+
+        let history = harness.voting.get_history_as(&creator, vote_id).unwrap();
+        let buckets: Vec<(u64, Vec<u32>)> = history.into_iter().map(|bucket| (bucket.upto_slot, bucket.per_option_counts[0].clone())).collect();
+        assert_eq!(buckets, vec![(9, vec![1, 1]), (19, vec![2, 1]), (29, vec![3, 1]), (49, vec![3, 2])]);
+
+        // The history is as hidden as the results
+        assert_eq!(harness.voting.get_history_as(&voters[0], vote_id), Err(ProgramError::InvalidArgument));
+    }
+
+    #[test]
+    fn test_write_ins() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voters = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+
+        let config = VoteConfig { allow_write_ins: true, ..VoteConfig::from_labels("Ideas".to_string(), vec!["Hackathon".to_string()]).unwrap() };
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+        for voter in &voters {
+            harness.allow(vote_id, &creator, voter).unwrap();
+        }
+
+        // A new label becomes an option of the writer, repeated labels join the existing option
+        let voter_infos: Vec<AccountInfo> = voters.iter().map(|voter| harness.account_for(voter)).collect();
+        assert_eq!(harness.voting.vote_write_in(vote_id, std::slice::from_ref(&voter_infos[0]), " Game night "), Ok(OptionIndex(1)));
+        assert_eq!(harness.voting.vote_write_in(vote_id, std::slice::from_ref(&voter_infos[1]), "Game night"), Ok(OptionIndex(1)));
+        assert_eq!(harness.voting.vote_write_in(vote_id, std::slice::from_ref(&voter_infos[2]), "Hackathon"), Ok(OptionIndex(0)));
+
+        let options = harness.voting.get_options(vote_id).unwrap().clone();
+        assert_eq!(options.len(), 2);
+        assert_eq!(options[1], VoteOption { proposed_by: Some(voters[0]), ..VoteOption::new("Game night".to_string(), 1).unwrap() });
+
+        let results = harness.voting.get_full_results_as(&creator, vote_id).unwrap();
+        let votes: Vec<u32> = results.questions[0].options.iter().map(|option| option.votes).collect();
+        assert_eq!(votes, vec![1, 2]);
+
+        // A write-in without a vote left adds nothing, neither do empty or overlong labels
+        assert_eq!(harness.voting.vote_write_in(vote_id, std::slice::from_ref(&voter_infos[0]), "Picnic"), Err(VoteError::InsufficientVotes.into()));
+        assert_eq!(harness.voting.vote_write_in(vote_id, std::slice::from_ref(&voter_infos[0]), "  "), Err(ProgramError::InvalidArgument));
+        assert_eq!(harness.voting.vote_write_in(vote_id, std::slice::from_ref(&voter_infos[0]), &"x".repeat(MAX_WRITE_IN_LEN + 1)), Err(ProgramError::InvalidArgument));
+        assert_eq!(harness.voting.get_options(vote_id).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_vote_by_name_normalized() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voters = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+
+        // "Café" with a composed "é" and the Cyrillic "Голосование", padded with whitespace
+        let config = VoteConfig {
+            allow_write_ins: true,
+            ..VoteConfig::from_labels("Venue".to_string(), vec!["Caf\u{e9}".to_string(), " Голосование ".to_string()]).unwrap()
+        };
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+        for voter in &voters {
+            harness.allow(vote_id, &creator, voter).unwrap();
+        }
+        assert_eq!(harness.voting.get_options(vote_id).unwrap()[1].label, "Голосование");
+
+        // The decomposed "e" followed by a combining acute accent matches the stored option, which keeps its text
+        assert_eq!(harness.voting.vote_by_name_as(&voters[0], vote_id, 0, "Cafe\u{301}"), Ok(OptionIndex(0)));
+        assert_eq!(harness.voting.vote_by_name_as(&voters[1], vote_id, 0, "Голосование\n"), Ok(OptionIndex(1)));
+        assert_eq!(harness.voting.get_options(vote_id).unwrap()[0].label, "Caf\u{e9}");
+        assert_eq!(harness.voting.vote_by_name_as(&voters[2], vote_id, 0, "Cafe"), Err(ProgramError::InvalidArgument));
+
+        // Write-ins join the option they normalize to
+        assert_eq!(harness.voting.vote_write_in_as(&voters[2], vote_id, " Cafe\u{301}"), Ok(OptionIndex(0)));
+        assert_eq!(harness.voting.get_options(vote_id).unwrap().len(), 2);
+
+        let results = harness.voting.get_full_results_as(&creator, vote_id).unwrap();
+        assert_eq!(results.questions[0].options[1].label, "Голосование");
+        let votes: Vec<u32> = results.questions[0].options.iter().map(|option| option.votes).collect();
+        assert_eq!(votes, vec![2, 1]);
+
+        // Labels differing only in their normalization form are the same label
+        let options = vec![VoteOption::new("Caf\u{e9}".to_string(), 0).unwrap(), VoteOption::new("Cafe\u{301}".to_string(), 1).unwrap()];
+        let config = VoteConfig::with_questions("Twice".to_string(), vec![QuestionConfig { title: "Venue".try_into().unwrap(), options }]).unwrap();
+        assert!(harness.create_vote(config, &creator).is_err());
+    }
+
+    #[test]
+    fn test_write_ins_not_allowed() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voter = Pubkey::new_unique();
+
+        let vote_id = harness.create_vote(VoteConfig::from_labels("Ideas".to_string(), vec!["Hackathon".to_string()]).unwrap(), &creator).unwrap();
+        harness.allow(vote_id, &creator, &voter).unwrap();
+
+        assert_eq!(harness.voting.vote_write_in_as(&voter, vote_id, "Game night"), Err(ProgramError::InvalidArgument));
+        assert_eq!(harness.voting.get_options(vote_id).unwrap().len(), 1);
+        assert_eq!(harness.voting.votes[&vote_id].allowed_voters[&voter].votes_left, vec![1]);
+    }
+
+    #[test]
+    fn test_option_validation() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+
+        let duplicate_label = vec![VoteOption::new("Yes".to_string(), 0).unwrap(), VoteOption::new("Yes".to_string(), 1).unwrap()];
+        let duplicate_order = vec![VoteOption::new("Yes".to_string(), 3).unwrap(), VoteOption::new("No".to_string(), 3).unwrap()];
+        for options in [duplicate_label, duplicate_order] {
+            let creator_info = harness.account_for(&creator);
+            let result = harness.voting.create_vote_with_config(VoteConfig::new("Test Vote".to_string(), options).unwrap(), &[creator_info]);
+            assert_eq!(result, Err(ProgramError::InvalidArgument));
+        }
+
+        // Descriptions over the limit cannot be built in the first place
+        let long_description = "x".repeat(MAX_DESCRIPTION_LEN + 1);
+        assert_eq!(Description::try_from(long_description), Err(StringTooLong { field: "string", len: MAX_DESCRIPTION_LEN + 1, max: MAX_DESCRIPTION_LEN }));
+
+        // Gaps in the display order are fine, as is a description of the maximum length
+        let options = vec![
+            VoteOption { description: Some(Description::try_from("x".repeat(MAX_DESCRIPTION_LEN)).unwrap()), ..VoteOption::new("Yes".to_string(), 5).unwrap() },
+            VoteOption::new("No".to_string(), 100).unwrap(),
+        ];
+        let creator_info = harness.account_for(&creator);
+        assert!(harness.voting.create_vote_with_config(VoteConfig::new("Test Vote".to_string(), options).unwrap(), &[creator_info]).is_ok());
+    }
+
+    #[test]
+    fn test_string_limits() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+
+        // Constructors name the field over its limit
+        let long_label = "x".repeat(MAX_LABEL_LEN + 1);
+        assert_eq!(
+            VoteConfig::from_labels("Test Vote".to_string(), vec!["Yes".to_string(), long_label]),
+            Err(StringTooLong { field: "label", len: MAX_LABEL_LEN + 1, max: MAX_LABEL_LEN })
+        );
+        assert_eq!(VoteConfig::from_labels("x".repeat(MAX_TITLE_LEN + 1), vec![]).unwrap_err().field, "title");
+
+        // An instruction or account carrying an over-long title fails to decode, whatever wrote it
+        let config = VoteConfig::from_labels("Test Vote".to_string(), vec!["Yes".to_string()]).unwrap();
+        let mut encoded = borsh::to_vec(&"x".repeat(MAX_TITLE_LEN + 1)).unwrap();
+        encoded.extend_from_slice(&borsh::to_vec(&config).unwrap()[4 + "Test Vote".len()..]);
+        assert!(VoteConfig::try_from_slice(&encoded).is_err());
+
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+        let mut encoded = borsh::to_vec(&harness.voting.votes[&vote_id]).unwrap();
+        let label_at = encoded.windows(3).position(|window| window == b"Yes").unwrap() - 4;
+        encoded[label_at..label_at + 4].copy_from_slice(&(MAX_LABEL_LEN as u32 + 1).to_le_bytes());
+        assert!(Vote::try_from_slice(&encoded).is_err());
+
+        // Longer titles and reasons coming from callers are rejected with a log naming the field
+        take_logs();
+        let creator_info = harness.account_for(&creator);
+        assert_eq!(harness.voting.clone_vote(vote_id, "x".repeat(MAX_TITLE_LEN + 1), false, &[creator_info]), Err(ProgramError::InvalidArgument));
+        assert_eq!(take_logs(), vec![format!("title is {} bytes long, the limit is {}", MAX_TITLE_LEN + 1, MAX_TITLE_LEN)]);
+        assert_eq!(harness.voting.cancel_vote_as(&creator, vote_id, "x".repeat(MAX_REASON_LEN + 1)), Err(ProgramError::InvalidArgument));
+        assert_eq!(take_logs(), vec![format!("reason is {} bytes long, the limit is {}", MAX_REASON_LEN + 1, MAX_REASON_LEN)]);
+    }
+
+    #[test]
+    fn test_vote_edits() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voter = Pubkey::new_unique();
+        set_clock(1_000);
+
+        let vote_id = harness.create_vote(VoteConfig::from_labels("Budegt 2025".to_string(), vec!["Yes".to_string(), "No".to_string()]).unwrap(), &creator).unwrap();
+        harness.allow(vote_id, &creator, &voter).unwrap();
+
+        // Before any ballot, by the creator only
+        assert_eq!(harness.voting.update_title_as(&voter, vote_id, "Budget 2025".to_string()), Err(ProgramError::InvalidArgument));
+        assert!(harness.voting.update_title_as(&creator, vote_id, "Budget 2025".to_string()).is_ok());
+        assert_eq!(harness.voting.get_vote_summary(vote_id).unwrap().title, "Budget 2025");
+
+        // After a ballot the edits are still allowed, and recorded all the same
+        harness.cast(vote_id, &voter, 0).unwrap();
+        set_clock(2_000);
+        assert!(harness.voting.update_description_as(&creator, vote_id, Some("Spending plan for next year".to_string())).is_ok());
+        set_clock(3_000);
+        assert!(harness.voting.update_description_as(&creator, vote_id, None).is_ok());
+
+        let summary = harness.voting.get_vote_summary(vote_id).unwrap();
+        assert_eq!(summary.description, None);
+        assert_eq!(summary.edit_count, 3);
+        assert_eq!(
+            harness.voting.get_edits(vote_id).unwrap(),
+            vec![
+                EditRecord { field: EditedField::Title, old_hash: hash(b"Budegt 2025"), at: 1_000, by: creator },
+                EditRecord { field: EditedField::Description, old_hash: hash(b""), at: 2_000, by: creator },
+                EditRecord { field: EditedField::Description, old_hash: hash(b"Spending plan for next year"), at: 3_000, by: creator },
+            ]
+        );
+        assert_eq!(harness.voting.get_changes_since(vote_id, 0).unwrap().entries.last().unwrap().kind, ChangeKind::Edited);
+
+        // New values have the limits of creation
+        take_logs();
+        assert_eq!(harness.voting.update_title_as(&creator, vote_id, "x".repeat(MAX_TITLE_LEN + 1)), Err(ProgramError::InvalidArgument));
+        assert_eq!(take_logs(), vec![format!("title is {} bytes long, the limit is {}", MAX_TITLE_LEN + 1, MAX_TITLE_LEN)]);
+        assert_eq!(harness.voting.update_description_as(&creator, vote_id, Some("x".repeat(MAX_DESCRIPTION_LEN + 1))), Err(ProgramError::InvalidArgument));
+        assert_eq!(harness.voting.get_vote_summary(vote_id).unwrap().edit_count, 3);
+
+        // The audit trail is bounded and fits the state account
+        for index in 3..MAX_EDITS {
+            assert!(harness.voting.update_title_as(&creator, vote_id, format!("Budget {}", index)).is_ok());
+        }
+        assert_eq!(harness.voting.update_title_as(&creator, vote_id, "Budget".to_string()), Err(ProgramError::InvalidArgument));
+        let vote = &harness.voting.votes[&vote_id];
+        let config = vote.to_config(vote.title.to_string()).unwrap();
+        assert!(borsh::to_vec(vote).unwrap().len() <= Vote::required_space(&config, 1));
+    }
+
+    #[test]
+    fn test_registry_stats_lifecycle() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voter1 = Pubkey::new_unique();
+        let voter2 = Pubkey::new_unique();
+        let delegate = Pubkey::new_unique();
+        set_clock(1_000);
+
+        let expect = |total_votes_created, currently_open, total_ballots_cast, total_voters_registered| RegistryStats {
+            total_votes_created,
+            currently_open,
+            total_ballots_cast,
+            total_voters_registered,
+        };
+        assert_eq!(harness.voting.get_registry_stats(), RegistryStats::default());
+
+        let config = VoteConfig { delegation_policy: DelegationPolicy::AnyRecipient, ..VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string(), "Option 2".to_string()]).unwrap() };
+        let closed_id = harness.create_vote(config.clone(), &creator).unwrap();
+        let cancelled_id = harness.create_vote(config, &creator).unwrap();
+        assert_eq!(harness.voting.get_registry_stats(), expect(2, 2, 0, 0));
+
+        harness.allow(closed_id, &creator, &voter1).unwrap();
+        harness.allow(closed_id, &creator, &voter2).unwrap();
+        harness.allow(cancelled_id, &creator, &voter1).unwrap();
+        // Adding a voter again only resets their entry
+        harness.allow(closed_id, &creator, &voter2).unwrap();
+        assert_eq!(harness.voting.get_registry_stats(), expect(2, 2, 0, 3));
+
+        // The delegate gets a new entry
+        harness.delegate(closed_id, &voter2, &delegate).unwrap();
+        assert_eq!(harness.voting.get_registry_stats(), expect(2, 2, 0, 4));
+
+        harness.cast(closed_id, &voter1, 0).unwrap();
+        harness.cast(closed_id, &delegate, 1).unwrap();
+        harness.cast(cancelled_id, &voter1, 1).unwrap();
+        // Rejected ballots are not counted
+        assert!(harness.cast(closed_id, &voter1, 0).is_err());
+        assert_eq!(harness.voting.get_registry_stats(), expect(2, 2, 3, 4));
+
+        // Removed voters stay counted as registered
+        harness.voting.remove_allowed_voter_as(&creator, closed_id, &voter2).unwrap();
+        assert_eq!(harness.voting.get_registry_stats(), expect(2, 2, 3, 4));
+
+        harness.close(closed_id, &creator).unwrap();
+        assert_eq!(harness.voting.get_registry_stats(), expect(2, 1, 3, 4));
+
+        // Closing again changes nothing, and a closed vote cannot be cancelled
+        assert!(harness.close(closed_id, &creator).is_ok());
+        assert!(harness.voting.cancel_vote_as(&creator, closed_id, "Again".to_string()).is_err());
+        assert_eq!(harness.voting.get_registry_stats(), expect(2, 1, 3, 4));
+
+        harness.voting.cancel_vote_as(&creator, cancelled_id, "Withdrawn".to_string()).unwrap();
+        assert!(harness.close(cancelled_id, &creator).is_err());
+        assert_eq!(harness.voting.get_registry_stats(), expect(2, 0, 3, 4));
+    }
+
+    #[test]
+    fn test_voter_group_snapshot() {
+        let mut harness = TestHarness::new();
+        let owner = Pubkey::new_unique();
+        let stranger = Pubkey::new_unique();
+        let members = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+
+        let group_id = harness.voting.create_group_as(&owner, vec![(members[0], 1), (members[1], 2)]).unwrap();
+
+        let config = VoteConfig {
+            allowlist_group: Some(group_id),
+            ..VoteConfig::from_labels("Weekly Vote".to_string(), vec!["Option 1".to_string()]).unwrap()
+        };
+        let first_id = harness.create_vote(config.clone(), &owner).unwrap();
+        let second_id = harness.create_vote(config.clone(), &owner).unwrap();
+        assert_eq!(harness.voting.get_registry_stats().total_voters_registered, 4);
+
+        // Only the owner edits the group
+        assert!(harness.voting.add_group_member_as(&stranger, group_id, stranger, 1).is_err());
+        assert!(harness.voting.remove_group_member_as(&stranger, group_id, &members[0]).is_err());
+
+        assert!(harness.voting.add_group_member_as(&owner, group_id, members[2], 3).is_ok());
+        assert!(harness.voting.remove_group_member_as(&owner, group_id, &members[0]).is_ok());
+        assert!(harness.voting.remove_group_member_as(&owner, group_id, &members[0]).is_err());
+        assert_eq!(harness.voting.get_group(group_id).unwrap().members, vec![(members[1], 2), (members[2], 3)]);
+
+        // Existing votes keep the group as it was at their creation
+        for vote_id in [first_id, second_id] {
+            let vote = &harness.voting.votes[&vote_id];
+            assert_eq!(vote.allowed_voters.len(), 2);
+            assert_eq!(vote.allowed_voters[&members[0]].votes_left, vec![1]);
+            assert_eq!(vote.allowed_voters[&members[1]].votes_left, vec![2]);
+        }
+
+        let third_id = harness.create_vote(config, &owner).unwrap();
+        let vote = &harness.voting.votes[&third_id];
+        assert_eq!(vote.allowed_voters.len(), 2);
+        assert!(!vote.is_voter_allowed(&members[0]));
+        assert_eq!(vote.allowed_voters[&members[2]].votes_left, vec![3]);
+
+        assert!(harness.cast(third_id, &members[2], 0).is_ok());
+        assert!(harness.cast(third_id, &members[0], 0).is_err());
+    }
+
+    #[test]
+    fn test_voter_group_invalid() {
+        let mut harness = TestHarness::new();
+        let owner = Pubkey::new_unique();
+        let member = Pubkey::new_unique();
+
+        assert!(harness.voting.create_group_as(&owner, vec![(member, 1), (member, 2)]).is_err());
+
+        let config = VoteConfig {
+            allowlist_group: Some(7),
+            ..VoteConfig::from_labels("Weekly Vote".to_string(), vec!["Option 1".to_string()]).unwrap()
+        };
+        assert_eq!(harness.create_vote(config, &owner), Err(ProgramError::InvalidArgument));
+        assert!(harness.voting.votes.is_empty());
+    }
+
+    #[test]
+    fn test_vote_templates() {
+        let mut harness = TestHarness::new();
+        let admin = *harness.voting.admin();
+        let creators = [Pubkey::new_unique(), Pubkey::new_unique()];
+
+        let config = VoteConfig {
+            quorum: Some(2),
+            min_duration: Some(600),
+            results_visibility: Visibility::AllowlistOnly,
+            ..VoteConfig::from_labels("Board motion".to_string(), vec!["Approve".to_string(), "Reject".to_string()]).unwrap()
+        };
+
+        // Only the admin publishes, and settings of a single vote stay out of templates
+        let stranger_info = harness.account_for(&creators[0]);
+        assert_eq!(harness.voting.publish_template("Motion".to_string(), config.clone(), &[stranger_info]), Err(ProgramError::InvalidArgument));
+        let admin_info = harness.account_for(&admin);
+        assert!(harness.voting.publish_template("Motion".to_string(), VoteConfig { deadline: Some(5_000), ..config.clone() }, std::slice::from_ref(&admin_info)).is_err());
+        let template_id = harness.voting.publish_template("Motion".to_string(), config.clone(), std::slice::from_ref(&admin_info)).unwrap();
+
+        // Anyone instantiates it under their own title
+        let mut vote_ids = Vec::new();
+        for (creator, title) in creators.iter().zip(["Motion 1", "Motion 2"]) {
+            let creator_info = harness.account_for(creator);
+            vote_ids.push(harness.voting.create_vote_from_template(template_id, title.to_string(), &[creator_info]).unwrap());
+        }
+        for (vote_id, (creator, title)) in vote_ids.iter().zip(creators.iter().zip(["Motion 1", "Motion 2"])) {
+            let vote = &harness.voting.votes[vote_id];
+            assert_eq!((vote.title.as_str(), vote.creator), (title, *creator));
+            assert_eq!((vote.quorum, vote.results_visibility), (Some(2), Visibility::AllowlistOnly));
+            assert_eq!(vote.questions[0].options, config.questions[0].options);
+        }
+
+        // A retired template makes no more votes, the existing ones stay
+        let creator_info = harness.account_for(&creators[0]);
+        assert!(harness.voting.retire_template(template_id, std::slice::from_ref(&creator_info)).is_err());
+        assert!(harness.voting.retire_template(template_id, &[admin_info]).is_ok());
+        let template = harness.voting.get_template(template_id).unwrap();
+        assert_eq!((template.name.as_str(), template.retired), ("Motion", true));
+        assert_eq!(harness.voting.create_vote_from_template(template_id, "Motion 3".to_string(), std::slice::from_ref(&creator_info)), Err(ProgramError::InvalidArgument));
+        assert_eq!(harness.voting.create_vote_from_template(template_id + 1, "Motion 3".to_string(), &[creator_info]), Err(ProgramError::InvalidArgument));
+        assert_eq!(harness.voting.votes.len(), 2);
+        assert_eq!(harness.voting.check_vote(vote_ids[0]), Ok(()));
+    }
+
+    #[test]
+    fn test_invalidate_voter_ballots() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let sybil = Pubkey::new_unique();
+        let delegate = Pubkey::new_unique();
+        let honest = Pubkey::new_unique();
+        let other = Pubkey::new_unique();
+
+        let group_id = harness.voting.create_group_as(&creator, vec![(sybil, 3), (delegate, 1), (honest, 1), (other, 1)]).unwrap();
+        let config = VoteConfig {
+            allowlist_group: Some(group_id),
+            ..VoteConfig::from_labels("Weekly Vote".to_string(), vec!["Option 1".to_string(), "Option 2".to_string()]).unwrap()
+        };
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+
+        // Two ballots, one incoming and one outgoing delegation
+        assert!(harness.delegate(vote_id, &honest, &sybil).is_ok());
+        set_slot(1);
+        assert!(harness.cast(vote_id, &sybil, 0).is_ok());
+        set_slot(2);
+        assert!(harness.cast(vote_id, &sybil, 0).is_ok());
+        assert!(harness.delegate(vote_id, &sybil, &delegate).is_ok());
+        assert!(harness.cast(vote_id, &delegate, 0).is_ok());
+        assert!(harness.cast(vote_id, &other, 1).is_ok());
+        assert_eq!(harness.voting.votes[&vote_id].allowed_voters[&sybil].votes_left, vec![1]);
+        assert_eq!(harness.voting.votes[&vote_id].allowed_voters[&delegate].votes_left, vec![1]);
+
+        // Only the creator can strike ballots
+        assert!(harness.voting.invalidate_voter_ballots_as(&other, vote_id, &sybil).is_err());
+
+        crate::test_utils::take_events();
+        assert!(harness.voting.invalidate_voter_ballots_as(&creator, vote_id, &sybil).is_ok());
+        assert_eq!(
+            crate::test_utils::take_events(),
+            vec![VoteEvent::VoterBallotsInvalidated { vote_id, voter: sybil, ballots_removed: 2 }]
+        );
+
+        // The ballot the delegate cast stands, the unspent delegated vote is gone
+        let results = harness.voting.get_full_results_as(&creator, vote_id).unwrap();
+        assert_eq!(results.questions[0].options[0].votes, 1);
+        assert_eq!(results.questions[0].options[0].weighted_votes, FULL_WEIGHT_BPS);
+        assert_eq!(results.questions[0].options[1].votes, 1);
+
+        let vote = &harness.voting.votes[&vote_id];
+        assert!(vote.allowed_voters[&sybil].banned);
+        assert_eq!(vote.allowed_voters[&sybil].votes_left, vec![0]);
+        assert_eq!(vote.allowed_voters[&delegate].votes_left, vec![0]);
+        assert!(!vote.is_voter_allowed(&sybil));
+
+        // The ban holds
+        assert_eq!(harness.cast(vote_id, &sybil, 1), Err(VoteError::VoterNotAllowed.into()));
+        assert_eq!(harness.allow(vote_id, &creator, &sybil), Err(VoteError::VoterBanned.into()));
+        assert_eq!(harness.voting.remove_allowed_voter_as(&creator, vote_id, &sybil), Err(VoteError::VoterBanned.into()));
+        assert_eq!(harness.voting.invalidate_voter_ballots_as(&creator, vote_id, &sybil), Err(VoteError::VoterBanned.into()));
+        assert!(!harness.voting.get_allowed_voters_as(&creator, vote_id).unwrap().contains(&sybil));
+
+        // Closed votes keep their ballots
+        assert!(harness.close(vote_id, &creator).is_ok());
+        assert!(harness.voting.invalidate_voter_ballots_as(&creator, vote_id, &other).is_err());
+    }
+
+    #[test]
+    fn test_milestones() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voters: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+
+        set_clock(0);
+        let config = VoteConfig {
+            quorum: Some(2),
+            deadline: Some(10_000),
+            ..VoteConfig::from_labels("Milestone Vote".to_string(), vec!["Yes".to_string(), "No".to_string()]).unwrap()
+        };
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+        for voter in &voters {
+            assert!(harness.allow(vote_id, &creator, voter).is_ok());
+        }
+        assert_eq!(harness.voting.get_milestones(vote_id), Ok(Milestones::default()));
+
+        // Only the milestones count here, the ballots and additions log their own events
+        let milestone_events = || crate::test_utils::take_events().into_iter().filter(|event| matches!(event, VoteEvent::MilestoneReached { .. })).collect::<Vec<_>>();
+
+        // The second ballot meets the quorum, the third makes a majority of the allowlist
+        crate::test_utils::take_events();
+        assert!(harness.cast(vote_id, &voters[0], 0).is_ok());
+        assert_eq!(milestone_events(), vec![]);
+        assert!(harness.cast(vote_id, &voters[1], 1).is_ok());
+        assert_eq!(milestone_events(), vec![VoteEvent::MilestoneReached { vote_id, milestone: Milestones::QUORUM_REACHED }]);
+        assert!(harness.cast(vote_id, &voters[2], 0).is_ok());
+        assert_eq!(milestone_events(), vec![VoteEvent::MilestoneReached { vote_id, milestone: Milestones::MAJORITY_TURNOUT }]);
+
+        // Raised flags are not raised again, the deadline one waits for a change close to the deadline
+        assert!(harness.cast(vote_id, &voters[3], 0).is_ok());
+        assert_eq!(milestone_events(), vec![]);
+        set_clock(10_000 - DEADLINE_SOON_WINDOW);
+        assert!(harness.voting.remove_allowed_voter_as(&creator, vote_id, &Pubkey::new_unique()).is_err());
+        assert_eq!(milestone_events(), vec![]);
+        assert!(harness.allow(vote_id, &creator, &Pubkey::new_unique()).is_ok());
+        assert_eq!(milestone_events(), vec![VoteEvent::MilestoneReached { vote_id, milestone: Milestones::DEADLINE_SOON }]);
+
+        let milestones = harness.voting.get_milestones(vote_id).unwrap();
+        assert!(Milestones::ALL.iter().all(|milestone| milestones.contains(*milestone)));
+        assert_eq!(milestones.bits(), 0b111);
+
+        // The flags are part of the state
+        let decoded = Voting::try_from_slice(&borsh::to_vec(&harness.voting).unwrap()).unwrap();
+        assert_eq!(decoded.get_milestones(vote_id), Ok(milestones));
+    }
+
+    #[test]
+    fn test_delegation_expiry() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let early = Pubkey::new_unique();
+        let late = Pubkey::new_unique();
+        let colleague = Pubkey::new_unique();
+
+        set_clock(1_000);
+        let config = VoteConfig { delegation_policy: DelegationPolicy::AnyRecipient, ..VoteConfig::from_labels("Weekly Vote".to_string(), vec!["Option 1".to_string()]).unwrap() };
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+        harness.allow(vote_id, &creator, &early).unwrap();
+        harness.allow(vote_id, &creator, &late).unwrap();
+
+        // Expiry must lie in the future
+        assert!(harness.voting.delegate_vote_as(&early, vote_id, &colleague, Some(1_000)).is_err());
+
+        assert!(harness.voting.delegate_vote_as(&early, vote_id, &colleague, Some(2_000)).is_ok());
+        assert!(harness.voting.delegate_vote_as(&late, vote_id, &colleague, Some(3_000)).is_ok());
+        assert_eq!(harness.voting.votes[&vote_id].allowed_voters[&colleague].votes_left, vec![2]);
+
+        // Spending before the expiry works
+        set_clock(1_999);
+        assert!(harness.cast(vote_id, &colleague, 0).is_ok());
+
+        // Once both expired, the remaining delegated vote cannot be spent
+        set_clock(3_000);
+        assert_eq!(harness.cast(vote_id, &colleague, 0), Err(VoteError::DelegationExpired.into()));
+
+        // The spent vote is gone, the unspent one returns
+        assert!(harness.voting.reclaim_expired_delegations(vote_id, &early).is_ok());
+        assert!(harness.voting.reclaim_expired_delegations(vote_id, &late).is_ok());
+        let vote = &harness.voting.votes[&vote_id];
+        assert_eq!(vote.allowed_voters[&colleague].votes_left, vec![0]);
+        assert_eq!(vote.allowed_voters[&early].votes_left, vec![0]);
+        assert_eq!(vote.allowed_voters[&late].votes_left, vec![1]);
+        assert_eq!(vote.allowed_voters[&late].delegate, None);
+
+        assert!(harness.cast(vote_id, &late, 0).is_ok());
+    }
+
+    #[test]
+    fn test_delegation_expiry_lazy_reclaim() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let delegator = Pubkey::new_unique();
+        let colleague = Pubkey::new_unique();
+
+        set_clock(1_000);
+        let config = VoteConfig { delegation_policy: DelegationPolicy::AnyRecipient, ..VoteConfig::from_labels("Weekly Vote".to_string(), vec!["Option 1".to_string()]).unwrap() };
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+        harness.allow(vote_id, &creator, &delegator).unwrap();
+
+        assert!(harness.voting.delegate_vote_as(&delegator, vote_id, &colleague, Some(2_000)).is_ok());
+
+        // The delegated vote cannot be passed on after the expiry either
+        set_clock(2_000);
+        assert!(harness.delegate(vote_id, &colleague, &creator).is_err());
+
+        // The delegator's next ballot reclaims the vote first
+        assert!(harness.cast(vote_id, &delegator, 0).is_ok());
+        assert_eq!(harness.voting.votes[&vote_id].allowed_voters[&colleague].votes_left, vec![0]);
+        assert_eq!(harness.voting.get_full_results_as(&creator, vote_id).unwrap().questions[0].total_votes, 1);
+    }
+
+    #[test]
+    fn test_per_voter_window() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let early = Pubkey::new_unique();
+        let late = Pubkey::new_unique();
+
+        set_clock(1_000);
+        let config = VoteConfig {
+            per_voter_window: Some(48 * 3_600),
+            ..VoteConfig::from_labels("Weekly Vote".to_string(), vec!["Option 1".to_string()]).unwrap()
+        };
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+        harness.allow(vote_id, &creator, &early).unwrap();
+
+        set_clock(100_000);
+        harness.allow(vote_id, &creator, &late).unwrap();
+
+        assert_eq!(harness.voting.get_voter_status_as(&early, vote_id, &early).unwrap().deadline, Some(1_000 + 48 * 3_600));
+        assert_eq!(harness.voting.get_voter_status_as(&late, vote_id, &late).unwrap().deadline, Some(100_000 + 48 * 3_600));
+
+        // The early voter's window closed while the vote and the late voter's window stay open
+        set_clock(1_000 + 48 * 3_600 + 1);
+        assert_eq!(harness.cast(vote_id, &early, 0), Err(VoteError::VoterWindowClosed.into()));
+        assert!(harness.cast(vote_id, &late, 0).is_ok());
+        assert_eq!(harness.voting.get_vote_summary(vote_id).unwrap().status, VoteStatus::Open);
+
+        // Votes without a window report no personal deadline
+        let other_id = harness.create_vote(VoteConfig::from_labels("Weekly Vote".to_string(), vec!["Option 1".to_string()]).unwrap(), &creator).unwrap();
+        harness.allow(other_id, &creator, &early).unwrap();
+        let status = harness.voting.get_voter_status_as(&creator, other_id, &early).unwrap();
+        assert_eq!(status, VoterStatus { votes_left: vec![1], delegate: None, banned: false, deadline: None, weight: 1, received_weight: 0, received_delegations: 0, via_delegation_only: false, valid_until: None });
+    }
+
+    #[test]
+    fn test_per_voter_window_edge() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voter = Pubkey::new_unique();
+
+        set_clock(5_000);
+        let config = VoteConfig {
+            per_voter_window: Some(60),
+            ..VoteConfig::from_labels("Weekly Vote".to_string(), vec!["Option 1".to_string()]).unwrap()
+        };
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+        harness.allow(vote_id, &creator, &voter).unwrap();
+
+        // The deadline itself is still inside the window
+        set_clock(5_060);
+        assert!(harness.cast(vote_id, &voter, 0).is_ok());
+    }
+
+    #[test]
+    fn test_expiring_voters() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let contractors = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let employee = Pubkey::new_unique();
+
+        set_clock(1_000);
+        let vote_id = harness.create_vote(VoteConfig::from_labels("Roadmap".to_string(), vec!["Option 1".to_string(), "Option 2".to_string()]).unwrap(), &creator).unwrap();
+        assert_eq!(harness.voting.add_expiring_voter_as(&creator, vote_id, contractors[0], Some(999)), Err(ProgramError::InvalidArgument));
+        for contractor in &contractors {
+            harness.voting.add_expiring_voter_as(&creator, vote_id, *contractor, Some(2_000)).unwrap();
+        }
+        harness.allow(vote_id, &creator, &employee).unwrap();
+        assert_eq!(harness.voting.get_voter_status_as(&creator, vote_id, &contractors[0]).unwrap().valid_until, Some(2_000));
+
+        // The expiry itself is still inside the term
+        set_clock(2_000);
+        assert!(harness.cast(vote_id, &contractors[0], 0).is_ok());
+
+        set_clock(2_001);
+        assert_eq!(harness.cast(vote_id, &contractors[1], 0), Err(VoteError::VoterExpired.into()));
+        assert_eq!(harness.voting.delegate_vote_as(&contractors[1], vote_id, &employee, None), Err(VoteError::VoterExpired.into()));
+        assert!(harness.cast(vote_id, &employee, 1).is_ok());
+
+        // Anyone prunes the expired entries, the ballot of the pruned contractor stands
+        assert_eq!(harness.voting.prune_expired_voters(vote_id, 1), Ok(1));
+        assert_eq!(harness.voting.prune_expired_voters(vote_id, 10), Ok(1));
+        assert_eq!(harness.voting.prune_expired_voters(vote_id, 10), Ok(0));
+        assert_eq!(harness.voting.get_allowed_voters_as(&creator, vote_id), Ok(vec![employee]));
+        let results = harness.voting.get_full_results_as(&creator, vote_id).unwrap();
+        assert_eq!(results.questions[0].options.iter().map(|option| option.votes).collect::<Vec<_>>(), vec![1, 1]);
+        assert_eq!(harness.voting.check_vote(vote_id), Ok(()));
+    }
+
+    // Closed election with one ballot from a new voter for each entry of `ballots`
+    fn elected_after(config: VoteConfig, ballots: &[u16]) -> Vec<usize> {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+
+        for option_index in ballots {
+            let voter = Pubkey::new_unique();
+            harness.allow(vote_id, &creator, &voter).unwrap();
+            harness.cast(vote_id, &voter, *option_index).unwrap();
+        }
+
+        assert_eq!(harness.voting.get_elected_as(&creator, vote_id), Err(VoteError::VoteStillOpen.into()));
+        harness.close(vote_id, &creator).unwrap();
+        harness.voting.get_elected_as(&creator, vote_id).unwrap().iter().map(|elected| elected.option_index).collect()
+    }
+
+    #[test]
+    fn test_elected_options() {
+        let committee = |seats, per_option_quorum, tie_break| VoteConfig { seats, per_option_quorum, tie_break, ..VoteConfig::from_labels("Committee".to_string(), ["Ada", "Grace", "Edsger", "Barbara"].map(String::from).to_vec()).unwrap() };
+
+        // Three seats, but only two candidates clear the quorum of two ballots
+        assert_eq!(elected_after(committee(3, Some(2), TieBreak::None), &[0, 0, 1, 1, 1, 2, 3]), vec![1, 0]);
+        assert_eq!(elected_after(committee(3, None, TieBreak::None), &[0, 0, 1, 1, 1, 2]), vec![1, 0, 2]);
+
+        // Two candidates tie for the last seat: without a tie-break it stays empty, otherwise the first listed takes it
+        assert_eq!(elected_after(committee(2, None, TieBreak::None), &[3, 3, 3, 1, 1, 2, 2]), vec![3]);
+        assert_eq!(elected_after(committee(2, None, TieBreak::FirstListed), &[3, 3, 3, 1, 1, 2, 2]), vec![3, 1]);
+        assert_eq!(elected_after(committee(3, None, TieBreak::None), &[3, 3, 3, 1, 1, 2, 2]), vec![3, 1, 2]);
+
+        let mut harness = TestHarness::new();
+        assert_eq!(harness.create_vote(committee(0, None, TieBreak::None), &Pubkey::new_unique()), Err(ProgramError::InvalidArgument));
+    }
+
+    #[test]
+    fn test_certify_results() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let [treasurer, secretary, voter] = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+        set_clock(1_000);
+
+        let config = VoteConfig { required_certifications: 2, ..VoteConfig::from_labels("Board".to_string(), vec!["Option 1".to_string(), "Option 2".to_string()]).unwrap() };
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+        harness.voting.add_observer_as(&creator, vote_id, treasurer).unwrap();
+        harness.voting.add_observer_as(&creator, vote_id, secretary).unwrap();
+        harness.allow(vote_id, &creator, &voter).unwrap();
+        harness.cast(vote_id, &voter, 1).unwrap();
+
+        // The results are certified once final
+        assert_eq!(harness.voting.certify_results_as(&creator, vote_id), Err(VoteError::VoteStillOpen.into()));
+        harness.close(vote_id, &creator).unwrap();
+        assert_eq!(harness.voting.certify_results_as(&voter, vote_id), Err(ProgramError::InvalidArgument));
+
+        let results_hash = harness.voting.get_full_results_as(&creator, vote_id).unwrap().hash();
+        crate::test_utils::take_events();
+        harness.voting.certify_results_as(&treasurer, vote_id).unwrap();
+        let certified_at = harness.voting.get_certifications(vote_id).unwrap()[0].certified_at;
+        assert_eq!(crate::test_utils::take_events(), vec![VoteEvent::ResultsCertified { vote_id, certifier: treasurer, results_hash, certified_at }]);
+        assert_eq!(harness.voting.is_certified(vote_id), Ok(false));
+        assert_eq!(harness.voting.certify_results_as(&treasurer, vote_id), Err(ProgramError::InvalidArgument));
+
+        set_clock(2_000);
+        harness.voting.certify_results_as(&secretary, vote_id).unwrap();
+        assert_eq!(harness.voting.is_certified(vote_id), Ok(true));
+        assert_eq!(
+            harness.voting.get_certifications(vote_id),
+            Ok(vec![
+                Certification { certifier: treasurer, results_hash, certified_at: 1_000 },
+                Certification { certifier: secretary, results_hash, certified_at: 2_000 },
+            ])
+        );
+
+        // Certifications of other results do not count
+        harness.voting.votes.get_mut(&vote_id).unwrap().certifications[0].results_hash = Hash::default();
+        assert_eq!(harness.voting.is_certified(vote_id), Ok(false));
+        harness.voting.certify_results_as(&creator, vote_id).unwrap();
+        assert_eq!(harness.voting.is_certified(vote_id), Ok(true));
+
+        // Votes requiring no certification are never certified
+        let config = VoteConfig::from_labels("Lunch".to_string(), vec!["Option 1".to_string(), "Option 2".to_string()]).unwrap();
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+        harness.close(vote_id, &creator).unwrap();
+        harness.voting.certify_results_as(&creator, vote_id).unwrap();
+        assert_eq!(harness.voting.is_certified(vote_id), Ok(false));
+    }
+
+    #[test]
+    fn test_attest_non_participation() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let [abstainer, voter, delegator, delegate, reclaimed, retracted] = [(); 6].map(|_| Pubkey::new_unique());
+        set_clock(1_000);
+
+        let config = VoteConfig::from_labels("Merger".to_string(), vec!["Approve".to_string(), "Reject".to_string()]).unwrap();
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+        for member in [abstainer, voter, delegator, delegate, reclaimed, retracted] {
+            harness.allow(vote_id, &creator, &member).unwrap();
+        }
+        harness.cast(vote_id, &voter, 0).unwrap();
+        harness.delegate(vote_id, &delegator, &delegate).unwrap();
+        harness.cast(vote_id, &delegate, 1).unwrap();
+        harness.voting.delegate_vote_as(&reclaimed, vote_id, &delegate, Some(1_500)).unwrap();
+        harness.cast(vote_id, &retracted, 1).unwrap();
+        harness.voting.retract_ballot_as(&retracted, vote_id, 0).unwrap();
+
+        set_clock(1_500);
+        harness.voting.reclaim_expired_delegations(vote_id, &reclaimed).unwrap();
+        assert_eq!(harness.voting.get_voter_status_as(&reclaimed, vote_id, &reclaimed).map(|status| status.delegate), Ok(None));
+
+        // Only a closed vote is attested
+        assert_eq!(harness.voting.attest_non_participation_as(&abstainer, vote_id), Err(VoteError::VoteStillOpen.into()));
+        harness.close(vote_id, &creator).unwrap();
+
+        set_clock(2_000);
+        let abstainer_info = harness.account_for(&abstainer);
+        harness.voting.attest_non_participation(vote_id, &[abstainer_info]).unwrap();
+        assert_eq!(harness.voting.get_attestations(vote_id), Ok(vec![NonParticipationAttestation { voter: abstainer, at: 2_000 }]));
+        assert_eq!(harness.voting.attest_non_participation_as(&abstainer, vote_id), Err(ProgramError::InvalidArgument));
+
+        // Ballots and delegations count even once retracted or taken back
+        for participant in [voter, delegator, delegate, reclaimed, retracted] {
+            assert_eq!(harness.voting.attest_non_participation_as(&participant, vote_id), Err(VoteError::VoterParticipated.into()));
+        }
+        assert_eq!(harness.voting.attest_non_participation_as(&Pubkey::new_unique(), vote_id), Err(ProgramError::InvalidArgument));
+        assert_eq!(harness.voting.get_attestations(vote_id).unwrap().len(), 1);
 
-            // Increase the number of votes for the selected option
-            let option_key = self.options[option_index].clone();
-            let count = self.votes.entry(option_key).or_insert(0);
-            *count += 1; // Increase the vote count
+        // Scrubbing the voter removes their key from the attestation too
+        harness.voting.scrub_voter_as(&creator, vote_id, &abstainer).unwrap();
+        assert_eq!(harness.voting.get_attestations(vote_id), Ok(vec![NonParticipationAttestation { voter: scrubbed_key(1), at: 2_000 }]));
+    }
 
-            // Decrease the remaining votes
-            voter_info.votes_left -= 1;
+    #[test]
+    fn test_scrub_voter() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let [leaver, voter, delegator] = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
 
-            Ok(())
-        } else {
-            Err(ProgramError::InvalidArgument) // Return error if the voter is not found
+        let config = VoteConfig { per_option_voter_cap: Some(5), ..VoteConfig::from_labels("Budget".to_string(), vec!["Option 1".to_string(), "Option 2".to_string()]).unwrap() };
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+        for key in [leaver, voter, delegator] {
+            harness.allow(vote_id, &creator, &key).unwrap();
         }
+        harness.voting.delegate_vote_as(&delegator, vote_id, &leaver, None).unwrap();
+        harness.cast(vote_id, &leaver, 0).unwrap();
+        set_slot(1);
+        harness.cast(vote_id, &leaver, 1).unwrap();
+        harness.cast(vote_id, &voter, 1).unwrap();
+        assert_eq!(harness.voting.scrub_voter_as(&creator, vote_id, &leaver), Err(VoteError::VoteStillOpen.into()));
+        harness.close(vote_id, &creator).unwrap();
+
+        let results = harness.voting.get_full_results_as(&creator, vote_id).unwrap();
+        let summary = harness.voting.get_summary_as(&creator, vote_id).unwrap();
+        let ballots = harness.voting.get_ballots_as(&creator, vote_id, 0).unwrap();
+
+        // Only the creator or the admin scrubs, and never a creator
+        assert_eq!(harness.voting.scrub_voter_as(&voter, vote_id, &leaver), Err(ProgramError::InvalidArgument));
+        let admin = *harness.voting.admin();
+        assert_eq!(harness.voting.scrub_voter_as(&admin, vote_id, &creator), Err(ProgramError::InvalidArgument));
+        crate::test_utils::take_events();
+        assert_eq!(harness.voting.scrub_voter_as(&creator, vote_id, &leaver), Ok(2));
+        assert_eq!(crate::test_utils::take_events(), vec![VoteEvent::VoterScrubbed { vote_id, ballots_anonymized: 2 }]);
+        assert_eq!(harness.voting.scrub_voter_as(&creator, vote_id, &leaver), Err(ProgramError::InvalidArgument));
+
+        // The aggregates stay, the ballots are still there under another key
+        assert_eq!(harness.voting.get_full_results_as(&creator, vote_id).unwrap(), results);
+        assert_eq!(harness.voting.get_summary_as(&creator, vote_id).unwrap(), summary);
+        let mut anonymized = harness.voting.get_ballots_as(&creator, vote_id, 0).unwrap();
+        anonymized.sort_by_key(|ballot| ballot.option_index);
+        assert_eq!(anonymized.len(), ballots.len());
+        assert!(!harness.voting.has_voted(vote_id, &leaver));
+        assert_eq!(harness.voting.check_vote(vote_id), Ok(()));
+
+        // Nothing in the registry names the voter any more
+        let state = borsh::to_vec(&harness.voting).unwrap();
+        assert!(!state.windows(32).any(|window| window == leaver.as_ref()));
+        assert!(state.windows(32).any(|window| window == voter.as_ref()));
     }
 
-    fn delegate_vote(&mut self, delegate: &Pubkey, delegator: &Pubkey) -> Result<(), ProgramError> {
-        // Check if the delegator is allowed
-        if let Some(voter_info) = self.allowed_voters.get(delegator).cloned() {
-            // Check if the voting is closed
-            if !self.is_vote_open {
-                return Err(ProgramError::InvalidArgument); // Return error if voting is closed
-            }
-
-            if voter_info.votes_left > 0 {
-                // Decrease the number of votes for the delegator
-                let mut updated_voter_info = voter_info;
-                updated_voter_info.votes_left -= 1;
+    #[test]
+    fn test_vote_bundles() {
+        let mut devnet = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voters = [Pubkey::new_unique(), Pubkey::new_unique()];
 
-                // Get or create an entry for the delegate
-                let entry = self.allowed_voters.entry(*delegate).or_insert(VoterInfo {
-                    votes_left: 0,
-                    delegate: None,
-                });
+        let vote_id = devnet.create_vote(VoteConfig::from_labels("Treasury".to_string(), vec!["Option 1".to_string(), "Option 2".to_string()]).unwrap(), &creator).unwrap();
+        for (voter, option) in voters.iter().zip([1, 1]) {
+            devnet.allow(vote_id, &creator, voter).unwrap();
+            devnet.cast(vote_id, voter, option).unwrap();
+        }
 
-                // Increase the number of votes for the delegate
-                entry.votes_left += 1;
+        // Only closed votes are exported
+        assert_eq!(devnet.voting.votes[&vote_id].export_bundle(), Err(ProgramError::InvalidArgument));
+        let creator_info = devnet.account_for(&creator);
+        devnet.voting.close_vote(vote_id, &[creator_info]).unwrap();
+        let bundle = VoteBundle::from_base64(&devnet.voting.votes[&vote_id].export_bundle().unwrap().to_base64()).unwrap();
+        let results_hash = devnet.voting.get_results_hash_as(&creator, vote_id).unwrap();
+        assert_eq!(bundle.results_hash, results_hash);
+
+        // A fresh registry takes the vote under its next ID, closed, only from its admin
+        let mut mainnet = TestHarness::new();
+        mainnet.create_vote(VoteConfig::from_labels("Newer".to_string(), vec!["Option 1".to_string()]).unwrap(), &creator).unwrap();
+        let voter_info = mainnet.account_for(&voters[0]);
+        assert_eq!(mainnet.voting.import_bundle(bundle.clone(), &[voter_info]), Err(ProgramError::InvalidArgument));
+        let admin = *mainnet.voting.admin();
+        let admin_info = mainnet.account_for(&admin);
+        let imported = mainnet.voting.import_bundle(bundle.clone(), std::slice::from_ref(&admin_info)).unwrap();
+        assert_eq!(imported, VoteId(1));
+        assert_eq!(mainnet.voting.get_import_origin(imported), Ok(Some(ImportOrigin { vote_id, results_hash })));
+        assert_eq!(mainnet.voting.check_vote(imported), Ok(()));
+
+        // The results match the source, and give its hash under the source ID
+        let mut results = mainnet.voting.get_full_results_as(&creator, imported).unwrap();
+        assert_eq!(results.questions, devnet.voting.get_full_results_as(&creator, vote_id).unwrap().questions);
+        results.vote_id = vote_id;
+        assert_eq!(results.hash(), results_hash);
+        assert_eq!(mainnet.cast(imported, &voters[0], 0), Err(ProgramError::InvalidArgument));
+
+        // Edited bundles are refused, even with a recomputed hash when the state no longer gives the results
+        let mut tampered = bundle.clone();
+        *tampered.vote_state.last_mut().unwrap() ^= 1;
+        assert_eq!(mainnet.voting.import_bundle(tampered, std::slice::from_ref(&admin_info)), Err(ProgramError::InvalidAccountData));
+        let mut source = devnet.voting.votes[&vote_id].clone();
+        source.questions[0].votes.insert("Option 1".to_string(), 5);
+        let forged = VoteBundle::new(borsh::to_vec(&source).unwrap(), results_hash);
+        assert!(mainnet.voting.import_bundle(forged, std::slice::from_ref(&admin_info)).is_err());
+        assert_eq!(mainnet.voting.votes.len(), 2);
+    }
 
-                // Set the delegate
-                updated_voter_info.delegate = Some(*delegate);
-                self.allowed_voters.insert(*delegator, updated_voter_info); // Update the voter's information
+    #[test]
+    fn test_received_delegations_cap() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let delegate = Pubkey::new_unique();
+        let delegators = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+
+        let mut config = VoteConfig::from_labels("Grants".to_string(), vec!["Option 1".to_string(), "Option 2".to_string()]).unwrap();
+        config.max_received_delegations = Some(2);
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+        harness.allow(vote_id, &creator, &delegate).unwrap();
+        for delegator in &delegators {
+            harness.allow(vote_id, &creator, delegator).unwrap();
+        }
 
-                Ok(())
-            } else {
-                Err(ProgramError::InvalidArgument) // No available votes
-            }
-        } else {
-            Err(ProgramError::InvalidArgument) // Delegator is not allowed
+        // Two delegations fill the cap, the delegate's own vote does not count
+        for delegator in &delegators[..2] {
+            harness.voting.delegate_vote_as(delegator, vote_id, &delegate, None).unwrap();
         }
+        let status = harness.voting.get_voter_status_as(&creator, vote_id, &delegate).unwrap();
+        assert_eq!((status.votes_left, status.received_delegations), (vec![3], 2));
+        assert_eq!(harness.voting.delegate_vote_as(&delegators[2], vote_id, &delegate, None), Err(VoteError::DelegateCapReached.into()));
+
+        // Spending the received votes does not free the capacity, taking them back does
+        assert!(harness.cast(vote_id, &delegate, 0).is_ok());
+        assert_eq!(harness.voting.delegate_vote_as(&delegators[2], vote_id, &delegate, None), Err(VoteError::DelegateCapReached.into()));
+        harness.voting.leave_vote_as(&delegators[0], vote_id, false).unwrap();
+        assert_eq!(harness.voting.get_voter_status_as(&creator, vote_id, &delegate).unwrap().received_delegations, 1);
+        assert!(harness.voting.delegate_vote_as(&delegators[2], vote_id, &delegate, None).is_ok());
+        assert_eq!(harness.voting.check_vote(vote_id), Ok(()));
     }
-}
-
-pub struct Voting {
-    pub votes: HashMap<u32, Vote>, // List of votes
-    current_id: u32,
-}
 
-impl Voting {
+    #[test]
+    fn test_simulated_ballots() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voter = Pubkey::new_unique();
+        let delegate = Pubkey::new_unique();
 
-    pub fn create_vote(&mut self, title: String, options: Vec<String>, is_close_vote_results: bool, accounts: &[AccountInfo]) -> Result<u32, ProgramError> {
-        if accounts.is_empty() {
-            return Err(ProgramError::InvalidArgument); // Return error if no accounts are provided
-        }
+        let vote_id = harness.create_vote(VoteConfig::from_labels("Budget".to_string(), vec!["Option 1".to_string(), "Option 2".to_string()]).unwrap(), &creator).unwrap();
+        harness.allow(vote_id, &creator, &voter).unwrap();
+        harness.allow(vote_id, &creator, &delegate).unwrap();
+
+        // The simulation projects the tallies and leaves everything as it was
+        let before = harness.voting.clone();
+        let simulation = harness.voting.simulate_vote_as(&voter, vote_id, 0, OptionIndex(1)).unwrap();
+        assert_eq!((simulation.predicted_error(), simulation.questions[0].votes.clone()), (None, vec![0, 1]));
+        assert_eq!(harness.voting, before);
+        assert!(harness.cast(vote_id, &voter, 1).is_ok());
+
+        // A second ballot and a delegation of the spent vote fail in the simulation as they do for real
+        let before = harness.voting.clone();
+        let simulation = harness.voting.simulate_vote_as(&voter, vote_id, 0, OptionIndex(0)).unwrap();
+        let delegation = harness.voting.simulate_delegate_as(&voter, vote_id, &delegate, None).unwrap();
+        assert!(simulation.questions.is_empty());
+        assert_eq!(harness.voting, before);
+        assert_eq!(simulation.predicted_error().map(Err), Some(harness.cast(vote_id, &voter, 0)));
+        assert_eq!(delegation.predicted_error().map(Err), Some(harness.voting.delegate_vote_as(&voter, vote_id, &delegate, None)));
+
+        // So does a ballot on a missing option
+        let simulation = harness.voting.simulate_vote_as(&delegate, vote_id, 0, OptionIndex(2)).unwrap();
+        assert_eq!(simulation.predicted_error(), Some(ProgramError::InvalidArgument));
+        assert_eq!(harness.cast(vote_id, &delegate, 2), Err(ProgramError::InvalidArgument));
+    }
 
-        let creator = accounts[0].key;
-        let is_vote_open = true;
+    #[test]
+    fn test_ballot_rate_limit() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let bot = Pubkey::new_unique();
+        let voter = Pubkey::new_unique();
 
-        let vote = Vote {
-            id: self.current_id,
-            title,
-            options,
-            votes: HashMap::new(), // Initialize an empty map for votes
-            creator: *creator,
-            allowed_voters: HashMap::new(), // Initialize an empty map for allowed voters
-            is_close_vote_results,
-            is_vote_open
+        let group_id = harness.voting.create_group_as(&creator, vec![(bot, 3), (voter, 1)]).unwrap();
+        let config = VoteConfig {
+            allowlist_group: Some(group_id),
+            ..VoteConfig::from_labels("Open Poll".to_string(), vec!["Option 1".to_string(), "Option 2".to_string()]).unwrap()
         };
-        self.votes.insert(self.current_id, vote); // Add the vote to the list
-        self.current_id += 1; // Increment the identifier for the next vote
-
-        Ok(self.current_id - 1)
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+
+        // A second ballot in the same slot is rejected, single-vote voters are unaffected
+        set_slot(10);
+        assert!(harness.cast(vote_id, &bot, 0).is_ok());
+        assert_eq!(harness.cast(vote_id, &bot, 0), Err(VoteError::TooSoon.into()));
+        assert!(harness.cast(vote_id, &voter, 0).is_ok());
+        assert_eq!(harness.voting.votes[&vote_id].allowed_voters[&bot].votes_left, vec![2]);
+        assert_eq!(harness.voting.votes[&vote_id].allowed_voters[&voter].last_voted_slot, None);
+
+        set_slot(11);
+        assert!(harness.cast(vote_id, &bot, 1).is_ok());
+        assert_eq!(harness.cast(vote_id, &bot, 1), Err(VoteError::TooSoon.into()));
+
+        // Each question of a multi-question vote takes its first ballot right away
+        let vote_id = harness.create_vote(board_meeting_config(), &creator).unwrap();
+        harness.allow(vote_id, &creator, &voter).unwrap();
+        assert!(harness.voting.vote_on_question_as(&voter, vote_id, 0, OptionIndex(0)).is_ok());
+        assert!(harness.voting.vote_on_question_as(&voter, vote_id, 1, OptionIndex(0)).is_ok());
     }
 
-    pub fn vote(&mut self, vote_id: u32, accounts: &[AccountInfo], option_index: usize) -> Result<(), ProgramError> {
-        // Check if the provided vote ID is valid
-        if !self.votes.contains_key(&vote_id) {
-            return Err(ProgramError::InvalidArgument); // Return error if the ID does not exist
-        }
+    #[test]
+    fn test_ballot_rate_limit_configured() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let bot = Pubkey::new_unique();
 
-        // Get the vote by ID
-        let vote = self.votes.get_mut(&vote_id).unwrap(); // Safely extract the vote as we already checked for existence
+        let group_id = harness.voting.create_group_as(&creator, vec![(bot, 3)]).unwrap();
+        let config = VoteConfig {
+            allowlist_group: Some(group_id),
+            min_slots_between_ballots: Some(5),
+            ..VoteConfig::from_labels("Open Poll".to_string(), vec!["Option 1".to_string()]).unwrap()
+        };
+        let vote_id = harness.create_vote(config, &creator).unwrap();
 
-        if accounts.is_empty() {
-            return Err(ProgramError::InvalidArgument); // Return error if no accounts are provided
-        }
+        set_slot(100);
+        assert!(harness.cast(vote_id, &bot, 0).is_ok());
 
-        let voter = accounts[0].key;
+        // The next ballot is accepted from the fifth slot on
+        set_slot(104);
+        assert_eq!(harness.cast(vote_id, &bot, 0), Err(VoteError::TooSoon.into()));
+        set_slot(105);
+        assert!(harness.cast(vote_id, &bot, 0).is_ok());
 
-        // Call the voting method
-        vote.vote(voter, option_index)
+        // The last vote leaves no later ballot to limit
+        set_slot(110);
+        assert!(harness.cast(vote_id, &bot, 0).is_ok());
+        assert_eq!(harness.voting.votes[&vote_id].allowed_voters[&bot].last_voted_slot, Some(105));
     }
 
-    pub fn close_vote(&mut self, vote_id: u32, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
-        if accounts.is_empty() {
-            return Err(ProgramError::InvalidArgument);
-        }
-
-        let caller = accounts[0].key;
-
-        if let Some(vote) = self.votes.get_mut(&vote_id) {
-            if vote.creator != *caller {
-                return Err(ProgramError::InvalidArgument); // Only the creator can close the vote
-            }
-            vote.is_vote_open = false; // Close the vote
-            Ok(())
-        } else {
-            Err(ProgramError::InvalidArgument) // Vote not found
-        }
+    #[test]
+    fn test_clone_vote() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voter = Pubkey::new_unique();
+        let facilitator = Pubkey::new_unique();
+
+        let config = VoteConfig {
+            results_visibility: Visibility::AllowlistOnly,
+            quorum: Some(2),
+            deadline: Some(1_000),
+            ..VoteConfig::from_labels("Sprint 1 Retro".to_string(), vec!["Keep".to_string(), "Change".to_string()]).unwrap()
+        };
+        set_clock(100);
+        let source_id = harness.create_vote(config, &creator).unwrap();
+        harness.allow(source_id, &creator, &voter).unwrap();
+        harness.cast(source_id, &voter, 1).unwrap();
+        harness.close(source_id, &creator).unwrap();
+        let source_before = harness.voting.votes[&source_id].clone();
+
+        // Any caller can clone a closed vote and becomes its creator
+        let facilitator_info = harness.account_for(&facilitator);
+        let clone_id = harness.voting.clone_vote(source_id, "Sprint 2 Retro".to_string(), false, std::slice::from_ref(&facilitator_info)).unwrap();
+        assert_ne!(clone_id, source_id);
+        assert_eq!(harness.voting.votes[&source_id], source_before);
+
+        let summary = harness.voting.get_vote_summary(clone_id).unwrap();
+        assert_eq!(summary.title, "Sprint 2 Retro");
+        assert_eq!(summary.creator, facilitator);
+        assert_eq!(summary.status, VoteStatus::Open);
+        assert_eq!(summary.results_visibility, Visibility::AllowlistOnly);
+        assert_eq!(summary.quorum, Some(2));
+        assert_eq!(summary.deadline, None);
+        assert_eq!(summary.closure, None);
+
+        // The clone starts without voters and with empty tallies
+        let clone = &harness.voting.votes[&clone_id];
+        assert!(clone.allowed_voters.is_empty());
+        assert_eq!(clone.questions[0].options, source_before.questions[0].options);
+        let results = harness.voting.get_full_results_as(&facilitator, clone_id).unwrap();
+        assert!(results.questions[0].options.iter().all(|option| option.votes == 0));
+        assert_eq!(results.questions[0].total_votes, 0);
+
+        assert!(harness.voting.clone_vote(VoteId(99), "Missing".to_string(), false, &[facilitator_info]).is_err());
     }
 
-    pub fn get_results(&self, vote_id: u32, accounts: &[AccountInfo]) -> Result<HashMap<String, u32>, ProgramError> {
-        if accounts.is_empty() {
-            return Err(ProgramError::InvalidArgument); // Return error if no accounts are provided
+    // Closed vote over four options where the voters cast the given ballots, one voter per ballot
+    fn runoff_source(harness: &mut TestHarness, creator: &Pubkey, ballots: &[u16]) -> VoteId {
+        let labels = ["A", "B", "C", "D"].iter().map(|label| label.to_string()).collect();
+        let vote_id = harness.create_vote(VoteConfig { quorum: Some(2), ..VoteConfig::from_labels("Chair".to_string(), labels).unwrap() }, creator).unwrap();
+        for option_index in ballots {
+            let voter = Pubkey::new_unique();
+            harness.allow(vote_id, creator, &voter).unwrap();
+            harness.cast(vote_id, &voter, *option_index).unwrap();
         }
+        vote_id
+    }
 
-        let caller = accounts[0].key;
+    fn option_labels(harness: &TestHarness, vote_id: VoteId) -> Vec<String> {
+        harness.voting.votes[&vote_id].questions[0].options.iter().map(|option| option.label.to_string()).collect()
+    }
 
-        // Extract the vote by ID
-        let vote = self.votes.get(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+    #[test]
+    fn test_create_runoff() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let source_id = runoff_source(&mut harness, &creator, &[1, 0, 1, 2, 1, 0]);
+        let creator_info = harness.account_for(&creator);
+
+        // Runoffs only start from a result
+        assert_eq!(harness.voting.create_runoff(source_id, 2, std::slice::from_ref(&creator_info)), Err(VoteError::VoteStillOpen.into()));
+        harness.close(source_id, &creator).unwrap();
+
+        let stranger_info = harness.account_for(&Pubkey::new_unique());
+        assert!(harness.voting.create_runoff(source_id, 2, &[stranger_info]).is_err());
+        assert!(harness.voting.create_runoff(source_id, 1, std::slice::from_ref(&creator_info)).is_err());
+
+        let runoff_id = harness.voting.create_runoff(source_id, 2, std::slice::from_ref(&creator_info)).unwrap();
+        assert_eq!(option_labels(&harness, runoff_id), vec!["B", "A"]);
+
+        let summary = harness.voting.get_vote_summary(runoff_id).unwrap();
+        assert_eq!(summary.title, "Chair (runoff)");
+        assert_eq!(summary.status, VoteStatus::Open);
+        assert_eq!(summary.quorum, Some(2));
+        assert_eq!(summary.runoff_of, Some(source_id));
+        assert_eq!(harness.voting.get_vote_summary(source_id).unwrap().runoff_of, None);
+
+        // Every voter of the first round votes again
+        let source_voters = harness.voting.get_allowed_voters_as(&creator, source_id).unwrap();
+        assert_eq!(harness.voting.get_allowed_voters_as(&creator, runoff_id).unwrap(), source_voters);
+        assert!(harness.voting.votes[&runoff_id].allowed_voters.values().all(|voter_info| voter_info.votes_left == vec![1]));
+    }
 
-        // Check if the results of the vote are closed
-        if vote.is_close_vote_results {
-            // Check if the voter is allowed
-            if !vote.is_voter_allowed(caller) {
-                return Err(ProgramError::InvalidArgument); // Return error if the voter is not allowed
-            }
-        }
+    #[test]
+    fn test_create_runoff_ties_at_the_cutoff() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
 
-        // Return the voting results
-        Ok(vote.votes.clone())
+        // B leads and C ties with D for second place, both make it
+        let source_id = runoff_source(&mut harness, &creator, &[1, 1, 1, 2, 3, 3, 2, 0]);
+        harness.close(source_id, &creator).unwrap();
+        let creator_info = harness.account_for(&creator);
+        let runoff_id = harness.voting.create_runoff(source_id, 2, std::slice::from_ref(&creator_info)).unwrap();
+        assert_eq!(option_labels(&harness, runoff_id), vec!["B", "C", "D"]);
+
+        // A top N beyond the options with votes takes all of them
+        let runoff_id = harness.voting.create_runoff(source_id, 10, std::slice::from_ref(&creator_info)).unwrap();
+        assert_eq!(option_labels(&harness, runoff_id), vec!["B", "C", "D", "A"]);
+
+        // A single option with votes leaves nothing to run off
+        let landslide_id = runoff_source(&mut harness, &creator, &[2, 2]);
+        harness.close(landslide_id, &creator).unwrap();
+        assert_eq!(harness.voting.create_runoff(landslide_id, 2, &[creator_info]), Err(ProgramError::InvalidArgument));
     }
 
-    pub fn add_allowed_voter(&mut self, vote_id: u32, voter: Pubkey, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
-        if accounts.is_empty() {
-            return Err(ProgramError::InvalidArgument); // Return error if no accounts are provided
+    #[test]
+    fn test_create_subvote() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voters = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+        let creator_info = harness.account_for(&creator);
+        let budget = || VoteConfig::from_labels("Budget".to_string(), vec!["Low".to_string(), "High".to_string()]).unwrap();
+
+        // The first and third voter pick the winning project, the fourth one does not vote
+        let labels = vec!["Park".to_string(), "Library".to_string()];
+        let parent_id = harness.create_vote(VoteConfig::from_labels("Project".to_string(), labels).unwrap(), &creator).unwrap();
+        for voter in &voters {
+            harness.allow(parent_id, &creator, voter).unwrap();
         }
-
-        let caller = accounts[0].key;
-
-        if let Some(vote) = self.votes.get_mut(&vote_id) {
-            vote.add_allowed_voter(voter, caller)
-        } else {
-            Err(ProgramError::InvalidArgument) // Return error if the vote does not exist
+        for (voter, option_index) in voters.iter().zip([1, 0, 1]) {
+            harness.cast(parent_id, voter, option_index).unwrap();
         }
+        assert_eq!(harness.voting.create_subvote(parent_id, OptionIndex(1), budget(), std::slice::from_ref(&creator_info)), Err(VoteError::VoteStillOpen.into()));
+        harness.close(parent_id, &creator).unwrap();
+
+        // Only the creator of the parent opens sub-votes, for options that exist
+        let stranger_info = harness.account_for(&Pubkey::new_unique());
+        assert_eq!(harness.voting.create_subvote(parent_id, OptionIndex(1), budget(), &[stranger_info]), Err(ProgramError::InvalidArgument));
+        assert_eq!(harness.voting.create_subvote(parent_id, OptionIndex(2), budget(), std::slice::from_ref(&creator_info)), Err(ProgramError::InvalidArgument));
+        let grouped = VoteConfig { allowlist_group: Some(1), ..budget() };
+        assert_eq!(harness.voting.create_subvote(parent_id, OptionIndex(1), grouped, std::slice::from_ref(&creator_info)), Err(ProgramError::InvalidArgument));
+
+        let subvote_id = harness.voting.create_subvote(parent_id, OptionIndex(1), budget(), std::slice::from_ref(&creator_info)).unwrap();
+        let mut supporters = vec![voters[0], voters[2]];
+        supporters.sort();
+        assert_eq!(harness.voting.get_allowed_voters_as(&creator, subvote_id).unwrap(), supporters);
+        assert_eq!(harness.voting.get_vote_summary(subvote_id).unwrap().parent, Some((parent_id, OptionIndex(1))));
+        assert_eq!(harness.voting.get_vote_summary(parent_id).unwrap().parent, None);
+
+        // Supporters of the other project cannot vote on the budget
+        assert_eq!(harness.cast(subvote_id, &voters[0], 1), Ok(()));
+        assert!(harness.cast(subvote_id, &voters[1], 1).is_err());
+        assert!(harness.cast(subvote_id, &voters[3], 1).is_err());
     }
 
-    pub fn remove_allowed_voter(&mut self, vote_id: u32, voter: &Pubkey, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
-        if accounts.is_empty() {
-            return Err(ProgramError::InvalidArgument); // Return error if no accounts are provided
-        }
+    #[test]
+    fn test_outbox() {
+        let mut harness = TestHarness::new();
+        let admin = *harness.voting.admin();
+        let creator = Pubkey::new_unique();
+        let voter = Pubkey::new_unique();
+        let outbox = Pubkey::new_unique();
+        set_clock(1_000);
+
+        // The outbox account has to hold every entry, and only the admin enables it
+        harness.set_data(&outbox, vec![0; Outbox::space(2) - 1]);
+        let enable_accounts = [harness.account_for(&admin), harness.account_for(&outbox)];
+        assert_eq!(harness.voting.enable_outbox(2, OutboxPolicy::RejectWhenFull, &enable_accounts), Err(ProgramError::AccountDataTooSmall));
+        harness.set_data(&outbox, vec![0; Outbox::space(2)]);
+        let enable_accounts = [harness.account_for(&admin), harness.account_for(&outbox)];
+        assert_eq!(harness.voting.enable_outbox(MAX_OUTBOX_CAPACITY + 1, OutboxPolicy::RejectWhenFull, &enable_accounts), Err(ProgramError::InvalidArgument));
+        let stranger_accounts = [harness.account_for(&creator), harness.account_for(&outbox)];
+        assert_eq!(harness.voting.enable_outbox(2, OutboxPolicy::RejectWhenFull, &stranger_accounts), Err(ProgramError::InvalidArgument));
+        harness.voting.enable_outbox(2, OutboxPolicy::RejectWhenFull, &enable_accounts).unwrap();
+        assert_eq!(harness.voting.outbox_address(), Some(outbox));
+        assert_eq!(harness.voting.enable_outbox(2, OutboxPolicy::EvictOldest, &enable_accounts), Err(ProgramError::InvalidArgument));
+
+        let config = |deadline| VoteConfig { deadline, ..VoteConfig::from_labels("Chair".to_string(), vec!["Alice".to_string(), "Bob".to_string()]).unwrap() };
+        let vote_ids: Vec<VoteId> = (0..3).map(|_| harness.create_vote(config(Some(2_000)), &creator).unwrap()).collect();
+        harness.allow(vote_ids[0], &creator, &voter).unwrap();
+        harness.cast(vote_ids[0], &voter, 1).unwrap();
+        let read_outbox = |harness: &mut TestHarness| Outbox::unpack(&harness.account_for(&outbox).data.borrow()).unwrap();
+
+        // Closures take the outbox as their last account and queue the outcome
+        assert_eq!(harness.close(vote_ids[0], &creator), Err(ProgramError::NotEnoughAccountKeys));
+        let close_accounts = [harness.account_for(&creator), harness.account_for(&outbox)];
+        harness.voting.close_vote(vote_ids[0], &close_accounts).unwrap();
+        set_clock(2_001);
+        let finalize_accounts = [harness.account_for(&voter), harness.account_for(&outbox)];
+        harness.voting.finalize_expired_vote(vote_ids[1], &finalize_accounts).unwrap();
+        let entries = read_outbox(&mut harness).entries;
+        assert_eq!(
+            entries,
+            vec![
+                OutboxEntry { vote_id: vote_ids[0], outcome: OutboxOutcome::Winner { option_index: 1 }, closed_at: 1_000, consumed: false },
+                OutboxEntry { vote_id: vote_ids[1], outcome: OutboxOutcome::NoVotes, closed_at: 2_001, consumed: false },
+            ]
+        );
 
-        let caller = accounts[0].key;
+        // The full outbox refuses the next closure and the vote stays open
+        assert_eq!(harness.voting.finalize_expired_vote(vote_ids[2], &finalize_accounts), Err(VoteError::OutboxFull.into()));
+        assert_eq!(harness.voting.get_vote_summary(vote_ids[2]).unwrap().status, VoteStatus::Open);
+
+        // The admin acknowledges what the worker handled, which frees the slot for the next closure
+        let ack_accounts = [harness.account_for(&admin), harness.account_for(&outbox)];
+        assert_eq!(harness.voting.ack_outbox(&[vote_ids[0]], &stranger_accounts), Err(ProgramError::InvalidArgument));
+        let wrong_accounts = [harness.account_for(&admin), harness.account_for(&voter)];
+        assert_eq!(harness.voting.ack_outbox(&[vote_ids[0]], &wrong_accounts), Err(ProgramError::InvalidArgument));
+        assert_eq!(harness.voting.ack_outbox(&[vote_ids[0]], &ack_accounts), Ok(1));
+        harness.voting.finalize_expired_vote(vote_ids[2], &finalize_accounts).unwrap();
+        let outbox_state = read_outbox(&mut harness);
+        assert_eq!(outbox_state.pending().map(|entry| entry.vote_id).collect::<Vec<_>>(), vec![vote_ids[1], vote_ids[2]]);
+        assert_eq!((outbox_state.entries.len(), outbox_state.overflow_count), (2, 0));
+    }
 
-        if let Some(vote) = self.votes.get_mut(&vote_id) {
-            vote.remove_allowed_voter(voter, caller)
-        } else {
-            Err(ProgramError::InvalidArgument) // Return error if the vote does not exist
-        }
+    #[test]
+    fn test_registry_sharding() {
+        let mut harness = TestHarness::new();
+        let admin = harness.voting.admin;
+        let creator = Pubkey::new_unique();
+        let admin_info = harness.account_for(&admin);
+        let config = |title: &str| VoteConfig::from_labels(title.to_string(), vec!["Yes".to_string(), "No".to_string()]).unwrap();
+
+        // Only the registry admin enables sharding, with a capacity a shard account can hold
+        let stranger_info = harness.account_for(&Pubkey::new_unique());
+        assert_eq!(harness.voting.enable_sharding(2, &[stranger_info]), Err(ProgramError::InvalidArgument));
+        assert_eq!(harness.voting.enable_sharding(0, std::slice::from_ref(&admin_info)), Err(ProgramError::InvalidArgument));
+        assert_eq!(harness.voting.enable_sharding(MAX_SHARD_CAPACITY + 1, std::slice::from_ref(&admin_info)), Err(ProgramError::InvalidArgument));
+        assert_eq!(harness.voting.next_shard_index(), None);
+        assert!(harness.voting.enable_sharding(2, std::slice::from_ref(&admin_info)).is_ok());
+        assert_eq!(harness.voting.enable_sharding(2, std::slice::from_ref(&admin_info)), Err(ProgramError::InvalidArgument));
+        assert_eq!((harness.voting.shard_capacity(), harness.voting.shard_count(), harness.voting.next_shard_index()), (Some(2), 0, Some(0)));
+
+        // The third vote finds the first shard full and opens the second one
+        let vote_ids: Vec<VoteId> = ["A", "B", "C"].iter().map(|title| harness.create_vote(config(title), &creator).unwrap()).collect();
+        assert_eq!(harness.voting.shard_count(), 2);
+        let shards: Vec<Option<u32>> = vote_ids.iter().map(|vote_id| harness.voting.shard_for_vote_id(*vote_id)).collect();
+        assert_eq!(shards, vec![Some(0), Some(0), Some(1)]);
+        assert_eq!(harness.voting.shard_for_vote_id(VoteId(9)), None);
+        assert_eq!(harness.voting.next_shard_index(), Some(1));
+
+        // Listing goes shard by shard, and across both
+        let titles = |summaries: Vec<VoteSummary>| summaries.into_iter().map(|summary| summary.title).collect::<Vec<_>>();
+        assert_eq!(titles(harness.voting.list_shard(0).unwrap()), vec!["A", "B"]);
+        assert_eq!(titles(harness.voting.list_shard(1).unwrap()), vec!["C"]);
+        assert_eq!(harness.voting.list_shard(2), Err(ProgramError::InvalidArgument));
+        assert_eq!(titles(harness.voting.list_votes()), vec!["A", "B", "C"]);
+
+        // The shards survive a save and load of the registry
+        harness.create_vote(config("D"), &creator).unwrap();
+        assert_eq!(harness.voting.next_shard_index(), Some(2));
+        let voting = Voting::try_from_slice(&borsh::to_vec(&harness.voting).unwrap()).unwrap();
+        assert_eq!(titles(voting.list_shard(1).unwrap()), vec!["C", "D"]);
+
+        // Sharding cannot be turned on over existing votes
+        let mut unsharded = TestHarness::new();
+        let admin = unsharded.voting.admin;
+        let admin_info = unsharded.account_for(&admin);
+        unsharded.create_vote(config("A"), &creator).unwrap();
+        assert_eq!(unsharded.voting.enable_sharding(2, &[admin_info]), Err(ProgramError::InvalidArgument));
+        assert_eq!(unsharded.voting.shard_for_vote_id(VoteId(0)), None);
     }
 
-    pub fn is_voter_allowed(&self, vote_id: u32, voter: &Pubkey) -> Result<bool, ProgramError> {
-        if let Some(vote) = self.votes.get(&vote_id) {
-            Ok(vote.is_voter_allowed(voter))
-        } else {
-            Err(ProgramError::InvalidArgument) // Return error if the vote does not exist
+    #[test]
+    fn test_import_allowlist() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let stranger = Pubkey::new_unique();
+        let voters = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+        let labels = vec!["Alice".to_string(), "Bob".to_string()];
+
+        // Round one: the first two voters take part, the last one is struck
+        let round_one = harness.create_vote(VoteConfig::from_labels("Round 1".to_string(), labels.clone()).unwrap(), &creator).unwrap();
+        for voter in &voters {
+            harness.allow(round_one, &creator, voter).unwrap();
         }
+        harness.cast(round_one, &voters[0], 0).unwrap();
+        harness.cast(round_one, &voters[1], 1).unwrap();
+        harness.voting.invalidate_voter_ballots_as(&creator, round_one, &voters[3]).unwrap();
+        harness.close(round_one, &creator).unwrap();
+
+        // Voters already on the destination keep their votes
+        let runoff = harness.create_vote(VoteConfig::from_labels("Runoff".to_string(), labels.clone()).unwrap(), &creator).unwrap();
+        harness.allow(runoff, &creator, &voters[1]).unwrap();
+        assert_eq!(harness.voting.import_allowlist_as(&creator, runoff, round_one, ImportFilter::ParticipantsOnly, 2), Ok(1));
+        let runoff_voters = &harness.voting.votes[&runoff].allowed_voters;
+        assert_eq!(runoff_voters.len(), 2);
+        assert_eq!(runoff_voters[&voters[0]].votes_left, vec![2]);
+        assert_eq!(runoff_voters[&voters[1]].votes_left, vec![1]);
+        assert_eq!(harness.voting.import_allowlist_as(&creator, runoff, round_one, ImportFilter::ParticipantsOnly, 2), Ok(0));
+
+        let reminder = harness.create_vote(VoteConfig::from_labels("Reminder".to_string(), labels.clone()).unwrap(), &creator).unwrap();
+        assert_eq!(harness.voting.import_allowlist_as(&creator, reminder, round_one, ImportFilter::NonParticipants, 1), Ok(1));
+        assert!(harness.voting.is_voter_allowed(reminder, &voters[2]).unwrap());
+
+        let rerun = harness.create_vote(VoteConfig::from_labels("Rerun".to_string(), labels).unwrap(), &creator).unwrap();
+        assert_eq!(harness.voting.import_allowlist_as(&creator, rerun, round_one, ImportFilter::AllEligible, 1), Ok(3));
+        assert!(!harness.voting.is_voter_allowed(rerun, &voters[3]).unwrap());
+        assert_eq!(harness.voting.get_registry_stats().total_voters_registered, 4 + 1 + 1 + 1 + 3);
+
+        // Only the creator of an open destination imports, and every voter gets at least one vote
+        assert!(harness.voting.import_allowlist_as(&stranger, rerun, round_one, ImportFilter::AllEligible, 1).is_err());
+        assert!(harness.voting.import_allowlist_as(&creator, rerun, round_one, ImportFilter::AllEligible, 0).is_err());
+        assert!(harness.voting.import_allowlist_as(&creator, rerun, rerun, ImportFilter::AllEligible, 1).is_err());
+        assert!(harness.voting.import_allowlist_as(&creator, round_one, rerun, ImportFilter::AllEligible, 1).is_err());
+        assert!(harness.voting.import_allowlist_as(&creator, rerun, VoteId(99), ImportFilter::AllEligible, 1).is_err());
     }
 
-    pub fn delegate_vote(&mut self, vote_id: u32, delegate: &Pubkey, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
-        // Check if the vote with the given ID exists
-        let vote = self.votes.get_mut(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+    #[test]
+    fn test_import_allowlist_limits() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let config = VoteConfig::from_labels("Round 1".to_string(), vec!["Alice".to_string(), "Bob".to_string()]).unwrap();
+        let voters: Vec<Pubkey> = (0..=MAX_BATCH_VOTERS).map(|_| Pubkey::new_unique()).collect();
 
-        if accounts.is_empty() {
-            return Err(ProgramError::InvalidArgument); // Return error if no accounts are provided
+        let at_limit = harness.create_vote(config.clone(), &creator).unwrap();
+        let over_limit = harness.create_vote(config.clone(), &creator).unwrap();
+        for voter in &voters {
+            harness.allow(over_limit, &creator, voter).unwrap();
+        }
+        for voter in &voters[..MAX_BATCH_VOTERS] {
+            harness.allow(at_limit, &creator, voter).unwrap();
         }
 
-        let delegator = accounts[0].key;
-
-        // Call the delegate_vote method of the vote
-        vote.delegate_vote(delegate, delegator)
+        let dest = harness.create_vote(config.clone(), &creator).unwrap();
+        let before = harness.voting.clone();
+        assert_eq!(harness.voting.import_allowlist_as(&creator, dest, over_limit, ImportFilter::AllEligible, 1), Err(VoteError::BatchTooLarge.into()));
+        assert_eq!(harness.voting, before);
+        assert_eq!(harness.voting.import_allowlist_as(&creator, dest, at_limit, ImportFilter::AllEligible, 1), Ok(MAX_BATCH_VOTERS as u32));
+
+        // Voters already on the destination do not count against the batch
+        assert_eq!(harness.voting.import_allowlist_as(&creator, dest, over_limit, ImportFilter::AllEligible, 1), Ok(1));
+
+        // An allowlist without room for the whole batch takes none of it
+        let state_key = Pubkey::new_unique();
+        harness.set_data(&state_key, vec![0; Vote::required_space(&config, 3)]);
+        let accounts = [harness.account_for(&creator), harness.account_for(&state_key)];
+        let small = harness.voting.create_vote_with_config(config, &accounts).unwrap();
+        harness.allow(small, &creator, &voters[0]).unwrap();
+        let before = harness.voting.clone();
+        assert_eq!(harness.voting.import_allowlist_as(&creator, small, at_limit, ImportFilter::AllEligible, 1), Err(VoteError::AllowlistFull.into()));
+        assert_eq!(harness.voting, before);
     }
 
-    pub fn get_options(&mut self, vote_id: u32) -> Result<&Vec<String>, ProgramError> {
-        if let Some(vote) = self.votes.get(&vote_id) {
-            Ok(vote.get_options())
-        } else {
-            Err(ProgramError::InvalidArgument) // Return error if the vote does not exist
+    #[test]
+    fn test_allowlist_diff() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let auditor = Pubkey::new_unique();
+        let [kept, updated, removed, added] = [0; 4].map(|_| Pubkey::new_unique());
+        let config = VoteConfig::from_labels("Board".to_string(), vec!["Alice".to_string(), "Bob".to_string()]).unwrap();
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+        for voter in [kept, updated, removed] {
+            harness.allow(vote_id, &creator, &voter).unwrap();
         }
+        harness.cast(vote_id, &removed, 0).unwrap();
+        harness.voting.add_observer_as(&creator, vote_id, auditor).unwrap();
+
+        // One change of each kind, the voter who voted is removed with their ballot standing
+        let desired = [(updated, 3), (added, 2), (kept, 1)];
+        let diff = harness.voting.diff_allowlist_as(&auditor, vote_id, &desired).unwrap();
+        assert_eq!(diff, AllowlistDiff { to_add: vec![(added, 2)], to_remove: vec![removed], to_update: vec![(updated, 3)] });
+        assert_eq!(harness.voting.diff_allowlist_as(&kept, vote_id, &desired), Err(ProgramError::InvalidArgument));
+        assert_eq!(harness.voting.diff_allowlist_as(&creator, vote_id, &[(added, 1), (added, 2)]), Err(ProgramError::InvalidArgument));
+
+        assert_eq!(harness.voting.apply_allowlist_diff_as(&auditor, vote_id, &diff), Err(ProgramError::InvalidArgument));
+        harness.voting.apply_allowlist_diff_as(&creator, vote_id, &diff).unwrap();
+
+        let mut expected: Vec<Pubkey> = desired.iter().map(|(voter, _)| *voter).collect();
+        expected.sort();
+        assert_eq!(harness.voting.get_allowed_voters_as(&creator, vote_id).unwrap(), expected);
+        for (voter, votes) in desired {
+            assert_eq!(harness.voting.get_voter_status_as(&creator, vote_id, &voter).unwrap().votes_left, vec![votes]);
+        }
+        assert_eq!(harness.voting.get_results_as(&creator, vote_id, Some(0)).unwrap()[0].get("Alice"), Some(&1));
+        assert!(harness.voting.diff_allowlist_as(&creator, vote_id, &desired).unwrap().is_empty());
+
+        // A stale diff is refused as a whole
+        harness.cast(vote_id, &updated, 1).unwrap();
+        let stale = AllowlistDiff { to_add: vec![(removed, 1)], to_update: vec![(updated, 5)], ..AllowlistDiff::default() };
+        let before = harness.voting.clone();
+        assert_eq!(harness.voting.apply_allowlist_diff_as(&creator, vote_id, &stale), Err(ProgramError::InvalidArgument));
+        assert_eq!(harness.voting, before);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use solana_program::pubkey::Pubkey;
-    use std::collections::HashMap;
-
-    struct TestVoting {
-        voting: Voting,
-        lamports: u64,
-        data: Vec<u8>,
-        owner: Pubkey,
-    }
-
-    impl TestVoting {
-        fn new() -> Self {
-            Self {
-                voting: Voting {
-                    votes: HashMap::new(),
-                    current_id: 0,
-                },
-                lamports: 0,
-                data: vec![],
-                owner: Pubkey::new_unique(),
-            }
-        }
-
-        fn add_vote(&mut self, title: String, options: Vec<String>, is_close_vote_results: bool, creator: Pubkey) -> u32 {
-            let is_signer = true;
-            let is_writable = false;
-            let executable = false;
-
-            // Create AccountInfo for the vote creator
-            let account_info = AccountInfo::new(
-                &creator,
-                is_signer,
-                is_writable,
-                &mut self.lamports,
-                &mut self.data,
-                &self.owner,
-                executable,
-                0,
-            );
-
-            match self.voting.create_vote(title, options, is_close_vote_results, &[account_info]) {
-                Ok(vote_id) => vote_id,
-                Err(err) => {
-                    panic!("Failed to create vote: {:?}", err);
-                }
-            }
+    #[test]
+    fn test_allowlist_diff_pages() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let config = VoteConfig::from_labels("Board".to_string(), vec!["Alice".to_string(), "Bob".to_string()]).unwrap();
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+        let leaving = Pubkey::new_unique();
+        harness.allow(vote_id, &creator, &leaving).unwrap();
+
+        let desired: Vec<(Pubkey, u32)> = (0..MAX_BATCH_VOTERS + 10).map(|_| (Pubkey::new_unique(), 1)).collect();
+        let diff = harness.voting.diff_allowlist_as(&creator, vote_id, &desired).unwrap();
+        assert_eq!((diff.to_add.len(), diff.to_remove.len()), (MAX_BATCH_VOTERS + 10, 1));
+        assert_eq!(harness.voting.apply_allowlist_diff_as(&creator, vote_id, &diff), Err(VoteError::BatchTooLarge.into()));
+
+        // Pages fit the batch limit and together make the whole diff, the removal first
+        let pages = diff.pages(MAX_BATCH_VOTERS);
+        assert_eq!(pages.iter().map(AllowlistDiff::len).collect::<Vec<_>>(), vec![MAX_BATCH_VOTERS, 11]);
+        assert_eq!(pages[0].to_remove, vec![leaving]);
+        for page in &pages {
+            harness.voting.apply_allowlist_diff_as(&creator, vote_id, page).unwrap();
         }
+        assert!(harness.voting.diff_allowlist_as(&creator, vote_id, &desired).unwrap().is_empty());
+        assert_eq!(harness.voting.get_registry_stats().total_voters_registered, 1 + desired.len() as u64);
     }
 
     #[test]
-    fn test_create_vote() {
-        let mut test_voting = TestVoting::new();
+    fn test_full_results_option_limit() {
+        let mut harness = TestHarness::new();
         let creator = Pubkey::new_unique();
-
-        test_voting.add_vote("Test Vote".to_string(), vec!["Option 1".to_string(), "Option 2".to_string()], false, creator);
-
-        assert_eq!(test_voting.voting.votes.len(), 1);
-        let vote = test_voting.voting.votes.get(&0).unwrap();
-        assert_eq!(vote.title, "Test Vote");
-        assert_eq!(vote.options.len(), 2);
+        let labels = |count: usize| (0..count).map(|index| format!("Option {}", index)).collect::<Vec<_>>();
+        let question = |count| QuestionConfig { title: "Question".try_into().unwrap(), options: VoteOption::from_labels(labels(count)).unwrap() };
+
+        // The limit counts the options of every question
+        let at_limit = harness.create_vote(VoteConfig::from_labels("At".to_string(), labels(MAX_OPTIONS_FULL_RESULTS)).unwrap(), &creator).unwrap();
+        let split = VoteConfig::with_questions("Split".to_string(), vec![question(MAX_OPTIONS_FULL_RESULTS), question(1)]).unwrap();
+        let over_limit = harness.create_vote(split, &creator).unwrap();
+
+        let creator_info = harness.account_for(&creator);
+        let accounts = std::slice::from_ref(&creator_info);
+        assert_eq!(harness.voting.get_full_results(at_limit, accounts).unwrap().questions[0].options.len(), MAX_OPTIONS_FULL_RESULTS);
+        assert!(harness.voting.get_results_hash(at_limit, accounts).is_ok());
+        assert_eq!(harness.voting.get_full_results(over_limit, accounts), Err(VoteError::TooManyOptionsForFullResults.into()));
+        assert_eq!(harness.voting.get_results_hash(over_limit, accounts), Err(VoteError::TooManyOptionsForFullResults.into()));
+
+        // Pages and the off-chain readers still serve the large vote
+        assert_eq!(harness.voting.get_results_page_as(&creator, over_limit, 0, 0, MAX_RESULTS_PAGE).unwrap().total_options, MAX_OPTIONS_FULL_RESULTS as u32);
+        assert_eq!(harness.voting.get_full_results_as(&creator, over_limit).unwrap().questions.len(), 2);
     }
 
     #[test]
-    fn test_add_allowed_voter() {
-        let mut test_voting = TestVoting::new();
+    fn test_clone_vote_with_allowlist() {
+        let mut harness = TestHarness::new();
         let creator = Pubkey::new_unique();
         let voter1 = Pubkey::new_unique();
+        let voter2 = Pubkey::new_unique();
+        let sybil = Pubkey::new_unique();
 
-        test_voting.add_vote("Test Vote".to_string(), vec!["Option 1".to_string()], false, creator);
-
-        let is_signer = true;
-        let is_writable = false;
-        let executable = false;
-
-        // Create AccountInfo for the vote creator
-        let account_info = AccountInfo::new(
-            &creator,
-            is_signer,
-            is_writable,
-            &mut test_voting.lamports,
-            &mut test_voting.data,
-            &test_voting.owner,
-            executable,
-            0,
-        );
-
-        assert!(test_voting.voting.add_allowed_voter(0, voter1, &[account_info]).is_ok());
-
-        let vote = test_voting.voting.votes.get(&0).unwrap();
-        assert!(vote.is_voter_allowed(&voter1));
+        let config = VoteConfig::from_labels("Sprint 1 Retro".to_string(), vec!["Keep".to_string(), "Change".to_string()]).unwrap();
+        let source_id = harness.create_vote(config, &creator).unwrap();
+        for voter in [voter1, voter2, sybil] {
+            harness.allow(source_id, &creator, &voter).unwrap();
+        }
+        harness.cast(source_id, &voter1, 0).unwrap();
+        harness.cast(source_id, &sybil, 0).unwrap();
+        let creator_info = harness.account_for(&creator);
+        harness.voting.invalidate_voter_ballots_as(&creator, source_id, &sybil).unwrap();
+
+        // The source is still open, voters of the clone get a fresh vote and banned voters stay out
+        let clone_id = harness.voting.clone_vote(source_id, "Sprint 2 Retro".to_string(), true, &[creator_info]).unwrap();
+        let clone = &harness.voting.votes[&clone_id];
+        assert_eq!(clone.allowed_voters.len(), 2);
+        assert_eq!(clone.allowed_voters[&voter1].votes_left, vec![1]);
+        assert!(clone.allowed_voters[&voter1].ballots.is_empty());
+        assert!(!clone.allowed_voters.contains_key(&sybil));
+        assert_eq!(harness.voting.votes[&source_id].allowed_voters[&voter1].votes_left, vec![0]);
+        assert_eq!(harness.voting.get_registry_stats().total_voters_registered, 5);
+
+        assert!(harness.cast(clone_id, &voter1, 1).is_ok());
+        assert!(harness.cast(clone_id, &voter2, 1).is_ok());
+        assert_eq!(harness.voting.votes[&source_id].questions[0].votes.get("Change"), None);
     }
 
     #[test]
-    fn test_vote() {
-        let mut test_voting = TestVoting::new();
+    fn test_results_page() {
+        let mut harness = TestHarness::new();
         let creator = Pubkey::new_unique();
-        let voter1 = Pubkey::new_unique();
-
-        test_voting.add_vote("Test Vote".to_string(), vec!["Option 1".to_string(), "Option 2".to_string()], false, creator);
-
-        let is_signer = true;
-        let is_writable = false;
-        let executable = false;
-
-        // Create AccountInfo for the vote creator
-        let account_info = AccountInfo::new(
-            &creator,
-            is_signer,
-            is_writable,
-            &mut test_voting.lamports,
-            &mut test_voting.data,
-            &test_voting.owner,
-            executable,
-            0,
-        );
 
-        assert!(test_voting.voting.add_allowed_voter(0, voter1, &[account_info]).is_ok());
-
-        let account_info_voter1 = AccountInfo::new(
-            &voter1,
-            is_signer,
-            is_writable,
-            &mut test_voting.lamports,
-            &mut test_voting.data,
-            &test_voting.owner,
-            executable,
-            0,
+        let labels = (0..100).map(|index| format!("Option {}", index)).collect();
+        let vote_id = harness.create_vote(VoteConfig::from_labels("Large Vote".to_string(), labels).unwrap(), &creator).unwrap();
+
+        // Option i receives i % 7 ballots, option 98 leads with 7
+        let mut ballots = 0;
+        for index in 0..100 {
+            let count = if index == 98 { 7 } else { index % 7 };
+            for _ in 0..count {
+                let voter = Pubkey::new_unique();
+                harness.allow(vote_id, &creator, &voter).unwrap();
+                harness.cast(vote_id, &voter, index).unwrap();
+                ballots += 1;
+            }
+        }
+        harness.allow(vote_id, &creator, &Pubkey::new_unique()).unwrap();
+
+        // Pages are capped and cover every option once, in option order
+        let mut paged = Vec::new();
+        let mut offset = 0;
+        loop {
+            let page = harness.voting.get_results_page_as(&creator, vote_id, 0, offset, 50).unwrap();
+            assert_eq!(page.total_options, 100);
+            assert!(page.options.len() <= MAX_RESULTS_PAGE as usize);
+            if page.options.is_empty() {
+                break;
+            }
+            offset += page.options.len() as u32;
+            paged.extend(page.options);
+        }
+        let full = harness.voting.get_full_results_as(&creator, vote_id).unwrap();
+        assert_eq!(paged, full.questions[0].options);
+        assert!(harness.voting.get_results_page_as(&creator, vote_id, 1, 0, 10).is_err());
+
+        let summary = harness.voting.get_summary_as(&creator, vote_id).unwrap();
+        assert_eq!(summary.eligible_voters, ballots + 1);
+        assert_eq!(summary.participating_voters, ballots);
+        assert_eq!(
+            summary.questions,
+            vec![QuestionSummary {
+                total_options: 100,
+                total_votes: full.questions[0].total_votes,
+                total_weighted_votes: full.questions[0].total_weighted_votes,
+                leading_option: Some(98),
+                leading_share_bps: full.questions[0].options[98].share_bps,
+            }]
         );
-
-        assert!(test_voting.voting.vote(0, &[account_info_voter1], 0).is_ok());
-
-        let vote = test_voting.voting.votes.get_mut(&0).unwrap();
-        assert_eq!(*vote.votes.get("Option 1").unwrap(), 1);
+        assert_eq!(full.questions[0].total_votes, ballots);
     }
 
     #[test]
-    fn test_vote_not_allowed() {
-        let mut test_voting = TestVoting::new();
+    fn test_results_summary_tie_and_visibility() {
+        let mut harness = TestHarness::new();
         let creator = Pubkey::new_unique();
         let voter1 = Pubkey::new_unique();
         let voter2 = Pubkey::new_unique();
+        let outsider = Pubkey::new_unique();
 
-        test_voting.add_vote("Test Vote".to_string(), vec!["Option 1".to_string()], false, creator);
-
-        let is_signer = true;
-        let is_writable = false;
-        let executable = false;
-
-        // Create AccountInfo for the vote creator
-        let account_info = AccountInfo::new(
-            &creator,
-            is_signer,
-            is_writable,
-            &mut test_voting.lamports,
-            &mut test_voting.data,
-            &test_voting.owner,
-            executable,
-            0,
-        );
+        let config = VoteConfig {
+            results_visibility: Visibility::AllowlistOnly,
+            ..VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string(), "Option 2".to_string()]).unwrap()
+        };
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+        harness.allow(vote_id, &creator, &voter1).unwrap();
+        harness.allow(vote_id, &creator, &voter2).unwrap();
 
-        assert!(test_voting.voting.add_allowed_voter(0, voter1, &[account_info]).is_ok());
-
-        let account_info_voter2 = AccountInfo::new(
-            &voter2,
-            is_signer,
-            is_writable,
-            &mut test_voting.lamports,
-            &mut test_voting.data,
-            &test_voting.owner,
-            executable,
-            0,
-        );
+        assert_eq!(harness.voting.get_summary_as(&voter1, vote_id).unwrap().questions[0].leading_option, None);
+
+        harness.cast(vote_id, &voter1, 0).unwrap();
+        harness.cast(vote_id, &voter2, 1).unwrap();
+        let summary = harness.voting.get_summary_as(&voter1, vote_id).unwrap();
+        assert_eq!(summary.questions[0].leading_option, None);
+        assert_eq!(summary.questions[0].leading_share_bps, 5_000);
 
-        assert!(test_voting.voting.vote(0, &[account_info_voter2], 0).is_err()); // Voter is not allowed
+        assert!(harness.voting.get_summary_as(&outsider, vote_id).is_err());
+        assert!(harness.voting.get_results_page_as(&outsider, vote_id, 0, 0, 10).is_err());
     }
 
     #[test]
-    fn test_vote_no_votes_left() {
-        let mut test_voting = TestVoting::new();
+    fn test_results_batch() {
+        let mut harness = TestHarness::new();
         let creator = Pubkey::new_unique();
-        let voter1 = Pubkey::new_unique();
-
-        test_voting.add_vote("Test Vote".to_string(), vec!["Option 1".to_string()], false, creator);
-
-        let is_signer = true;
-        let is_writable = false;
-        let executable = false;
-
-        // Create AccountInfo for the vote creator
-        let account_info = AccountInfo::new(
-            &creator,
-            is_signer,
-            is_writable,
-            &mut test_voting.lamports,
-            &mut test_voting.data,
-            &test_voting.owner,
-            executable,
-            0,
+        let voter = Pubkey::new_unique();
+        let outsider = Pubkey::new_unique();
+        let config = VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string(), "Option 2".to_string()]).unwrap();
+
+        let public_id = harness.create_vote(VoteConfig { quorum: Some(2), ..config.clone() }, &creator).unwrap();
+        let hidden_id = harness.create_vote(VoteConfig { results_visibility: Visibility::CreatorOnly, ..config.clone() }, &creator).unwrap();
+        let closed_id = harness.create_vote(config, &creator).unwrap();
+        for vote_id in [public_id, hidden_id, closed_id] {
+            harness.allow(vote_id, &creator, &voter).unwrap();
+            harness.cast(vote_id, &voter, 1).unwrap();
+        }
+        harness.close(closed_id, &creator).unwrap();
+
+        // The hidden and the missing vote carry their error, the others are answered as usual
+        let missing_id = VoteId(closed_id.0 + 1);
+        let batch = harness.voting.get_results_batch_as(&outsider, &[public_id, hidden_id, missing_id, closed_id]).unwrap();
+        let lite = |status, quorum_met| Ok(VoteResultsLite { leading_option: Some(1), total_ballots: 1, status, quorum_met });
+        assert_eq!(
+            batch,
+            vec![
+                (public_id, lite(VoteStatusKind::Open, false)),
+                (hidden_id, Err(VoteError::ResultsHidden)),
+                (missing_id, Err(VoteError::VoteNotFound)),
+                (closed_id, lite(VoteStatusKind::Closed, true)),
+            ]
         );
+        assert_eq!(harness.voting.get_results_batch_as(&creator, &[hidden_id]).unwrap(), vec![(hidden_id, lite(VoteStatusKind::Open, true))]);
 
-        test_voting.voting.add_allowed_voter(0, voter1, &[account_info]).unwrap();
+        let too_many = vec![public_id; MAX_RESULTS_BATCH + 1];
+        assert_eq!(harness.voting.get_results_batch_as(&creator, &too_many), Err(VoteError::BatchTooLarge.into()));
+    }
 
-        // Set that voter1 has no votes left
-        let new_voter = VoterInfo {
-            votes_left: 0,
-            delegate: None,
+    // 2-of-3 vote created by the first of the given creators
+    fn treasury_vote(harness: &mut TestHarness, creators: &[Pubkey; 3]) -> VoteId {
+        let config = VoteConfig {
+            creators: creators.to_vec(),
+            approval_threshold: 2,
+            ..VoteConfig::from_labels("Treasury Vote".to_string(), vec!["Fund".to_string(), "Reject".to_string()]).unwrap()
         };
-        test_voting.voting.votes.get_mut(&0).unwrap().allowed_voters.insert(voter1, new_voter);
-
-        let account_info_voter1 = AccountInfo::new(
-            &voter1,
-            is_signer,
-            is_writable,
-            &mut test_voting.lamports,
-            &mut test_voting.data,
-            &test_voting.owner,
-            executable,
-            0,
+        harness.create_vote(config, &creators[0]).unwrap()
+    }
+
+    #[test]
+    fn test_multi_creator_close() {
+        let mut harness = TestHarness::new();
+        let creators = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+        let outsider = Pubkey::new_unique();
+        set_clock(1_000);
+        let vote_id = treasury_vote(&mut harness, &creators);
+
+        // Neither the creator nor the registry admin can close the vote alone
+        assert_eq!(harness.close(vote_id, &creators[0]), Err(VoteError::ApprovalRequired.into()));
+        let admin = harness.voting.admin;
+        assert_eq!(harness.close(vote_id, &admin), Err(VoteError::ApprovalRequired.into()));
+        assert_eq!(harness.voting.cancel_vote_as(&creators[0], vote_id, "Withdrawn".to_string()), Err(VoteError::ApprovalRequired.into()));
+
+        let outsider_info = harness.account_for(&outsider);
+        assert!(harness.voting.propose_admin_action(vote_id, AdminAction::Close, &[outsider_info]).is_err());
+        let mut unsigned_info = harness.account_for(&creators[1]);
+        unsigned_info.is_signer = false;
+        assert_eq!(harness.voting.propose_admin_action(vote_id, AdminAction::Close, &[unsigned_info]), Err(ProgramError::MissingRequiredSignature));
+
+        // The proposal is the first approval, the second distinct creator closes the vote
+        let proposer_info = harness.account_for(&creators[1]);
+        harness.voting.propose_admin_action(vote_id, AdminAction::Close, std::slice::from_ref(&proposer_info)).unwrap();
+        assert_eq!(harness.voting.get_vote_summary(vote_id).unwrap().status, VoteStatus::Open);
+        let pending = harness.voting.get_pending_admin_action(vote_id).unwrap().unwrap();
+        assert_eq!(pending.approvals, vec![creators[1]]);
+        assert_eq!(pending.expires_at, 1_000 + PENDING_ACTION_LIFETIME);
+
+        // Approving twice with the same key or approving another action does not count
+        assert_eq!(harness.voting.approve_admin_action(vote_id, &AdminAction::Close, &[proposer_info]), Err(VoteError::AlreadyApproved.into()));
+        let approver_info = harness.account_for(&creators[2]);
+        let cancel = AdminAction::Cancel { reason: "Withdrawn".try_into().unwrap() };
+        assert!(harness.voting.approve_admin_action(vote_id, &cancel, std::slice::from_ref(&approver_info)).is_err());
+        assert_eq!(harness.voting.get_vote_summary(vote_id).unwrap().status, VoteStatus::Open);
+
+        harness.voting.approve_admin_action(vote_id, &AdminAction::Close, &[approver_info]).unwrap();
+        let summary = harness.voting.get_vote_summary(vote_id).unwrap();
+        assert_eq!(summary.status, VoteStatus::Closed);
+        assert_eq!(summary.closure, Some(ClosureRecord { closed_by: creators[2], closed_at: 1_000, reason: ClosureReason::Creator, tie_break_hash: None }));
+        assert_eq!(harness.voting.get_pending_admin_action(vote_id), Ok(None));
+        assert_eq!(harness.voting.get_registry_stats().currently_open, 0);
+    }
+
+    #[test]
+    fn test_multi_creator_pending_action_expiry() {
+        let mut harness = TestHarness::new();
+        let creators = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+        set_clock(1_000);
+        let vote_id = treasury_vote(&mut harness, &creators);
+        let cancel = AdminAction::Cancel { reason: "Withdrawn".try_into().unwrap() };
+
+        let creator_info = harness.account_for(&creators[0]);
+        harness.voting.propose_admin_action(vote_id, cancel.clone(), std::slice::from_ref(&creator_info)).unwrap();
+
+        // A live proposal blocks others, a stale one can no longer be approved and gets replaced
+        let other_info = harness.account_for(&creators[1]);
+        assert!(harness.voting.propose_admin_action(vote_id, AdminAction::Close, std::slice::from_ref(&other_info)).is_err());
+        set_clock(1_001 + PENDING_ACTION_LIFETIME);
+        assert_eq!(harness.voting.approve_admin_action(vote_id, &cancel, std::slice::from_ref(&other_info)), Err(VoteError::PendingActionExpired.into()));
+
+        harness.voting.propose_admin_action(vote_id, cancel.clone(), std::slice::from_ref(&other_info)).unwrap();
+        harness.voting.approve_admin_action(vote_id, &cancel, &[creator_info]).unwrap();
+        assert_eq!(
+            harness.voting.get_vote_summary(vote_id).unwrap().status,
+            VoteStatus::Cancelled { reason: "Withdrawn".try_into().unwrap(), at: 1_001 + PENDING_ACTION_LIFETIME }
         );
 
-        assert!(test_voting.voting.vote(0, &[account_info_voter1], 0).is_err()); // No votes left for voting
+        // Co-creators must include the creator, be unique and reach the threshold
+        let config = |creators: Vec<Pubkey>, approval_threshold| VoteConfig {
+            creators,
+            approval_threshold,
+            ..VoteConfig::from_labels("Treasury Vote".to_string(), vec!["Fund".to_string()]).unwrap()
+        };
+        assert!(harness.create_vote(config(vec![creators[1], creators[2]], 1), &creators[0]).is_err());
+        assert!(harness.create_vote(config(vec![creators[0], creators[0]], 1), &creators[0]).is_err());
+        assert!(harness.create_vote(config(creators.to_vec(), 4), &creators[0]).is_err());
+        assert!(harness.create_vote(config(creators.to_vec(), 0), &creators[0]).is_err());
+        assert!(harness.create_vote(config(Vec::new(), 1), &creators[0]).is_err());
     }
 
     #[test]
-    fn test_remove_allowed_voter() {
-        let mut test_voting = TestVoting::new();
+    fn test_display_vote() {
+        let mut harness = TestHarness::new();
         let creator = Pubkey::new_unique();
         let voter1 = Pubkey::new_unique();
+        let voter2 = Pubkey::new_unique();
+        let voter3 = Pubkey::new_unique();
 
-        test_voting.add_vote("Test Vote".to_string(), vec!["Option 1".to_string()], false, creator);
-
-        let is_signer = true;
-        let is_writable = false;
-        let executable = false;
-
-        // Create AccountInfo for the vote creator
-        let account_info = AccountInfo::new(
-            &creator,
-            is_signer,
-            is_writable,
-            &mut test_voting.lamports,
-            &mut test_voting.data,
-            &test_voting.owner,
-            executable,
-            0,
+        let vote_id = harness.create_vote(board_meeting_config(), &creator).unwrap();
+        for voter in [voter1, voter2, voter3] {
+            harness.allow(vote_id, &creator, &voter).unwrap();
+        }
+        harness.cast(vote_id, &voter1, 0).unwrap();
+        harness.cast(vote_id, &voter2, 1).unwrap();
+        harness.voting.vote_on_question_as(&voter1, vote_id, 1, OptionIndex(1)).unwrap();
+
+        assert_eq!(
+            harness.voting.votes[&vote_id].to_string(),
+            "Vote 0: Board Meeting (open)\n\
+             Question 0: Motion 1\n\
+             \x20 0. Yes: 1\n\
+             \x20 1. No: 1\n\
+             Question 1: Motion 2\n\
+             \x20 0. Yes: 0\n\
+             \x20 1. No: 1\n\
+             Turnout: 2 of 3 voters"
         );
 
-        assert!(test_voting.voting.add_allowed_voter(0, voter1, &[account_info.clone()]).is_ok());
-
-        // Remove the allowed voter
-        assert!(test_voting.voting.remove_allowed_voter(0, &voter1, &[account_info]).is_ok());
-        let vote = test_voting.voting.votes.get(&0).unwrap();
-        assert!(!vote.is_voter_allowed(&voter1)); // Check that the voter has been removed
+        harness.close(vote_id, &creator).unwrap();
+        assert!(harness.voting.votes[&vote_id].to_string().starts_with("Vote 0: Board Meeting (closed by the creator)\n"));
+        assert_eq!(VoteError::TooSoon.to_string(), VoteError::TooSoon.description());
     }
 
     #[test]
-    fn test_remove_allowed_voter_not_creator() {
-        let mut test_voting = TestVoting::new();
+    fn test_display_vote_results() {
+        let mut harness = TestHarness::new();
         let creator = Pubkey::new_unique();
-        let voter1 = Pubkey::new_unique();
-        let non_creator = Pubkey::new_unique();
 
-        test_voting.add_vote("Test Vote".to_string(), vec!["Option 1".to_string()], false, creator);
-
-        let is_signer = true;
-        let is_writable = false;
-        let executable = false;
-
-        // Create AccountInfo for the vote creator
-        let account_info = AccountInfo::new(
-            &creator,
-            is_signer,
-            is_writable,
-            &mut test_voting.lamports,
-            &mut test_voting.data,
-            &test_voting.owner,
-            executable,
-            0,
-        );
+        let vote_id = harness.create_vote(VoteConfig::from_labels("Budget".to_string(), vec!["Yes".to_string(), "Rejected".to_string()]).unwrap(), &creator).unwrap();
+        for option_index in [0, 0, 1] {
+            let voter = Pubkey::new_unique();
+            harness.allow(vote_id, &creator, &voter).unwrap();
+            harness.cast(vote_id, &voter, option_index).unwrap();
+        }
 
-        assert!(test_voting.voting.add_allowed_voter(0, voter1, &[account_info]).is_ok());
-
-        let non_creator_info = AccountInfo::new(
-            &non_creator,
-            is_signer,
-            is_writable,
-            &mut test_voting.lamports,
-            &mut test_voting.data,
-            &test_voting.owner,
-            executable,
-            0,
+        let results = harness.voting.get_full_results_as(&creator, vote_id).unwrap();
+        assert_eq!(
+            results.to_string(),
+            "Vote 0\n\
+             \n\
+             Question 0: Budget\n\
+             Index  Option       Votes     Share\n\
+             \x20   0  Yes              2    66.67%\n\
+             \x20   1  Rejected         1    33.33%\n\
+             \x20      Total            3\n"
         );
-
-        // Attempt to remove an allowed voter not as the creator
-        assert!(test_voting.voting.remove_allowed_voter(0, &voter1, &[non_creator_info]).is_err());
     }
 
     #[test]
-    fn test_delegate_vote() {
-        let mut test_voting = TestVoting::new();
+    fn test_display_vote_results_non_ascii() {
+        let mut harness = TestHarness::new();
         let creator = Pubkey::new_unique();
-        let voter1 = Pubkey::new_unique();
-        let delegate = Pubkey::new_unique();
 
-        test_voting.add_vote("Test Vote".to_string(), vec!["Option 1".to_string()], false, creator);
-
-        let is_signer = true;
-        let is_writable = false;
-        let executable = false;
-
-        // Create AccountInfo for the vote creator
-        let account_info = AccountInfo::new(
-            &creator,
-            is_signer,
-            is_writable,
-            &mut test_voting.lamports,
-            &mut test_voting.data,
-            &test_voting.owner,
-            executable,
-            0,
+        // Columns line up by characters, "Übernahme" takes one byte more than it is wide
+        let vote_id = harness.create_vote(VoteConfig::from_labels("Budget".to_string(), vec!["Ja".to_string(), "Übernahme".to_string()]).unwrap(), &creator).unwrap();
+        for option_index in [0, 0, 1] {
+            let voter = Pubkey::new_unique();
+            harness.allow(vote_id, &creator, &voter).unwrap();
+            harness.cast(vote_id, &voter, option_index).unwrap();
+        }
+
+        let results = harness.voting.get_full_results_as(&creator, vote_id).unwrap();
+        assert_eq!(
+            results.to_string(),
+            "Vote 0\n\
+             \n\
+             Question 0: Budget\n\
+             Index  Option        Votes     Share\n\
+             \x20   0  Ja                2    66.67%\n\
+             \x20   1  Übernahme         1    33.33%\n\
+             \x20      Total             3\n"
         );
+    }
 
-        assert!(test_voting.voting.add_allowed_voter(0, voter1, &[account_info]).is_ok());
+    // Run one step of a fixed sequence of registry mutations, each step builds on the previous ones.
+    // Vote 0 is created up front for the invitation and the cancellation, step 0 creates vote 1
+    fn run_mutation(harness: &mut TestHarness, step: usize, creator: &Keypair, voter1: &Pubkey, voter2: &Pubkey) -> Result<(), ProgramError> {
+        if step == 7 {
+            return redeem_voucher(harness, Voucher { vote_id: VoteId(0), voter: *voter1, votes: 1, expiry: 2_000 }, creator);
+        }
 
-        // Set that voter1 has 1 vote
-        let new_voter = VoterInfo {
-            votes_left: 1,
-            delegate: None,
+        let caller = match step {
+            3 => *voter1,
+            4 => *voter2,
+            9 => harness.voting.admin,
+            _ => creator.pubkey(),
         };
-        test_voting.voting.votes.get_mut(&0).unwrap().allowed_voters.insert(voter1, new_voter);
-
-        let account_info_voter1 = AccountInfo::new(
-            &voter1,
-            is_signer,
-            is_writable,
-            &mut test_voting.lamports,
-            &mut test_voting.data,
-            &test_voting.owner,
-            executable,
-            0,
-        );
+        let info = harness.account_for(&caller);
+        let accounts = std::slice::from_ref(&info);
+
+        match step {
+            0 => harness.voting.create_vote_with_config(VoteConfig::from_labels("Vote".to_string(), vec!["Option 1".to_string()]).unwrap(), accounts).map(|_| ()),
+            1 => harness.voting.add_allowed_voter(VoteId(1), *voter1, accounts),
+            2 => harness.voting.add_allowed_voter(VoteId(1), *voter2, accounts),
+            3 => harness.voting.vote(VoteId(1), accounts, OptionIndex(0)),
+            4 => harness.voting.delegate_vote(VoteId(1), voter1, None, accounts),
+            5 => harness.voting.remove_allowed_voter(VoteId(1), voter2, accounts),
+            6 => harness.voting.close_vote(VoteId(1), accounts),
+            8 => harness.voting.cancel_vote(VoteId(0), "Withdrawn".to_string(), accounts),
+            _ => harness.voting.update_policy(CreationPolicy::default(), accounts),
+        }
+    }
 
-        // Perform the vote delegation
-        let result = test_voting.voting.delegate_vote(0, &delegate, &[account_info_voter1]);
+    #[test]
+    fn test_pause_blocks_mutations() {
+        let mut harness = TestHarness::new();
+        let admin = harness.voting.admin;
+        let creator = Keypair::new();
+        let voter1 = Pubkey::new_unique();
+        let voter2 = Pubkey::new_unique();
+        set_clock(1_000);
 
-        assert!(result.is_ok());
+        harness.create_vote(VoteConfig::from_labels("Invitation Vote".to_string(), vec!["Option 1".to_string()]).unwrap(), &creator.pubkey()).unwrap();
 
-        if let Some(voter_info) = test_voting.voting.votes.get_mut(&0).unwrap().allowed_voters.get(&voter1) {
-            assert_eq!(voter_info.votes_left, 0);
-            assert_eq!(voter_info.delegate, Some(delegate));
-        } else {
-            panic!("Voter1 information not found.");
+        let admin_info = harness.account_for(&admin);
+        assert!(harness.voting.pause(&[admin_info]).is_ok());
+        assert!(harness.voting.is_paused());
+
+        for step in 0..10 {
+            assert_eq!(run_mutation(&mut harness, step, &creator, &voter1, &voter2), Err(VoteError::ProgramPaused.into()), "step {}", step);
         }
 
-        if let Some(delegate_info) = test_voting.voting.votes.get_mut(&0).unwrap().allowed_voters.get(&delegate) {
-            assert_eq!(delegate_info.votes_left, 1);
-        } else {
-            panic!("Delegate information not found.");
+        // Reads keep working while paused
+        assert!(harness.voting.get_vote_summary(VoteId(0)).is_ok());
+        let viewer = Pubkey::new_unique();
+        assert!(harness.voting.get_results_as(&viewer, VoteId(0), None).is_ok());
+
+        let admin_info = harness.account_for(&admin);
+        assert!(harness.voting.unpause(&[admin_info]).is_ok());
+        assert!(!harness.voting.is_paused());
+
+        for step in 0..10 {
+            assert!(run_mutation(&mut harness, step, &creator, &voter1, &voter2).is_ok(), "step {}", step);
         }
     }
 
     #[test]
-    fn test_delegate_vote_not_allowed() {
-        let mut test_voting = TestVoting::new();
-        let creator = Pubkey::new_unique();
-        let voter1 = Pubkey::new_unique();
-        let delegate = Pubkey::new_unique();
-        let non_allowed_voter = Pubkey::new_unique();
-
-        test_voting.add_vote("Test Vote".to_string(), vec!["Option 1".to_string()], false, creator);
-
-        let is_signer = true;
-        let is_writable = false;
-        let executable = false;
-
-        // Create AccountInfo for the vote creator
-        let account_info = AccountInfo::new(
-            &creator,
-            is_signer,
-            is_writable,
-            &mut test_voting.lamports,
-            &mut test_voting.data,
-            &test_voting.owner,
-            executable,
-            0,
-        );
+    fn test_pause_admin_only() {
+        let mut harness = TestHarness::new();
+        let stranger = Pubkey::new_unique();
 
-        assert!(test_voting.voting.add_allowed_voter(0, voter1, &[account_info]).is_ok());
-
-        let account_info_non_allowed = AccountInfo::new(
-            &non_allowed_voter,
-            is_signer,
-            is_writable,
-            &mut test_voting.lamports,
-            &mut test_voting.data,
-            &test_voting.owner,
-            executable,
-            0,
-        );
+        let stranger_info = harness.account_for(&stranger);
+        assert!(harness.voting.pause(std::slice::from_ref(&stranger_info)).is_err());
+        assert!(!harness.voting.is_paused());
 
-        // Attempt to delegate vote to a non-allowed voter
-        assert!(test_voting.voting.delegate_vote(0, &delegate, &[account_info_non_allowed]).is_err());
+        harness.voting.paused = true;
+        assert!(harness.voting.unpause(&[stranger_info]).is_err());
+        assert!(harness.voting.is_paused());
     }
 
     #[test]
-    fn test_delegate_vote_no_votes_left() {
-        let mut test_voting = TestVoting::new();
+    fn test_creation_cooldown() {
+        let mut harness = TestHarness::new();
+        let admin = harness.voting.admin;
         let creator = Pubkey::new_unique();
-        let voter1 = Pubkey::new_unique();
-        let delegate = Pubkey::new_unique();
+        let other_creator = Pubkey::new_unique();
 
-        test_voting.add_vote("Test Vote".to_string(), vec!["Option 1".to_string()], false, creator);
-
-        let is_signer = true;
-        let is_writable = false;
-        let executable = false;
-
-        // Create AccountInfo for the vote creator
-        let account_info = AccountInfo::new(
-            &creator,
-            is_signer,
-            is_writable,
-            &mut test_voting.lamports,
-            &mut test_voting.data,
-            &test_voting.owner,
-            executable,
-            0,
-        );
+        let admin_info = harness.account_for(&admin);
+        assert!(harness.voting.update_policy(CreationPolicy { fee: None, cooldown_slots: Some(10), deposit: None }, &[admin_info]).is_ok());
 
-        assert!(test_voting.voting.add_allowed_voter(0, voter1, &[account_info]).is_ok());
+        set_slot(100);
+        harness.create_vote(VoteConfig::from_labels("Vote 1".to_string(), vec!["Option 1".to_string()]).unwrap(), &creator).unwrap();
 
-        // Set that voter1 has no votes
-        let new_voter = VoterInfo {
-            votes_left: 0,
-            delegate: None,
-        };
-        test_voting.voting.votes.get_mut(&0).unwrap().allowed_voters.insert(voter1, new_voter);
-
-        let account_info_voter1 = AccountInfo::new(
-            &voter1,
-            is_signer,
-            is_writable,
-            &mut test_voting.lamports,
-            &mut test_voting.data,
-            &test_voting.owner,
-            executable,
-            0,
+        // Still cooling down
+        set_slot(109);
+        let account_info = harness.account_for(&creator);
+        assert_eq!(
+            harness.voting.create_vote("Vote 2".to_string(), vec!["Option 1".to_string()], false, &[account_info]),
+            Err(ProgramError::from(VoteError::CreationCooldown))
         );
 
-        // Check that delegation fails as voter1 has no votes
-        assert!(test_voting.voting.delegate_vote(0, &delegate, &[account_info_voter1]).is_err());
-    }
-
-    #[test]
-    fn test_vote_nonexistent() {
-        let mut test_voting = TestVoting::new();
-        let voter1 = Pubkey::new_unique();
-
-        let account_info = AccountInfo::new(
-            &voter1,
-            true,
-            false,
-            &mut test_voting.lamports,
-            &mut test_voting.data,
-            &test_voting.owner,
-            false,
-            0,
-        );
+        // Other creators are not affected
+        harness.create_vote(VoteConfig::from_labels("Vote 2".to_string(), vec!["Option 1".to_string()]).unwrap(), &other_creator).unwrap();
 
-        // Attempt to vote on a non-existent voting
-        assert!(test_voting.voting.vote(999, &[account_info], 0).is_err());
+        set_slot(110);
+        harness.create_vote(VoteConfig::from_labels("Vote 3".to_string(), vec!["Option 1".to_string()]).unwrap(), &creator).unwrap();
+        assert_eq!(harness.voting.votes.len(), 3);
     }
 
     #[test]
-    fn test_vote_after_closing() {
-        let mut test_voting = TestVoting::new();
+    fn test_creation_fee() {
+        let mut harness = TestHarness::new();
+        let admin = harness.voting.admin;
         let creator = Pubkey::new_unique();
-        let voter1 = Pubkey::new_unique();
+        let treasury = Pubkey::new_unique();
+        install_stubs();
 
-        // Create a vote
-        test_voting.add_vote("Test Vote".to_string(), vec!["Option 1".to_string(), "Option 2".to_string()], false, creator);
+        let policy = CreationPolicy { fee: Some(CreationFee { lamports: 100, treasury }), cooldown_slots: None, deposit: None };
+        let admin_info = harness.account_for(&admin);
+        assert!(harness.voting.update_policy(policy, &[admin_info]).is_ok());
 
-        let is_signer = true;
-        let is_writable = false;
-        let executable = false;
+        harness.set_lamports(&creator, 1_000);
 
-        // Create AccountInfo for the vote creator
-        let account_info = AccountInfo::new(&creator, is_signer, is_writable, &mut test_voting.lamports, &mut test_voting.data, &test_voting.owner, executable, 0, );
+        let creator_info = harness.account_for(&creator);
+        let treasury_info = harness.account_for(&treasury);
+        let system_info = harness.account_for(&system_program::id());
 
-        // Add allowed voter
-        assert!(test_voting.voting.add_allowed_voter(0, voter1, &[account_info.clone()]).is_ok());
+        // The fee accounts are required
+        assert_eq!(
+            harness.voting.create_vote("Test Vote".to_string(), vec!["Option 1".to_string()], false, std::slice::from_ref(&creator_info)),
+            Err(ProgramError::NotEnoughAccountKeys)
+        );
 
-        // Close the vote
-        assert!(test_voting.voting.close_vote(0, &[account_info.clone()]).is_ok());
+        // The fee must go to the configured treasury
+        assert!(harness
+            .voting
+            .create_vote("Test Vote".to_string(), vec!["Option 1".to_string()], false, &[creator_info.clone(), creator_info.clone(), system_info.clone()])
+            .is_err());
 
-        // Now try to vote after the voting is closed
-        let account_info_voter1 = AccountInfo::new(&voter1, is_signer, is_writable, &mut test_voting.lamports, &mut test_voting.data, &test_voting.owner, executable, 0, );
+        assert!(harness
+            .voting
+            .create_vote("Test Vote".to_string(), vec!["Option 1".to_string()], false, &[creator_info.clone(), treasury_info.clone(), system_info])
+            .is_ok());
 
-        // Check that voting does not pass as it is closed
-        assert!(test_voting.voting.vote(0, &[account_info_voter1], 0).is_err());
+        assert_eq!(harness.lamports(&creator), 900);
+        assert_eq!(harness.lamports(&treasury), 100);
+        assert_eq!(harness.voting.votes.len(), 1);
+    }
+
+    #[test]
+    fn test_creation_deposit() {
+        let mut harness = TestHarness::new();
+        let admin = *harness.voting.admin();
+        let creator = Pubkey::new_unique();
+        let finalizer = Pubkey::new_unique();
+        let treasury = Pubkey::new_unique();
+        let program_id = Pubkey::new_unique();
+        let registry = Pubkey::new_unique();
+        harness.set_owner(&registry, &program_id);
+        set_clock(1_000);
+
+        // The vault of a vote has to stay rent exempt
+        let admin_info = harness.account_for(&admin);
+        let deposit = |lamports| CreationPolicy { deposit: Some(CreationDeposit { lamports, treasury }), ..CreationPolicy::default() };
+        let rent_exempt = Rent::default().minimum_balance(0);
+        assert_eq!(harness.voting.update_policy(deposit(rent_exempt - 1), std::slice::from_ref(&admin_info)), Err(ProgramError::InvalidArgument));
+        assert!(harness.voting.update_policy(deposit(rent_exempt), &[admin_info]).is_ok());
+
+        let config = |deadline| VoteConfig { deadline, ..VoteConfig::from_labels("Deposit Vote".to_string(), vec!["Option 1".to_string()]).unwrap() };
+        let vault = |vote_id| get_vote_address(&program_id, &registry, vote_id).0;
+        let accounts = |harness: &mut TestHarness, keys: &[Pubkey]| keys.iter().map(|key| harness.account_for(key)).collect::<Vec<_>>();
+        let system = system_program::id();
+
+        // Creation needs the vault of the new vote and enough lamports for the deposit
+        harness.set_lamports(&creator, rent_exempt * 2);
+        let create_accounts = accounts(&mut harness, &[creator, registry, vault(VoteId(1)), system]);
+        assert_eq!(harness.voting.create_vote_with_config(config(None), &create_accounts), Err(ProgramError::InvalidSeeds));
+        let create_accounts = accounts(&mut harness, &[creator, registry, vault(VoteId(0)), system]);
+        assert_eq!(harness.voting.create_vote_with_config(config(None), &create_accounts[..3]), Err(ProgramError::NotEnoughAccountKeys));
+        let refunded_id = harness.voting.create_vote_with_config(config(None), &create_accounts).unwrap();
+        let create_accounts = accounts(&mut harness, &[creator, registry, vault(VoteId(1)), system]);
+        let forfeited_id = harness.voting.create_vote_with_config(config(Some(2_000)), &create_accounts).unwrap();
+        assert_eq!(harness.lamports(&creator), 0);
+        let create_accounts = accounts(&mut harness, &[creator, registry, vault(VoteId(2)), system]);
+        assert_eq!(harness.voting.create_vote_with_config(config(None), &create_accounts), Err(ProgramError::InsufficientFunds));
+        assert_eq!(harness.voting.votes.len(), 2);
+
+        let held = Some(CreatorDeposit { lamports: rent_exempt, vault: vault(VoteId(0)), registry, bump: get_vote_address(&program_id, &registry, VoteId(0)).1, treasury, status: DepositStatus::Held });
+        assert_eq!(harness.voting.get_vote_summary(refunded_id).unwrap().deposit, held);
+        assert_eq!(harness.lamports(&vault(VoteId(0))), rent_exempt);
+
+        // The creator ending the vote before its deadline gets the deposit back, to their own account only
+        assert_eq!(harness.voting.cancel_vote_as(&creator, refunded_id, "Withdrawn".to_string()), Err(ProgramError::NotEnoughAccountKeys));
+        let close_accounts = accounts(&mut harness, &[creator, vault(VoteId(0)), treasury, system]);
+        assert_eq!(harness.voting.close_vote(refunded_id, &close_accounts), Err(ProgramError::InvalidArgument));
+        assert_eq!(harness.voting.get_vote_summary(refunded_id).unwrap().status, VoteStatus::Open);
+
+        // The vault named again as the recipient is refused before anything moves
+        let close_accounts = accounts(&mut harness, &[creator, vault(VoteId(0)), vault(VoteId(0)), system]);
+        assert_eq!(harness.voting.close_vote(refunded_id, &close_accounts), Err(VoteError::DuplicateAccount.into()));
+        assert_eq!((harness.lamports(&vault(VoteId(0))), harness.lamports(&creator)), (rent_exempt, 0));
+        let create_accounts = accounts(&mut harness, &[creator, registry, registry, system]);
+        assert_eq!(harness.voting.create_vote_with_config(config(None), &create_accounts), Err(VoteError::DuplicateAccount.into()));
+
+        let close_accounts = accounts(&mut harness, &[creator, vault(VoteId(0)), creator, system]);
+        harness.voting.close_vote(refunded_id, &close_accounts).unwrap();
+        assert_eq!(harness.lamports(&creator), rent_exempt);
+        assert_eq!(harness.lamports(&vault(VoteId(0))), 0);
+        assert_eq!(harness.voting.get_vote_summary(refunded_id).unwrap().deposit.map(|deposit| deposit.status), Some(DepositStatus::Refunded));
+
+        // Cancelling refunds as well
+        let create_accounts = accounts(&mut harness, &[creator, registry, vault(VoteId(2)), system]);
+        let cancelled_id = harness.voting.create_vote_with_config(config(None), &create_accounts).unwrap();
+        let cancel_accounts = accounts(&mut harness, &[creator, vault(VoteId(2)), creator, system]);
+        harness.voting.cancel_vote(cancelled_id, "Withdrawn".to_string(), &cancel_accounts).unwrap();
+        assert_eq!(harness.voting.get_vote_summary(cancelled_id).unwrap().deposit.map(|deposit| deposit.status), Some(DepositStatus::Refunded));
+        assert_eq!(harness.lamports(&creator), rent_exempt);
+
+        // Finalizing after the deadline forfeits the deposit to the treasury, the bulk finalize leaves the vote alone
+        set_clock(2_001);
+        let finalizer_info = harness.account_for(&finalizer);
+        assert_eq!(harness.voting.finalize_all_expired(10, &[finalizer_info]), Ok(0));
+        let finalize_accounts = accounts(&mut harness, &[finalizer, vault(VoteId(1)), creator, system]);
+        assert_eq!(harness.voting.finalize_expired_vote(forfeited_id, &finalize_accounts), Err(ProgramError::InvalidArgument));
+        let finalize_accounts = accounts(&mut harness, &[finalizer, vault(VoteId(1)), treasury, system]);
+        harness.voting.finalize_expired_vote(forfeited_id, &finalize_accounts).unwrap();
+        assert_eq!(harness.lamports(&treasury), rent_exempt);
+        assert_eq!(harness.lamports(&creator), rent_exempt);
+        assert_eq!(harness.voting.get_vote_summary(forfeited_id).unwrap().deposit.map(|deposit| deposit.status), Some(DepositStatus::Forfeited));
+        assert_eq!(harness.voting.get_registry_stats().currently_open, 0);
     }
 }
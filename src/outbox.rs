@@ -0,0 +1,210 @@
+// Outbox account of a registry, a bounded queue of the outcomes of closed votes. Off-chain workers that cannot
+// index logs poll it and acknowledge what they handled, which frees the slots of those entries
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::account_info::AccountInfo;
+use solana_program::program_error::ProgramError;
+
+use crate::{Outcome, VoteError, VoteId};
+
+pub const OUTBOX_DISCRIMINATOR: [u8; 8] = *b"svoutbox";
+
+// Largest outbox capacity, a full outbox stays below the size an account created through CPI may have
+pub const MAX_OUTBOX_CAPACITY: u32 = 256;
+
+// What an outbox does with a closure when every slot holds an unconsumed entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub enum OutboxPolicy {
+    RejectWhenFull, // The closure fails with `OutboxFull` until the worker catches up
+    EvictOldest,    // The oldest unconsumed entry makes room and `overflow_count` goes up
+}
+
+// Outcome of the first question, fixed size so every entry takes the same room. Ties only keep their size,
+// the tied options are in the results of the vote
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub enum OutboxOutcome {
+    Winner { option_index: u32 },
+    Tie { options: u32 },
+    NoVotes,
+    NoThresholdMet { leading_option: u32, share_bps: u16 },
+    QuorumNotMet { total_votes: u32 },
+}
+
+impl From<&Outcome> for OutboxOutcome {
+    fn from(outcome: &Outcome) -> Self {
+        match outcome {
+            Outcome::Winner { option_index } => Self::Winner { option_index: *option_index as u32 },
+            Outcome::Tie { option_indices } => Self::Tie { options: option_indices.len() as u32 },
+            Outcome::NoVotes => Self::NoVotes,
+            Outcome::NoThresholdMet { leading_option, share_bps } => Self::NoThresholdMet { leading_option: *leading_option as u32, share_bps: *share_bps },
+            Outcome::QuorumNotMet { total_votes } => Self::QuorumNotMet { total_votes: *total_votes },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct OutboxEntry {
+    pub vote_id: VoteId,
+    pub outcome: OutboxOutcome,
+    pub closed_at: i64,
+    pub consumed: bool, // Acknowledged by the worker, the slot can be reused
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct Outbox {
+    pub capacity: u32,
+    pub policy: OutboxPolicy,
+    pub overflow_count: u64, // Unconsumed entries evicted under `EvictOldest`
+    pub entries: Vec<OutboxEntry>, // In append order
+}
+
+impl Outbox {
+    pub fn new(capacity: u32, policy: OutboxPolicy) -> Self {
+        Self { capacity, policy, overflow_count: 0, entries: Vec::new() }
+    }
+
+    // Account size of an outbox holding `capacity` entries of the largest outcome
+    pub fn space(capacity: u32) -> usize {
+        let entry = OutboxEntry { vote_id: VoteId(0), outcome: OutboxOutcome::NoThresholdMet { leading_option: 0, share_bps: 0 }, closed_at: 0, consumed: false };
+        let mut prototype = Self::new(0, OutboxPolicy::RejectWhenFull);
+        prototype.entries = vec![entry; capacity as usize];
+
+        OUTBOX_DISCRIMINATOR.len() + borsh::object_length(&prototype).unwrap_or(usize::MAX)
+    }
+
+    // Entries the worker has not acknowledged yet, oldest first
+    pub fn pending(&self) -> impl Iterator<Item = &OutboxEntry> {
+        self.entries.iter().filter(|entry| !entry.consumed)
+    }
+
+    // Return error if the next entry would be refused, so a closure can be checked before the vote changes
+    pub fn ensure_room(&self) -> Result<(), ProgramError> {
+        let full = self.entries.len() >= self.capacity as usize && self.entries.iter().all(|entry| !entry.consumed);
+        if full && self.policy == OutboxPolicy::RejectWhenFull {
+            return Err(VoteError::OutboxFull.into());
+        }
+        Ok(())
+    }
+
+    // Append an entry, reusing the oldest consumed slot of a full outbox or applying the policy when there is none
+    pub fn push(&mut self, entry: OutboxEntry) -> Result<(), ProgramError> {
+        self.ensure_room()?;
+
+        if self.entries.len() >= self.capacity as usize {
+            match self.entries.iter().position(|entry| entry.consumed) {
+                Some(index) => {
+                    self.entries.remove(index);
+                }
+                None => {
+                    self.entries.remove(0); // Evict the oldest unconsumed entry
+                    self.overflow_count = self.overflow_count.checked_add(1).ok_or(ProgramError::ArithmeticOverflow)?;
+                }
+            }
+        }
+
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    // Mark the unconsumed entries of the votes consumed, returns how many. Entries acknowledged before or
+    // evicted are skipped, so a worker can acknowledge again after a failed transaction
+    pub fn ack(&mut self, vote_ids: &[VoteId]) -> u32 {
+        let mut acked = 0;
+        for entry in self.entries.iter_mut().filter(|entry| !entry.consumed && vote_ids.contains(&entry.vote_id)) {
+            entry.consumed = true;
+            acked += 1;
+        }
+        acked
+    }
+
+    pub fn save(&self, account: &AccountInfo) -> Result<(), ProgramError> {
+        let mut serialized = OUTBOX_DISCRIMINATOR.to_vec();
+        self.serialize(&mut serialized)?;
+
+        let mut data = account.try_borrow_mut_data()?;
+        if serialized.len() > data.len() {
+            return Err(ProgramError::AccountDataTooSmall); // Return error if the outbox does not fit
+        }
+
+        data[..serialized.len()].copy_from_slice(&serialized);
+
+        Ok(())
+    }
+
+    // Decode the data of an outbox account, e.g. fetched by a worker
+    pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        if !data.starts_with(&OUTBOX_DISCRIMINATOR) {
+            return Err(ProgramError::UninitializedAccount); // Return error if the outbox was never written
+        }
+
+        Ok(Self::deserialize(&mut &data[OUTBOX_DISCRIMINATOR.len()..])?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(vote_id: u64) -> OutboxEntry {
+        OutboxEntry { vote_id: VoteId(vote_id), outcome: OutboxOutcome::Winner { option_index: 1 }, closed_at: 1_000 + vote_id as i64, consumed: false }
+    }
+
+    fn pending_ids(outbox: &Outbox) -> Vec<u64> {
+        outbox.pending().map(|entry| entry.vote_id.0).collect()
+    }
+
+    #[test]
+    fn test_outbox_queue() {
+        // Appends keep their order, acknowledged entries leave the pending ones
+        let mut outbox = Outbox::new(3, OutboxPolicy::RejectWhenFull);
+        for vote_id in 0..3 {
+            outbox.push(entry(vote_id)).unwrap();
+        }
+        assert_eq!(pending_ids(&outbox), vec![0, 1, 2]);
+
+        // A full outbox refuses the next closure under the default policy
+        assert_eq!(outbox.ensure_room(), Err(VoteError::OutboxFull.into()));
+        assert_eq!(outbox.push(entry(3)), Err(VoteError::OutboxFull.into()));
+
+        assert_eq!(outbox.ack(&[VoteId(1), VoteId(7)]), 1);
+        assert_eq!(outbox.ack(&[VoteId(1)]), 0); // Acknowledging again changes nothing
+        assert_eq!(pending_ids(&outbox), vec![0, 2]);
+
+        // The consumed slot is reused, nothing unconsumed is lost
+        outbox.push(entry(3)).unwrap();
+        assert_eq!(outbox.entries.len(), 3);
+        assert_eq!(pending_ids(&outbox), vec![0, 2, 3]);
+        assert_eq!(outbox.overflow_count, 0);
+        assert_eq!(outbox.push(entry(4)), Err(VoteError::OutboxFull.into()));
+    }
+
+    #[test]
+    fn test_outbox_eviction() {
+        let mut outbox = Outbox::new(2, OutboxPolicy::EvictOldest);
+        for vote_id in 0..5 {
+            outbox.push(entry(vote_id)).unwrap();
+        }
+        assert_eq!(pending_ids(&outbox), vec![3, 4]);
+        assert_eq!(outbox.overflow_count, 3);
+
+        // Consumed entries go first, the counter only counts entries the worker never saw
+        outbox.ack(&[VoteId(4)]);
+        outbox.push(entry(5)).unwrap();
+        assert_eq!((pending_ids(&outbox), outbox.overflow_count), (vec![3, 5], 3));
+    }
+
+    #[test]
+    fn test_outbox_space() {
+        let mut outbox = Outbox::new(MAX_OUTBOX_CAPACITY, OutboxPolicy::EvictOldest);
+        for vote_id in 0..MAX_OUTBOX_CAPACITY as u64 {
+            let outcome = OutboxOutcome::NoThresholdMet { leading_option: u32::MAX, share_bps: u16::MAX };
+            outbox.push(OutboxEntry { outcome, ..entry(vote_id) }).unwrap();
+        }
+
+        let mut data = OUTBOX_DISCRIMINATOR.to_vec();
+        data.extend(borsh::to_vec(&outbox).unwrap());
+        assert_eq!(data.len(), Outbox::space(MAX_OUTBOX_CAPACITY));
+        assert!(data.len() <= solana_program::entrypoint::MAX_PERMITTED_DATA_INCREASE);
+        assert_eq!(Outbox::unpack(&data), Ok(outbox));
+        assert_eq!(Outbox::unpack(&data[1..]), Err(ProgramError::UninitializedAccount));
+    }
+}
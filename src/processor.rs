@@ -0,0 +1,1567 @@
+use borsh::BorshSerialize;
+use solana_program::account_info::{next_account_info, AccountInfo};
+use solana_program::entrypoint::ProgramResult;
+use solana_program::msg;
+use solana_program::program::{invoke_signed, set_return_data, MAX_RETURN_DATA};
+use solana_program::program_option::COption;
+use solana_program::program_pack::Pack;
+use solana_program::rent::Rent;
+use solana_program::sysvar::Sysvar;
+use solana_program::{program_error::ProgramError, pubkey::Pubkey};
+use solana_program::{system_instruction, system_program};
+use std::collections::HashSet;
+
+use crate::instruction::VoteInstruction;
+use crate::{check_unique_accounts, current_timestamp, get_outbox_address, get_receipt_address, get_shard_address, get_vote_address, log_error, OptionIndex, Outbox, RegistryShard, ResultsBatchResponse, ShardEntry, Visibility, VoteError, VoteId, VoteReceipt, VoteSlot, VoteType, Voting, MAX_OUTBOX_CAPACITY};
+
+// Log the reason of an error of the voting program before it is returned, `context` names the vote and the caller
+fn report(error: ProgramError, context: &str) -> ProgramError {
+    if let ProgramError::Custom(code) = error {
+        if let Ok(vote_error) = VoteError::try_from(code) {
+            log_error(&vote_error, context);
+        }
+    }
+
+    error
+}
+
+// Check that the registry account belongs to this program
+fn check_registry_owner(program_id: &Pubkey, registry: &AccountInfo) -> ProgramResult {
+    if registry.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    Ok(())
+}
+
+// The account acting in the instruction at the position it documents. A short account list is reported by the
+// role of the missing account, instead of taking whatever account comes first
+fn caller_account<'a, 'info>(instruction: &VoteInstruction, accounts: &'a [AccountInfo<'info>]) -> Result<&'a AccountInfo<'info>, ProgramError> {
+    let missing = if instruction.is_voter_instruction() { VoteError::MissingVoterAccount } else { VoteError::MissingCreatorAccount };
+    instruction.caller_index().and_then(|index| accounts.get(index)).ok_or(missing.into())
+}
+
+// Load the registry for an instruction on one of its votes, refusing a vote with inconsistent state before anything is written
+fn load_for_vote(registry: &AccountInfo, vote_id: VoteId) -> Result<Voting, ProgramError> {
+    let voting = Voting::load(registry)?;
+    voting.check_vote(vote_id).map_err(|error| report(error, &format!("vote {}, registry {}", vote_id, registry.key)))?;
+    Ok(voting)
+}
+
+// Load the registry for a ballot or delegation, decoding only the vote when the others cannot matter. Save it
+// with `Voting::save_vote_slot`
+fn load_vote_slot(registry: &AccountInfo, vote_id: VoteId) -> Result<(Voting, Option<VoteSlot>), ProgramError> {
+    let (voting, slot) = Voting::load_vote_slot(registry, vote_id)?;
+    voting.check_vote(vote_id).map_err(|error| report(error, &format!("vote {}, registry {}", vote_id, registry.key)))?;
+    Ok((voting, slot))
+}
+
+// Answer a read-only query through the return data, so calling programs can read it after the CPI
+// with `responses::decode_return_data`. The query gets the accounts after the registry, the read methods of
+// `Voting` refuse restricted data to a viewer who did not sign with `MissingRequiredSignature`
+fn answer_query<T: BorshSerialize>(program_id: &Pubkey, accounts: &[AccountInfo], query: impl FnOnce(&Voting, &[AccountInfo]) -> Result<T, ProgramError>) -> ProgramResult {
+    let registry = next_account_info(&mut accounts.iter())?;
+    check_registry_owner(program_id, registry)?;
+
+    let voting = Voting::load(registry)?;
+    let answer = borsh::to_vec(&query(&voting, &accounts[1..])?).map_err(|_| ProgramError::InvalidAccountData)?;
+    if answer.len() > MAX_RETURN_DATA {
+        msg!("The answer takes {} bytes, return data holds at most {}", answer.len(), MAX_RETURN_DATA);
+        return Err(VoteError::ResponseTooLarge.into());
+    }
+
+    set_return_data(&answer);
+    Ok(())
+}
+
+// Create the receipt account of a voter's first ballot. The receipt address is a PDA of the program,
+// so only this handler can create it and it can only be created once
+fn create_receipt(program_id: &Pubkey, accounts: [&AccountInfo; 3], vote_address: &Pubkey, bump: u8, receipt: &VoteReceipt) -> ProgramResult {
+    let [receipt_account, payer, system_program_account] = accounts;
+
+    if *system_program_account.key != system_program::id() {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let space = VoteReceipt::LEN;
+    let lamports = Rent::get()?.minimum_balance(space);
+
+    invoke_signed(
+        &system_instruction::create_account(payer.key, receipt_account.key, lamports, space as u64, program_id),
+        &[payer.clone(), receipt_account.clone(), system_program_account.clone()],
+        &[&[b"receipt", vote_address.as_ref(), receipt.voter.as_ref(), &[bump]]],
+    )?;
+
+    receipt.serialize(&mut &mut receipt_account.try_borrow_mut_data()?[..])?;
+
+    Ok(())
+}
+
+// List a new vote in the account of its shard. The vote opening a shard creates the account, funded by the creator
+fn record_in_shard(program_id: &Pubkey, accounts: [&AccountInfo; 4], voting: &Voting, vote_id: VoteId) -> ProgramResult {
+    let [registry, creator, shard_account, system_program_account] = accounts;
+    check_unique_accounts(&[registry.key, creator.key, shard_account.key])?;
+
+    let shard_index = voting.shard_for_vote_id(vote_id).ok_or(ProgramError::InvalidArgument)?;
+    let (shard_address, bump) = get_shard_address(program_id, registry.key, shard_index);
+    if *shard_account.key != shard_address {
+        return Err(ProgramError::InvalidSeeds); // Return error if the account is not the shard of the vote
+    }
+
+    let mut shard = if shard_account.data_is_empty() {
+        if *system_program_account.key != system_program::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let space = RegistryShard::space(voting.shard_capacity().ok_or(ProgramError::InvalidArgument)?);
+        let lamports = Rent::get()?.minimum_balance(space);
+        invoke_signed(
+            &system_instruction::create_account(creator.key, shard_account.key, lamports, space as u64, program_id),
+            &[creator.clone(), shard_account.clone(), system_program_account.clone()],
+            &[&[b"registry", registry.key.as_ref(), &shard_index.to_le_bytes(), &[bump]]],
+        )?;
+        RegistryShard::new(*registry.key, shard_index)
+    } else {
+        RegistryShard::load(shard_account, program_id)?
+    };
+
+    let vote = voting.votes.get(&vote_id).ok_or(ProgramError::InvalidArgument)?;
+    shard.entries.push(ShardEntry { id: vote_id, creator: vote.creator, title: vote.title.clone() });
+    shard.save(shard_account)
+}
+
+// Ballot a cast instruction applies, with the voter it is counted for
+struct CastBallot<'a> {
+    vote_id: VoteId,
+    voter: &'a Pubkey,
+    question_index: u32,
+    option_index: OptionIndex,
+}
+
+// Burn what a ballot of a token-burn vote costs, with the vote address as the approved delegate of the token
+// account. Accounts: [token account of the voter, mint, vote address, token program]
+fn burn_ballot_cost(program_id: &Pubkey, registry: &Pubkey, ballot: &CastBallot, cost: (Pubkey, u64), accounts: &[AccountInfo]) -> ProgramResult {
+    let (mint, burn_amount) = cost;
+    let [token_account, mint_account, vote_account, token_program, ..] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys); // Return error if the token accounts are missing
+    };
+    if *token_program.key != spl_token::id() || *token_account.owner != spl_token::id() {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let (vote_address, bump) = get_vote_address(program_id, registry, ballot.vote_id);
+    if *vote_account.key != vote_address {
+        return Err(ProgramError::InvalidSeeds);
+    }
+
+    let holding = spl_token::state::Account::unpack(&token_account.try_borrow_data()?)?;
+    if holding.mint != mint || *mint_account.key != mint {
+        return Err(VoteError::WrongTokenMint.into());
+    }
+    if holding.owner != *ballot.voter {
+        return Err(ProgramError::InvalidArgument); // Return error if the token account is not the voter's
+    }
+    if holding.amount < burn_amount {
+        return Err(VoteError::InsufficientTokens.into());
+    }
+    if holding.delegate != COption::Some(vote_address) || holding.delegated_amount < burn_amount {
+        return Err(VoteError::BurnNotApproved.into());
+    }
+
+    invoke_signed(
+        &spl_token::instruction::burn(token_program.key, token_account.key, mint_account.key, &vote_address, &[], burn_amount)?,
+        &[token_account.clone(), mint_account.clone(), vote_account.clone(), token_program.clone()],
+        &[&[b"vote", registry.as_ref(), &ballot.vote_id.to_le_bytes(), &[bump]]],
+    )
+}
+
+// Apply a ballot with `cast` and leave the receipt of the voter's first ballot, funded by the payer
+// Ballots of token-burn votes burn their cost from the `burn_accounts` before they are tallied, instructions
+// without them cannot vote in such votes
+fn cast_ballot(program_id: &Pubkey, accounts: [&AccountInfo; 4], ballot: CastBallot, burn_accounts: Option<&[AccountInfo]>, cast: impl FnOnce(&mut Voting) -> ProgramResult) -> ProgramResult {
+    let [registry, payer, receipt_account, system_program_account] = accounts;
+    let CastBallot { vote_id, voter, question_index, option_index } = ballot;
+    check_unique_accounts(&[registry.key, payer.key, receipt_account.key])?;
+
+    let (vote_address, _) = get_vote_address(program_id, registry.key, vote_id);
+    let (receipt_address, bump) = get_receipt_address(program_id, &vote_address, voter);
+    if *receipt_account.key != receipt_address {
+        return Err(ProgramError::InvalidSeeds); // Return error if the receipt account is not the voter's
+    }
+
+    let (mut voting, slot) = load_vote_slot(registry, vote_id)?;
+    let summary = voting.get_vote_summary(vote_id)?;
+
+    // An existing receipt means the voter already cast their ballot. Only the questions of a
+    // multi-question vote share the receipt of the first one
+    let has_receipt = !receipt_account.data_is_empty();
+    if has_receipt && summary.questions_count == 1 {
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+
+    if let VoteType::TokenBurn { mint, burn_amount } = voting.get_vote_type(vote_id)? {
+        let burn_accounts = burn_accounts.ok_or(ProgramError::InvalidArgument)?; // Return error if the instruction cannot burn
+        burn_ballot_cost(program_id, registry.key, &ballot, (mint, burn_amount), burn_accounts).map_err(|error| report(error, &format!("vote {}, caller {}", vote_id, voter)))?;
+    }
+
+    cast(&mut voting).map_err(|error| report(error, &format!("vote {}, caller {}", vote_id, voter)))?;
+
+    if !has_receipt {
+        let receipt = VoteReceipt {
+            vote_id,
+            voter: *voter,
+            question_index,
+            option_index: (summary.results_visibility == Visibility::Public).then_some(option_index),
+            timestamp: current_timestamp()?,
+        };
+        create_receipt(program_id, [receipt_account, payer, system_program_account], &vote_address, bump, &receipt)?;
+    }
+
+    voting.save_vote_slot(registry, slot)
+}
+
+pub fn process_instruction(program_id: &Pubkey, accounts: &[AccountInfo], instruction_data: &[u8]) -> ProgramResult {
+    let instruction = VoteInstruction::unpack(instruction_data)?;
+
+    // Accounts sit at the positions the instruction documents, the ones following the registry are read in order
+    let caller = caller_account(&instruction, accounts);
+    let registry = accounts.get(instruction.registry_index()).ok_or(ProgramError::NotEnoughAccountKeys);
+    let account_iter = &mut accounts.iter().skip(instruction.registry_index() + 1);
+
+    match instruction {
+        VoteInstruction::InitializeRegistry { admin } => {
+            let registry = registry?;
+            check_registry_owner(program_id, registry)?;
+
+            Voting::initialize_registry(registry, admin)
+        }
+        VoteInstruction::UpdatePolicy { policy } => {
+            let admin = caller?;
+            let registry = registry?;
+            check_registry_owner(program_id, registry)?;
+
+            let mut voting = Voting::load(registry)?;
+            voting
+                .update_policy(policy, std::slice::from_ref(admin))
+                .map_err(|error| report(error, &format!("registry {}, caller {}", registry.key, admin.key)))?;
+            voting.save(registry)
+        }
+        VoteInstruction::Pause | VoteInstruction::Unpause => {
+            let admin = caller?;
+            let registry = registry?;
+            check_registry_owner(program_id, registry)?;
+
+            let mut voting = Voting::load(registry)?;
+            let result = if instruction == VoteInstruction::Pause {
+                voting.pause(std::slice::from_ref(admin))
+            } else {
+                voting.unpause(std::slice::from_ref(admin))
+            };
+            result.map_err(|error| report(error, &format!("registry {}, caller {}", registry.key, admin.key)))?;
+            voting.save(registry)
+        }
+        VoteInstruction::CastVote { .. } | VoteInstruction::CastVoteWithComment { .. } => {
+            let (vote_id, question_index, option_index, comment) = match &instruction {
+                VoteInstruction::CastVoteWithComment { vote_id, question_index, option_index, comment } => (*vote_id, *question_index, *option_index, Some(comment.clone())),
+                VoteInstruction::CastVote { vote_id, question_index, option_index } => (*vote_id, *question_index, *option_index, None),
+                _ => unreachable!(),
+            };
+            let voter = caller?;
+            let registry = registry?;
+            let payer = accounts.first().ok_or(ProgramError::NotEnoughAccountKeys)?; // Comes before the voter
+            let receipt_account = next_account_info(account_iter)?;
+            let system_program_account = next_account_info(account_iter)?;
+            check_registry_owner(program_id, registry)?;
+
+            if !voter.is_signer {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+
+            let burn_accounts: Vec<AccountInfo> = account_iter.cloned().collect();
+
+            // The eligibility check of an open vote reads the voter's own account, which the ballot already holds
+            let voter_accounts = [voter.clone(), voter.clone()];
+            let ballot = CastBallot { vote_id, voter: voter.key, question_index, option_index };
+            cast_ballot(program_id, [registry, payer, receipt_account, system_program_account], ballot, Some(&burn_accounts), |voting| match comment {
+                Some(comment) => voting.vote_with_comment(vote_id, &voter_accounts, question_index as usize, option_index, comment),
+                None => voting.vote_on_question(vote_id, &voter_accounts, question_index as usize, option_index),
+            })
+        }
+        VoteInstruction::CastVotes { ballots } => {
+            let voter = caller?;
+            let registry = registry?;
+            let payer = accounts.first().ok_or(ProgramError::NotEnoughAccountKeys)?; // Comes before the voter
+            let system_program_account = next_account_info(account_iter)?;
+            let receipt_accounts: Vec<&AccountInfo> = account_iter.take(ballots.len()).collect();
+            if receipt_accounts.len() < ballots.len() {
+                return Err(ProgramError::NotEnoughAccountKeys);
+            }
+            check_registry_owner(program_id, registry)?;
+
+            if !voter.is_signer {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+
+            // Receipts follow the rules of single ballots, a single-question vote takes one ballot of the voter
+            let mut voting = Voting::load(registry)?;
+            let mut seeds = Vec::with_capacity(ballots.len());
+            let mut single_question_votes = HashSet::new();
+            for ((vote_id, _), receipt_account) in ballots.iter().zip(&receipt_accounts) {
+                voting.check_vote(*vote_id).map_err(|error| report(error, &format!("vote {}, registry {}", vote_id, registry.key)))?;
+                if let VoteType::TokenBurn { .. } = voting.get_vote_type(*vote_id)? {
+                    return Err(ProgramError::InvalidArgument); // Return error if the ballot would skip its burn
+                }
+
+                let (vote_address, _) = get_vote_address(program_id, registry.key, *vote_id);
+                let (receipt_address, bump) = get_receipt_address(program_id, &vote_address, voter.key);
+                if *receipt_account.key != receipt_address {
+                    return Err(ProgramError::InvalidSeeds); // Return error if the receipt account is not the voter's
+                }
+                if voting.get_vote_summary(*vote_id)?.questions_count == 1 && (!receipt_account.data_is_empty() || !single_question_votes.insert(*vote_id)) {
+                    return Err(ProgramError::AccountAlreadyInitialized);
+                }
+                seeds.push((vote_address, bump));
+            }
+
+            voting.vote_many(&ballots, std::slice::from_ref(voter)).map_err(|error| report(error, &format!("registry {}, caller {}", registry.key, voter.key)))?;
+
+            // A vote appearing twice gets a single receipt
+            let timestamp = current_timestamp()?;
+            for (((vote_id, option_index), receipt_account), (vote_address, bump)) in ballots.iter().zip(receipt_accounts).zip(seeds) {
+                if receipt_account.data_is_empty() {
+                    let summary = voting.get_vote_summary(*vote_id)?;
+                    let receipt = VoteReceipt {
+                        vote_id: *vote_id,
+                        voter: *voter.key,
+                        question_index: 0,
+                        option_index: (summary.results_visibility == Visibility::Public).then_some(*option_index),
+                        timestamp,
+                    };
+                    create_receipt(program_id, [receipt_account, payer, system_program_account], &vote_address, bump, &receipt)?;
+                }
+            }
+
+            voting.save(registry)
+        }
+        VoteInstruction::CastVoteSigned { ballot: signed, signature } => {
+            let relayer = caller?;
+            let registry = registry?;
+            let receipt_account = next_account_info(account_iter)?;
+            let system_program_account = next_account_info(account_iter)?;
+            let instructions_sysvar = next_account_info(account_iter)?;
+            check_registry_owner(program_id, registry)?;
+
+            let ballot = CastBallot { vote_id: signed.vote_id, voter: &signed.voter, question_index: 0, option_index: signed.option_index };
+            cast_ballot(program_id, [registry, relayer, receipt_account, system_program_account], ballot, None, |voting| {
+                voting.cast_vote_signed(&[relayer.clone(), instructions_sysvar.clone(), registry.clone()], signed, signature)
+            })
+        }
+        VoteInstruction::CreateVote { config } => {
+            let creator = caller?;
+            let registry = registry?;
+            check_registry_owner(program_id, registry)?;
+
+            if !creator.is_signer {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+
+            // The creator is followed by the optional state account, the fee and the deposit accounts
+            let mut vote_accounts = vec![creator.clone()];
+            vote_accounts.extend(account_iter.cloned());
+
+            // The shard accounts come last and stay with the processor
+            let mut voting = Voting::load(registry)?;
+            let shard_accounts = if voting.shard_capacity().is_some() {
+                if vote_accounts.len() < 3 {
+                    return Err(ProgramError::NotEnoughAccountKeys); // Return error if the shard accounts are missing
+                }
+                vote_accounts.split_off(vote_accounts.len() - 2)
+            } else {
+                Vec::new()
+            };
+
+            // The library derives the vault of the deposit from the registry, which the instruction lists only once
+            if voting.get_creation_policy().deposit.is_some() && vote_accounts.len() >= 3 {
+                vote_accounts.insert(vote_accounts.len() - 2, registry.clone());
+            }
+            let vote_id = voting
+                .create_vote_with_config(*config, &vote_accounts)
+                .map_err(|error| report(error, &format!("registry {}, caller {}", registry.key, creator.key)))?;
+            if let [shard_account, system_program_account] = &shard_accounts[..] {
+                record_in_shard(program_id, [registry, creator, shard_account, system_program_account], &voting, vote_id)?;
+            }
+            msg!("Created vote {}", vote_id);
+            voting.save(registry)
+        }
+        VoteInstruction::AddAllowedVoter { vote_id, voter } => {
+            let creator = caller?;
+            let registry = registry?;
+            check_registry_owner(program_id, registry)?;
+
+            if !creator.is_signer {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+
+            let mut voting = load_for_vote(registry, vote_id)?;
+            voting
+                .add_allowed_voter_as(creator.key, vote_id, voter)
+                .map_err(|error| report(error, &format!("vote {}, caller {}", vote_id, creator.key)))?;
+            voting.save(registry)
+        }
+        VoteInstruction::DelegateVote { vote_id, delegate, expires_at } => {
+            let delegator = caller?;
+            let registry = registry?;
+            check_registry_owner(program_id, registry)?;
+
+            if !delegator.is_signer {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+
+            let (mut voting, slot) = load_vote_slot(registry, vote_id)?;
+            voting
+                .delegate_vote_as(delegator.key, vote_id, &delegate, expires_at)
+                .map_err(|error| report(error, &format!("vote {}, caller {}", vote_id, delegator.key)))?;
+            voting.save_vote_slot(registry, slot)
+        }
+        VoteInstruction::DelegateVoteScoped { vote_id, delegate, scope } => {
+            let delegator = caller?;
+            let registry = registry?;
+            check_registry_owner(program_id, registry)?;
+
+            if !delegator.is_signer {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+
+            let (mut voting, slot) = load_vote_slot(registry, vote_id)?;
+            voting
+                .delegate_vote_scoped_as(delegator.key, vote_id, &delegate, scope)
+                .map_err(|error| report(error, &format!("vote {}, caller {}", vote_id, delegator.key)))?;
+            voting.save_vote_slot(registry, slot)
+        }
+        VoteInstruction::CloseVote { vote_id } => {
+            let caller = caller?;
+            let registry = registry?;
+            check_registry_owner(program_id, registry)?;
+
+            if !caller.is_signer {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+
+            // The caller is followed by the recent blockhashes sysvar
+            let mut vote_accounts = vec![caller.clone()];
+            vote_accounts.extend(account_iter.cloned());
+
+            let mut voting = load_for_vote(registry, vote_id)?;
+            voting
+                .close_vote(vote_id, &vote_accounts)
+                .map_err(|error| report(error, &format!("vote {}, caller {}", vote_id, caller.key)))?;
+            voting.save(registry)
+        }
+        VoteInstruction::RepairVote { vote_id } => {
+            let admin = caller?;
+            let registry = registry?;
+            check_registry_owner(program_id, registry)?;
+
+            let mut voting = Voting::load(registry)?;
+            voting
+                .admin_repair(vote_id, std::slice::from_ref(admin))
+                .map_err(|error| report(error, &format!("vote {}, caller {}", vote_id, admin.key)))?;
+            msg!("Repaired vote {}", vote_id);
+            voting.save(registry)
+        }
+        VoteInstruction::AcceptDelegation { vote_id, delegator } => {
+            let delegate = caller?;
+            let registry = registry?;
+            check_registry_owner(program_id, registry)?;
+
+            if !delegate.is_signer {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+
+            let mut voting = load_for_vote(registry, vote_id)?;
+            voting
+                .accept_delegation_as(delegate.key, vote_id, &delegator)
+                .map_err(|error| report(error, &format!("vote {}, caller {}", vote_id, delegate.key)))?;
+            voting.save(registry)
+        }
+        VoteInstruction::WithdrawDelegation { vote_id, delegate } => {
+            let delegator = caller?;
+            let registry = registry?;
+            check_registry_owner(program_id, registry)?;
+
+            if !delegator.is_signer {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+
+            let mut voting = load_for_vote(registry, vote_id)?;
+            voting
+                .withdraw_delegation_as(delegator.key, vote_id, &delegate)
+                .map_err(|error| report(error, &format!("vote {}, caller {}", vote_id, delegator.key)))?;
+            voting.save(registry)
+        }
+        VoteInstruction::RegisterVoter { vote_id } => {
+            let voter = caller?;
+            let registry = registry?;
+            check_registry_owner(program_id, registry)?;
+
+            if !voter.is_signer {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+
+            // The voter is followed by the eligibility account
+            let mut vote_accounts = vec![voter.clone()];
+            vote_accounts.extend(account_iter.cloned());
+
+            let mut voting = load_for_vote(registry, vote_id)?;
+            voting
+                .register_voter(vote_id, &vote_accounts)
+                .map_err(|error| report(error, &format!("vote {}, caller {}", vote_id, voter.key)))?;
+            voting.save(registry)
+        }
+        VoteInstruction::ScrubVoter { vote_id, voter } => {
+            let caller = caller?;
+            let registry = registry?;
+            let receipt_account = next_account_info(account_iter)?;
+            check_registry_owner(program_id, registry)?;
+            check_unique_accounts(&[caller.key, registry.key, receipt_account.key])?; // The rent of the receipt goes to the caller
+
+            if !caller.is_signer {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+
+            let (vote_address, _) = get_vote_address(program_id, registry.key, vote_id);
+            if *receipt_account.key != get_receipt_address(program_id, &vote_address, &voter).0 {
+                return Err(ProgramError::InvalidSeeds); // Return error if the receipt account is not the voter's
+            }
+
+            let mut voting = load_for_vote(registry, vote_id)?;
+            voting
+                .scrub_voter_as(caller.key, vote_id, &voter)
+                .map_err(|error| report(error, &format!("vote {}, caller {}", vote_id, caller.key)))?;
+
+            // The receipt names the voter too, it is wiped and emptied so the runtime deletes it
+            if receipt_account.owner == program_id && !receipt_account.data_is_empty() {
+                receipt_account.try_borrow_mut_data()?.fill(0);
+                let refund = receipt_account.lamports();
+                let caller_lamports = caller.lamports();
+                **receipt_account.try_borrow_mut_lamports()? = 0;
+                **caller.try_borrow_mut_lamports()? = caller_lamports.checked_add(refund).ok_or(ProgramError::ArithmeticOverflow)?;
+            }
+
+            // A shorter state would leave the end of the previous one behind
+            registry.try_borrow_mut_data()?.fill(0);
+            voting.save(registry)
+        }
+        VoteInstruction::CertifyResults { vote_id } => {
+            let certifier = caller?;
+            let registry = registry?;
+            check_registry_owner(program_id, registry)?;
+
+            if !certifier.is_signer {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+
+            let mut voting = load_for_vote(registry, vote_id)?;
+            voting
+                .certify_results_as(certifier.key, vote_id)
+                .map_err(|error| report(error, &format!("vote {}, caller {}", vote_id, certifier.key)))?;
+            voting.save(registry)
+        }
+        VoteInstruction::EnableSharding { shard_capacity } => {
+            let admin = caller?;
+            let registry = registry?;
+            check_registry_owner(program_id, registry)?;
+
+            let mut voting = Voting::load(registry)?;
+            voting
+                .enable_sharding(shard_capacity, std::slice::from_ref(admin))
+                .map_err(|error| report(error, &format!("registry {}, caller {}", registry.key, admin.key)))?;
+            voting.save(registry)
+        }
+        VoteInstruction::EnableOutbox { capacity, policy } => {
+            let admin = caller?;
+            let registry = registry?;
+            let outbox_account = next_account_info(account_iter)?;
+            let system_program_account = next_account_info(account_iter)?;
+            check_registry_owner(program_id, registry)?;
+            check_unique_accounts(&[admin.key, registry.key, outbox_account.key])?;
+
+            let (outbox_address, bump) = get_outbox_address(program_id, registry.key);
+            if *outbox_account.key != outbox_address {
+                return Err(ProgramError::InvalidSeeds); // Return error if the account is not the outbox of the registry
+            }
+
+            let mut voting = Voting::load(registry)?;
+            if outbox_account.data_is_empty() {
+                if *system_program_account.key != system_program::id() {
+                    return Err(ProgramError::IncorrectProgramId);
+                }
+
+                let space = Outbox::space(capacity.min(MAX_OUTBOX_CAPACITY));
+                let lamports = Rent::get()?.minimum_balance(space);
+                invoke_signed(
+                    &system_instruction::create_account(admin.key, outbox_account.key, lamports, space as u64, program_id),
+                    &[admin.clone(), outbox_account.clone(), system_program_account.clone()],
+                    &[&[b"outbox", registry.key.as_ref(), &[bump]]],
+                )?;
+            } else if outbox_account.owner != program_id {
+                return Err(ProgramError::IncorrectProgramId); // Return error if the outbox was not created by the program
+            }
+
+            voting
+                .enable_outbox(capacity, policy, &[admin.clone(), outbox_account.clone()])
+                .map_err(|error| report(error, &format!("registry {}, caller {}", registry.key, admin.key)))?;
+            voting.save(registry)
+        }
+        VoteInstruction::AckOutbox { vote_ids } => {
+            let admin = caller?;
+            let registry = registry?;
+            let outbox_account = next_account_info(account_iter)?;
+            check_registry_owner(program_id, registry)?;
+
+            if outbox_account.owner != program_id {
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            let voting = Voting::load(registry)?;
+            voting
+                .ack_outbox(&vote_ids, &[admin.clone(), outbox_account.clone()])
+                .map_err(|error| report(error, &format!("registry {}, caller {}", registry.key, admin.key)))?;
+            Ok(())
+        }
+        VoteInstruction::AttestNonParticipation { vote_id } => {
+            let voter = caller?;
+            let registry = registry?;
+            check_registry_owner(program_id, registry)?;
+
+            if !voter.is_signer {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+
+            let mut voting = load_for_vote(registry, vote_id)?;
+            voting
+                .attest_non_participation_as(voter.key, vote_id)
+                .map_err(|error| report(error, &format!("vote {}, caller {}", vote_id, voter.key)))?;
+            voting.save(registry)
+        }
+        VoteInstruction::VoteExists { vote_id } => answer_query(program_id, accounts, |voting, _| Ok(voting.vote_exists(vote_id))),
+        VoteInstruction::HasVoted { vote_id, voter } => answer_query(program_id, accounts, |voting, _| Ok(voting.has_voted(vote_id, &voter))),
+        VoteInstruction::OptionExists { vote_id, index } => answer_query(program_id, accounts, |voting, _| Ok(voting.option_exists(vote_id, index))),
+        VoteInstruction::GetResults { vote_id, question_index } => {
+            answer_query(program_id, accounts, |voting, viewer| voting.get_results_response(vote_id, question_index.map(|index| index as usize), viewer))
+        }
+        VoteInstruction::GetOptions { vote_id } => answer_query(program_id, accounts, |voting, _| voting.get_options_response(vote_id)),
+        VoteInstruction::IsVoterAllowed { vote_id, voter } => answer_query(program_id, accounts, |voting, _| voting.is_voter_allowed(vote_id, &voter)),
+        VoteInstruction::GetVoterStatus { vote_id, voter } => answer_query(program_id, accounts, |voting, viewer| voting.get_voter_status_response(vote_id, &voter, viewer)),
+        VoteInstruction::GetResultsBatch { vote_ids } => {
+            answer_query(program_id, accounts, |voting, viewer| voting.get_results_batch(&vote_ids, viewer).map(ResultsBatchResponse::new))
+        }
+        VoteInstruction::SimulateVote { vote_id, question_index, option_index } => {
+            answer_query(program_id, accounts, |voting, voter| voting.simulate_vote(vote_id, question_index as usize, option_index, voter))
+        }
+        VoteInstruction::SimulateDelegate { vote_id, delegate, expires_at } => {
+            answer_query(program_id, accounts, |voting, delegator| voting.simulate_delegate(vote_id, &delegate, expires_at, delegator))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{process_arbitrary_instruction, set_clock, take_logs, take_return_data, TestHarness};
+    use crate::responses::decode_return_data;
+    use crate::{instruction, CreationFee, CreationPolicy, Eligibility, MinStake, OptionsResponse, OutboxPolicy, QuestionConfig, QuestionTally, ResultsResponse, Title, SimulationResponse, Vote, VoteConfig, VoteOption, VoteResultsLite, VoteStatusKind, VoterStatusResponse, MAX_RESULTS_BATCH};
+    use borsh::BorshDeserialize;
+    use proptest::prelude::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_initialize_registry() {
+        let program_id = Pubkey::new_unique();
+        let admin = Pubkey::new_unique();
+
+        let registry_key = Pubkey::new_unique();
+        let mut registry_lamports = 0;
+        let mut registry_data = vec![0; 1024];
+        let registry = AccountInfo::new(&registry_key, false, true, &mut registry_lamports, &mut registry_data, &program_id, false, 0);
+
+        let data = VoteInstruction::InitializeRegistry { admin }.pack();
+        assert!(process_instruction(&program_id, std::slice::from_ref(&registry), &data).is_ok());
+
+        let voting = Voting::load(&registry).unwrap();
+        assert_eq!(voting.admin(), &admin);
+        assert_eq!(voting, Voting::new(admin));
+
+        // A second initialization must not overwrite the registry
+        let data = VoteInstruction::InitializeRegistry { admin: Pubkey::new_unique() }.pack();
+        assert_eq!(process_instruction(&program_id, std::slice::from_ref(&registry), &data), Err(ProgramError::AccountAlreadyInitialized));
+        assert_eq!(Voting::load(&registry).unwrap().admin(), &admin);
+    }
+
+    #[test]
+    fn test_initialize_registry_wrong_owner() {
+        let program_id = Pubkey::new_unique();
+        let other_program = Pubkey::new_unique();
+
+        let registry_key = Pubkey::new_unique();
+        let mut registry_lamports = 0;
+        let mut registry_data = vec![0; 1024];
+        let registry = AccountInfo::new(&registry_key, false, true, &mut registry_lamports, &mut registry_data, &other_program, false, 0);
+
+        let data = VoteInstruction::InitializeRegistry { admin: Pubkey::new_unique() }.pack();
+        assert_eq!(process_instruction(&program_id, &[registry], &data), Err(ProgramError::IncorrectProgramId));
+    }
+
+    #[test]
+    fn test_update_policy_requires_admin() {
+        let program_id = Pubkey::new_unique();
+        let admin = Pubkey::new_unique();
+        let stranger = Pubkey::new_unique();
+
+        let registry_key = Pubkey::new_unique();
+        let mut registry_lamports = 0;
+        let mut registry_data = vec![0; 1024];
+        let registry = AccountInfo::new(&registry_key, false, true, &mut registry_lamports, &mut registry_data, &program_id, false, 0);
+
+        let data = VoteInstruction::InitializeRegistry { admin }.pack();
+        assert!(process_instruction(&program_id, std::slice::from_ref(&registry), &data).is_ok());
+
+        let policy = CreationPolicy { fee: None, cooldown_slots: Some(5), deposit: None };
+        let data = VoteInstruction::UpdatePolicy { policy }.pack();
+
+        let (mut caller_lamports, mut caller_data) = (0, vec![]);
+        let stranger_info = AccountInfo::new(&stranger, true, false, &mut caller_lamports, &mut caller_data, &program_id, false, 0);
+        assert!(process_instruction(&program_id, &[stranger_info, registry.clone()], &data).is_err());
+
+        // The admin key alone is not enough, it has to sign
+        let (mut admin_lamports, mut admin_data) = (0, vec![]);
+        let unsigned_admin = AccountInfo::new(&admin, false, false, &mut admin_lamports, &mut admin_data, &program_id, false, 0);
+        assert_eq!(process_instruction(&program_id, &[unsigned_admin.clone(), registry.clone()], &data), Err(ProgramError::MissingRequiredSignature));
+        assert_eq!(Voting::load(&registry).unwrap().get_creation_policy(), CreationPolicy::default());
+
+        let mut admin_info = unsigned_admin;
+        admin_info.is_signer = true;
+        assert!(process_instruction(&program_id, &[admin_info, registry.clone()], &data).is_ok());
+        assert_eq!(Voting::load(&registry).unwrap().get_creation_policy(), policy);
+    }
+
+    #[test]
+    fn test_pause_instruction() {
+        let program_id = Pubkey::new_unique();
+        let admin = Pubkey::new_unique();
+
+        let registry_key = Pubkey::new_unique();
+        let mut registry_lamports = 0;
+        let mut registry_data = vec![0; 1024];
+        let registry = AccountInfo::new(&registry_key, false, true, &mut registry_lamports, &mut registry_data, &program_id, false, 0);
+
+        let data = VoteInstruction::InitializeRegistry { admin }.pack();
+        assert!(process_instruction(&program_id, std::slice::from_ref(&registry), &data).is_ok());
+
+        let (mut admin_lamports, mut admin_data) = (0, vec![]);
+        let admin_info = AccountInfo::new(&admin, true, false, &mut admin_lamports, &mut admin_data, &program_id, false, 0);
+
+        assert!(process_instruction(&program_id, &[admin_info.clone(), registry.clone()], &VoteInstruction::Pause.pack()).is_ok());
+        assert!(Voting::load(&registry).unwrap().is_paused());
+
+        assert!(process_instruction(&program_id, &[admin_info, registry.clone()], &VoteInstruction::Unpause.pack()).is_ok());
+        assert!(!Voting::load(&registry).unwrap().is_paused());
+    }
+
+    #[test]
+    fn test_outbox_instructions() {
+        let program_id = Pubkey::new_unique();
+        let mut harness = TestHarness::new();
+        let admin = *harness.voting.admin();
+        let creator = Pubkey::new_unique();
+        let config = VoteConfig::from_labels("Chair".to_string(), vec!["Alice".to_string(), "Bob".to_string()]).unwrap();
+        let vote_ids = [harness.create_vote(config.clone(), &creator).unwrap(), harness.create_vote(config, &creator).unwrap()];
+        harness.set_lamports(&admin, 1_000_000_000);
+        let registry_key = save_registry(&mut harness, &program_id);
+        let run = |harness: &mut TestHarness, instruction: solana_program::instruction::Instruction| {
+            let accounts: Vec<AccountInfo> = instruction.accounts.iter().map(|meta| harness.account_for(&meta.pubkey)).collect();
+            process_instruction(&program_id, &accounts, &instruction.data)
+        };
+
+        // The admin funds the outbox, whose overflow policy evicts the oldest entry
+        let wrong_outbox = Pubkey::new_unique();
+        let mut enable = instruction::enable_outbox(&program_id, &registry_key, &admin, 1, OutboxPolicy::EvictOldest);
+        let outbox_key = enable.accounts[2].pubkey;
+        enable.accounts[2].pubkey = wrong_outbox;
+        assert_eq!(run(&mut harness, enable.clone()), Err(ProgramError::InvalidSeeds));
+        enable.accounts[2].pubkey = outbox_key;
+        assert_eq!(run(&mut harness, enable), Ok(()));
+        assert_eq!(harness.lamports(&admin), 1_000_000_000 - Rent::default().minimum_balance(Outbox::space(1)));
+
+        for vote_id in vote_ids {
+            let mut close = instruction::close_vote(&program_id, &registry_key, &creator, vote_id);
+            close.accounts.push(instruction::outbox_account(&program_id, &registry_key));
+            assert_eq!(run(&mut harness, close), Ok(()));
+        }
+        let outbox = Outbox::unpack(&harness.account_for(&outbox_key).data.borrow()).unwrap();
+        assert_eq!((outbox.entries.iter().map(|entry| entry.vote_id).collect::<Vec<_>>(), outbox.overflow_count), (vec![vote_ids[1]], 1));
+
+        assert_eq!(run(&mut harness, instruction::ack_outbox(&program_id, &registry_key, &creator, vec![vote_ids[1]])), Err(ProgramError::InvalidArgument));
+        assert_eq!(run(&mut harness, instruction::ack_outbox(&program_id, &registry_key, &admin, vec![vote_ids[1]])), Ok(()));
+        let outbox = Outbox::unpack(&harness.account_for(&outbox_key).data.borrow()).unwrap();
+        assert_eq!(outbox.pending().count(), 0);
+    }
+
+    #[test]
+    fn test_create_vote_sharded() {
+        let program_id = Pubkey::new_unique();
+        let mut harness = TestHarness::new();
+        let admin = *harness.voting.admin();
+        let creator = Pubkey::new_unique();
+        harness.set_lamports(&creator, 1_000_000_000);
+        let registry_key = save_registry(&mut harness, &program_id);
+
+        let accounts = [harness.account_for(&admin), harness.account_for(&registry_key)];
+        assert!(process_instruction(&program_id, &accounts, &VoteInstruction::EnableSharding { shard_capacity: 2 }.pack()).is_ok());
+
+        let create = |harness: &mut TestHarness, title: &str, shard_index: u32| {
+            let config = VoteConfig::from_labels(title.to_string(), vec!["Yes".to_string(), "No".to_string()]).unwrap();
+            let (shard_key, _) = get_shard_address(&program_id, &registry_key, shard_index);
+            let accounts = [harness.account_for(&creator), harness.account_for(&registry_key), harness.account_for(&shard_key), harness.account_for(&system_program::id())];
+            process_instruction(&program_id, &accounts, &VoteInstruction::CreateVote { config: Box::new(config) }.pack())
+        };
+
+        // The shard accounts are required, for the shard the vote goes into
+        let config = VoteConfig::from_labels("A".to_string(), vec!["Yes".to_string()]).unwrap();
+        let accounts = [harness.account_for(&creator), harness.account_for(&registry_key)];
+        assert_eq!(process_instruction(&program_id, &accounts, &VoteInstruction::CreateVote { config: Box::new(config) }.pack()), Err(ProgramError::NotEnoughAccountKeys));
+        assert_eq!(create(&mut harness, "A", 1), Err(ProgramError::InvalidSeeds));
+
+        // The first and the third vote open a shard, funded by the creator
+        for (title, shard_index) in [("A", 0), ("B", 0), ("C", 1)] {
+            assert!(create(&mut harness, title, shard_index).is_ok());
+        }
+        let shard_rent = Rent::default().minimum_balance(RegistryShard::space(2));
+        assert_eq!(harness.lamports(&creator), 1_000_000_000 - 2 * shard_rent);
+
+        let load_shard = |harness: &mut TestHarness, shard_index: u32| {
+            let (shard_key, _) = get_shard_address(&program_id, &registry_key, shard_index);
+            RegistryShard::load(&harness.account_for(&shard_key), &program_id).unwrap()
+        };
+        let shard = load_shard(&mut harness, 0);
+        assert_eq!((shard.registry, shard.index), (registry_key, 0));
+        assert_eq!(shard.entries.iter().map(|entry| (entry.id, entry.title.as_str())).collect::<Vec<_>>(), vec![(VoteId(0), "A"), (VoteId(1), "B")]);
+        assert_eq!(load_shard(&mut harness, 1).entries, vec![ShardEntry { id: VoteId(2), creator, title: Title::new("title", "C").unwrap() }]);
+
+        let voting = Voting::load(&harness.account_for(&registry_key)).unwrap();
+        assert_eq!((voting.shard_count(), voting.shard_for_vote_id(VoteId(2))), (2, Some(1)));
+    }
+
+    // Registry account owned by the program, holding the harness votes
+    fn save_registry(harness: &mut TestHarness, program_id: &Pubkey) -> Pubkey {
+        let registry_key = Pubkey::new_unique();
+        harness.set_data(&registry_key, vec![0; 4096]);
+        harness.set_owner(&registry_key, program_id);
+
+        let registry = harness.account_for(&registry_key);
+        harness.voting.save(&registry).unwrap();
+
+        registry_key
+    }
+
+    fn cast_vote(harness: &mut TestHarness, program_id: &Pubkey, registry_key: &Pubkey, voter: &Pubkey, payer: &Pubkey, ballot: (VoteId, u32, u16)) -> ProgramResult {
+        let (vote_id, question_index, option_index) = ballot;
+        let (vote_address, _) = get_vote_address(program_id, registry_key, vote_id);
+        let (receipt_key, _) = get_receipt_address(program_id, &vote_address, voter);
+
+        let accounts = [
+            harness.account_for(payer),
+            harness.account_for(voter),
+            harness.account_for(registry_key),
+            harness.account_for(&receipt_key),
+            harness.account_for(&system_program::id()),
+        ];
+        let data = VoteInstruction::CastVote { vote_id, question_index, option_index: OptionIndex(option_index) }.pack();
+
+        process_instruction(program_id, &accounts, &data)
+    }
+
+    #[test]
+    fn test_cast_vote_receipt() {
+        let program_id = Pubkey::new_unique();
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voter = Pubkey::new_unique();
+        let payer = Pubkey::new_unique();
+        set_clock(1_000);
+
+        let config = VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string(), "Option 2".to_string()]).unwrap();
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+        harness.allow(vote_id, &creator, &voter).unwrap();
+        // Give the voter a second vote, so only the receipt stops another ballot
+        harness.voting.votes.get_mut(&vote_id).unwrap().grant_votes(&voter, 1).unwrap();
+
+        let registry_key = save_registry(&mut harness, &program_id);
+        harness.set_lamports(&payer, 1_000_000_000);
+
+        // The registry cannot pay for the receipt
+        assert_eq!(cast_vote(&mut harness, &program_id, &registry_key, &voter, &registry_key, (vote_id, 0, 1)), Err(VoteError::DuplicateAccount.into()));
+        assert!(cast_vote(&mut harness, &program_id, &registry_key, &voter, &payer, (vote_id, 0, 1)).is_ok());
+
+        let (vote_address, _) = get_vote_address(&program_id, &registry_key, vote_id);
+        let (receipt_key, _) = get_receipt_address(&program_id, &vote_address, &voter);
+        let receipt = VoteReceipt::load(&harness.account_for(&receipt_key), &program_id).unwrap();
+        assert_eq!(receipt, VoteReceipt { vote_id, voter, question_index: 0, option_index: Some(OptionIndex(1)), timestamp: 1_000 });
+        assert_eq!(harness.lamports(&receipt_key), Rent::default().minimum_balance(VoteReceipt::LEN));
+
+        let voting = Voting::load(&harness.account_for(&registry_key)).unwrap();
+        assert_eq!(voting.votes[&vote_id].questions[0].votes["Option 2"], 1);
+
+        // The receipt already exists, so the voter cannot cast a second ballot
+        assert_eq!(cast_vote(&mut harness, &program_id, &registry_key, &voter, &payer, (vote_id, 0, 0)), Err(ProgramError::AccountAlreadyInitialized));
+        let voting = Voting::load(&harness.account_for(&registry_key)).unwrap();
+        assert_eq!(voting.votes[&vote_id].questions[0].votes.get("Option 1"), None);
+    }
+
+    #[test]
+    fn test_cast_vote_with_comment() {
+        let program_id = Pubkey::new_unique();
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voter = Pubkey::new_unique();
+
+        let config = VoteConfig { require_comment: true, ..VoteConfig::from_labels("Board Vote".to_string(), vec!["Approve".to_string(), "Reject".to_string()]).unwrap() };
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+        harness.allow(vote_id, &creator, &voter).unwrap();
+        let registry_key = save_registry(&mut harness, &program_id);
+        harness.set_lamports(&voter, 1_000_000_000);
+
+        // A plain ballot is refused, the one with a comment is stored with it
+        assert_eq!(cast_vote(&mut harness, &program_id, &registry_key, &voter, &voter, (vote_id, 0, 1)), Err(VoteError::CommentRequired.into()));
+        let instruction = instruction::cast_vote_with_comment(&program_id, &registry_key, &voter, &voter, vote_id, 0, OptionIndex(1), "Over budget".to_string());
+        let accounts: Vec<AccountInfo> = instruction.accounts.iter().map(|meta| harness.account_for(&meta.pubkey)).collect();
+        assert_eq!(process_instruction(&program_id, &accounts, &instruction.data), Ok(()));
+
+        let voting = Voting::load(&harness.account_for(&registry_key)).unwrap();
+        assert_eq!(voting.get_ballot_as(&creator, vote_id, &voter, 0).unwrap().comment.as_deref(), Some("Over budget"));
+    }
+
+    #[test]
+    fn test_cast_votes_receipts() {
+        let program_id = Pubkey::new_unique();
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voter = Pubkey::new_unique();
+        let payer = Pubkey::new_unique();
+        set_clock(1_000);
+
+        let config = || VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string(), "Option 2".to_string()]).unwrap();
+        let vote_ids: Vec<VoteId> = (0..3).map(|_| harness.create_vote(config(), &creator).unwrap()).collect();
+        for &vote_id in &vote_ids {
+            harness.allow(vote_id, &creator, &voter).unwrap();
+            harness.voting.votes.get_mut(&vote_id).unwrap().grant_votes(&voter, 1).unwrap();
+        }
+
+        let registry_key = save_registry(&mut harness, &program_id);
+        harness.set_lamports(&payer, 1_000_000_000);
+        let mut process = |ballots: &[(VoteId, OptionIndex)]| {
+            let instruction = instruction::cast_votes(&program_id, &registry_key, &voter, &payer, ballots);
+            let accounts: Vec<AccountInfo> = instruction.accounts.iter().map(|meta| harness.account_for(&meta.pubkey)).collect();
+            process_instruction(&program_id, &accounts, &instruction.data)
+        };
+
+        assert!(process(&[(vote_ids[0], OptionIndex(1)), (vote_ids[1], OptionIndex(0))]).is_ok());
+        // Each vote takes a single ballot of the voter, whether the receipt exists or comes from the same batch
+        assert_eq!(process(&[(vote_ids[0], OptionIndex(0))]), Err(ProgramError::AccountAlreadyInitialized));
+        assert_eq!(process(&[(vote_ids[2], OptionIndex(0)), (vote_ids[2], OptionIndex(1))]), Err(ProgramError::AccountAlreadyInitialized));
+
+        let instruction = instruction::cast_votes(&program_id, &registry_key, &voter, &payer, &[]);
+        assert_eq!(process_instruction(&program_id, &[harness.account_for(&payer), harness.account_for(&voter), harness.account_for(&registry_key)], &instruction.data), Err(ProgramError::NotEnoughAccountKeys));
+
+        for (vote_id, option_index) in [(vote_ids[0], OptionIndex(1)), (vote_ids[1], OptionIndex(0))] {
+            let (vote_address, _) = get_vote_address(&program_id, &registry_key, vote_id);
+            let (receipt_key, _) = get_receipt_address(&program_id, &vote_address, &voter);
+            let receipt = VoteReceipt::load(&harness.account_for(&receipt_key), &program_id).unwrap();
+            assert_eq!(receipt, VoteReceipt { vote_id, voter, question_index: 0, option_index: Some(option_index), timestamp: 1_000 });
+        }
+        let voting = Voting::load(&harness.account_for(&registry_key)).unwrap();
+        assert_eq!(voting.votes[&vote_ids[0]].questions[0].votes["Option 2"], 1);
+        assert_eq!(voting.votes[&vote_ids[1]].questions[0].votes["Option 1"], 1);
+    }
+
+    #[test]
+    fn test_cast_vote_receipt_shared_by_questions() {
+        let program_id = Pubkey::new_unique();
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voter = Pubkey::new_unique();
+        set_clock(1_000);
+
+        let motion = |title: &str| QuestionConfig { title: title.try_into().unwrap(), options: VoteOption::from_labels(vec!["Yes".to_string(), "No".to_string()]).unwrap() };
+        let config = VoteConfig {
+            results_visibility: Visibility::CreatorOnly,
+            ..VoteConfig::with_questions("Board Meeting".to_string(), vec![motion("Motion 1"), motion("Motion 2")]).unwrap()
+        };
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+        harness.allow(vote_id, &creator, &voter).unwrap();
+
+        let registry_key = save_registry(&mut harness, &program_id);
+        harness.set_lamports(&voter, 1_000_000_000);
+
+        assert!(cast_vote(&mut harness, &program_id, &registry_key, &voter, &voter, (vote_id, 0, 0)).is_ok());
+        set_clock(2_000);
+        assert!(cast_vote(&mut harness, &program_id, &registry_key, &voter, &voter, (vote_id, 1, 1)).is_ok());
+
+        // The receipt keeps the first ballot and hides the choice of a vote with restricted results
+        let (vote_address, _) = get_vote_address(&program_id, &registry_key, vote_id);
+        let (receipt_key, _) = get_receipt_address(&program_id, &vote_address, &voter);
+        let receipt = VoteReceipt::load(&harness.account_for(&receipt_key), &program_id).unwrap();
+        assert_eq!(receipt, VoteReceipt { vote_id, voter, question_index: 0, option_index: None, timestamp: 1_000 });
+    }
+
+    #[test]
+    fn test_ballots_decode_only_their_vote() {
+        let program_id = Pubkey::new_unique();
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let (voter, delegator, delegate) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+
+        let config = |title: &str| VoteConfig::from_labels(title.to_string(), vec!["Yes".to_string(), "No".to_string()]).unwrap();
+        harness.create_vote(config("Unread"), &creator).unwrap();
+        let vote_id = harness.create_vote(config("Budget"), &creator).unwrap();
+        harness.allow(vote_id, &creator, &voter).unwrap();
+        for key in [&delegator, &delegate] {
+            harness.allow(vote_id, &creator, key).unwrap();
+        }
+        harness.set_lamports(&voter, 1_000_000_000);
+        let registry_key = save_registry(&mut harness, &program_id);
+
+        // The titles of the other vote no longer decode, ballots and delegations on this vote never read them
+        let replace = |harness: &mut TestHarness, pattern: &[u8; 6], replacement: &[u8; 6]| {
+            let mut data = harness.account_for(&registry_key).data.borrow().to_vec();
+            for start in 0..data.len() - pattern.len() {
+                if data[start..].starts_with(pattern) {
+                    data[start..start + pattern.len()].copy_from_slice(replacement);
+                }
+            }
+            harness.set_data(&registry_key, data);
+        };
+        replace(&mut harness, b"Unread", &[0xff; 6]);
+        assert!(Voting::load(&harness.account_for(&registry_key)).is_err());
+
+        assert!(cast_vote(&mut harness, &program_id, &registry_key, &voter, &voter, (vote_id, 0, 1)).is_ok());
+        let accounts = [harness.account_for(&delegator), harness.account_for(&registry_key)];
+        assert!(process_instruction(&program_id, &accounts, &VoteInstruction::DelegateVote { vote_id, delegate, expires_at: None }.pack()).is_ok());
+
+        replace(&mut harness, &[0xff; 6], b"Unread");
+        harness.cast(vote_id, &voter, 1).unwrap();
+        harness.delegate(vote_id, &delegator, &delegate).unwrap();
+        assert_eq!(Voting::load(&harness.account_for(&registry_key)).unwrap(), harness.voting);
+    }
+
+    #[test]
+    fn test_rejected_ballot_is_logged() {
+        let program_id = Pubkey::new_unique();
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let stranger = Pubkey::new_unique();
+        set_clock(1_000);
+
+        let vote_id = harness.create_vote(VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string()]).unwrap(), &creator).unwrap();
+        let registry_key = save_registry(&mut harness, &program_id);
+
+        take_logs();
+        assert_eq!(cast_vote(&mut harness, &program_id, &registry_key, &stranger, &stranger, (vote_id, 0, 0)), Err(VoteError::VoterNotAllowed.into()));
+
+        let expected = format!("Error VoterNotAllowed: the caller is not allowed to vote (vote {}, caller {})", vote_id, stranger);
+        assert_eq!(take_logs(), vec![expected]);
+    }
+
+    #[test]
+    fn test_handlers_take_the_caller_from_the_signer() {
+        let program_id = Pubkey::new_unique();
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let delegator = Pubkey::new_unique();
+        let delegate = Pubkey::new_unique();
+
+        let vote_id = harness.create_vote(VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string()]).unwrap(), &creator).unwrap();
+        harness.allow(vote_id, &creator, &delegator).unwrap();
+        harness.allow(vote_id, &creator, &delegate).unwrap();
+        let registry_key = save_registry(&mut harness, &program_id);
+
+        // The delegator key alone is not enough, it has to sign
+        let data = VoteInstruction::DelegateVote { vote_id, delegate, expires_at: None }.pack();
+        let mut delegator_info = harness.account_for(&delegator);
+        delegator_info.is_signer = false;
+        let registry = harness.account_for(&registry_key);
+        assert_eq!(process_instruction(&program_id, &[delegator_info.clone(), registry.clone()], &data), Err(ProgramError::MissingRequiredSignature));
+        assert_eq!(Voting::load(&registry).unwrap().votes[&vote_id].allowed_voters[&delegator].delegate, None);
+
+        // The signer is the delegator handed to the library
+        delegator_info.is_signer = true;
+        assert!(process_instruction(&program_id, &[delegator_info, registry.clone()], &data).is_ok());
+        let voting = Voting::load(&registry).unwrap();
+        assert_eq!(voting.votes[&vote_id].allowed_voters[&delegator].delegate, Some(delegate));
+        assert_eq!(voting.votes[&vote_id].allowed_voters[&delegate].votes_left, vec![2]);
+    }
+
+    #[test]
+    fn test_short_account_lists_name_the_missing_caller() {
+        let program_id = Pubkey::new_unique();
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let payer = Pubkey::new_unique();
+
+        let vote_id = harness.create_vote(VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string()]).unwrap(), &creator).unwrap();
+        let registry_key = save_registry(&mut harness, &program_id);
+
+        // Only the payer of a ballot is not read as the voter
+        let data = VoteInstruction::CastVote { vote_id, question_index: 0, option_index: OptionIndex(0) }.pack();
+        let payer_info = harness.account_for(&payer);
+        assert_eq!(process_instruction(&program_id, std::slice::from_ref(&payer_info), &data), Err(VoteError::MissingVoterAccount.into()));
+
+        let data = VoteInstruction::DelegateVote { vote_id, delegate: payer, expires_at: None }.pack();
+        assert_eq!(process_instruction(&program_id, &[], &data), Err(VoteError::MissingVoterAccount.into()));
+
+        let data = VoteInstruction::AddAllowedVoter { vote_id, voter: payer }.pack();
+        assert_eq!(process_instruction(&program_id, &[], &data), Err(VoteError::MissingCreatorAccount.into()));
+        assert_eq!(process_instruction(&program_id, std::slice::from_ref(&payer_info), &data), Err(ProgramError::NotEnoughAccountKeys));
+
+        // The registry follows the caller
+        let creator_info = harness.account_for(&creator);
+        let registry = harness.account_for(&registry_key);
+        assert!(process_instruction(&program_id, &[creator_info, registry.clone()], &data).is_ok());
+        assert!(Voting::load(&registry).unwrap().is_voter_allowed(vote_id, &payer).unwrap());
+    }
+
+    // Overwrite the first match of `pattern` in the data of the account, which has to be the only one
+    fn patch_data(harness: &mut TestHarness, key: &Pubkey, pattern: &[u8], replacement: &[u8]) {
+        let mut data = harness.account_for(key).data.borrow().to_vec();
+        let matches: Vec<usize> = (0..data.len() - pattern.len()).filter(|start| data[*start..].starts_with(pattern)).collect();
+        assert_eq!(matches.len(), 1);
+        data[matches[0]..matches[0] + replacement.len()].copy_from_slice(replacement);
+        harness.set_data(key, data);
+    }
+
+    fn repair_vote(harness: &mut TestHarness, program_id: &Pubkey, registry_key: &Pubkey, caller: &Pubkey, vote_id: VoteId) -> ProgramResult {
+        let accounts = [harness.account_for(caller), harness.account_for(registry_key)];
+        process_instruction(program_id, &accounts, &VoteInstruction::RepairVote { vote_id }.pack())
+    }
+
+    #[test]
+    fn test_corrupt_state_guard_and_repair() {
+        let program_id = Pubkey::new_unique();
+        let mut harness = TestHarness::new();
+        let admin = *harness.voting.admin();
+        let creator = Pubkey::new_unique();
+        let (voter1, voter2) = (Pubkey::new_unique(), Pubkey::new_unique());
+        set_clock(1_000);
+
+        let vote_id = harness.create_vote(VoteConfig::from_labels("Budget".to_string(), vec!["Yes".to_string(), "No".to_string()]).unwrap(), &creator).unwrap();
+        harness.allow(vote_id, &creator, &voter1).unwrap();
+        harness.allow(vote_id, &creator, &voter2).unwrap();
+        harness.cast(vote_id, &voter1, 0).unwrap();
+        harness.set_lamports(&voter2, 1_000_000_000);
+        let registry_key = save_registry(&mut harness, &program_id);
+
+        // Zero the tally of "Yes" although a ballot for it is on record
+        patch_data(&mut harness, &registry_key, b"\x03\x00\x00\x00Yes\x01\x00\x00\x00", b"\x03\x00\x00\x00Yes\x00\x00\x00\x00");
+        let corrupted = harness.account_for(&registry_key).data.borrow().to_vec();
+
+        assert_eq!(cast_vote(&mut harness, &program_id, &registry_key, &voter2, &voter2, (vote_id, 0, 1)), Err(VoteError::CorruptState.into()));
+        assert_eq!(*harness.account_for(&registry_key).data.borrow(), &corrupted[..]); // Nothing was written back
+
+        // Only the admin can repair the vote
+        assert_eq!(repair_vote(&mut harness, &program_id, &registry_key, &creator, vote_id), Err(ProgramError::InvalidArgument));
+        assert!(repair_vote(&mut harness, &program_id, &registry_key, &admin, vote_id).is_ok());
+
+        let voting = Voting::load(&harness.account_for(&registry_key)).unwrap();
+        assert_eq!(voting.votes[&vote_id].questions[0].votes, HashMap::from([("Yes".to_string(), 1)]));
+        assert!(cast_vote(&mut harness, &program_id, &registry_key, &voter2, &voter2, (vote_id, 0, 1)).is_ok());
+
+        // A tally of an option that does not exist is caught as well
+        patch_data(&mut harness, &registry_key, b"\x02\x00\x00\x00No\x01\x00\x00\x00", b"\x02\x00\x00\x00Nx");
+        let voting = Voting::load(&harness.account_for(&registry_key)).unwrap();
+        assert_eq!(voting.check_vote(vote_id), Err(VoteError::CorruptState.into()));
+    }
+
+    #[test]
+    fn test_corrupt_state_beyond_repair() {
+        let program_id = Pubkey::new_unique();
+        let mut harness = TestHarness::new();
+        let admin = *harness.voting.admin();
+        let creator = Pubkey::new_unique();
+        let voter = Pubkey::new_unique();
+        set_clock(1_000);
+
+        let vote_id = harness.create_vote(VoteConfig::from_labels("Budget".to_string(), vec!["Yes".to_string(), "No".to_string()]).unwrap(), &creator).unwrap();
+        harness.allow(vote_id, &creator, &voter).unwrap();
+        harness.cast(vote_id, &voter, 1).unwrap();
+
+        // The vote state account refuses the same corruption when it is loaded
+        let state_key = Pubkey::new_unique();
+        harness.set_data(&state_key, vec![0; 1024]);
+        let state_info = harness.account_for(&state_key);
+        harness.voting.votes[&vote_id].save(&state_info).unwrap();
+        patch_data(&mut harness, &state_key, b"\x02\x00\x00\x00No\x01\x00\x00\x00", b"\x02\x00\x00\x00No\x00\x00\x00\x00");
+        assert_eq!(Vote::load(&harness.account_for(&state_key)), Err(VoteError::CorruptState.into()));
+
+        // A ballot for a missing option leaves nothing to rebuild the tallies from
+        harness.voting.votes.get_mut(&vote_id).unwrap().allowed_voters.get_mut(&voter).unwrap().ballots[0].option_index = OptionIndex(7);
+        let registry_key = save_registry(&mut harness, &program_id);
+        let before = harness.account_for(&registry_key).data.borrow().to_vec();
+
+        assert_eq!(repair_vote(&mut harness, &program_id, &registry_key, &admin, vote_id), Err(VoteError::CorruptState.into()));
+        assert_eq!(*harness.account_for(&registry_key).data.borrow(), &before[..]);
+    }
+
+    #[test]
+    fn test_query_instructions() {
+        let program_id = Pubkey::new_unique();
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voter = Pubkey::new_unique();
+
+        let config = VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string(), "Option 2".to_string()]).unwrap();
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+        harness.allow(vote_id, &creator, &voter).unwrap();
+        harness.cast(vote_id, &voter, 1).unwrap();
+        let registry_key = save_registry(&mut harness, &program_id);
+
+        let mut query = |instruction: VoteInstruction| {
+            let registry = harness.account_for(&registry_key);
+            process_instruction(&program_id, &[registry], &instruction.pack()).map(|_| take_return_data())
+        };
+
+        assert_eq!(query(VoteInstruction::VoteExists { vote_id }), Ok(Some(vec![1])));
+        assert_eq!(query(VoteInstruction::VoteExists { vote_id: VoteId(vote_id.0 + 1) }), Ok(Some(vec![0])));
+        assert_eq!(query(VoteInstruction::HasVoted { vote_id, voter }), Ok(Some(vec![1])));
+        assert_eq!(query(VoteInstruction::HasVoted { vote_id, voter: creator }), Ok(Some(vec![0])));
+        assert_eq!(query(VoteInstruction::OptionExists { vote_id, index: OptionIndex(1) }), Ok(Some(vec![1])));
+        assert_eq!(query(VoteInstruction::OptionExists { vote_id, index: OptionIndex(2) }), Ok(Some(vec![0])));
+        assert_eq!(query(VoteInstruction::IsVoterAllowed { vote_id, voter }), Ok(Some(vec![1])));
+        assert_eq!(query(VoteInstruction::IsVoterAllowed { vote_id: VoteId(vote_id.0 + 1), voter }), Err(ProgramError::InvalidArgument));
+
+        // The other read instructions answer with the Borsh encoded responses
+        let mut read = |instruction: VoteInstruction, viewer: &Pubkey| {
+            let accounts = [harness.account_for(&registry_key), harness.account_for(viewer)];
+            process_instruction(&program_id, &accounts, &instruction.pack())?;
+            Ok::<_, ProgramError>(take_return_data().unwrap())
+        };
+
+        let results: ResultsResponse = decode_return_data(&program_id, &program_id, &read(VoteInstruction::GetResults { vote_id, question_index: None }, &creator).unwrap()).unwrap();
+        assert_eq!(results, ResultsResponse { vote_id, questions: vec![QuestionTally { question_index: 0, votes: vec![0, 1] }] });
+        assert_eq!(read(VoteInstruction::GetResults { vote_id, question_index: Some(1) }, &creator), Err(ProgramError::InvalidArgument));
+
+        let options: OptionsResponse = decode_return_data(&program_id, &program_id, &read(VoteInstruction::GetOptions { vote_id }, &creator).unwrap()).unwrap();
+        assert_eq!(options.options.iter().map(|option| option.label.as_str()).collect::<Vec<_>>(), vec!["Option 1", "Option 2"]);
+
+        let status: VoterStatusResponse = decode_return_data(&program_id, &program_id, &read(VoteInstruction::GetVoterStatus { vote_id, voter }, &voter).unwrap()).unwrap();
+        assert_eq!((status.voter, status.status.votes_left), (voter, vec![0]));
+
+        let batch: ResultsBatchResponse = decode_return_data(&program_id, &program_id, &read(VoteInstruction::GetResultsBatch { vote_ids: vec![vote_id, VoteId(vote_id.0 + 1)] }, &creator).unwrap()).unwrap();
+        let lite = VoteResultsLite { leading_option: Some(1), total_ballots: 1, status: VoteStatusKind::Open, quorum_met: true };
+        assert_eq!(batch.results, vec![(vote_id, Ok(lite)), (VoteId(vote_id.0 + 1), Err(VoteError::VoteNotFound.code()))]);
+
+        // A full batch of the largest entries fits in the return data
+        let lite = VoteResultsLite { leading_option: Some(u32::MAX), total_ballots: u32::MAX, status: VoteStatusKind::Cancelled, quorum_met: false };
+        let full = ResultsBatchResponse { results: vec![(VoteId(u64::MAX), Ok(lite)); MAX_RESULTS_BATCH] };
+        assert!(borsh::to_vec(&full).unwrap().len() <= MAX_RETURN_DATA);
+
+        // Queries only answer for registries of the program
+        harness.set_owner(&registry_key, &Pubkey::new_unique());
+        let registry = harness.account_for(&registry_key);
+        assert_eq!(process_instruction(&program_id, &[registry], &VoteInstruction::VoteExists { vote_id }.pack()), Err(ProgramError::IncorrectProgramId));
+    }
+
+    #[test]
+    fn test_restricted_read_instructions() {
+        let program_id = Pubkey::new_unique();
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voter = Pubkey::new_unique();
+
+        let config = VoteConfig {
+            results_visibility: Visibility::AllowlistOnly,
+            allowlist_visibility: Visibility::CreatorOnly,
+            ..VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string(), "Option 2".to_string()]).unwrap()
+        };
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+        harness.allow(vote_id, &creator, &voter).unwrap();
+        harness.cast(vote_id, &voter, 1).unwrap();
+        let registry_key = save_registry(&mut harness, &program_id);
+
+        let mut read = |instruction: VoteInstruction, viewer: &Pubkey, signed: bool| {
+            let mut viewer_info = harness.account_for(viewer);
+            viewer_info.is_signer = signed;
+            process_instruction(&program_id, &[harness.account_for(&registry_key), viewer_info], &instruction.pack()).map(|_| take_return_data())
+        };
+
+        // Naming the creator or a voter does not show their data without their signature
+        let reads = [VoteInstruction::GetResults { vote_id, question_index: None }, VoteInstruction::GetVoterStatus { vote_id, voter }, VoteInstruction::GetResultsBatch { vote_ids: vec![vote_id] }];
+        for instruction in reads {
+            for viewer in [creator, voter] {
+                assert_eq!(read(instruction.clone(), &viewer, false), Err(ProgramError::MissingRequiredSignature), "{:?}", instruction);
+            }
+            assert!(read(instruction.clone(), &creator, true).is_ok(), "{:?}", instruction);
+        }
+
+        // Public data still reads without any signer
+        let other = harness.create_vote(VoteConfig::from_labels("Open Vote".to_string(), vec!["Option 1".to_string()]).unwrap(), &creator).unwrap();
+        let registry_key = save_registry(&mut harness, &program_id);
+        let mut stranger = harness.account_for(&Pubkey::new_unique());
+        stranger.is_signer = false;
+        let accounts = [harness.account_for(&registry_key), stranger];
+        assert!(process_instruction(&program_id, &accounts, &VoteInstruction::GetResults { vote_id: other, question_index: None }.pack()).is_ok());
+    }
+
+    #[test]
+    fn test_simulation_instructions() {
+        let program_id = Pubkey::new_unique();
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voters = [Pubkey::new_unique(), Pubkey::new_unique()];
+
+        let config = VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string(), "Option 2".to_string()]).unwrap();
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+        for voter in &voters {
+            harness.allow(vote_id, &creator, voter).unwrap();
+        }
+        harness.cast(vote_id, &voters[0], 1).unwrap();
+        let registry_key = save_registry(&mut harness, &program_id);
+        let before = harness.account_for(&registry_key).data.borrow().to_vec();
+
+        // The voters do not sign the dry runs, which write nothing
+        let mut simulate = |instruction: VoteInstruction, voter: &Pubkey| {
+            let mut voter_info = harness.account_for(voter);
+            voter_info.is_signer = false;
+            process_instruction(&program_id, &[harness.account_for(&registry_key), voter_info], &instruction.pack()).unwrap();
+            decode_return_data::<SimulationResponse>(&program_id, &program_id, &take_return_data().unwrap()).unwrap()
+        };
+
+        let ballot = simulate(VoteInstruction::SimulateVote { vote_id, question_index: 0, option_index: OptionIndex(0) }, &voters[1]);
+        assert_eq!((ballot.predicted_error(), ballot.questions[0].votes.clone()), (None, vec![1, 1]));
+        let delegation = simulate(VoteInstruction::SimulateDelegate { vote_id, delegate: voters[1], expires_at: None }, &voters[0]);
+        assert_eq!(*harness.account_for(&registry_key).data.borrow(), &before[..]);
+
+        // The voter already cast their ballot, the real delegation fails the way the simulation said
+        let accounts = [harness.account_for(&voters[0]), harness.account_for(&registry_key)];
+        let delegated = process_instruction(&program_id, &accounts, &VoteInstruction::DelegateVote { vote_id, delegate: voters[1], expires_at: None }.pack());
+        assert_eq!(delegation.predicted_error().map(Err), Some(delegated));
+    }
+
+    #[test]
+    fn test_cast_vote_joins_open_vote() {
+        let program_id = Pubkey::new_unique();
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let (staked, unstaked) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+        let config = VoteConfig {
+            eligibility: Some(Eligibility::MinStake(MinStake { min_lamports: 5_000_000 })),
+            ..VoteConfig::from_labels("Open Poll".to_string(), vec!["Yes".to_string(), "No".to_string()]).unwrap()
+        };
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+        let registry_key = save_registry(&mut harness, &program_id);
+        for (voter, lamports) in [(staked, 1_000_000_000), (unstaked, 4_000_000)] {
+            harness.set_owner(&voter, &system_program::id());
+            harness.set_lamports(&voter, lamports);
+        }
+
+        // The dry run gets the eligibility account after the voter, the ballot reads the voter account itself
+        let mut simulate = |voter: &Pubkey| {
+            let mut voter_info = harness.account_for(voter);
+            voter_info.is_signer = false;
+            let accounts = [harness.account_for(&registry_key), voter_info, harness.account_for(voter)];
+            process_instruction(&program_id, &accounts, &VoteInstruction::SimulateVote { vote_id, question_index: 0, option_index: OptionIndex(0) }.pack()).unwrap();
+            decode_return_data::<SimulationResponse>(&program_id, &program_id, &take_return_data().unwrap()).unwrap().predicted_error()
+        };
+        let predictions = [simulate(&staked), simulate(&unstaked)];
+        assert_eq!(predictions, [None, Some(VoteError::EligibilityNotMet.into())]);
+
+        // First ballots join the vote when the voter passes the check, as the simulations predicted
+        for (voter, prediction) in [staked, unstaked].iter().zip(predictions) {
+            let cast = cast_vote(&mut harness, &program_id, &registry_key, voter, voter, (vote_id, 0, 0));
+            assert_eq!(cast.err(), prediction);
+        }
+        let voting = Voting::load(&harness.account_for(&registry_key)).unwrap();
+        assert!(voting.has_voted(vote_id, &staked));
+        assert!(!voting.votes[&vote_id].is_voter_allowed(&unstaked));
+    }
+
+    #[test]
+    fn test_scrub_voter_instruction() {
+        let program_id = Pubkey::new_unique();
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voter = Pubkey::new_unique();
+        let payer = Pubkey::new_unique();
+
+        let config = VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string(), "Option 2".to_string()]).unwrap();
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+        harness.allow(vote_id, &creator, &voter).unwrap();
+        let registry_key = save_registry(&mut harness, &program_id);
+        harness.set_lamports(&payer, 1_000_000_000);
+        assert!(cast_vote(&mut harness, &program_id, &registry_key, &voter, &payer, (vote_id, 0, 1)).is_ok());
+
+        harness.voting = Voting::load(&harness.account_for(&registry_key)).unwrap();
+        harness.close(vote_id, &creator).unwrap();
+        let registry = harness.account_for(&registry_key);
+        harness.voting.save(&registry).unwrap();
+
+        let (vote_address, _) = get_vote_address(&program_id, &registry_key, vote_id);
+        let (receipt_key, _) = get_receipt_address(&program_id, &vote_address, &voter);
+        let refund = harness.lamports(&receipt_key);
+        let data = VoteInstruction::ScrubVoter { vote_id, voter }.pack();
+
+        // The receipt has to be the voter's
+        let accounts = [harness.account_for(&creator), harness.account_for(&registry_key), harness.account_for(&payer)];
+        assert_eq!(process_instruction(&program_id, &accounts, &data), Err(ProgramError::InvalidSeeds));
+
+        // The caller passed again as the receipt would be credited its own balance, and the registry cannot stand in
+        for receipt in [creator, registry_key] {
+            let accounts = [harness.account_for(&creator), harness.account_for(&registry_key), harness.account_for(&receipt)];
+            assert_eq!(process_instruction(&program_id, &accounts, &data), Err(VoteError::DuplicateAccount.into()));
+        }
+        assert_eq!(harness.lamports(&creator), 0);
+
+        let accounts = [harness.account_for(&creator), harness.account_for(&registry_key), harness.account_for(&receipt_key)];
+        assert_eq!(process_instruction(&program_id, &accounts, &data), Ok(()));
+        assert_eq!((harness.lamports(&creator), harness.lamports(&receipt_key)), (refund, 0));
+        assert!(harness.account_for(&receipt_key).data.borrow().iter().all(|byte| *byte == 0));
+
+        let registry = harness.account_for(&registry_key);
+        assert!(!registry.data.borrow().windows(32).any(|window| window == voter.as_ref()));
+        assert_eq!(Voting::load(&registry).unwrap().votes[&vote_id].questions[0].votes["Option 2"], 1);
+    }
+
+    #[test]
+    fn test_token_burn_checks() {
+        let program_id = Pubkey::new_unique();
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voter = Pubkey::new_unique();
+        let [mint, token_account] = [Pubkey::new_unique(), Pubkey::new_unique()];
+
+        let vote_type = VoteType::TokenBurn { mint, burn_amount: 50 };
+        let config = VoteConfig { vote_type, ..VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string(), "Option 2".to_string()]).unwrap() };
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+        harness.allow(vote_id, &creator, &voter).unwrap();
+        let registry_key = save_registry(&mut harness, &program_id);
+        harness.set_lamports(&voter, 1_000_000_000);
+        let (vote_address, _) = get_vote_address(&program_id, &registry_key, vote_id);
+        let (receipt_key, _) = get_receipt_address(&program_id, &vote_address, &voter);
+
+        // Token account of the voter, `approved` being what the vote address may burn
+        let set_holding = |harness: &mut TestHarness, holding_mint: Pubkey, amount: u64, approved: u64| {
+            let holding = spl_token::state::Account {
+                mint: holding_mint,
+                owner: voter,
+                amount,
+                delegate: COption::Some(vote_address),
+                state: spl_token::state::AccountState::Initialized,
+                delegated_amount: approved,
+                ..spl_token::state::Account::default()
+            };
+            let mut data = vec![0; spl_token::state::Account::LEN];
+            holding.pack_into_slice(&mut data);
+            harness.set_data(&token_account, data);
+            harness.set_owner(&token_account, &spl_token::id());
+        };
+        let cast = |harness: &mut TestHarness, burning: bool| {
+            let mut accounts = vec![harness.account_for(&voter), harness.account_for(&voter), harness.account_for(&registry_key), harness.account_for(&receipt_key), harness.account_for(&system_program::id())];
+            if burning {
+                accounts.extend([token_account, mint, vote_address, spl_token::id()].iter().map(|key| harness.account_for(key)));
+            }
+            process_instruction(&program_id, &accounts, &VoteInstruction::CastVote { vote_id, question_index: 0, option_index: OptionIndex(1) }.pack())
+        };
+
+        set_holding(&mut harness, Pubkey::new_unique(), 100, 100);
+        assert_eq!(cast(&mut harness, true), Err(VoteError::WrongTokenMint.into()));
+        set_holding(&mut harness, mint, 40, 100);
+        assert_eq!(cast(&mut harness, true), Err(VoteError::InsufficientTokens.into()));
+        set_holding(&mut harness, mint, 100, 40);
+        assert_eq!(cast(&mut harness, true), Err(VoteError::BurnNotApproved.into()));
+        set_holding(&mut harness, mint, 100, 100);
+        assert_eq!(cast(&mut harness, false), Err(ProgramError::NotEnoughAccountKeys));
+
+        // No failed burn left a ballot behind
+        assert!(!Voting::load(&harness.account_for(&registry_key)).unwrap().has_voted(vote_id, &voter));
+
+        // Batched ballots cannot burn
+        let accounts = [harness.account_for(&voter), harness.account_for(&voter), harness.account_for(&registry_key), harness.account_for(&system_program::id()), harness.account_for(&receipt_key)];
+        let data = VoteInstruction::CastVotes { ballots: vec![(vote_id, OptionIndex(1))] }.pack();
+        assert_eq!(process_instruction(&program_id, &accounts, &data), Err(ProgramError::InvalidArgument));
+    }
+
+    // One encoded instruction of every kind, for the malformed input tests
+    fn sample_instructions() -> Vec<Vec<u8>> {
+        let policy = CreationPolicy { fee: Some(CreationFee { lamports: 100, treasury: Pubkey::new_unique() }), cooldown_slots: Some(5), deposit: None };
+        vec![
+            VoteInstruction::InitializeRegistry { admin: Pubkey::new_unique() }.pack(),
+            VoteInstruction::UpdatePolicy { policy }.pack(),
+            VoteInstruction::Pause.pack(),
+            VoteInstruction::Unpause.pack(),
+            VoteInstruction::CastVote { vote_id: VoteId(0), question_index: 0, option_index: OptionIndex(1) }.pack(),
+            VoteInstruction::RepairVote { vote_id: VoteId(3) }.pack(),
+            VoteInstruction::HasVoted { vote_id: VoteId(0), voter: Pubkey::new_unique() }.pack(),
+            VoteInstruction::GetResults { vote_id: VoteId(0), question_index: Some(1) }.pack(),
+            VoteInstruction::DelegateVote { vote_id: VoteId(2), delegate: Pubkey::new_unique(), expires_at: Some(-7) }.pack(),
+            VoteInstruction::DelegateVote { vote_id: VoteId(2), delegate: Pubkey::new_unique(), expires_at: None }.pack(),
+        ]
+    }
+
+    #[test]
+    fn test_unpack_fixed_layouts() {
+        // The hand-written decoders agree with Borsh on every ballot and delegation, and leave their neighbours to it
+        let instructions = [
+            VoteInstruction::CastVote { vote_id: VoteId(u64::MAX), question_index: 3, option_index: OptionIndex(513) },
+            VoteInstruction::DelegateVote { vote_id: VoteId(1 << 40), delegate: Pubkey::new_unique(), expires_at: Some(i64::MIN) },
+            VoteInstruction::DelegateVote { vote_id: VoteId(0), delegate: Pubkey::new_unique(), expires_at: None },
+            VoteInstruction::CastVotes { ballots: vec![(VoteId(1), OptionIndex(2))] },
+            VoteInstruction::AddAllowedVoter { vote_id: VoteId(0), voter: Pubkey::new_unique() },
+            VoteInstruction::CloseVote { vote_id: VoteId(5) },
+        ];
+        for instruction in instructions {
+            let data = instruction.pack();
+            assert_eq!(VoteInstruction::unpack(&data), Ok(VoteInstruction::try_from_slice(&data).unwrap()));
+            assert_eq!(VoteInstruction::unpack(&data), Ok(instruction));
+        }
+
+        // An option tag Borsh refuses is refused too
+        let mut data = VoteInstruction::DelegateVote { vote_id: VoteId(0), delegate: Pubkey::new_unique(), expires_at: None }.pack();
+        *data.last_mut().unwrap() = 2;
+        assert!(VoteInstruction::try_from_slice(&data).is_err());
+        assert_eq!(VoteInstruction::unpack(&data), Err(ProgramError::InvalidInstructionData));
+    }
+
+    #[test]
+    fn test_unpack_truncated_and_oversized() {
+        for data in sample_instructions() {
+            assert!(VoteInstruction::unpack(&data).is_ok());
+
+            for len in 0..data.len() {
+                assert_eq!(VoteInstruction::unpack(&data[..len]), Err(ProgramError::InvalidInstructionData));
+            }
+
+            let mut oversized = data.clone();
+            oversized.push(0);
+            assert_eq!(VoteInstruction::unpack(&oversized), Err(ProgramError::InvalidInstructionData));
+        }
+
+        assert_eq!(VoteInstruction::unpack(&[0xff]), Err(ProgramError::InvalidInstructionData));
+    }
+
+    proptest! {
+        #[test]
+        fn test_arbitrary_instruction_data(data in proptest::collection::vec(any::<u8>(), 0..256)) {
+            let _ = process_arbitrary_instruction(&data);
+        }
+
+        // Start from a valid instruction so the handlers are reached, then corrupt it
+        #[test]
+        fn test_mutated_instruction_data(kind in 0..5usize, position in any::<usize>(), byte in any::<u8>(), len in any::<usize>()) {
+            let mut data = sample_instructions()[kind].clone();
+            let index = position % data.len();
+            data[index] = byte;
+            data.truncate(len % (data.len() + 1));
+
+            let _ = process_arbitrary_instruction(&data);
+        }
+    }
+}
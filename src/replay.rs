@@ -0,0 +1,178 @@
+// Rebuild the state of a vote from its events, e.g. for an indexer checking the logs it collected against the
+// account. Creation, allowlist additions, ballots, closing and certifications are replayed. Other changes log no
+// event or too little to apply them, so a vote changed that way replays to a different state, and
+// `compare_with_account` names the first field where it differs
+use std::cell::Cell;
+
+use borsh::BorshDeserialize;
+use solana_program::clock::Slot;
+use solana_program::program_error::ProgramError;
+
+use crate::{Vote, VoteEvent};
+
+thread_local! {
+    // Clock of the event being replayed, read in place of the Clock sysvar
+    static REPLAY_CLOCK: Cell<Option<(Slot, i64)>> = const { Cell::new(None) };
+}
+
+pub(crate) fn replay_clock() -> Option<(Slot, i64)> {
+    REPLAY_CLOCK.with(Cell::get)
+}
+
+// Run `apply` with the library reading the clock of an event
+fn at_clock<T>(slot: Slot, timestamp: i64, apply: impl FnOnce() -> Result<T, ProgramError>) -> Result<T, ProgramError> {
+    REPLAY_CLOCK.with(|clock| clock.set(Some((slot, timestamp))));
+    let result = apply();
+    REPLAY_CLOCK.with(|clock| clock.set(None));
+    result
+}
+
+// The events of one vote in sequence order, starting with its `VoteCreated`. Milestones are raised again by the
+// replayed changes. Returns error for events of another vote and for events that cannot be applied
+pub fn replay_events(events: &[VoteEvent]) -> Result<Vote, ProgramError> {
+    let Some((VoteEvent::VoteCreated { state, .. }, events)) = events.split_first() else {
+        return Err(ProgramError::InvalidArgument); // Return error unless the events start with the creation
+    };
+    let mut vote = Vote::try_from_slice(state).map_err(|_| ProgramError::InvalidAccountData)?;
+    vote.events_muted = true;
+    vote.event_seq = vote.event_seq.saturating_add(1); // The creation itself
+
+    for event in events {
+        if event.vote_id() != vote.id {
+            return Err(ProgramError::InvalidArgument); // Return error for events of another vote
+        }
+
+        match event {
+            VoteEvent::VoterAdded { voter, weight, valid_until, slot, timestamp, .. } => {
+                let creator = vote.creator;
+                at_clock(*slot, *timestamp, || vote.add_allowed_voter(*voter, *weight, *valid_until, &creator))?;
+            }
+            VoteEvent::BallotCast { voter, question_index, option_index, comment, slot, timestamp, .. } => at_clock(*slot, *timestamp, || {
+                if vote.is_voter_allowed(voter) {
+                    vote.reclaim_expired_delegations(voter)?;
+                }
+                vote.vote_with_comment(voter, *question_index as usize, *option_index, comment.clone())
+            })?,
+            VoteEvent::OptionWithdrawn { option_index, slot, timestamp, .. } => {
+                let creator = vote.creator;
+                at_clock(*slot, *timestamp, || vote.withdraw_option(*option_index, &creator))?;
+            }
+            VoteEvent::VoteClosed { closure, slot, .. } => at_clock(*slot, closure.closed_at, || vote.close_with(*closure))?,
+            VoteEvent::ResultsCertified { certifier, certified_at, .. } => {
+                vote.certify_results(certifier, *certified_at)?;
+                vote.emit(event.clone());
+            }
+            VoteEvent::MilestoneReached { .. } => {}
+            // The events do not say enough to strike or scrub the ballots again
+            VoteEvent::VoteCreated { .. } | VoteEvent::VoterBallotsInvalidated { .. } | VoteEvent::VoterScrubbed { .. } => {
+                return Err(ProgramError::InvalidArgument);
+            }
+        }
+    }
+
+    vote.events_muted = false;
+    Ok(vote)
+}
+
+// First field, in the order of the state, where a vote differs from the one stored in a state account
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldDiff {
+    pub field: &'static str,
+    pub replayed: String, // Debug formatted values
+    pub stored: String,
+}
+
+// Every field of the vote, so a field added to `Vote` and not here fails to compile
+macro_rules! first_difference {
+    ($replayed:expr, $stored:expr, [$($field:ident),* $(,)?]) => {{
+        let Vote { $($field,)* events_muted: _ } = $replayed;
+        $(
+            if *$field != $stored.$field {
+                return Ok(Some(FieldDiff { field: stringify!($field), replayed: format!("{:?}", $field), stored: format!("{:?}", $stored.$field) }));
+            }
+        )*
+    }};
+}
+
+// Compare a replayed vote with the data of its state account, `None` when they match
+pub fn compare_with_account(vote: &Vote, account_bytes: &[u8]) -> Result<Option<FieldDiff>, ProgramError> {
+    let stored = Vote::unpack(account_bytes)?;
+
+    first_difference!(vote, stored, [
+        id, title, description, questions, vote_type, creator, allowed_voters, results_visibility, allowlist_visibility,
+        redeemed_vouchers, max_voters, max_options, min_close_at, per_option_cap, per_option_voter_cap, winning_threshold_bps,
+        quorum, per_voter_window, deadline, min_slots_between_ballots, creators, approval_threshold, pending_action, tie_break,
+        tie_resolutions, require_delegate_acceptance, pending_delegations, allow_write_ins, delegation_policy, eligibility,
+        history_bucket_slots, history, changelog, latest_change_seq, closure, runoff_of, parent, conviction_frozen_at, observers,
+        edits, deposit, projection_visibility, milestones, opted_out, sample, prerequisite, pinned, display_order, ledger,
+        max_received_delegations, imported, seats, per_option_quorum, scrubbed_voters, required_certifications, certifications,
+        translations, option_format, grace_period_slots, require_comment, refund_on_withdraw, attestations,
+        event_seq, status,
+    ]);
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{set_clock, set_slot, take_envelopes, TestHarness};
+    use crate::{VoteConfig, EVENT_VERSION};
+    use solana_program::pubkey::Pubkey;
+
+    #[test]
+    fn test_replay_events() {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voters = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+        take_envelopes();
+
+        // A lifecycle spread over several clocks
+        set_clock(1_000);
+        set_slot(10);
+        let config = VoteConfig { quorum: Some(2), ..VoteConfig::from_labels("Budget".to_string(), vec!["Yes".to_string(), "No".to_string()]).unwrap() };
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+        for voter in &voters {
+            harness.allow(vote_id, &creator, voter).unwrap();
+        }
+        set_clock(2_000);
+        set_slot(20);
+        for (voter, option_index) in voters.iter().zip([0, 1, 0]) {
+            harness.cast(vote_id, voter, option_index).unwrap();
+        }
+        set_clock(3_000);
+        set_slot(30);
+        harness.close(vote_id, &creator).unwrap();
+        harness.voting.certify_results_as(&creator, vote_id).unwrap();
+
+        // The events of the vote are numbered in order, without gaps
+        let envelopes = take_envelopes();
+        assert!(envelopes.iter().all(|envelope| envelope.version == EVENT_VERSION));
+        assert_eq!(envelopes.iter().map(|envelope| envelope.seq).collect::<Vec<_>>(), (0..envelopes.len() as u64).collect::<Vec<_>>());
+        let events: Vec<VoteEvent> = envelopes.into_iter().map(|envelope| envelope.event).collect();
+
+        // Replaying them gives the state of the account
+        let account_key = Pubkey::new_unique();
+        harness.set_data(&account_key, vec![0; 8192]);
+        let account = harness.account_for(&account_key);
+        let stored = &harness.voting.votes[&vote_id];
+        stored.save(&account).unwrap();
+        let replayed = replay_events(&events).unwrap();
+        assert_eq!(&replayed, stored);
+        assert_eq!(compare_with_account(&replayed, &account.data.borrow()), Ok(None));
+
+        // Without the second ballot the tallies are the first field to differ
+        let position = events.iter().position(|event| matches!(event, VoteEvent::BallotCast { voter, .. } if *voter == voters[1])).unwrap();
+        let mut dropped = events.clone();
+        dropped.remove(position);
+        let diff = compare_with_account(&replay_events(&dropped).unwrap(), &account.data.borrow()).unwrap().unwrap();
+        assert_eq!(diff.field, "questions");
+        assert_ne!(diff.replayed, diff.stored);
+
+        // The events have to start with the creation, and belong to one vote
+        assert_eq!(replay_events(&events[1..]), Err(ProgramError::InvalidArgument));
+        let mut foreign = events.clone();
+        foreign.push(VoteEvent::MilestoneReached { vote_id: crate::VoteId(9), milestone: crate::Milestones::QUORUM_REACHED });
+        assert_eq!(replay_events(&foreign), Err(ProgramError::InvalidArgument));
+        assert_eq!(replay_clock(), None);
+    }
+}
@@ -0,0 +1,102 @@
+// Answers of the read instructions, set as Borsh encoded return data. Shared by the program, by clients
+// decoding them from simulated transactions and by programs reading them after a CPI
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::program_error::ProgramError;
+use solana_program::pubkey::Pubkey;
+#[cfg(feature = "client")]
+use {base64::prelude::*, solana_client::rpc_response::RpcSimulateTransactionResult};
+
+use crate::{BatchResult, OptionFormat, VoteId, VoteOption, VoteResultsLite, VoterStatus};
+
+// Tallies of a vote, answer of `GetResults`
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct ResultsResponse {
+    pub vote_id: VoteId,
+    pub questions: Vec<QuestionTally>, // Every question, or only the one asked for
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct QuestionTally {
+    pub question_index: u32,
+    pub votes: Vec<u32>, // Ballots of each option, in option order
+}
+
+// Options of the first question, answer of `GetOptions`
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct OptionsResponse {
+    pub vote_id: VoteId,
+    pub options: Vec<VoteOption>,
+    pub format: OptionFormat, // Syntax the labels follow
+}
+
+// Compact results of several votes, answer of `GetResultsBatch`
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct ResultsBatchResponse {
+    pub results: Vec<(VoteId, Result<VoteResultsLite, u32>)>, // In request order, a failed vote carries the code of its `VoteError`
+}
+
+impl ResultsBatchResponse {
+    pub fn new(results: Vec<BatchResult>) -> Self {
+        Self { results: results.into_iter().map(|(vote_id, result)| (vote_id, result.map_err(|error| error.code()))).collect() }
+    }
+}
+
+// What a voter can still do in a vote, answer of `GetVoterStatus`
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct VoterStatusResponse {
+    pub vote_id: VoteId,
+    pub voter: Pubkey,
+    pub status: VoterStatus,
+}
+
+// Outcome of a simulated ballot or delegation, answer of `SimulateVote` and `SimulateDelegate`
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct SimulationResponse {
+    pub vote_id: VoteId,
+    pub error: Option<u64>,           // Error the real call would fail with, encoded like `ProgramError` into a `u64`
+    pub questions: Vec<QuestionTally>, // Tallies after the call, empty when it fails or the results are hidden from the caller
+}
+
+impl SimulationResponse {
+    pub fn predicted_error(&self) -> Option<ProgramError> {
+        self.error.map(ProgramError::from)
+    }
+}
+
+// Decode the return data of a read instruction. `returned_by` is the program that set the data, which has to
+// be the voting program: a program called after it may have replaced the data with its own
+pub fn decode_return_data<T: BorshDeserialize>(program_id: &Pubkey, returned_by: &Pubkey, data: &[u8]) -> Result<T, ProgramError> {
+    if returned_by != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    T::try_from_slice(data).map_err(|_| ProgramError::InvalidAccountData)
+}
+
+// Decode the return data of a transaction simulated through RPC, which reports it base64 encoded
+#[cfg(feature = "client")]
+pub fn decode_simulation<T: BorshDeserialize>(program_id: &Pubkey, simulation: &RpcSimulateTransactionResult) -> Result<T, ProgramError> {
+    let return_data = simulation.return_data.as_ref().ok_or(ProgramError::InvalidAccountData)?; // Return error if the simulation set no return data
+    let returned_by: Pubkey = return_data.program_id.parse().map_err(|_| ProgramError::InvalidArgument)?;
+    let data = BASE64_STANDARD.decode(&return_data.data.0).map_err(|_| ProgramError::InvalidArgument)?;
+    decode_return_data(program_id, &returned_by, &data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_return_data() {
+        let program_id = Pubkey::new_unique();
+        let response = ResultsResponse { vote_id: VoteId(2), questions: vec![QuestionTally { question_index: 0, votes: vec![3, 1] }] };
+        let data = borsh::to_vec(&response).unwrap();
+
+        assert_eq!(decode_return_data(&program_id, &program_id, &data), Ok(response));
+        assert_eq!(decode_return_data::<ResultsResponse>(&program_id, &Pubkey::new_unique(), &data), Err(ProgramError::IncorrectProgramId));
+        assert_eq!(decode_return_data::<ResultsResponse>(&program_id, &program_id, &data[..data.len() - 1]), Err(ProgramError::InvalidAccountData));
+
+        // The yes/no queries answer with a Borsh bool
+        assert_eq!(decode_return_data(&program_id, &program_id, &[1]), Ok(true));
+    }
+}
@@ -0,0 +1,82 @@
+// Drawing of a committee from the allowlist of a closed vote. The draw only depends on its seed and the
+// voters in canonical order, so anyone holding the recorded seed can repeat it off-chain
+use solana_program::hash::{hashv, Hash};
+use solana_program::pubkey::Pubkey;
+
+// Seed of a sample, binding the final results to a slot hash the creator could not know in advance
+pub fn sample_seed(results_hash: &Hash, slot_hash: &Hash) -> Hash {
+    hashv(&[results_hash.as_ref(), slot_hash.as_ref()])
+}
+
+// xorshift64* generator, plenty for picking voters and cheap in compute units
+struct Xorshift(u64);
+
+impl Xorshift {
+    fn new(seed: &Hash) -> Self {
+        let state = u64::from_le_bytes(seed.to_bytes()[..8].try_into().unwrap_or_default());
+        Self(state.max(1)) // The generator gets stuck at zero
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+}
+
+// `count` distinct voters drawn from `voters` with a partial Fisher-Yates shuffle, in the order they were
+// drawn. The voters are sorted first, so the order they are passed in does not matter
+pub fn draw_sample(seed: &Hash, voters: &[Pubkey], count: usize) -> Vec<Pubkey> {
+    let mut pool = voters.to_vec();
+    pool.sort();
+    pool.dedup();
+
+    let mut rng = Xorshift::new(seed);
+    let count = count.min(pool.len());
+    for index in 0..count {
+        let remaining = (pool.len() - index) as u64;
+        let pick = index + (rng.next() % remaining) as usize;
+        pool.swap(index, pick);
+    }
+
+    pool.truncate(count);
+    pool
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_program::hash::hash;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_draw_sample_deterministic() {
+        let voters: Vec<Pubkey> = (0..20).map(|_| Pubkey::new_unique()).collect();
+        let seed = sample_seed(&hash(b"results"), &hash(b"slot 7"));
+
+        // The order the voters come in has no effect
+        let mut reversed = voters.clone();
+        reversed.reverse();
+        assert_eq!(draw_sample(&seed, &voters, 7), draw_sample(&seed, &reversed, 7));
+
+        // Another slot hash gives another sample
+        let other_seed = sample_seed(&hash(b"results"), &hash(b"slot 8"));
+        assert_ne!(draw_sample(&seed, &voters, 7), draw_sample(&other_seed, &voters, 7));
+    }
+
+    #[test]
+    fn test_draw_sample_without_replacement() {
+        let voters: Vec<Pubkey> = (0..10).map(|_| Pubkey::new_unique()).collect();
+
+        for round in 0..50u32 {
+            let seed = hash(&round.to_le_bytes());
+            let sample = draw_sample(&seed, &voters, 10);
+            assert_eq!(sample.iter().collect::<HashSet<_>>().len(), 10);
+            assert!(sample.iter().all(|voter| voters.contains(voter)));
+        }
+
+        // A zero seed still draws
+        assert_eq!(draw_sample(&Hash::default(), &voters, 3).len(), 3);
+    }
+}
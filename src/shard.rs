@@ -0,0 +1,74 @@
+// Directory accounts of a sharded registry. Each shard lists the votes created while it was the newest one, up to
+// the shard capacity of the registry, so clients can page through a large registry one bounded account at a time
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::account_info::AccountInfo;
+use solana_program::program_error::ProgramError;
+use solana_program::pubkey::Pubkey;
+
+use crate::{Title, VoteId};
+
+pub const SHARD_DISCRIMINATOR: [u8; 8] = *b"svshard_";
+
+// Largest shard capacity, a full shard stays below the size an account created through CPI may have
+pub const MAX_SHARD_CAPACITY: u32 = 48;
+
+// What a shard keeps of a vote, the parts that never change after the creation
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct ShardEntry {
+    pub id: VoteId,
+    pub creator: Pubkey,
+    pub title: Title,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct RegistryShard {
+    pub registry: Pubkey,
+    pub index: u32,
+    pub entries: Vec<ShardEntry>, // In creation order
+}
+
+impl RegistryShard {
+    pub fn new(registry: Pubkey, index: u32) -> Self {
+        Self { registry, index, entries: Vec::new() }
+    }
+
+    // Account size of a shard holding `capacity` entries with the longest titles
+    pub fn space(capacity: u32) -> usize {
+        let entry = ShardEntry { id: VoteId(0), creator: Pubkey::default(), title: Title::longest() };
+        let mut prototype = Self::new(Pubkey::default(), 0);
+        prototype.entries = vec![entry; capacity as usize];
+
+        SHARD_DISCRIMINATOR.len() + borsh::object_length(&prototype).unwrap_or(usize::MAX)
+    }
+
+    pub fn save(&self, account: &AccountInfo) -> Result<(), ProgramError> {
+        let mut serialized = SHARD_DISCRIMINATOR.to_vec();
+        self.serialize(&mut serialized)?;
+
+        let mut data = account.try_borrow_mut_data()?;
+        if serialized.len() > data.len() {
+            return Err(ProgramError::AccountDataTooSmall); // Return error if the shard is full
+        }
+
+        data[..serialized.len()].copy_from_slice(&serialized);
+
+        Ok(())
+    }
+
+    pub fn load(account: &AccountInfo, program_id: &Pubkey) -> Result<Self, ProgramError> {
+        if account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId); // Return error if the shard was not created by the program
+        }
+
+        Self::unpack(&account.try_borrow_data()?)
+    }
+
+    // Decode the data of a shard account, e.g. fetched by a client
+    pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        if !data.starts_with(&SHARD_DISCRIMINATOR) {
+            return Err(ProgramError::UninitializedAccount); // Return error if the shard was never written
+        }
+
+        Ok(Self::deserialize(&mut &data[SHARD_DISCRIMINATOR.len()..])?)
+    }
+}
@@ -0,0 +1,137 @@
+// A registry shared between the threads of an off-chain server, e.g. behind an `Arc` in a web service.
+// Reads take the lock for reading, so they run side by side; changes go through the `write` guard
+use solana_program::program_error::ProgramError;
+use solana_program::pubkey::Pubkey;
+use std::sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::{OptionIndex, OptionsResponse, ResultsSummary, VoteId, VoteOption, VoteResults, VoterStatus, Voting};
+
+#[derive(Debug)]
+pub struct SharedVoting {
+    voting: RwLock<Voting>,
+}
+
+impl SharedVoting {
+    pub fn new(voting: Voting) -> Self {
+        Self { voting: RwLock::new(voting) }
+    }
+
+    // A thread panicking while holding the lock does not lock the others out, they go on with the
+    // registry as the panicking call left it
+    pub fn read(&self) -> RwLockReadGuard<'_, Voting> {
+        self.voting.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn write(&self) -> RwLockWriteGuard<'_, Voting> {
+        self.voting.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn into_inner(self) -> Voting {
+        self.voting.into_inner().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn vote_exists(&self, vote_id: VoteId) -> bool {
+        self.read().vote_exists(vote_id)
+    }
+
+    pub fn has_voted(&self, vote_id: VoteId, voter: &Pubkey) -> bool {
+        self.read().has_voted(vote_id, voter)
+    }
+
+    pub fn option_exists(&self, vote_id: VoteId, index: OptionIndex) -> bool {
+        self.read().option_exists(vote_id, index)
+    }
+
+    // Owned copies, the lock is released before the caller looks at them
+    pub fn get_options(&self, vote_id: VoteId) -> Result<Vec<VoteOption>, ProgramError> {
+        self.read().get_options(vote_id).cloned()
+    }
+
+    pub fn get_options_response(&self, vote_id: VoteId) -> Result<OptionsResponse, ProgramError> {
+        self.read().get_options_response(vote_id)
+    }
+
+    pub fn get_full_results_as(&self, caller: &Pubkey, vote_id: VoteId) -> Result<VoteResults, ProgramError> {
+        self.read().get_full_results_as(caller, vote_id)
+    }
+
+    pub fn get_summary_as(&self, caller: &Pubkey, vote_id: VoteId) -> Result<ResultsSummary, ProgramError> {
+        self.read().get_summary_as(caller, vote_id)
+    }
+
+    pub fn get_voter_status_as(&self, caller: &Pubkey, vote_id: VoteId, voter: &Pubkey) -> Result<VoterStatus, ProgramError> {
+        self.read().get_voter_status_as(caller, vote_id, voter)
+    }
+
+    pub fn is_voter_allowed(&self, vote_id: VoteId, voter: &Pubkey) -> Result<bool, ProgramError> {
+        self.read().is_voter_allowed(vote_id, voter)
+    }
+}
+
+impl From<Voting> for SharedVoting {
+    fn from(voting: Voting) -> Self {
+        Self::new(voting)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestHarness;
+    use crate::VoteConfig;
+    use std::sync::Arc;
+    use std::thread;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn test_shared_voting_threads() {
+        assert_send_sync::<Voting>();
+        assert_send_sync::<SharedVoting>();
+
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+        let voters: Vec<Pubkey> = (0..20).map(|_| Pubkey::new_unique()).collect();
+        let config = VoteConfig::from_labels("Test Vote".to_string(), vec!["Option 1".to_string(), "Option 2".to_string()]).unwrap();
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+        for voter in &voters {
+            harness.allow(vote_id, &creator, voter).unwrap();
+        }
+        let shared = Arc::new(SharedVoting::new(harness.voting));
+
+        // One writer casts the ballots while the readers keep reading the results
+        let writer = {
+            let (shared, voters) = (Arc::clone(&shared), voters.clone());
+            thread::spawn(move || {
+                for (index, voter) in voters.iter().enumerate() {
+                    shared.write().vote_as(voter, vote_id, OptionIndex(index as u16 % 2)).unwrap();
+                }
+            })
+        };
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let (shared, voters) = (Arc::clone(&shared), voters.clone());
+                thread::spawn(move || {
+                    for _ in 0..50 {
+                        // A ballot is in the summary exactly when the voter has voted, whatever the writer is at
+                        let guard = shared.read();
+                        let summary = guard.get_summary_as(&creator, vote_id).unwrap();
+                        let voted = voters.iter().filter(|voter| guard.has_voted(vote_id, voter)).count();
+                        assert_eq!(summary.participating_voters as usize, voted);
+                        drop(guard);
+                        assert_eq!(shared.get_options(vote_id).unwrap().len(), 2);
+                    }
+                })
+            })
+            .collect();
+
+        writer.join().unwrap();
+        for reader in readers {
+            reader.join().unwrap();
+        }
+
+        assert_eq!(shared.get_summary_as(&creator, vote_id).unwrap().participating_voters, 20);
+        let results = shared.get_full_results_as(&creator, vote_id).unwrap();
+        assert_eq!(Arc::into_inner(shared).unwrap().into_inner().get_full_results_as(&creator, vote_id), Ok(results));
+    }
+}
@@ -0,0 +1,347 @@
+// Layout of the registry account. The settings and counters of the registry come first, then every vote in a slot
+// of its own behind its ID and its length, so a ballot or a delegation decodes and writes back only its vote
+//
+//     discriminator | settings | vote count (u32) | per vote in ID order: ID (u64), length (u32), Borsh vote
+//
+// Accounts written before the slots start with `LEGACY_REGISTRY_DISCRIMINATOR` and hold the votes as one Borsh
+// map in front of the settings. They are read whole and take the slot layout on their next write
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::ops::Range;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::account_info::AccountInfo;
+use solana_program::program_error::ProgramError;
+
+use crate::{Vote, VoteId, VoteStatus, Voting, REGISTRY_DISCRIMINATOR};
+
+// Where the vote of a registry loaded with `Voting::load_vote_slot` sits in the account data
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VoteSlot {
+    vote_id: VoteId,
+    settings: Range<usize>,
+    vote: Range<usize>, // The Borsh vote, after its length
+    end: usize,         // End of the registry, the free space of the account follows
+}
+
+fn serialize_settings<W: Write>(voting: &Voting, writer: &mut W) -> io::Result<()> {
+    voting.current_id.serialize(writer)?;
+    voting.admin.serialize(writer)?;
+    voting.creation_policy.serialize(writer)?;
+    voting.last_created.serialize(writer)?;
+    voting.paused.serialize(writer)?;
+    voting.stats.serialize(writer)?;
+    voting.groups.serialize(writer)?;
+    voting.next_group_id.serialize(writer)?;
+    voting.templates.serialize(writer)?;
+    voting.next_template_id.serialize(writer)?;
+    voting.shard_capacity.serialize(writer)?;
+    voting.shard_count.serialize(writer)?;
+    voting.newest_shard_len.serialize(writer)?;
+    voting.vote_shards.serialize(writer)?;
+    voting.outbox.serialize(writer)
+}
+
+fn deserialize_settings<R: Read>(reader: &mut R, votes: HashMap<VoteId, Vote>) -> io::Result<Voting> {
+    Ok(Voting {
+        votes,
+        current_id: BorshDeserialize::deserialize_reader(reader)?,
+        admin: BorshDeserialize::deserialize_reader(reader)?,
+        creation_policy: BorshDeserialize::deserialize_reader(reader)?,
+        last_created: BorshDeserialize::deserialize_reader(reader)?,
+        paused: BorshDeserialize::deserialize_reader(reader)?,
+        stats: BorshDeserialize::deserialize_reader(reader)?,
+        groups: BorshDeserialize::deserialize_reader(reader)?,
+        next_group_id: BorshDeserialize::deserialize_reader(reader)?,
+        templates: BorshDeserialize::deserialize_reader(reader)?,
+        next_template_id: BorshDeserialize::deserialize_reader(reader)?,
+        shard_capacity: BorshDeserialize::deserialize_reader(reader)?,
+        shard_count: BorshDeserialize::deserialize_reader(reader)?,
+        newest_shard_len: BorshDeserialize::deserialize_reader(reader)?,
+        vote_shards: BorshDeserialize::deserialize_reader(reader)?,
+        outbox: BorshDeserialize::deserialize_reader(reader)?,
+    })
+}
+
+fn slot_len(len: usize) -> io::Result<u32> {
+    u32::try_from(len).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "registry slot too long"))
+}
+
+impl BorshSerialize for Voting {
+    fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        serialize_settings(self, writer)?;
+
+        let mut vote_ids: Vec<&VoteId> = self.votes.keys().collect();
+        vote_ids.sort();
+        slot_len(vote_ids.len())?.serialize(writer)?;
+        for vote_id in vote_ids {
+            let vote = borsh::to_vec(&self.votes[vote_id])?;
+            vote_id.serialize(writer)?;
+            slot_len(vote.len())?.serialize(writer)?;
+            writer.write_all(&vote)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl BorshDeserialize for Voting {
+    // Slots come in ascending ID order and hold exactly their vote, so every registry has one encoding
+    fn deserialize_reader<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let mut voting = deserialize_settings(reader, HashMap::new())?;
+
+        let count = u32::deserialize_reader(reader)?;
+        let mut previous = None;
+        for _ in 0..count {
+            let vote_id = VoteId::deserialize_reader(reader)?;
+            let len = u32::deserialize_reader(reader)?;
+            if previous.is_some_and(|previous| previous >= vote_id) {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "registry slots out of order"));
+            }
+
+            let mut slot = reader.take(len.into());
+            let vote = Vote::deserialize_reader(&mut slot)?;
+            if slot.limit() != 0 {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "registry slot longer than its vote"));
+            }
+
+            voting.votes.insert(vote_id, vote);
+            previous = Some(vote_id);
+        }
+
+        Ok(voting)
+    }
+}
+
+// Decode the body of a registry account in the layout before the slots
+pub(crate) fn deserialize_legacy(mut state: &[u8]) -> io::Result<Voting> {
+    let votes = HashMap::deserialize_reader(&mut state)?;
+    deserialize_settings(&mut state, votes)
+}
+
+// Replace `range` of `data` by `bytes`, moving the data up to `end` behind it. Returns the new end, the caller
+// checked that it fits
+fn splice(data: &mut [u8], range: Range<usize>, end: usize, bytes: &[u8]) -> usize {
+    let new_end = end - range.len() + bytes.len();
+    data.copy_within(range.end..end, range.start + bytes.len());
+    data[range.start..range.start + bytes.len()].copy_from_slice(bytes);
+    if new_end < end {
+        data[new_end..end].fill(0); // Free space stays zeroed
+    }
+
+    new_end
+}
+
+impl Voting {
+    // Decode the registry with `vote_id` as its only vote, for a ballot or delegation that touches no other vote.
+    // The registry is decoded whole, without a slot, when the account is still in the legacy layout, when the vote
+    // is not in the registry, and when the vote waits on a prerequisite, whose outcome lives in another vote
+    pub fn load_vote_slot(account: &AccountInfo, vote_id: VoteId) -> Result<(Self, Option<VoteSlot>), ProgramError> {
+        let data = account.try_borrow_data()?;
+        let Some(state) = data.strip_prefix(&REGISTRY_DISCRIMINATOR) else {
+            return Ok((Self::unpack(&data)?, None));
+        };
+        let offset = |rest: &[u8]| data.len() - rest.len();
+
+        let mut reader = state;
+        let mut voting = deserialize_settings(&mut reader, HashMap::new())?;
+        let settings = REGISTRY_DISCRIMINATOR.len()..offset(reader);
+
+        // Only the headers of the other slots are read
+        let mut found = None;
+        for _ in 0..u32::deserialize(&mut reader)? {
+            let slot_id = VoteId::deserialize(&mut reader)?;
+            let len = u32::deserialize(&mut reader)? as usize;
+            if len > reader.len() {
+                return Err(ProgramError::InvalidAccountData); // Return error if a slot runs past the data
+            }
+
+            if slot_id == vote_id {
+                found = Some(offset(reader)..offset(reader) + len);
+            }
+            reader = &reader[len..];
+        }
+        let end = offset(reader);
+
+        let Some(slot) = found else {
+            return Ok((Self::unpack(&data)?, None));
+        };
+        let vote = Vote::try_from_slice(&data[slot.clone()])?;
+        if vote.status == VoteStatus::Pending && vote.prerequisite.is_some() {
+            return Ok((Self::unpack(&data)?, None));
+        }
+
+        voting.votes.insert(vote_id, vote);
+        Ok((voting, Some(VoteSlot { vote_id, settings, vote: slot, end })))
+    }
+
+    // Write back a registry loaded with `load_vote_slot`: the settings and the vote in its slot, moving the votes
+    // after them when their length changed. Registries loaded without a slot are saved whole
+    pub fn save_vote_slot(&self, account: &AccountInfo, slot: Option<VoteSlot>) -> Result<(), ProgramError> {
+        let Some(VoteSlot { vote_id, settings, vote: vote_range, end }) = slot else {
+            return self.save(account);
+        };
+        let Some(vote) = self.votes.get(&vote_id).filter(|_| self.votes.len() == 1) else {
+            return Err(ProgramError::InvalidArgument); // Return error if the registry no longer holds just the vote of the slot
+        };
+
+        let vote = borsh::to_vec(vote)?;
+        let mut encoded_settings = Vec::with_capacity(settings.len());
+        serialize_settings(self, &mut encoded_settings)?;
+
+        let mut data = account.try_borrow_mut_data()?;
+        let new_end = end - settings.len() - vote_range.len() + encoded_settings.len() + vote.len();
+        if new_end > data.len() {
+            return Err(ProgramError::AccountDataTooSmall); // Return error if the state does not fit
+        }
+
+        // The vote first, its offsets move with the settings in front of it
+        let end = splice(&mut data, vote_range.clone(), end, &vote);
+        data[vote_range.start - 4..vote_range.start].copy_from_slice(&slot_len(vote.len())?.to_le_bytes());
+        splice(&mut data, settings, end, &encoded_settings);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout_snapshots::{canonical_registry, fixture};
+    use crate::test_utils::TestHarness;
+    use crate::{OptionIndex, Prerequisite, VoteConfig, VoteType};
+    use solana_program::pubkey::Pubkey;
+
+    fn config(title: &str) -> VoteConfig {
+        VoteConfig::from_labels(title.to_string(), vec!["Yes".to_string(), "No".to_string()]).unwrap()
+    }
+
+    // A registry of three votes with the same voter on each
+    fn registry_with_votes() -> (TestHarness, Pubkey, Pubkey) {
+        let mut harness = TestHarness::new();
+        let (creator, voter) = (Pubkey::new_unique(), Pubkey::new_unique());
+        for title in ["First", "Second", "Third"] {
+            let vote_id = harness.create_vote(config(title), &creator).unwrap();
+            harness.allow(vote_id, &creator, &voter).unwrap();
+        }
+
+        (harness, creator, voter)
+    }
+
+    fn serialized(voting: &Voting) -> Vec<u8> {
+        let mut serialized = REGISTRY_DISCRIMINATOR.to_vec();
+        voting.serialize(&mut serialized).unwrap();
+        serialized
+    }
+
+    // Registry account holding the harness registry, with `free` bytes to grow into
+    fn registry_account(harness: &mut TestHarness, free: usize) -> AccountInfo<'static> {
+        let key = Pubkey::new_unique();
+        let mut data = serialized(&harness.voting);
+        data.resize(data.len() + free, 0);
+        harness.set_data(&key, data);
+        harness.account_for(&key)
+    }
+
+    #[test]
+    fn test_vote_slot_matches_full_save() {
+        let (mut harness, _, voter) = registry_with_votes();
+        let account = registry_account(&mut harness, 1_024);
+
+        let (mut voting, slot) = Voting::load_vote_slot(&account, VoteId(1)).unwrap();
+        assert!(slot.is_some());
+        assert_eq!(voting.votes.keys().collect::<Vec<_>>(), vec![&VoteId(1)]);
+        voting.vote_as(&voter, VoteId(1), OptionIndex(0)).unwrap();
+        voting.save_vote_slot(&account, slot).unwrap();
+
+        // The account holds what a ballot on the whole registry writes, the grown slot moved the vote after it
+        harness.cast(VoteId(1), &voter, 0).unwrap();
+        let expected = serialized(&harness.voting);
+        let data = account.try_borrow_data().unwrap();
+        assert_eq!(data[..expected.len()], expected[..]);
+        assert!(data[expected.len()..].iter().all(|byte| *byte == 0));
+    }
+
+    #[test]
+    fn test_vote_slot_skips_other_votes() {
+        let (mut harness, _, voter) = registry_with_votes();
+        let account = registry_account(&mut harness, 1_024);
+
+        // Damage the first vote, a ballot on the last one never decodes it
+        let first = Voting::load_vote_slot(&account, VoteId(0)).unwrap().1.unwrap().vote;
+        account.try_borrow_mut_data().unwrap()[first.clone()].fill(0xff);
+        assert!(Voting::load(&account).is_err());
+
+        let (mut voting, slot) = Voting::load_vote_slot(&account, VoteId(2)).unwrap();
+        voting.vote_as(&voter, VoteId(2), OptionIndex(1)).unwrap();
+        voting.save_vote_slot(&account, slot).unwrap();
+
+        assert!(account.try_borrow_data().unwrap()[first].iter().all(|byte| *byte == 0xff));
+        harness.cast(VoteId(2), &voter, 1).unwrap();
+        assert_eq!(Voting::load_vote_slot(&account, VoteId(2)).unwrap().0.votes[&VoteId(2)], harness.voting.votes[&VoteId(2)]);
+    }
+
+    #[test]
+    fn test_vote_slot_falls_back_to_whole_registry() {
+        let (mut harness, creator, _) = registry_with_votes();
+        let prerequisite = Prerequisite { vote_id: VoteId(0), required_option_index: OptionIndex(0) };
+        let pending = harness.create_vote(VoteConfig { prerequisite: Some(prerequisite), ..config("Pending") }, &creator).unwrap();
+        let account = registry_account(&mut harness, 1_024);
+
+        // Unknown votes and votes waiting on a prerequisite need the whole registry
+        for vote_id in [VoteId(99), pending] {
+            assert_eq!(Voting::load_vote_slot(&account, vote_id).unwrap(), (harness.voting.clone(), None));
+        }
+
+        // An account in the legacy layout is read whole and takes the slot layout on its save
+        let key = Pubkey::new_unique();
+        let mut legacy = fixture("registry_v1");
+        legacy.resize(legacy.len() + 1_024, 0);
+        harness.set_data(&key, legacy);
+        let account = harness.account_for(&key);
+        let (mut voting, slot) = Voting::load_vote_slot(&account, VoteId(0)).unwrap();
+        assert_eq!((&voting, &slot), (&canonical_registry(VoteType::SingleChoice).0, &None));
+        voting.add_allowed_voter_as(&Pubkey::new_from_array([2; 32]), VoteId(0), Pubkey::new_unique()).unwrap();
+        voting.save_vote_slot(&account, slot).unwrap();
+        assert!(account.try_borrow_data().unwrap().starts_with(&REGISTRY_DISCRIMINATOR));
+        assert_eq!(Voting::load(&account).unwrap(), voting);
+        assert!(Voting::load_vote_slot(&account, VoteId(0)).unwrap().1.is_some());
+    }
+
+    #[test]
+    fn test_vote_slot_too_small() {
+        let (mut harness, _, voter) = registry_with_votes();
+        let account = registry_account(&mut harness, 0);
+        let before = account.try_borrow_data().unwrap().to_vec();
+
+        // A ballot grows the slot past the end of the account, nothing is written
+        let (mut voting, slot) = Voting::load_vote_slot(&account, VoteId(0)).unwrap();
+        voting.vote_as(&voter, VoteId(0), OptionIndex(0)).unwrap();
+        assert_eq!(voting.save_vote_slot(&account, slot), Err(ProgramError::AccountDataTooSmall));
+        assert_eq!(account.try_borrow_data().unwrap()[..], before[..]);
+
+        // A registry that gained a vote since the load does not go back into the slot
+        let (mut voting, slot) = Voting::load_vote_slot(&account, VoteId(0)).unwrap();
+        voting.votes.insert(VoteId(1), harness.voting.votes[&VoteId(1)].clone());
+        assert_eq!(voting.save_vote_slot(&account, slot), Err(ProgramError::InvalidArgument));
+    }
+
+    #[test]
+    fn test_registry_slots_reject_crafted_data() {
+        let (harness, _, _) = registry_with_votes();
+        let bytes = borsh::to_vec(&harness.voting).unwrap();
+        let slots: usize = harness.voting.votes.values().map(|vote| 12 + borsh::to_vec(vote).unwrap().len()).sum();
+        let first = bytes.len() - slots; // ID of the first slot, after the vote count
+        let patched = |offset: usize, patch: &[u8]| {
+            let mut bytes = bytes.clone();
+            bytes[offset..offset + patch.len()].copy_from_slice(patch);
+            bytes
+        };
+
+        // Slots out of ID order, and a slot claiming a byte more than its vote
+        assert!(Voting::try_from_slice(&patched(first, &5u64.to_le_bytes())).is_err());
+        let len = u32::from_le_bytes(bytes[first + 8..first + 12].try_into().unwrap());
+        assert!(Voting::try_from_slice(&patched(first + 8, &(len + 1).to_le_bytes())).is_err());
+        assert_eq!(Voting::try_from_slice(&bytes).unwrap(), harness.voting);
+    }
+}
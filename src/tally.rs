@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+
+use solana_program::program_error::ProgramError;
+
+use crate::{OptionResult, VoteError, VoteOption, FULL_WEIGHT_BPS};
+#[cfg(any(test, feature = "client"))]
+use crate::{Ballot, VoteResults};
+
+// Counting rules of a question, shared by the program and the off-chain verifier so the two cannot drift.
+// Tallies are keyed by option label, options without ballots may have no entry
+
+// Add a ballot of the given weight to an option, unless the option already reached the per-option cap
+pub fn add_ballot(votes: &mut HashMap<String, u32>, weighted_votes: &mut HashMap<String, u64>, label: &str, weight: u64, per_option_cap: Option<u64>) -> Result<(), ProgramError> {
+    let count = votes.entry(label.to_string()).or_insert(0);
+
+    // Check if the option can take another ballot
+    if per_option_cap.is_some_and(|cap| u64::from(*count) >= cap) {
+        return Err(VoteError::OptionCapReached.into());
+    }
+
+    *count += 1; // Increase the vote count
+
+    let weighted_count = weighted_votes.entry(label.to_string()).or_insert(0);
+    *weighted_count += weight; // Increase the weighted tally
+
+    Ok(())
+}
+
+// Take a previously added ballot back out of the tallies
+pub fn remove_ballot(votes: &mut HashMap<String, u32>, weighted_votes: &mut HashMap<String, u64>, label: &str, weight: u64) {
+    if let Some(count) = votes.get_mut(label) {
+        *count = count.saturating_sub(1);
+    }
+    if let Some(weighted_count) = weighted_votes.get_mut(label) {
+        *weighted_count = weighted_count.saturating_sub(weight);
+    }
+}
+
+// Part of the total in basis points, rounded half up so two of three ballots give 6667
+pub fn share_bps(part: u64, total: u64) -> u16 {
+    if total == 0 {
+        return 0;
+    }
+
+    let full = u128::from(FULL_WEIGHT_BPS);
+    ((u128::from(part) * full * 2 + u128::from(total)) / (u128::from(total) * 2)).min(full) as u16
+}
+
+// Weighted ballots the shares are computed over, abstentions excluded
+pub fn counted_total(options: &[VoteOption], weighted_votes: &HashMap<String, u64>) -> u64 {
+    options.iter().filter(|option| !option.abstain).map(|option| weighted_votes.get(option.label.as_str()).copied().unwrap_or(0)).sum()
+}
+
+// Tally of a single option, `counted_total` comes from `counted_total` over all options of the question
+pub fn option_result(option: &VoteOption, votes: &HashMap<String, u32>, weighted_votes: &HashMap<String, u64>, per_option_cap: Option<u64>, counted_total: u64) -> OptionResult {
+    let count = votes.get(option.label.as_str()).copied().unwrap_or(0);
+    let weighted_count = weighted_votes.get(option.label.as_str()).copied().unwrap_or(0);
+
+    OptionResult {
+        label: option.label.to_string(),
+        description: option.description.as_deref().map(String::from),
+        content_hash: option.content_hash,
+        order: option.order,
+        votes: count,
+        weighted_votes: weighted_count,
+        is_full: per_option_cap.is_some_and(|cap| u64::from(count) >= cap),
+        abstain: option.abstain,
+        withdrawn: option.withdrawn,
+        share_bps: if option.abstain { 0 } else { share_bps(weighted_count, counted_total) },
+    }
+}
+
+// Tallies of every option, in option order
+pub fn option_results(options: &[VoteOption], votes: &HashMap<String, u32>, weighted_votes: &HashMap<String, u64>, per_option_cap: Option<u64>) -> Vec<OptionResult> {
+    let counted_total = counted_total(options, weighted_votes);
+    options.iter().map(|option| option_result(option, votes, weighted_votes, per_option_cap, counted_total)).collect()
+}
+
+// First difference between a replayed ballot log and the reported results
+#[cfg(any(test, feature = "client"))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TallyMismatch {
+    InvalidBallot { ballot_index: usize }, // The program would have rejected the ballot
+    MissingQuestion { question_index: u32 },
+    OptionCount { expected: usize, actual: usize },
+    Label { option_index: usize, expected: String, actual: String },
+    Votes { option_index: usize, expected: u32, actual: u32 },
+    WeightedVotes { option_index: usize, expected: u64, actual: u64 },
+    ShareBps { option_index: usize, expected: u16, actual: u16 },
+    TotalVotes { expected: u32, actual: u32 },
+    TotalWeightedVotes { expected: u64, actual: u64 },
+}
+
+// Replay the ballots of a single question over its options with the rules of the program and compare
+// the outcome with the reported results of that question. Ballot weights were fixed by the vote type
+// at cast time, the per-option cap is taken from the reported results
+#[cfg(any(test, feature = "client"))]
+pub fn verify_tally(ballots: &[Ballot], options: &[VoteOption], reported: &VoteResults) -> Result<(), TallyMismatch> {
+    let question_index = ballots.first().map_or(0, |ballot| ballot.question_index);
+
+    let mut votes = HashMap::new();
+    let mut weighted_votes = HashMap::new();
+
+    for (ballot_index, ballot) in ballots.iter().enumerate() {
+        if ballot.question_index != question_index {
+            return Err(TallyMismatch::InvalidBallot { ballot_index }); // Every ballot must belong to the same question
+        }
+
+        let option = options.get(ballot.option_index.index()).ok_or(TallyMismatch::InvalidBallot { ballot_index })?;
+        add_ballot(&mut votes, &mut weighted_votes, &option.label, ballot.weight, reported.per_option_cap).map_err(|_| TallyMismatch::InvalidBallot { ballot_index })?;
+    }
+
+    let reported_question = reported.questions.get(question_index as usize).ok_or(TallyMismatch::MissingQuestion { question_index })?;
+    let expected = option_results(options, &votes, &weighted_votes, reported.per_option_cap);
+
+    if expected.len() != reported_question.options.len() {
+        return Err(TallyMismatch::OptionCount { expected: expected.len(), actual: reported_question.options.len() });
+    }
+
+    for (option_index, (expected, actual)) in expected.iter().zip(&reported_question.options).enumerate() {
+        if expected.label != actual.label {
+            return Err(TallyMismatch::Label { option_index, expected: expected.label.clone(), actual: actual.label.clone() });
+        }
+        if expected.votes != actual.votes {
+            return Err(TallyMismatch::Votes { option_index, expected: expected.votes, actual: actual.votes });
+        }
+        if expected.weighted_votes != actual.weighted_votes {
+            return Err(TallyMismatch::WeightedVotes { option_index, expected: expected.weighted_votes, actual: actual.weighted_votes });
+        }
+        if expected.share_bps != actual.share_bps {
+            return Err(TallyMismatch::ShareBps { option_index, expected: expected.share_bps, actual: actual.share_bps });
+        }
+    }
+
+    let total_votes = expected.iter().map(|option| option.votes).sum();
+    if total_votes != reported_question.total_votes {
+        return Err(TallyMismatch::TotalVotes { expected: total_votes, actual: reported_question.total_votes });
+    }
+
+    let total_weighted_votes = expected.iter().map(|option| option.weighted_votes).sum();
+    if total_weighted_votes != reported_question.total_weighted_votes {
+        return Err(TallyMismatch::TotalWeightedVotes { expected: total_weighted_votes, actual: reported_question.total_weighted_votes });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestHarness;
+    use crate::{OptionIndex, VoteConfig, VoteId};
+    use solana_program::pubkey::Pubkey;
+
+    // Vote with three ballots on the first option and one on the second
+    fn replayed_vote(per_option_cap: Option<u64>) -> (TestHarness, VoteId, Pubkey) {
+        let mut harness = TestHarness::new();
+        let creator = Pubkey::new_unique();
+
+        let config = VoteConfig {
+            per_option_cap,
+            ..VoteConfig::from_labels("Weekly Vote".to_string(), vec!["Option 1".to_string(), "Option 2".to_string()]).unwrap()
+        };
+        let vote_id = harness.create_vote(config, &creator).unwrap();
+
+        for option_index in [0, 0, 1, 0] {
+            let voter = Pubkey::new_unique();
+            harness.allow(vote_id, &creator, &voter).unwrap();
+            harness.cast(vote_id, &voter, option_index).unwrap();
+        }
+
+        (harness, vote_id, creator)
+    }
+
+    #[test]
+    fn test_verify_tally_matches() {
+        let (mut harness, vote_id, creator) = replayed_vote(Some(3));
+        let creator_info = harness.account_for(&creator);
+
+        let ballots = harness.voting.get_ballots(vote_id, 0, std::slice::from_ref(&creator_info)).unwrap();
+        let options = harness.voting.get_options(vote_id).unwrap().clone();
+        let reported = harness.voting.get_full_results(vote_id, &[creator_info]).unwrap();
+
+        assert_eq!(ballots.len(), 4);
+        assert_eq!(verify_tally(&ballots, &options, &reported), Ok(()));
+
+        // Without ballots every option must be empty
+        assert_eq!(verify_tally(&[], &options, &reported), Err(TallyMismatch::Votes { option_index: 0, expected: 0, actual: 3 }));
+    }
+
+    #[test]
+    fn test_verify_tally_tampered() {
+        let (mut harness, vote_id, creator) = replayed_vote(None);
+        let creator_info = harness.account_for(&creator);
+
+        let ballots = harness.voting.get_ballots(vote_id, 0, std::slice::from_ref(&creator_info)).unwrap();
+        let options = harness.voting.get_options(vote_id).unwrap().clone();
+        let reported = harness.voting.get_full_results(vote_id, &[creator_info]).unwrap();
+
+        let mut tampered = reported.clone();
+        tampered.questions[0].options[1].votes = 2;
+        assert_eq!(verify_tally(&ballots, &options, &tampered), Err(TallyMismatch::Votes { option_index: 1, expected: 1, actual: 2 }));
+
+        let mut tampered = reported.clone();
+        tampered.questions[0].options[0].weighted_votes += 1;
+        assert_eq!(
+            verify_tally(&ballots, &options, &tampered),
+            Err(TallyMismatch::WeightedVotes { option_index: 0, expected: 30_000, actual: 30_001 })
+        );
+
+        let mut tampered = reported.clone();
+        tampered.questions[0].total_votes = 5;
+        assert_eq!(verify_tally(&ballots, &options, &tampered), Err(TallyMismatch::TotalVotes { expected: 4, actual: 5 }));
+
+        // A log over the cap could not have been accepted by the program, the third ballot for the first option is rejected
+        let ballot_index = ballots.iter().enumerate().filter(|(_, ballot)| ballot.option_index == OptionIndex(0)).nth(2).unwrap().0;
+        let mut tampered = reported;
+        tampered.per_option_cap = Some(2);
+        assert_eq!(verify_tally(&ballots, &options, &tampered), Err(TallyMismatch::InvalidBallot { ballot_index }));
+    }
+}
@@ -0,0 +1,283 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::Once;
+
+use borsh::BorshDeserialize;
+use solana_program::account_info::AccountInfo;
+use solana_program::clock::{Clock, Slot};
+use solana_program::entrypoint::{ProgramResult, SUCCESS};
+use solana_program::instruction::Instruction;
+use solana_program::program_error::ProgramError;
+use solana_program::program_stubs::{set_syscall_stubs, SyscallStubs};
+use solana_program::program_utils::limited_deserialize;
+use solana_program::pubkey::Pubkey;
+use solana_program::rent::Rent;
+use solana_program::system_instruction::SystemInstruction;
+use solana_program::system_program;
+
+use crate::instruction::VoteInstruction;
+use crate::processor::process_instruction;
+use crate::{EventEnvelope, OptionIndex, VoteConfig, VoteEvent, VoteId, Voting};
+
+thread_local! {
+    static CLOCK: RefCell<Clock> = RefCell::new(Clock::default());
+    static LOGS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    static EVENTS: RefCell<Vec<Vec<u8>>> = const { RefCell::new(Vec::new()) };
+    static RETURN_DATA: RefCell<Option<Vec<u8>>> = const { RefCell::new(None) };
+}
+
+// Syscall stubs serving the Clock sysvar from a per-thread value, so every test controls its own time,
+// the default Rent, and the system program instructions the voting program invokes. Logged messages
+// and emitted event data are kept per thread as well, like the return data
+struct TestSyscallStubs;
+
+impl SyscallStubs for TestSyscallStubs {
+    fn sol_log(&self, message: &str) {
+        LOGS.with(|logs| logs.borrow_mut().push(message.to_string()));
+    }
+
+    fn sol_log_data(&self, fields: &[&[u8]]) {
+        EVENTS.with(|events| events.borrow_mut().extend(fields.iter().map(|field| field.to_vec())));
+    }
+
+    fn sol_set_return_data(&self, data: &[u8]) {
+        RETURN_DATA.with(|return_data| *return_data.borrow_mut() = Some(data.to_vec()));
+    }
+
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        CLOCK.with(|clock| unsafe { (var_addr as *mut Clock).write(clock.borrow().clone()) });
+        SUCCESS
+    }
+
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe { (var_addr as *mut Rent).write(Rent::default()) };
+        SUCCESS
+    }
+
+    // Run system program instructions directly on the passed accounts
+    fn sol_invoke_signed(&self, instruction: &Instruction, account_infos: &[AccountInfo], signers_seeds: &[&[&[u8]]]) -> ProgramResult {
+        if instruction.program_id != system_program::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let find_account = |key: &Pubkey| account_infos.iter().find(|account| account.key == key).ok_or(ProgramError::NotEnoughAccountKeys);
+
+        let from = find_account(&instruction.accounts[0].pubkey)?;
+        let to = find_account(&instruction.accounts[1].pubkey)?;
+
+        if !from.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        match limited_deserialize(&instruction.data, 1232).map_err(|_| ProgramError::InvalidInstructionData)? {
+            SystemInstruction::Transfer { lamports } => {
+                if from.lamports() < lamports {
+                    return Err(ProgramError::InsufficientFunds);
+                }
+
+                **from.try_borrow_mut_lamports()? -= lamports;
+                **to.try_borrow_mut_lamports()? += lamports;
+                Ok(())
+            }
+            SystemInstruction::CreateAccount { lamports, space, owner } => {
+                // The new account signs either itself or through the seeds of the calling program
+                if !to.is_signer && signers_seeds.is_empty() {
+                    return Err(ProgramError::MissingRequiredSignature);
+                }
+                if to.lamports() > 0 || !to.data_is_empty() {
+                    return Err(ProgramError::AccountAlreadyInitialized); // The system program reports the address as in use
+                }
+                if from.lamports() < lamports {
+                    return Err(ProgramError::InsufficientFunds);
+                }
+
+                **from.try_borrow_mut_lamports()? -= lamports;
+                **to.try_borrow_mut_lamports()? += lamports;
+                *to.data.borrow_mut() = Box::leak(vec![0; space as usize].into_boxed_slice());
+                to.assign(&owner);
+                Ok(())
+            }
+            _ => Err(ProgramError::InvalidInstructionData),
+        }
+    }
+}
+
+pub fn install_stubs() {
+    static STUBS: Once = Once::new();
+    STUBS.call_once(|| {
+        set_syscall_stubs(Box::new(TestSyscallStubs));
+    });
+}
+
+// Unix timestamp returned by the Clock sysvar on the current thread
+pub fn set_clock(unix_timestamp: i64) {
+    install_stubs();
+    CLOCK.with(|clock| clock.borrow_mut().unix_timestamp = unix_timestamp);
+}
+
+pub fn set_slot(slot: Slot) {
+    install_stubs();
+    CLOCK.with(|clock| clock.borrow_mut().slot = slot);
+}
+
+// Messages logged on the current thread since the previous call
+pub fn take_logs() -> Vec<String> {
+    install_stubs();
+    LOGS.with(|logs| logs.take())
+}
+
+// Envelopes of the events emitted on the current thread since the previous call
+pub fn take_envelopes() -> Vec<EventEnvelope> {
+    install_stubs();
+    EVENTS.with(|events| events.take().iter().map(|data| EventEnvelope::try_from_slice(data).unwrap()).collect())
+}
+
+// Events emitted on the current thread since the previous call
+pub fn take_events() -> Vec<VoteEvent> {
+    take_envelopes().into_iter().map(|envelope| envelope.event).collect()
+}
+
+// Return data set on the current thread since the previous call
+pub fn take_return_data() -> Option<Vec<u8>> {
+    install_stubs();
+    RETURN_DATA.with(|return_data| return_data.take())
+}
+
+// Lamports and data of a single simulated account, shared by every AccountInfo built for its key
+struct AccountBuffers {
+    key: &'static Pubkey,
+    owner: &'static Pubkey, // Separate per account, so assigning one account leaves the others alone
+    lamports: Rc<RefCell<&'static mut u64>>,
+    data: Rc<RefCell<&'static mut [u8]>>,
+}
+
+// In-memory registry with simulated accounts, for tests of the program and of crates embedding `Voting`.
+// Account buffers are leaked so the returned AccountInfos do not borrow the harness, they live until the test process exits
+pub struct TestHarness {
+    pub voting: Voting,
+    owner: &'static Pubkey,
+    accounts: HashMap<Pubkey, AccountBuffers>,
+}
+
+impl Default for TestHarness {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TestHarness {
+    // Harness around an empty registry with a random admin
+    pub fn new() -> Self {
+        Self::with_voting(Voting::new(Pubkey::new_unique()))
+    }
+
+    pub fn with_voting(voting: Voting) -> Self {
+        install_stubs();
+        Self {
+            voting,
+            owner: Box::leak(Box::new(Pubkey::new_unique())),
+            accounts: HashMap::new(),
+        }
+    }
+
+    // Initial owner of every simulated account
+    pub fn owner(&self) -> &'static Pubkey {
+        self.owner
+    }
+
+    fn buffers(&mut self, key: &Pubkey) -> &AccountBuffers {
+        let owner = *self.owner;
+        self.accounts.entry(*key).or_insert_with(|| AccountBuffers {
+            key: Box::leak(Box::new(*key)),
+            owner: Box::leak(Box::new(owner)),
+            lamports: Rc::new(RefCell::new(Box::leak(Box::new(0)))),
+            data: Rc::new(RefCell::new(Box::leak(Vec::new().into_boxed_slice()))),
+        })
+    }
+
+    // Signing, writable account for the key. Every call for the same key shares the same lamports and data
+    pub fn account_for(&mut self, key: &Pubkey) -> AccountInfo<'static> {
+        let buffers = self.buffers(key);
+
+        AccountInfo {
+            key: buffers.key,
+            lamports: buffers.lamports.clone(),
+            data: buffers.data.clone(),
+            owner: buffers.owner,
+            rent_epoch: 0,
+            is_signer: true,
+            is_writable: true,
+            executable: false,
+        }
+    }
+
+    pub fn set_owner(&mut self, key: &Pubkey, owner: &Pubkey) {
+        let owner: &'static Pubkey = Box::leak(Box::new(*owner));
+        self.buffers(key);
+        if let Some(buffers) = self.accounts.get_mut(key) {
+            buffers.owner = owner;
+        }
+    }
+
+    pub fn lamports(&mut self, key: &Pubkey) -> u64 {
+        **self.buffers(key).lamports.borrow()
+    }
+
+    pub fn set_lamports(&mut self, key: &Pubkey, lamports: u64) {
+        **self.buffers(key).lamports.borrow_mut() = lamports;
+    }
+
+    // Replace the data of the account, e.g. to allocate a vote state account of a given size
+    pub fn set_data(&mut self, key: &Pubkey, data: Vec<u8>) {
+        *self.buffers(key).data.borrow_mut() = Box::leak(data.into_boxed_slice());
+    }
+
+    pub fn create_vote(&mut self, config: VoteConfig, creator: &Pubkey) -> Result<VoteId, ProgramError> {
+        let creator_info = self.account_for(creator);
+        self.voting.create_vote_with_config(config, &[creator_info])
+    }
+
+    // Add `voter` to the allowlist on behalf of `creator`
+    pub fn allow(&mut self, vote_id: VoteId, creator: &Pubkey, voter: &Pubkey) -> Result<(), ProgramError> {
+        self.voting.add_allowed_voter_as(creator, vote_id, *voter)
+    }
+
+    pub fn cast(&mut self, vote_id: VoteId, voter: &Pubkey, option_index: u16) -> Result<(), ProgramError> {
+        self.voting.vote_as(voter, vote_id, OptionIndex(option_index))
+    }
+
+    pub fn delegate(&mut self, vote_id: VoteId, delegator: &Pubkey, delegate: &Pubkey) -> Result<(), ProgramError> {
+        self.voting.delegate_vote_as(delegator, vote_id, delegate, None)
+    }
+
+    pub fn close(&mut self, vote_id: VoteId, creator: &Pubkey) -> Result<(), ProgramError> {
+        let creator_info = self.account_for(creator);
+        self.voting.close_vote(vote_id, &[creator_info])
+    }
+}
+
+// Decode arbitrary instruction data and, when it decodes, run it against a fresh registry
+// with the accounts the instruction expects. Shared by the fuzz target and the property tests,
+// so the accounts live on the stack instead of leaking harness buffers on every input
+pub fn process_arbitrary_instruction(data: &[u8]) -> ProgramResult {
+    let instruction = VoteInstruction::unpack(data)?;
+
+    let program_id = Pubkey::new_unique();
+    let admin = Pubkey::new_unique();
+    let registry_key = Pubkey::new_unique();
+
+    let (mut registry_lamports, mut registry_data) = (0, vec![0; 1024]);
+    let registry = AccountInfo::new(&registry_key, false, true, &mut registry_lamports, &mut registry_data, &program_id, false, 0);
+
+    match instruction {
+        VoteInstruction::InitializeRegistry { .. } => process_instruction(&program_id, &[registry], data),
+        _ => {
+            Voting::new(admin).save(&registry)?;
+
+            let (mut admin_lamports, mut admin_data) = (0, vec![]);
+            let admin_info = AccountInfo::new(&admin, true, false, &mut admin_lamports, &mut admin_data, &program_id, false, 0);
+            process_instruction(&program_id, &[admin_info, registry], data)
+        }
+    }
+}
@@ -0,0 +1,428 @@
+// Fixed byte layout of a vote for explorers reading raw account data, so they need no Borsh schema.
+// A vote state account starts with the view and holds the Borsh encoded vote at its state offset.
+// All integers are little-endian, the layout only changes together with `VIEW_VERSION`.
+//
+// Header, `VIEW_HEADER_LEN` bytes:
+//     0..8    discriminator, `VIEW_DISCRIMINATOR`
+//     8..10   version, u16
+//     10      status, u8: 0 open, 1 closed, 2 cancelled, 3 pending
+//     11      vote type, u8: 0 single choice, 1 time-weighted, 2 token-weighted, 3 point allocation
+//     12..16  vote ID, u32
+//     16..48  creator
+//     48..52  number of questions, u32
+//     52..56  number of options over all questions, u32
+//     56..60  eligible voters, u32, the allowlist without banned voters
+//     60..64  voters who cast a ballot, u32
+//     64..68  offset of the option entries, u32
+//     68..72  offset of the tally entries, u32
+//     72..76  offset of the labels, u32
+//     76..80  length of the labels, u32
+//     80..84  offset of the Borsh encoded vote, u32, which is also the length of the view
+//     84..96  reserved, zero
+//
+// Option entries, `VIEW_OPTION_LEN` bytes each, questions in order and the options of each question in order:
+//     0..4    question index, u32
+//     4..6    display order, u16
+//     6       flags, u8: bit 0 set for abstentions
+//     7       reserved, zero
+//     8..12   start of the label within the labels, u32
+//     12..16  length of the label in bytes, u32
+//
+// Tally entries, `VIEW_TALLY_LEN` bytes each, one per option entry at the same position:
+//     0..8    ballots, u64
+//     8..16   weighted ballots in basis points, u64
+//
+// Labels are the UTF-8 option labels back to back.
+use solana_program::{program_error::ProgramError, pubkey::Pubkey};
+
+use crate::{Vote, VoteId, VoteStatus, VoteType};
+
+pub const VIEW_DISCRIMINATOR: [u8; 8] = *b"svvotevw";
+pub const VIEW_VERSION: u16 = 1;
+pub const VIEW_HEADER_LEN: usize = 96;
+pub const VIEW_OPTION_LEN: usize = 16;
+pub const VIEW_TALLY_LEN: usize = 16;
+
+const ABSTAIN_FLAG: u8 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViewStatus {
+    Open,
+    Closed,
+    Cancelled,
+    Pending,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViewVoteType {
+    SingleChoice,
+    TimeWeighted,
+    TokenWeighted,
+    PointAllocation,
+    Conviction,
+    TokenBurn,
+}
+
+// Option entry with its label borrowed from the view bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OptionView<'a> {
+    pub question_index: u32,
+    pub order: u16,
+    pub abstain: bool,
+    pub label: &'a str,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TallyView {
+    pub votes: u64,
+    pub weighted_votes: u64, // In basis points
+}
+
+// Read-only view over the bytes of a vote state account, checked once by `try_from_bytes`
+#[derive(Debug, Clone, Copy)]
+pub struct VoteView<'a> {
+    data: &'a [u8],
+}
+
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    let mut bytes = [0; 2];
+    bytes.copy_from_slice(&data[offset..offset + 2]);
+    u16::from_le_bytes(bytes)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    let mut bytes = [0; 4];
+    bytes.copy_from_slice(&data[offset..offset + 4]);
+    u32::from_le_bytes(bytes)
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&data[offset..offset + 8]);
+    u64::from_le_bytes(bytes)
+}
+
+fn write_u32(buf: &mut [u8], offset: usize, value: usize) -> Result<(), ProgramError> {
+    let value = u32::try_from(value).map_err(|_| ProgramError::InvalidArgument)?; // Return error if the vote is too large for the layout
+    buf[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    Ok(())
+}
+
+// End of a section of `count` entries of `len` bytes from `offset`, if it fits in `data_len`
+fn section_end(offset: u32, count: u32, len: usize, data_len: usize) -> Option<usize> {
+    let end = (count as usize).checked_mul(len)?.checked_add(offset as usize)?;
+    (offset as usize >= VIEW_HEADER_LEN && end <= data_len).then_some(end)
+}
+
+impl<'a> VoteView<'a> {
+    // Check the header and that every section and label lies within `data`
+    pub fn try_from_bytes(data: &'a [u8]) -> Result<Self, ProgramError> {
+        if data.len() < VIEW_HEADER_LEN || data[..8] != VIEW_DISCRIMINATOR {
+            return Err(ProgramError::InvalidAccountData); // Return error if the account holds no view
+        }
+        if read_u16(data, 8) != VIEW_VERSION {
+            return Err(ProgramError::InvalidAccountData); // Return error for layouts this version cannot read
+        }
+        if data[10] > 3 || data[11] > 5 {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let view = Self { data };
+        let option_count = view.option_count();
+        let state_offset = view.state_offset() as usize;
+        let labels_len = read_u32(data, 76) as usize;
+
+        let options_end = section_end(read_u32(data, 64), option_count, VIEW_OPTION_LEN, state_offset);
+        let tallies_end = section_end(read_u32(data, 68), option_count, VIEW_TALLY_LEN, state_offset);
+        let labels_end = section_end(read_u32(data, 72), 1, labels_len, state_offset);
+        if state_offset > data.len() || options_end.is_none() || tallies_end.is_none() || labels_end.is_none() {
+            return Err(ProgramError::InvalidAccountData); // Return error if a section lies outside the view
+        }
+
+        // Labels have to be valid and in bounds, so the accessors cannot fail
+        let labels = view.labels();
+        for index in 0..option_count as usize {
+            let entry = read_u32(data, 64) as usize + index * VIEW_OPTION_LEN;
+            let (start, len) = (read_u32(data, entry + 8) as usize, read_u32(data, entry + 12) as usize);
+            let label = start.checked_add(len).and_then(|end| labels.get(start..end)).ok_or(ProgramError::InvalidAccountData)?;
+            std::str::from_utf8(label).map_err(|_| ProgramError::InvalidAccountData)?;
+        }
+
+        Ok(view)
+    }
+
+    pub fn version(&self) -> u16 {
+        read_u16(self.data, 8)
+    }
+
+    pub fn status(&self) -> ViewStatus {
+        match self.data[10] {
+            0 => ViewStatus::Open,
+            1 => ViewStatus::Closed,
+            3 => ViewStatus::Pending,
+            _ => ViewStatus::Cancelled,
+        }
+    }
+
+    pub fn vote_type(&self) -> ViewVoteType {
+        match self.data[11] {
+            0 => ViewVoteType::SingleChoice,
+            1 => ViewVoteType::TimeWeighted,
+            2 => ViewVoteType::TokenWeighted,
+            3 => ViewVoteType::PointAllocation,
+            4 => ViewVoteType::Conviction,
+            _ => ViewVoteType::TokenBurn,
+        }
+    }
+
+    pub fn id(&self) -> VoteId {
+        VoteId::from(read_u32(self.data, 12))
+    }
+
+    pub fn creator(&self) -> Pubkey {
+        let mut bytes = [0; 32];
+        bytes.copy_from_slice(&self.data[16..48]);
+        Pubkey::new_from_array(bytes)
+    }
+
+    pub fn question_count(&self) -> u32 {
+        read_u32(self.data, 48)
+    }
+
+    pub fn option_count(&self) -> u32 {
+        read_u32(self.data, 52)
+    }
+
+    pub fn eligible_voters(&self) -> u32 {
+        read_u32(self.data, 56)
+    }
+
+    pub fn participating_voters(&self) -> u32 {
+        read_u32(self.data, 60)
+    }
+
+    // Offset of the Borsh encoded vote, right after the view
+    pub fn state_offset(&self) -> u32 {
+        read_u32(self.data, 80)
+    }
+
+    fn labels(&self) -> &'a [u8] {
+        let start = read_u32(self.data, 72) as usize;
+        &self.data[start..start + read_u32(self.data, 76) as usize]
+    }
+
+    pub fn option(&self, index: u32) -> Option<OptionView<'a>> {
+        if index >= self.option_count() {
+            return None;
+        }
+
+        let entry = read_u32(self.data, 64) as usize + index as usize * VIEW_OPTION_LEN;
+        let (start, len) = (read_u32(self.data, entry + 8) as usize, read_u32(self.data, entry + 12) as usize);
+        let label = std::str::from_utf8(&self.labels()[start..start + len]).ok()?;
+
+        Some(OptionView {
+            question_index: read_u32(self.data, entry),
+            order: read_u16(self.data, entry + 4),
+            abstain: self.data[entry + 6] & ABSTAIN_FLAG != 0,
+            label,
+        })
+    }
+
+    pub fn tally(&self, index: u32) -> Option<TallyView> {
+        if index >= self.option_count() {
+            return None;
+        }
+
+        let entry = read_u32(self.data, 68) as usize + index as usize * VIEW_TALLY_LEN;
+        Some(TallyView { votes: read_u64(self.data, entry), weighted_votes: read_u64(self.data, entry + 8) })
+    }
+
+    // Option entries in view order
+    pub fn options(&self) -> impl Iterator<Item = OptionView<'a>> + '_ {
+        (0..self.option_count()).filter_map(|index| self.option(index))
+    }
+}
+
+impl Vote {
+    // Bytes the view of the vote takes, the Borsh encoded vote follows them
+    pub(crate) fn view_len(&self) -> usize {
+        let option_count: usize = self.questions.iter().map(|question| question.options.len()).sum();
+        let labels_len: usize = self.questions.iter().flat_map(|question| &question.options).map(|option| option.label.len()).sum();
+        VIEW_HEADER_LEN + option_count * (VIEW_OPTION_LEN + VIEW_TALLY_LEN) + labels_len
+    }
+
+    // Write the view of the vote at the start of `buf`, returns its length
+    pub fn write_view(&self, buf: &mut [u8]) -> Result<usize, ProgramError> {
+        let view_len = self.view_len();
+        if buf.len() < view_len {
+            return Err(ProgramError::AccountDataTooSmall); // Return error if the view does not fit
+        }
+
+        let buf = &mut buf[..view_len];
+        buf.fill(0);
+
+        let options = || self.questions.iter().enumerate().flat_map(|(question_index, question)| question.options.iter().map(move |option| (question_index, question, option)));
+        let option_count = options().count();
+        let options_offset = VIEW_HEADER_LEN;
+        let tallies_offset = options_offset + option_count * VIEW_OPTION_LEN;
+        let labels_offset = tallies_offset + option_count * VIEW_TALLY_LEN;
+        let (eligible, participating) = self.turnout();
+
+        // Tallies of conviction votes carry the conviction built up by the slot the view is written at
+        let slot = self.conviction_slot(None)?;
+        let weighted_tallies: Vec<_> = (0..self.questions.len()).map(|index| self.weighted_votes_at(index, slot)).collect();
+
+        buf[..8].copy_from_slice(&VIEW_DISCRIMINATOR);
+        buf[8..10].copy_from_slice(&VIEW_VERSION.to_le_bytes());
+        buf[10] = match self.status {
+            VoteStatus::Open => 0,
+            VoteStatus::Closed => 1,
+            VoteStatus::Cancelled { .. } => 2,
+            VoteStatus::Pending => 3,
+        };
+        buf[11] = match self.vote_type {
+            VoteType::SingleChoice => 0,
+            VoteType::TimeWeighted { .. } => 1,
+            VoteType::TokenWeighted => 2,
+            VoteType::PointAllocation { .. } => 3,
+            VoteType::Conviction { .. } => 4,
+            VoteType::TokenBurn { .. } => 5,
+        };
+        let id = u32::try_from(self.id.0).map_err(|_| ProgramError::InvalidArgument)?; // Return error if the ID does not fit the layout
+        buf[12..16].copy_from_slice(&id.to_le_bytes());
+        buf[16..48].copy_from_slice(self.creator.as_ref());
+        write_u32(buf, 48, self.questions.len())?;
+        write_u32(buf, 52, option_count)?;
+        write_u32(buf, 56, eligible as usize)?;
+        write_u32(buf, 60, participating as usize)?;
+        write_u32(buf, 64, options_offset)?;
+        write_u32(buf, 68, tallies_offset)?;
+        write_u32(buf, 72, labels_offset)?;
+        write_u32(buf, 76, view_len - labels_offset)?;
+        write_u32(buf, 80, view_len)?;
+
+        let mut label_start = 0;
+        for (index, (question_index, question, option)) in options().enumerate() {
+            let entry = options_offset + index * VIEW_OPTION_LEN;
+            write_u32(buf, entry, question_index)?;
+            buf[entry + 4..entry + 6].copy_from_slice(&option.order.to_le_bytes());
+            buf[entry + 6] = if option.abstain { ABSTAIN_FLAG } else { 0 };
+            write_u32(buf, entry + 8, label_start)?;
+            write_u32(buf, entry + 12, option.label.len())?;
+
+            let tally = tallies_offset + index * VIEW_TALLY_LEN;
+            let votes = u64::from(question.votes.get(option.label.as_str()).copied().unwrap_or(0));
+            let weighted_votes = weighted_tallies[question_index].get(option.label.as_str()).copied().unwrap_or(0);
+            buf[tally..tally + 8].copy_from_slice(&votes.to_le_bytes());
+            buf[tally + 8..tally + 16].copy_from_slice(&weighted_votes.to_le_bytes());
+
+            let label = labels_offset + label_start;
+            buf[label..label + option.label.len()].copy_from_slice(option.label.as_bytes());
+            label_start += option.label.len();
+        }
+
+        Ok(view_len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use borsh::BorshDeserialize;
+    use crate::test_utils::{set_clock, TestHarness};
+    use crate::{OptionIndex, QuestionConfig, VoteConfig, VoteOption};
+
+    // Closed two-question vote of three voters, two of them voted, saved to a state account
+    fn saved_vote(harness: &mut TestHarness) -> (VoteId, Pubkey, Vec<u8>) {
+        let creator = Pubkey::new_unique();
+        set_clock(1_000);
+
+        let budget = QuestionConfig {
+            title: "Budget".try_into().unwrap(),
+            options: vec![VoteOption::new("Yes".to_string(), 0).unwrap(), VoteOption::new("No".to_string(), 1).unwrap(), VoteOption::abstention("Abstain".to_string(), 2).unwrap()],
+        };
+        let venue = QuestionConfig { title: "Venue".try_into().unwrap(), options: VoteOption::from_labels(vec!["Nord".to_string(), "Süd".to_string()]).unwrap() };
+        let vote_id = harness.create_vote(VoteConfig::with_questions("Assembly".to_string(), vec![budget, venue]).unwrap(), &creator).unwrap();
+
+        let voters = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+        for voter in &voters {
+            harness.allow(vote_id, &creator, voter).unwrap();
+        }
+        for (voter, question_index, option_index) in [(voters[0], 0, 0), (voters[0], 1, 1), (voters[1], 0, 2)] {
+            let voter_info = harness.account_for(&voter);
+            harness.voting.vote_on_question(vote_id, &[voter_info], question_index, OptionIndex(option_index)).unwrap();
+        }
+        harness.close(vote_id, &creator).unwrap();
+
+        let state_key = Pubkey::new_unique();
+        harness.set_data(&state_key, vec![0; 4096]);
+        let state_info = harness.account_for(&state_key);
+        harness.voting.votes[&vote_id].save(&state_info).unwrap();
+
+        let data = state_info.data.borrow().to_vec();
+        (vote_id, creator, data)
+    }
+
+    #[test]
+    fn test_view_of_saved_vote() {
+        let mut harness = TestHarness::new();
+        let (vote_id, creator, data) = saved_vote(&mut harness);
+        let vote = &harness.voting.votes[&vote_id];
+
+        let view = VoteView::try_from_bytes(&data).unwrap();
+        assert_eq!(view.version(), VIEW_VERSION);
+        assert_eq!(view.status(), ViewStatus::Closed);
+        assert_eq!(view.vote_type(), ViewVoteType::SingleChoice);
+        assert_eq!(view.id(), vote_id);
+        assert_eq!(view.creator(), creator);
+        assert_eq!(view.question_count(), 2);
+        assert_eq!(view.option_count(), 5);
+        assert_eq!(view.eligible_voters(), 3);
+        assert_eq!(view.participating_voters(), 2);
+        assert_eq!(view.state_offset() as usize, vote.view_len());
+
+        let option = |question_index, order, abstain, label| OptionView { question_index, order, abstain, label };
+        assert_eq!(
+            view.options().collect::<Vec<_>>(),
+            vec![option(0, 0, false, "Yes"), option(0, 1, false, "No"), option(0, 2, true, "Abstain"), option(1, 0, false, "Nord"), option(1, 1, false, "Süd")]
+        );
+
+        let tallies: Vec<(u64, u64)> = (0..5).map(|index| view.tally(index).map(|tally| (tally.votes, tally.weighted_votes)).unwrap()).collect();
+        assert_eq!(tallies, vec![(1, 10_000), (0, 0), (1, 10_000), (0, 0), (1, 10_000)]);
+        assert_eq!(view.option(5), None);
+        assert_eq!(view.tally(5), None);
+
+        // The Borsh encoded vote follows the view
+        let state_offset = view.state_offset() as usize;
+        assert_eq!(&Vote::deserialize(&mut &data[state_offset..]).unwrap(), vote);
+    }
+
+    #[test]
+    fn test_view_rejects_invalid_bytes() {
+        let mut harness = TestHarness::new();
+        let (vote_id, _, data) = saved_vote(&mut harness);
+        let view_len = harness.voting.votes[&vote_id].view_len();
+
+        let patched = |offset: usize, bytes: &[u8]| {
+            let mut data = data.clone();
+            data[offset..offset + bytes.len()].copy_from_slice(bytes);
+            data
+        };
+
+        assert_eq!(VoteView::try_from_bytes(&vec![0; 4096]).err(), Some(ProgramError::InvalidAccountData));
+        assert_eq!(VoteView::try_from_bytes(&data[..VIEW_HEADER_LEN - 1]).err(), Some(ProgramError::InvalidAccountData));
+        assert_eq!(VoteView::try_from_bytes(&data[..view_len - 1]).err(), Some(ProgramError::InvalidAccountData));
+        assert_eq!(VoteView::try_from_bytes(&patched(8, &2u16.to_le_bytes())).err(), Some(ProgramError::InvalidAccountData));
+        assert_eq!(VoteView::try_from_bytes(&patched(10, &[4])).err(), Some(ProgramError::InvalidAccountData));
+        assert_eq!(VoteView::try_from_bytes(&patched(11, &[6])).err(), Some(ProgramError::InvalidAccountData));
+
+        // An option count running past the tallies, and a label running past the labels
+        assert_eq!(VoteView::try_from_bytes(&patched(52, &1_000u32.to_le_bytes())).err(), Some(ProgramError::InvalidAccountData));
+        assert_eq!(VoteView::try_from_bytes(&patched(VIEW_HEADER_LEN + 12, &100u32.to_le_bytes())).err(), Some(ProgramError::InvalidAccountData));
+
+        // Splitting the two bytes of "ü" leaves invalid UTF-8
+        let last_option = VIEW_HEADER_LEN + 4 * VIEW_OPTION_LEN;
+        assert_eq!(VoteView::try_from_bytes(&patched(last_option + 12, &2u32.to_le_bytes())).err(), Some(ProgramError::InvalidAccountData));
+        assert!(VoteView::try_from_bytes(&data[..view_len]).is_ok());
+    }
+}
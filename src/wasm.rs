@@ -0,0 +1,191 @@
+// Bindings for browser dApps. Decoded values are plain JS objects with camelCase keys,
+// public keys and content hashes are base58 strings, 64-bit amounts and vote IDs are BigInts
+use js_sys::{Array, Object, Reflect};
+use solana_program::hash::Hash;
+use solana_program::pubkey::Pubkey;
+use wasm_bindgen::prelude::*;
+
+use crate::instruction::VoteInstruction;
+use crate::{DepositStatus, EventEnvelope, OptionIndex, Visibility, VoteEvent, VoteId, VoteResults, VoteStatus, VoteSummary, Voting};
+
+fn object(fields: &[(&str, JsValue)]) -> JsValue {
+    let object = Object::new();
+    for (key, value) in fields {
+        let _ = Reflect::set(&object, &JsValue::from_str(key), value);
+    }
+    object.into()
+}
+
+fn error(error: impl std::fmt::Display) -> JsError {
+    JsError::new(&error.to_string())
+}
+
+fn summary_to_js(summary: &VoteSummary, results: Option<VoteResults>) -> JsValue {
+    let status = match summary.status {
+        VoteStatus::Open => "open",
+        VoteStatus::Closed => "closed",
+        VoteStatus::Cancelled { .. } => "cancelled",
+        VoteStatus::Pending => "pending",
+    };
+
+    let deposit = summary.deposit.map(|deposit| match deposit.status {
+        DepositStatus::Held => "held",
+        DepositStatus::Refunded => "refunded",
+        DepositStatus::Forfeited => "forfeited",
+    });
+
+    object(&[
+        ("id", summary.id.0.into()),
+        ("title", summary.title.as_str().into()),
+        ("creator", summary.creator.to_string().into()),
+        ("status", status.into()),
+        ("deadline", summary.deadline.map_or(JsValue::NULL, |deadline| deadline.into())),
+        ("editCount", summary.edit_count.into()),
+        ("deposit", deposit.map_or(JsValue::NULL, JsValue::from)),
+        ("results", results.map_or(JsValue::NULL, |results| results_to_js(&results))),
+    ])
+}
+
+fn results_to_js(results: &VoteResults) -> JsValue {
+    let questions: Array = results
+        .questions
+        .iter()
+        .map(|question| {
+            let options: Array = question
+                .options
+                .iter()
+                .map(|option| {
+                    object(&[
+                        ("label", option.label.as_str().into()),
+                        ("votes", option.votes.into()),
+                        ("weightedVotes", option.weighted_votes.into()),
+                        ("shareBps", option.share_bps.into()),
+                        ("abstain", option.abstain.into()),
+                        ("withdrawn", option.withdrawn.into()),
+                        ("contentHash", option.content_hash.map_or(JsValue::NULL, |content_hash| Hash::new_from_array(content_hash).to_string().into())),
+                    ])
+                })
+                .collect();
+
+            object(&[
+                ("title", question.title.as_str().into()),
+                ("options", options.into()),
+                ("totalVotes", question.total_votes.into()),
+                ("totalWeightedVotes", question.total_weighted_votes.into()),
+            ])
+        })
+        .collect();
+
+    object(&[("questions", questions.into()), ("isVoid", results.is_void.into())])
+}
+
+// Instruction data of a ballot, the accounts are listed at `VoteInstruction::CastVote`
+#[wasm_bindgen(js_name = encodeCastVote)]
+pub fn encode_cast_vote(vote_id: u64, question_index: u32, option_index: u16) -> Vec<u8> {
+    VoteInstruction::CastVote { vote_id: VoteId(vote_id), question_index, option_index: OptionIndex(option_index) }.pack()
+}
+
+// Votes of a registry account. Results are only included where they are public. Conviction votes need
+// the current `slot` to weigh their ballots, there is no Clock sysvar in the browser
+#[wasm_bindgen(js_name = decodeVoteState)]
+pub fn decode_vote_state(bytes: &[u8], slot: Option<u64>) -> Result<Array, JsError> {
+    let voting = Voting::unpack(bytes).map_err(error)?;
+
+    // Anonymous viewer, so hidden results stay hidden
+    let viewer = Pubkey::default();
+
+    voting
+        .vote_ids()
+        .into_iter()
+        .map(|vote_id| {
+            let summary = voting.get_vote_summary(vote_id).map_err(error)?;
+            let results = if summary.results_visibility == Visibility::Public {
+                let results = match slot {
+                    Some(slot) => voting.get_full_results_at(&viewer, vote_id, slot),
+                    None => voting.get_full_results_as(&viewer, vote_id),
+                };
+                Some(results.map_err(error)?)
+            } else {
+                None
+            };
+            Ok(summary_to_js(&summary, results))
+        })
+        .collect()
+}
+
+// Event of a transaction log line with its sequence number among the events of its vote, `null` for lines
+// without program data
+#[wasm_bindgen(js_name = parseEvent)]
+pub fn parse_event(log: &str) -> Result<JsValue, JsError> {
+    let Some(EventEnvelope { seq, event, .. }) = EventEnvelope::from_log(log).map_err(error)? else {
+        return Ok(JsValue::NULL);
+    };
+
+    let mut fields: Vec<(&str, JsValue)> = match event {
+        VoteEvent::VoterBallotsInvalidated { vote_id, voter, ballots_removed } => vec![
+            ("kind", "voterBallotsInvalidated".into()),
+            ("voteId", vote_id.0.into()),
+            ("voter", voter.to_string().into()),
+            ("ballotsRemoved", ballots_removed.into()),
+        ],
+        VoteEvent::MilestoneReached { vote_id, milestone } => vec![
+            ("kind", "milestoneReached".into()),
+            ("voteId", vote_id.0.into()),
+            ("milestone", milestone.bits().into()),
+        ],
+        VoteEvent::VoterScrubbed { vote_id, ballots_anonymized } => vec![
+            ("kind", "voterScrubbed".into()),
+            ("voteId", vote_id.0.into()),
+            ("ballotsAnonymized", ballots_anonymized.into()),
+        ],
+        VoteEvent::ResultsCertified { vote_id, certifier, results_hash, certified_at } => vec![
+            ("kind", "resultsCertified".into()),
+            ("voteId", vote_id.0.into()),
+            ("certifier", certifier.to_string().into()),
+            ("resultsHash", results_hash.to_string().into()),
+            ("certifiedAt", certified_at.into()),
+        ],
+        VoteEvent::VoteCreated { vote_id, state } => vec![
+            ("kind", "voteCreated".into()),
+            ("voteId", vote_id.0.into()),
+            ("state", state.into_boxed_slice().into()),
+        ],
+        VoteEvent::VoterAdded { vote_id, voter, weight, valid_until, slot, timestamp } => vec![
+            ("kind", "voterAdded".into()),
+            ("voteId", vote_id.0.into()),
+            ("voter", voter.to_string().into()),
+            ("weight", weight.into()),
+            ("validUntil", valid_until.map_or(JsValue::NULL, |valid_until| valid_until.into())),
+            ("slot", slot.into()),
+            ("timestamp", timestamp.into()),
+        ],
+        VoteEvent::BallotCast { vote_id, voter, question_index, option_index, comment, slot, timestamp } => vec![
+            ("kind", "ballotCast".into()),
+            ("voteId", vote_id.0.into()),
+            ("voter", voter.to_string().into()),
+            ("questionIndex", question_index.into()),
+            ("optionIndex", option_index.0.into()),
+            ("comment", comment.as_ref().map_or(JsValue::NULL, |comment| comment.as_str().into())),
+            ("slot", slot.into()),
+            ("timestamp", timestamp.into()),
+        ],
+        VoteEvent::VoteClosed { vote_id, closure, slot } => vec![
+            ("kind", "voteClosed".into()),
+            ("voteId", vote_id.0.into()),
+            ("closedBy", closure.closed_by.to_string().into()),
+            ("closedAt", closure.closed_at.into()),
+            ("slot", slot.into()),
+        ],
+        VoteEvent::OptionWithdrawn { vote_id, option_index, ballots_refunded, slot, timestamp } => vec![
+            ("kind", "optionWithdrawn".into()),
+            ("voteId", vote_id.0.into()),
+            ("optionIndex", option_index.0.into()),
+            ("ballotsRefunded", ballots_refunded.into()),
+            ("slot", slot.into()),
+            ("timestamp", timestamp.into()),
+        ],
+    };
+    fields.push(("seq", seq.into()));
+
+    Ok(object(&fields))
+}
@@ -0,0 +1,219 @@
+// Full lifecycle of a vote through the instruction builders and a local bank, compared byte for byte with
+// the registry the library builds when it is driven directly with the same calls
+use std::sync::Mutex;
+
+use borsh::BorshDeserialize;
+
+use solana_program_test::{processor, tokio, ProgramTest, ProgramTestContext};
+use solana_sdk::account::Account;
+use solana_sdk::account_info::AccountInfo;
+use solana_sdk::clock::Clock;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::program_stubs::{set_syscall_stubs, SyscallStubs};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::system_program;
+use solana_sdk::transaction::Transaction;
+
+use solana_vote::processor::process_instruction;
+use solana_vote::responses::decode_return_data;
+use solana_vote::{instruction, OptionIndex, OptionsResponse, Outcome, QuestionConfig, QuestionTally, ResultsResponse, VoteConfig, VoteId, VoteOption, VoteStatus, VoterStatusResponse, Visibility, Voting, REGISTRY_DISCRIMINATOR};
+
+// Compute units a single transaction of the lifecycle may take. The native build of the program only
+// charges a nominal unit per instruction, the budget bites when the SBF build is loaded through BPF_OUT_DIR
+const COMPUTE_BUDGET: u64 = 200_000;
+
+// Clock the library reads while the expected registry is built off-chain
+static REPLAY_CLOCK: Mutex<Option<Clock>> = Mutex::new(None);
+
+struct ReplayStubs;
+
+impl SyscallStubs for ReplayStubs {
+    fn sol_log(&self, _message: &str) {}
+
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        let clock = REPLAY_CLOCK.lock().unwrap().clone().expect("the replay clock is set");
+        unsafe { *(var_addr as *mut Clock) = clock };
+        solana_sdk::entrypoint::SUCCESS
+    }
+}
+
+// Account of a signing wallet, for the library calls that take accounts
+fn signer_info(key: &Pubkey) -> AccountInfo<'static> {
+    let key = Box::leak(Box::new(*key));
+    let lamports = Box::leak(Box::new(0));
+    let data = Box::leak(Vec::new().into_boxed_slice());
+    AccountInfo::new(key, true, false, lamports, data, &system_program::ID, false, 0)
+}
+
+struct Bank {
+    context: ProgramTestContext,
+    registry: Pubkey,
+}
+
+impl Bank {
+    // Simulate the transaction to check its compute units, then process it. Returns the return data of the simulation
+    async fn send(&mut self, instructions: &[Instruction], signers: &[&Keypair]) -> Option<Vec<u8>> {
+        let payer = self.context.payer.insecure_clone();
+        let mut all_signers = vec![&payer];
+        all_signers.extend_from_slice(signers);
+        let transaction = Transaction::new_signed_with_payer(instructions, Some(&payer.pubkey()), &all_signers, self.context.last_blockhash);
+
+        let simulation = self.context.banks_client.simulate_transaction(transaction.clone()).await.unwrap();
+        let details = simulation.simulation_details.unwrap();
+        assert_eq!(simulation.result, Some(Ok(())), "logs: {:?}", details.logs);
+        assert!(details.units_consumed <= COMPUTE_BUDGET, "{} compute units, logs: {:?}", details.units_consumed, details.logs);
+
+        self.context.banks_client.process_transaction(transaction).await.unwrap();
+        details.return_data.map(|return_data| return_data.data)
+    }
+
+    // Simulate a read instruction and decode its return data, which the voting program has to have set. Reads of
+    // restricted data are signed by their viewer
+    async fn read<T: BorshDeserialize>(&mut self, program_id: &Pubkey, read: Instruction, signers: &[&Keypair]) -> T {
+        let payer = self.context.payer.insecure_clone();
+        let mut all_signers = vec![&payer];
+        all_signers.extend_from_slice(signers);
+        let transaction = Transaction::new_signed_with_payer(&[read], Some(&payer.pubkey()), &all_signers, self.context.last_blockhash);
+
+        let simulation = self.context.banks_client.simulate_transaction(transaction).await.unwrap();
+        let details = simulation.simulation_details.unwrap();
+        assert_eq!(simulation.result, Some(Ok(())), "logs: {:?}", details.logs);
+        let return_data = details.return_data.expect("the read instruction sets return data");
+        decode_return_data(program_id, &return_data.program_id, &return_data.data).unwrap()
+    }
+
+    async fn clock(&mut self) -> Clock {
+        self.context.banks_client.get_sysvar::<Clock>().await.unwrap()
+    }
+
+    async fn registry_data(&mut self) -> Vec<u8> {
+        self.context.banks_client.get_account(self.registry).await.unwrap().unwrap().data
+    }
+}
+
+#[tokio::test]
+async fn test_full_lifecycle() {
+    let program_id = Pubkey::new_unique();
+    let registry = Pubkey::new_unique();
+    let admin = Keypair::new();
+    let creator = Keypair::new();
+    let voters = [Keypair::new(), Keypair::new(), Keypair::new()];
+
+    let mut program_test = ProgramTest::new("solana_vote", program_id, processor!(process_instruction));
+    program_test.add_account(registry, Account { lamports: 1_000_000_000, data: vec![0; 10_240], owner: program_id, ..Account::default() });
+    let mut bank = Bank { context: program_test.start_with_context().await, registry };
+
+    // Two questions, so the receipt of a voter does not stop their second ballot
+    let question = |title: &str, labels: &[&str]| QuestionConfig {
+        title: title.try_into().unwrap(),
+        options: VoteOption::from_labels(labels.iter().map(|label| label.to_string()).collect()).unwrap(),
+    };
+    let config = VoteConfig {
+        results_visibility: Visibility::AllowlistOnly,
+        quorum: Some(3),
+        ..VoteConfig::with_questions("Budget".to_string(), vec![question("Spending", &["Parks", "Roads", "Schools"]), question("Chair", &["Alice", "Bob"])]).unwrap()
+    };
+
+    // Registry, vote and allowlist
+    let mut setup = vec![
+        instruction::initialize_registry(&program_id, &registry, &admin.pubkey()),
+        instruction::create_vote(&program_id, &registry, &creator.pubkey(), config.clone(), None),
+    ];
+    setup.extend(voters.iter().map(|voter| instruction::add_allowed_voter(&program_id, &registry, &creator.pubkey(), VoteId(0), &voter.pubkey())));
+    bank.send(&setup, &[&creator]).await;
+    let setup_clock = bank.clock().await;
+
+    // The third voter hands their votes to the first, who casts both on the first question in separate slots
+    let payer = bank.context.payer.pubkey();
+    let ballots = [
+        instruction::delegate_vote(&program_id, &registry, &voters[2].pubkey(), VoteId(0), &voters[0].pubkey(), None),
+        instruction::cast_vote(&program_id, &registry, &voters[0].pubkey(), &payer, VoteId(0), 0, OptionIndex(0)),
+        instruction::cast_vote(&program_id, &registry, &voters[1].pubkey(), &payer, VoteId(0), 0, OptionIndex(1)),
+        instruction::cast_vote(&program_id, &registry, &voters[1].pubkey(), &payer, VoteId(0), 1, OptionIndex(0)),
+    ];
+    bank.send(&ballots, &[&voters[0], &voters[1], &voters[2]]).await;
+    let ballots_clock = bank.clock().await;
+
+    let next_slot = ballots_clock.slot + 10;
+    bank.context.warp_to_slot(next_slot).unwrap();
+    bank.context.last_blockhash = bank.context.get_new_latest_blockhash().await.unwrap();
+    let ballots = [
+        instruction::cast_vote(&program_id, &registry, &voters[0].pubkey(), &payer, VoteId(0), 0, OptionIndex(0)),
+        instruction::cast_vote(&program_id, &registry, &voters[0].pubkey(), &payer, VoteId(0), 1, OptionIndex(1)),
+    ];
+    bank.send(&ballots, &[&voters[0]]).await;
+    let second_ballot_clock = bank.clock().await;
+
+    // Queries answer through return data
+    assert_eq!(bank.send(&[instruction::has_voted(&program_id, &registry, VoteId(0), &voters[1].pubkey())], &[]).await, Some(vec![1]));
+    assert_eq!(bank.send(&[instruction::has_voted(&program_id, &registry, VoteId(0), &voters[2].pubkey())], &[]).await, Some(vec![0]));
+
+    let results: ResultsResponse = bank.read(&program_id, instruction::get_results(&program_id, &registry, &voters[1].pubkey(), VoteId(0), None), &[&voters[1]]).await;
+    let votes: Vec<Vec<u32>> = results.questions.iter().map(|question| question.votes.clone()).collect();
+    assert_eq!(votes, vec![vec![2, 1, 0], vec![1, 1]]);
+    let results: ResultsResponse = bank.read(&program_id, instruction::get_results(&program_id, &registry, &creator.pubkey(), VoteId(0), Some(1)), &[&creator]).await;
+    assert_eq!(results.questions, vec![QuestionTally { question_index: 1, votes: vec![1, 1] }]);
+
+    let options: OptionsResponse = bank.read(&program_id, instruction::get_options(&program_id, &registry, VoteId(0)), &[]).await;
+    assert_eq!(options.options.iter().map(|option| option.label.as_str()).collect::<Vec<_>>(), vec!["Parks", "Roads", "Schools"]);
+
+    assert!(bank.read::<bool>(&program_id, instruction::is_voter_allowed(&program_id, &registry, VoteId(0), &voters[2].pubkey()), &[]).await);
+    assert!(!bank.read::<bool>(&program_id, instruction::is_voter_allowed(&program_id, &registry, VoteId(0), &payer), &[]).await);
+
+    let status: VoterStatusResponse = bank.read(&program_id, instruction::get_voter_status(&program_id, &registry, &voters[2].pubkey(), VoteId(0), &voters[2].pubkey()), &[&voters[2]]).await;
+    assert_eq!(status.status.delegate, Some(voters[0].pubkey()));
+    assert_eq!(status.status.votes_left, vec![0, 0]);
+
+    bank.send(&[instruction::close_vote(&program_id, &registry, &creator.pubkey(), VoteId(0))], &[&creator]).await;
+    let close_clock = bank.clock().await;
+
+    let data = bank.registry_data().await;
+    let voting = Voting::unpack(&data).unwrap();
+    let summary = voting.get_vote_summary(VoteId(0)).unwrap();
+    assert_eq!(summary.status, VoteStatus::Closed);
+    assert_eq!(summary.closure.map(|closure| closure.closed_at), Some(close_clock.unix_timestamp));
+
+    let results = voting.get_full_results_as(&creator.pubkey(), VoteId(0)).unwrap();
+    let votes: Vec<Vec<u32>> = results.questions.iter().map(|question| question.options.iter().map(|option| option.votes).collect()).collect();
+    assert_eq!(votes, vec![vec![2, 1, 0], vec![1, 1]]);
+    assert_eq!(voting.get_winner_as(&creator.pubkey(), VoteId(0), 0), Ok(Outcome::Winner { option_index: 0 }));
+    assert_eq!(voting.get_winner_as(&creator.pubkey(), VoteId(0), 1), Ok(Outcome::QuorumNotMet { total_votes: 2 }));
+    assert!(voting.get_full_results_as(&Pubkey::new_unique(), VoteId(0)).is_err()); // Results stay with the allowlist
+
+    // Receipts of the voters who cast a ballot themselves, paid by the fee payer
+    for voter in &voters[..2] {
+        let ballot = instruction::cast_vote(&program_id, &registry, &voter.pubkey(), &payer, VoteId(0), 0, OptionIndex(0));
+        assert!(bank.context.banks_client.get_account(ballot.accounts[3].pubkey).await.unwrap().is_some());
+    }
+
+    // Drive the library with the same calls at the same clocks, the process stubs only read the clock
+    let process_stubs = set_syscall_stubs(Box::new(ReplayStubs));
+    let set_clock = |clock: &Clock| *REPLAY_CLOCK.lock().unwrap() = Some(clock.clone());
+
+    set_clock(&setup_clock);
+    let mut expected = Voting::new(admin.pubkey());
+    assert_eq!(expected.create_vote_with_config(config, &[signer_info(&creator.pubkey())]), Ok(VoteId(0)));
+    for voter in &voters {
+        expected.add_allowed_voter_as(&creator.pubkey(), VoteId(0), voter.pubkey()).unwrap();
+    }
+
+    set_clock(&ballots_clock);
+    expected.delegate_vote_as(&voters[2].pubkey(), VoteId(0), &voters[0].pubkey(), None).unwrap();
+    expected.vote_on_question_as(&voters[0].pubkey(), VoteId(0), 0, OptionIndex(0)).unwrap();
+    expected.vote_on_question_as(&voters[1].pubkey(), VoteId(0), 0, OptionIndex(1)).unwrap();
+    expected.vote_on_question_as(&voters[1].pubkey(), VoteId(0), 1, OptionIndex(0)).unwrap();
+
+    set_clock(&second_ballot_clock);
+    expected.vote_on_question_as(&voters[0].pubkey(), VoteId(0), 0, OptionIndex(0)).unwrap();
+    expected.vote_on_question_as(&voters[0].pubkey(), VoteId(0), 1, OptionIndex(1)).unwrap();
+
+    set_clock(&close_clock);
+    expected.close_vote(VoteId(0), &[signer_info(&creator.pubkey())]).unwrap();
+    set_syscall_stubs(process_stubs);
+
+    let mut expected_data = REGISTRY_DISCRIMINATOR.to_vec();
+    expected_data.extend_from_slice(&borsh::to_vec(&expected).unwrap());
+    assert_eq!(&data[..expected_data.len()], &expected_data[..]);
+    assert_eq!(voting, expected);
+}
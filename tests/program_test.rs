@@ -0,0 +1,515 @@
+// End-to-end run of the instruction builders against the program in a local bank
+use solana_program_test::{processor, tokio, BanksClient, ProgramTest};
+use solana_sdk::account::Account;
+use solana_sdk::account_info::{next_account_info, AccountInfo};
+use solana_sdk::ed25519_instruction::new_ed25519_instruction;
+use solana_sdk::entrypoint::ProgramResult;
+use solana_sdk::instruction::{AccountMeta, Instruction, InstructionError};
+use solana_sdk::program::{get_return_data, invoke};
+use solana_sdk::program_error::ProgramError;
+use solana_sdk::program_option::COption;
+use solana_sdk::program_pack::Pack;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::rent::Rent;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::transaction::{Transaction, TransactionError};
+
+use solana_vote::processor::process_instruction;
+use solana_vote::responses::decode_return_data;
+use solana_vote::{get_vote_address, instruction, CreationDeposit, CreationPolicy, DepositStatus, OptionIndex, Prerequisite, ResultsResponse, SignedBallot, VoteConfig, VoteError, VoteId, VoteStatus, VoteType, Voting};
+
+// Program asking the voting program a query through CPI and writing the answer into its output account.
+// Accounts: [output (writable), voting program, registry], the instruction data is the query
+fn process_query_caller(_program_id: &Pubkey, accounts: &[AccountInfo], instruction_data: &[u8]) -> ProgramResult {
+    let account_iter = &mut accounts.iter();
+    let output = next_account_info(account_iter)?;
+    let voting_program = next_account_info(account_iter)?;
+    let registry = next_account_info(account_iter)?;
+
+    let query = Instruction::new_with_bytes(*voting_program.key, instruction_data, vec![AccountMeta::new_readonly(*registry.key, false)]);
+    invoke(&query, std::slice::from_ref(registry))?;
+
+    let (answered_by, answer) = get_return_data().ok_or(ProgramError::InvalidAccountData)?;
+    if answered_by != *voting_program.key {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    output.try_borrow_mut_data()?[..answer.len()].copy_from_slice(&answer);
+    Ok(())
+}
+
+// Program reading the results of a vote through CPI and writing the votes of its first question into its
+// output account, little-endian. Accounts: [output (writable), voting program, registry, viewer], the
+// instruction data is the `GetResults` instruction
+fn process_results_caller(_program_id: &Pubkey, accounts: &[AccountInfo], instruction_data: &[u8]) -> ProgramResult {
+    let account_iter = &mut accounts.iter();
+    let output = next_account_info(account_iter)?;
+    let voting_program = next_account_info(account_iter)?;
+    let registry = next_account_info(account_iter)?;
+    let viewer = next_account_info(account_iter)?;
+
+    let query = Instruction::new_with_bytes(*voting_program.key, instruction_data, vec![AccountMeta::new_readonly(*registry.key, false), AccountMeta::new_readonly(*viewer.key, false)]);
+    invoke(&query, &[registry.clone(), viewer.clone()])?;
+
+    let (answered_by, answer) = get_return_data().ok_or(ProgramError::InvalidAccountData)?;
+    let results: ResultsResponse = decode_return_data(voting_program.key, &answered_by, &answer)?;
+    let votes: Vec<u8> = results.questions[0].votes.iter().flat_map(|votes| votes.to_le_bytes()).collect();
+    output.try_borrow_mut_data()?[..votes.len()].copy_from_slice(&votes);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_vote_lifecycle() {
+    let program_id = Pubkey::new_unique();
+    let registry = Pubkey::new_unique();
+    let creator = Keypair::new();
+    let voter = Keypair::new();
+
+    let mut program_test = ProgramTest::new("solana_vote", program_id, processor!(process_instruction));
+    program_test.add_account(registry, Account { lamports: 1_000_000_000, data: vec![0; 10_240], owner: program_id, ..Account::default() });
+    program_test.add_account(creator.pubkey(), Account { lamports: 1_000_000_000, ..Account::default() });
+    let (mut banks_client, payer, blockhash) = program_test.start().await;
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            instruction::initialize_registry(&program_id, &registry, &creator.pubkey()),
+            instruction::create_vote(&program_id, &registry, &creator.pubkey(), VoteConfig::from_labels("Budget".to_string(), vec!["Yes".to_string(), "No".to_string()]).unwrap(), None),
+            instruction::add_allowed_voter(&program_id, &registry, &creator.pubkey(), VoteId(0), &voter.pubkey()),
+        ],
+        Some(&payer.pubkey()),
+        &[&payer, &creator],
+        blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // The payer funds the receipt of the voter
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::cast_vote(&program_id, &registry, &voter.pubkey(), &payer.pubkey(), VoteId(0), 0, OptionIndex(1))],
+        Some(&payer.pubkey()),
+        &[&payer, &voter],
+        blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::close_vote(&program_id, &registry, &creator.pubkey(), VoteId(0))],
+        Some(&payer.pubkey()),
+        &[&payer, &creator],
+        blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let account = banks_client.get_account(registry).await.unwrap().unwrap();
+    let voting = Voting::unpack(&account.data).unwrap();
+    assert_eq!(voting.vote_ids(), vec![VoteId(0)]);
+    assert_eq!(voting.get_vote_summary(VoteId(0)).unwrap().status, VoteStatus::Closed);
+
+    let results = voting.get_full_results_as(&creator.pubkey(), VoteId(0)).unwrap();
+    let votes: Vec<u32> = results.questions[0].options.iter().map(|option| option.votes).collect();
+    assert_eq!(votes, vec![0, 1]);
+}
+
+#[tokio::test]
+async fn test_creation_deposit_refund() {
+    let program_id = Pubkey::new_unique();
+    let registry = Pubkey::new_unique();
+    let creator = Keypair::new();
+    let treasury = Pubkey::new_unique();
+    let lamports = Rent::default().minimum_balance(0);
+
+    let mut program_test = ProgramTest::new("solana_vote", program_id, processor!(process_instruction));
+    program_test.add_account(registry, Account { lamports: 1_000_000_000, data: vec![0; 10_240], owner: program_id, ..Account::default() });
+    program_test.add_account(creator.pubkey(), Account { lamports: 1_000_000_000, ..Account::default() });
+    let (mut banks_client, payer, blockhash) = program_test.start().await;
+
+    // The deposit of the first vote goes to its vote address
+    let policy = CreationPolicy { deposit: Some(CreationDeposit { lamports, treasury }), ..CreationPolicy::default() };
+    let config = VoteConfig::from_labels("Budget".to_string(), vec!["Yes".to_string(), "No".to_string()]).unwrap();
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            instruction::initialize_registry(&program_id, &registry, &creator.pubkey()),
+            instruction::update_policy(&program_id, &registry, &creator.pubkey(), policy),
+            instruction::create_vote_with_deposit(&program_id, &registry, &creator.pubkey(), config, None, VoteId(0)),
+        ],
+        Some(&payer.pubkey()),
+        &[&payer, &creator],
+        blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let (vault, _) = get_vote_address(&program_id, &registry, VoteId(0));
+    assert_eq!(banks_client.get_balance(vault).await.unwrap(), lamports);
+    assert_eq!(banks_client.get_balance(creator.pubkey()).await.unwrap(), 1_000_000_000 - lamports);
+
+    // Closing it signs for the vault with its seeds and refunds the creator
+    let mut close = instruction::close_vote(&program_id, &registry, &creator.pubkey(), VoteId(0));
+    close.accounts.extend(instruction::deposit_accounts(&program_id, &registry, VoteId(0), &creator.pubkey()));
+    let transaction = Transaction::new_signed_with_payer(&[close], Some(&payer.pubkey()), &[&payer, &creator], blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    assert_eq!(banks_client.get_balance(vault).await.unwrap(), 0);
+    assert_eq!(banks_client.get_balance(creator.pubkey()).await.unwrap(), 1_000_000_000);
+    let account = banks_client.get_account(registry).await.unwrap().unwrap();
+    let summary = Voting::unpack(&account.data).unwrap().get_vote_summary(VoteId(0)).unwrap();
+    assert_eq!(summary.deposit.map(|deposit| deposit.status), Some(DepositStatus::Refunded));
+}
+
+#[tokio::test]
+async fn test_query_instructions_through_cpi() {
+    let program_id = Pubkey::new_unique();
+    let caller_id = Pubkey::new_unique();
+    let registry = Pubkey::new_unique();
+    let output = Pubkey::new_unique();
+    let creator = Keypair::new();
+    let voter = Keypair::new();
+
+    let mut program_test = ProgramTest::new("solana_vote", program_id, processor!(process_instruction));
+    program_test.add_program("query_caller", caller_id, processor!(process_query_caller));
+    program_test.add_account(registry, Account { lamports: 1_000_000_000, data: vec![0; 10_240], owner: program_id, ..Account::default() });
+    program_test.add_account(output, Account { lamports: 1_000_000_000, data: vec![0xff], owner: caller_id, ..Account::default() });
+    program_test.add_account(creator.pubkey(), Account { lamports: 1_000_000_000, ..Account::default() });
+    let (mut banks_client, payer, blockhash) = program_test.start().await;
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            instruction::initialize_registry(&program_id, &registry, &creator.pubkey()),
+            instruction::create_vote(&program_id, &registry, &creator.pubkey(), VoteConfig::from_labels("Budget".to_string(), vec!["Yes".to_string(), "No".to_string()]).unwrap(), None),
+            instruction::add_allowed_voter(&program_id, &registry, &creator.pubkey(), VoteId(0), &voter.pubkey()),
+            instruction::cast_vote(&program_id, &registry, &voter.pubkey(), &payer.pubkey(), VoteId(0), 0, OptionIndex(0)),
+        ],
+        Some(&payer.pubkey()),
+        &[&payer, &creator, &voter],
+        blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let queries = [
+        (instruction::vote_exists(&program_id, &registry, VoteId(0)), 1),
+        (instruction::vote_exists(&program_id, &registry, VoteId(1)), 0),
+        (instruction::has_voted(&program_id, &registry, VoteId(0), &voter.pubkey()), 1),
+        (instruction::has_voted(&program_id, &registry, VoteId(0), &creator.pubkey()), 0),
+        (instruction::option_exists(&program_id, &registry, VoteId(0), OptionIndex(1)), 1),
+        (instruction::option_exists(&program_id, &registry, VoteId(0), OptionIndex(2)), 0),
+    ];
+    for (query, expected) in queries {
+        let accounts = vec![AccountMeta::new(output, false), AccountMeta::new_readonly(program_id, false), AccountMeta::new_readonly(registry, false)];
+        let caller = Instruction::new_with_bytes(caller_id, &query.data, accounts);
+
+        let transaction = Transaction::new_signed_with_payer(&[caller], Some(&payer.pubkey()), &[&payer], blockhash);
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        let account = banks_client.get_account(output).await.unwrap().unwrap();
+        assert_eq!(account.data, vec![expected]);
+    }
+}
+
+#[tokio::test]
+async fn test_results_through_cpi() {
+    let program_id = Pubkey::new_unique();
+    let caller_id = Pubkey::new_unique();
+    let registry = Pubkey::new_unique();
+    let output = Pubkey::new_unique();
+    let creator = Keypair::new();
+    let voter = Keypair::new();
+
+    let mut program_test = ProgramTest::new("solana_vote", program_id, processor!(process_instruction));
+    program_test.add_program("results_caller", caller_id, processor!(process_results_caller));
+    program_test.add_account(registry, Account { lamports: 1_000_000_000, data: vec![0; 10_240], owner: program_id, ..Account::default() });
+    program_test.add_account(output, Account { lamports: 1_000_000_000, data: vec![0xff; 8], owner: caller_id, ..Account::default() });
+    program_test.add_account(creator.pubkey(), Account { lamports: 1_000_000_000, ..Account::default() });
+    let (mut banks_client, payer, blockhash) = program_test.start().await;
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            instruction::initialize_registry(&program_id, &registry, &creator.pubkey()),
+            instruction::create_vote(&program_id, &registry, &creator.pubkey(), VoteConfig::from_labels("Budget".to_string(), vec!["Yes".to_string(), "No".to_string()]).unwrap(), None),
+            instruction::add_allowed_voter(&program_id, &registry, &creator.pubkey(), VoteId(0), &voter.pubkey()),
+            instruction::cast_vote(&program_id, &registry, &voter.pubkey(), &payer.pubkey(), VoteId(0), 0, OptionIndex(1)),
+        ],
+        Some(&payer.pubkey()),
+        &[&payer, &creator, &voter],
+        blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let query = instruction::get_results(&program_id, &registry, &creator.pubkey(), VoteId(0), None);
+    let accounts = vec![
+        AccountMeta::new(output, false),
+        AccountMeta::new_readonly(program_id, false),
+        AccountMeta::new_readonly(registry, false),
+        AccountMeta::new_readonly(creator.pubkey(), false),
+    ];
+    let caller = Instruction::new_with_bytes(caller_id, &query.data, accounts);
+    let transaction = Transaction::new_signed_with_payer(&[caller], Some(&payer.pubkey()), &[&payer], blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let account = banks_client.get_account(output).await.unwrap().unwrap();
+    assert_eq!(account.data, [0u32.to_le_bytes(), 1u32.to_le_bytes()].concat());
+}
+
+#[tokio::test]
+async fn test_fee_payer_before_the_voter() {
+    let program_id = Pubkey::new_unique();
+    let registry = Pubkey::new_unique();
+    let creator = Keypair::new();
+    let voter = Keypair::new();
+
+    let mut program_test = ProgramTest::new("solana_vote", program_id, processor!(process_instruction));
+    program_test.add_account(registry, Account { lamports: 1_000_000_000, data: vec![0; 10_240], owner: program_id, ..Account::default() });
+    let (mut banks_client, payer, blockhash) = program_test.start().await;
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            instruction::initialize_registry(&program_id, &registry, &creator.pubkey()),
+            instruction::create_vote(&program_id, &registry, &creator.pubkey(), VoteConfig::from_labels("Budget".to_string(), vec!["Yes".to_string(), "No".to_string()]).unwrap(), None),
+            instruction::add_allowed_voter(&program_id, &registry, &creator.pubkey(), VoteId(0), &voter.pubkey()),
+        ],
+        Some(&payer.pubkey()),
+        &[&payer, &creator],
+        blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // The fee payer of the transaction comes first and pays the receipt, the voter only signs
+    let ballot = instruction::cast_vote(&program_id, &registry, &voter.pubkey(), &payer.pubkey(), VoteId(0), 0, OptionIndex(1));
+    assert_eq!(ballot.accounts[0].pubkey, payer.pubkey());
+    assert_eq!(ballot.accounts[1].pubkey, voter.pubkey());
+    let transaction = Transaction::new_signed_with_payer(&[ballot], Some(&payer.pubkey()), &[&payer, &voter], blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let account = banks_client.get_account(registry).await.unwrap().unwrap();
+    let voting = Voting::unpack(&account.data).unwrap();
+    assert!(voting.has_voted(VoteId(0), &voter.pubkey()));
+    assert!(!voting.has_voted(VoteId(0), &payer.pubkey()));
+}
+
+#[tokio::test]
+async fn test_relayed_ballot() {
+    let program_id = Pubkey::new_unique();
+    let registry = Pubkey::new_unique();
+    let other_registry = Pubkey::new_unique();
+    let creator = Keypair::new();
+    let voter = Keypair::new();
+    let other_relayer = Keypair::new();
+
+    let mut program_test = ProgramTest::new("solana_vote", program_id, processor!(process_instruction));
+    for registry in [registry, other_registry] {
+        program_test.add_account(registry, Account { lamports: 1_000_000_000, data: vec![0; 10_240], owner: program_id, ..Account::default() });
+    }
+    program_test.add_account(other_relayer.pubkey(), Account { lamports: 1_000_000_000, ..Account::default() });
+    let (mut banks_client, relayer, blockhash) = program_test.start().await;
+
+    // Both registries hold vote 0 with the voter on its allowlist
+    for registry in [registry, other_registry] {
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                instruction::initialize_registry(&program_id, &registry, &creator.pubkey()),
+                instruction::create_vote(&program_id, &registry, &creator.pubkey(), VoteConfig::from_labels("Budget".to_string(), vec!["Yes".to_string(), "No".to_string()]).unwrap(), None),
+                instruction::add_allowed_voter(&program_id, &registry, &creator.pubkey(), VoteId(0), &voter.pubkey()),
+            ],
+            Some(&relayer.pubkey()),
+            &[&relayer, &creator],
+            blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+    }
+
+    // The voter signs the ballot off-chain, only the relayer signs the transaction
+    let ballot = SignedBallot { vote_id: VoteId(0), option_index: OptionIndex(1), voter: voter.pubkey(), nonce: 1, expiry: i64::MAX };
+    let dalek_keypair = ed25519_dalek::Keypair::from_bytes(&voter.to_bytes()).unwrap();
+    let verify = new_ed25519_instruction(&dalek_keypair, &ballot.to_message(&program_id, &registry));
+    let signature_offset = u16::from_le_bytes([verify.data[2], verify.data[3]]) as usize;
+    let signature: [u8; 64] = verify.data[signature_offset..signature_offset + 64].try_into().unwrap();
+    let relay = instruction::cast_vote_signed(&program_id, &registry, &relayer.pubkey(), ballot, signature);
+
+    let transaction = Transaction::new_signed_with_payer(&[verify.clone(), relay.clone()], Some(&relayer.pubkey()), &[&relayer], blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let account = banks_client.get_account(registry).await.unwrap().unwrap();
+    let voting = Voting::unpack(&account.data).unwrap();
+    assert!(voting.has_voted(VoteId(0), &voter.pubkey()));
+    assert!(!voting.has_voted(VoteId(0), &relayer.pubkey()));
+    assert!(banks_client.get_account(relay.accounts[2].pubkey).await.unwrap().is_some()); // Receipt paid by the relayer
+
+    // The same ballot cannot be relayed twice, not even by another relayer
+    let relay = instruction::cast_vote_signed(&program_id, &registry, &other_relayer.pubkey(), ballot, signature);
+    let transaction = Transaction::new_signed_with_payer(&[verify.clone(), relay], Some(&other_relayer.pubkey()), &[&other_relayer], blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_err());
+
+    // Nor in the other registry, where the voter's nonce is unused
+    let relay = instruction::cast_vote_signed(&program_id, &other_registry, &other_relayer.pubkey(), ballot, signature);
+    let transaction = Transaction::new_signed_with_payer(&[verify, relay], Some(&other_relayer.pubkey()), &[&other_relayer], blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_err());
+    let account = banks_client.get_account(other_registry).await.unwrap().unwrap();
+    assert!(!Voting::unpack(&account.data).unwrap().has_voted(VoteId(0), &voter.pubkey()));
+}
+
+// Token-burn vote whose voter holds `balance` tokens and approved the vote address to burn all of them
+async fn token_burn_bank(balance: u64) -> (solana_program_test::BanksClient, Keypair, Pubkey, Pubkey, Keypair, [Pubkey; 2]) {
+    let program_id = Pubkey::new_unique();
+    let registry = Pubkey::new_unique();
+    let creator = Keypair::new();
+    let voter = Keypair::new();
+    let [mint, token_account] = [Pubkey::new_unique(), Pubkey::new_unique()];
+    let (vote_address, _) = get_vote_address(&program_id, &registry, VoteId(0));
+
+    let mut program_test = ProgramTest::new("solana_vote", program_id, processor!(process_instruction));
+    program_test.add_account(registry, Account { lamports: 1_000_000_000, data: vec![0; 10_240], owner: program_id, ..Account::default() });
+    let mut mint_data = vec![0; spl_token::state::Mint::LEN];
+    spl_token::state::Mint { supply: balance, decimals: 0, is_initialized: true, ..spl_token::state::Mint::default() }.pack_into_slice(&mut mint_data);
+    program_test.add_account(mint, Account { lamports: Rent::default().minimum_balance(mint_data.len()), data: mint_data, owner: spl_token::id(), ..Account::default() });
+    let holding = spl_token::state::Account {
+        mint,
+        owner: voter.pubkey(),
+        amount: balance,
+        delegate: COption::Some(vote_address),
+        state: spl_token::state::AccountState::Initialized,
+        delegated_amount: balance,
+        ..spl_token::state::Account::default()
+    };
+    let mut token_data = vec![0; spl_token::state::Account::LEN];
+    holding.pack_into_slice(&mut token_data);
+    program_test.add_account(token_account, Account { lamports: Rent::default().minimum_balance(token_data.len()), data: token_data, owner: spl_token::id(), ..Account::default() });
+    let (mut banks_client, payer, blockhash) = program_test.start().await;
+
+    let config = VoteConfig { vote_type: VoteType::TokenBurn { mint, burn_amount: 30 }, ..VoteConfig::from_labels("Budget".to_string(), vec!["Yes".to_string(), "No".to_string()]).unwrap() };
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            instruction::initialize_registry(&program_id, &registry, &creator.pubkey()),
+            instruction::create_vote(&program_id, &registry, &creator.pubkey(), config, None),
+            instruction::add_allowed_voter(&program_id, &registry, &creator.pubkey(), VoteId(0), &voter.pubkey()),
+        ],
+        Some(&payer.pubkey()),
+        &[&payer, &creator],
+        blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    (banks_client, payer, program_id, registry, voter, [mint, token_account])
+}
+
+fn burning_ballot(program_id: &Pubkey, registry: &Pubkey, voter: &Pubkey, payer: &Pubkey, [mint, token_account]: [Pubkey; 2]) -> Instruction {
+    let mut ballot = instruction::cast_vote(program_id, registry, voter, payer, VoteId(0), 0, OptionIndex(1));
+    ballot.accounts.extend(instruction::token_burn_accounts(program_id, registry, VoteId(0), &token_account, &mint));
+    ballot
+}
+
+#[tokio::test]
+async fn test_token_burn_ballot() {
+    let (mut banks_client, payer, program_id, registry, voter, tokens) = token_burn_bank(100).await;
+    let blockhash = banks_client.get_latest_blockhash().await.unwrap();
+
+    let ballot = burning_ballot(&program_id, &registry, &voter.pubkey(), &payer.pubkey(), tokens);
+    let transaction = Transaction::new_signed_with_payer(&[ballot], Some(&payer.pubkey()), &[&payer, &voter], blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // The ballot counts and its tokens are gone, from the account and from the supply
+    let account = banks_client.get_account(registry).await.unwrap().unwrap();
+    assert!(Voting::unpack(&account.data).unwrap().has_voted(VoteId(0), &voter.pubkey()));
+    let holding = spl_token::state::Account::unpack(&banks_client.get_account(tokens[1]).await.unwrap().unwrap().data).unwrap();
+    assert_eq!((holding.amount, holding.delegated_amount), (70, 70));
+    let mint = spl_token::state::Mint::unpack(&banks_client.get_account(tokens[0]).await.unwrap().unwrap().data).unwrap();
+    assert_eq!(mint.supply, 70);
+}
+
+#[tokio::test]
+async fn test_token_burn_insufficient_balance() {
+    let (mut banks_client, payer, program_id, registry, voter, tokens) = token_burn_bank(20).await;
+    let blockhash = banks_client.get_latest_blockhash().await.unwrap();
+
+    let ballot = burning_ballot(&program_id, &registry, &voter.pubkey(), &payer.pubkey(), tokens);
+    let transaction = Transaction::new_signed_with_payer(&[ballot], Some(&payer.pubkey()), &[&payer, &voter], blockhash);
+    let error = banks_client.process_transaction(transaction).await.unwrap_err().unwrap();
+    assert_eq!(error, TransactionError::InstructionError(0, InstructionError::Custom(VoteError::InsufficientTokens as u32)));
+
+    // Nothing was burned and no ballot counts
+    let account = banks_client.get_account(registry).await.unwrap().unwrap();
+    assert!(!Voting::unpack(&account.data).unwrap().has_voted(VoteId(0), &voter.pubkey()));
+    let holding = spl_token::state::Account::unpack(&banks_client.get_account(tokens[1]).await.unwrap().unwrap().data).unwrap();
+    assert_eq!(holding.amount, 20);
+}
+
+// Compute units a ballot or delegation may take on a vote with `LARGE_ALLOWLIST` voters. The native build only
+// charges for syscalls, the budget and the saving show when the SBF build is loaded through BPF_OUT_DIR
+const BALLOT_BUDGET: u64 = 200_000;
+const LARGE_ALLOWLIST: usize = 1_000;
+
+// Units of the transaction, checked by simulating it before it is processed
+async fn units_consumed(banks_client: &mut BanksClient, payer: &Keypair, instruction: Instruction, signer: &Keypair) -> u64 {
+    let blockhash = banks_client.get_latest_blockhash().await.unwrap();
+    let transaction = Transaction::new_signed_with_payer(&[instruction], Some(&payer.pubkey()), &[payer, signer], blockhash);
+    let simulation = banks_client.simulate_transaction(transaction.clone()).await.unwrap();
+    let details = simulation.simulation_details.unwrap();
+    assert_eq!(simulation.result, Some(Ok(())), "logs: {:?}", details.logs);
+    banks_client.process_transaction(transaction).await.unwrap();
+    details.units_consumed
+}
+
+#[tokio::test]
+async fn test_ballot_compute_units_large_allowlist() {
+    let program_id = Pubkey::new_unique();
+    let registry = Pubkey::new_unique();
+    let creator = Keypair::new();
+    let voters: Vec<Keypair> = (0..2).map(|_| Keypair::new()).collect();
+
+    let mut program_test = ProgramTest::new("solana_vote", program_id, processor!(process_instruction));
+    program_test.add_account(registry, Account { lamports: 1_000_000_000, data: vec![0; 1024 * 1024], owner: program_id, ..Account::default() });
+    let (mut banks_client, payer, blockhash) = program_test.start().await;
+
+    // A passed gate vote, two votes waiting on it and an open one. Ballots and delegations on the waiting votes
+    // read their outcome, they go through the whole registry like before the votes had slots of their own
+    let config = |title: &str| VoteConfig::from_labels(title.to_string(), vec!["Yes".to_string(), "No".to_string()]).unwrap();
+    let gated = |title: &str| VoteConfig { prerequisite: Some(Prerequisite { vote_id: VoteId(0), required_option_index: OptionIndex(0) }), ..config(title) };
+    let (gate, full_ballot, full_delegation, open) = (VoteId(0), VoteId(1), VoteId(2), VoteId(3));
+    let setup = [
+        instruction::initialize_registry(&program_id, &registry, &creator.pubkey()),
+        instruction::create_vote(&program_id, &registry, &creator.pubkey(), config("Gate"), None),
+        instruction::create_vote(&program_id, &registry, &creator.pubkey(), gated("Budget"), None),
+        instruction::create_vote(&program_id, &registry, &creator.pubkey(), gated("Roadmap"), None),
+        instruction::create_vote(&program_id, &registry, &creator.pubkey(), config("Charter"), None),
+    ];
+    banks_client.process_transaction(Transaction::new_signed_with_payer(&setup, Some(&payer.pubkey()), &[&payer, &creator], blockhash)).await.unwrap();
+
+    // The two voters acting below and enough others to fill the allowlists, as many per transaction as fit.
+    // The delegation baseline only needs its delegator and delegate, the other votes hold the whole allowlist
+    let others = (voters.len()..LARGE_ALLOWLIST).map(|_| Pubkey::new_unique());
+    let allowlist: Vec<Pubkey> = voters.iter().map(|voter| voter.pubkey()).chain(others).collect();
+    let additions = [(gate, &allowlist[..1]), (full_ballot, &allowlist[..]), (full_delegation, &allowlist[..2]), (open, &allowlist[..])];
+    for (vote_id, voters) in additions {
+        for chunk in voters.chunks(16) {
+            let blockhash = banks_client.get_latest_blockhash().await.unwrap(); // The setup outlives a blockhash
+            let additions: Vec<Instruction> = chunk.iter().map(|voter| instruction::add_allowed_voter(&program_id, &registry, &creator.pubkey(), vote_id, voter)).collect();
+            banks_client.process_transaction(Transaction::new_signed_with_payer(&additions, Some(&payer.pubkey()), &[&payer, &creator], blockhash)).await.unwrap();
+        }
+    }
+    let blockhash = banks_client.get_latest_blockhash().await.unwrap();
+    let gate_passes = [
+        instruction::cast_vote(&program_id, &registry, &voters[0].pubkey(), &payer.pubkey(), gate, 0, OptionIndex(0)),
+        instruction::close_vote(&program_id, &registry, &creator.pubkey(), gate),
+    ];
+    banks_client.process_transaction(Transaction::new_signed_with_payer(&gate_passes, Some(&payer.pubkey()), &[&payer, &voters[0], &creator], blockhash)).await.unwrap();
+
+    let delegate = |vote_id| instruction::delegate_vote(&program_id, &registry, &voters[1].pubkey(), vote_id, &voters[0].pubkey(), None);
+    let ballot = |vote_id| instruction::cast_vote(&program_id, &registry, &voters[0].pubkey(), &payer.pubkey(), vote_id, 0, OptionIndex(1));
+    let full = [
+        units_consumed(&mut banks_client, &payer, delegate(full_delegation), &voters[1]).await,
+        units_consumed(&mut banks_client, &payer, ballot(full_ballot), &voters[0]).await,
+    ];
+    let slot = [
+        units_consumed(&mut banks_client, &payer, delegate(open), &voters[1]).await,
+        units_consumed(&mut banks_client, &payer, ballot(open), &voters[0]).await,
+    ];
+    // ProgramTest loads the SBF build when one of these is set. The native build only charges for syscalls such as
+    // the receipt address search, which differ between the votes, so the saving is compared against the SBF build
+    let sbf = std::env::var_os("BPF_OUT_DIR").is_some() || std::env::var_os("SBF_OUT_DIR").is_some();
+    for (slot, full) in slot.into_iter().zip(full) {
+        assert!(slot <= BALLOT_BUDGET, "{} compute units", slot);
+        assert!(!sbf || slot < full, "{} compute units in the slot of the vote, {} through the whole registry", slot, full);
+    }
+
+    let account = banks_client.get_account(registry).await.unwrap().unwrap();
+    let voting = Voting::unpack(&account.data).unwrap();
+    for vote_id in [full_ballot, open] {
+        assert_eq!(voting.get_allowed_voters_as(&creator.pubkey(), vote_id).unwrap().len(), LARGE_ALLOWLIST);
+        assert!(voting.has_voted(vote_id, &voters[0].pubkey()));
+    }
+}
@@ -0,0 +1,25 @@
+// Run with `wasm-pack test --node --features wasm -- --test wasm`
+#![cfg(all(target_arch = "wasm32", feature = "wasm"))]
+
+use solana_program::pubkey::Pubkey;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+use solana_vote::instruction::VoteInstruction;
+use solana_vote::wasm::{decode_vote_state, encode_cast_vote, parse_event};
+use solana_vote::{OptionIndex, VoteId, Voting};
+
+#[wasm_bindgen_test]
+fn test_encode_cast_vote() {
+    let data = encode_cast_vote(4, 1, 2);
+    assert_eq!(VoteInstruction::unpack(&data), Ok(VoteInstruction::CastVote { vote_id: VoteId(4), question_index: 1, option_index: OptionIndex(2) }));
+}
+
+#[wasm_bindgen_test]
+fn test_decode_vote_state() {
+    let mut data = solana_vote::REGISTRY_DISCRIMINATOR.to_vec();
+    data.extend(borsh::to_vec(&Voting::new(Pubkey::new_unique())).unwrap());
+
+    assert_eq!(decode_vote_state(&data, None).ok().map(|votes| votes.length()), Some(0));
+    assert!(decode_vote_state(&[0; 16], None).is_err());
+    assert!(parse_event("Program log: Created vote 0").is_ok_and(|event| event.is_null()));
+}